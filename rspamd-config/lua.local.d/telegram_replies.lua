@@ -155,6 +155,12 @@ local function telegram_reply_callback(task)
     rspamd_logger.debugx(task, "Processing reply to message %s (type: %s)", 
                         reply_info.message_id, reply_info.type)
     
+    -- The bot marks replies to the sender's own trusted message; they get no reduction
+    if reply_info.type == "self_reply" then
+        task:insert_result('TG_REPLY_SELF', 1.0)
+        return
+    end
+    
     -- Check if this is a reply to a trusted message
     local key = config.redis_prefix .. reply_info.message_id
     
@@ -363,6 +369,13 @@ rspamd_config.TG_REPLY_VERIFIED = {
     group = 'telegram_replies'
 }
 
+rspamd_config.TG_REPLY_SELF = {
+    callback = telegram_reply_callback,
+    description = 'Reply to own trusted message, no reduction',
+    score = 0.0,
+    group = 'telegram_replies'
+}
+
 rspamd_config.TG_REPLY_TRACKED = {
     callback = telegram_reply_check_callback,
     description = 'Message is tracked reply to trusted message',
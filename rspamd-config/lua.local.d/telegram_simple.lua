@@ -14,36 +14,80 @@ end
 -- Load logger for debugging
 local rspamd_logger = require "rspamd_logger"
 
+-- Load hashing for content fingerprints
+local rspamd_cryptobox_hash = require "rspamd_cryptobox_hash"
+
+-- UTF-8 aware lowercasing for repeat comparison
+local rspamd_util = require "rspamd_util"
+
+-- Regex entries ('re:<pattern>') of white- and blacklists
+local rspamd_regexp = require "rspamd_regexp"
+
 -- Shared settings
 local settings = {
     -- Core settings
     flood = 30,
     repeated = 6,
+    repeat_window = 3600, -- identical messages further apart start a new count
+    repeat_similarity = 1.0, -- 1 means normalized messages must be equal
+    repeat_fuzzy_max_chars = 500, -- longer messages only repeat when equal
+    -- Reputation gates (defaults, overridable via the tg:thresholds hash)
     suspicious = 10,
     ban = 20,
+    ban_rep_penalty = 4,
     user_prefix = 'tg:users:',
     chat_prefix = 'tg:chats:',
     exp_flood = '30',
     exp_ban = '3600',
     banned_q = 3,
+    cross_post = 2,
+    cross_post_window = 3600, -- 1 hour in seconds
+    cross_post_prefix = 'tg:crosspost:',
     ban_reduction_interval = 172800, -- 48 hours in seconds
     
-    -- Content thresholds
+    -- Content thresholds (defaults, overridable via the tg:thresholds hash)
+    thresholds_key = 'tg:thresholds',
     link_spam = 3,
     mentions = 5,
     caps_ratio = 0.7,
     emoji_limit = 10,
+    char_run_max = 4,
+    char_flood_ratio = 0.3,
+    char_flood_min_run = 3,
+    char_flood_min_length = 20,
+    gibberish_ratio = 0.8, -- share of consonants among Latin letters
+    gibberish_min_length = 50, -- fewest Latin letters TG_GIBBERISH looks at
+    min_content_length = 5, -- shorter messages skip caps, emoji and gibberish checks
     
-    -- Timing heuristics (seconds)
+    -- Timing heuristics (seconds, join windows overridable per chat)
     join_fast  = 10,
     join_slow  = 86400,
+    join_gate_suffix = ':join_gate',
     silence    = 2592000,
     
     -- Pattern matching
     invite_link_patterns = {'t.me/joinchat', 't.me/+', 'telegram.me/joinchat'},
     phone_regex = '%+?%d[%d%-%s%(%)]%d%d%d%d',
     spam_chat_regex = 't.me/joinchat',
-    shorteners = {'bit%.ly', 't%.co', 'goo%.gl', 'tinyurl%.com', 'is%.gd', 'ow%.ly'},
+    -- Used while tg:shorteners is empty (mirrors config::shortener::DEFAULT_DOMAINS)
+    shorteners = {'bit.ly', 't.co', 'goo.gl', 'tinyurl.com', 'is.gd', 'ow.ly', 'cutt.ly', 'rebrand.ly',
+                  'shorturl.at', 'rb.gy', 'tiny.cc', 'buff.ly'},
+    shorteners_key = 'tg:shorteners',
+    trusted_domains_key = 'tg:trusted_domains',
+    
+    -- Script filtering (Unicode ranges of each script name)
+    allowed_scripts_suffix = ':allowed_scripts',
+    scripts = {
+        {name = 'latin',      ranges = {{0x0041, 0x005A}, {0x0061, 0x007A}, {0x00C0, 0x024F}}},
+        {name = 'greek',      ranges = {{0x0370, 0x03FF}}},
+        {name = 'cyrillic',   ranges = {{0x0400, 0x052F}}},
+        {name = 'hebrew',     ranges = {{0x0590, 0x05FF}}},
+        {name = 'arabic',     ranges = {{0x0600, 0x06FF}, {0x0750, 0x077F}}},
+        {name = 'devanagari', ranges = {{0x0900, 0x097F}}},
+        {name = 'thai',       ranges = {{0x0E00, 0x0E7F}}},
+        {name = 'hangul',     ranges = {{0x1100, 0x11FF}, {0xAC00, 0xD7AF}}},
+        {name = 'cjk',        ranges = {{0x3040, 0x30FF}, {0x4E00, 0x9FFF}}},
+    },
     
     -- Reputation settings
     reputation_key_prefix = 'tg:reputation:user:',
@@ -67,21 +111,324 @@ local function safe_num(val, default)
     return tonumber(val) or (default or 0)
 end
 
+-- Decode a single UTF-8 character (as matched by utf8_char_pattern) to its codepoint
+local utf8_char_pattern = '[%z\1-\127\194-\244][\128-\191]*'
+local function codepoint(ch)
+    local b1, b2, b3, b4 = ch:byte(1, -1)
+    if #ch == 1 then
+        return b1
+    elseif #ch == 2 then
+        return (b1 % 0x20) * 0x40 + (b2 % 0x40)
+    elseif #ch == 3 then
+        return (b1 % 0x10) * 0x1000 + (b2 % 0x40) * 0x40 + (b3 % 0x40)
+    end
+    return (b1 % 0x08) * 0x40000 + (b2 % 0x40) * 0x1000 + (b3 % 0x40) * 0x40 + (b4 % 0x40)
+end
+
 local function get_user_chat_ids(task)
     local user_id = safe_str(task:get_header('X-Telegram-User', true))
     local chat_id = safe_str(task:get_header('X-Telegram-Chat', true))
     return user_id, chat_id
 end
 
+-- Sender's username (X-Telegram-Username), empty when they have none
+local function get_username(task)
+    return safe_str(task:get_header('X-Telegram-Username', true))
+end
+
+-- /testmessage previews must not touch user history, so stateful rules skip them
+local function is_preview(task)
+    return task:get_header('X-Telegram-Preview', true) ~= nil
+end
+
+-- Headroom above settings.suspicious for members in good standing
+-- (X-Telegram-Rep-Credit, see config::good_standing)
+local function rep_credit(task)
+    return safe_num(task:get_header('X-Telegram-Rep-Credit', true))
+end
+
+-- Redis key in the bot's namespace (X-Telegram-Namespace, see BOT_NAMESPACE)
+local function ns_key(task, key)
+    local namespace = task:get_header('X-Telegram-Namespace', true)
+    if namespace and namespace ~= '' then
+        return tostring(namespace) .. ':' .. key
+    end
+    return key
+end
+
 local function get_message_text(task)
     return safe_str(task:get_rawbody())
 end
 
+-- Run cb only if the feature is enabled for the chat (per-chat flag, then global set)
+local function if_feature_enabled(task, chat_id, feature, cb)
+    local chat_key = ns_key(task, settings.chat_prefix .. chat_id)
+    
+    lua_redis.redis_make_request(task,
+        redis_params,
+        chat_key,
+        false, -- is write
+        function(err, data)
+            if err then return end
+            if data == '1' then
+                cb()
+            elseif data == '0' then
+                return
+            else
+                lua_redis.redis_make_request(task,
+                    redis_params,
+                    ns_key(task, 'tg:enabled_features'),
+                    false, -- is write
+                    function(e, d)
+                        if e then return end
+                        if d == 1 or d == true then cb() end
+                    end,
+                    'SISMEMBER',
+                    {ns_key(task, 'tg:enabled_features'), feature}
+                )
+            end
+        end,
+        'HGET',
+        {chat_key, 'feat:' .. feature}
+    )
+end
+
+-- Wrap a detection callback to run only while its feature is enabled for the
+-- chat, so the per-chat toggles and /togglesymbol switch the symbol off
+-- (config::TOGGLEABLE_SYMBOLS lists the wrapped symbols)
+local function when_enabled(feature, cb)
+    return function(task)
+        local _, chat_id = get_user_chat_ids(task)
+        if_feature_enabled(task, chat_id, feature, function()
+            cb(task)
+        end)
+    end
+end
+
+-- Run cb unless the chat is in dry-run mode (opt-in per chat), otherwise run dry_cb
+local function unless_dry_run(task, chat_id, cb, dry_cb)
+    local chat_key = ns_key(task, settings.chat_prefix .. chat_id)
+    
+    lua_redis.redis_make_request(task,
+        redis_params,
+        chat_key,
+        false, -- is write
+        function(err, data)
+            if not err and data == '1' then
+                rspamd_logger.infox(task, 'Dry-run active for chat %1, skipping penalties', safe_str(chat_id))
+                if dry_cb then dry_cb() end
+                return
+            end
+            cb()
+        end,
+        'HGET',
+        {chat_key, 'feat:dry_run'}
+    )
+end
+
+-- Read an admin-configured threshold, falling back to the default
+local function with_threshold(task, name, default, cb)
+    lua_redis.redis_make_request(task,
+        redis_params,
+        ns_key(task, settings.thresholds_key),
+        false, -- is write
+        function(err, data)
+            if err then
+                rspamd_logger.errx(task, 'Failed to read threshold %1: %2', name, safe_str(err))
+                cb(default)
+                return
+            end
+            cb(safe_num(data, default))
+        end,
+        'HGET',
+        {ns_key(task, settings.thresholds_key), name}
+    )
+end
+
+-- Read the admin-managed trusted domains as a lookup table (empty on error)
+local function with_trusted_domains(task, cb)
+    lua_redis.redis_make_request(task,
+        redis_params,
+        ns_key(task, settings.trusted_domains_key),
+        false, -- is write
+        function(err, data)
+            local trusted = {}
+            if err then
+                rspamd_logger.errx(task, 'Failed to read trusted domains: %1', safe_str(err))
+            elseif type(data) == 'table' then
+                for _, domain in ipairs(data) do
+                    trusted[safe_str(domain):lower()] = true
+                end
+            end
+            cb(trusted)
+        end,
+        'SMEMBERS',
+        {ns_key(task, settings.trusted_domains_key)}
+    )
+end
+
+-- Whether a host or any of its parent domains is in the set of domains
+local function host_in_set(domains, host)
+    host = safe_str(host):lower()
+    while host ~= '' do
+        if domains[host] then return true end
+        local dot = host:find('.', 1, true)
+        if not dot then break end
+        host = host:sub(dot + 1)
+    end
+    return false
+end
+
+-- A host is trusted if it or any parent domain is in the trusted set
+local function is_trusted_host(trusted, host)
+    return host_in_set(trusted, host)
+end
+
+-- Read the admin-managed URL shorteners as a lookup table; settings.shorteners
+-- apply while the set is empty or can't be read
+local function with_shorteners(task, cb)
+    lua_redis.redis_make_request(task,
+        redis_params,
+        ns_key(task, settings.shorteners_key),
+        false, -- is write
+        function(err, data)
+            local shorteners = {}
+            if err then
+                rspamd_logger.errx(task, 'Failed to read shorteners: %1', safe_str(err))
+            elseif type(data) == 'table' then
+                for _, domain in ipairs(data) do
+                    shorteners[safe_str(domain):lower()] = true
+                end
+            end
+            if next(shorteners) == nil then
+                for _, domain in ipairs(settings.shorteners) do
+                    shorteners[domain] = true
+                end
+            end
+            cb(shorteners)
+        end,
+        'SMEMBERS',
+        {ns_key(task, settings.shorteners_key)}
+    )
+end
+
+-- Collect the hosts of the message's links that aren't trusted, then call cb(hosts, total)
+local function with_untrusted_hosts(task, cb)
+    local urls = task:get_urls() or {}
+    with_trusted_domains(task, function(trusted)
+        local hosts = {}
+        for _, url in ipairs(urls) do
+            local host = url:get_host()
+            if not is_trusted_host(trusted, host) then
+                table.insert(hosts, safe_str(host):lower())
+            end
+        end
+        cb(hosts, #urls)
+    end)
+end
+
+-- Decode a punycode label (without the xn-- prefix) into codepoints, RFC 3492
+local function punycode_decode(input)
+    local base, tmin, tmax, skew, damp = 36, 1, 26, 38, 700
+    local n, i, bias = 128, 0, 72
+    local output = {}
+
+    local basic_end = 0
+    for pos = #input, 1, -1 do
+        if input:sub(pos, pos) == '-' then
+            basic_end = pos
+            break
+        end
+    end
+    for pos = 1, basic_end - 1 do
+        table.insert(output, input:byte(pos))
+    end
+
+    local function digit(c)
+        if c >= 48 and c <= 57 then return c - 22 end
+        if c >= 65 and c <= 90 then return c - 65 end
+        if c >= 97 and c <= 122 then return c - 97 end
+        return nil
+    end
+
+    local function adapt(delta, numpoints, first)
+        delta = first and math.floor(delta / damp) or math.floor(delta / 2)
+        delta = delta + math.floor(delta / numpoints)
+        local k = 0
+        while delta > math.floor(((base - tmin) * tmax) / 2) do
+            delta = math.floor(delta / (base - tmin))
+            k = k + base
+        end
+        return k + math.floor(((base - tmin + 1) * delta) / (delta + skew))
+    end
+
+    local pos = basic_end + 1
+    while pos <= #input do
+        local oldi, w, k = i, 1, base
+        while true do
+            if pos > #input then return nil end
+            local d = digit(input:byte(pos))
+            if not d then return nil end
+            pos = pos + 1
+            i = i + d * w
+            local t = k <= bias and tmin or (k >= bias + tmax and tmax or k - bias)
+            if d < t then break end
+            w = w * (base - t)
+            k = k + base
+        end
+        bias = adapt(i - oldi, #output + 1, oldi == 0)
+        n = n + math.floor(i / (#output + 1))
+        i = i % (#output + 1)
+        table.insert(output, i + 1, n)
+        i = i + 1
+    end
+    return output
+end
+
+-- Name of the script a codepoint belongs to (see settings.scripts), or nil
+local function script_of(cp)
+    for _, script in ipairs(settings.scripts) do
+        for _, range in ipairs(script.ranges) do
+            if cp >= range[1] and cp <= range[2] then
+                return script.name
+            end
+        end
+    end
+    return nil
+end
+
+-- A host is a lookalike if one of its labels, with punycode decoded, mixes scripts
+local function is_lookalike_host(host)
+    for label in safe_str(host):lower():gmatch('[^%.]+') do
+        local codepoints
+        if label:sub(1, 4) == 'xn--' then
+            codepoints = punycode_decode(label:sub(5))
+        else
+            codepoints = {}
+            for ch in label:gmatch(utf8_char_pattern) do
+                table.insert(codepoints, codepoint(ch))
+            end
+        end
+
+        local scripts, count = {}, 0
+        for _, cp in ipairs(codepoints or {}) do
+            local script = script_of(cp)
+            if script and not scripts[script] then
+                scripts[script] = true
+                count = count + 1
+            end
+        end
+        if count > 1 then return true end
+    end
+    return false
+end
+
 -- Reputation integration functions
+-- Content symbols don't call this: the bot adds their weights (tg:symbol_weights) after each scan
 local function update_user_reputation(task, user_id, is_spam)
     if user_id == "" then return end
     
-    local reputation_key = settings.reputation_key_prefix .. user_id
+    local reputation_key = ns_key(task, settings.reputation_key_prefix .. user_id)
     local field = is_spam and 'bad' or 'good'
     local status = is_spam and 'spam' or 'good'
     
@@ -133,7 +480,7 @@ end
 local function get_user_reputation(task, user_id)
     if user_id == "" then return 0 end
     
-    local reputation_key = settings.reputation_key_prefix .. user_id
+    local reputation_key = ns_key(task, settings.reputation_key_prefix .. user_id)
     
     local function reputation_cb(err, data)
         if err then
@@ -161,12 +508,55 @@ local function get_user_reputation(task, user_id)
     return 0 -- Default return value
 end
 
+-- The chat's flood limit: scaled to its message rate while adaptive
+-- thresholds are on (adaptive_flood_max, see adaptive.rs), else flood_max in
+-- its hash (see /setflood), falling back to the global flood_max threshold
+-- (flood::flood_limit)
+local function with_flood_limit(task, chat_id, cb)
+    local chat_key = ns_key(task, settings.chat_prefix .. chat_id)
+    lua_redis.redis_make_request(task,
+        redis_params,
+        chat_key,
+        false, -- is write
+        function(err, data)
+            local limit = nil
+            if not err and type(data) == 'table' then
+                limit = tonumber(data[1]) or tonumber(data[2])
+            end
+            if limit then
+                cb(limit)
+            else
+                with_threshold(task, 'flood_max', settings.flood, cb)
+            end
+        end,
+        'HMGET',
+        {chat_key, 'adaptive_flood_max', 'flood_max'}
+    )
+end
+
+-- The chat's repeat limit, scaled to its message rate while adaptive
+-- thresholds are on (adaptive_repeat_max, see adaptive.rs)
+local function with_repeat_limit(task, chat_id, cb)
+    local chat_key = ns_key(task, settings.chat_prefix .. chat_id)
+    lua_redis.redis_make_request(task,
+        redis_params,
+        chat_key,
+        false, -- is write
+        function(err, data)
+            cb(not err and tonumber(data) or settings.repeated)
+        end,
+        'HGET',
+        {chat_key, 'adaptive_repeat_max'}
+    )
+end
+
 -- TG_FLOOD: Detect message flooding
 local function tg_flood_cb(task)
+    if is_preview(task) then return end
     local user_id, chat_id = get_user_chat_ids(task)
     if user_id == "" then return end
     
-    local user_key = settings.user_prefix .. user_id
+    local user_key = ns_key(task, settings.user_prefix .. user_id)
     
     local function flood_cb(err, data)
         if err then 
@@ -184,8 +574,9 @@ local function tg_flood_cb(task)
             {user_key, settings.exp_flood, 'NX', 'FIELDS', 1, 'flood'}
         )
         
-        if count > settings.flood then
-            local chat_key = settings.chat_prefix .. chat_id
+        with_flood_limit(task, chat_id, function(limit)
+            if count <= limit then return end
+            local chat_key = ns_key(task, settings.chat_prefix .. chat_id)
             lua_redis.redis_make_request(task,
                 redis_params,
                 chat_key,
@@ -194,21 +585,23 @@ local function tg_flood_cb(task)
                 'HINCRBY',
                 {chat_key, 'spam_count', '1'}
             )
-            lua_redis.redis_make_request(task,
-                redis_params,
-                user_key,
-                true, -- is write
-                function() end,
-                'HINCRBY',
-                {user_key, 'rep', '1'}
-            )
-            
-            -- Update reputation for spam detection
-            update_user_reputation(task, user_id, true)
+            unless_dry_run(task, chat_id, function()
+                lua_redis.redis_make_request(task,
+                    redis_params,
+                    user_key,
+                    true, -- is write
+                    function() end,
+                    'HINCRBY',
+                    {user_key, 'rep', '1'}
+                )
+                
+                -- Update reputation for spam detection
+                update_user_reputation(task, user_id, true)
+            end)
             
             task:insert_result('TG_FLOOD')
             rspamd_logger.infox(task, 'TG_FLOOD triggered for user %1, count: %2', safe_str(user_id), safe_str(count))
-        end
+        end)
     end
     
     lua_redis.redis_make_request(task,
@@ -226,14 +619,28 @@ local function tg_link_spam_cb(task)
     local user_id, chat_id = get_user_chat_ids(task)
     if chat_id == "" then return end
 
-    local urls = task:get_urls() or {}
-    if #urls >= settings.link_spam then
-        -- Update reputation for spam detection
-        update_user_reputation(task, user_id, true)
-        
-        task:insert_result('TG_LINK_SPAM', 1.0)
-        rspamd_logger.infox(task, 'TG_LINK_SPAM triggered, URLs: %1', #urls)
-    end
+    -- Links to trusted domains don't count towards the limit
+    with_untrusted_hosts(task, function(hosts, total)
+        with_threshold(task, 'link_spam_max', settings.link_spam, function(limit)
+            if #hosts > limit then
+                task:insert_result('TG_LINK_SPAM', 1.0)
+                rspamd_logger.infox(task, 'TG_LINK_SPAM triggered, URLs: %1 (%2 counted)', total, #hosts)
+            end
+        end)
+    end)
+end
+
+-- TG_LOOKALIKE_DOMAIN: Detect links to hosts that mix scripts (e.g. punycode "аpple.com")
+local function tg_lookalike_domain_cb(task)
+    with_untrusted_hosts(task, function(hosts)
+        for _, host in ipairs(hosts) do
+            if is_lookalike_host(host) then
+                task:insert_result('TG_LOOKALIKE_DOMAIN', 1.0, host)
+                rspamd_logger.infox(task, 'TG_LOOKALIKE_DOMAIN triggered, host: %1', host)
+                return
+            end
+        end
+    end)
 end
 
 -- TG_MENTIONS: Detect excessive user mentions
@@ -246,13 +653,69 @@ local function tg_mentions_cb(task)
         n = n + 1 
     end
     
-    if n >= settings.mentions then
-        -- Update reputation for spam detection
-        update_user_reputation(task, user_id, true)
-        
-        task:insert_result('TG_MENTIONS')
-        rspamd_logger.infox(task, 'TG_MENTIONS triggered, mentions: %1', n)
+    with_threshold(task, 'mentions_max', settings.mentions, function(limit)
+        if n > limit then
+            task:insert_result('TG_MENTIONS')
+            rspamd_logger.infox(task, 'TG_MENTIONS triggered, mentions: %1', n)
+        end
+    end)
+end
+
+-- Case of a codepoint in the common cased scripts (Latin, Greek, Cyrillic):
+-- 'u', 'l', or nil for uncased characters such as CJK, digits and punctuation
+local function letter_case(cp)
+    if (cp >= 0x41 and cp <= 0x5A) or (cp >= 0xC0 and cp <= 0xDE and cp ~= 0xD7) then
+        return 'u'
+    elseif (cp >= 0x61 and cp <= 0x7A) or (cp >= 0xDF and cp <= 0xFF and cp ~= 0xF7) then
+        return 'l'
+    elseif cp >= 0x100 and cp <= 0x17F then
+        -- Latin Extended-A alternates upper/lower, shifting parity twice
+        if cp == 0x178 then return 'u' end
+        if cp == 0x138 or cp == 0x149 or cp == 0x17F then return 'l' end
+        local upper_even = cp < 0x139 or (cp > 0x149 and cp < 0x179)
+        return ((cp % 2 == 0) == upper_even) and 'u' or 'l'
+    elseif (cp >= 0x391 and cp <= 0x3A9) or (cp >= 0x400 and cp <= 0x42F) then
+        return 'u'
+    elseif (cp >= 0x3B1 and cp <= 0x3C9) or (cp >= 0x430 and cp <= 0x45F) then
+        return 'l'
+    elseif cp >= 0x460 and cp <= 0x4FF and not (cp >= 0x482 and cp <= 0x489) then
+        -- Cyrillic extensions pair up as even upper/odd lower, except around palochka
+        if cp == 0x4C0 then return 'u' end
+        if cp == 0x4CF then return 'l' end
+        local upper_even = cp < 0x4C1 or cp > 0x4CE
+        return ((cp % 2 == 0) == upper_even) and 'u' or 'l'
+    end
+    return nil
+end
+
+-- Count upper- and lowercase letters; uncased scripts don't count as letters
+local function case_counts(text)
+    local upper, lower = 0, 0
+    for ch in text:gmatch(utf8_char_pattern) do
+        local case = letter_case(codepoint(ch))
+        if case == 'u' then
+            upper = upper + 1
+        elseif case == 'l' then
+            lower = lower + 1
+        end
+    end
+    return upper, lower
+end
+
+-- Run cb only if the message (without surrounding whitespace) reaches the
+-- configured minimum length; ratio-based checks are meaningless below it
+local function if_long_enough(task, text, cb)
+    local length = 0
+    for _ in text:match('^%s*(.-)%s*$'):gmatch(utf8_char_pattern) do
+        length = length + 1
     end
+    with_threshold(task, 'min_content_length', settings.min_content_length, function(min_length)
+        if length >= min_length then
+            cb()
+        else
+            rspamd_logger.infox(task, 'Message shorter than %1 characters, skipping content checks', min_length)
+        end
+    end)
 end
 
 -- TG_CAPS: Detect excessive capital letters
@@ -267,36 +730,82 @@ local function tg_caps_cb(task)
         return 
     end
 
-    local letters, caps = 0, 0
-    for ch in text:gmatch("%a") do
-        letters = letters + 1
-        if ch:match("%u") then 
-            caps = caps + 1 
-        end
-    end
+    if_long_enough(task, text, function()
+        local caps, lower = case_counts(text)
+        local letters = caps + lower
     
-    rspamd_logger.infox(task, 'TG_CAPS: Letters: %1, Caps: %2, Ratio: %3', letters, caps, letters > 0 and (caps/letters) or 0)
+        rspamd_logger.infox(task, 'TG_CAPS: Letters: %1, Caps: %2, Ratio: %3', letters, caps, letters > 0 and (caps/letters) or 0)
+        if letters == 0 then return end
     
-    if letters > 0 and (caps / letters) >= settings.caps_ratio then
-        -- Update reputation for spam detection
-        update_user_reputation(task, user_id, true)
-        
-        task:insert_result('TG_CAPS', 1.0)
-        rspamd_logger.infox(task, 'TG_CAPS triggered, caps ratio: %1', caps/letters)
-    else
-        rspamd_logger.infox(task, 'TG_CAPS: Not triggered, ratio %1 < threshold %2', caps/letters, settings.caps_ratio)
+        with_threshold(task, 'caps_ratio', settings.caps_ratio, function(ratio)
+            if (caps / letters) >= ratio then
+                task:insert_result('TG_CAPS', 1.0)
+                rspamd_logger.infox(task, 'TG_CAPS triggered, caps ratio: %1', caps/letters)
+            else
+                rspamd_logger.infox(task, 'TG_CAPS: Not triggered, ratio %1 < threshold %2', caps/letters, ratio)
+            end
+        end)
+    end)
+end
+
+-- Lowercase, collapse whitespace and strip trailing digits, whitespace and
+-- punctuation, so "buy now 1" and "buy  now 2" compare equal (repeat::normalize)
+local function normalize_repeat(text)
+    local collapsed = rspamd_util.lower_utf8(text):gsub('%s+', ' '):gsub('^ ', '')
+    return (collapsed:gsub('[%d%s%p]+$', ''))
+end
+
+local function utf8_chars(text)
+    local chars = {}
+    for ch in text:gmatch(utf8_char_pattern) do
+        chars[#chars + 1] = ch
+    end
+    return chars
+end
+
+-- One minus the edit distance over the longer length (repeat::similarity)
+local function similarity(a, b)
+    a, b = utf8_chars(a), utf8_chars(b)
+    local longest = math.max(#a, #b)
+    if longest == 0 then return 1.0 end
+    local previous = {}
+    for j = 0, #b do previous[j] = j end
+    for i = 1, #a do
+        local current = {[0] = i}
+        for j = 1, #b do
+            local substitution = previous[j - 1] + ((a[i] == b[j]) and 0 or 1)
+            current[j] = math.min(substitution, previous[j] + 1, current[j - 1] + 1)
+        end
+        previous = current
+    end
+    return 1.0 - previous[#b] / longest
+end
+
+-- Whether normalized text repeats last, the previous normalized message (repeat::is_repeat)
+local function is_repeat(last, text, min_similarity)
+    if text == '' then return false end
+    if text == last then return true end
+    if min_similarity >= 1.0
+        or #utf8_chars(last) > settings.repeat_fuzzy_max_chars
+        or #utf8_chars(text) > settings.repeat_fuzzy_max_chars then
+        return false
     end
+    return similarity(last, text) >= min_similarity
 end
 
 -- TG_REPEAT: Detect repeated messages
 local function tg_repeat_cb(task)
+    if is_preview(task) then return end
     local user_id, chat_id = get_user_chat_ids(task)
     if user_id == "" then return end
     
             rspamd_logger.infox(task, 'TG_REPEAT: Processing message for user %1', safe_str(user_id))
     
-    local user_key = settings.user_prefix .. user_id
-    local msg = get_message_text(task)
+    local user_key = ns_key(task, settings.user_prefix .. user_id)
+    local msg = normalize_repeat(get_message_text(task))
+    
+    -- read from the thresholds and chat hashes before last_msg_cb runs
+    local repeat_window, repeat_similarity, repeat_limit
     
     local function last_msg_cb(err, data)
         if err then 
@@ -308,8 +817,8 @@ local function tg_repeat_cb(task)
             if _err then return end
             local count = safe_num(_data)
             rspamd_logger.infox(task, 'TG_REPEAT: Current count for user %1 is %2', safe_str(user_id), safe_str(count))
-            if count > settings.repeated then
-                local chat_key = settings.chat_prefix .. chat_id
+            if count > repeat_limit then
+                local chat_key = ns_key(task, settings.chat_prefix .. chat_id)
                 lua_redis.redis_make_request(task,
                     redis_params,
                     chat_key,
@@ -318,24 +827,30 @@ local function tg_repeat_cb(task)
                     'HINCRBY',
                     {chat_key, 'spam_count', '1'}
                 )
-                lua_redis.redis_make_request(task,
-                    redis_params,
-                    user_key,
-                    true, -- is write
-                    function() end,
-                    'HINCRBY',
-                    {user_key, 'rep', '1'}
-                )
-                
-                -- Update reputation for spam detection
-                update_user_reputation(task, user_id, true)
+                unless_dry_run(task, chat_id, function()
+                    lua_redis.redis_make_request(task,
+                        redis_params,
+                        user_key,
+                        true, -- is write
+                        function() end,
+                        'HINCRBY',
+                        {user_key, 'rep', '1'}
+                    )
+                    
+                    -- Update reputation for spam detection
+                    update_user_reputation(task, user_id, true)
+                end)
                 
                 task:insert_result('TG_REPEAT', 1.0)
                 rspamd_logger.infox(task, 'TG_REPEAT triggered for user %1, count: %2', safe_str(user_id), safe_str(count))
             end
         end
         
-        if safe_str(data) == msg then
+        local last_msg = type(data) == 'table' and data[1] or nil
+        local last_msg_time = type(data) == 'table' and safe_num(data[2]) or 0
+        local stale = last_msg_time ~= 0 and os.time() - last_msg_time > repeat_window
+
+        if is_repeat(safe_str(last_msg), msg, repeat_similarity) and not stale then
             rspamd_logger.infox(task, 'TG_REPEAT: Message matches previous for user %1', safe_str(user_id))
             lua_redis.redis_make_request(task,
                 redis_params,
@@ -346,7 +861,7 @@ local function tg_repeat_cb(task)
                 {user_key, 'eq_msg_count', 1}
             )
         else
-            rspamd_logger.infox(task, 'TG_REPEAT: Message is different for user %1', safe_str(user_id))
+            rspamd_logger.infox(task, 'TG_REPEAT: Message is different or outside the repeat window for user %1', safe_str(user_id))
             lua_redis.redis_make_request(task,
                 redis_params,
                 user_key,
@@ -367,171 +882,246 @@ local function tg_repeat_cb(task)
         )
     end
     
-    lua_redis.redis_make_request(task,
-        redis_params,
-        user_key,
-        false, -- is write
-        last_msg_cb,
-        'HGET',
-        {user_key, 'last_msg'}
-    )
+    -- last_msg_time is written by the bot after each scan, so it still holds
+    -- the time of the previous message here
+    with_threshold(task, 'repeat_window', settings.repeat_window, function(window)
+        with_threshold(task, 'repeat_similarity', settings.repeat_similarity, function(min_similarity)
+            with_repeat_limit(task, chat_id, function(limit)
+                repeat_window = window
+                repeat_similarity = min_similarity
+                repeat_limit = limit
+                lua_redis.redis_make_request(task,
+                    redis_params,
+                    user_key,
+                    false, -- is write
+                    last_msg_cb,
+                    'HMGET',
+                    {user_key, 'last_msg', 'last_msg_time'}
+                )
+            end)
+        end)
+    end)
 end
 
--- TG_SUSPICIOUS: Detect suspicious activity
-local function tg_suspicious_cb(task)
+-- TG_CROSS_POST: Detect the same message sent to multiple chats
+local function tg_cross_post_cb(task)
+    if is_preview(task) then return end
     local user_id, chat_id = get_user_chat_ids(task)
-    if user_id == "" then return end
+    if user_id == "" or chat_id == "" then return end
+    
+    local msg = get_message_text(task)
+    if msg == "" then return end
     
-    local user_key = settings.user_prefix .. user_id
+    local digest = rspamd_cryptobox_hash.create(msg):hex()
+    local cross_key = ns_key(task, settings.cross_post_prefix .. user_id .. ':' .. digest)
     
-    local function spam_cb(err, data)
-        if err then 
-            rspamd_logger.errx(task, 'spam_cb error: %1', err)
-            return 
+    local function scard_cb(err, data)
+        if err then
+            rspamd_logger.errx(task, 'cross_post scard_cb error: %1', err)
+            return
         end
         
-        local total = safe_num(data)
-        if total > settings.suspicious then
-            local chat_key = settings.chat_prefix .. chat_id
-            lua_redis.redis_make_request(task,
-                redis_params,
-                chat_key,
-                true, -- is write
-                function() end,
-                'HINCRBY',
-                {chat_key, 'spam_count', '1'}
-            )
-            lua_redis.redis_make_request(task,
-                redis_params,
-                user_key,
-                true, -- is write
-                function() end,
-                'HINCRBY',
-                {user_key, 'rep', '1'}
-            )
-            
-            -- Update reputation for spam detection
-            update_user_reputation(task, user_id, true)
-            
-            task:insert_result('TG_SUSPICIOUS', 1.0)
-            rspamd_logger.infox(task, 'TG_SUSPICIOUS triggered for user %1, total: %2', safe_str(user_id), safe_str(total))
-        end
-    end
-    
-    lua_redis.redis_make_request(task,
-        redis_params,
-        user_key,
-        false, -- is write
-        spam_cb,
-        'HGET',
-        {user_key, 'spam_count'}
+        local chats = safe_num(data)
+        if chats > settings.cross_post then
+            local user_key = ns_key(task, settings.user_prefix .. user_id)
+            unless_dry_run(task, chat_id, function()
+                lua_redis.redis_make_request(task,
+                    redis_params,
+                    user_key,
+                    true, -- is write
+                    function() end,
+                    'HINCRBY',
+                    {user_key, 'rep', '1'}
+                )
+                
+                -- Update reputation for spam detection
+                update_user_reputation(task, user_id, true)
+            end)
+            
+            task:insert_result('TG_CROSS_POST', 1.0)
+            rspamd_logger.infox(task, 'TG_CROSS_POST triggered for user %1, chats: %2', safe_str(user_id), safe_str(chats))
+        end
+    end
+    
+    local function sadd_cb(err)
+        if err then
+            rspamd_logger.errx(task, 'cross_post sadd_cb error: %1', err)
+            return
+        end
+        
+        lua_redis.redis_make_request(task,
+            redis_params,
+            cross_key,
+            true, -- is write
+            function() end,
+            'EXPIRE',
+            {cross_key, tostring(settings.cross_post_window), 'NX'}
+        )
+        lua_redis.redis_make_request(task,
+            redis_params,
+            cross_key,
+            false, -- is write
+            scard_cb,
+            'SCARD',
+            {cross_key}
+        )
+    end
+    
+    lua_redis.redis_make_request(task,
+        redis_params,
+        cross_key,
+        true, -- is write
+        sadd_cb,
+        'SADD',
+        {cross_key, chat_id}
     )
 end
 
--- TG_BAN: Temporary ban system
-local function tg_ban_cb(task)
+-- TG_SUSPICIOUS: Detect suspicious activity
+local function tg_suspicious_cb(task)
+    if is_preview(task) then return end
     local user_id, chat_id = get_user_chat_ids(task)
     if user_id == "" then return end
     
-    local user_key = settings.user_prefix .. user_id
+    local user_key = ns_key(task, settings.user_prefix .. user_id)
     
-    local function ban_cb(err, data)
+    local function spam_cb(err, data)
         if err then 
-            rspamd_logger.errx(task, 'ban_cb error: %1', err)
+            rspamd_logger.errx(task, 'spam_cb error: %1', err)
             return 
         end
         
         local total = safe_num(data)
-        if total > settings.ban then
-            local chat_key = settings.chat_prefix .. chat_id
-            lua_redis.redis_make_request(task,
-                redis_params,
-                chat_key,
-                true, -- is write
-                function() end,
-                'HINCRBY',
-                {chat_key, 'banned', '1'}
-            )
-            
-            -- Get current ban count
-            local function get_banned_q_cb(_err, _data)
-                if _err then
-                    rspamd_logger.errx(task, 'get_banned_q_cb error: %1', _err)
-                    return
-                end
-                
-                local banned_q = safe_num(_data)
-                
-                -- Increment ban counter
+        with_threshold(task, 'suspicious_rep', settings.suspicious, function(suspicious)
+            if total > suspicious + rep_credit(task) then
+                local chat_key = ns_key(task, settings.chat_prefix .. chat_id)
                 lua_redis.redis_make_request(task,
                     redis_params,
-                    user_key,
+                    chat_key,
                     true, -- is write
                     function() end,
                     'HINCRBY',
-                    {user_key, 'banned_q', '1'}
+                    {chat_key, 'spam_count', '1'}
                 )
-                
-                -- Set ban flag with expiration
-                local function banned_cb(__err, __data)
-                    if __err or not __data then return end
+                unless_dry_run(task, chat_id, function()
                     lua_redis.redis_make_request(task,
                         redis_params,
                         user_key,
                         true, -- is write
                         function() end,
-                        'HEXPIRE',
-                        {user_key, settings.exp_ban, 'FIELDS', 1, 'banned'}
+                        'HINCRBY',
+                        {user_key, 'rep', '1'}
                     )
-                end
-                
-                lua_redis.redis_make_request(task,
-                    redis_params,
-                    user_key,
-                    true, -- is write
-                    banned_cb,
-                    'HSET',
-                    {user_key, 'banned', '1'}
-                )
                 
-                -- Reduce reputation
-                lua_redis.redis_make_request(task,
-                    redis_params,
-                    user_key,
-                    true, -- is write
-                    function() end,
-                    'HINCRBY',
-                    {user_key, 'rep', '-5'}
-                )
-                
-                -- Update reputation for ban
-                update_user_reputation(task, user_id, true)
+                    -- Update reputation for spam detection
+                    update_user_reputation(task, user_id, true)
+                end)
+            
+                task:insert_result('TG_SUSPICIOUS', 1.0)
+                rspamd_logger.infox(task, 'TG_SUSPICIOUS triggered for user %1, total: %2', safe_str(user_id), safe_str(total))
+            end
+        end)
+    end
+    
+    lua_redis.redis_make_request(task,
+        redis_params,
+        user_key,
+        false, -- is write
+        spam_cb,
+        'HGET',
+        {user_key, 'spam_count'}
+    )
+end
+
+-- Bans the user if rep is above ARGV[1], in one step so concurrent scans of
+-- the same user can't both ban for the same reputation. Returns the new ban
+-- count, or nil if rep wasn't above the threshold. Same as BAN_SCRIPT in
+-- reputation_update.rs.
+local ban_script = [[
+local rep = tonumber(redis.call('HGET', KEYS[1], 'rep')) or 0
+if rep <= tonumber(ARGV[1]) then
+    return false
+end
+redis.call('HINCRBY', KEYS[1], 'rep', -tonumber(ARGV[2]))
+redis.call('HSET', KEYS[1], 'banned', '1', 'banned_in', ARGV[3])
+redis.call('HINCRBY', KEYS[2], 'banned', 1)
+return redis.call('HINCRBY', KEYS[1], 'banned_q', 1)
+]]
+
+-- TG_BAN: Temporary ban system
+local function tg_ban_cb(task)
+    if is_preview(task) then return end
+    local user_id, chat_id = get_user_chat_ids(task)
+    if user_id == "" then return end
+    
+    local user_key = ns_key(task, settings.user_prefix .. user_id)
+    
+    local function ban_cb(err, data)
+        if err then 
+            rspamd_logger.errx(task, 'ban_cb error: %1', err)
+            return 
+        end
+        
+        local total = safe_num(data)
+        with_threshold(task, 'ban_rep', settings.ban, function(ban)
+            if total > ban then
+                unless_dry_run(task, chat_id, function()
+                    local chat_key = ns_key(task, settings.chat_prefix .. chat_id)
                 
-                -- Set ban reduction time for automatic counter reduction
-                local current_time = os.time()
-                local reduction_time = current_time + settings.ban_reduction_interval
-                lua_redis.redis_make_request(task,
-                    redis_params,
-                    user_key,
-                    true, -- is write
-                    function() end,
-                    'HSET',
-                    {user_key, 'ban_reduction_time', tostring(reduction_time)}
-                )
+                    -- Re-checks rep and bans atomically; another scan may have banned already
+                    local function banned_cb(_err, _data)
+                        if _err then
+                            rspamd_logger.errx(task, 'banned_cb error: %1', _err)
+                            return
+                        end
+                        local banned_q = tonumber(_data)
+                        if not banned_q then return end
+                    
+                        -- Ban flag expires on its own
+                        lua_redis.redis_make_request(task,
+                            redis_params,
+                            user_key,
+                            true, -- is write
+                            function() end,
+                            'HEXPIRE',
+                            {user_key, settings.exp_ban, 'FIELDS', 2, 'banned', 'banned_in'}
+                        )
+                    
+                        -- Update reputation for ban
+                        update_user_reputation(task, user_id, true)
+                    
+                        -- Set ban reduction time for automatic counter reduction
+                        local current_time = os.time()
+                        local reduction_time = current_time + settings.ban_reduction_interval
+                        lua_redis.redis_make_request(task,
+                            redis_params,
+                            user_key,
+                            true, -- is write
+                            function() end,
+                            'HSET',
+                            {user_key, 'ban_reduction_time', tostring(reduction_time)}
+                        )
+                    
+                        task:insert_result('TG_BAN', 1.0)
+                        rspamd_logger.infox(task, 'TG_BAN triggered for user %1, rep: %2, ban count: %3', safe_str(user_id), safe_str(total), safe_str(banned_q))
+                    end
                 
-                task:insert_result('TG_BAN', 1.0)
-                rspamd_logger.infox(task, 'TG_BAN triggered for user %1, rep: %2, ban count: %3', safe_str(user_id), safe_str(total), safe_str(banned_q + 1))
+                    with_threshold(task, 'ban_rep_penalty', settings.ban_rep_penalty, function(penalty)
+                        lua_redis.redis_make_request(task,
+                            redis_params,
+                            user_key,
+                            true, -- is write
+                            banned_cb,
+                            'EVAL',
+                            {ban_script, '2', user_key, chat_key, tostring(ban), tostring(math.floor(penalty)), chat_id}
+                        )
+                    end)
+                end, function()
+                    task:insert_result('TG_BAN', 1.0)
+                    rspamd_logger.infox(task, 'TG_BAN (dry-run) for user %1, rep: %2', safe_str(user_id), safe_str(total))
+                end)
             end
-            
-            lua_redis.redis_make_request(task,
-                redis_params,
-                user_key,
-                false, -- is write
-                get_banned_q_cb,
-                'HGET',
-                {user_key, 'banned_q'}
-            )
-        end
+        end)
     end
     
     lua_redis.redis_make_request(task,
@@ -546,10 +1136,11 @@ end
 
 -- TG_PERM_BAN: Permanent ban system
 local function tg_perm_ban_cb(task)
+    if is_preview(task) then return end
     local user_id, chat_id = get_user_chat_ids(task)
     if user_id == "" then return end
     
-    local user_key = settings.user_prefix .. user_id
+    local user_key = ns_key(task, settings.user_prefix .. user_id)
     
     local function perm_ban_cb(err, data)
         if err then 
@@ -558,23 +1149,27 @@ local function tg_perm_ban_cb(task)
         end
         
         local banned_q = safe_num(data)
-        if banned_q >= 3 then -- Changed from > to >= to trigger on 3rd ban
-            local chat_key = settings.chat_prefix .. chat_id
-            lua_redis.redis_make_request(task,
-                redis_params,
-                chat_key,
-                true, -- is write
-                function() end,
-                'HINCRBY',
-                {chat_key, 'perm_banned', '1'}
-            )
-            
-            -- Update reputation for permanent ban
-            update_user_reputation(task, user_id, true)
+        with_threshold(task, 'perm_ban_bans', settings.banned_q, function(perm_bans)
+            if banned_q >= perm_bans then -- >= so the perm_bans-th ban triggers it
+                unless_dry_run(task, chat_id, function()
+                    local chat_key = ns_key(task, settings.chat_prefix .. chat_id)
+                    lua_redis.redis_make_request(task,
+                        redis_params,
+                        chat_key,
+                        true, -- is write
+                        function() end,
+                        'HINCRBY',
+                        {chat_key, 'perm_banned', '1'}
+                    )
+                
+                    -- Update reputation for permanent ban
+                    update_user_reputation(task, user_id, true)
+                end)
             
-            task:insert_result('TG_PERM_BAN', 1.0)
-            rspamd_logger.infox(task, 'TG_PERM_BAN triggered for user %1, banned_q: %2', safe_str(user_id), safe_str(banned_q))
-        end
+                task:insert_result('TG_PERM_BAN', 1.0)
+                rspamd_logger.infox(task, 'TG_PERM_BAN triggered for user %1, banned_q: %2', safe_str(user_id), safe_str(banned_q))
+            end
+        end)
     end
     
     lua_redis.redis_make_request(task,
@@ -587,60 +1182,84 @@ local function tg_perm_ban_cb(task)
     )
 end
 
--- WHITELIST_USER: Check if user is whitelisted
+-- Members of a white- or blacklist (e.g. 'whitelist:words'): the global
+-- tg:<list> set plus the chat's own tg:chats:<id>:<list> set
+local function with_list(task, list, chat_id, cb)
+    local global_key = ns_key(task, 'tg:' .. list)
+    local chat_key = ns_key(task, settings.chat_prefix .. chat_id .. ':' .. list)
+    lua_redis.redis_make_request(task,
+        redis_params,
+        global_key,
+        false, -- is write
+        function(err, data)
+            local members = {}
+            if err then
+                rspamd_logger.errx(task, 'Failed to read %1: %2', list, safe_str(err))
+            elseif type(data) == 'table' then
+                for _, member in ipairs(data) do
+                    members[safe_str(member)] = true
+                end
+            end
+            cb(members)
+        end,
+        'SUNION',
+        {global_key, chat_key}
+    )
+end
+
+-- Whether value is matched by one of the list's 're:' entries; see
+-- lists::PATTERN_PREFIX in the bot. Invalid patterns are skipped.
+local function matches_pattern(members, value)
+    if value == "" then return false end
+    for member in pairs(members) do
+        if member:sub(1, 3) == 're:' then
+            local re = rspamd_regexp.create_cached(member:sub(4))
+            if re and re:match(value) then
+                return true
+            end
+        end
+    end
+    return false
+end
+
+-- Number of words in the message that are members of the list
+local function count_listed_words(task, list, chat_id, cb)
+    local msg = get_message_text(task)
+    with_list(task, list, chat_id, function(members)
+        local count = 0
+        for word in msg:gmatch("%w+") do
+            if members[word] or matches_pattern(members, word) then
+                count = count + 1
+            end
+        end
+        cb(count)
+    end)
+end
+
+-- WHITELIST_USER: Check if user is whitelisted, by id or username pattern
 local function whitelist_user_cb(task)
     local user_id, chat_id = get_user_chat_ids(task)
     if user_id == "" then return end
     
-    local user_key = settings.user_prefix .. user_id
-    
-    local function whitelist_cb(err, data)
-        if err then
-            rspamd_logger.errx(task, 'whitelist_cb error: %1', err)
-            return
-        end
-        if data then
+    with_list(task, 'whitelist:users', chat_id, function(members)
+        if members[user_id] or matches_pattern(members, get_username(task)) then
             task:insert_result('WHITELIST_USER', 1.0)
             rspamd_logger.infox(task, 'WHITELIST_USER triggered for user %1', safe_str(user_id))
         end
-    end
-    
-    lua_redis.redis_make_request(task,
-        redis_params,
-        'tg:whitelist:users',
-        false, -- is write
-        whitelist_cb,
-        'SISMEMBER',
-        {'tg:whitelist:users', user_key}
-    )
+    end)
 end
 
--- BLACKLIST_USER: Check if user is blacklisted
+-- BLACKLIST_USER: Check if user is blacklisted, by id or username pattern
 local function blacklist_user_cb(task)
     local user_id, chat_id = get_user_chat_ids(task)
     if user_id == "" then return end
     
-    local user_key = settings.user_prefix .. user_id
-    
-    local function blacklist_cb(err, data)
-        if err then
-            rspamd_logger.errx(task, 'blacklist_cb error: %1', err)
-            return
-        end
-        if data then
+    with_list(task, 'blacklist:users', chat_id, function(members)
+        if members[user_id] or matches_pattern(members, get_username(task)) then
             task:insert_result('BLACKLIST_USER', 1.0)
             rspamd_logger.infox(task, 'BLACKLIST_USER triggered for user %1', safe_str(user_id))
         end
-    end
-    
-    lua_redis.redis_make_request(task,
-        redis_params,
-        'tg:blacklist:users',
-        false, -- is write
-        blacklist_cb,
-        'SISMEMBER',
-        {'tg:blacklist:users', user_key}
-    )
+    end)
 end
 
 -- WHITELIST_WORD: Check for whitelisted words
@@ -648,38 +1267,12 @@ local function whitelist_word_cb(task)
     local user_id, chat_id = get_user_chat_ids(task)
     if user_id == "" then return end
     
-    local msg = get_message_text(task)
-    local words = {}
-    for word in msg:gmatch("%w+") do
-        words[#words + 1] = word
-    end
-    
-    local count = 0
-    local function if_member_cb(err, data)
-        if err then
-            rspamd_logger.errx(task, 'whitelist_word_cb error: %1', err)
-            return
+    count_listed_words(task, 'whitelist:words', chat_id, function(count)
+        if count > 0 then
+            task:insert_result('WHITELIST_WORD', count)
+            rspamd_logger.infox(task, 'WHITELIST_WORD triggered for user %1, count: %2', safe_str(user_id), safe_str(count))
         end
-        if data then
-            count = count + 1
-        end
-    end
-    
-    for _, word in ipairs(words) do
-        lua_redis.redis_make_request(task,
-            redis_params,
-            'tg:whitelist:words',
-            false, -- is write
-            if_member_cb,
-            'SISMEMBER',
-            {'tg:whitelist:words', word}
-        )
-    end
-    
-    if count > 0 then
-        task:insert_result('WHITELIST_WORD', count)
-        rspamd_logger.infox(task, 'WHITELIST_WORD triggered for user %1, count: %2', safe_str(user_id), safe_str(count))
-    end
+    end)
 end
 
 -- BLACKLIST_WORD: Check for blacklisted words
@@ -687,59 +1280,140 @@ local function blacklist_word_cb(task)
     local user_id, chat_id = get_user_chat_ids(task)
     if user_id == "" then return end
     
-    local msg = get_message_text(task)
-    local words = {}
-    for word in msg:gmatch("%w+") do
-        words[#words + 1] = word
-    end
-    
-    local count = 0
-    local function if_member_cb(err, data)
-        if err then
-            rspamd_logger.errx(task, 'blacklist_word_cb error: %1', err)
-            return
+    count_listed_words(task, 'blacklist:words', chat_id, function(count)
+        if count > 0 then
+            task:insert_result('BLACKLIST_WORD', count)
+            rspamd_logger.infox(task, 'BLACKLIST_WORD triggered for user %1, count: %2', safe_str(user_id), safe_str(count))
         end
-        if data then
-            count = count + 1
+    end)
+end
+
+-- Emoji codepoint ranges; keep in sync with config::emoji in the bot
+local emoji_ranges = {
+    {0x2600, 0x26FF}, {0x2700, 0x27BF}, {0x2B50, 0x2B55}, {0x1F004, 0x1F004}, {0x1F0CF, 0x1F0CF},
+    {0x1F170, 0x1F251}, {0x1F1E6, 0x1F1FF}, {0x1F300, 0x1F5FF}, {0x1F600, 0x1F64F}, {0x1F680, 0x1F6FF},
+    {0x1F7E0, 0x1F7FF}, {0x1F900, 0x1F9FF}, {0x1FA00, 0x1FAFF},
+}
+local emoji_modifiers = {{0xFE0F, 0xFE0F}, {0x20E3, 0x20E3}, {0x1F3FB, 0x1F3FF}, {0xE0020, 0xE007F}}
+local zwj = 0x200D
+
+local function in_ranges(cp, ranges)
+    for _, range in ipairs(ranges) do
+        if cp >= range[1] and cp <= range[2] then
+            return true
         end
     end
-    
-    for _, word in ipairs(words) do
-        lua_redis.redis_make_request(task,
-            redis_params,
-            'tg:blacklist:words',
-            false, -- is write
-            if_member_cb,
-            'SISMEMBER',
-            {'tg:blacklist:words', word}
-        )
+    return false
+end
+
+local function is_emoji(cp)
+    return not in_ranges(cp, emoji_modifiers) and in_ranges(cp, emoji_ranges)
+end
+
+local function is_regional_indicator(cp)
+    return cp >= 0x1F1E6 and cp <= 0x1F1FF
+end
+
+-- Count emoji as displayed: flags, modifier and ZWJ sequences count once
+local function count_emoji(text)
+    local cps = {}
+    for ch in text:gmatch(utf8_char_pattern) do
+        cps[#cps + 1] = codepoint(ch)
     end
-    
-    if count > 0 then
-        task:insert_result('BLACKLIST_WORD', count)
-        rspamd_logger.infox(task, 'BLACKLIST_WORD triggered for user %1, count: %2', safe_str(user_id), safe_str(count))
+    local count, i = 0, 1
+    while i <= #cps do
+        local cp = cps[i]
+        i = i + 1
+        if is_emoji(cp) then
+            count = count + 1
+            if is_regional_indicator(cp) then
+                if cps[i] and is_regional_indicator(cps[i]) then
+                    i = i + 1
+                end
+            else
+                while cps[i] do
+                    if in_ranges(cps[i], emoji_modifiers) then
+                        i = i + 1
+                    elseif cps[i] == zwj then
+                        i = i + 1
+                        if cps[i] and is_emoji(cps[i]) then
+                            i = i + 1
+                        end
+                    else
+                        break
+                    end
+                end
+            end
+        end
     end
+    return count
 end
 
 -- TG_EMOJI_SPAM: Detect excessive emoji usage
 local function tg_emoji_spam_cb(task)
     local user_id = get_user_chat_ids(task)
     local text = get_message_text(task)
-    local count = 0
-    
-    -- Count emoji characters using Unicode ranges
-    -- This covers most common emoji ranges
-    for _ in text:gmatch('[\240-\244][\128-\191][\128-\191][\128-\191]') do
-        count = count + 1
-        if count > settings.emoji_limit then
-            -- Update reputation for spam detection
-            update_user_reputation(task, user_id, true)
-            
-            task:insert_result('TG_EMOJI_SPAM', 1.0)
-            rspamd_logger.infox(task, 'TG_EMOJI_SPAM triggered, emoji count: %1', count)
-            break
+    local count = count_emoji(text)
+    
+    if_long_enough(task, text, function()
+        with_threshold(task, 'emoji_max', settings.emoji_limit, function(limit)
+            if count > limit then
+                task:insert_result('TG_EMOJI_SPAM', 1.0)
+                rspamd_logger.infox(task, 'TG_EMOJI_SPAM triggered, emoji count: %1', count)
+            end
+        end)
+    end)
+end
+
+-- Measure runs of one repeated character (whitespace breaks runs, ASCII is case-insensitive)
+local function char_runs(text)
+    local longest, flooded, total = 0, 0, 0
+    local prev, run = nil, 0
+    
+    local function close_run()
+        if run > longest then longest = run end
+        if run >= settings.char_flood_min_run then flooded = flooded + run end
+    end
+    
+    for ch in text:gmatch('[%z\1-\127\194-\244][\128-\191]*') do
+        if ch:match('^%s$') then
+            close_run()
+            prev, run = nil, 0
+        else
+            total = total + 1
+            ch = ch:lower()
+            if ch == prev then
+                run = run + 1
+            else
+                close_run()
+                prev, run = ch, 1
+            end
         end
     end
+    close_run()
+    
+    return longest, flooded, total
+end
+
+-- TG_CHAR_FLOOD: Detect stretched text ("buyyyyy nowwwww!!!!!")
+local function tg_char_flood_cb(task)
+    local user_id, chat_id = get_user_chat_ids(task)
+    if chat_id == "" then return end
+    
+    local longest, flooded, total = char_runs(get_message_text(task))
+    if total == 0 then return end
+    
+    if_feature_enabled(task, chat_id, 'char_flood', function()
+        with_threshold(task, 'char_run_max', settings.char_run_max, function(max_run)
+            with_threshold(task, 'char_flood_ratio', settings.char_flood_ratio, function(max_ratio)
+                local ratio = flooded / total
+                if longest > max_run or (total >= settings.char_flood_min_length and ratio >= max_ratio) then
+                    task:insert_result('TG_CHAR_FLOOD', 1.0, tostring(longest))
+                    rspamd_logger.infox(task, 'TG_CHAR_FLOOD triggered, longest run: %1, ratio: %2', longest, ratio)
+                end
+            end)
+        end)
+    end)
 end
 
 -- TG_INVITE_LINK: Detect Telegram invite links
@@ -749,9 +1423,6 @@ local function tg_invite_link_cb(task)
     
     for _, pattern in ipairs(settings.invite_link_patterns) do
         if text:find(pattern, 1, true) then
-            -- Update reputation for spam detection
-            update_user_reputation(task, user_id, true)
-            
             task:insert_result('TG_INVITE_LINK', 1.0)
             rspamd_logger.infox(task, 'TG_INVITE_LINK triggered, pattern: %1', pattern)
             break
@@ -765,48 +1436,194 @@ local function tg_phone_spam_cb(task)
     local text = get_message_text(task)
     
     if text:match(settings.phone_regex) then
-        -- Update reputation for spam detection
-        update_user_reputation(task, user_id, true)
-        
         task:insert_result('TG_PHONE_SPAM', 1.0)
         rspamd_logger.infox(task, 'TG_PHONE_SPAM triggered')
     end
 end
 
--- TG_SHORTENER: Detect URL shortener links
+-- TG_SHORTENER: Detect links whose host is a URL shortener (or a subdomain of
+-- one), so a shortener's name inside another host doesn't count
 local function tg_shortener_cb(task)
-    local user_id = get_user_chat_ids(task)
-    local text = get_message_text(task):lower()
-    
-    for _, shortener in ipairs(settings.shorteners) do
-        if text:find(shortener) then
-            -- Update reputation for spam detection
-            update_user_reputation(task, user_id, true)
-            
-            task:insert_result('TG_SHORTENER', 1.0)
-            rspamd_logger.infox(task, 'TG_SHORTENER triggered, shortener: %1', shortener)
-            break
-        end
-    end
+    with_untrusted_hosts(task, function(hosts)
+        if #hosts == 0 then return end
+        with_shorteners(task, function(shorteners)
+            for _, host in ipairs(hosts) do
+                if host_in_set(shorteners, host) then
+                    task:insert_result('TG_SHORTENER', 1.0, host)
+                    rspamd_logger.infox(task, 'TG_SHORTENER triggered, shortener: %1', host)
+                    break
+                end
+            end
+        end)
+    end)
 end
 
--- TG_GIBBERISH: Detect long sequences of random consonants
+-- TG_GIBBERISH: Detect random-letter text by its share of consonants. All
+-- Latin letters count wherever the spaces fall, so gibberish split into
+-- words is caught too (mirrored by gibberish.rs)
 local function tg_gibberish_cb(task)
-    local user_id = get_user_chat_ids(task)
     local text = get_message_text(task)
     
-    -- Pattern for 5+ consecutive consonants (indicating gibberish)
-    if text:match('[bcdfghjklmnpqrstvwxzBCDFGHJKLMNPQRSTVWXZ][bcdfghjklmnpqrstvwxzBCDFGHJKLMNPQRSTVWXZ][bcdfghjklmnpqrstvwxzBCDFGHJKLMNPQRSTVWXZ][bcdfghjklmnpqrstvwxzBCDFGHJKLMNPQRSTVWXZ][bcdfghjklmnpqrstvwxzBCDFGHJKLMNPQRSTVWXZ]') then
-        -- Update reputation for spam detection
-        update_user_reputation(task, user_id, true)
+    if_long_enough(task, text, function()
+        local letters, vowels = 0, 0
+        for ch in text:gmatch('[A-Za-z]') do
+            letters = letters + 1
+            if ch:match('[aeiouyAEIOUY]') then
+                vowels = vowels + 1
+            end
+        end
+        if letters == 0 then
+            return
+        end
+        with_threshold(task, 'gibberish_min_length', settings.gibberish_min_length, function(min_letters)
+            if letters < min_letters then
+                return
+            end
+            with_threshold(task, 'gibberish_ratio', settings.gibberish_ratio, function(max_ratio)
+                local ratio = 1 - vowels / letters
+                if ratio > max_ratio then
+                    task:insert_result('TG_GIBBERISH', 1.0, string.format('%.2f', ratio))
+                    rspamd_logger.infox(task, 'TG_GIBBERISH triggered, consonant ratio %1', ratio)
+                end
+            end)
+        end)
+    end)
+end
+
+-- Determine the script most letters of the text belong to
+local function dominant_script(text)
+    local counts = {}
+    for ch in text:gmatch(utf8_char_pattern) do
+        local cp = codepoint(ch)
         
-        task:insert_result('TG_GIBBERISH', 1.0)
-        rspamd_logger.infox(task, 'TG_GIBBERISH triggered')
+        for _, script in ipairs(settings.scripts) do
+            for _, range in ipairs(script.ranges) do
+                if cp >= range[1] and cp <= range[2] then
+                    counts[script.name] = (counts[script.name] or 0) + 1
+                end
+            end
+        end
     end
+    
+    local best, best_count = nil, 0
+    for name, count in pairs(counts) do
+        if count > best_count then
+            best, best_count = name, count
+        end
+    end
+    return best
+end
+
+-- TG_FOREIGN_SCRIPT: Detect messages written in a script the chat doesn't allow
+local function tg_foreign_script_cb(task)
+    local user_id, chat_id = get_user_chat_ids(task)
+    if chat_id == "" then return end
+    
+    local script = dominant_script(get_message_text(task))
+    if not script then return end
+    
+    local allowed_key = ns_key(task, settings.chat_prefix .. chat_id .. settings.allowed_scripts_suffix)
+    
+    if_feature_enabled(task, chat_id, 'foreign_script', function()
+        lua_redis.redis_make_request(task,
+            redis_params,
+            allowed_key,
+            false, -- is write
+            function(err, data)
+                if err then
+                    rspamd_logger.errx(task, 'foreign_script_cb error: %1', err)
+                    return
+                end
+                -- An empty allowlist means no restriction
+                if type(data) ~= 'table' or #data == 0 then return end
+                
+                for _, allowed in ipairs(data) do
+                    if allowed == script then return end
+                end
+                
+                task:insert_result('TG_FOREIGN_SCRIPT', 1.0, script)
+                rspamd_logger.infox(task, 'TG_FOREIGN_SCRIPT triggered, script: %1', script)
+            end,
+            'SMEMBERS',
+            {allowed_key}
+        )
+    end)
+end
+
+-- TG_FORWARDED: Flag forwarded messages (the bot adds the new-user channel penalty)
+local function tg_forwarded_cb(task)
+    local _, chat_id = get_user_chat_ids(task)
+    if chat_id == "" then return end
+    
+    local origin = safe_str(task:get_header('X-Telegram-Forward', true))
+    if origin == "" then return end
+    
+    if_feature_enabled(task, chat_id, 'forwarded', function()
+        task:insert_result('TG_FORWARDED', 1.0, origin)
+        rspamd_logger.infox(task, 'TG_FORWARDED triggered, origin: %1', origin)
+    end)
+end
+
+-- Run cb(age, first_fast, first_slow) for a user's first message since joining,
+-- with the chat's join windows (the bot records join_time and last_msg_time)
+local function with_first_message_age(task, cb)
+    local user_id, chat_id = get_user_chat_ids(task)
+    if user_id == "" or chat_id == "" then return end
+    
+    local user_key = ns_key(task, settings.user_prefix .. user_id)
+    lua_redis.redis_make_request(task,
+        redis_params,
+        user_key,
+        false, -- is write
+        function(err, data)
+            if err or type(data) ~= 'table' then return end
+            local join_time = safe_num(data[1])
+            local last_msg_time = safe_num(data[2])
+            if join_time == 0 or last_msg_time ~= 0 then return end
+            
+            local gate_key = ns_key(task, settings.chat_prefix .. chat_id .. settings.join_gate_suffix)
+            lua_redis.redis_make_request(task,
+                redis_params,
+                gate_key,
+                false, -- is write
+                function(e, windows)
+                    if e or type(windows) ~= 'table' then windows = {} end
+                    cb(os.time() - join_time,
+                        safe_num(windows[1], settings.join_fast),
+                        safe_num(windows[2], settings.join_slow))
+                end,
+                'HMGET',
+                {gate_key, 'first_fast', 'first_slow'}
+            )
+        end,
+        'HMGET',
+        {user_key, 'join_time', 'last_msg_time'}
+    )
+end
+
+-- TG_FIRST_FAST: First message sent too soon after joining
+local function tg_first_fast_cb(task)
+    with_first_message_age(task, function(age, first_fast)
+        if age < first_fast then
+            task:insert_result('TG_FIRST_FAST', 1.0, tostring(age))
+            rspamd_logger.infox(task, 'TG_FIRST_FAST triggered, %1s after joining (window %2s)', age, first_fast)
+        end
+    end)
+end
+
+-- TG_FIRST_SLOW: First message sent long after joining
+local function tg_first_slow_cb(task)
+    with_first_message_age(task, function(age, _, first_slow)
+        if age > first_slow then
+            task:insert_result('TG_FIRST_SLOW', 1.0, tostring(age))
+            rspamd_logger.infox(task, 'TG_FIRST_SLOW triggered, %1s after joining (window %2s)', age, first_slow)
+        end
+    end)
 end
 
 -- TG_GOOD_REPUTATION: Update good reputation for legitimate messages
 local function tg_good_reputation_cb(task)
+    if is_preview(task) then return end
     local user_id = get_user_chat_ids(task)
     if user_id == "" then return end
     
@@ -830,26 +1647,40 @@ rspamd_config.TG_REPEAT = {
 }
 
 rspamd_config.TG_LINK_SPAM = {
-    callback = tg_link_spam_cb,
+    callback = when_enabled('link_spam', tg_link_spam_cb),
     score = 2.5,
     description = 'Message contains excessive number of links',
     group = 'telegram_content'
 }
 
+rspamd_config.TG_LOOKALIKE_DOMAIN = {
+    callback = tg_lookalike_domain_cb,
+    score = 4.0,
+    description = 'Link to a domain that mixes scripts to imitate another',
+    group = 'telegram_heuristics'
+}
+
 rspamd_config.TG_MENTIONS = {
-    callback = tg_mentions_cb,
+    callback = when_enabled('mentions', tg_mentions_cb),
     score = 2.5,
     description = 'Message mentions too many users',
     group = 'telegram_content'
 }
 
 rspamd_config.TG_CAPS = {
-    callback = tg_caps_cb,
+    callback = when_enabled('caps', tg_caps_cb),
     score = 1.5,
     description = 'Message is written almost entirely in capital letters',
     group = 'telegram_content'
 }
 
+rspamd_config.TG_CROSS_POST = {
+    callback = tg_cross_post_cb,
+    score = 3.0,
+    description = 'Same message posted to multiple chats',
+    group = 'telegram_core'
+}
+
 rspamd_config.TG_SUSPICIOUS = {
     callback = tg_suspicious_cb,
     score = 5.0,
@@ -898,40 +1729,75 @@ rspamd_config.BLACKLIST_WORD = {
 
 -- Register advanced detection symbols
 rspamd_config.TG_EMOJI_SPAM = {
-    callback = tg_emoji_spam_cb,
+    callback = when_enabled('emoji_spam', tg_emoji_spam_cb),
     score = 2.5,
     description = 'Excessive emoji usage',
     group = 'telegram_content'
 }
 
+rspamd_config.TG_CHAR_FLOOD = {
+    callback = tg_char_flood_cb,
+    score = 1.5,
+    description = 'Message stretches words with long runs of repeated characters',
+    group = 'telegram_content'
+}
+
 rspamd_config.TG_INVITE_LINK = {
-    callback = tg_invite_link_cb,
+    callback = when_enabled('invite_link', tg_invite_link_cb),
     score = 4.0,
     description = 'Telegram invite link detected',
     group = 'telegram_heuristics'
 }
 
 rspamd_config.TG_PHONE_SPAM = {
-    callback = tg_phone_spam_cb,
+    callback = when_enabled('phone_spam', tg_phone_spam_cb),
     score = 3.0,
     description = 'Contains phone number spam',
     group = 'telegram_heuristics'
 }
 
 rspamd_config.TG_SHORTENER = {
-    callback = tg_shortener_cb,
+    callback = when_enabled('shortener', tg_shortener_cb),
     score = 2.0,
     description = 'Contains URL shortener link',
     group = 'telegram_heuristics'
 }
 
 rspamd_config.TG_GIBBERISH = {
-    callback = tg_gibberish_cb,
+    callback = when_enabled('gibberish', tg_gibberish_cb),
     score = 2.0,
-    description = 'Gibberish consonant sequences',
+    description = 'Random-letter text made up mostly of consonants',
+    group = 'telegram_heuristics'
+}
+
+rspamd_config.TG_FOREIGN_SCRIPT = {
+    callback = tg_foreign_script_cb,
+    score = 3.0,
+    description = 'Message written in a script not allowed in this chat',
     group = 'telegram_heuristics'
 }
 
+rspamd_config.TG_FORWARDED = {
+    callback = tg_forwarded_cb,
+    score = 0.5,
+    description = 'Message was forwarded from another chat or user',
+    group = 'telegram_heuristics'
+}
+
+rspamd_config.TG_FIRST_FAST = {
+    callback = when_enabled('first_fast', tg_first_fast_cb),
+    score = 3.0,
+    description = 'First message sent immediately after join',
+    group = 'telegram_timing'
+}
+
+rspamd_config.TG_FIRST_SLOW = {
+    callback = when_enabled('first_slow', tg_first_slow_cb),
+    score = 2.0,
+    description = 'First message sent long after join',
+    group = 'telegram_timing'
+}
+
 rspamd_config.TG_GOOD_REPUTATION = {
     callback = tg_good_reputation_cb,
     score = 0.0, -- No score impact, just updates reputation
@@ -940,4 +1806,4 @@ rspamd_config.TG_GOOD_REPUTATION = {
 }
 
 -- Log that symbols are registered
-rspamd_logger.infox(rspamd_config, 'Telegram symbols registered: TG_FLOOD, TG_REPEAT, TG_LINK_SPAM, TG_MENTIONS, TG_CAPS, TG_SUSPICIOUS, TG_BAN, TG_PERM_BAN, TG_EMOJI_SPAM, TG_INVITE_LINK, TG_PHONE_SPAM, TG_SHORTENER, TG_GIBBERISH, TG_GOOD_REPUTATION, WHITELIST_USER, BLACKLIST_USER, WHITELIST_WORD, BLACKLIST_WORD') 
\ No newline at end of file
+rspamd_logger.infox(rspamd_config, 'Telegram symbols registered: TG_FLOOD, TG_REPEAT, TG_LINK_SPAM, TG_MENTIONS, TG_CAPS, TG_CROSS_POST, TG_SUSPICIOUS, TG_BAN, TG_PERM_BAN, TG_EMOJI_SPAM, TG_CHAR_FLOOD, TG_INVITE_LINK, TG_PHONE_SPAM, TG_SHORTENER, TG_LOOKALIKE_DOMAIN, TG_GIBBERISH, TG_FOREIGN_SCRIPT, TG_FORWARDED, TG_FIRST_FAST, TG_FIRST_SLOW, TG_GOOD_REPUTATION, WHITELIST_USER, BLACKLIST_USER, WHITELIST_WORD, BLACKLIST_WORD') 
\ No newline at end of file
@@ -0,0 +1,140 @@
+//! Flood and repeat limits scaled to a chat's message rate, behind the
+//! opt-in `adaptive_thresholds` feature.
+//!
+//! Every message bumps the `msg_tick` field of its `tg:chats:<id>` hash; every
+//! `adaptive::SAMPLE_SECS` the periodic task turns the tick into a sample of
+//! messages per minute and folds it into the chat's `msg_rate`, an
+//! exponentially weighted moving average:
+//!
+//! ```text
+//! rate = EWMA_ALPHA * sample + (1 - EWMA_ALPHA) * rate
+//! ```
+//!
+//! The chat's limits are then scaled in proportion to that rate, clamped to
+//! the chat's bounds (`adaptive_min`/`adaptive_max`, see `/setadaptive`):
+//!
+//! ```text
+//! scale = clamp(rate / REFERENCE_RATE, min, max)
+//! limit = max(1, round(base_limit * scale))
+//! ```
+//!
+//! so a busy chat tolerates more messages per user before `TG_FLOOD` and
+//! `TG_REPEAT` fire and a quiet one fewer. The scaled limits are written to
+//! `adaptive_flood_max` and `adaptive_repeat_max`, which `telegram_simple.lua`
+//! prefers over the unscaled ones.
+
+use std::error::Error;
+
+use chrono::Utc;
+use redis::{Commands, RedisResult};
+use teloxide::types::ChatId;
+
+use crate::config::{adaptive, field, is_feature_enabled, key, ADAPTIVE_FEATURE};
+use crate::flood::flood_limit;
+
+fn chat_key(chat_id: ChatId) -> String {
+    format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0)
+}
+
+/// Counts a message towards `chat_id`'s next rate sample.
+pub fn record_message(conn: &mut redis::Connection, chat_id: ChatId) -> RedisResult<()> {
+    conn.hincr(chat_key(chat_id), field::MSG_TICK, 1)
+}
+
+/// `chat_id`'s message rate in messages per minute, or `None` before its first sample.
+pub fn message_rate(conn: &mut redis::Connection, chat_id: ChatId) -> Option<f64> {
+    conn.hget(chat_key(chat_id), field::MSG_RATE).ok().flatten()
+}
+
+/// Folds the messages counted since the last sample of `chat_id` into its
+/// rate, at `now` (Unix seconds). Returns the new rate.
+pub fn sample_rate(conn: &mut redis::Connection, chat_id: ChatId, now: i64) -> RedisResult<f64> {
+    let chat_key = chat_key(chat_id);
+    let (tick, rate, sampled_at): (Option<i64>, Option<f64>, Option<i64>) = redis::pipe()
+        .atomic()
+        .hget(&chat_key, field::MSG_TICK)
+        .hget(&chat_key, field::MSG_RATE)
+        .hget(&chat_key, field::MSG_RATE_AT)
+        .hdel(&chat_key, field::MSG_TICK).ignore()
+        .query(conn)?;
+
+    let elapsed = sampled_at
+        .map(|at| now - at)
+        .filter(|elapsed| *elapsed > 0)
+        .unwrap_or(adaptive::SAMPLE_SECS as i64);
+    let sample = tick.unwrap_or(0) as f64 * 60.0 / elapsed as f64;
+    let rate = match rate {
+        Some(rate) => adaptive::EWMA_ALPHA * sample + (1.0 - adaptive::EWMA_ALPHA) * rate,
+        None => sample,
+    };
+    conn.hset_multiple::<_, _, _, ()>(&chat_key, &[(field::MSG_RATE, rate.to_string()), (field::MSG_RATE_AT, now.to_string())])?;
+    Ok(rate)
+}
+
+/// Bounds of the scale applied to `chat_id`'s limits.
+pub fn scale_bounds(conn: &mut redis::Connection, chat_id: ChatId) -> (f64, f64) {
+    let (min, max): (Option<f64>, Option<f64>) = conn
+        .hget(chat_key(chat_id), &[field::ADAPTIVE_MIN, field::ADAPTIVE_MAX])
+        .unwrap_or((None, None));
+    (min.unwrap_or(adaptive::DEFAULT_MIN_SCALE), max.unwrap_or(adaptive::DEFAULT_MAX_SCALE))
+}
+
+/// Sets `chat_id`'s scale bounds, or goes back to the defaults with `None`.
+pub fn set_scale_bounds(conn: &mut redis::Connection, chat_id: ChatId, bounds: Option<(f64, f64)>) -> RedisResult<()> {
+    match bounds {
+        Some((min, max)) => conn.hset_multiple(chat_key(chat_id), &[(field::ADAPTIVE_MIN, min), (field::ADAPTIVE_MAX, max)]),
+        None => conn.hdel(chat_key(chat_id), &[field::ADAPTIVE_MIN, field::ADAPTIVE_MAX]),
+    }
+}
+
+/// Scale for a chat sending `rate` messages per minute: proportional to the
+/// rate, with `REFERENCE_RATE` leaving the limits unchanged, clamped to `(min, max)`.
+pub fn scale(rate: f64, (min, max): (f64, f64)) -> f64 {
+    (rate / adaptive::REFERENCE_RATE).clamp(min, max)
+}
+
+fn scaled(base: i64, scale: f64) -> i64 {
+    ((base as f64 * scale).round() as i64).max(1)
+}
+
+/// `chat_id`'s flood and repeat limits scaled to its message rate. Chats
+/// without a rate yet keep their limits, within the bounds.
+pub fn effective_limits(conn: &mut redis::Connection, chat_id: ChatId) -> (i64, i64) {
+    let rate = message_rate(conn, chat_id).unwrap_or(adaptive::REFERENCE_RATE);
+    let scale = scale(rate, scale_bounds(conn, chat_id));
+    let (flood, _) = flood_limit(conn, chat_id);
+    (scaled(flood, scale), scaled(adaptive::BASE_REPEAT_MAX, scale))
+}
+
+/// Samples `chat_id`'s rate at `now` and stores its scaled limits for
+/// detection, or removes them when the feature is off for the chat.
+pub fn refresh_chat(conn: &mut redis::Connection, chat_id: ChatId, now: i64) -> RedisResult<()> {
+    sample_rate(conn, chat_id, now)?;
+    if !is_feature_enabled(conn, chat_id.0, ADAPTIVE_FEATURE) {
+        return conn.hdel(chat_key(chat_id), &[field::ADAPTIVE_FLOOD_MAX, field::ADAPTIVE_REPEAT_MAX]);
+    }
+    let (flood, repeat) = effective_limits(conn, chat_id);
+    conn.hset_multiple(chat_key(chat_id), &[(field::ADAPTIVE_FLOOD_MAX, flood), (field::ADAPTIVE_REPEAT_MAX, repeat)])
+}
+
+/// Runs `refresh_chat` for every chat with messages counted or a rate to
+/// decay, for the periodic task. Returns how many chats were refreshed.
+pub fn refresh_adaptive_limits() -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+    let prefix = key::ns(key::TG_CHATS_PREFIX);
+    let keys: Vec<String> = conn.scan_match::<_, String>(format!("{}*", prefix))?.collect();
+    let now = Utc::now().timestamp();
+    let mut refreshed = 0;
+    for chat_key in keys {
+        let Some(chat_id) = chat_key.strip_prefix(prefix.as_str()).and_then(|id| id.parse::<i64>().ok()) else {
+            continue;
+        };
+        let sampled: bool = conn.hexists(&chat_key, field::MSG_TICK)? || conn.hexists(&chat_key, field::MSG_RATE)?;
+        if !sampled {
+            continue;
+        }
+        refresh_chat(&mut conn, ChatId(chat_id), now)?;
+        refreshed += 1;
+    }
+    Ok(refreshed)
+}
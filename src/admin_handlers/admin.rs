@@ -1,19 +1,15 @@
-use crate::admin_handlers::{AdminCommand, handle_neural_stats, handle_neural_reset, handle_neural_status, handle_neural_features};
-use crate::config::{field, key, suffix, ENABLED_FEATURES_KEY, reply_aware, rate_limit};
-use crate::trust_manager::{TrustManager, TrustedMessageType, TrustedMessageMetadata};
-use crate::bayes_manager::BayesManager;
-use redis::{Commands, RedisResult};
-use std::collections::HashMap;
-use std::fmt::Write;
+use crate::admin_handlers::{command_access, AdminCommand, CommandAccess, handle_report_spam, handle_appeal, handle_purge, handle_reset_chat, handle_search_messages, handle_global_stats, handle_worst_users, handle_diagnose, handle_simulate_raid, handle_toggle_symbol, handle_health, handle_neural_stats, handle_neural_reset, handle_neural_train, handle_neural_status, handle_neural_features, handle_test_message, handle_set_threshold, handle_set_action, handle_set_join_window, handle_set_flood, handle_set_ban_rate, handle_set_adaptive, handle_perm_ban_action, handle_forwards, handle_allow_script, handle_feature_status, handle_lockdown, handle_ban_list, handle_mute_list, handle_mute, handle_unmute, handle_symbol_stats, handle_trend, handle_whois, handle_note, handle_notes, handle_import_whitelist, handle_whitelist_export, handle_blacklist_export, handle_trusted_domain, handle_risky_ext, handle_shortener, handle_domain_rep, handle_trust_user, handle_untrust_user, handle_fuzzy_add, handle_fuzzy_del, handle_manage_features, handle_stats, handle_whitelist, handle_blacklist, handle_reply_config, handle_rate_limit_stats, handle_list_messages, handle_reputation, handle_mark_trusted, handle_trust_stats, handle_spam_patterns, handle_selective_trust, handle_anti_evasion_stats, handle_reset_rate_limit, handle_learn_spam, handle_learn_ham, handle_bayes_stats, handle_bayes_reset, handle_check_message, handle_add_admin, handle_remove_admin, handle_set_permissions, handle_list_admins, handle_monitored_chats, handle_export_audit_log, handle_emergency_stop, handle_export_config, handle_import_config};
+use crate::config::{field, key, stats, suffix, ENABLED_FEATURES_KEY};
+use crate::handlers::{message_sender, stored_message_content, Sender};
+use redis::Commands;
 use teloxide::types::{Chat, ChatMemberStatus};
 use teloxide::{prelude::*, types::InlineKeyboardButton, types::InlineKeyboardMarkup};
 use tokio::fs::OpenOptions;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
-use teloxide::types::{ChatId, MessageId, UserId};
+use teloxide::types::UserId;
 
 use anyhow::Result;
-use regex::Regex;
 
 async fn is_user_admin(bot: &Bot, chat: Chat, user_id: UserId) -> anyhow::Result<bool> {
     if !chat.is_private() {
@@ -28,1050 +24,283 @@ async fn is_user_admin(bot: &Bot, chat: Chat, user_id: UserId) -> anyhow::Result
     }
 }
 
-/// Shared helper for both whitelist and blacklist logic.
-///
-/// - `bot` / `chat_id`: for sending replies.
-/// - `redis_conn`: mutable connection to Redis.
-/// - `redis_key`: the exact SET key (e.g. `key::TG_WHITELIST_USER_KEY`).
-/// - `item_kind`: `"user"` or `"word"` (used in reply text).
-/// - `list_name`: `"whitelist"` or `"blacklist"` (used in reply text).
-/// - `action`: must be `"add"` or `"find"`.
-/// - `target`: the third part of the pattern. If `action=="add"`, it must not be `"*"`.
-///             If `action=="find"`, it can be `"*"`, a plain literal, or a Rust‐regex.
-///
-/// This sends the appropriate reply and returns `Ok(())`.
-async fn process_set(
-    bot: &Bot,
-    chat_id: ChatId,
-    redis_conn: &mut redis::Connection,
-    redis_key: &str,
-    item_kind: &str,  // "user" or "word"
-    list_name: &str,  // "whitelist" or "blacklist"
-    action: &str,     // "add" or "find"
-    target: &str,     // third part of the pattern
-) -> ResponseResult<()> {
-    match action {
-        // ────────────────────────────────────────────────────────────────────
-        "add" => {
-            // You cannot do “add|*”. Must specify exactly one literal (user_id or word).
-            if target == "*" {
-                bot.send_message(
-                    chat_id,
-                    format!(
-                        "Cannot use `*` with `add`. You must specify exactly one {} to add.",
-                        item_kind
-                    ),
-                )
-                    .await?;
-            } else {
-                // Straight SADD
-                let rv: RedisResult<()> = redis_conn.sadd(redis_key, target);
-                match rv {
-                    Ok(()) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("Added {} `{}` to the {}.", item_kind, target, list_name),
-                        )
-                            .await?;
-                    }
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("Failed to add {} to {}: {}", item_kind, target, e),
-                        )
-                            .await?;
-                    }
-                }
-            }
-        }
-
-        // ────────────────────────────────────────────────────────────────────
-        "find" => {
-            // 1) If target == "*", list all members via SMEMBERS.
-            if target == "*" {
-                let all_items: Vec<String> =
-                    redis_conn.smembers(redis_key).unwrap_or_else(|_| Vec::new());
-                if all_items.is_empty() {
-                    bot.send_message(chat_id, format!("(no {}ed {}s)", list_name, item_kind))
-                        .await?;
-                } else {
-                    let joined = all_items.join(", ");
-                    bot.send_message(
-                        chat_id,
-                        format!("{}ed {}s: {}",
-                                // Capitalize first letter of list_name for nicer output
-                                {
-                                    let mut s = list_name.to_owned();
-                                    s.get_mut(0..1).map(|c| c.make_ascii_uppercase());
-                                    s
-                                },
-                                item_kind,
-                                joined,
-                        ),
-                    )
-                        .await?;
-                }
-                return Ok(());
-            }
-
-            // 2) If no regex meta‐characters, treat target as a plain literal → SISMEMBER
-            let is_plain_literal = !target.chars().any(|c| {
-                matches!(
-                    c,
-                    '.' | '^' | '$' | '*' | '+' | '?' | '(' | ')' | '[' | ']' | '{' | '}' | '|' | '\\'
-                )
-            });
-
-            if is_plain_literal {
-                let exists: bool = redis_conn.sismember(redis_key, target).unwrap_or(false);
-                if exists {
-                    bot.send_message(
-                        chat_id,
-                        format!(
-                            "{} `{}` is in the {}.",
-                            item_kind, target, list_name
-                        ),
-                    )
-                        .await?;
-                } else {
-                    bot.send_message(
-                        chat_id,
-                        format!(
-                            "{} `{}` is NOT in the {}.",
-                            item_kind, target, list_name
-                        ),
-                    )
-                        .await?;
-                }
-            } else {
-                // 3) Regex case: attempt to compile `target` as a Rust regex,
-                // then SMEMBERS and filter in Rust.
-                let pattern = match Regex::new(target) {
-                    Ok(rgx) => rgx,
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("Invalid regex `{}`: {}", target, e),
-                        )
-                            .await?;
-                        return Ok(());
-                    }
-                };
-
-                let all_items: Vec<String> =
-                    redis_conn.smembers(redis_key).unwrap_or_else(|_| Vec::new());
-                let mut matches = Vec::new();
-                for item in all_items.iter() {
-                    if pattern.is_match(item) {
-                        matches.push(item.clone());
-                    }
-                }
-
-                if matches.is_empty() {
-                    bot.send_message(
-                        chat_id,
-                        format!(
-                            "No {}ed {}s match `/ {}`.",
-                            list_name, item_kind, target
-                        ),
-                    )
-                        .await?;
-                } else {
-                    let joined = matches.join(", ");
-                    bot.send_message(
-                        chat_id,
-                        format!(
-                            "{}s matching `/ {}`: {}",
-                            // Capitalize list_name for output
-                            {
-                                let mut s = list_name.to_owned();
-                                s.get_mut(0..1).map(|c| c.make_ascii_uppercase());
-                                s
-                            },
-                            target,
-                            joined
-                        ),
-                    )
-                        .await?;
-                }
-            }
-        }
-
-        _ => {
-            // Should never happen if the caller only passes "add" or "find"
-            bot.send_message(
-                chat_id,
-                format!("Invalid action `{}`. Must be `add` or `find`.", action),
-            )
-                .await?;
-        }
+#[tracing::instrument(skip_all, fields(chat_id = msg.chat.id.0, user_id = message_sender(&msg).map(|sender| sender.id()), message_id = msg.id.0))]
+pub async fn handle_admin_command(bot: Bot, msg: Message, cmd: AdminCommand) -> ResponseResult<()> {
+    // Any member may report spam, so this bypasses the admin check below
+    if let AdminCommand::ReportSpam = cmd {
+        return handle_report_spam(bot, msg).await;
+    }
+    // Banned users appeal from a private chat, so this skips the admin check as well
+    if let AdminCommand::Appeal { reason } = cmd {
+        return handle_appeal(bot, msg, reason).await;
     }
 
-    Ok(())
-}
-
-pub async fn handle_admin_command(bot: Bot, msg: Message, cmd: AdminCommand) -> ResponseResult<()> {
     let redis_client =
         redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
     let mut redis_conn = redis_client
         .get_connection()
         .expect("Failed to get Redis connection");
-    let user_id = msg.from.unwrap().id;
-    let chat = msg.chat;
+    let chat = msg.chat.clone();
     let chat_id = chat.id;
+    // Admin commands are tied to a user account (admin chats, trust metadata)
+    let user_id = match message_sender(&msg) {
+        Some(Sender::User(user_id)) => user_id,
+        Some(Sender::AnonymousAdmin(_)) | Some(Sender::Channel(_)) => {
+            bot.send_message(chat_id, "Admin commands can't be sent anonymously or as a channel; post as yourself to use them.").await?;
+            return Ok(());
+        }
+        None => return Ok(()),
+    };
     let is_admin = is_user_admin(&bot, chat, user_id).await.unwrap_or(false);
     if is_admin {
+        // Once the admin panel is set up, its permissions narrow down what each admin may run
+        match command_access(&mut redis_conn, user_id, &cmd) {
+            Ok(CommandAccess::Unrestricted | CommandAccess::Granted) => {}
+            Ok(CommandAccess::Denied(permission)) => {
+                bot.send_message(chat_id, format!("This command needs the \"{}\" admin panel permission.", permission)).await?;
+                return Ok(());
+            }
+            Err(e) => {
+                bot.send_message(chat_id, format!("Failed to check admin panel permissions: {}", e)).await?;
+                return Ok(());
+            }
+        }
         match cmd {
             AdminCommand::MakeAdmin => {
-                let _: () = redis_conn
-                    .sadd(format!("{}{}", user_id.to_string(), suffix::ADMIN_CHATS), chat_id.0)
-                    .expect("Failed to add chat to admin_chats");
-                
-                let bot_chats: Vec<i64> = redis_conn
-                    .smembers(format!("{}{}", user_id.to_string(), suffix::BOT_CHATS))
-                    .unwrap_or_else(|_| Vec::new());
-
-                let mut rows: Vec<Vec<InlineKeyboardButton>> = Vec::new();
-                for chat in bot_chats {
-                    if chat == chat_id.0 { continue; }
-                    let chat_name: String = redis_conn
-                        .hget(format!("{}{}", key::TG_CHATS_PREFIX, chat), field::NAME)
-                        .expect("Failed to get chat name");
-                    rows.push(vec![InlineKeyboardButton::callback(
-                        format!("Chat: {}", chat_name),
-                        format!("makeadmin:{}", chat),
-                    )]);
-                }
-                let keyboard = InlineKeyboardMarkup::new(rows);
-
-                bot.send_message(
-                    chat_id,
-                    "Admin chat registered! Please select chats to moderate:",
-                )
-                .reply_markup(keyboard)
-                .await?;
+                handle_make_admin(bot.clone(), msg.clone(), user_id).await?;
             }
             AdminCommand::Help => {
-                bot.send_message(
-                    chat_id,
-                    "Commands:\n\
-                    /help – show help for commands\n\
-                    /makeadmin – register current chat as admin control chat\n\
-                    /reputation <username> – show user's reputation\n\
-                    /addregex <symbol|pattern|score> – add regex rule to rspamd\n\
-                    /stats – show stats\n\
-                    /whitelist <user|word>|<add|find>|<target>\n\
-                    /blacklist <user|word>|<add|find>|<target>\n\
-                    /marktrusted <message_id>|<bot|admin|verified> – mark message as trusted for reply-aware filtering\n\
-                    /truststats – show trust management statistics\n\
-                    \n\
-                    Advanced Reply-Aware Filtering Commands:\n\
-                    /replyconfig <setting>|<value> – configure reply-aware filtering settings\n\
-                    /ratelimitstats – show rate limiting statistics\n\
-                    /resetratelimit <user> – reset rate limiting for a user\n\
-                    /spampatterns <user> – show spam pattern history for a user\n\
-                    /selectivetrust <rule>|<true|false> – configure selective trusting rules\n\
-                    /antievasionstats – show anti-evasion statistics\n\
-                    \n\
-                    Bayesian Learning Commands:\n\
-                    /learnspam <message_id> – learn a message as spam for Bayesian classifier\n\
-                    /learnham <message_id> – learn a message as ham for Bayesian classifier\n\
-                    /bayesstats – show Bayesian classifier statistics\n\
-                    /bayesreset – reset all Bayesian classifier data\n\
-                    \n\
-                    Neural Network Commands:\n\
-                    /neuralstats – show neural network statistics\n\
-                    /neuralstatus – show detailed neural network training status\n\
-                    /neuralreset – reset neural network model and training data\n\
-                    /neuralfeatures <message_id> – show neural network feature analysis\n\
-                    \n\
-                    Debug Commands:\n\
-                    /listmessages – list recent messages stored in Redis (for debugging)\n\
-                    /checkmessage <message_id> – check learning status of a specific message",
-                ).await?;
+                handle_help(bot.clone(), msg.clone()).await?;
             }
             AdminCommand::ManageFeatures => {
-                let redis_client =
-                    redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
-                let mut redis_conn = redis_client
-                    .get_connection()
-                    .expect("Failed to get Redis connection");
-
-                let key_moderated =
-                    format!("{}{}{}", key::ADMIN_PREFIX, chat_id.0, suffix::MODERATED_CHATS);
-                let moderated_chats: Vec<i64> =
-                    redis_conn.smembers(key_moderated).unwrap_or_else(|_| Vec::new());
-
-                // 2) Build one row-per-chat button: callback_data = "managefeat:<chat_id>"
-                let mut rows: Vec<Vec<InlineKeyboardButton>> = Vec::new();
-                for chat in moderated_chats.into_iter() {
-                    // You might want to fetch the chat's stored name for labeling:
-                    let chat_name: String = redis_conn
-                        .hget(format!("{}{}", key::TG_CHATS_PREFIX, chat), field::NAME)
-                        .unwrap_or_else(|_| chat.to_string());
-                    rows.push(vec![
-                        InlineKeyboardButton::callback(
-                            format!("Chat: {}", chat_name),
-                            format!("managefeat:{}", chat),
-                        ),
-                    ]);
-                }
-                // If the admin has no moderated chats, send a simple message instead:
-                if rows.is_empty() {
-                    bot.send_message(chat_id, "You do not moderate any chats yet.")
-                        .await?;
-                } else {
-                    let keyboard = InlineKeyboardMarkup::new(rows);
-                    bot.send_message(chat_id, "Select a chat to manage features:")
-                        .reply_markup(keyboard)
-                        .await?;
-                }
+                handle_manage_features(bot.clone(), msg.clone()).await?;
             }
-            AdminCommand::Stats => {
-                let is_admin: bool = redis_conn
-                    .sismember(format!("{}{}", user_id, suffix::ADMIN_CHATS), chat_id.0)
-                    .expect("Failed to get admin chat");
-                if !is_admin {
-                    let stats: HashMap<String, String> = redis_conn
-                        .hgetall(format!("{}{}", key::TG_CHATS_PREFIX, chat_id.0))
-                        .expect("Failed to get chat stats");
-                    let mut response = String::new();
-                    for (field, value) in stats {
-                        if field == field::NAME || field == field::ADMIN_CHAT {
-                            continue;
-                        }
-                        writeln!(&mut response, "{}: {}", field, value).unwrap();
-                    }
-                    bot.send_message(chat_id, response).await?;
-                } else {
-                    let chats: Vec<i64> = redis_conn
-                        .smembers(format!("admin:{}:moderated_chats", chat_id.0))
-                        .expect("Failed to get moderated chats");
-                    let mut rows: Vec<Vec<InlineKeyboardButton>> = Vec::new();
-                    for chat in chats {
-                        let chat_name: String = redis_conn
-                            .hget(format!("{}{}", key::TG_CHATS_PREFIX, chat), field::NAME)
-                            .expect("Failed to get chat name");
-                        rows.push(vec![InlineKeyboardButton::callback(
-                            format!("Chat: {}", chat_name),
-                            format!("stats:{}", chat),
-                        )]);
-                    }
-                    let keyboard = InlineKeyboardMarkup::new(rows);
-                    bot.send_message(
-                        chat_id,
-                        "Which chat's stats do you want to see:",
-                    )
-                    .reply_markup(keyboard)
-                    .await?;
-                }
+            AdminCommand::TestMessage { text } => {
+                handle_test_message(bot.clone(), msg.clone(), text).await?;
+            }
+            AdminCommand::SetThreshold { args } => {
+                handle_set_threshold(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::SetAction { args } => {
+                handle_set_action(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::SetJoinWindow { args } => {
+                handle_set_join_window(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::SetFlood { args } => {
+                handle_set_flood(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::SetBanRate { args } => {
+                handle_set_ban_rate(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::SetAdaptive { args } => {
+                handle_set_adaptive(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::PermBanAction { args } => {
+                handle_perm_ban_action(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::Forwards { args } => {
+                handle_forwards(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::Lockdown { args } => {
+                handle_lockdown(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::AllowScript { args } => {
+                handle_allow_script(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::Stats { args } if args.trim() == stats::GLOBAL_FLAG => {
+                handle_global_stats(bot, msg).await?;
+            }
+            AdminCommand::Stats { .. } => {
+                handle_stats(bot.clone(), msg.clone(), user_id).await?;
+            }
+            AdminCommand::SymbolStats { chat } => {
+                handle_symbol_stats(bot.clone(), msg.clone(), chat).await?;
+            }
+            AdminCommand::FeatureStatus { chat } => {
+                handle_feature_status(bot.clone(), msg.clone(), chat).await?;
+            }
+            AdminCommand::BanList { args } => {
+                handle_ban_list(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::WorstUsers { n } => {
+                handle_worst_users(bot, msg, n).await?;
+            }
+            AdminCommand::MuteList { args } => {
+                handle_mute_list(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::Mute { args } => {
+                handle_mute(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::Unmute { args } => {
+                handle_unmute(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::Trend { args } => {
+                handle_trend(bot.clone(), msg.clone(), args).await?;
             }
             AdminCommand::Reputation { user } => {
-                let key = format!("{}{}", key::TG_USERS_PREFIX, user);
-
-                let user_rep: RedisResult<i64> = redis_conn.hget(key.clone(), field::REP);
-
-                match user_rep {
-                    Ok(rep) => {
-                        bot.send_message(chat_id, format!("Reputation for {}: {}", user, rep))
-                            .await?;
-                    }
-                    Err(_) => {
-                        bot.send_message(chat_id, format!("Reputation for {}: 0", user))
-                            .await?;
-                    }
-                }
+                handle_reputation(bot.clone(), msg.clone(), user).await?;
+            }
+            AdminCommand::ReportSpam | AdminCommand::Appeal { .. } => unreachable!("handled before the admin check"),
+            AdminCommand::Purge { user } => {
+                handle_purge(bot.clone(), chat_id, user).await?;
+            }
+            AdminCommand::Health => {
+                handle_health(bot.clone(), chat_id).await?;
+            }
+            AdminCommand::Diagnose { message_id } => {
+                handle_diagnose(bot.clone(), msg.clone(), message_id).await?;
+            }
+            AdminCommand::SimulateRaid { members } => {
+                handle_simulate_raid(bot.clone(), msg.clone(), members).await?;
+            }
+            AdminCommand::AddAdmin { args } => {
+                handle_add_admin(bot.clone(), msg.clone(), user_id, args).await?;
+            }
+            AdminCommand::RemoveAdmin { user } => {
+                handle_remove_admin(bot.clone(), msg.clone(), user_id, user).await?;
+            }
+            AdminCommand::SetPermissions { args } => {
+                handle_set_permissions(bot.clone(), msg.clone(), args).await?;
+            }
+            AdminCommand::ListAdmins => {
+                handle_list_admins(bot.clone(), msg.clone()).await?;
+            }
+            AdminCommand::MonitoredChats => {
+                handle_monitored_chats(bot.clone(), msg.clone()).await?;
+            }
+            AdminCommand::ExportAuditLog { hours } => {
+                handle_export_audit_log(bot.clone(), msg.clone(), hours).await?;
+            }
+            AdminCommand::EmergencyStop { mode } => {
+                handle_emergency_stop(bot.clone(), msg.clone(), mode).await?;
+            }
+            AdminCommand::ExportConfig => {
+                handle_export_config(bot.clone(), msg.clone()).await?;
+            }
+            AdminCommand::ImportConfig => {
+                handle_import_config(bot.clone(), msg.clone()).await?;
+            }
+            AdminCommand::SearchMessages { args } => {
+                handle_search_messages(bot.clone(), chat_id, args).await?;
+            }
+            AdminCommand::ResetChat { chat } => {
+                handle_reset_chat(bot.clone(), msg.clone(), user_id, chat).await?;
+            }
+            AdminCommand::Whois { user } => {
+                handle_whois(bot.clone(), msg.clone(), user).await?;
+            }
+            AdminCommand::Note { args } => {
+                handle_note(bot.clone(), msg.clone(), user_id, args).await?;
+            }
+            AdminCommand::Notes { user } => {
+                handle_notes(bot.clone(), msg.clone(), user).await?;
             }
             AdminCommand::AddRegex { pattern } => {
-                let parts: Vec<&str> = pattern.split('|').map(str::trim).collect();
-                if parts.len() != 3 {
-                    bot.send_message(chat_id, "Usage: /addregex symbol|pattern|score").await?;
-                    return Ok(());
-                }
-                let (symbol, regex_pattern, score) = (parts[0], parts[1], parts[2]);
-                
-                let lua_rule = format!(
-                    "config['regexp']['{}'] = {{
-                        re = '{}',
-                        score = {},
-                        condition = function(task)
-                            if task:get_header('Subject') then
-                                return true
-                            end
-                            return false
-                        end,
-                    }}\n",
-                    symbol, regex_pattern, score
-                );
-                
-                let path = format!("/etc/rspamd/lua.local.d/telegram_regex_{}.lua", symbol);
-                
-                let mut file = match OpenOptions::new().create(true).append(true).open(&path).await {
-                    Ok(f) => f,
-                    Err(e) => {
-                        bot.send_message(chat_id, format!("Failed to open file: {e}")).await?;
-                        return Ok(());
-                    }
-                };
-
-                let _ = restart_rspamd_async();
-                
-                if let Err(e) = file.write_all(lua_rule.as_bytes()).await {
-                    bot.send_message(chat_id, format!("Failed to write: {e}")).await?;
-                    return Ok(());
-                }
-
-                // Register the new symbol as a feature enabled by default
-                let _: redis::RedisResult<()> = redis_conn.sadd(ENABLED_FEATURES_KEY, symbol);
-
-                bot.send_message(chat_id, format!(
-                    "Added regex pattern: '{}' with symbol '{}' and score {}.\nPlease reload Rspamd to apply the rule.",
-                    regex_pattern, symbol, score
-                )).await?;
+                handle_add_regex(bot.clone(), msg.clone(), pattern).await?;
             }
             AdminCommand::Whitelist { pattern } => {
-                // Now expect exactly 3 parts: kind|action|target
-                let parts: Vec<&str> = pattern.split('|').map(str::trim).collect();
-                if parts.len() != 3 {
-                    bot.send_message(
-                        chat_id,
-                        "Usage: /whitelist <user|word>|<add|find>|<target>\n\
-                     - If add: target must be the literal user_id or word.\n\
-                     - If find: target can be '*' (list all),\n\
-                       or a plain string (SISMEMBER),\n\
-                       or a Rust‐regex (full regex syntax).",
-                    )
-                        .await?;
-                    return Ok(());
-                }
+                handle_whitelist(bot.clone(), msg.clone(), pattern).await?;
+            }
 
-                let (kind, action, target) = (parts[0], parts[1], parts[2]);
+            AdminCommand::ImportWhitelist { args } => {
+                handle_import_whitelist(bot.clone(), msg.clone(), args).await?;
+            }
 
-                // Dispatch to the shared helper with the correct Redis key
-                match kind {
-                    "user" => {
-                        process_set(
-                            &bot,
-                            chat_id,
-                            &mut redis_conn,
-                            key::TG_WHITELIST_USER_KEY,
-                            "user",
-                            "whitelist",
-                            action,
-                            target,
-                        )
-                            .await?;
-                    }
-                    "word" => {
-                        process_set(
-                            &bot,
-                            chat_id,
-                            &mut redis_conn,
-                            key::TG_WHITELIST_WORD_KEY,
-                            "word",
-                            "whitelist",
-                            action,
-                            target,
-                        )
-                            .await?;
-                    }
-                    _ => {
-                        bot.send_message(
-                            chat_id,
-                            "First part must be `user` or `word`. Usage: /whitelist <user|word>|<add|find>|<target>",
-                        )
-                            .await?;
-                    }
-                }
+            AdminCommand::WhitelistExport { chat } => {
+                handle_whitelist_export(bot.clone(), msg.clone(), chat).await?;
             }
 
             AdminCommand::Blacklist { pattern } => {
-                // Exactly the same parsing, but pass in the BLACKLIST key
-                let parts: Vec<&str> = pattern.split('|').map(str::trim).collect();
-                if parts.len() != 3 {
-                    bot.send_message(
-                        chat_id,
-                        "Usage: /blacklist <user|word>|<add|find>|<target>\n\
-                     - If add: target must be the literal user_id or word.\n\
-                     - If find: target can be '*' (list all),\n\
-                       or a plain string (SISMEMBER),\n\
-                       or a Rust‐regex (full regex syntax).",
-                    )
-                        .await?;
-                    return Ok(());
-                }
+                handle_blacklist(bot.clone(), msg.clone(), pattern).await?;
+            }
 
-                let (kind, action, target) = (parts[0], parts[1], parts[2]);
+            AdminCommand::BlacklistExport { chat } => {
+                handle_blacklist_export(bot.clone(), msg.clone(), chat).await?;
+            }
 
-                match kind {
-                    "user" => {
-                        process_set(
-                            &bot,
-                            chat_id,
-                            &mut redis_conn,
-                            key::TG_BLACKLIST_USER_KEY,
-                            "user",
-                            "blacklist",
-                            action,
-                            target,
-                        )
-                            .await?;
-                    }
-                    "word" => {
-                        process_set(
-                            &bot,
-                            chat_id,
-                            &mut redis_conn,
-                            key::TG_BLACKLIST_WORD_KEY,
-                            "word",
-                            "blacklist",
-                            action,
-                            target,
-                        )
-                            .await?;
-                    }
-                    _ => {
-                        bot.send_message(
-                            chat_id,
-                            "First part must be `user` or `word`. Usage: /blacklist <user|word>|<add|find>|<target>",
-                        )
-                            .await?;
-                    }
-                }
+            AdminCommand::TrustedDomain { pattern } => {
+                handle_trusted_domain(bot.clone(), msg.clone(), pattern).await?;
             }
 
-            AdminCommand::MarkTrusted { args } => {
-                // Parse args: "message_id|trust_type"
-                let parts: Vec<&str> = args.split('|').map(str::trim).collect();
-                if parts.len() != 2 {
-                    bot.send_message(
-                        chat_id,
-                        "Usage: /marktrusted <message_id>|<bot|admin|verified>\n\
-                     - message_id: the ID of the message to mark as trusted\n\
-                     - trust_type: bot, admin, or verified",
-                    )
-                        .await?;
-                    return Ok(());
-                }
+            AdminCommand::RiskyExt { pattern } => {
+                handle_risky_ext(bot.clone(), msg.clone(), pattern).await?;
+            }
+
+            AdminCommand::Shortener { pattern } => {
+                handle_shortener(bot.clone(), msg.clone(), pattern).await?;
+            }
 
-                let (message_id_str, trust_type_str) = (parts[0], parts[1]);
-                
-                // Parse message ID
-                let message_id = match message_id_str.parse::<i32>() {
-                    Ok(id) => MessageId(id),
-                    Err(_) => {
-                        bot.send_message(chat_id, "Invalid message ID. Must be a number.").await?;
-                        return Ok(());
-                    }
-                };
+            AdminCommand::ToggleSymbol { symbol } => {
+                handle_toggle_symbol(bot.clone(), msg.clone(), symbol).await?;
+            }
 
-                // Parse trust type
-                let trust_type = match trust_type_str {
-                    "bot" => TrustedMessageType::Bot,
-                    "admin" => TrustedMessageType::Admin,
-                    "verified" => TrustedMessageType::Verified,
-                    _ => {
-                        bot.send_message(
-                            chat_id,
-                            "Invalid trust type. Must be 'bot', 'admin', or 'verified'.",
-                        )
-                            .await?;
-                        return Ok(());
-                    }
-                };
+            AdminCommand::DomainRep { args } => {
+                handle_domain_rep(bot.clone(), msg.clone(), args).await?;
+            }
 
-                // Initialize trust manager
-                let trust_manager = match TrustManager::new("redis://127.0.0.1/") {
-                    Ok(tm) => tm,
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("Failed to initialize trust manager: {}", e),
-                        )
-                            .await?;
-                        return Ok(());
-                    }
-                };
+            AdminCommand::MarkTrusted { args } => {
+                handle_mark_trusted(bot.clone(), msg.clone(), user_id, args).await?;
+            }
 
-                // Create metadata for the trusted message
-                let metadata = TrustedMessageMetadata::new(
-                    message_id,
-                    chat_id,
-                    user_id,
-                    trust_type.clone(),
-                );
+            AdminCommand::TrustUser { user } => {
+                handle_trust_user(bot.clone(), msg.clone(), user).await?;
+            }
 
-                // Mark the message as trusted
-                match trust_manager.mark_trusted(metadata).await {
-                    Ok(_) => {
-                        bot.send_message(
-                            chat_id,
-                            format!(
-                                "Message {} marked as trusted ({}) for reply-aware filtering.",
-                                message_id.0,
-                                trust_type.as_str()
-                            ),
-                        )
-                            .await?;
-                    }
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("Failed to mark message as trusted: {}", e),
-                        )
-                            .await?;
-                    }
-                }
+            AdminCommand::UntrustUser { user } => {
+                handle_untrust_user(bot.clone(), msg.clone(), user).await?;
             }
 
             AdminCommand::TrustStats => {
-                let trust_manager = TrustManager::new("redis://127.0.0.1/")
-                    .expect("Failed to create trust manager");
-                
-                match trust_manager.get_stats().await {
-                    Ok(stats) => {
-                        bot.send_message(
-                            chat_id,
-                            format!(
-                                "Trust Management Statistics:\n\
-                                • Trusted messages: {}\n\
-                                • Reply tracking entries: {}\n\
-                                • Rate limiting enabled: {}\n\
-                                • Anti-evasion enabled: {}\n\
-                                • Selective trusting enabled: {}",
-                                stats.trusted_messages,
-                                stats.reply_tracking,
-                                reply_aware::ENABLE_RATE_LIMITING,
-                                reply_aware::ENABLE_ANTI_EVASION,
-                                reply_aware::ENABLE_SELECTIVE_TRUSTING
-                            ),
-                        ).await?;
-                    }
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("Failed to get trust statistics: {}", e),
-                        ).await?;
-                    }
-                }
+                handle_trust_stats(bot.clone(), msg.clone()).await?;
             }
             
             AdminCommand::ReplyConfig { args } => {
-                let parts: Vec<&str> = args.split('|').collect();
-                if parts.len() != 2 {
-                    bot.send_message(
-                        chat_id,
-                        "Usage: /replyconfig <setting>|<value>\n\
-                        Settings: rate_limit, anti_evasion, selective_trust, max_reduction, min_spam_score",
-                    ).await?;
-                    return Ok(());
-                }
-                
-                let setting = parts[0];
-                let value = parts[1];
-                
-                match setting {
-                    "rate_limit" => {
-                        let enabled = value.parse::<bool>().unwrap_or(true);
-                        bot.send_message(
-                            chat_id,
-                            format!("Rate limiting for reply-aware filtering: {}", enabled),
-                        ).await?;
-                    }
-                    "anti_evasion" => {
-                        let enabled = value.parse::<bool>().unwrap_or(true);
-                        bot.send_message(
-                            chat_id,
-                            format!("Anti-evasion measures: {}", enabled),
-                        ).await?;
-                    }
-                    "selective_trust" => {
-                        let enabled = value.parse::<bool>().unwrap_or(true);
-                        bot.send_message(
-                            chat_id,
-                            format!("Selective trusting: {}", enabled),
-                        ).await?;
-                    }
-                    "max_reduction" => {
-                        let reduction = value.parse::<f64>().unwrap_or(-5.0);
-                        bot.send_message(
-                            chat_id,
-                            format!("Maximum score reduction: {}", reduction),
-                        ).await?;
-                    }
-                    "min_spam_score" => {
-                        let score = value.parse::<f64>().unwrap_or(1.0);
-                        bot.send_message(
-                            chat_id,
-                            format!("Minimum spam score in replies: {}", score),
-                        ).await?;
-                    }
-                    _ => {
-                        bot.send_message(
-                            chat_id,
-                            "Invalid setting. Use: rate_limit, anti_evasion, selective_trust, max_reduction, min_spam_score",
-                        ).await?;
-                    }
-                }
+                handle_reply_config(bot.clone(), msg.clone(), args).await?;
             }
             
             AdminCommand::RateLimitStats => {
-                let mut conn = redis_client.get_connection().expect("Failed to get Redis connection");
-                
-                // Count rate limiting entries
-                let trusted_rate_pattern = format!("{}*", rate_limit::TRUSTED_MESSAGE_RATE_PREFIX);
-                let reply_rate_pattern = format!("{}*", rate_limit::REPLY_RATE_PREFIX);
-                
-                let trusted_rate_keys: Vec<String> = conn.keys(&trusted_rate_pattern).unwrap_or_default();
-                let reply_rate_keys: Vec<String> = conn.keys(&reply_rate_pattern).unwrap_or_default();
-                
-                let mut trusted_rate_count = 0;
-                let mut reply_rate_count = 0;
-                
-                for key in &trusted_rate_keys {
-                    let count: u32 = conn.get(key).unwrap_or(0);
-                    trusted_rate_count += count;
-                }
-                
-                for key in &reply_rate_keys {
-                    let count: u32 = conn.get(key).unwrap_or(0);
-                    reply_rate_count += count;
-                }
-                
-                bot.send_message(
-                    chat_id,
-                    format!(
-                        "Rate Limiting Statistics:\n\
-                        • Trusted message rate limits: {} ({} total)\n\
-                        • Reply rate limits: {} ({} total)\n\
-                        • Max trusted messages per hour: {}\n\
-                        • Max replies per hour: {}",
-                        trusted_rate_keys.len(),
-                        trusted_rate_count,
-                        reply_rate_keys.len(),
-                        reply_rate_count,
-                        reply_aware::MAX_TRUSTED_MESSAGES_PER_HOUR,
-                        reply_aware::MAX_REPLIES_PER_HOUR
-                    ),
-                ).await?;
+                handle_rate_limit_stats(bot.clone(), msg.clone()).await?;
             }
             
             AdminCommand::ResetRateLimit { user } => {
-                let mut conn = redis_client.get_connection().expect("Failed to get Redis connection");
-                
-                let trusted_rate_key = format!("{}{}", rate_limit::TRUSTED_MESSAGE_RATE_PREFIX, user);
-                let reply_rate_key = format!("{}{}", rate_limit::REPLY_RATE_PREFIX, user);
-                
-                let _: () = conn.del(&trusted_rate_key).unwrap_or(());
-                let _: () = conn.del(&reply_rate_key).unwrap_or(());
-                
-                bot.send_message(
-                    chat_id,
-                    format!("Rate limiting reset for user: {}", user),
-                ).await?;
+                handle_reset_rate_limit(bot.clone(), msg.clone(), user).await?;
             }
             
             AdminCommand::SpamPatterns { user } => {
-                let trust_manager = TrustManager::new("redis://127.0.0.1/")
-                    .expect("Failed to create trust manager");
-                
-                let user_id = user.parse::<u64>().unwrap_or(0);
-                match trust_manager.get_spam_patterns(UserId(user_id)).await {
-                    Ok(patterns) => {
-                        if patterns.is_empty() {
-                            bot.send_message(
-                                chat_id,
-                                format!("No spam patterns found for user: {}", user),
-                            ).await?;
-                        } else {
-                            bot.send_message(
-                                chat_id,
-                                format!(
-                                    "Spam patterns for user {}:\n{}",
-                                    user,
-                                    patterns.join("\n")
-                                ),
-                            ).await?;
-                        }
-                    }
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("Failed to get spam patterns: {}", e),
-                        ).await?;
-                    }
-                }
+                handle_spam_patterns(bot.clone(), msg.clone(), user).await?;
             }
             
             AdminCommand::SelectiveTrust { args } => {
-                let parts: Vec<&str> = args.split('|').collect();
-                if parts.len() != 2 {
-                    bot.send_message(
-                        chat_id,
-                        "Usage: /selectivetrust <rule>|<true|false>\n\
-                        Rules: trust_bot, trust_admin, trust_verified, trust_good_reputation, trust_recent_only",
-                    ).await?;
-                    return Ok(());
-                }
-                
-                let rule = parts[0];
-                let enabled = parts[1].parse::<bool>().unwrap_or(true);
-                
-                match rule {
-                    "trust_bot" => {
-                        bot.send_message(
-                            chat_id,
-                            format!("Trust bot messages: {}", enabled),
-                        ).await?;
-                    }
-                    "trust_admin" => {
-                        bot.send_message(
-                            chat_id,
-                            format!("Trust admin messages: {}", enabled),
-                        ).await?;
-                    }
-                    "trust_verified" => {
-                        bot.send_message(
-                            chat_id,
-                            format!("Trust verified user messages: {}", enabled),
-                        ).await?;
-                    }
-                    "trust_good_reputation" => {
-                        bot.send_message(
-                            chat_id,
-                            format!("Trust messages from users with good reputation: {}", enabled),
-                        ).await?;
-                    }
-                    "trust_recent_only" => {
-                        bot.send_message(
-                            chat_id,
-                            format!("Trust only recent messages: {}", enabled),
-                        ).await?;
-                    }
-                    _ => {
-                        bot.send_message(
-                            chat_id,
-                            "Invalid rule. Use: trust_bot, trust_admin, trust_verified, trust_good_reputation, trust_recent_only",
-                        ).await?;
-                    }
-                }
+                handle_selective_trust(bot.clone(), msg.clone(), args).await?;
             }
             
             AdminCommand::AntiEvasionStats => {
-                let mut conn = redis_client.get_connection().expect("Failed to get Redis connection");
-                
-                // Count spam pattern entries
-                let spam_pattern_prefix = format!("{}*", rate_limit::SPAM_PATTERN_PREFIX);
-                let spam_pattern_keys: Vec<String> = conn.keys(&spam_pattern_prefix).unwrap_or_default();
-                
-                let mut total_patterns = 0;
-                for key in &spam_pattern_keys {
-                    let patterns: Vec<String> = conn.smembers(key).unwrap_or_default();
-                    total_patterns += patterns.len();
-                }
-                
-                bot.send_message(
-                    chat_id,
-                    format!(
-                        "Anti-Evasion Statistics:\n\
-                        • Users with spam patterns: {}\n\
-                        • Total spam patterns tracked: {}\n\
-                        • Max links in reply: {}\n\
-                        • Max phone numbers in reply: {}\n\
-                        • Max invite links in reply: {}\n\
-                        • Max caps ratio in reply: {}\n\
-                        • Max emoji in reply: {}",
-                        spam_pattern_keys.len(),
-                        total_patterns,
-                        reply_aware::anti_evasion::MAX_LINKS_IN_REPLY,
-                        reply_aware::anti_evasion::MAX_PHONE_NUMBERS_IN_REPLY,
-                        reply_aware::anti_evasion::MAX_INVITE_LINKS_IN_REPLY,
-                        reply_aware::anti_evasion::MAX_CAPS_RATIO_IN_REPLY,
-                        reply_aware::anti_evasion::MAX_EMOJI_IN_REPLY
-                    ),
-                ).await?;
+                handle_anti_evasion_stats(bot.clone(), msg.clone()).await?;
             }
 
             AdminCommand::LearnSpam { message_id } => {
-                let bayes_manager = match BayesManager::new() {
-                    Ok(manager) => manager,
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to create Bayes manager: {}", e)
-                        ).await?;
-                        return Ok(());
-                    }
-                };
-                
-                // Get message content from Redis
-                let content = match get_message_content(&mut redis_conn, &message_id).await {
-                    Ok(content) => content,
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to get message content: {}", e)
-                        ).await?;
-                        return Ok(());
-                    }
-                };
-                
-                // Validate content before learning
-                if let Err(e) = bayes_manager.validate_content_for_learning(&message_id, &content) {
-                    bot.send_message(
-                        chat_id,
-                        format!("❌ Validation failed: {}", e)
-                    ).await?;
-                    return Ok(());
-                }
-                
-                match bayes_manager.learn_spam(&message_id, &content).await {
-                    Ok(()) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("✅ Message {} learned as spam", message_id)
-                        ).await?;
-                    }
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to learn as spam: {}", e)
-                        ).await?;
-                    }
-                }
+                handle_learn_spam(bot.clone(), msg.clone(), message_id).await?;
             }
             
             AdminCommand::LearnHam { message_id } => {
-                let bayes_manager = match BayesManager::new() {
-                    Ok(manager) => manager,
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to create Bayes manager: {}", e)
-                        ).await?;
-                        return Ok(());
-                    }
-                };
-                
-                let content = match get_message_content(&mut redis_conn, &message_id).await {
-                    Ok(content) => content,
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to get message content: {}", e)
-                        ).await?;
-                        return Ok(());
-                    }
-                };
-                
-                // Validate content before learning
-                if let Err(e) = bayes_manager.validate_content_for_learning(&message_id, &content) {
-                    bot.send_message(
-                        chat_id,
-                        format!("❌ Validation failed: {}", e)
-                    ).await?;
-                    return Ok(());
-                }
-                
-                match bayes_manager.learn_ham(&message_id, &content).await {
-                    Ok(()) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("✅ Message {} learned as ham", message_id)
-                        ).await?;
-                    }
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to learn as ham: {}", e)
-                        ).await?;
-                    }
-                }
+                handle_learn_ham(bot.clone(), msg.clone(), message_id).await?;
             }
             
             AdminCommand::BayesStats => {
-                let bayes_manager = match BayesManager::new() {
-                    Ok(manager) => manager,
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to create Bayes manager: {}", e)
-                        ).await?;
-                        return Ok(());
-                    }
-                };
-                
-                match bayes_manager.get_detailed_info() {
-                    Ok(info) => {
-                        let status = if info.get("is_ready").unwrap_or(&"false".to_string()) == "true" { 
-                            "✅ Ready" 
-                        } else { 
-                            "⏳ Training" 
-                        };
-                        
-                        let response = format!(
-                            "🤖 Bayes Classifier Status: {}\n\n\
-                             📊 Statistics:\n\
-                             • Spam tokens: {}\n\
-                             • Ham tokens: {}\n\
-                             • Spam messages: {}\n\
-                             • Ham messages: {}\n\
-                             • Total messages: {}\n\
-                             • Spam ratio: {}%\n\n\
-                             📈 Progress:\n\
-                             • Spam progress: {}%\n\
-                             • Ham progress: {}%\n\
-                             • Min required: {} spam, {} ham",
-                            status,
-                            info.get("spam_tokens").unwrap_or(&"0".to_string()),
-                            info.get("ham_tokens").unwrap_or(&"0".to_string()),
-                            info.get("spam_messages").unwrap_or(&"0".to_string()),
-                            info.get("ham_messages").unwrap_or(&"0".to_string()),
-                            info.get("total_messages").unwrap_or(&"0".to_string()),
-                            info.get("spam_ratio_percent").unwrap_or(&"0".to_string()),
-                            info.get("spam_progress_percent").unwrap_or(&"0".to_string()),
-                            info.get("ham_progress_percent").unwrap_or(&"0".to_string()),
-                            info.get("min_spam_required").unwrap_or(&"200".to_string()),
-                            info.get("min_ham_required").unwrap_or(&"200".to_string())
-                        );
-                        
-                        bot.send_message(chat_id, response).await?;
-                    }
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to get Bayes stats: {}", e)
-                        ).await?;
-                    }
-                }
+                handle_bayes_stats(bot.clone(), msg.clone()).await?;
             }
             
             AdminCommand::BayesReset => {
-                let bayes_manager = match BayesManager::new() {
-                    Ok(manager) => manager,
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to create Bayes manager: {}", e)
-                        ).await?;
-                        return Ok(());
-                    }
-                };
-                
-                match bayes_manager.reset_all_data() {
-                    Ok(()) => {
-                        bot.send_message(
-                            chat_id,
-                            "🗑️ Bayes classifier data has been reset. The classifier will need to be retrained."
-                        ).await?;
-                    }
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to reset Bayes data: {}", e)
-                        ).await?;
-                    }
-                }
+                handle_bayes_reset(bot.clone(), msg.clone()).await?;
+            }
+            
+            AdminCommand::FuzzyAdd { message_id } => {
+                handle_fuzzy_add(bot.clone(), msg.clone(), message_id).await?;
+            }
+            
+            AdminCommand::FuzzyDel { message_id } => {
+                handle_fuzzy_del(bot.clone(), msg.clone(), message_id).await?;
             }
             
             AdminCommand::NeuralStats => {
@@ -1092,6 +321,15 @@ pub async fn handle_admin_command(bot: Bot, msg: Message, cmd: AdminCommand) ->
                 }
             }
             
+            AdminCommand::NeuralTrain => {
+                if let Err(e) = handle_neural_train(bot.clone(), chat_id).await {
+                    bot.send_message(
+                        chat_id,
+                        format!("❌ Failed to train neural network: {}", e)
+                    ).await?;
+                }
+            }
+            
             AdminCommand::NeuralStatus => {
                 if let Err(e) = handle_neural_status(bot.clone(), chat_id).await {
                     bot.send_message(
@@ -1111,122 +349,11 @@ pub async fn handle_admin_command(bot: Bot, msg: Message, cmd: AdminCommand) ->
             }
             
             AdminCommand::ListMessages => {
-                // Get all message keys from Redis
-                let keys: Vec<String> = match redis_conn.keys("tg:message:*") {
-                    Ok(keys) => keys,
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to get message keys: {}", e)
-                        ).await?;
-                        return Ok(());
-                    }
-                };
-                
-                if keys.is_empty() {
-                    bot.send_message(
-                        chat_id,
-                        "📝 No messages found in Redis storage. Messages are stored for 24 hours after processing."
-                    ).await?;
-                    return Ok(());
-                }
-                
-                // Get the 10 most recent messages
-                let mut message_list = Vec::new();
-                for key in keys.iter().take(10) {
-                    if let Some(message_id) = key.strip_prefix("tg:message:") {
-                        if let Ok(content) = redis_conn.get::<_, String>(key) {
-                            let preview = if content.len() > 50 {
-                                format!("{}...", &content[..50])
-                            } else {
-                                content
-                            };
-                            message_list.push(format!("ID: {} | Content: {}", message_id, preview));
-                        }
-                    }
-                }
-                
-                let response = if message_list.is_empty() {
-                    "📝 No message content found in Redis storage.".to_string()
-                } else {
-                    format!(
-                        "📝 Recent messages in Redis storage (showing up to 10):\n\n{}",
-                        message_list.join("\n\n")
-                    )
-                };
-                
-                bot.send_message(chat_id, response).await?;
+                handle_list_messages(bot.clone(), msg.clone()).await?;
             }
 
             AdminCommand::CheckMessage { message_id } => {
-                let bayes_manager = match BayesManager::new() {
-                    Ok(manager) => manager,
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to create Bayes manager: {}", e)
-                        ).await?;
-                        return Ok(());
-                    }
-                };
-                
-                // Check if message exists in Redis
-                let key = format!("tg:message:{}", message_id);
-                let content_exists: bool = match redis_conn.exists(&key) {
-                    Ok(exists) => exists,
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to check message existence: {}", e)
-                        ).await?;
-                        return Ok(());
-                    }
-                };
-                
-                // Check learning status
-                let learning_status = match bayes_manager.get_message_learning_type(&message_id) {
-                    Ok(status) => status,
-                    Err(e) => {
-                        bot.send_message(
-                            chat_id,
-                            format!("❌ Failed to check learning status: {}", e)
-                        ).await?;
-                        return Ok(());
-                    }
-                };
-                
-                let mut response = format!("📋 Message Status for ID: {}\n\n", message_id);
-                
-                if content_exists {
-                    response.push_str("✅ Message content found in Redis\n");
-                    
-                    // Get content preview
-                    if let Ok(content) = redis_conn.get::<_, String>(&key) {
-                        let preview = if content.len() > 100 {
-                            format!("{}...", &content[..100])
-                        } else {
-                            content
-                        };
-                        response.push_str(&format!("📝 Content preview: {}\n", preview));
-                    }
-                } else {
-                    response.push_str("❌ Message content not found in Redis\n");
-                    response.push_str("💡 Message may have expired (24h TTL) or was never stored\n");
-                }
-                
-                match learning_status {
-                    Some(learned_as) => {
-                        response.push_str(&format!("🎯 Learning status: Already learned as {}\n", learned_as));
-                    }
-                    None => {
-                        response.push_str("🎯 Learning status: Not learned yet\n");
-                        if content_exists {
-                            response.push_str("💡 You can use /learnspam or /learnham to learn this message\n");
-                        }
-                    }
-                }
-                
-                bot.send_message(chat_id, response).await?;
+                handle_check_message(bot.clone(), msg.clone(), message_id).await?;
             }
 
         }
@@ -1246,10 +373,9 @@ pub async fn handle_admin_command(bot: Bot, msg: Message, cmd: AdminCommand) ->
 /// # Returns
 /// 
 /// A `Result<String>` containing the message content or an error
-async fn get_message_content(redis_conn: &mut redis::Connection, message_id: &str) -> Result<String> {
+pub async fn get_message_content(redis_conn: &mut redis::Connection, message_id: &str) -> Result<String> {
     // Try to get message content from Redis
-    let key = format!("tg:message:{}", message_id);
-    let content: Option<String> = redis_conn.get(&key)?;
+    let content = stored_message_content(redis_conn, message_id)?;
     
     if let Some(content) = content {
         if content.is_empty() {
@@ -1283,3 +409,197 @@ async fn restart_rspamd_async() -> Result<()> {
         Err(anyhow::anyhow!("rspamd restart failed: {}", stderr))
     }
 }
+
+/// Handles the /makeadmin command: makes this chat the admin chat for the chats the user moderates
+pub async fn handle_make_admin(bot: Bot, msg: Message, user_id: UserId) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let _: () = redis_conn
+        .sadd(key::ns(format!("{}{}", user_id, suffix::ADMIN_CHATS)), chat_id.0)
+        .expect("Failed to add chat to admin_chats");
+    
+    let bot_chats: Vec<i64> = redis_conn
+        .smembers(key::ns(format!("{}{}", user_id, suffix::BOT_CHATS)))
+        .unwrap_or_else(|_| Vec::new());
+
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = Vec::new();
+    for chat in bot_chats {
+        if chat == chat_id.0 { continue; }
+        let chat_name: String = redis_conn
+            .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat), field::NAME)
+            .expect("Failed to get chat name");
+        rows.push(vec![InlineKeyboardButton::callback(
+            format!("Chat: {}", chat_name),
+            format!("makeadmin:{}", chat),
+        )]);
+    }
+    let keyboard = InlineKeyboardMarkup::new(rows);
+
+    bot.send_message(
+        chat_id,
+        "Admin chat registered! Please select chats to moderate:",
+    )
+    .reply_markup(keyboard)
+    .await?;
+    Ok(())
+}
+
+/// Handles the /help command: lists every admin command
+pub async fn handle_help(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    bot.send_message(
+        chat_id,
+        "Commands:\n\
+        /help – show help for commands\n\
+        Long commands have short forms, e.g. /aes for /antievasionstats or /wu for /worstusers; type / to see them all\n\
+        /makeadmin – register current chat as admin control chat\n\
+        /reputation <username> – show user's reputation\n\
+        /whois <user_id> – show everything known about a user\n\
+        /note <user_id|@username>|<text> – leave a timestamped note on a user for the other moderators\n\
+        /notes <user_id|@username> – list the notes left on a user\n\
+        /reportspam – (reply) report a message as spam; available to all members\n\
+        /appeal <reason> – (private chat) banned users ask the admins to lift their ban\n\
+        /purge <user_id|@username> – delete the user's recent messages in this chat\n\
+        /resetchat [chat_id] – delete all bot state for a chat (default: this chat) after you confirm\n\
+        /addregex <symbol|pattern|score> – add regex rule to rspamd\n\
+        /stats [--global] – show stats, or totals and the busiest chats across every moderated chat\n\
+        /health – check Redis, Rspamd and the Bayes and neural classifiers\n\
+        /symbolstats [chat_id] – show the most triggered symbols for a chat\n\
+        /featurestatus [chat_id] – show which features are on in a chat and whether that's the global default or a chat override\n\
+        /banlist [chat_id][|<page>] – list the users currently banned in a chat\n\
+        /mutelist [chat_id] – list the users currently muted in a chat\n\
+        /worstusers [n] – list the n users with the highest reputation across all chats (default: 10)\n\
+        /mute <user_id|@username>|<minutes>[|<chat_id>] – make a user read-only in a chat (default: this chat)\n\
+        /unmute <user_id|@username>[|<chat_id>] – lift a user's mute (default: the chat they were muted in)\n\
+        /trend [chat_id][|<days>] – show daily spam actions in a chat over the last days\n\
+        /testmessage <text> – show which symbols the text triggers without posting it\n\
+        /whitelist <user|word>|<add|find>|<target>[|<chat_id>] – without a chat_id the entry is global\n\
+        /importwhitelist <user|word>[|<chat_id>] – reply to a file with one entry per line to whitelist them all\n\
+        /whitelistexport [chat_id] – send the whitelisted users and words as a file (default: the global lists)\n\
+        /blacklist <user|word>|<add|find>|<target>[|<chat_id>] – without a chat_id the entry is global\n\
+        /blacklistexport [chat_id] – send the blacklisted users and words as a file (default: the global lists)\n\
+        /trusteddomain <add|find|remove>|<domain> – manage domains exempt from link spam checks\n\
+        /riskyext <add|find|remove>|<extension> – manage the document types flagged by TG_ATTACHMENT_SPAM\n\
+        /shortener <add|find|remove>|<domain> – manage the URL shorteners flagged by TG_SHORTENER\n\
+        /togglesymbol [symbol] – switch a detection symbol (e.g. TG_GIBBERISH) on or off for every chat without its own setting\n\
+        /domainrep <domain>[|<delta>] – show or adjust a domain's reputation (scores TG_URL_REPUTATION)\n\
+        /setthreshold <name>|<value> – set a detection threshold or reputation gate (suspicious_rep, ban_rep, ban_rep_penalty, perm_ban_bans, reputation_decay_rate, reputation_decay_floor, suspicious_decay_step)\n\
+        /allowscript <chat_id>|<script> – allow a script in a chat (empty list allows all)\n\
+        /setaction <chat_id>|<threshold>|<warn|delete|mute|ban>[|<minutes>] – set the score that triggers an action in a chat; minutes sets how long tg_mute lasts\n\
+        /setjoinwindow <chat_id>|<first_fast|first_slow|probation|new_user_link|new_user_forward>|<seconds> – set a chat's join timing windows\n\
+        /setflood <chat_id>|<messages|default> – set how many messages per window trigger TG_FLOOD in a chat\n\
+        /setbanrate <chat_id>|<bans|default> – cap automated bans per minute in a chat; bans past it only delete and alert\n\
+        /setadaptive <chat_id>|<min>|<max> – bound how far the adaptive_thresholds feature scales a chat's flood and repeat limits (default: 0.5 to 3)\n\
+        /permbanaction <ban|kick|report|default>[|<chat_id>] – what happens to users hitting TG_PERM_BAN in a chat (default: ban)\n\
+        /forwards <allow|restrict_new|deny|default>[|<chat_id>] – which forwarded messages a chat accepts (default: allow)\n\
+        /lockdown <on|off|status>[|<chat_id>] – mute every new member of a chat until lifted (raids turn it on)\n\
+        /marktrusted <message_id>|<bot|admin|verified> – mark message as trusted for reply-aware filtering\n\
+        /truststats – show trust management statistics\n\
+        /trustuser <user_id|@username>[|<hours>] – trust all future messages from a user\n\
+        /untrustuser <user_id|@username> – stop trusting a user\n\
+        \n\
+        Advanced Reply-Aware Filtering Commands:\n\
+        /replyconfig <setting>|<value> – configure reply-aware filtering settings\n\
+        /ratelimitstats – show rate limiting statistics\n\
+        /resetratelimit <user> – reset rate limiting for a user\n\
+        /spampatterns <user> – show spam pattern history for a user\n\
+        /selectivetrust <rule>|<true|false> – configure selective trusting rules\n\
+        /antievasionstats – show anti-evasion statistics\n\
+        \n\
+        Bayesian Learning Commands:\n\
+        /learnspam <message_id> – learn a message as spam for Bayesian classifier\n\
+        /learnham <message_id> – learn a message as ham for Bayesian classifier\n\
+        /bayesstats – show Bayesian classifier statistics\n\
+        /bayesreset – reset all Bayesian classifier data\n\
+        \n\
+        Fuzzy Storage Commands:\n\
+        /fuzzyadd <message_id>|[flag]|[weight] – add a message to fuzzy storage\n\
+        /fuzzydel <message_id>|[flag] – remove a message from fuzzy storage\n\
+        \n\
+        Neural Network Commands:\n\
+        /neuralstats – show neural network statistics\n\
+        /neuralstatus – show detailed neural network training status\n\
+        /neuralreset – reset neural network model and training data\n\
+        /neuraltrain – train the neural network on collected samples\n\
+        /neuralfeatures <message_id> – show neural network feature analysis\n\
+        \n\
+        Debug Commands:\n\
+        /listmessages – list recent messages stored in Redis (for debugging)\n\
+        /searchmessages <chat_id|all>|<hours>|<text or /regex/> – find stored messages containing a text or matching a regex\n\
+        /checkmessage <message_id> – check learning status of a specific message\n\
+        /diagnose <message_id> – re-scan a stored message and list its symbols, reductions, reputation delta and action\n\
+        /simulateraid [members] – join and post as synthetic members and report which raid defenses fired (staging chats only)\n\
+        \n\
+        Admin Panel Commands:\n\
+        /addadmin <user_id|@username>[|<viewer|moderator|manager|administrator>] – add a user to the admin panel (default: viewer)\n\
+        /removeadmin <user_id|@username> – remove a user from the admin panel\n\
+        /setpermissions <user_id|@username>|<permission,...> – replace a member's permissions, e.g. view_stats,manage_chats\n\
+        /listadmins – list the admin panel members and their permissions, with Prev/Next buttons\n\
+        /monitoredchats – list the chats moderated from this chat, with Prev/Next buttons\n\
+        /exportauditlog [hours] – send the admin panel audit log of the last hours as a JSON Lines file (default: 24)\n\
+        /emergencystop <on|off|status> – stop scanning and moderating messages in every chat, or resume\n\
+        /exportconfig – send the features, thresholds, lists and chat settings as a JSON file\n\
+        /importconfig – (reply) restore the configuration from an /exportconfig file",
+    ).await?;
+    Ok(())
+}
+
+/// Handles the /addregex command: writes a regex rule to the Rspamd config and restarts Rspamd
+pub async fn handle_add_regex(bot: Bot, msg: Message, pattern: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = pattern.split('|').map(str::trim).collect();
+    if parts.len() != 3 {
+        bot.send_message(chat_id, "Usage: /addregex symbol|pattern|score").await?;
+        return Ok(());
+    }
+    let (symbol, regex_pattern, score) = (parts[0], parts[1], parts[2]);
+    
+    let lua_rule = format!(
+        "config['regexp']['{}'] = {{
+            re = '{}',
+            score = {},
+            condition = function(task)
+                if task:get_header('Subject') then
+                    return true
+                end
+                return false
+            end,
+        }}\n",
+        symbol, regex_pattern, score
+    );
+    
+    let path = format!("/etc/rspamd/lua.local.d/telegram_regex_{}.lua", symbol);
+    
+    let mut file = match OpenOptions::new().create(true).append(true).open(&path).await {
+        Ok(f) => f,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Failed to open file: {e}")).await?;
+            return Ok(());
+        }
+    };
+
+    let _ = restart_rspamd_async();
+    
+    if let Err(e) = file.write_all(lua_rule.as_bytes()).await {
+        bot.send_message(chat_id, format!("Failed to write: {e}")).await?;
+        return Ok(());
+    }
+
+    // Register the new symbol as a feature enabled by default
+    let _: redis::RedisResult<()> = redis_conn.sadd(key::ns(ENABLED_FEATURES_KEY), symbol);
+
+    bot.send_message(chat_id, format!(
+        "Added regex pattern: '{}' with symbol '{}' and score {}.\nPlease reload Rspamd to apply the rule.",
+        regex_pattern, symbol, score
+    )).await?;
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use teloxide::prelude::*;
+use teloxide::types::{ChatMemberStatus, InlineKeyboardButton, InlineKeyboardMarkup};
+use crate::ban_manager::clear_ban;
+use crate::config::{appeal, field, key};
+use crate::notifications::{alert_enabled, Severity};
+use redis::{Commands, RedisResult};
+
+/// Prefix of the decision buttons' callback data: `appeal:<approve|reject>:<user_id>`
+pub const APPEAL_CALLBACK: &str = "appeal:";
+
+const PENDING: &str = "pending";
+const APPROVED: &str = "approved";
+const REJECTED: &str = "rejected";
+
+/// A banned user's request to have their ban reviewed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Appeal {
+    pub user_id: UserId,
+    /// Chat the user was banned in
+    pub chat_id: ChatId,
+    pub reason: String,
+    pub timestamp: i64,
+    /// `pending`, `approved` or `rejected`
+    pub status: String,
+}
+
+/// Result of a user's `/appeal`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AppealOutcome {
+    /// The user has no ban on record to appeal
+    NotBanned,
+    /// The user appealed recently; seconds until they may appeal again
+    CoolingDown(i64),
+    /// Appeal stored and waiting for an admin
+    Recorded(Appeal),
+}
+
+fn appeal_key(user_id: UserId) -> String {
+    format!("{}{}", key::ns(key::TG_APPEALS_PREFIX), user_id.0)
+}
+
+/// The latest appeal of `user_id`, if one is kept.
+pub fn get_appeal(conn: &mut redis::Connection, user_id: UserId) -> RedisResult<Option<Appeal>> {
+    let stored: HashMap<String, String> = conn.hgetall(appeal_key(user_id))?;
+    let chat_id = stored.get(appeal::CHAT_ID).and_then(|chat| chat.parse().ok());
+    let timestamp = stored.get(appeal::TIMESTAMP).and_then(|time| time.parse().ok());
+    let (Some(chat_id), Some(timestamp)) = (chat_id, timestamp) else {
+        return Ok(None);
+    };
+    Ok(Some(Appeal {
+        user_id,
+        chat_id: ChatId(chat_id),
+        reason: stored.get(appeal::REASON).cloned().unwrap_or_default(),
+        timestamp,
+        status: stored.get(appeal::STATUS).cloned().unwrap_or_else(|| PENDING.to_string()),
+    }))
+}
+
+/// Records `user_id`'s appeal against their current ban, made at `now`.
+///
+/// Only users with a ban on record (`banned_in` in their hash) can appeal, and
+/// at most once per `appeal::COOLDOWN_SECS`; a new appeal replaces the last one.
+pub fn record_appeal(conn: &mut redis::Connection, user_id: UserId, reason: &str, now: i64) -> RedisResult<AppealOutcome> {
+    let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user_id.0);
+    let banned_in: Option<i64> = conn.hget(&user_key, field::BANNED_IN)?;
+    let Some(chat_id) = banned_in else {
+        return Ok(AppealOutcome::NotBanned);
+    };
+    if let Some(last) = get_appeal(conn, user_id)? {
+        let wait = last.timestamp + appeal::COOLDOWN_SECS - now;
+        if wait > 0 {
+            return Ok(AppealOutcome::CoolingDown(wait));
+        }
+    }
+
+    let appeal = Appeal {
+        user_id,
+        chat_id: ChatId(chat_id),
+        reason: reason.trim().chars().take(appeal::MAX_REASON_CHARS).collect(),
+        timestamp: now,
+        status: PENDING.to_string(),
+    };
+    let appeal_key = appeal_key(user_id);
+    redis::pipe()
+        .del(&appeal_key).ignore()
+        .hset_multiple(&appeal_key, &[
+            (appeal::USER_ID, user_id.0.to_string()),
+            (appeal::CHAT_ID, chat_id.to_string()),
+            (appeal::REASON, appeal.reason.clone()),
+            (appeal::TIMESTAMP, now.to_string()),
+            (appeal::STATUS, PENDING.to_string()),
+        ]).ignore()
+        .expire(&appeal_key, appeal::TTL).ignore()
+        .query::<()>(conn)?;
+    Ok(AppealOutcome::Recorded(appeal))
+}
+
+/// Records `admin`'s decision on `user_id`'s pending appeal; approving also
+/// clears the user's ban. Returns the decided appeal, or `None` if there was
+/// no pending one.
+pub fn decide_appeal(conn: &mut redis::Connection, user_id: UserId, approve: bool, admin: UserId) -> RedisResult<Option<Appeal>> {
+    let Some(mut appeal) = get_appeal(conn, user_id)?.filter(|appeal| appeal.status == PENDING) else {
+        return Ok(None);
+    };
+    // Lift the ban first so a failure leaves the appeal pending for another try
+    if approve {
+        clear_ban(conn, user_id)?;
+    }
+    appeal.status = if approve { APPROVED } else { REJECTED }.to_string();
+    let _: () = conn.hset_multiple(
+        appeal_key(user_id),
+        &[(appeal::STATUS, appeal.status.clone()), (appeal::DECIDED_BY, admin.0.to_string())],
+    )?;
+    Ok(Some(appeal))
+}
+
+/// Handles the /appeal command, which banned users send to the bot in a private chat
+pub async fn handle_appeal(bot: Bot, msg: Message, reason: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    if !msg.chat.is_private() {
+        bot.send_message(chat_id, "Send /appeal to the bot in a private chat.").await?;
+        return Ok(());
+    }
+    if reason.trim().is_empty() {
+        bot.send_message(chat_id, "Usage: /appeal <why your ban should be lifted>").await?;
+        return Ok(());
+    }
+
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let outcome = match record_appeal(&mut redis_conn, user.id, &reason, chrono::Utc::now().timestamp()) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Failed to record your appeal: {}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    let appeal = match outcome {
+        AppealOutcome::NotBanned => {
+            bot.send_message(chat_id, "You have no ban on record to appeal.").await?;
+            return Ok(());
+        }
+        AppealOutcome::CoolingDown(wait) => {
+            bot.send_message(
+                chat_id,
+                format!("You appealed recently. You can appeal again in {} hour(s).", (wait + 3599) / 3600),
+            ).await?;
+            return Ok(());
+        }
+        AppealOutcome::Recorded(appeal) => appeal,
+    };
+    bot.send_message(chat_id, "Your appeal was sent to the admins. You'll hear back here once they decide.").await?;
+
+    let who = match user.username.as_deref() {
+        Some(username) => format!("@{}", username),
+        None => format!("user {}", user.id),
+    };
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Approve", format!("{}approve:{}", APPEAL_CALLBACK, user.id.0)),
+        InlineKeyboardButton::callback("Reject", format!("{}reject:{}", APPEAL_CALLBACK, user.id.0)),
+    ]]);
+    let notify_text = format!("Ban appeal from {} (banned in chat {}):\n{}", who, appeal.chat_id, appeal.reason);
+    let admin_chat: Option<i64> = redis_conn
+        .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), appeal.chat_id.0), field::ADMIN_CHAT)
+        .unwrap_or(None);
+    if alert_enabled(&mut redis_conn, Severity::Medium) {
+        let target = admin_chat.map(ChatId).unwrap_or(appeal.chat_id);
+        bot.send_message(target, notify_text).reply_markup(keyboard).await?;
+    }
+    Ok(())
+}
+
+/// Called when an appeal's Approve or Reject button is pressed
+pub async fn appeal_handler(bot: Bot, query: CallbackQuery) -> ResponseResult<()> {
+    let (Some(data), Some(callback_msg)) = (query.data.as_deref(), query.message.as_ref()) else {
+        return Ok(());
+    };
+    let parsed = data
+        .strip_prefix(APPEAL_CALLBACK)
+        .and_then(|rest| rest.split_once(':'))
+        .and_then(|(decision, user)| Some((decision == "approve", user.parse::<u64>().ok()?)));
+    let Some((approve, user_id)) = parsed else {
+        return Ok(());
+    };
+    let user_id = UserId(user_id);
+
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let Ok(Some(appeal)) = get_appeal(&mut redis_conn, user_id) else {
+        bot.answer_callback_query(query.id.clone()).text("This appeal has expired.").await?;
+        return Ok(());
+    };
+
+    // Only admins of the chat the user was banned in may decide
+    let is_admin = match bot.get_chat_member(appeal.chat_id, query.from.id).await {
+        Ok(member) => matches!(member.status(), ChatMemberStatus::Owner | ChatMemberStatus::Administrator),
+        Err(_) => false,
+    };
+    if !is_admin {
+        bot.answer_callback_query(query.id.clone())
+            .text("Only admins of the chat the user was banned in can decide this appeal.")
+            .await?;
+        return Ok(());
+    }
+
+    let reply = match decide_appeal(&mut redis_conn, user_id, approve, query.from.id) {
+        Ok(Some(appeal)) if approve => {
+            // The ban may only be on record; Telegram rejects unbanning a member
+            let _ = bot.unban_chat_member(appeal.chat_id, user_id).only_if_banned(true).await;
+            let _ = bot.send_message(ChatId(user_id.0 as i64), "Your appeal was approved and your ban was lifted.").await;
+            format!("Appeal of user {} approved; their ban in chat {} was lifted.", user_id, appeal.chat_id)
+        }
+        Ok(Some(_)) => {
+            let _ = bot.send_message(ChatId(user_id.0 as i64), "Your appeal was reviewed and rejected.").await;
+            format!("Appeal of user {} rejected.", user_id)
+        }
+        Ok(None) => format!("The appeal of user {} was already decided.", user_id),
+        Err(e) => format!("Failed to decide the appeal of user {}: {}", user_id, e),
+    };
+
+    bot.answer_callback_query(query.id.clone()).await?;
+    bot.edit_message_reply_markup(callback_msg.chat().id, callback_msg.id()).await?;
+    bot.send_message(callback_msg.chat().id, reply).await?;
+    Ok(())
+}
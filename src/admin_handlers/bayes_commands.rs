@@ -0,0 +1,294 @@
+use crate::admin_handlers::get_message_content;
+use crate::bayes_manager::{announce_bayes_ready, BayesManager};
+use crate::config::key;
+use redis::Commands;
+use teloxide::prelude::*;
+
+/// Handles the /learnspam command: teaches the Bayes classifier that a stored message is spam
+pub async fn handle_learn_spam(bot: Bot, msg: Message, message_id: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let bayes_manager = match BayesManager::new() {
+        Ok(manager) => manager,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to create Bayes manager: {}", e)
+            ).await?;
+            return Ok(());
+        }
+    };
+    
+    // Get message content from Redis
+    let content = match get_message_content(&mut redis_conn, &message_id).await {
+        Ok(content) => content,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to get message content: {}", e)
+            ).await?;
+            return Ok(());
+        }
+    };
+    
+    // Validate content before learning
+    if let Err(e) = bayes_manager.validate_content_for_learning(&message_id, &content) {
+        bot.send_message(
+            chat_id,
+            format!("❌ Validation failed: {}", e)
+        ).await?;
+        return Ok(());
+    }
+    
+    match bayes_manager.learn_spam(&message_id, &content).await {
+        Ok(()) => {
+            bot.send_message(
+                chat_id,
+                format!("✅ Message {} learned as spam", message_id)
+            ).await?;
+            if let Err(e) = announce_bayes_ready(&bot, &bayes_manager, chat_id).await {
+                tracing::warn!("Failed to announce that the classifier is ready: {}", e);
+            }
+        }
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to learn as spam: {}", e)
+            ).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /learnham command: teaches the Bayes classifier that a stored message is ham
+pub async fn handle_learn_ham(bot: Bot, msg: Message, message_id: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let bayes_manager = match BayesManager::new() {
+        Ok(manager) => manager,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to create Bayes manager: {}", e)
+            ).await?;
+            return Ok(());
+        }
+    };
+    
+    let content = match get_message_content(&mut redis_conn, &message_id).await {
+        Ok(content) => content,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to get message content: {}", e)
+            ).await?;
+            return Ok(());
+        }
+    };
+    
+    // Validate content before learning
+    if let Err(e) = bayes_manager.validate_content_for_learning(&message_id, &content) {
+        bot.send_message(
+            chat_id,
+            format!("❌ Validation failed: {}", e)
+        ).await?;
+        return Ok(());
+    }
+    
+    match bayes_manager.learn_ham(&message_id, &content).await {
+        Ok(()) => {
+            bot.send_message(
+                chat_id,
+                format!("✅ Message {} learned as ham", message_id)
+            ).await?;
+            if let Err(e) = announce_bayes_ready(&bot, &bayes_manager, chat_id).await {
+                tracing::warn!("Failed to announce that the classifier is ready: {}", e);
+            }
+        }
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to learn as ham: {}", e)
+            ).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /bayesstats command: shows the Bayes classifier's learning statistics
+pub async fn handle_bayes_stats(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let bayes_manager = match BayesManager::new() {
+        Ok(manager) => manager,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to create Bayes manager: {}", e)
+            ).await?;
+            return Ok(());
+        }
+    };
+    
+    match bayes_manager.get_detailed_info() {
+        Ok(info) => {
+            let status = if info.get("is_ready").unwrap_or(&"false".to_string()) == "true" { 
+                "✅ Ready" 
+            } else { 
+                "⏳ Training" 
+            };
+            
+            let response = format!(
+                "🤖 Bayes Classifier Status: {}\n\n\
+                 📊 Statistics:\n\
+                 • Spam tokens: {}\n\
+                 • Ham tokens: {}\n\
+                 • Spam messages: {}\n\
+                 • Ham messages: {}\n\
+                 • Total messages: {}\n\
+                 • Spam ratio: {}%\n\n\
+                 📈 Progress:\n\
+                 • Spam progress: {}%\n\
+                 • Ham progress: {}%\n\
+                 • Min required: {} spam, {} ham",
+                status,
+                info.get("spam_tokens").unwrap_or(&"0".to_string()),
+                info.get("ham_tokens").unwrap_or(&"0".to_string()),
+                info.get("spam_messages").unwrap_or(&"0".to_string()),
+                info.get("ham_messages").unwrap_or(&"0".to_string()),
+                info.get("total_messages").unwrap_or(&"0".to_string()),
+                info.get("spam_ratio_percent").unwrap_or(&"0".to_string()),
+                info.get("spam_progress_percent").unwrap_or(&"0".to_string()),
+                info.get("ham_progress_percent").unwrap_or(&"0".to_string()),
+                info.get("min_spam_required").unwrap_or(&"200".to_string()),
+                info.get("min_ham_required").unwrap_or(&"200".to_string())
+            );
+            
+            bot.send_message(chat_id, response).await?;
+        }
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to get Bayes stats: {}", e)
+            ).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /bayesreset command: clears everything the Bayes classifier has learned
+pub async fn handle_bayes_reset(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let bayes_manager = match BayesManager::new() {
+        Ok(manager) => manager,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to create Bayes manager: {}", e)
+            ).await?;
+            return Ok(());
+        }
+    };
+    
+    match bayes_manager.reset_all_data() {
+        Ok(()) => {
+            bot.send_message(
+                chat_id,
+                "🗑️ Bayes classifier data has been reset. The classifier will need to be retrained."
+            ).await?;
+        }
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to reset Bayes data: {}", e)
+            ).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /checkmessage command: shows whether a stored message was learned as spam or ham
+pub async fn handle_check_message(bot: Bot, msg: Message, message_id: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let bayes_manager = match BayesManager::new() {
+        Ok(manager) => manager,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to create Bayes manager: {}", e)
+            ).await?;
+            return Ok(());
+        }
+    };
+    
+    // Check if message exists in Redis
+    let key = format!("{}{}", key::ns(key::TG_MESSAGE_PREFIX), message_id);
+    let content_exists: bool = match redis_conn.exists(&key) {
+        Ok(exists) => exists,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to check message existence: {}", e)
+            ).await?;
+            return Ok(());
+        }
+    };
+    
+    // Check learning status
+    let learning_status = match bayes_manager.get_message_learning_type(&message_id) {
+        Ok(status) => status,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to check learning status: {}", e)
+            ).await?;
+            return Ok(());
+        }
+    };
+    
+    let mut response = format!("📋 Message Status for ID: {}\n\n", message_id);
+    
+    if content_exists {
+        response.push_str("✅ Message content found in Redis\n");
+        
+        // Get content preview
+        if let Ok(content) = redis_conn.get::<_, String>(&key) {
+            let preview = if content.len() > 100 {
+                format!("{}...", &content[..100])
+            } else {
+                content
+            };
+            response.push_str(&format!("📝 Content preview: {}\n", preview));
+        }
+    } else {
+        response.push_str("❌ Message content not found in Redis\n");
+        response.push_str("💡 Message may have expired (24h TTL) or was never stored\n");
+    }
+    
+    match learning_status {
+        Some(learned_as) => {
+            response.push_str(&format!("🎯 Learning status: Already learned as {}\n", learned_as));
+        }
+        None => {
+            response.push_str("🎯 Learning status: Not learned yet\n");
+            if content_exists {
+                response.push_str("💡 You can use /learnspam or /learnham to learn this message\n");
+            }
+        }
+    }
+    
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
@@ -0,0 +1,135 @@
+//! Admin-panel permissions behind the admin commands.
+//!
+//! Until the admin panel is set up, any admin of the chat may run every admin
+//! command. Once it is, a command also needs the panel permission it maps to
+//! in `required_permission`, so e.g. only panel members with `ConfigureBot`
+//! can reset Bayes data.
+//!
+//! In a private chat every user counts as an admin of the chat, so with no
+//! panel set up anyone may run admin commands in their DM with the bot.
+//! Commands acting on another chat therefore also check `moderates_chat`.
+
+use redis::{Commands, RedisResult};
+use teloxide::types::{ChatId, Message, UserId};
+
+use crate::admin_handlers::AdminCommand;
+use crate::admin_panel::config::key as panel_key;
+use crate::admin_panel::permissions::{AdminPermission, AdminUser};
+use crate::config::{key, suffix};
+
+/// Whether a user may run a command, according to the admin panel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandAccess {
+    /// The panel isn't set up or the command needs no permission. In a
+    /// private chat that leaves the command open to anyone.
+    Unrestricted,
+    /// The user holds the permission the command needs.
+    Granted,
+    /// The user isn't a panel member or lacks this permission.
+    Denied(AdminPermission),
+}
+
+/// Panel permission needed to run `cmd`, or `None` for commands open to
+/// every chat admin.
+pub fn required_permission(cmd: &AdminCommand) -> Option<AdminPermission> {
+    use AdminCommand::*;
+
+    match cmd {
+        Help | ReportSpam | Appeal { .. } => None,
+        // Bot-wide configuration and training data
+        AddRegex { .. } | Whitelist { .. } | ImportWhitelist { .. } | WhitelistExport { .. } | Blacklist { .. }
+        | BlacklistExport { .. } | TrustedDomain { .. } | RiskyExt { .. } | Shortener { .. } | ToggleSymbol { .. }
+        | DomainRep { .. } | SetThreshold { .. } | ReplyConfig { .. } | SelectiveTrust { .. } | ResetRateLimit { .. }
+        | LearnSpam { .. } | LearnHam { .. } | BayesReset | FuzzyAdd { .. } | FuzzyDel { .. } | NeuralReset
+        | NeuralTrain => Some(AdminPermission::ConfigureBot),
+        // Moderation and settings of single chats
+        Mute { .. } | Unmute { .. } | Note { .. } | Purge { .. } | MakeAdmin | ResetChat { .. } | ManageFeatures | AllowScript { .. }
+        | SetAction { .. } | SetJoinWindow { .. } | SetFlood { .. } | SetBanRate { .. } | SetAdaptive { .. } | PermBanAction { .. } | Forwards { .. } | Lockdown { .. } | SimulateRaid { .. } | MarkTrusted { .. } | TrustUser { .. } | UntrustUser { .. } => {
+            Some(AdminPermission::ManageChats)
+        }
+        Stats { .. } | Health | SymbolStats { .. } | BanList { .. } | MuteList { .. } | WorstUsers { .. } | Trend { .. } | TestMessage { .. }
+        | Reputation { .. } | Whois { .. } | Notes { .. } | FeatureStatus { .. } | TrustStats | RateLimitStats | SpamPatterns { .. }
+        | AntiEvasionStats | BayesStats | NeuralStats | NeuralStatus | NeuralFeatures { .. } | ListMessages | SearchMessages { .. }
+        | CheckMessage { .. } | Diagnose { .. } => Some(AdminPermission::ViewStats),
+        AddAdmin { .. } | RemoveAdmin { .. } | SetPermissions { .. } => Some(AdminPermission::ManageUsers),
+        ListAdmins => Some(AdminPermission::ViewStats),
+        MonitoredChats => Some(AdminPermission::ManageChats),
+        ExportAuditLog { .. } => Some(AdminPermission::ViewAuditLog),
+        EmergencyStop { .. } => Some(AdminPermission::EmergencyControl),
+        ExportConfig => Some(AdminPermission::ViewConfig),
+        ImportConfig => Some(AdminPermission::ConfigureBot),
+    }
+}
+
+/// Whether the admin panel has been set up.
+pub fn is_admin_panel_setup(conn: &mut redis::Connection) -> RedisResult<bool> {
+    conn.exists(key::ns(panel_key::ADMIN_PANEL_CHAT_KEY))
+}
+
+/// The panel member `user_id`, if they are one.
+pub fn admin_panel_user(conn: &mut redis::Connection, user_id: UserId) -> RedisResult<Option<AdminUser>> {
+    let is_member: bool = conn.sismember(key::ns(panel_key::ADMIN_PANEL_MEMBERS_KEY), user_id.0.to_string())?;
+    if !is_member {
+        return Ok(None);
+    }
+    let data: Option<String> = conn.hget(key::ns(panel_key::ADMIN_PANEL_PERMISSIONS_KEY), user_id.0.to_string())?;
+    Ok(data.and_then(|data| match serde_json::from_str(&data) {
+        Ok(user) => Some(user),
+        Err(e) => {
+            log::warn!("Ignoring unreadable admin panel permissions of user {}: {}", user_id, e);
+            None
+        }
+    }))
+}
+
+/// Whether the admin panel lets `user_id` run `cmd`.
+pub fn command_access(conn: &mut redis::Connection, user_id: UserId, cmd: &AdminCommand) -> RedisResult<CommandAccess> {
+    let Some(permission) = required_permission(cmd) else {
+        return Ok(CommandAccess::Unrestricted);
+    };
+    if !is_admin_panel_setup(conn)? {
+        return Ok(CommandAccess::Unrestricted);
+    }
+    match admin_panel_user(conn, user_id)? {
+        Some(user) if user.has_permission(&permission) => Ok(CommandAccess::Granted),
+        _ => Ok(CommandAccess::Denied(permission)),
+    }
+}
+
+/// Whether the sender of `msg` may act on `target`: the chat the command was
+/// sent in, a chat assigned to it with /makeadmin, or a chat the sender is
+/// an admin of.
+pub fn moderates_chat(conn: &mut redis::Connection, msg: &Message, target: ChatId) -> RedisResult<bool> {
+    if msg.chat.id == target {
+        return Ok(true);
+    }
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(false);
+    };
+    let assigned: bool = conn.sismember(
+        format!("{}{}{}", key::ns(key::ADMIN_PREFIX), msg.chat.id.0, suffix::MODERATED_CHATS),
+        target.0,
+    )?;
+    if assigned {
+        return Ok(true);
+    }
+    conn.sismember(key::ns(format!("{}{}", user.id, suffix::BOT_CHATS)), target.0)
+}
+
+/// Whether the sender of `msg` moderates any chat: a group they sent the
+/// command in (the admin check has passed), one assigned to the chat with
+/// /makeadmin, or one they are an admin of.
+pub fn moderates_any_chat(conn: &mut redis::Connection, msg: &Message) -> RedisResult<bool> {
+    if !msg.chat.is_private() {
+        return Ok(true);
+    }
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(false);
+    };
+    let assigned: usize = conn.scard(format!("{}{}{}", key::ns(key::ADMIN_PREFIX), msg.chat.id.0, suffix::MODERATED_CHATS))?;
+    if assigned > 0 {
+        return Ok(true);
+    }
+    let administered: usize = conn.scard(key::ns(format!("{}{}", user.id, suffix::BOT_CHATS)))?;
+    Ok(administered > 0)
+}
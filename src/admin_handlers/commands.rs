@@ -1,39 +1,116 @@
-use teloxide::{utils::command::BotCommands};
+use teloxide::types::BotCommand;
+use teloxide::utils::command::BotCommands;
+
+use crate::config::command_menu;
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase", description = "Admin commands:")]
 pub enum AdminCommand {
     #[command(description = "show help.")]
     Help,
-    #[command(description = "show spam stats.")]
-    Stats,
+    #[command(description = "show spam stats, or --global for every moderated chat.")]
+    Stats { args: String },
+    #[command(description = "show the status of Redis, Rspamd and the classifiers.")]
+    Health,
+    #[command(description = "show the most triggered symbols for a chat.", alias = "ss")]
+    SymbolStats { chat: String },
+    #[command(description = "list the users currently banned in a chat.")]
+    BanList { args: String },
+    #[command(description = "list the users currently muted in a chat.")]
+    MuteList { args: String },
+    #[command(description = "list the users with the highest reputation.", alias = "wu")]
+    WorstUsers { n: String },
+    #[command(description = "make a user read-only: <user>|<minutes>[|<chat_id>].")]
+    Mute { args: String },
+    #[command(description = "give a muted user their permissions back.")]
+    Unmute { args: String },
+    #[command(description = "show daily spam actions in a chat over the last days.")]
+    Trend { args: String },
+    #[command(description = "preview which symbols a text triggers.", alias = "tm")]
+    TestMessage { text: String },
     #[command(description = "show user reputation.")]
     Reputation { user: String },
+    #[command(description = "show everything known about a user.")]
+    Whois { user: String },
+    #[command(description = "leave a note on a user for the other moderators: <user>|<text>.")]
+    Note { args: String },
+    #[command(description = "list the notes left on a user.")]
+    Notes { user: String },
+    #[command(description = "report the replied-to message as spam.", alias = "report")]
+    ReportSpam,
+    #[command(description = "ask the admins to lift your ban (in a private chat with the bot).")]
+    Appeal { reason: String },
     #[command(description = "add a regex filter.")]
     AddRegex { pattern: String },
+    #[command(description = "delete a user's recent messages in this chat.")]
+    Purge { user: String },
     #[command(description = "make this chat admin-chat.")]
     MakeAdmin,
+    #[command(description = "delete all bot state for a chat, after confirmation.")]
+    ResetChat { chat: String },
     #[command(description = "show whitelist of users/words or add user/word to whitelist.")]
     Whitelist { pattern: String },
+    #[command(description = "add every line of the replied-to file to the user/word whitelist.", alias = "iwl")]
+    ImportWhitelist { args: String },
+    #[command(description = "send the whitelisted users and words as a file.", alias = "wlexport")]
+    WhitelistExport { chat: String },
     #[command(description = "show blacklist of users/words or add user/word to blacklist.")]
     Blacklist { pattern: String },
+    #[command(description = "send the blacklisted users and words as a file.", alias = "blexport")]
+    BlacklistExport { chat: String },
+    #[command(description = "show or edit the domains exempt from link spam checks.")]
+    TrustedDomain { pattern: String },
+    #[command(description = "show or edit the file extensions flagged as risky attachments.")]
+    RiskyExt { pattern: String },
+    #[command(description = "show or edit the URL shorteners flagged by TG_SHORTENER.")]
+    Shortener { pattern: String },
+    #[command(description = "switch a detection symbol on or off for all chats.", alias = "tsym")]
+    ToggleSymbol { symbol: String },
+    #[command(description = "show or adjust a domain's reputation.")]
+    DomainRep { args: String },
     #[command(description = "Start managing features (callback flow)")]
     ManageFeatures,
+    #[command(description = "show which features are on in a chat and why.", alias = "fs")]
+    FeatureStatus { chat: String },
+    #[command(description = "set a detection threshold or reputation gate.", alias = "st")]
+    SetThreshold { args: String },
+    #[command(description = "allow a script (e.g. latin, cyrillic) in a chat.")]
+    AllowScript { args: String },
+    #[command(description = "set the score at which a chat warns, deletes or bans.")]
+    SetAction { args: String },
+    #[command(description = "set a chat's first-message timing windows and probation.")]
+    SetJoinWindow { args: String },
+    #[command(description = "set how many messages per window trigger TG_FLOOD in a chat: <chat_id>|<messages|default>.")]
+    SetFlood { args: String },
+    #[command(description = "cap automated bans per minute in a chat: <chat_id>|<bans|default>.")]
+    SetBanRate { args: String },
+    #[command(description = "show or set the bounds of a chat's adaptive flood and repeat limits.")]
+    SetAdaptive { args: String },
+    #[command(description = "show or set what happens to users hitting TG_PERM_BAN in a chat.", alias = "pba")]
+    PermBanAction { args: String },
+    #[command(description = "show or set which forwarded messages a chat accepts.")]
+    Forwards { args: String },
+    #[command(description = "lock a chat down, muting new members, or lift it: <on|off|status>[|<chat_id>].")]
+    Lockdown { args: String },
     #[command(description = "mark a message as trusted for reply-aware filtering.")]
     MarkTrusted { args: String },
-    #[command(description = "show trust management statistics.")]
+    #[command(description = "trust all future messages from a user: <user>|[hours].")]
+    TrustUser { user: String },
+    #[command(description = "stop trusting a user.")]
+    UntrustUser { user: String },
+    #[command(description = "show trust management statistics.", alias = "ts")]
     TrustStats,
     #[command(description = "configure reply-aware filtering settings.")]
     ReplyConfig { args: String },
-    #[command(description = "show rate limiting statistics.")]
+    #[command(description = "show rate limiting statistics.", alias = "rls")]
     RateLimitStats,
-    #[command(description = "reset rate limiting for a user.")]
+    #[command(description = "reset rate limiting for a user.", alias = "rrl")]
     ResetRateLimit { user: String },
-    #[command(description = "show spam pattern history for a user.")]
+    #[command(description = "show spam pattern history for a user.", alias = "sp")]
     SpamPatterns { user: String },
-    #[command(description = "configure selective trusting rules.")]
+    #[command(description = "configure selective trusting rules.", alias = "strust")]
     SelectiveTrust { args: String },
-    #[command(description = "show anti-evasion statistics.")]
+    #[command(description = "show anti-evasion statistics.", alias = "aes")]
     AntiEvasionStats,
     #[command(description = "learn a message as spam for Bayesian classifier.")]
     LearnSpam { message_id: String },
@@ -43,16 +120,105 @@ pub enum AdminCommand {
     BayesStats,
     #[command(description = "reset all Bayesian classifier data.")]
     BayesReset,
+    #[command(description = "add a message to fuzzy storage: <message_id>|[flag]|[weight].")]
+    FuzzyAdd { message_id: String },
+    #[command(description = "remove a message from fuzzy storage: <message_id>|[flag].")]
+    FuzzyDel { message_id: String },
     #[command(description = "show neural network statistics.")]
     NeuralStats,
     #[command(description = "reset neural network model and training data.")]
     NeuralReset,
+    #[command(description = "train the neural network on collected samples.")]
+    NeuralTrain,
     #[command(description = "show neural network training status.")]
     NeuralStatus,
-    #[command(description = "show neural network feature analysis.")]
+    #[command(description = "show neural network feature analysis.", alias = "nf")]
     NeuralFeatures { message_id: String },
     #[command(description = "list recent messages stored in Redis (for debugging).")]
     ListMessages,
+    #[command(description = "search stored messages: <chat_id|all>|<hours>|<text or /regex/>.", alias = "sm")]
+    SearchMessages { args: String },
     #[command(description = "check learning status of a specific message.")]
     CheckMessage { message_id: String },
-}
\ No newline at end of file
+    #[command(description = "re-scan a stored message and explain its score.")]
+    Diagnose { message_id: String },
+    #[command(description = "simulate a raid of synthetic members in a staging chat.", alias = "sr")]
+    SimulateRaid { members: String },
+    #[command(description = "add a user to the admin panel: <user>[|<group>].")]
+    AddAdmin { args: String },
+    #[command(description = "remove a user from the admin panel.")]
+    RemoveAdmin { user: String },
+    #[command(description = "set an admin panel member's permissions: <user>|<permission,...>.")]
+    SetPermissions { args: String },
+    #[command(description = "list the admin panel members.")]
+    ListAdmins,
+    #[command(description = "list the chats moderated from this chat.")]
+    MonitoredChats,
+    #[command(description = "send the admin panel audit log of the last hours as a file.")]
+    ExportAuditLog { hours: String },
+    #[command(description = "stop or resume scanning and moderation in every chat: <on|off|status>.")]
+    EmergencyStop { mode: String },
+    #[command(description = "send the bot configuration as a JSON file.")]
+    ExportConfig,
+    #[command(description = "restore the bot configuration from the replied-to export.")]
+    ImportConfig,
+}
+
+/// Short forms of the long commands as `(alias, command)`, matching the
+/// `alias` attributes above; listed in the command menu next to the command.
+pub const ALIASES: &[(&str, &str)] = &[
+    ("ss", "symbolstats"),
+    ("wu", "worstusers"),
+    ("tm", "testmessage"),
+    ("report", "reportspam"),
+    ("iwl", "importwhitelist"),
+    ("wlexport", "whitelistexport"),
+    ("blexport", "blacklistexport"),
+    ("tsym", "togglesymbol"),
+    ("fs", "featurestatus"),
+    ("st", "setthreshold"),
+    ("pba", "permbanaction"),
+    ("ts", "truststats"),
+    ("rls", "ratelimitstats"),
+    ("rrl", "resetratelimit"),
+    ("sp", "spampatterns"),
+    ("strust", "selectivetrust"),
+    ("aes", "antievasionstats"),
+    ("nf", "neuralfeatures"),
+    ("sm", "searchmessages"),
+    ("sr", "simulateraid"),
+];
+
+/// Telegram's command menu for chat admins: every command with its
+/// description, followed by the aliases. Telegram rejects a longer menu
+/// outright, so aliases past `command_menu::MAX_COMMANDS` are left out;
+/// the command menu test fails before any alias is actually dropped.
+pub fn admin_command_menu() -> Vec<BotCommand> {
+    // The derive prefixes names with `/`, which the Bot API doesn't expect
+    let mut menu: Vec<BotCommand> = AdminCommand::bot_commands()
+        .into_iter()
+        .map(|entry| BotCommand::new(entry.command.trim_start_matches('/'), entry.description))
+        .collect();
+    menu.extend(
+        ALIASES
+            .iter()
+            .map(|(alias, command)| BotCommand::new(*alias, format!("short for /{}.", command))),
+    );
+    menu.truncate(command_menu::MAX_COMMANDS);
+    menu
+}
+
+/// Telegram's command menu for everyone else: the commands open to all
+/// members and their aliases.
+pub fn member_command_menu() -> Vec<BotCommand> {
+    admin_command_menu()
+        .into_iter()
+        .filter(|entry| {
+            let command = ALIASES
+                .iter()
+                .find(|(alias, _)| *alias == entry.command)
+                .map_or(entry.command.as_str(), |(_, command)| command);
+            command_menu::MEMBER_COMMANDS.contains(&command)
+        })
+        .collect()
+}
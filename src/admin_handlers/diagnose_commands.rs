@@ -0,0 +1,120 @@
+use std::fmt::Write;
+use teloxide::prelude::*;
+use crate::config::{key, message_store};
+use crate::handlers::{preview_scan, resolve_action, stored_message_content, trace_scan, ScanTrace};
+use redis::Commands;
+
+/// Renders a trace for `/diagnose`: every symbol with its score and reputation
+/// weight, the reductions, the final score, reputation delta and `action`.
+pub fn render_trace(message_id: &str, chat: ChatId, trace: &ScanTrace, action: &str) -> String {
+    let mut response = format!(
+        "Diagnosis of message {} in chat {} (scanned as if you sent it, nothing was recorded):\n",
+        message_id, chat
+    );
+    writeln!(&mut response, "Symbols:").unwrap();
+    if trace.symbols.is_empty() {
+        writeln!(&mut response, "• none").unwrap();
+    }
+    for symbol in &trace.symbols {
+        writeln!(&mut response, "• {} ({:+.2}, rep {:+})", symbol.name, symbol.score, symbol.weight).unwrap();
+    }
+    writeln!(&mut response, "Reductions:").unwrap();
+    if trace.reductions.is_empty() {
+        writeln!(&mut response, "• none").unwrap();
+    }
+    for reduction in &trace.reductions {
+        writeln!(&mut response, "• {} ({:+.2})", reduction.name, reduction.score).unwrap();
+    }
+    writeln!(&mut response, "Score: {:.2}", trace.score).unwrap();
+    writeln!(&mut response, "Reputation delta: {:+}", trace.reputation_delta).unwrap();
+    writeln!(&mut response, "Action: {}", action).unwrap();
+    response
+}
+
+/// Handles the /diagnose command: re-scans a stored message and explains its score
+pub async fn handle_diagnose(bot: Bot, msg: Message, message_id: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let message_id = message_id.trim().to_string();
+    if message_id.parse::<i32>().is_err() {
+        bot.send_message(chat_id, "Usage: /diagnose <message_id>").await?;
+        return Ok(());
+    }
+
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let text = match stored_message_content(&mut redis_conn, &message_id) {
+        Ok(Some(text)) => text,
+        Ok(None) => {
+            bot.send_message(chat_id, format!("Message {} is not stored; it may have expired.", message_id)).await?;
+            return Ok(());
+        }
+        Err(e) => {
+            bot.send_message(chat_id, format!("Failed to read message {}: {}", message_id, e)).await?;
+            return Ok(());
+        }
+    };
+
+    // Scan in the chat the message was posted in, so its lists and features apply
+    let info_key = format!("{}{}", key::ns(key::TG_MESSAGE_INFO_PREFIX), message_id);
+    let stored_chat: Option<i64> = redis_conn.hget(&info_key, message_store::CHAT_ID).unwrap_or(None);
+    let mut probe = msg.clone();
+    if let Some(stored_chat) = stored_chat {
+        probe.chat.id = ChatId(stored_chat);
+    }
+    let target_chat = probe.chat.id;
+
+    let trace = match trace_scan(probe, text).await {
+        Ok(trace) => trace,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Scan failed: {}", e)).await?;
+            return Ok(());
+        }
+    };
+    let action = resolve_action(&mut redis_conn, target_chat, trace.score);
+    bot.send_message(chat_id, render_trace(&message_id, target_chat, &trace, action)).await?;
+    Ok(())
+}
+
+/// Handles the /testmessage command: previews which symbols a text triggers without recording anything
+pub async fn handle_test_message(bot: Bot, msg: Message, text: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    if text.trim().is_empty() {
+        bot.send_message(chat_id, "Usage: /testmessage <text to scan>").await?;
+        return Ok(());
+    }
+
+    // Scan the text as if this admin posted it here, without recording anything
+    let reply = match preview_scan(msg.clone(), text.clone()).await {
+        Ok(reply) => reply,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Scan failed: {}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    let mut symbols: Vec<_> = reply.symbols.values().collect();
+    symbols.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+    let mut response = String::from("Test scan (nothing was recorded):\n");
+    if symbols.is_empty() {
+        writeln!(&mut response, "• No symbols triggered").unwrap();
+    }
+    for symbol in symbols {
+        writeln!(&mut response, "• {} ({:.2})", symbol.name, symbol.score).unwrap();
+    }
+    writeln!(&mut response, "Score: {:.2}", reply.score).unwrap();
+    writeln!(
+        &mut response,
+        "Would-be action: {}",
+        resolve_action(&mut redis_conn, chat_id, reply.score)
+    ).unwrap();
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
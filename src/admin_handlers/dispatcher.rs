@@ -1,16 +1,22 @@
 use std::collections::HashMap;
-use crate::admin_handlers::{handle_admin_command, AdminCommand};
-use crate::handlers::handle_message;
+use crate::admin_handlers::{admin_command_menu, member_command_menu, handle_admin_command, appeal_handler, panel_page_handler, reset_chat_handler, AdminCommand, APPEAL_CALLBACK, LIST_ADMINS_CALLBACK, MONITORED_CHATS_CALLBACK, RESET_CHAT_CALLBACK};
+use crate::handlers::{handle_message, message_sender, Sender};
+use crate::reputation_update::init_rep;
+use crate::impersonation::{forget_admin_name, record_admin_name};
+use crate::lockdown::{announce_lockdown, lockdown_since, mute_for_lockdown, record_raid_event};
+use crate::join_verify::{challenge_new_member, clear_pending, verify_handler, VERIFY_CALLBACK};
+use crate::notifications::is_silent;
 use redis::{Commands, RedisResult};
 use teloxide::dispatching::{Dispatcher, UpdateFilterExt};
 use teloxide::dptree;
 use teloxide::payloads::{AnswerCallbackQuerySetters, SendMessageSetters, SetMyCommandsSetters};
 use teloxide::prelude::{CallbackQuery, ChatId, ChatMemberUpdated, Message, Requester, Update};
-use teloxide::types::{BotCommand, BotCommandScope, ChatKind, ChatMemberStatus, InlineKeyboardButton, InlineKeyboardMarkup};
-use teloxide::utils::command::BotCommands;
+use teloxide::types::{BotCommandScope, ChatKind, ChatMemberStatus, InlineKeyboardButton, InlineKeyboardMarkup, UserId};
 use teloxide::{Bot, RequestError};
 use std::fmt::Write;
-use crate::config::{field, key, suffix, DEFAULT_FEATURES, ENABLED_FEATURES_KEY};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use crate::config::{field, is_feature_enabled, key, suffix, DEFAULT_FEATURES, ENABLED_FEATURES_KEY, JOIN_VERIFY_FEATURE, OPT_IN_FEATURES, SEEDED_FEATURES_KEY};
 
 /// Helper function to parse commands that may have bot username appended
 fn parse_command_with_botname<T: teloxide::utils::command::BotCommands>(text: &str, bot_name: &str) -> Result<T, teloxide::utils::command::ParseError> {
@@ -32,37 +38,59 @@ fn parse_command_with_botname<T: teloxide::utils::command::BotCommands>(text: &s
     })
 }
 
+/// Record `username -> user_id` in the username index so admins can refer to users by name.
+pub fn index_username(conn: &mut redis::Connection, username: &str, user_id: UserId) -> RedisResult<()> {
+    let username = username.trim_start_matches('@').to_lowercase();
+    conn.hset(key::ns(key::TG_USERNAMES_KEY), username, user_id.0)
+}
+
+/// Look up a user ID in the username index. Accepts names with or without a leading `@`.
+pub fn lookup_username(conn: &mut redis::Connection, username: &str) -> Option<UserId> {
+    let username = username.trim_start_matches('@').to_lowercase();
+    let user_id: Option<u64> = conn.hget(key::ns(key::TG_USERNAMES_KEY), username).ok()?;
+    user_id.map(UserId)
+}
+
+/// Handles every incoming message: commands go to `handle_admin_command`,
+/// everything else, including captioned media and shared contacts, is scanned
+/// by `handle_message`.
 pub async fn message_handler(bot: Bot, msg: Message) -> Result<(), RequestError> {
-    if let Some(text) = msg.text() {
-        let client = redis::Client::open("redis://127.0.0.1/").expect("failed to get redis client.");
-        let mut conn = client.get_connection().expect("Failed to connect");
-        let key = format!("{}{}", key::TG_USERS_PREFIX, msg.clone().from.unwrap().id.0);
-
-        let user_rep: RedisResult<i64> = conn.hget(&key, field::REP);
-        
-        match user_rep {
-            Ok(_) => {}
-            Err(_) => {
-                let _: () = conn
-                    .hset(key.clone(), field::REP, 0)
-                    .expect("Failed to update user's reputation");
+    let client = redis::Client::open("redis://127.0.0.1/").expect("failed to get redis client.");
+    let mut conn = client.get_connection().expect("Failed to connect");
+
+    // Posts made as a channel or by an anonymous admin have no user to track
+    if let (Some(Sender::User(user_id)), Some(user)) = (message_sender(&msg), msg.from.as_ref()) {
+        let key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user_id.0);
+
+        // HSETNX, so a scan updating the reputation concurrently isn't overwritten
+        let is_new = init_rep(&mut conn, user_id.0).expect("Failed to update user's reputation");
+        if is_new {
+            if let Some(username) = user.username.clone() {
                 let _: () = conn
-                    .hset(key.clone(), field::USERNAME, msg.clone().from.unwrap().username.unwrap().to_string())
+                    .hset(key.clone(), field::USERNAME, username)
                     .expect("Failed to update user's reputation");
             }
         }
-        
-        // Try to parse as admin command, handling both formats:
-        // 1. /command (in private chats)
-        // 2. /command@botname (in group chats)
-        let cmd_result = parse_command_with_botname::<AdminCommand>(text, "rspamd-bot");
-        
-        if let Ok(cmd) = cmd_result {
-            handle_admin_command(bot.clone(), msg.clone(), cmd).await?;
-        } else {
-            let _ = handle_message(bot.clone(), msg.clone()).await;
+
+        if let Some(username) = user.username.as_deref() {
+            let _ = index_username(&mut conn, username, user.id);
         }
     }
+    
+    // Try to parse as admin command, handling both formats:
+    // 1. /command (in private chats)
+    // 2. /command@botname (in group chats)
+    // Only text messages carry commands; documents, photos, stickers and
+    // contacts have no text() and are scanned like any other message
+    let cmd_result = msg
+        .text()
+        .map(|text| parse_command_with_botname::<AdminCommand>(text, "rspamd-bot"));
+    
+    if let Some(Ok(cmd)) = cmd_result {
+        handle_admin_command(bot.clone(), msg.clone(), cmd).await?;
+    } else {
+        let _ = handle_message(bot.clone(), msg.clone()).await;
+    }
     Ok(())
 }
 
@@ -78,13 +106,13 @@ pub async fn makeadmin_handler(bot: Bot, query: CallbackQuery) -> Result<(), Req
                 let mut redis_conn = redis_client
                     .get_connection()
                     .expect("Failed to get Redis connection");
-                let key = format!("{}{}{}", key::ADMIN_PREFIX, admin_id, suffix::MODERATED_CHATS);
+                let key = format!("{}{}{}", key::ns(key::ADMIN_PREFIX), admin_id, suffix::MODERATED_CHATS);
                 let _: () = redis_conn
                     .sadd(key, selected_chat.clone())
                     .expect("Failed to add moderated chat to admin");
                 
                 let _: () = redis_conn
-                    .hset(format!("{}{}", key::TG_CHATS_PREFIX, selected_chat.clone()), field::ADMIN_CHAT, admin_id.0)
+                    .hset(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), selected_chat.clone()), field::ADMIN_CHAT, admin_id.0)
                     .expect("Failed to add admin chat to selected chat");
 
                 bot.answer_callback_query(query.id)
@@ -109,11 +137,11 @@ pub async fn stats_handler(bot: Bot, query: CallbackQuery) -> Result<(), Request
             
             // Get the chat name for the response
             let chat_name: String = redis_conn
-                .hget(format!("{}{}", key::TG_CHATS_PREFIX, selected_chat), field::NAME)
+                .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), selected_chat), field::NAME)
                 .unwrap_or_else(|_| selected_chat.to_string());
             
             let stats: HashMap<String, String> = redis_conn
-                .hgetall(format!("{}{}", key::TG_CHATS_PREFIX, selected_chat))
+                .hgetall(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), selected_chat))
                 .expect("Failed to get chat stats");
             let mut response = String::new();
             writeln!(&mut response, "Stats for chat: {}", chat_name).unwrap();
@@ -157,26 +185,17 @@ pub async fn manage_features_select_chat(
                     .get_connection()
                     .expect("Failed to get Redis connection");
 
-                let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, target_chat_id);
                 let mut rows: Vec<Vec<InlineKeyboardButton>> = Vec::new();
 
                 // Show all available features, not just the ones already in Redis
                 let feats: Vec<String> = DEFAULT_FEATURES
                     .iter()
+                    .chain(OPT_IN_FEATURES)
                     .map(|&s| s.to_string())
                     .collect();
 
                 for feat in feats {
-                    let field_name = format!("feat:{}", feat);
-                    let chat_val: Option<String> =
-                        redis_conn.hget(chat_key.clone(), field_name.clone()).unwrap_or(None);
-                    let global_on: bool =
-                        redis_conn.sismember(ENABLED_FEATURES_KEY, feat.clone()).unwrap_or(false);
-                    let is_enabled = match chat_val.as_deref() {
-                        Some("1") => true,
-                        Some("0") => false,
-                        _ => global_on,
-                    };
+                    let is_enabled = is_feature_enabled(&mut redis_conn, target_chat_id, &feat);
 
                     // Decide button text and callback_data:
                     if is_enabled {
@@ -242,18 +261,10 @@ pub async fn toggle_feature_handler(
                     let mut redis_conn = redis_client
                         .get_connection()
                         .expect("Failed to get Redis connection");
-                    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, target_chat_id);
+                    let chat_key = format!("{}{}", key::ns(key::TG_CHATS_PREFIX), target_chat_id);
                     let field_name = format!("feat:{}", feat_name);
 
-                    let chat_val: Option<String> =
-                        redis_conn.hget(chat_key.clone(), field_name.clone()).unwrap_or(None);
-                    let global_on: bool =
-                        redis_conn.sismember(ENABLED_FEATURES_KEY, feat_name).unwrap_or(false);
-                    let currently_on = match chat_val.as_deref() {
-                        Some("1") => true,
-                        Some("0") => false,
-                        _ => global_on,
-                    };
+                    let currently_on = is_feature_enabled(&mut redis_conn, target_chat_id, feat_name);
 
                     if currently_on {
                         // It was on → set explicit 0 (disable)
@@ -310,46 +321,85 @@ pub async fn discard_handler(
     Ok(())
 }
 
+/// Keeps user state in step with membership changes. Joins record
+/// `join_time` (timing `TG_FIRST_FAST`/`TG_FIRST_SLOW`), the username and
+/// admin status, count towards raid detection, and in chats with
+/// `join_verify` on get the welcome challenge; leaving or being kicked drops
+/// the user's state.
 pub async fn chat_member_handler(
-    _bot: Bot,
+    bot: Bot,
     update: ChatMemberUpdated,
 ) -> Result<(), RequestError> {
-    let new_status = update.new_chat_member.status();
     let chat_id = ChatId(update.chat.id.0);
-    let username = update.new_chat_member.user.username.as_ref().unwrap();
+    let user = &update.new_chat_member.user;
 
     let client = redis::Client::open("redis://127.0.0.1/").expect("failed to get redis client.");
     let mut conn = client.get_connection().expect("Failed to connect");
-    let key = format!("{}{}", key::TG_USERS_PREFIX, update.new_chat_member.user.id.0);
-    let admin_key = format!("{}{}", update.new_chat_member.user.id, suffix::BOT_CHATS);
-
-    match new_status {
-        ChatMemberStatus::Member | ChatMemberStatus::Administrator | ChatMemberStatus::Owner => {
-            if new_status == ChatMemberStatus::Administrator || new_status == ChatMemberStatus::Owner {
-                if !update.new_chat_member.user.is_bot {
-                    let _: () = conn
-                        .sadd(admin_key.clone(), chat_id.0)
-                        .expect("Failed to add chat to admin's bot_chats");
-                }
-            }
-            let _: () = conn.hset(key.clone(), field::REP, 0)
-                .expect("Failed to set rep");
+    let key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user.id.0);
+    let admin_key = key::ns(format!("{}{}", user.id, suffix::BOT_CHATS));
+    let was_admin = update.old_chat_member.is_privileged();
 
-            let _: () = conn.hset(key.clone(), field::USERNAME, &username)
+    if update.new_chat_member.is_present() {
+        if update.new_chat_member.is_privileged() {
+            if !user.is_bot {
+                let _: () = conn
+                    .sadd(admin_key.clone(), chat_id.0)
+                    .expect("Failed to add chat to admin's bot_chats");
+            }
+            let _: RedisResult<()> = record_admin_name(&mut conn, chat_id, user.id, &user.full_name());
+        } else {
+            if was_admin && !user.is_bot {
+                let _: () = conn
+                    .srem(admin_key.clone(), chat_id.0)
+                    .expect("Failed to remove chat from bot_chats");
+            }
+            let _: RedisResult<()> = forget_admin_name(&mut conn, chat_id, user.id);
+        }
+        // HSETNX, so a promotion or restriction doesn't reset the reputation
+        init_rep(&mut conn, user.id.0).expect("Failed to set rep");
+        if let Some(username) = user.username.as_deref() {
+            let _: () = conn.hset(&key, field::USERNAME, username)
                 .expect("Failed to set username");
+            let _ = index_username(&mut conn, username, user.id);
         }
-        ChatMemberStatus::Left | ChatMemberStatus::Banned | ChatMemberStatus::Restricted => {
-            if update.old_chat_member.status() == ChatMemberStatus::Administrator || update.old_chat_member.status() == ChatMemberStatus::Owner {
-                if !update.new_chat_member.user.is_bot {
-                    let _: () = conn
-                        .srem(admin_key.clone(), chat_id.0)
-                        .expect("Failed to remove chat from bot_chats");
+
+        // A join, as opposed to a change of an existing member's status
+        if !update.old_chat_member.is_present() {
+            // Remember when the user joined so their first message can be timed
+            let now = chrono::Utc::now().timestamp();
+            let _: RedisResult<()> = conn.hset(&key, field::JOIN_TIME, now);
+
+            // Joins count towards raid detection; a locked down chat mutes
+            // newcomers, otherwise chats may greet them with the challenge unless
+            // they are silent
+            if !user.is_bot && !update.new_chat_member.is_privileged() {
+                if record_raid_event(&mut conn, chat_id, now).unwrap_or(false) {
+                    let _ = announce_lockdown(&bot, &mut conn, chat_id).await;
+                }
+                if lockdown_since(&mut conn, chat_id).is_some() {
+                    if let Err(e) = mute_for_lockdown(&bot, &mut conn, chat_id, user.id).await {
+                        log::warn!("Failed to mute user {} during the lockdown of chat {}: {}", user.id, chat_id, e);
+                    }
+                } else if is_feature_enabled(&mut conn, chat_id.0, JOIN_VERIFY_FEATURE) && !is_silent(&mut conn, chat_id) {
+                    if let Err(e) = challenge_new_member(&bot, &mut conn, chat_id, user).await {
+                        log::warn!("Failed to challenge new member {} of chat {}: {}", user.id, chat_id, e);
+                    }
                 }
             }
+        }
+    } else {
+        if was_admin && !user.is_bot {
             let _: () = conn
-                .del(key.clone())
-                .expect("Failed to remove user's reputation");
+                .srem(admin_key.clone(), chat_id.0)
+                .expect("Failed to remove chat from bot_chats");
+        }
+        let _: RedisResult<()> = forget_admin_name(&mut conn, chat_id, user.id);
+        if let Ok(Some(Some(challenge))) = clear_pending(&mut conn, chat_id, user.id) {
+            let _ = bot.delete_message(chat_id, challenge).await;
         }
+        let _: () = conn
+            .del(key.clone())
+            .expect("Failed to remove user's reputation");
     }
 
     Ok(())
@@ -365,14 +415,14 @@ pub async fn my_chat_member_handler(
     let client = redis::Client::open("redis://127.0.0.1/").expect("failed to get redis client.");
     let mut conn = client.get_connection().expect("Failed to connect");
     let chat_id = ChatId(update.chat.id.0);
-    let admins_key = format!("{}{}", update.chat.id.0, suffix::ADMINS);
-    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, update.chat.id.0);
+    let admins_key = key::ns(format!("{}{}", update.chat.id.0, suffix::ADMINS));
+    let chat_key = format!("{}{}", key::ns(key::TG_CHATS_PREFIX), update.chat.id.0);
     if update.new_chat_member.status() == ChatMemberStatus::Banned || update.new_chat_member.status() == ChatMemberStatus::Left || update.new_chat_member.status() == ChatMemberStatus::Restricted {
         let admins: Vec<String> = conn
             .smembers(admins_key.clone())
             .expect("failed to get admins of the chat");
         for admin in admins {
-            let admin_key = format!("{}{}", admin, suffix::BOT_CHATS);
+            let admin_key = key::ns(format!("{}{}", admin, suffix::BOT_CHATS));
             let _: () = conn
                 .srem(admin_key, update.chat.id.0)
                 .expect("Failed to remove admin from bot_chats");
@@ -394,6 +444,7 @@ pub async fn my_chat_member_handler(
                 for admin in admins {
                     log::info!("Admin: {:?}", admin.user.username);
                     let admin_key = format!("{}:bot_chats", admin.user.id);
+                    let _: redis::RedisResult<()> = record_admin_name(&mut conn, chat_id, admin.user.id, &admin.user.full_name());
                     if !admin.user.is_bot {
                         let _: () = conn
                                 .sadd(admin_key, update.chat.id.0)
@@ -417,18 +468,28 @@ pub async fn my_chat_member_handler(
     Ok(())
 }
 
-pub async fn run_dispatcher(bot: Bot) {
-    // Ensure all default features exist in the global enabled set
+/// Runs the dispatcher until `shutdown` is cancelled, letting in-flight updates finish.
+pub async fn run_dispatcher(bot: Bot, shutdown: CancellationToken) {
+    // Enable default features the first time they're seen, so features
+    // switched off globally (e.g. with /togglesymbol) stay off across restarts
     if let Ok(client) = redis::Client::open("redis://127.0.0.1/") {
         if let Ok(mut conn) = client.get_connection() {
             for feat in DEFAULT_FEATURES {
-                let _ : redis::RedisResult<()> = conn.sadd(ENABLED_FEATURES_KEY, *feat);
+                let first_seen: redis::RedisResult<bool> = conn.sadd(key::ns(SEEDED_FEATURES_KEY), *feat);
+                if let Ok(true) = first_seen {
+                    let _ : redis::RedisResult<()> = conn.sadd(key::ns(ENABLED_FEATURES_KEY), *feat);
+                }
             }
         }
     }
 
-    let commands:Vec<BotCommand> = AdminCommand::bot_commands();
-    let _ = bot.set_my_commands(commands).scope(BotCommandScope::Default).await;
+    // Members only see the commands open to them; chat admins get the full menu with the aliases
+    if let Err(e) = bot.set_my_commands(member_command_menu()).scope(BotCommandScope::Default).await {
+        log::warn!("Failed to register the member command menu: {}", e);
+    }
+    if let Err(e) = bot.set_my_commands(admin_command_menu()).scope(BotCommandScope::AllChatAdministrators).await {
+        log::warn!("Failed to register the admin command menu: {}", e);
+    }
     let handler = dptree::entry()
         .branch(Update::filter_message().endpoint(message_handler))
         .branch(
@@ -478,7 +539,67 @@ pub async fn run_dispatcher(bot: Bot) {
                 })
                 .endpoint(discard_handler),
         )
+        .branch(
+            // When admin confirms /resetchat:
+            Update::filter_callback_query()
+                .filter(|q: CallbackQuery| {
+                    q.data
+                        .as_deref()
+                        .map(|s| s.starts_with(RESET_CHAT_CALLBACK))
+                        .unwrap_or(false)
+                })
+                .endpoint(reset_chat_handler),
+        )
+        .branch(
+            // When an admin approves or rejects a ban appeal:
+            Update::filter_callback_query()
+                .filter(|q: CallbackQuery| {
+                    q.data
+                        .as_deref()
+                        .map(|s| s.starts_with(APPEAL_CALLBACK))
+                        .unwrap_or(false)
+                })
+                .endpoint(appeal_handler),
+        )
+        .branch(
+            Update::filter_callback_query()
+                .filter(|q: CallbackQuery| {
+                    q.data.as_deref()
+                        .map(|s| s.starts_with(VERIFY_CALLBACK))
+                        .unwrap_or(false)
+                })
+                .endpoint(verify_handler),
+        )
+        .branch(
+            // When an admin pages through /listadmins or /monitoredchats:
+            Update::filter_callback_query()
+                .filter(|q: CallbackQuery| {
+                    q.data
+                        .as_deref()
+                        .map(|s| s.starts_with(LIST_ADMINS_CALLBACK) || s.starts_with(MONITORED_CHATS_CALLBACK))
+                        .unwrap_or(false)
+                })
+                .endpoint(panel_page_handler),
+        )
         .branch(Update::filter_chat_member().endpoint(chat_member_handler))
         .branch(Update::filter_my_chat_member().endpoint(my_chat_member_handler));
-    Dispatcher::builder(bot, handler).build().dispatch().await;
+    let mut dispatcher = Dispatcher::builder(bot, handler).build();
+
+    let shutdown_token = dispatcher.shutdown_token();
+    tokio::spawn(async move {
+        shutdown.cancelled().await;
+        // Shutting down fails while the dispatcher is still starting, so retry until it runs
+        loop {
+            match shutdown_token.shutdown() {
+                Ok(stopped) => {
+                    log::info!("Waiting for the dispatcher to finish in-flight updates...");
+                    stopped.await;
+                    break;
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+            }
+        }
+    });
+
+    dispatcher.dispatch().await;
 }
\ No newline at end of file
@@ -0,0 +1,112 @@
+use crate::admin_handlers::get_message_content;
+use crate::config::rspamd;
+use crate::fuzzy_trainer::FuzzyTrainer;
+use teloxide::prelude::*;
+
+/// Handles the /fuzzyadd command: adds a stored message to fuzzy storage
+pub async fn handle_fuzzy_add(bot: Bot, msg: Message, message_id: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = message_id.split('|').map(|s| s.trim()).collect();
+    let flag = match parts.get(1).filter(|p| !p.is_empty()) {
+        Some(p) => match p.parse::<u8>() {
+            Ok(flag) => flag,
+            Err(_) => {
+                bot.send_message(chat_id, "Invalid flag. Usage: /fuzzyadd <message_id>|[flag]|[weight]").await?;
+                return Ok(());
+            }
+        },
+        None => rspamd::FUZZY_FLAG,
+    };
+    let weight = match parts.get(2).filter(|p| !p.is_empty()) {
+        Some(p) => match p.parse::<i32>() {
+            Ok(weight) => weight,
+            Err(_) => {
+                bot.send_message(chat_id, "Invalid weight. Usage: /fuzzyadd <message_id>|[flag]|[weight]").await?;
+                return Ok(());
+            }
+        },
+        None => rspamd::FUZZY_WEIGHT,
+    };
+    let message_id = parts[0];
+
+    let content = match get_message_content(&mut redis_conn, message_id).await {
+        Ok(content) => content,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to get message content: {}", e)
+            ).await?;
+            return Ok(());
+        }
+    };
+
+    match FuzzyTrainer::new().add_hash(message_id, &content, flag, weight).await {
+        Ok(()) => {
+            bot.send_message(
+                chat_id,
+                format!("✅ Message {} added to fuzzy storage (flag {}, weight {})", message_id, flag, weight)
+            ).await?;
+        }
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to add fuzzy hash: {}", e)
+            ).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /fuzzydel command: removes a stored message from fuzzy storage
+pub async fn handle_fuzzy_del(bot: Bot, msg: Message, message_id: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = message_id.split('|').map(|s| s.trim()).collect();
+    let flag = match parts.get(1).filter(|p| !p.is_empty()) {
+        Some(p) => match p.parse::<u8>() {
+            Ok(flag) => flag,
+            Err(_) => {
+                bot.send_message(chat_id, "Invalid flag. Usage: /fuzzydel <message_id>|[flag]").await?;
+                return Ok(());
+            }
+        },
+        None => rspamd::FUZZY_FLAG,
+    };
+    let message_id = parts[0];
+
+    let content = match get_message_content(&mut redis_conn, message_id).await {
+        Ok(content) => content,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to get message content: {}", e)
+            ).await?;
+            return Ok(());
+        }
+    };
+
+    match FuzzyTrainer::new().del_hash(message_id, &content, flag).await {
+        Ok(()) => {
+            bot.send_message(
+                chat_id,
+                format!("✅ Message {} removed from fuzzy storage (flag {})", message_id, flag)
+            ).await?;
+        }
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to delete fuzzy hash: {}", e)
+            ).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use std::fmt::Write;
+use std::time::Duration;
+use teloxide::prelude::*;
+use crate::bayes_manager::BayesManager;
+use crate::config::rspamd;
+use crate::handlers::rspamd_url;
+use crate::neural_manager::NeuralManager;
+
+/// State of one subsystem as reported by `/health`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HealthState {
+    /// Working
+    Up,
+    /// Reachable but not fully usable yet (e.g. a classifier still training)
+    Degraded,
+    /// Unreachable or failing
+    Down,
+}
+
+impl HealthState {
+    fn icon(&self) -> &'static str {
+        match self {
+            HealthState::Up => "✅",
+            HealthState::Degraded => "🔄",
+            HealthState::Down => "❌",
+        }
+    }
+}
+
+/// One line of the `/health` reply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubsystemHealth {
+    pub name: &'static str,
+    pub state: HealthState,
+    pub detail: String,
+}
+
+impl SubsystemHealth {
+    fn new(name: &'static str, state: HealthState, detail: impl Into<String>) -> Self {
+        Self { name, state, detail: detail.into() }
+    }
+}
+
+/// Redis answers PING.
+fn redis_health() -> SubsystemHealth {
+    let pong = redis::Client::open("redis://127.0.0.1/")
+        .and_then(|client| client.get_connection())
+        .and_then(|mut conn| redis::cmd("PING").query::<String>(&mut conn));
+    match pong {
+        Ok(_) => SubsystemHealth::new("Redis", HealthState::Up, "connected"),
+        Err(e) => SubsystemHealth::new("Redis", HealthState::Down, e.to_string()),
+    }
+}
+
+/// The Rspamd worker at `RSPAMD_URL` answers `/ping` within
+/// `rspamd::HEALTH_TIMEOUT_SECS`.
+async fn rspamd_health() -> SubsystemHealth {
+    let url = rspamd_url();
+    let response = reqwest::Client::new()
+        .get(format!("{}/ping", url.trim_end_matches('/')))
+        .timeout(Duration::from_secs(rspamd::HEALTH_TIMEOUT_SECS))
+        .send()
+        .await;
+    match response {
+        Ok(response) if response.status().is_success() => {
+            SubsystemHealth::new("Rspamd", HealthState::Up, format!("reachable at {}", url))
+        }
+        Ok(response) => SubsystemHealth::new("Rspamd", HealthState::Down, format!("{} answered {}", url, response.status())),
+        Err(e) => SubsystemHealth::new("Rspamd", HealthState::Down, format!("unreachable at {}: {}", url, e)),
+    }
+}
+
+/// Whether a classifier has enough training data.
+fn classifier_health(name: &'static str, ready: anyhow::Result<bool>) -> SubsystemHealth {
+    match ready {
+        Ok(true) => SubsystemHealth::new(name, HealthState::Up, "ready"),
+        Ok(false) => SubsystemHealth::new(name, HealthState::Degraded, "needs more training data"),
+        Err(e) => SubsystemHealth::new(name, HealthState::Down, e.to_string()),
+    }
+}
+
+/// Checks Redis, Rspamd and the Bayes and neural classifiers.
+pub async fn check_health() -> Vec<SubsystemHealth> {
+    vec![
+        redis_health(),
+        rspamd_health().await,
+        classifier_health("Bayes", BayesManager::new().and_then(|bayes| bayes.is_ready())),
+        classifier_health("Neural", NeuralManager::new().and_then(|neural| neural.is_ready())),
+    ]
+}
+
+/// The `/health` reply: a status line per subsystem.
+pub fn render_health(subsystems: &[SubsystemHealth]) -> String {
+    let mut response = String::from("System health:\n");
+    for subsystem in subsystems {
+        writeln!(&mut response, "{} {}: {}", subsystem.state.icon(), subsystem.name, subsystem.detail).unwrap();
+    }
+    response
+}
+
+/// Handles the /health command
+pub async fn handle_health(bot: Bot, chat_id: ChatId) -> ResponseResult<()> {
+    let subsystems = check_health().await;
+    bot.send_message(chat_id, render_health(&subsystems)).await?;
+    Ok(())
+}
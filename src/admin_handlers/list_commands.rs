@@ -0,0 +1,649 @@
+use crate::config::{import, key};
+use crate::lists;
+use crate::domain_rep::{adjust_domain, domain_score};
+use crate::attachment_spam::normalize_extension;
+use crate::shortener;
+use redis::{Commands, RedisResult};
+use teloxide::types::InputFile;
+use teloxide::net::Download;
+use teloxide::prelude::*;
+use teloxide::types::ChatId;
+
+use regex::Regex;
+
+/// Sends the user and word `list_name` (`"whitelist"` or `"blacklist"`) for
+/// `scope` (the global lists when `None`) as a text file.
+async fn send_list_export(
+    bot: &Bot,
+    chat_id: ChatId,
+    redis_conn: &mut redis::Connection,
+    list_name: &str,
+    users: lists::List,
+    words: lists::List,
+    scope: Option<ChatId>,
+) -> ResponseResult<()> {
+    let export = match lists::export(redis_conn, &[("users", users), ("words", words)], scope) {
+        Ok(export) => export,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Failed to export the {}: {}", list_name, e)).await?;
+            return Ok(());
+        }
+    };
+    let file_name = match scope {
+        Some(scope) => format!("{}_{}.txt", list_name, scope.0),
+        None => format!("{}.txt", list_name),
+    };
+    let caption = match scope {
+        Some(scope) => format!("The {} of chat {}", list_name, scope),
+        None => format!("The global {}", list_name),
+    };
+    bot.send_document(chat_id, InputFile::memory(export.into_bytes()).file_name(file_name))
+        .caption(caption)
+        .await?;
+    Ok(())
+}
+
+/// Shared helper for the whitelist, blacklist and trusted domain logic.
+///
+/// - `bot` / `chat_id`: for sending replies.
+/// - `redis_conn`: mutable connection to Redis.
+/// - `redis_key`: the exact SET key (e.g. `key::TG_WHITELIST_USER_KEY`).
+/// - `item_kind`: `"user"`, `"word"` or `"domain"` (used in reply text).
+/// - `list_name`: `"whitelist"`, `"blacklist"` or `"allowlist"` (used in reply text).
+/// - `action`: must be `"add"`, `"find"` or `"remove"`.
+/// - `target`: the third part of the pattern. If `action` is `"add"` or `"remove"`, it must not be `"*"`;
+///             an added `re:` entry must be a valid regex.
+///             If `action=="find"`, it can be `"*"`, a plain literal, or a Rust‐regex.
+///
+/// This sends the appropriate reply and returns `Ok(())`.
+pub async fn process_set(
+    bot: &Bot,
+    chat_id: ChatId,
+    redis_conn: &mut redis::Connection,
+    redis_key: &str,
+    item_kind: &str,  // "user", "word" or "domain"
+    list_name: &str,  // "whitelist", "blacklist" or "allowlist"
+    action: &str,     // "add", "find" or "remove"
+    target: &str,     // third part of the pattern
+) -> ResponseResult<()> {
+    match action {
+        // ────────────────────────────────────────────────────────────────────
+        "add" => {
+            // You cannot do “add|*”. Must specify exactly one literal (user_id or word).
+            if target == "*" {
+                bot.send_message(
+                    chat_id,
+                    format!(
+                        "Cannot use `*` with `add`. You must specify exactly one {} to add.",
+                        item_kind
+                    ),
+                )
+                    .await?;
+            } else if target.starts_with(lists::PATTERN_PREFIX) && lists::pattern(target).is_none() {
+                bot.send_message(chat_id, format!("`{}` is not a valid regex pattern.", target))
+                    .await?;
+            } else {
+                // Straight SADD
+                let rv: RedisResult<()> = redis_conn.sadd(redis_key, target);
+                match rv {
+                    Ok(()) => {
+                        bot.send_message(
+                            chat_id,
+                            format!("Added {} `{}` to the {}.", item_kind, target, list_name),
+                        )
+                            .await?;
+                    }
+                    Err(e) => {
+                        bot.send_message(
+                            chat_id,
+                            format!("Failed to add {} to {}: {}", item_kind, target, e),
+                        )
+                            .await?;
+                    }
+                }
+            }
+        }
+
+        // ────────────────────────────────────────────────────────────────────
+        "find" => {
+            // 1) If target == "*", list all members via SMEMBERS.
+            if target == "*" {
+                let all_items: Vec<String> =
+                    redis_conn.smembers(redis_key).unwrap_or_else(|_| Vec::new());
+                if all_items.is_empty() {
+                    bot.send_message(chat_id, format!("(no {}ed {}s)", list_name, item_kind))
+                        .await?;
+                } else {
+                    let joined = all_items.join(", ");
+                    bot.send_message(
+                        chat_id,
+                        format!("{}ed {}s: {}",
+                                // Capitalize first letter of list_name for nicer output
+                                {
+                                    let mut s = list_name.to_owned();
+                                    s.get_mut(0..1).map(|c| c.make_ascii_uppercase());
+                                    s
+                                },
+                                item_kind,
+                                joined,
+                        ),
+                    )
+                        .await?;
+                }
+                return Ok(());
+            }
+
+            // 2) If no regex meta‐characters, treat target as a plain literal → SISMEMBER
+            let is_plain_literal = !target.chars().any(|c| {
+                matches!(
+                    c,
+                    '.' | '^' | '$' | '*' | '+' | '?' | '(' | ')' | '[' | ']' | '{' | '}' | '|' | '\\'
+                )
+            });
+
+            if is_plain_literal {
+                let exists: bool = redis_conn.sismember(redis_key, target).unwrap_or(false);
+                if exists {
+                    bot.send_message(
+                        chat_id,
+                        format!(
+                            "{} `{}` is in the {}.",
+                            item_kind, target, list_name
+                        ),
+                    )
+                        .await?;
+                } else {
+                    bot.send_message(
+                        chat_id,
+                        format!(
+                            "{} `{}` is NOT in the {}.",
+                            item_kind, target, list_name
+                        ),
+                    )
+                        .await?;
+                }
+            } else {
+                // 3) Regex case: attempt to compile `target` as a Rust regex,
+                // then SMEMBERS and filter in Rust.
+                let pattern = match Regex::new(target) {
+                    Ok(rgx) => rgx,
+                    Err(e) => {
+                        bot.send_message(
+                            chat_id,
+                            format!("Invalid regex `{}`: {}", target, e),
+                        )
+                            .await?;
+                        return Ok(());
+                    }
+                };
+
+                let all_items: Vec<String> =
+                    redis_conn.smembers(redis_key).unwrap_or_else(|_| Vec::new());
+                let mut matches = Vec::new();
+                for item in all_items.iter() {
+                    if pattern.is_match(item) {
+                        matches.push(item.clone());
+                    }
+                }
+
+                if matches.is_empty() {
+                    bot.send_message(
+                        chat_id,
+                        format!(
+                            "No {}ed {}s match `/ {}`.",
+                            list_name, item_kind, target
+                        ),
+                    )
+                        .await?;
+                } else {
+                    let joined = matches.join(", ");
+                    bot.send_message(
+                        chat_id,
+                        format!(
+                            "{}s matching `/ {}`: {}",
+                            // Capitalize list_name for output
+                            {
+                                let mut s = list_name.to_owned();
+                                s.get_mut(0..1).map(|c| c.make_ascii_uppercase());
+                                s
+                            },
+                            target,
+                            joined
+                        ),
+                    )
+                        .await?;
+                }
+            }
+        }
+
+        // ────────────────────────────────────────────────────────────────────
+        "remove" => {
+            if target == "*" {
+                bot.send_message(
+                    chat_id,
+                    format!(
+                        "Cannot use `*` with `remove`. You must specify exactly one {} to remove.",
+                        item_kind
+                    ),
+                )
+                    .await?;
+            } else {
+                let rv: RedisResult<i64> = redis_conn.srem(redis_key, target);
+                let reply = match rv {
+                    Ok(0) => format!("{} `{}` is NOT in the {}.", item_kind, target, list_name),
+                    Ok(_) => format!("Removed {} `{}` from the {}.", item_kind, target, list_name),
+                    Err(e) => format!("Failed to remove {} from {}: {}", item_kind, target, e),
+                };
+                bot.send_message(chat_id, reply).await?;
+            }
+        }
+
+        _ => {
+            // Should never happen if the caller only passes "add", "find" or "remove"
+            bot.send_message(
+                chat_id,
+                format!("Invalid action `{}`. Must be `add`, `find` or `remove`.", action),
+            )
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles the /importwhitelist command: whitelists every line of the replied-to file
+pub async fn handle_import_whitelist(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    let list = match parts[0] {
+        "user" => Some(lists::WHITELIST_USERS),
+        "word" => Some(lists::WHITELIST_WORDS),
+        _ => None,
+    };
+    let scope = match parts.get(1) {
+        None => Some(None),
+        Some(chat) => chat.parse::<i64>().ok().map(|id| Some(ChatId(id))),
+    };
+    let document = msg.reply_to_message().and_then(|reply| reply.document());
+    let (Some(list), Some(scope), Some(document), 1..=2) = (list, scope, document, parts.len()) else {
+        bot.send_message(
+            chat_id,
+            "Usage: reply to a file with /importwhitelist <user|word>[|<chat_id>]\n\
+         - The file holds one user_id or word per line.\n\
+         - With a chat_id the entries only apply in that chat.",
+        )
+            .await?;
+        return Ok(());
+    };
+    if document.file.size > import::MAX_FILE_BYTES {
+        bot.send_message(
+            chat_id,
+            format!("File is too large, the limit is {} KiB.", import::MAX_FILE_BYTES / 1024),
+        )
+            .await?;
+        return Ok(());
+    }
+
+    let file = bot.get_file(document.file.id.clone()).await?;
+    let mut contents = Vec::new();
+    bot.download_file(&file.path, &mut contents).await?;
+    let Ok(contents) = String::from_utf8(contents) else {
+        bot.send_message(chat_id, "File must be UTF-8 text.").await?;
+        return Ok(());
+    };
+
+    let reply = match list.import(&mut redis_conn, scope, &contents) {
+        Ok(outcome) => format!(
+            "Imported {} {}(s) into the whitelist, skipped {} already listed and {} invalid.",
+            outcome.added, parts[0], outcome.duplicates, outcome.invalid
+        ),
+        Err(e) => format!("Failed to import the whitelist: {}", e),
+    };
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
+
+/// Handles the /whitelistexport command: sends the whitelisted users and words as a file
+pub async fn handle_whitelist_export(bot: Bot, msg: Message, chat: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let scope = match chat.trim() {
+        "" => Some(None),
+        chat => chat.parse::<i64>().ok().map(|id| Some(ChatId(id))),
+    };
+    let Some(scope) = scope else {
+        bot.send_message(chat_id, "Usage: /whitelistexport [chat_id]").await?;
+        return Ok(());
+    };
+    send_list_export(&bot, chat_id, &mut redis_conn, "whitelist", lists::WHITELIST_USERS, lists::WHITELIST_WORDS, scope).await?;
+    Ok(())
+}
+
+/// Handles the /blacklistexport command: sends the blacklisted users and words as a file
+pub async fn handle_blacklist_export(bot: Bot, msg: Message, chat: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let scope = match chat.trim() {
+        "" => Some(None),
+        chat => chat.parse::<i64>().ok().map(|id| Some(ChatId(id))),
+    };
+    let Some(scope) = scope else {
+        bot.send_message(chat_id, "Usage: /blacklistexport [chat_id]").await?;
+        return Ok(());
+    };
+    send_list_export(&bot, chat_id, &mut redis_conn, "blacklist", lists::BLACKLIST_USERS, lists::BLACKLIST_WORDS, scope).await?;
+    Ok(())
+}
+
+/// Handles the /trusteddomain command: manages the domains exempt from link spam checks
+pub async fn handle_trusted_domain(bot: Bot, msg: Message, pattern: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = pattern.split('|').map(str::trim).collect();
+    if parts.len() != 2 {
+        bot.send_message(
+            chat_id,
+            "Usage: /trusteddomain <add|find|remove>|<domain>\n\
+         - Links to a trusted domain (or its subdomains) don't count towards link spam.\n\
+         - If find: target can be '*' (list all),\n\
+           or a plain domain (SISMEMBER),\n\
+           or a Rust‐regex (full regex syntax).",
+        )
+            .await?;
+        return Ok(());
+    }
+
+    // Hosts are matched case-insensitively, so store domains lowercased
+    let (action, target) = (parts[0], parts[1].to_lowercase());
+    process_set(
+        &bot,
+        chat_id,
+        &mut redis_conn,
+        &key::ns(key::TG_TRUSTED_DOMAINS_KEY),
+        "domain",
+        "allowlist",
+        action,
+        &target,
+    )
+        .await?;
+    Ok(())
+}
+
+/// Handles the /riskyext command: manages the file extensions flagged as risky attachments
+pub async fn handle_risky_ext(bot: Bot, msg: Message, pattern: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = pattern.split('|').map(str::trim).collect();
+    if parts.len() != 2 {
+        bot.send_message(
+            chat_id,
+            "Usage: /riskyext <add|find|remove>|<extension>\n\
+         - Documents ending in a listed extension (e.g. apk, exe) get TG_ATTACHMENT_SPAM.\n\
+         - While the list is empty the built-in defaults apply.\n\
+         - If find: target can be '*' (list all),\n\
+           or a plain extension (SISMEMBER),\n\
+           or a Rust‐regex (full regex syntax).",
+        )
+            .await?;
+        return Ok(());
+    }
+
+    let action = parts[0];
+    let target = if action == "find" { parts[1].to_string() } else { normalize_extension(parts[1]) };
+    process_set(
+        &bot,
+        chat_id,
+        &mut redis_conn,
+        &key::ns(key::TG_RISKY_EXTENSIONS_KEY),
+        "extension",
+        "blocklist",
+        action,
+        &target,
+    )
+        .await?;
+    Ok(())
+}
+
+/// Handles the /shortener command: manages the URL shorteners flagged by TG_SHORTENER
+pub async fn handle_shortener(bot: Bot, msg: Message, pattern: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = pattern.split('|').map(str::trim).collect();
+    if parts.len() != 2 {
+        bot.send_message(
+            chat_id,
+            "Usage: /shortener <add|find|remove>|<domain>\n\
+         - Links to a listed domain (or its subdomains) get TG_SHORTENER.\n\
+         - The list starts out with the built-in defaults.\n\
+         - If find: target can be '*' (list all),\n\
+           or a plain domain (SISMEMBER),\n\
+           or a Rust‐regex (full regex syntax).",
+        )
+            .await?;
+        return Ok(());
+    }
+
+    // Start from the defaults, so adding a shortener doesn't drop them
+    if let Err(e) = shortener::seed_defaults(&mut redis_conn) {
+        bot.send_message(chat_id, format!("Failed to seed the default shorteners: {}", e)).await?;
+        return Ok(());
+    }
+    let action = parts[0];
+    let target = if action == "find" { parts[1].to_string() } else { shortener::normalize_domain(parts[1]) };
+    process_set(
+        &bot,
+        chat_id,
+        &mut redis_conn,
+        &key::ns(key::TG_SHORTENERS_KEY),
+        "domain",
+        "shortener list",
+        action,
+        &target,
+    )
+        .await?;
+    Ok(())
+}
+
+/// Handles the /domainrep command: shows or adjusts a domain's reputation
+pub async fn handle_domain_rep(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let mut parts = args.splitn(2, '|').map(str::trim);
+    let domain = parts
+        .next()
+        .map(|d| d.trim_end_matches('.').to_lowercase())
+        .filter(|d| !d.is_empty());
+    let delta = match parts.next() {
+        None => Some(None),
+        Some(delta) => delta.parse::<f64>().ok().filter(|d| d.is_finite()).map(Some),
+    };
+    let (Some(domain), Some(delta)) = (domain, delta) else {
+        bot.send_message(
+            chat_id,
+            "Usage: /domainrep <domain>[|<delta>]\n\
+         - Without a delta, shows the domain's reputation.\n\
+         - A positive delta makes links to the domain more suspicious, a negative one less.",
+        )
+            .await?;
+        return Ok(());
+    };
+
+    let result = match delta {
+        None => domain_score(&mut redis_conn, &domain),
+        Some(delta) => adjust_domain(&mut redis_conn, &domain, delta),
+    };
+    let reply = match result {
+        Ok(score) => format!("Reputation of `{}`: {}", domain, score),
+        Err(e) => format!("Failed to update reputation of `{}`: {}", domain, e),
+    };
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
+
+/// Handles the /whitelist command: adds or finds whitelisted users and words
+pub async fn handle_whitelist(bot: Bot, msg: Message, pattern: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    // kind|action|target, optionally followed by a chat id to scope the entry to that chat
+    let parts: Vec<&str> = pattern.split('|').map(str::trim).collect();
+    let scope = match parts.get(3) {
+        None => Some(None),
+        Some(chat) => chat.parse::<i64>().ok().map(|id| Some(ChatId(id))),
+    };
+    let (Some(scope), 3..=4) = (scope, parts.len()) else {
+        bot.send_message(
+            chat_id,
+            "Usage: /whitelist <user|word>|<add|find>|<target>[|<chat_id>]\n\
+         - If add: target must be the literal user_id or word,\n\
+           or re:<regex> to match usernames or words.\n\
+         - If find: target can be '*' (list all),\n\
+           or a plain string (SISMEMBER),\n\
+           or a Rust‐regex (full regex syntax).\n\
+         - With a chat_id the entry only applies in that chat.",
+        )
+            .await?;
+        return Ok(());
+    };
+
+    let (kind, action, target) = (parts[0], parts[1], parts[2]);
+
+    // Dispatch to the shared helper with the correct Redis key
+    match kind {
+        "user" => {
+            process_set(
+                &bot,
+                chat_id,
+                &mut redis_conn,
+                &lists::WHITELIST_USERS.key(scope),
+                "user",
+                "whitelist",
+                action,
+                target,
+            )
+                .await?;
+        }
+        "word" => {
+            process_set(
+                &bot,
+                chat_id,
+                &mut redis_conn,
+                &lists::WHITELIST_WORDS.key(scope),
+                "word",
+                "whitelist",
+                action,
+                target,
+            )
+                .await?;
+        }
+        _ => {
+            bot.send_message(
+                chat_id,
+                "First part must be `user` or `word`. Usage: /whitelist <user|word>|<add|find>|<target>[|<chat_id>]",
+            )
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /blacklist command: adds or finds blacklisted users and words
+pub async fn handle_blacklist(bot: Bot, msg: Message, pattern: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    // Exactly the same parsing, but pass in the BLACKLIST key
+    let parts: Vec<&str> = pattern.split('|').map(str::trim).collect();
+    let scope = match parts.get(3) {
+        None => Some(None),
+        Some(chat) => chat.parse::<i64>().ok().map(|id| Some(ChatId(id))),
+    };
+    let (Some(scope), 3..=4) = (scope, parts.len()) else {
+        bot.send_message(
+            chat_id,
+            "Usage: /blacklist <user|word>|<add|find>|<target>[|<chat_id>]\n\
+         - If add: target must be the literal user_id or word,\n\
+           or re:<regex> to match usernames or words.\n\
+         - If find: target can be '*' (list all),\n\
+           or a plain string (SISMEMBER),\n\
+           or a Rust‐regex (full regex syntax).\n\
+         - With a chat_id the entry only applies in that chat.",
+        )
+            .await?;
+        return Ok(());
+    };
+
+    let (kind, action, target) = (parts[0], parts[1], parts[2]);
+
+    match kind {
+        "user" => {
+            process_set(
+                &bot,
+                chat_id,
+                &mut redis_conn,
+                &lists::BLACKLIST_USERS.key(scope),
+                "user",
+                "blacklist",
+                action,
+                target,
+            )
+                .await?;
+        }
+        "word" => {
+            process_set(
+                &bot,
+                chat_id,
+                &mut redis_conn,
+                &lists::BLACKLIST_WORDS.key(scope),
+                "word",
+                "blacklist",
+                action,
+                target,
+            )
+                .await?;
+        }
+        _ => {
+            bot.send_message(
+                chat_id,
+                "First part must be `user` or `word`. Usage: /blacklist <user|word>|<add|find>|<target>[|<chat_id>]",
+            )
+                .await?;
+        }
+    }
+    Ok(())
+}
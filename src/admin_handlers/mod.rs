@@ -1,9 +1,47 @@
 mod admin;
+pub mod appeal_commands;
+pub mod bayes_commands;
+pub mod command_permissions;
 pub mod commands;
+pub mod diagnose_commands;
 pub mod dispatcher;
+pub mod fuzzy_commands;
+pub mod health_commands;
+pub mod list_commands;
+pub mod moderation_commands;
 pub mod neural_commands;
+pub mod panel_commands;
+pub mod purge_commands;
+pub mod raid_commands;
+pub mod report_commands;
+pub mod reset_commands;
+pub mod search_commands;
+pub mod settings_commands;
+pub mod stats_commands;
+pub mod symbol_commands;
+pub mod trust_commands;
+pub mod user_commands;
 
 pub use admin::*;
+pub use appeal_commands::*;
+pub use bayes_commands::*;
+pub use command_permissions::*;
+pub use diagnose_commands::*;
 pub use dispatcher::*;
-pub use self::commands::AdminCommand;
+pub use self::commands::{admin_command_menu, member_command_menu, AdminCommand};
+pub use fuzzy_commands::*;
+pub use health_commands::*;
+pub use list_commands::*;
+pub use moderation_commands::*;
 pub use neural_commands::*;
+pub use panel_commands::*;
+pub use purge_commands::*;
+pub use raid_commands::*;
+pub use report_commands::*;
+pub use reset_commands::*;
+pub use search_commands::*;
+pub use settings_commands::*;
+pub use stats_commands::*;
+pub use symbol_commands::*;
+pub use trust_commands::*;
+pub use user_commands::*;
@@ -0,0 +1,241 @@
+use crate::admin_handlers::{format_timestamp, lookup_username, moderates_chat};
+use crate::config::{ban_list, field, key, mute};
+use crate::lockdown::{activate_lockdown, end_lockdown, lockdown_since};
+use crate::ban_manager::banned_users;
+use crate::mutes::{mute_user, muted_users, unmute_user};
+use redis::Commands;
+use std::fmt::Write;
+use teloxide::prelude::*;
+use teloxide::types::{ChatId, UserId};
+
+/// Handles the /lockdown command: locks a chat down, lifts the lockdown or shows its status
+pub async fn handle_lockdown(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    let target_chat = match parts.get(1) {
+        None => Some(chat_id),
+        Some(chat) => chat.parse::<i64>().ok().map(ChatId),
+    };
+    let (Some(mode @ ("on" | "off" | "status")), Some(target_chat)) = (parts.first().copied(), target_chat) else {
+        bot.send_message(
+            chat_id,
+            "Usage: /lockdown <on|off|status>[|<chat_id>]\n\
+         - on: mute every member who joins from now on.\n\
+         - off: lift the lockdown and unmute the members it muted.",
+        ).await?;
+        return Ok(());
+    };
+    // Anyone passes the admin check in a private chat, so only admins of the target may lock it down
+    if !moderates_chat(&mut redis_conn, &msg, target_chat).unwrap_or(false) {
+        bot.send_message(chat_id, format!("You are not an admin of chat {}.", target_chat)).await?;
+        return Ok(());
+    }
+
+    let response = match mode {
+        "on" => match activate_lockdown(&mut redis_conn, target_chat, chrono::Utc::now().timestamp()) {
+            Ok(true) => format!("Chat {} is locked down; new members will be muted until /lockdown off.", target_chat),
+            Ok(false) => format!("Chat {} is already locked down.", target_chat),
+            Err(e) => format!("Failed to lock chat {} down: {}", target_chat, e),
+        },
+        "off" => match end_lockdown(&bot, &mut redis_conn, target_chat).await {
+            Ok(unmuted) => format!("Lifted the lockdown of chat {}; unmuted {} member(s).", target_chat, unmuted),
+            Err(e) => format!("Failed to lift the lockdown of chat {}: {}", target_chat, e),
+        },
+        _ => match lockdown_since(&mut redis_conn, target_chat) {
+            Some(since) => format!("Chat {} is locked down since {}.", target_chat, format_timestamp(Some(&since.to_string()))),
+            None => format!("Chat {} is not locked down.", target_chat),
+        },
+    };
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /banlist command: lists the users banned in a chat, a page at a time
+pub async fn handle_ban_list(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let mut parts = args.splitn(2, '|').map(str::trim);
+    let target_chat = match parts.next().filter(|c| !c.is_empty()) {
+        None => Some(chat_id.0),
+        Some(chat) => chat.parse::<i64>().ok(),
+    };
+    let page = match parts.next().filter(|p| !p.is_empty()) {
+        None => Some(1),
+        Some(page) => page.parse::<usize>().ok().filter(|p| *p > 0),
+    };
+    let (Some(target_chat), Some(page)) = (target_chat, page) else {
+        bot.send_message(chat_id, "Usage: /banlist [chat_id][|<page>]").await?;
+        return Ok(());
+    };
+
+    let users = match banned_users(&mut redis_conn, ChatId(target_chat)) {
+        Ok(users) => users,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Failed to list banned users: {}", e)).await?;
+            return Ok(());
+        }
+    };
+    let chat_name: String = redis_conn
+        .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), target_chat), field::NAME)
+        .unwrap_or_else(|_| target_chat.to_string());
+
+    let pages = users.len().div_ceil(ban_list::PAGE_SIZE).max(1);
+    let mut response = String::new();
+    if users.is_empty() {
+        writeln!(&mut response, "No banned users in chat: {}", chat_name).unwrap();
+    } else if page > pages {
+        writeln!(&mut response, "Page {} is past the end ({} pages).", page, pages).unwrap();
+    } else {
+        writeln!(&mut response, "Banned users in chat: {} (page {}/{})", chat_name, page, pages).unwrap();
+        for user in users.iter().skip((page - 1) * ban_list::PAGE_SIZE).take(ban_list::PAGE_SIZE) {
+            let name = user.username.as_ref().map(|u| format!(" @{}", u)).unwrap_or_default();
+            writeln!(&mut response, "{}{} – bans: {}, reputation: {}", user.user_id, name, user.ban_count, user.rep).unwrap();
+        }
+        if page < pages {
+            writeln!(&mut response, "Next page: /banlist {}|{}", target_chat, page + 1).unwrap();
+        }
+    }
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /mutelist command: lists the users muted in a chat
+pub async fn handle_mute_list(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let target_chat = match args.trim() {
+        "" => Some(chat_id),
+        chat => chat.parse::<i64>().ok().map(ChatId),
+    };
+    let Some(target_chat) = target_chat else {
+        bot.send_message(chat_id, "Usage: /mutelist [chat_id]").await?;
+        return Ok(());
+    };
+
+    let users = match muted_users(&mut redis_conn) {
+        Ok(users) => users,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Failed to list muted users: {}", e)).await?;
+            return Ok(());
+        }
+    };
+    let chat_name: String = redis_conn
+        .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), target_chat.0), field::NAME)
+        .unwrap_or_else(|_| target_chat.to_string());
+
+    let mut response = String::new();
+    let users: Vec<_> = users.into_iter().filter(|u| u.chat_id == target_chat).collect();
+    if users.is_empty() {
+        writeln!(&mut response, "No muted users in chat: {}", chat_name).unwrap();
+    } else {
+        writeln!(&mut response, "Muted users in chat: {}", chat_name).unwrap();
+        for user in &users {
+            let name = user.username.as_ref().map(|u| format!(" @{}", u)).unwrap_or_default();
+            let until = chrono::DateTime::from_timestamp(user.muted_until, 0)
+                .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_else(|| user.muted_until.to_string());
+            writeln!(&mut response, "{}{} – until {}", user.user_id, name, until).unwrap();
+        }
+    }
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /mute command: makes a user read-only in a chat for a number of minutes
+pub async fn handle_mute(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    let target = parts.first().and_then(|user| match user.parse::<u64>() {
+        Ok(id) => Some(UserId(id)),
+        Err(_) => lookup_username(&mut redis_conn, user),
+    });
+    let minutes = parts.get(1).and_then(|m| m.parse::<i64>().ok()).filter(|m| (1..=mute::MAX_MINUTES).contains(m));
+    let target_chat = match parts.get(2) {
+        None => Some(chat_id),
+        Some(chat) => chat.parse::<i64>().ok().map(ChatId),
+    };
+    let (Some(target), Some(minutes), Some(target_chat)) = (target, minutes, target_chat) else {
+        bot.send_message(
+            chat_id,
+            format!("Usage: /mute <user_id|@username>|<minutes>[|<chat_id>] (1-{} minutes)", mute::MAX_MINUTES),
+        ).await?;
+        return Ok(());
+    };
+    // Anyone passes the admin check in a private chat, so only admins of the target may mute its members
+    if !moderates_chat(&mut redis_conn, &msg, target_chat).unwrap_or(false) {
+        bot.send_message(chat_id, format!("You are not an admin of chat {}.", target_chat)).await?;
+        return Ok(());
+    }
+
+    let reply = match mute_user(&bot, &mut redis_conn, target_chat, target, minutes).await {
+        Ok(until) => format!(
+            "User {} is read-only in chat {} until {}.",
+            target, target_chat, until.format("%Y-%m-%d %H:%M UTC")
+        ),
+        Err(e) => format!("Failed to mute user {}: {}", target, e),
+    };
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
+
+/// Handles the /unmute command: gives a muted user their permissions back
+pub async fn handle_unmute(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    let target = parts.first().and_then(|user| match user.parse::<u64>() {
+        Ok(id) => Some(UserId(id)),
+        Err(_) => lookup_username(&mut redis_conn, user),
+    });
+    let target_chat = match parts.get(1) {
+        None => Some(None),
+        Some(chat) => chat.parse::<i64>().ok().map(|c| Some(ChatId(c))),
+    };
+    let (Some(target), Some(target_chat)) = (target, target_chat) else {
+        bot.send_message(chat_id, "Usage: /unmute <user_id|@username>[|<chat_id>]").await?;
+        return Ok(());
+    };
+    // Without a chat, lift the mute where it was issued, falling back to this chat
+    let target_chat = match target_chat {
+        Some(chat) => chat,
+        None => redis_conn
+            .hget::<_, _, Option<i64>>(format!("{}{}", key::ns(key::TG_USERS_PREFIX), target), field::MUTED_IN)
+            .ok()
+            .flatten()
+            .map(ChatId)
+            .unwrap_or(chat_id),
+    };
+    if !moderates_chat(&mut redis_conn, &msg, target_chat).unwrap_or(false) {
+        bot.send_message(chat_id, format!("You are not an admin of chat {}.", target_chat)).await?;
+        return Ok(());
+    }
+
+    let reply = match unmute_user(&bot, &mut redis_conn, target_chat, target).await {
+        Ok(()) => format!("User {} can write in chat {} again.", target, target_chat),
+        Err(e) => format!("Failed to unmute user {}: {}", target, e),
+    };
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
@@ -1,7 +1,7 @@
 use teloxide::prelude::*;
 use teloxide::types::ParseMode;
-use crate::neural_manager::NeuralManager;
-use crate::config::neural;
+use crate::neural_manager::{NeuralManager, TrainOutcome};
+use crate::config::{key, neural};
 use redis::Commands;
 
 use anyhow::Result;
@@ -90,6 +90,43 @@ pub async fn handle_neural_reset(bot: Bot, chat_id: ChatId) -> Result<(), Box<dy
     Ok(())
 }
 
+/// Handles the /neuraltrain command to train the neural network on collected samples
+pub async fn handle_neural_train(bot: Bot, chat_id: ChatId) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let neural_manager = NeuralManager::new()?;
+    
+    let response = match neural_manager.train() {
+        Ok(outcome) => format_train_outcome(&outcome),
+        Err(e) => format!("❌ Neural network training failed: {}", e),
+    };
+    
+    bot.send_message(chat_id, response).await?;
+    
+    Ok(())
+}
+
+/// Helper function to format the result of a training run as a plain-text reply
+pub fn format_train_outcome(outcome: &TrainOutcome) -> String {
+    match outcome {
+        TrainOutcome::NotReady { samples, missing } => format!(
+            "🔄 Not Enough Training Data\n\n\
+            Samples: {}/{}\n\
+            Still Needed: {}",
+            samples,
+            neural::MIN_SAMPLES_REQUIRED,
+            missing
+        ),
+        TrainOutcome::Trained { samples, iterations, accuracy } => format!(
+            "✅ Neural Network Trained\n\n\
+            Samples Used: {}\n\
+            Training Iterations: {}\n\
+            Model Accuracy: {:.1}%",
+            samples,
+            iterations,
+            accuracy * 100.0
+        ),
+    }
+}
+
 /// Handles the /neuralstatus command to show detailed neural network training status
 pub async fn handle_neural_status(bot: Bot, chat_id: ChatId) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let neural_manager = NeuralManager::new()?;
@@ -160,7 +197,7 @@ pub async fn handle_neural_features(bot: Bot, chat_id: ChatId, message_id: Strin
     let mut conn = redis_client.get_connection()?;
     
     // Try to get the message content from Redis
-    let message_key = format!("message:{}", message_id);
+    let message_key = key::ns(format!("message:{}", message_id));
     let message_content: Option<String> = conn.get(&message_key).ok();
     
     if let Some(content) = message_content {
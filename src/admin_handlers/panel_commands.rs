@@ -0,0 +1,556 @@
+use std::fmt::Write;
+use redis::{Commands, RedisResult};
+use serde::{Deserialize, Serialize};
+use teloxide::net::Download;
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, InputFile};
+use crate::admin_handlers::{admin_panel_user, command_access, format_timestamp, is_admin_panel_setup, lookup_username, moderates_any_chat, AdminCommand, CommandAccess};
+use crate::admin_panel::config::{key as panel_key, settings};
+use crate::admin_panel::permissions::{AdminPermission, AdminUser, PermissionGroup};
+use crate::config::{field, import, key, suffix};
+use crate::config_backup::{export_config, import_config, ConfigSnapshot};
+
+/// Prefix of the /listadmins page buttons' callback data: `listadmins:<page>`
+pub const LIST_ADMINS_CALLBACK: &str = "listadmins:";
+/// Prefix of the /monitoredchats page buttons' callback data: `monitoredchats:<page>`
+pub const MONITORED_CHATS_CALLBACK: &str = "monitoredchats:";
+
+/// An action taken through the admin panel, as stored in its audit log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    /// Admin who took the action.
+    pub user_id: UserId,
+    pub user_name: String,
+    pub action: String,
+    pub details: Option<String>,
+}
+
+impl AuditLogEntry {
+    /// An entry for an action `msg`'s sender took just now.
+    fn new(msg: &Message, action: &str, details: String) -> Self {
+        let (user_id, user_name) = msg
+            .from
+            .as_ref()
+            .map_or((UserId(0), String::new()), |user| (user.id, user.full_name()));
+        Self { timestamp: chrono::Utc::now(), user_id, user_name, action: action.to_string(), details: Some(details) }
+    }
+}
+
+/// Adds `entry` to the audit log, keeping the newest
+/// `settings::MAX_AUDIT_LOG_ENTRIES`.
+pub fn record_audit_entry(conn: &mut redis::Connection, entry: &AuditLogEntry) -> RedisResult<()> {
+    let log_key = key::ns(panel_key::ADMIN_PANEL_AUDIT_LOG_KEY);
+    let data = serde_json::to_string(entry).expect("AuditLogEntry always serializes");
+    redis::pipe()
+        .lpush(&log_key, data).ignore()
+        .ltrim(&log_key, 0, settings::MAX_AUDIT_LOG_ENTRIES as isize - 1).ignore()
+        .query(conn)
+}
+
+/// Audit log entries made at or after `since`, newest first.
+pub fn audit_entries_since(conn: &mut redis::Connection, since: chrono::DateTime<chrono::Utc>) -> RedisResult<Vec<AuditLogEntry>> {
+    let data: Vec<String> = conn.lrange(key::ns(panel_key::ADMIN_PANEL_AUDIT_LOG_KEY), 0, -1)?;
+    let mut entries: Vec<AuditLogEntry> = data
+        .iter()
+        .filter_map(|data| serde_json::from_str::<AuditLogEntry>(data).ok())
+        .filter(|entry| entry.timestamp >= since)
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.timestamp));
+    Ok(entries)
+}
+
+/// `entries` as JSON Lines, one entry per line.
+pub fn export_audit_log_jsonl(entries: &[AuditLogEntry]) -> String {
+    let mut export = String::new();
+    for entry in entries {
+        writeln!(&mut export, "{}", serde_json::to_string(entry).expect("AuditLogEntry always serializes")).unwrap();
+    }
+    export
+}
+
+/// Stores `admin` as an admin panel member, replacing their earlier entry.
+pub fn save_panel_admin(conn: &mut redis::Connection, admin: &AdminUser) -> RedisResult<()> {
+    let data = serde_json::to_string(admin).expect("AdminUser always serializes");
+    redis::pipe()
+        .sadd(key::ns(panel_key::ADMIN_PANEL_MEMBERS_KEY), admin.user_id.0.to_string()).ignore()
+        .hset(key::ns(panel_key::ADMIN_PANEL_PERMISSIONS_KEY), admin.user_id.0.to_string(), data).ignore()
+        .query(conn)
+}
+
+/// Removes `user_id` from the admin panel. Returns whether they were a member.
+pub fn remove_panel_admin(conn: &mut redis::Connection, user_id: UserId) -> RedisResult<bool> {
+    let removed: usize = conn.srem(key::ns(panel_key::ADMIN_PANEL_MEMBERS_KEY), user_id.0.to_string())?;
+    let _: () = conn.hdel(key::ns(panel_key::ADMIN_PANEL_PERMISSIONS_KEY), user_id.0.to_string())?;
+    Ok(removed > 0)
+}
+
+/// Resolves a `<user_id|@username>` argument through the username index.
+fn resolve_panel_user(conn: &mut redis::Connection, user: &str) -> Option<UserId> {
+    match user.trim().parse::<u64>() {
+        Ok(id) => Some(UserId(id)),
+        Err(_) => lookup_username(conn, user.trim()),
+    }
+}
+
+/// Handles the /addadmin command: adds a user to the admin panel with a
+/// permission group, Viewer unless another one is given
+pub async fn handle_add_admin(bot: Bot, msg: Message, user_id: UserId, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let (user, group) = args.split_once('|').unwrap_or((args.as_str(), "viewer"));
+    let group = PermissionGroup::from_string(group.trim());
+    let (Some(target), Some(group)) = (resolve_panel_user(&mut redis_conn, user), group) else {
+        bot.send_message(chat_id, "Usage: /addadmin <user_id|@username>[|viewer|moderator|manager|administrator]")
+            .await?;
+        return Ok(());
+    };
+    if matches!(admin_panel_user(&mut redis_conn, target), Ok(Some(_))) {
+        bot.send_message(chat_id, format!("User {} already is an admin panel member, use /setpermissions to change their permissions.", target))
+            .await?;
+        return Ok(());
+    }
+
+    let username = user.trim().strip_prefix('@').map(str::to_string);
+    let display_name = username.clone().unwrap_or_else(|| target.to_string());
+    let mut admin = AdminUser::new(target, username, display_name, user_id);
+    for permission in group.permissions() {
+        admin.add_permission(permission);
+    }
+    let reply = match save_panel_admin(&mut redis_conn, &admin) {
+        Ok(()) => {
+            let details = format!("Added user {} as {}", target, group.display_name());
+            let _ = record_audit_entry(&mut redis_conn, &AuditLogEntry::new(&msg, "Add Admin User", details));
+            format!("Added user {} to the admin panel as {}.", target, group.display_name())
+        }
+        Err(e) => format!("Failed to add the admin: {}", e),
+    };
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
+
+/// Handles the /removeadmin command: removes a user from the admin panel
+pub async fn handle_remove_admin(bot: Bot, msg: Message, user_id: UserId, user: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let Some(target) = resolve_panel_user(&mut redis_conn, &user) else {
+        bot.send_message(chat_id, "Usage: /removeadmin <user_id|@username>").await?;
+        return Ok(());
+    };
+    if target == user_id {
+        bot.send_message(chat_id, "You cannot remove yourself from the admin panel.").await?;
+        return Ok(());
+    }
+
+    let reply = match remove_panel_admin(&mut redis_conn, target) {
+        Ok(true) => {
+            let details = format!("Removed user {}", target);
+            let _ = record_audit_entry(&mut redis_conn, &AuditLogEntry::new(&msg, "Remove Admin User", details));
+            format!("Removed user {} from the admin panel.", target)
+        }
+        Ok(false) => format!("User {} is not an admin panel member.", target),
+        Err(e) => format!("Failed to remove the admin: {}", e),
+    };
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
+
+/// Handles the /setpermissions command: replaces the permissions of an
+/// admin panel member
+pub async fn handle_set_permissions(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let (user, permissions) = args.split_once('|').unwrap_or((args.as_str(), ""));
+    let permissions: Vec<AdminPermission> = permissions
+        .split(',')
+        .filter_map(|permission| AdminPermission::from_string(permission.trim()))
+        .collect();
+    let (Some(target), false) = (resolve_panel_user(&mut redis_conn, user), permissions.is_empty()) else {
+        bot.send_message(chat_id, "Usage: /setpermissions <user_id|@username>|<permission>,<permission>,... e.g. view_stats,manage_chats")
+            .await?;
+        return Ok(());
+    };
+    let Ok(Some(mut admin)) = admin_panel_user(&mut redis_conn, target) else {
+        bot.send_message(chat_id, format!("User {} is not an admin panel member, add them with /addadmin first.", target))
+            .await?;
+        return Ok(());
+    };
+
+    admin.permissions = permissions.iter().cloned().collect();
+    let listed: Vec<&str> = permissions.iter().map(AdminPermission::as_str).collect();
+    let reply = match save_panel_admin(&mut redis_conn, &admin) {
+        Ok(()) => {
+            let details = format!("Set the permissions of user {} to {}", target, listed.join(", "));
+            let _ = record_audit_entry(&mut redis_conn, &AuditLogEntry::new(&msg, "Update Permissions", details));
+            format!("Permissions of user {}: {}", target, listed.join(", "))
+        }
+        Err(e) => format!("Failed to update the permissions: {}", e),
+    };
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
+
+/// Every admin panel member, by user id.
+pub fn panel_admins(conn: &mut redis::Connection) -> RedisResult<Vec<AdminUser>> {
+    let data: Vec<String> = conn.hvals(key::ns(panel_key::ADMIN_PANEL_PERMISSIONS_KEY))?;
+    let mut admins: Vec<AdminUser> = data.iter().filter_map(|data| serde_json::from_str(data).ok()).collect();
+    admins.sort_by_key(|admin| admin.user_id.0);
+    Ok(admins)
+}
+
+/// The chats moderated from `admin_chat` as `(chat_id, name)`, by chat id.
+pub fn monitored_chats(conn: &mut redis::Connection, admin_chat: ChatId) -> RedisResult<Vec<(i64, Option<String>)>> {
+    let mut chats: Vec<i64> = conn.smembers(format!("{}{}{}", key::ns(key::ADMIN_PREFIX), admin_chat.0, suffix::MODERATED_CHATS))?;
+    chats.sort_unstable();
+    let mut pipe = redis::pipe();
+    for chat in &chats {
+        pipe.hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat), field::NAME);
+    }
+    let names: Vec<Option<String>> = pipe.query(conn)?;
+    Ok(chats.into_iter().zip(names).collect())
+}
+
+/// The entries on `page` (counted from 0) and the number of pages, at
+/// `settings::PAGE_SIZE` entries a page. Pages past the end show the last one.
+pub fn paginate<T>(items: &[T], page: usize) -> (&[T], usize) {
+    let pages = items.len().div_ceil(settings::PAGE_SIZE).max(1);
+    let start = page.min(pages - 1) * settings::PAGE_SIZE;
+    let end = (start + settings::PAGE_SIZE).min(items.len());
+    (&items[start..end], pages)
+}
+
+/// Prev/Next buttons for `page` of a list whose buttons' callback data starts with `prefix`.
+fn page_keyboard(prefix: &str, page: usize, pages: usize) -> InlineKeyboardMarkup {
+    let mut row = Vec::new();
+    if page > 0 {
+        row.push(InlineKeyboardButton::callback("« Prev", format!("{}{}", prefix, page - 1)));
+    }
+    if page + 1 < pages {
+        row.push(InlineKeyboardButton::callback("Next »", format!("{}{}", prefix, page + 1)));
+    }
+    if row.is_empty() {
+        InlineKeyboardMarkup::default()
+    } else {
+        InlineKeyboardMarkup::new(vec![row])
+    }
+}
+
+/// Renders `page` of the /listadmins list with its Prev/Next buttons.
+pub fn render_admin_list_page(admins: &[AdminUser], page: usize) -> (String, InlineKeyboardMarkup) {
+    let (entries, pages) = paginate(admins, page);
+    let page = page.min(pages - 1);
+    let mut response = String::new();
+    if admins.is_empty() {
+        writeln!(&mut response, "The admin panel has no members.").unwrap();
+    } else {
+        writeln!(&mut response, "Admin panel members (page {}/{}):", page + 1, pages).unwrap();
+        for admin in entries {
+            let name = admin.username.as_ref().map(|u| format!(" @{}", u)).unwrap_or_default();
+            let mut permissions: Vec<&str> = admin.permissions.iter().map(AdminPermission::as_str).collect();
+            permissions.sort_unstable();
+            writeln!(&mut response, "{}{} – {}", admin.user_id, name, permissions.join(", ")).unwrap();
+        }
+    }
+    (response, page_keyboard(LIST_ADMINS_CALLBACK, page, pages))
+}
+
+/// Renders `page` of the /monitoredchats list with its Prev/Next buttons.
+pub fn render_monitored_chats_page(chats: &[(i64, Option<String>)], page: usize) -> (String, InlineKeyboardMarkup) {
+    let (entries, pages) = paginate(chats, page);
+    let page = page.min(pages - 1);
+    let mut response = String::new();
+    if chats.is_empty() {
+        writeln!(&mut response, "No chats are moderated from this chat.").unwrap();
+    } else {
+        writeln!(&mut response, "Moderated chats (page {}/{}):", page + 1, pages).unwrap();
+        for (chat_id, name) in entries {
+            match name {
+                Some(name) => writeln!(&mut response, "{} – {}", chat_id, name).unwrap(),
+                None => writeln!(&mut response, "{}", chat_id).unwrap(),
+            }
+        }
+    }
+    (response, page_keyboard(MONITORED_CHATS_CALLBACK, page, pages))
+}
+
+/// Handles the /listadmins command: lists the admin panel members a page at a time
+pub async fn handle_list_admins(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    match panel_admins(&mut redis_conn) {
+        Ok(admins) => {
+            let (response, keyboard) = render_admin_list_page(&admins, 0);
+            bot.send_message(chat_id, response).reply_markup(keyboard).await?;
+        }
+        Err(e) => {
+            bot.send_message(chat_id, format!("Failed to list the admins: {}", e)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /monitoredchats command: lists the chats moderated from this
+/// admin chat a page at a time
+pub async fn handle_monitored_chats(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    match monitored_chats(&mut redis_conn, chat_id) {
+        Ok(chats) => {
+            let (response, keyboard) = render_monitored_chats_page(&chats, 0);
+            bot.send_message(chat_id, response).reply_markup(keyboard).await?;
+        }
+        Err(e) => {
+            bot.send_message(chat_id, format!("Failed to list the moderated chats: {}", e)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Called when a Prev/Next button of /listadmins or /monitoredchats is pressed
+pub async fn panel_page_handler(bot: Bot, query: CallbackQuery) -> ResponseResult<()> {
+    let (Some(data), Some(callback_msg)) = (query.data.as_deref(), query.message.as_ref()) else {
+        return Ok(());
+    };
+    let (cmd, page) = if let Some(page) = data.strip_prefix(LIST_ADMINS_CALLBACK) {
+        (AdminCommand::ListAdmins, page)
+    } else if let Some(page) = data.strip_prefix(MONITORED_CHATS_CALLBACK) {
+        (AdminCommand::MonitoredChats, page)
+    } else {
+        return Ok(());
+    };
+    let page = page.parse::<usize>().unwrap_or(0);
+
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    // The buttons are as restricted as the command that sent them
+    if let Ok(CommandAccess::Denied(permission)) = command_access(&mut redis_conn, query.from.id, &cmd) {
+        bot.answer_callback_query(query.id.clone())
+            .text(format!("This needs the \"{}\" admin panel permission.", permission))
+            .await?;
+        return Ok(());
+    }
+
+    let page = match cmd {
+        AdminCommand::ListAdmins => panel_admins(&mut redis_conn).map(|admins| render_admin_list_page(&admins, page)),
+        _ => monitored_chats(&mut redis_conn, callback_msg.chat().id).map(|chats| render_monitored_chats_page(&chats, page)),
+    };
+    bot.answer_callback_query(query.id.clone()).await?;
+    match page {
+        Ok((response, keyboard)) => {
+            bot.edit_message_text(callback_msg.chat().id, callback_msg.id(), response)
+                .reply_markup(keyboard)
+                .await?;
+        }
+        Err(e) => {
+            bot.send_message(callback_msg.chat().id, format!("Failed to load the page: {}", e)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /exportauditlog command: sends the audit log entries of the
+/// last hours (default: 24) as a JSON Lines document
+pub async fn handle_export_audit_log(bot: Bot, msg: Message, hours: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let hours = match hours.trim() {
+        "" => Some(24),
+        hours => hours.parse::<u32>().ok().filter(|hours| *hours > 0),
+    };
+    let Some(hours) = hours else {
+        bot.send_message(chat_id, "Usage: /exportauditlog [hours]").await?;
+        return Ok(());
+    };
+
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let since = chrono::Utc::now() - chrono::Duration::hours(hours.into());
+    let entries = match audit_entries_since(&mut redis_conn, since) {
+        Ok(entries) => entries,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Failed to read the audit log: {}", e)).await?;
+            return Ok(());
+        }
+    };
+    if entries.is_empty() {
+        bot.send_message(chat_id, format!("No audit log entries in the last {} hours.", hours)).await?;
+        return Ok(());
+    }
+
+    let file_name = format!("audit_log_{}.jsonl", chrono::Utc::now().format("%Y%m%d_%H%M%S"));
+    bot.send_document(chat_id, InputFile::memory(export_audit_log_jsonl(&entries).into_bytes()).file_name(file_name))
+        .caption(format!("The audit log of the last {} hours: {} entries", hours, entries.len()))
+        .await?;
+    Ok(())
+}
+
+/// When the emergency stop started, if one is active.
+pub fn emergency_stop_since(conn: &mut redis::Connection) -> Option<i64> {
+    conn.get(key::ns(key::EMERGENCY_STOP_KEY)).ok().flatten()
+}
+
+/// Stops all scanning and moderation from `now` on. Returns false if an
+/// emergency stop already was active.
+pub fn start_emergency_stop(conn: &mut redis::Connection, now: i64) -> RedisResult<bool> {
+    conn.set_nx(key::ns(key::EMERGENCY_STOP_KEY), now)
+}
+
+/// Resumes scanning and moderation. Returns false if no emergency stop was active.
+pub fn end_emergency_stop(conn: &mut redis::Connection) -> RedisResult<bool> {
+    conn.del(key::ns(key::EMERGENCY_STOP_KEY))
+}
+
+/// Handles the /emergencystop command: stops or resumes scanning and
+/// moderation in every chat
+pub async fn handle_emergency_stop(bot: Bot, msg: Message, mode: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let response = match mode.trim() {
+        "on" => match start_emergency_stop(&mut redis_conn, chrono::Utc::now().timestamp()) {
+            Ok(true) => {
+                let entry = AuditLogEntry::new(&msg, "Emergency Stop", "All monitoring has been stopped".to_string());
+                let _ = record_audit_entry(&mut redis_conn, &entry);
+                "Emergency stop activated: no chat is scanned or moderated until /emergencystop off.".to_string()
+            }
+            Ok(false) => "The emergency stop already is active.".to_string(),
+            Err(e) => format!("Failed to activate the emergency stop: {}", e),
+        },
+        "off" => match end_emergency_stop(&mut redis_conn) {
+            Ok(true) => {
+                let entry = AuditLogEntry::new(&msg, "Resume Monitoring", "All monitoring has been resumed".to_string());
+                let _ = record_audit_entry(&mut redis_conn, &entry);
+                "Monitoring resumed.".to_string()
+            }
+            Ok(false) => "No emergency stop is active.".to_string(),
+            Err(e) => format!("Failed to resume monitoring: {}", e),
+        },
+        "status" => match emergency_stop_since(&mut redis_conn) {
+            Some(since) => format!("The emergency stop is active since {}.", format_timestamp(Some(&since.to_string()))),
+            None => "No emergency stop is active.".to_string(),
+        },
+        _ => "Usage: /emergencystop <on|off|status>\n\
+         - on: stop scanning and moderating messages in every chat.\n\
+         - off: resume monitoring."
+            .to_string(),
+    };
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /exportconfig command: sends the features, thresholds, lists
+/// and chat settings as a JSON document /importconfig restores
+pub async fn handle_export_config(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let snapshot = match export_config(&mut redis_conn) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Failed to export the configuration: {}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    let export = serde_json::to_string_pretty(&snapshot).expect("ConfigSnapshot always serializes");
+    let file_name = format!("bot_config_{}.json", chrono::Utc::now().format("%Y%m%d_%H%M%S"));
+    bot.send_document(chat_id, InputFile::memory(export.into_bytes()).file_name(file_name))
+        .caption(format!(
+            "The bot configuration: {} enabled features, settings of {} chats. Reply to it with /importconfig to restore it.",
+            snapshot.enabled_features.len(),
+            snapshot.chats.len()
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Handles the /importconfig command: restores the configuration from the
+/// replied-to /exportconfig document
+pub async fn handle_import_config(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    // Once the panel is set up the import needs ConfigureBot; until then anyone passes
+    // the admin check in a private chat, so it needs an admin of a moderated chat
+    if !is_admin_panel_setup(&mut redis_conn).unwrap_or(false) && !moderates_any_chat(&mut redis_conn, &msg).unwrap_or(false) {
+        bot.send_message(chat_id, "Importing the configuration needs an admin of a chat the bot moderates.").await?;
+        return Ok(());
+    }
+    let Some(document) = msg.reply_to_message().and_then(|reply| reply.document()) else {
+        bot.send_message(chat_id, "Usage: reply to an /exportconfig document with /importconfig").await?;
+        return Ok(());
+    };
+    if document.file.size > import::MAX_FILE_BYTES {
+        bot.send_message(
+            chat_id,
+            format!("File is too large, the limit is {} KiB.", import::MAX_FILE_BYTES / 1024),
+        )
+            .await?;
+        return Ok(());
+    }
+
+    let file = bot.get_file(document.file.id.clone()).await?;
+    let mut contents = Vec::new();
+    bot.download_file(&file.path, &mut contents).await?;
+    // Check the whole document before anything is applied
+    let snapshot = match String::from_utf8(contents) {
+        Ok(contents) => ConfigSnapshot::from_json(&contents),
+        Err(e) => Err(e.into()),
+    };
+    let snapshot = match snapshot {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Invalid configuration document: {}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    let reply = match import_config(&mut redis_conn, &snapshot) {
+        Ok(()) => {
+            let details = format!("Restored the configuration of {} chats", snapshot.chats.len());
+            let _ = record_audit_entry(&mut redis_conn, &AuditLogEntry::new(&msg, "Import Configuration", details));
+            format!(
+                "Configuration imported: {} enabled features and the settings of {} chats restored.",
+                snapshot.enabled_features.len(),
+                snapshot.chats.len()
+            )
+        }
+        Err(e) => format!("Failed to import the configuration: {}", e),
+    };
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
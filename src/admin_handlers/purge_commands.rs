@@ -0,0 +1,109 @@
+use std::future::Future;
+use teloxide::prelude::*;
+use teloxide::types::MessageId;
+use crate::admin_handlers::lookup_username;
+use crate::config::{key, purge, suffix};
+use redis::{Commands, RedisResult};
+
+/// Result of deleting a user's recent messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PurgeOutcome {
+    /// Messages deleted
+    pub deleted: usize,
+    /// Messages Telegram refused to delete (usually older than 48 hours)
+    pub failed: usize,
+}
+
+fn recent_messages_key(chat_id: ChatId, user_id: UserId) -> String {
+    format!("{}{}{}:{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0, suffix::RECENT_MESSAGES, user_id.0)
+}
+
+/// Remembers `message_id` as one of `user_id`'s recent messages in `chat_id`.
+///
+/// Only the newest `purge::RECENT_MESSAGES_LIMIT` ids are kept, and the list
+/// expires once Telegram would no longer let the bot delete them anyway.
+pub fn record_recent_message(
+    conn: &mut redis::Connection,
+    chat_id: ChatId,
+    user_id: UserId,
+    message_id: MessageId,
+) -> RedisResult<()> {
+    let recent_key = recent_messages_key(chat_id, user_id);
+    redis::pipe()
+        .lpush(&recent_key, message_id.0).ignore()
+        .ltrim(&recent_key, 0, purge::RECENT_MESSAGES_LIMIT - 1).ignore()
+        .expire(&recent_key, purge::RECENT_MESSAGES_TTL).ignore()
+        .query(conn)
+}
+
+/// Recent message ids recorded for `user_id` in `chat_id`, newest first.
+pub fn recent_message_ids(
+    conn: &mut redis::Connection,
+    chat_id: ChatId,
+    user_id: UserId,
+) -> RedisResult<Vec<MessageId>> {
+    let ids: Vec<i32> = conn.lrange(recent_messages_key(chat_id, user_id), 0, -1)?;
+    Ok(ids.into_iter().map(MessageId).collect())
+}
+
+/// Deletes every message in `ids` with `delete`, counting successes and failures.
+pub async fn purge_messages<F, Fut, E>(ids: &[MessageId], mut delete: F) -> PurgeOutcome
+where
+    F: FnMut(MessageId) -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+{
+    let mut outcome = PurgeOutcome::default();
+    for id in ids {
+        match delete(*id).await {
+            Ok(()) => outcome.deleted += 1,
+            Err(_) => outcome.failed += 1,
+        }
+    }
+    outcome
+}
+
+/// Handles the /purge command: deletes the recent messages the bot recorded for a user in this chat
+pub async fn handle_purge(bot: Bot, chat_id: ChatId, user: String) -> ResponseResult<()> {
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+
+    let target = match user.trim().parse::<u64>() {
+        Ok(id) => Some(UserId(id)),
+        Err(_) => lookup_username(&mut redis_conn, user.trim()),
+    };
+    let target = match target {
+        Some(target) => target,
+        None => {
+            bot.send_message(chat_id, "Usage: /purge <user_id|@username>").await?;
+            return Ok(());
+        }
+    };
+
+    let ids = recent_message_ids(&mut redis_conn, chat_id, target).unwrap_or_default();
+    if ids.is_empty() {
+        bot.send_message(chat_id, format!("No recent messages recorded for user {}.", target))
+            .await?;
+        return Ok(());
+    }
+
+    let outcome = purge_messages(&ids, |id| {
+        let bot = bot.clone();
+        async move { bot.delete_message(chat_id, id).await.map(|_| ()) }
+    })
+    .await;
+    let _: RedisResult<()> = redis_conn.del(recent_messages_key(chat_id, target));
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "Purged {} message(s) from user {}; {} could not be deleted (too old or already gone).",
+            outcome.deleted, target, outcome.failed
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use std::error::Error;
+use std::fmt::Write;
+use chrono::Utc;
+use redis::Commands;
+use teloxide::prelude::*;
+use teloxide::types::{
+    Chat, ChatMember, ChatMemberKind, ChatMemberUpdated, MediaKind, MediaText, MessageCommon, MessageId, MessageKind, User,
+};
+use crate::admin_handlers::chat_member_handler;
+use crate::config::{key, lockdown, raid_simulation, suffix};
+use crate::handlers::handle_message;
+use crate::join_verify::is_pending;
+use crate::lockdown::lockdown_since;
+
+/// What the defenses did during a `/simulateraid` run.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RaidSimulation {
+    /// Synthetic members that joined and posted.
+    pub members: usize,
+    /// Event (joins and first messages counted apart, from 1) that started
+    /// the lockdown, `None` if none did.
+    pub lockdown_at: Option<usize>,
+    /// Whether the chat already was locked down before the run.
+    pub already_locked: bool,
+    /// Synthetic members the lockdown muted.
+    pub muted: usize,
+    /// Synthetic members sent the join challenge.
+    pub challenged: usize,
+}
+
+/// Whether `/simulateraid` may run in `chat_id`: only chats listed in
+/// `SIMULATE_RAID_CHATS` may, and with it unset no chat can.
+pub fn simulation_allowed(chat_id: ChatId) -> bool {
+    std::env::var(raid_simulation::CHATS_ENV)
+        .map(|chats| chats.split(',').any(|chat| chat.trim().parse::<i64>() == Ok(chat_id.0)))
+        .unwrap_or(false)
+}
+
+fn synthetic_user(index: usize) -> User {
+    User {
+        id: UserId(raid_simulation::FIRST_USER_ID + index as u64),
+        is_bot: false,
+        first_name: format!("Raider {}", index + 1),
+        last_name: None,
+        username: None,
+        language_code: None,
+        is_premium: false,
+        added_to_attachment_menu: false,
+    }
+}
+
+fn member_update(chat: &Chat, user: &User, old: ChatMemberKind, new: ChatMemberKind) -> ChatMemberUpdated {
+    ChatMemberUpdated {
+        chat: chat.clone(),
+        from: user.clone(),
+        date: Utc::now(),
+        old_chat_member: ChatMember { user: user.clone(), kind: old },
+        new_chat_member: ChatMember { user: user.clone(), kind: new },
+        invite_link: None,
+        via_join_request: false,
+        via_chat_folder_invite_link: false,
+    }
+}
+
+/// A text message from `user`. Its id is negative, so deleting it can never
+/// hit a real message of the chat.
+fn synthetic_message(chat: &Chat, user: &User, index: usize) -> Message {
+    Message {
+        id: MessageId(-(index as i32) - 1),
+        date: Utc::now(),
+        chat: chat.clone(),
+        kind: MessageKind::Common(MessageCommon {
+            author_signature: None,
+            effect_id: None,
+            forward_origin: None,
+            reply_to_message: None,
+            external_reply: None,
+            quote: None,
+            reply_to_story: None,
+            sender_boost_count: None,
+            edit_date: None,
+            media_kind: MediaKind::Text(MediaText {
+                text: raid_simulation::MESSAGE_TEXT.to_string(),
+                entities: Vec::new(),
+                link_preview_options: None,
+            }),
+            reply_markup: None,
+            is_automatic_forward: false,
+            has_protected_content: false,
+            is_from_offline: false,
+            business_connection_id: None,
+        }),
+        thread_id: None,
+        from: Some(user.clone()),
+        sender_chat: None,
+        is_topic_message: false,
+        via_bot: None,
+        sender_business_bot: None,
+    }
+}
+
+/// Runs `members` synthetic members through `chat_member_handler` and
+/// `handle_message` in `chat`, each joining and then posting, and reports
+/// which defenses activated. The members leave again afterwards, so only
+/// the raid events and a lockdown they triggered stay behind.
+///
+/// Callers must check `simulation_allowed` first.
+pub async fn simulate_raid(
+    bot: &Bot,
+    conn: &mut redis::Connection,
+    chat: &Chat,
+    members: usize,
+) -> Result<RaidSimulation, Box<dyn Error + Send + Sync>> {
+    let chat_id = chat.id;
+    let muted_key = format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0, suffix::LOCKDOWN_MUTED);
+    let mut outcome = RaidSimulation {
+        members,
+        already_locked: lockdown_since(conn, chat_id).is_some(),
+        ..RaidSimulation::default()
+    };
+    let mut event = 0;
+    let note_lockdown = |conn: &mut redis::Connection, outcome: &mut RaidSimulation, event: usize| {
+        if !outcome.already_locked && outcome.lockdown_at.is_none() && lockdown_since(conn, chat_id).is_some() {
+            outcome.lockdown_at = Some(event);
+        }
+    };
+
+    let users: Vec<User> = (0..members).map(synthetic_user).collect();
+    for (index, user) in users.iter().enumerate() {
+        chat_member_handler(bot.clone(), member_update(chat, user, ChatMemberKind::Left, ChatMemberKind::Member)).await?;
+        event += 1;
+        note_lockdown(conn, &mut outcome, event);
+        if is_pending(conn, chat_id, user.id)? {
+            outcome.challenged += 1;
+        }
+
+        handle_message(bot.clone(), synthetic_message(chat, user, index)).await?;
+        event += 1;
+        note_lockdown(conn, &mut outcome, event);
+    }
+
+    for user in &users {
+        // Lifting the lockdown shouldn't try to unmute members that never existed
+        let held: bool = conn.srem(&muted_key, user.id.0)?;
+        if held {
+            outcome.muted += 1;
+        }
+        chat_member_handler(bot.clone(), member_update(chat, user, ChatMemberKind::Member, ChatMemberKind::Left)).await?;
+    }
+    Ok(outcome)
+}
+
+/// Renders the `/simulateraid` report for `chat_id`.
+pub fn render_simulation(chat_id: ChatId, outcome: &RaidSimulation) -> String {
+    let mut response = format!(
+        "Simulated a raid of {} member(s) in chat {} ({} events):\n",
+        outcome.members, chat_id, outcome.members * 2
+    );
+    let lockdown = match (outcome.already_locked, outcome.lockdown_at) {
+        (true, _) => "already active before the run".to_string(),
+        (false, Some(event)) => format!("started at event {}", event),
+        (false, None) => format!("not triggered (needs {} events within {}s)", lockdown::RAID_EVENTS, lockdown::WINDOW_SECS),
+    };
+    writeln!(&mut response, "• Lockdown: {}", lockdown).unwrap();
+    writeln!(&mut response, "• Muted by the lockdown: {}", outcome.muted).unwrap();
+    writeln!(&mut response, "• Join challenges sent: {}", outcome.challenged).unwrap();
+    if outcome.lockdown_at.is_some() {
+        writeln!(&mut response, "Send /lockdown off|{} to lift the lockdown.", chat_id).unwrap();
+    }
+    response
+}
+
+/// Handles the /simulateraid command: runs a synthetic raid in this chat if
+/// it is a staging chat listed in `SIMULATE_RAID_CHATS`
+pub async fn handle_simulate_raid(bot: Bot, msg: Message, members: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    if !simulation_allowed(chat_id) {
+        bot.send_message(
+            chat_id,
+            format!("Raid simulation is disabled in this chat. List staging chats in {} to allow it.", raid_simulation::CHATS_ENV),
+        )
+        .await?;
+        return Ok(());
+    }
+    let members = match members.trim() {
+        "" => Some(raid_simulation::DEFAULT_MEMBERS),
+        members => members.parse::<usize>().ok().filter(|n| (1..=raid_simulation::MAX_MEMBERS).contains(n)),
+    };
+    let Some(members) = members else {
+        bot.send_message(chat_id, format!("Usage: /simulateraid [members], at most {}", raid_simulation::MAX_MEMBERS)).await?;
+        return Ok(());
+    };
+
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let response = match simulate_raid(&bot, &mut redis_conn, &msg.chat, members).await {
+        Ok(outcome) => render_simulation(chat_id, &outcome),
+        Err(e) => format!("Raid simulation failed: {}", e),
+    };
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
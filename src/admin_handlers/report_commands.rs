@@ -0,0 +1,113 @@
+use teloxide::prelude::*;
+use crate::config::{field, key, report};
+use crate::notifications::{post_in_chat, send_alert, Severity};
+use crate::handlers::{message_sender, Sender};
+use redis::{Commands, RedisResult};
+
+/// Result of recording a member's spam report
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReportOutcome {
+    /// The reporter tried to report their own message
+    SelfReport,
+    /// The reporter already reported this message
+    Duplicate,
+    /// Report stored, total distinct reporters so far
+    Recorded(u32),
+    /// Report stored and the threshold was just exceeded
+    Escalated(u32),
+}
+
+/// Records a report of `message_id` by `reporter` against `sender`.
+///
+/// Distinct reporters are tracked in `tg:reports:<chat_id>:<message_id>`. The
+/// sender's `rep` is bumped exactly once, when the number of reporters first
+/// exceeds `report::ESCALATION_THRESHOLD`.
+pub fn record_spam_report(
+    conn: &mut redis::Connection,
+    chat_id: ChatId,
+    message_id: i32,
+    reporter: UserId,
+    sender: UserId,
+) -> RedisResult<ReportOutcome> {
+    if reporter == sender {
+        return Ok(ReportOutcome::SelfReport);
+    }
+
+    let report_key = format!("{}{}:{}", key::ns(key::TG_REPORTS_PREFIX), chat_id.0, message_id);
+    let added: i64 = conn.sadd(&report_key, reporter.0)?;
+    if added == 0 {
+        return Ok(ReportOutcome::Duplicate);
+    }
+    let _: () = conn.expire(&report_key, report::REPORT_TTL)?;
+
+    let reporters: u32 = conn.scard(&report_key)?;
+    if reporters == report::ESCALATION_THRESHOLD + 1 {
+        let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), sender.0);
+        let _: () = conn.hincr(&user_key, field::REP, report::REP_PENALTY)?;
+        return Ok(ReportOutcome::Escalated(reporters));
+    }
+
+    Ok(ReportOutcome::Recorded(reporters))
+}
+
+/// Handles the /reportspam command, which any chat member may use as a reply to a spam message
+pub async fn handle_report_spam(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let reporter = match message_sender(&msg) {
+        Some(Sender::User(user_id)) => user_id,
+        _ => return Ok(()),
+    };
+
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+
+    let target = match msg.reply_to_message() {
+        Some(target) => target,
+        None => {
+            post_in_chat(&bot, &mut redis_conn, chat_id, "Reply to the message you want to report with /reportspam.".to_string())
+                .await?;
+            return Ok(());
+        }
+    };
+    let sender = match message_sender(target) {
+        Some(Sender::User(user_id)) => user_id,
+        // Posts made as a chat have no reputation to penalize
+        Some(Sender::AnonymousAdmin(_)) | Some(Sender::Channel(_)) => {
+            post_in_chat(&bot, &mut redis_conn, chat_id, "Messages posted as a channel or by an anonymous admin can't be reported.".to_string())
+                .await?;
+            return Ok(());
+        }
+        None => return Ok(()),
+    };
+
+    let outcome = record_spam_report(&mut redis_conn, chat_id, target.id.0, reporter, sender)
+        .expect("Failed to record spam report");
+
+    match outcome {
+        ReportOutcome::SelfReport => {
+            post_in_chat(&bot, &mut redis_conn, chat_id, "You cannot report your own message.".to_string()).await?;
+        }
+        ReportOutcome::Duplicate => {
+            post_in_chat(&bot, &mut redis_conn, chat_id, "You have already reported this message.".to_string()).await?;
+        }
+        ReportOutcome::Recorded(count) => {
+            post_in_chat(&bot, &mut redis_conn, chat_id, format!("Report received ({} so far). Thank you!", count))
+                .await?;
+        }
+        ReportOutcome::Escalated(count) => {
+            let notify_text = format!(
+                "Message {} from user {} in chat {} was reported as spam by {} members.",
+                target.id, sender, chat_id, count
+            );
+            let admin_chat: Option<i64> = redis_conn
+                .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0), field::ADMIN_CHAT)
+                .unwrap_or(None);
+            send_alert(&bot, &mut redis_conn, chat_id, admin_chat.map(ChatId), Severity::Medium, notify_text).await?;
+        }
+    }
+
+    Ok(())
+}
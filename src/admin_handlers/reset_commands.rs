@@ -0,0 +1,113 @@
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+use crate::admin_handlers::moderates_chat;
+use crate::config::{field, key};
+use redis::{Commands, RedisResult};
+
+/// Prefix of the confirmation button's callback data: `resetchat:<chat_id>:<admin_id>`
+pub const RESET_CHAT_CALLBACK: &str = "resetchat:";
+
+/// Keys holding `chat_id`'s state: its `tg:chats:<id>` hash (settings, feature
+/// overrides, counters) and every `tg:chats:<id>:...` key (symbol counts,
+/// daily buckets, lists, ...).
+pub fn chat_state_keys(conn: &mut redis::Connection, chat_id: ChatId) -> RedisResult<Vec<String>> {
+    let chat_key = format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0);
+    let mut keys: Vec<String> = conn.scan_match::<_, String>(format!("{}:*", chat_key))?.collect();
+    if conn.exists(&chat_key)? {
+        keys.push(chat_key);
+    }
+    Ok(keys)
+}
+
+/// Deletes all state of `chat_id`, leaving other chats and global state alone.
+/// Returns how many keys were deleted.
+pub fn reset_chat(conn: &mut redis::Connection, chat_id: ChatId) -> RedisResult<usize> {
+    let keys = chat_state_keys(conn, chat_id)?;
+    if keys.is_empty() {
+        return Ok(0);
+    }
+    conn.del(&keys)
+}
+
+/// Handles the /resetchat command: asks `admin` to confirm wiping a chat's state
+pub async fn handle_reset_chat(bot: Bot, msg: Message, admin: UserId, chat: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let target_chat = match chat.trim() {
+        "" => Some(chat_id.0),
+        chat => chat.parse::<i64>().ok(),
+    };
+    let Some(target_chat) = target_chat else {
+        bot.send_message(chat_id, "Usage: /resetchat [chat_id]").await?;
+        return Ok(());
+    };
+
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    // Anyone passes the admin check in a private chat, so only admins of the target may wipe it
+    if !moderates_chat(&mut redis_conn, &msg, ChatId(target_chat)).unwrap_or(false) {
+        bot.send_message(chat_id, format!("You are not an admin of chat {}.", target_chat)).await?;
+        return Ok(());
+    }
+    let chat_name: String = redis_conn
+        .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), target_chat), field::NAME)
+        .unwrap_or_else(|_| target_chat.to_string());
+
+    // Only the admin who asked may confirm
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback(
+            "Reset",
+            format!("{}{}:{}", RESET_CHAT_CALLBACK, target_chat, admin.0),
+        ),
+        InlineKeyboardButton::callback("Cancel", format!("discard:{}", target_chat)),
+    ]]);
+    bot.send_message(
+        chat_id,
+        format!(
+            "Reset all bot state for chat {}? Its settings, feature overrides, lists, symbol counts and daily stats \
+             will be deleted. This can't be undone.",
+            chat_name
+        ),
+    )
+    .reply_markup(keyboard)
+    .await?;
+
+    Ok(())
+}
+
+/// Called when the /resetchat confirmation button is pressed
+pub async fn reset_chat_handler(bot: Bot, query: CallbackQuery) -> ResponseResult<()> {
+    let (Some(data), Some(callback_msg)) = (query.data.as_deref(), query.message.as_ref()) else {
+        return Ok(());
+    };
+    let parsed = data
+        .strip_prefix(RESET_CHAT_CALLBACK)
+        .and_then(|rest| rest.split_once(':'))
+        .and_then(|(chat, admin)| Some((chat.parse::<i64>().ok()?, admin.parse::<u64>().ok()?)));
+    let Some((target_chat, admin)) = parsed else {
+        return Ok(());
+    };
+    if query.from.id != UserId(admin) {
+        bot.answer_callback_query(query.id.clone())
+            .text("Only the admin who asked for the reset can confirm it.")
+            .await?;
+        return Ok(());
+    }
+
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let reply = match reset_chat(&mut redis_conn, ChatId(target_chat)) {
+        Ok(deleted) => format!("Chat {} was reset; {} key(s) deleted.", target_chat, deleted),
+        Err(e) => format!("Failed to reset chat {}: {}", target_chat, e),
+    };
+
+    bot.answer_callback_query(query.id.clone()).await?;
+    let _ = bot.delete_message(callback_msg.chat().id, callback_msg.id()).await;
+    bot.send_message(callback_msg.chat().id, reply).await?;
+    Ok(())
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use teloxide::prelude::*;
+use crate::config::{key, message_store};
+use crate::handlers::stored_message_content;
+use redis::{Commands, RedisResult};
+use regex::Regex;
+
+/// What `/searchmessages` looks for in stored message text
+#[derive(Debug, Clone)]
+pub enum SearchPattern {
+    /// Case-insensitive substring
+    Text(String),
+    /// Rust regex, written as `/pattern/`
+    Regex(Regex),
+}
+
+impl SearchPattern {
+    /// Parses `/pattern/` as a regex and anything else as a substring.
+    pub fn parse(pattern: &str) -> Result<Self, regex::Error> {
+        match pattern.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+            Some(regex) if !regex.is_empty() => Ok(SearchPattern::Regex(Regex::new(regex)?)),
+            _ => Ok(SearchPattern::Text(pattern.to_lowercase())),
+        }
+    }
+
+    /// Byte offset of the first match in `text`.
+    fn find(&self, text: &str) -> Option<usize> {
+        match self {
+            // Lowercasing may change byte lengths, so search char by char
+            SearchPattern::Text(needle) => text
+                .char_indices()
+                .map(|(start, _)| start)
+                .find(|&start| text[start..].to_lowercase().starts_with(needle.as_str())),
+            SearchPattern::Regex(regex) => regex.find(text).map(|found| found.start()),
+        }
+    }
+}
+
+/// A stored message matching a search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageMatch {
+    pub message_id: String,
+    pub chat_id: i64,
+    /// When the message was stored, in unix seconds
+    pub timestamp: i64,
+    /// Text around the match
+    pub snippet: String,
+}
+
+/// Up to `message_store::SNIPPET_CHARS` characters of `text` starting a little
+/// before the byte offset `at`.
+fn snippet(text: &str, at: usize) -> String {
+    let before = text[..at].chars().count();
+    let skip = before.saturating_sub(message_store::SNIPPET_CHARS / 4);
+    let snippet: String = text.chars().skip(skip).take(message_store::SNIPPET_CHARS).collect();
+    let ellipsis_before = if skip > 0 { "…" } else { "" };
+    let ellipsis_after = if skip + message_store::SNIPPET_CHARS < text.chars().count() { "…" } else { "" };
+    format!("{}{}{}", ellipsis_before, snippet.replace('\n', " "), ellipsis_after)
+}
+
+/// Stored messages matching `pattern` that were posted in `chat` (any chat
+/// when `None`) at or after `since`, newest first and at most `limit`.
+///
+/// Keys are walked with SCAN so a large store doesn't block Redis. Messages
+/// stored without a `tg:message_info` entry have no chat or time and are skipped.
+pub fn search_messages(
+    conn: &mut redis::Connection,
+    chat: Option<ChatId>,
+    since: i64,
+    pattern: &SearchPattern,
+    limit: usize,
+) -> RedisResult<Vec<MessageMatch>> {
+    let prefix = key::ns(key::TG_MESSAGE_PREFIX);
+    let info_prefix = key::ns(key::TG_MESSAGE_INFO_PREFIX);
+    let message_keys: Vec<String> = conn.scan_match::<_, String>(format!("{}*", prefix))?.collect();
+
+    let mut matches = Vec::new();
+    for message_key in message_keys {
+        let Some(message_id) = message_key.strip_prefix(prefix.as_str()) else {
+            continue;
+        };
+        let info: HashMap<String, i64> = conn.hgetall(format!("{}{}", info_prefix, message_id))?;
+        let (Some(&chat_id), Some(&timestamp)) = (info.get(message_store::CHAT_ID), info.get(message_store::TIMESTAMP)) else {
+            continue;
+        };
+        if chat.is_some_and(|chat| chat.0 != chat_id) || timestamp < since {
+            continue;
+        }
+        let Some(text) = conn.get::<_, Option<String>>(&message_key)? else {
+            continue;
+        };
+        if let Some(at) = pattern.find(&text) {
+            matches.push(MessageMatch { message_id: message_id.to_string(), chat_id, timestamp, snippet: snippet(&text, at) });
+        }
+    }
+    matches.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| a.message_id.cmp(&b.message_id)));
+    matches.truncate(limit);
+    Ok(matches)
+}
+
+/// Handles the /searchmessages command: `<chat_id|all>|<hours>|<text or /regex/>`
+pub async fn handle_search_messages(bot: Bot, chat_id: ChatId, args: String) -> ResponseResult<()> {
+    let usage = "Usage: /searchmessages <chat_id|all>|<hours>|<text or /regex/>\n\
+                 - Searches the messages stored over the last hours (default 24, at most the storage TTL).\n\
+                 - Text matches case-insensitively; wrap a regex in slashes.";
+    let parts: Vec<&str> = args.splitn(3, '|').map(str::trim).collect();
+    let chat = match parts.first() {
+        Some(&"all") => Some(None),
+        Some(chat) => chat.parse::<i64>().ok().map(|id| Some(ChatId(id))),
+        None => None,
+    };
+    let hours = match parts.get(1) {
+        Some(&"") => Some(message_store::SEARCH_HOURS),
+        Some(hours) => hours.parse::<i64>().ok().filter(|hours| *hours > 0),
+        None => None,
+    };
+    let (Some(chat), Some(hours), Some(pattern)) = (chat, hours, parts.get(2).filter(|p| !p.is_empty())) else {
+        bot.send_message(chat_id, usage).await?;
+        return Ok(());
+    };
+    let pattern = match SearchPattern::parse(pattern) {
+        Ok(pattern) => pattern,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Invalid regex: {}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let since = chrono::Utc::now().timestamp() - hours * 3600;
+    let matches = match search_messages(&mut redis_conn, chat, since, &pattern, message_store::SEARCH_LIMIT) {
+        Ok(matches) => matches,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Failed to search messages: {}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    if matches.is_empty() {
+        bot.send_message(chat_id, "No stored messages match.").await?;
+        return Ok(());
+    }
+    let mut response = format!("Stored messages matching (newest first, up to {}):\n", message_store::SEARCH_LIMIT);
+    for found in matches {
+        let when = chrono::DateTime::from_timestamp(found.timestamp, 0)
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        writeln!(&mut response, "• {} in {} at {}: {}", found.message_id, found.chat_id, when, found.snippet).unwrap();
+    }
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /listmessages command: lists the stored messages available for learning
+pub async fn handle_list_messages(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    // Stored message ids, newest first
+    let ids: Vec<String> = match redis_conn.lrange(key::ns(key::TG_MESSAGE_INDEX_KEY), 0, -1) {
+        Ok(ids) => ids,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("❌ Failed to get message keys: {}", e)
+            ).await?;
+            return Ok(());
+        }
+    };
+    
+    if ids.is_empty() {
+        bot.send_message(
+            chat_id,
+            "📝 No messages found in Redis storage. Messages are stored for 24 hours after processing."
+        ).await?;
+        return Ok(());
+    }
+    
+    // Get the 10 most recent messages that haven't expired
+    let mut message_list = Vec::new();
+    for message_id in &ids {
+        if message_list.len() == 10 {
+            break;
+        }
+        if let Ok(Some(content)) = stored_message_content(&mut redis_conn, message_id) {
+            let preview = if content.len() > 50 {
+                format!("{}...", &content[..50])
+            } else {
+                content
+            };
+            message_list.push(format!("ID: {} | Content: {}", message_id, preview));
+        }
+    }
+    
+    let response = if message_list.is_empty() {
+        "📝 No message content found in Redis storage.".to_string()
+    } else {
+        format!(
+            "📝 Recent messages in Redis storage (showing up to 10):\n\n{}",
+            message_list.join("\n\n")
+        )
+    };
+    
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
@@ -0,0 +1,671 @@
+use crate::config::{action, feature_state, field, join_gate, key, mute, reputation, suffix, threshold, FeatureSource, DEFAULT_FEATURES, OPT_IN_FEATURES};
+use crate::admin_handlers::moderates_chat;
+use crate::script_filter;
+use crate::join_gate::join_windows;
+use crate::flood::{flood_limit, set_flood_limit, FloodLimitSource};
+use crate::adaptive::{effective_limits, message_rate, scale_bounds, set_scale_bounds};
+use crate::ban_rate::{ban_rate_limit, set_ban_rate_limit};
+use crate::perm_ban::{perm_ban_action, set_perm_ban_action, PermBanAction};
+use crate::forward_policy::{forward_policy, set_forward_policy, ForwardPolicy};
+use crate::mutes::mute_minutes;
+use redis::Commands;
+use std::collections::HashMap;
+use std::fmt::Write;
+use teloxide::prelude::*;
+use teloxide::types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup};
+
+/// Handles the /setthreshold command: sets a detection threshold or reputation gate
+pub async fn handle_set_threshold(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let names: Vec<&str> = threshold::ALL.iter().map(|(name, _)| *name).collect();
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    if parts.len() != 2 {
+        let current: HashMap<String, String> = redis_conn
+            .hgetall(key::ns(key::TG_THRESHOLDS_KEY))
+            .unwrap_or_default();
+        let mut response = String::from("Usage: /setthreshold <name>|<value>\nCurrent thresholds:\n");
+        for (name, default) in threshold::ALL {
+            let value = current.get(*name).cloned().unwrap_or_else(|| default.to_string());
+            writeln!(&mut response, "• {}: {}", name, value).unwrap();
+        }
+        let current: HashMap<String, String> = redis_conn
+            .hgetall(key::ns(reputation::SETTINGS_KEY))
+            .unwrap_or_default();
+        for (name, default, _) in reputation::ALL {
+            let value = current.get(*name).cloned().unwrap_or_else(|| default.to_string());
+            writeln!(&mut response, "• {}: {}", name, value).unwrap();
+        }
+        bot.send_message(chat_id, response).await?;
+        return Ok(());
+    }
+
+    let (name, value_str) = (parts[0], parts[1]);
+    // Reputation decay settings are whole numbers kept with the admin panel settings
+    if let Some((_, _, max)) = reputation::ALL.iter().find(|(setting, _, _)| *setting == name) {
+        let value = match value_str.parse::<i64>() {
+            Ok(v) if (0..=*max).contains(&v) => v,
+            _ => {
+                bot.send_message(
+                    chat_id,
+                    format!("Invalid value. {} must be a whole number from 0 to {}.", name, max),
+                ).await?;
+                return Ok(());
+            }
+        };
+        let _: () = redis_conn
+            .hset(key::ns(reputation::SETTINGS_KEY), name, value)
+            .expect("Failed to set reputation setting");
+        bot.send_message(chat_id, format!("Threshold {} set to {}", name, value))
+            .await?;
+        return Ok(());
+    }
+
+    if !names.contains(&name) {
+        bot.send_message(
+            chat_id,
+            format!(
+                "Unknown threshold `{}`. Use: {}",
+                name,
+                names.iter().chain(reputation::ALL.iter().map(|(name, _, _)| name)).copied().collect::<Vec<_>>().join(", ")
+            ),
+        ).await?;
+        return Ok(());
+    }
+
+    let value = match value_str.parse::<f64>() {
+        Ok(v) if v >= 0.0 && (!threshold::RATIOS.contains(&name) || v <= 1.0) => v,
+        _ => {
+            bot.send_message(
+                chat_id,
+                "Invalid value. Must be a non-negative number (ratios between 0 and 1).",
+            ).await?;
+            return Ok(());
+        }
+    };
+
+    let _: () = redis_conn
+        .hset(key::ns(key::TG_THRESHOLDS_KEY), name, value)
+        .expect("Failed to set threshold");
+
+    bot.send_message(chat_id, format!("Threshold {} set to {}", name, value))
+        .await?;
+    Ok(())
+}
+
+/// Handles the /setaction command: sets the score at which a chat warns, deletes, mutes or bans
+pub async fn handle_set_action(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    let target_chat = match parts.first().and_then(|c| c.parse::<i64>().ok()) {
+        Some(chat) => chat,
+        None => {
+            bot.send_message(
+                chat_id,
+                "Usage: /setaction <chat_id>|<threshold>|<warn|delete|mute|ban>[|<minutes>]\n\
+             - Omit the threshold to list the chat's action map.\n\
+             - minutes: how long tg_mute makes the user read-only.",
+            ).await?;
+            return Ok(());
+        }
+    };
+    // Anyone passes the admin check in a private chat, so only admins of the target may configure it
+    if !moderates_chat(&mut redis_conn, &msg, ChatId(target_chat)).unwrap_or(false) {
+        bot.send_message(chat_id, format!("You are not an admin of chat {}.", target_chat)).await?;
+        return Ok(());
+    }
+    let actions_key = format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), target_chat, suffix::ACTIONS);
+
+    if parts.len() < 3 {
+        let current: HashMap<String, String> = redis_conn.hgetall(&actions_key).unwrap_or_default();
+        let mut response = format!("Action map for chat {}:\n", target_chat);
+        for (name, default) in action::ALL {
+            match current.get(*name) {
+                Some(value) => writeln!(&mut response, "• score ≥ {} → {}", value, name).unwrap(),
+                None if default.is_finite() => writeln!(&mut response, "• score ≥ {} → {}", default, name).unwrap(),
+                None => writeln!(&mut response, "• {} is off", name).unwrap(),
+            }
+        }
+        let minutes = mute_minutes(&mut redis_conn, ChatId(target_chat));
+        writeln!(&mut response, "tg_mute lasts {} minute(s)", minutes).unwrap();
+        bot.send_message(chat_id, response).await?;
+        return Ok(());
+    }
+
+    let threshold = match parts[1].parse::<f64>() {
+        Ok(v) if v.is_finite() => v,
+        _ => {
+            bot.send_message(chat_id, "Invalid threshold. Must be a number.").await?;
+            return Ok(());
+        }
+    };
+    let action_name = parts[2].to_lowercase();
+    let action_name = match action::ALL
+        .iter()
+        .map(|(name, _)| *name)
+        .find(|name| *name == action_name || name.strip_prefix("tg_") == Some(action_name.as_str()))
+    {
+        Some(name) => name,
+        None => {
+            bot.send_message(chat_id, "Unknown action. Use: warn, delete, mute or ban").await?;
+            return Ok(());
+        }
+    };
+    let minutes = match parts.get(3) {
+        None => None,
+        Some(m) => match m.parse::<i64>() {
+            Ok(m) if action_name == action::MUTE && (1..=mute::MAX_MINUTES).contains(&m) => Some(m),
+            _ => {
+                bot.send_message(
+                    chat_id,
+                    format!("Minutes only apply to mute and must be between 1 and {}.", mute::MAX_MINUTES),
+                ).await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let _: () = redis_conn
+        .hset(&actions_key, action_name, threshold)
+        .expect("Failed to set action threshold");
+
+    if let Some(minutes) = minutes {
+        let _: () = redis_conn
+            .hset(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), target_chat), field::MUTE_MINUTES, minutes)
+            .expect("Failed to set mute duration");
+    }
+
+    bot.send_message(
+        chat_id,
+        format!("Chat {} will now {} at score ≥ {}", target_chat, action_name, threshold),
+    ).await?;
+    Ok(())
+}
+
+/// Handles the /setjoinwindow command: sets a chat's first-message timing windows and probation
+pub async fn handle_set_join_window(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    let target_chat = match parts.first().and_then(|c| c.parse::<i64>().ok()) {
+        Some(chat) => chat,
+        None => {
+            bot.send_message(
+                chat_id,
+                "Usage: /setjoinwindow <chat_id>|<first_fast|first_slow|probation|new_user_link|new_user_forward>|<seconds>\n\
+             - Omit the window to list the chat's join windows.\n\
+             - probation deletes messages sent sooner than <seconds> after joining (0 disables it).\n\
+             - new_user_link flags links sent sooner than <seconds> after joining with TG_NEW_USER_LINK\n\
+               and scores TG_CONTACT_CARD higher for contacts shared within it.\n\
+             - new_user_forward is how long members count as new for /forwards restrict_new.",
+            ).await?;
+            return Ok(());
+        }
+    };
+    let gate_key = format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), target_chat, suffix::JOIN_GATE);
+
+    if parts.len() < 3 {
+        let windows = join_windows(&mut redis_conn, ChatId(target_chat));
+        let response = format!(
+            "Join windows for chat {}:\n\
+            • first_fast: first message within {}s of joining → TG_FIRST_FAST\n\
+            • first_slow: first message after {}s → TG_FIRST_SLOW\n\
+            • new_user_link: links within {}s of joining → TG_NEW_USER_LINK, contacts → a higher TG_CONTACT_CARD\n\
+            • new_user_forward: forwards within {}s of joining are deleted with /forwards restrict_new\n\
+            • probation: {}",
+            target_chat,
+            windows.first_fast,
+            windows.first_slow,
+            windows.new_user_link,
+            windows.new_user_forward,
+            if windows.probation > 0 {
+                format!("messages within {}s of joining are deleted", windows.probation)
+            } else {
+                "off".to_string()
+            },
+        );
+        bot.send_message(chat_id, response).await?;
+        return Ok(());
+    }
+
+    let window = match join_gate::ALL.iter().map(|(name, _)| *name).find(|name| *name == parts[1]) {
+        Some(window) => window,
+        None => {
+            bot.send_message(chat_id, "Unknown window. Use: first_fast, first_slow, probation, new_user_link or new_user_forward").await?;
+            return Ok(());
+        }
+    };
+    let seconds = match parts[2].parse::<i64>() {
+        Ok(v) if v >= 0 => v,
+        _ => {
+            bot.send_message(chat_id, "Invalid window. Must be a non-negative number of seconds.").await?;
+            return Ok(());
+        }
+    };
+
+    let _: () = redis_conn
+        .hset(&gate_key, window, seconds)
+        .expect("Failed to set join window");
+
+    bot.send_message(
+        chat_id,
+        format!("Chat {} now uses {} = {}s", target_chat, window, seconds),
+    ).await?;
+    Ok(())
+}
+
+/// Handles the /setflood command: sets how many messages per window trigger TG_FLOOD in a chat
+pub async fn handle_set_flood(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    let target_chat = match parts.first().and_then(|c| c.parse::<i64>().ok()) {
+        Some(chat) => ChatId(chat),
+        None => {
+            bot.send_message(
+                chat_id,
+                "Usage: /setflood <chat_id>|<messages|default>\n\
+             - Omit the limit to show the chat's flood limit.\n\
+             - default goes back to the global flood_max threshold.",
+            ).await?;
+            return Ok(());
+        }
+    };
+
+    let limit = match parts.get(1).copied() {
+        None | Some("") => {
+            let (limit, source) = flood_limit(&mut redis_conn, target_chat);
+            let source = match source {
+                FloodLimitSource::Chat => "set for this chat",
+                FloodLimitSource::Global => "global",
+            };
+            bot.send_message(
+                chat_id,
+                format!("TG_FLOOD fires in chat {} after {} messages per window ({})", target_chat, limit, source),
+            ).await?;
+            return Ok(());
+        }
+        Some("default") => None,
+        Some(limit) => match limit.parse::<i64>() {
+            Ok(v) if v > 0 => Some(v),
+            _ => {
+                bot.send_message(chat_id, "Invalid limit. Must be a positive number of messages or default.").await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let response = match set_flood_limit(&mut redis_conn, target_chat, limit) {
+        Ok(()) => {
+            let (limit, _) = flood_limit(&mut redis_conn, target_chat);
+            format!("Chat {} now allows {} messages per flood window", target_chat, limit)
+        }
+        Err(e) => format!("Failed to set the flood limit: {}", e),
+    };
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /setbanrate command: caps automated bans per minute in a chat
+pub async fn handle_set_ban_rate(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    let target_chat = match parts.first().and_then(|c| c.parse::<i64>().ok()) {
+        Some(chat) => ChatId(chat),
+        None => {
+            bot.send_message(
+                chat_id,
+                "Usage: /setbanrate <chat_id>|<bans|default>\n\
+             - Omit the limit to show the chat's ban rate limit.\n\
+             - Bans past the limit within a minute only delete the message, and admins get a lockdown alert.",
+            ).await?;
+            return Ok(());
+        }
+    };
+
+    let limit = match parts.get(1).copied() {
+        None | Some("") => {
+            bot.send_message(
+                chat_id,
+                format!("Chat {} allows {} automated bans per minute", target_chat, ban_rate_limit(&mut redis_conn, target_chat)),
+            ).await?;
+            return Ok(());
+        }
+        Some("default") => None,
+        Some(limit) => match limit.parse::<i64>() {
+            Ok(v) if v > 0 => Some(v),
+            _ => {
+                bot.send_message(chat_id, "Invalid limit. Must be a positive number of bans or default.").await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let response = match set_ban_rate_limit(&mut redis_conn, target_chat, limit) {
+        Ok(()) => format!("Chat {} now allows {} automated bans per minute", target_chat, ban_rate_limit(&mut redis_conn, target_chat)),
+        Err(e) => format!("Failed to set the ban rate limit: {}", e),
+    };
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /setadaptive command: shows or sets the bounds of a chat's adaptive limits
+pub async fn handle_set_adaptive(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    let target_chat = match parts.first().and_then(|c| c.parse::<i64>().ok()) {
+        Some(chat) => ChatId(chat),
+        None => {
+            bot.send_message(
+                chat_id,
+                "Usage: /setadaptive <chat_id>|<min>|<max> or /setadaptive <chat_id>|default\n\
+             - Omit the bounds to show the chat's message rate and adaptive limits.\n\
+             - With adaptive_thresholds on, the flood and repeat limits scale with the chat's \
+             message rate, by at least min and at most max times.",
+            ).await?;
+            return Ok(());
+        }
+    };
+
+    let bounds = match parts.get(1..).unwrap_or_default() {
+        [] | [""] => {
+            let (min, max) = scale_bounds(&mut redis_conn, target_chat);
+            let (flood, repeat) = effective_limits(&mut redis_conn, target_chat);
+            let rate = message_rate(&mut redis_conn, target_chat)
+                .map(|rate| format!("{:.1} messages per minute", rate))
+                .unwrap_or_else(|| "not measured yet".to_string());
+            bot.send_message(
+                chat_id,
+                format!(
+                    "Chat {}: rate {}, scale bounds {} to {}, adaptive flood limit {} and repeat limit {}",
+                    target_chat, rate, min, max, flood, repeat
+                ),
+            ).await?;
+            return Ok(());
+        }
+        ["default"] => None,
+        [min, max] => match (min.parse::<f64>(), max.parse::<f64>()) {
+            (Ok(min), Ok(max)) if min > 0.0 && min <= max && max.is_finite() => Some((min, max)),
+            _ => {
+                bot.send_message(chat_id, "Invalid bounds. Must be positive numbers with min no greater than max, or default.").await?;
+                return Ok(());
+            }
+        },
+        _ => {
+            bot.send_message(chat_id, "Usage: /setadaptive <chat_id>|<min>|<max> or /setadaptive <chat_id>|default").await?;
+            return Ok(());
+        }
+    };
+
+    let response = match set_scale_bounds(&mut redis_conn, target_chat, bounds) {
+        Ok(()) => {
+            let (min, max) = scale_bounds(&mut redis_conn, target_chat);
+            format!("Chat {}'s adaptive limits now scale between {} and {} times", target_chat, min, max)
+        }
+        Err(e) => format!("Failed to set the adaptive bounds: {}", e),
+    };
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /permbanaction command: shows or sets what happens to users hitting TG_PERM_BAN
+pub async fn handle_perm_ban_action(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    let target_chat = match parts.get(1) {
+        None => Some(chat_id),
+        Some(chat) => chat.parse::<i64>().ok().map(ChatId),
+    };
+    let Some(target_chat) = target_chat.filter(|_| parts.len() <= 2) else {
+        bot.send_message(
+            chat_id,
+            "Usage: /permbanaction <ban|kick|report|default>[|<chat_id>]\n\
+         - ban: remove the user from the chat for good.\n\
+         - kick: remove the user but let them join again.\n\
+         - report: only tell the admins.\n\
+         - Omit the action to show the chat's current one.",
+        ).await?;
+        return Ok(());
+    };
+
+    let choice = match parts[0] {
+        "" => {
+            let current = perm_ban_action(&mut redis_conn, target_chat);
+            bot.send_message(chat_id, format!("Chat {} answers TG_PERM_BAN with: {}", target_chat, current.as_str())).await?;
+            return Ok(());
+        }
+        "default" => None,
+        name => match PermBanAction::parse(name) {
+            Some(choice) => Some(choice),
+            None => {
+                bot.send_message(chat_id, "Unknown action. Use: ban, kick, report or default").await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let response = match set_perm_ban_action(&mut redis_conn, target_chat, choice) {
+        Ok(()) => format!(
+            "Chat {} now answers TG_PERM_BAN with: {}",
+            target_chat,
+            perm_ban_action(&mut redis_conn, target_chat).as_str()
+        ),
+        Err(e) => format!("Failed to set the permanent ban action: {}", e),
+    };
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /forwards command: shows or sets which forwarded messages a chat accepts
+pub async fn handle_forwards(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    let target_chat = match parts.get(1) {
+        None => Some(chat_id),
+        Some(chat) => chat.parse::<i64>().ok().map(ChatId),
+    };
+    let Some(target_chat) = target_chat.filter(|_| parts.len() <= 2) else {
+        bot.send_message(
+            chat_id,
+            "Usage: /forwards <allow|restrict_new|deny|default>[|<chat_id>]\n\
+         - allow: keep every forwarded message.\n\
+         - restrict_new: delete forwards from members within their new_user_forward join window.\n\
+         - deny: delete every forwarded message.\n\
+         - Omit the policy to show the chat's current one.",
+        ).await?;
+        return Ok(());
+    };
+
+    let choice = match parts[0] {
+        "" => {
+            let current = forward_policy(&mut redis_conn, target_chat);
+            bot.send_message(chat_id, format!("Chat {} forwards policy: {}", target_chat, current.as_str())).await?;
+            return Ok(());
+        }
+        "default" => None,
+        name => match ForwardPolicy::parse(name) {
+            Some(choice) => Some(choice),
+            None => {
+                bot.send_message(chat_id, "Unknown policy. Use: allow, restrict_new, deny or default").await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let response = match set_forward_policy(&mut redis_conn, target_chat, choice) {
+        Ok(()) => format!(
+            "Chat {} forwards policy is now: {}",
+            target_chat,
+            forward_policy(&mut redis_conn, target_chat).as_str()
+        ),
+        Err(e) => format!("Failed to set the forwards policy: {}", e),
+    };
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /allowscript command: allows a script in a chat
+pub async fn handle_allow_script(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    let target_chat = match parts.first().and_then(|c| c.parse::<i64>().ok()) {
+        Some(chat) => chat,
+        None => {
+            bot.send_message(
+                chat_id,
+                "Usage: /allowscript <chat_id>|<script>\n\
+             - Omit the script to list the chat's allowed scripts.",
+            ).await?;
+            return Ok(());
+        }
+    };
+    let allowed_key = format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), target_chat, suffix::ALLOWED_SCRIPTS);
+
+    if parts.len() < 2 || parts[1].is_empty() {
+        let allowed: Vec<String> = redis_conn.smembers(&allowed_key).unwrap_or_default();
+        let text = if allowed.is_empty() {
+            format!("Chat {} allows all scripts.", target_chat)
+        } else {
+            format!("Chat {} allows: {}", target_chat, allowed.join(", "))
+        };
+        bot.send_message(chat_id, text).await?;
+        return Ok(());
+    }
+
+    let script = parts[1].to_lowercase();
+    if !script_filter::is_known_script(&script) {
+        let known: Vec<&str> = script_filter::SCRIPTS.iter().map(|(name, _)| *name).collect();
+        bot.send_message(
+            chat_id,
+            format!("Unknown script `{}`. Use: {}", script, known.join(", ")),
+        ).await?;
+        return Ok(());
+    }
+
+    let _: () = redis_conn
+        .sadd(&allowed_key, &script)
+        .expect("Failed to add allowed script");
+
+    bot.send_message(
+        chat_id,
+        format!("Script {} allowed in chat {}", script, target_chat),
+    ).await?;
+    Ok(())
+}
+
+/// Handles the /featurestatus command: shows which features are on in a chat and why
+pub async fn handle_feature_status(bot: Bot, msg: Message, chat: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let target_chat = match chat.trim() {
+        "" => Some(chat_id.0),
+        chat => chat.parse::<i64>().ok(),
+    };
+    let Some(target_chat) = target_chat else {
+        bot.send_message(chat_id, "Usage: /featurestatus [chat_id]").await?;
+        return Ok(());
+    };
+    let chat_name: String = redis_conn
+        .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), target_chat), field::NAME)
+        .unwrap_or_else(|_| target_chat.to_string());
+
+    let mut response = format!("Features in chat {}:\n", chat_name);
+    for feature in DEFAULT_FEATURES.iter().chain(OPT_IN_FEATURES) {
+        let state = feature_state(&mut redis_conn, target_chat, feature);
+        let source = match state.source {
+            FeatureSource::ChatOverride => "chat override",
+            FeatureSource::GlobalDefault => "global default",
+        };
+        let on_off = if state.enabled { "on" } else { "off" };
+        writeln!(&mut response, "• {}: {} ({})", feature, on_off, source).unwrap();
+    }
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /managefeatures command: shows the feature toggles for a moderated chat
+pub async fn handle_manage_features(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+
+    let key_moderated =
+        format!("{}{}{}", key::ns(key::ADMIN_PREFIX), chat_id.0, suffix::MODERATED_CHATS);
+    let moderated_chats: Vec<i64> =
+        redis_conn.smembers(key_moderated).unwrap_or_else(|_| Vec::new());
+
+    // 2) Build one row-per-chat button: callback_data = "managefeat:<chat_id>"
+    let mut rows: Vec<Vec<InlineKeyboardButton>> = Vec::new();
+    for chat in moderated_chats.into_iter() {
+        // You might want to fetch the chat's stored name for labeling:
+        let chat_name: String = redis_conn
+            .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat), field::NAME)
+            .unwrap_or_else(|_| chat.to_string());
+        rows.push(vec![
+            InlineKeyboardButton::callback(
+                format!("Chat: {}", chat_name),
+                format!("managefeat:{}", chat),
+            ),
+        ]);
+    }
+    // If the admin has no moderated chats, send a simple message instead:
+    if rows.is_empty() {
+        bot.send_message(chat_id, "You do not moderate any chats yet.")
+            .await?;
+    } else {
+        let keyboard = InlineKeyboardMarkup::new(rows);
+        bot.send_message(chat_id, "Select a chat to manage features:")
+            .reply_markup(keyboard)
+            .await?;
+    }
+    Ok(())
+}
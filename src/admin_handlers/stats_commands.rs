@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use teloxide::{prelude::*, types::InlineKeyboardButton, types::InlineKeyboardMarkup};
+use crate::ban_manager::{worst_users, BannedUser};
+use crate::config::{field, key, rate_limit, reply_aware, spam_event, stats, suffix, trend};
+use crate::spam_events::count_spam_events_since;
+use crate::spam_trend::{daily_totals, render_trend};
+use redis::{Commands, RedisResult};
+
+/// Counters of one moderated chat in `/stats --global`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatTotals {
+    pub chat_id: i64,
+    pub name: Option<String>,
+    pub spam: i64,
+    pub bans: i64,
+    pub perm_bans: i64,
+}
+
+/// Counters summed over every chat an admin chat moderates.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GlobalStats {
+    /// Each moderated chat, the one with the most spam first.
+    pub chats: Vec<ChatTotals>,
+    pub spam: i64,
+    pub bans: i64,
+    pub perm_bans: i64,
+    /// Spam events logged in the last 24 hours, across every chat.
+    pub recent_spam_events: usize,
+}
+
+/// Sums the spam, ban and permanent ban counters of the chats `admin_chat`
+/// moderates, fetching every chat hash in one pipeline, and counts the spam
+/// events of the last 24 hours.
+pub fn global_stats(conn: &mut redis::Connection, admin_chat: ChatId) -> RedisResult<GlobalStats> {
+    let moderated: Vec<i64> = conn.smembers(format!("{}{}{}", key::ns(key::ADMIN_PREFIX), admin_chat.0, suffix::MODERATED_CHATS))?;
+    let mut pipe = redis::pipe();
+    for chat in &moderated {
+        pipe.hgetall(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat));
+    }
+    let hashes: Vec<HashMap<String, String>> = pipe.query(conn)?;
+
+    let counter = |hash: &HashMap<String, String>, name: &str| hash.get(name).and_then(|value| value.parse::<i64>().ok()).unwrap_or(0);
+    let mut stats = GlobalStats {
+        recent_spam_events: count_spam_events_since(conn, chrono::Utc::now().timestamp() - spam_event::TTL)?,
+        ..GlobalStats::default()
+    };
+    for (chat_id, hash) in moderated.into_iter().zip(hashes) {
+        let totals = ChatTotals {
+            chat_id,
+            name: hash.get(field::NAME).cloned(),
+            spam: counter(&hash, field::SPAM_COUNT),
+            bans: counter(&hash, field::BANNED),
+            perm_bans: counter(&hash, field::PERM_BANNED),
+        };
+        stats.spam += totals.spam;
+        stats.bans += totals.bans;
+        stats.perm_bans += totals.perm_bans;
+        stats.chats.push(totals);
+    }
+    stats.chats.sort_by(|a, b| {
+        b.spam.cmp(&a.spam).then_with(|| b.bans.cmp(&a.bans)).then_with(|| a.chat_id.cmp(&b.chat_id))
+    });
+    Ok(stats)
+}
+
+/// Renders the `/stats --global` report.
+pub fn render_global_stats(stats: &GlobalStats) -> String {
+    let mut response = format!("Stats across {} moderated chat(s):\n", stats.chats.len());
+    writeln!(&mut response, "• Spam messages: {}", stats.spam).unwrap();
+    writeln!(&mut response, "• Bans: {}", stats.bans).unwrap();
+    writeln!(&mut response, "• Permanent bans: {}", stats.perm_bans).unwrap();
+    writeln!(&mut response, "• Spam events in the last 24 hours, across every chat: {}", stats.recent_spam_events).unwrap();
+    if !stats.chats.is_empty() {
+        writeln!(&mut response, "Busiest chats:").unwrap();
+    }
+    for (rank, chat) in stats.chats.iter().take(stats::BUSIEST_CHATS).enumerate() {
+        writeln!(
+            &mut response,
+            "{}. {}: {} spam, {} ban(s), {} permanent",
+            rank + 1,
+            chat.name.clone().unwrap_or_else(|| chat.chat_id.to_string()),
+            chat.spam,
+            chat.bans,
+            chat.perm_bans,
+        )
+        .unwrap();
+    }
+    response
+}
+
+/// Handles /stats --global: sums the stats of every chat moderated from this admin chat
+pub async fn handle_global_stats(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+
+    let response = match global_stats(&mut redis_conn, chat_id) {
+        Ok(stats) if stats.chats.is_empty() => "No chats are moderated from this chat. Send /stats --global in your admin chat.".to_string(),
+        Ok(stats) => render_global_stats(&stats),
+        Err(e) => format!("Failed to load the stats: {}", e),
+    };
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Renders the `/worstusers` list.
+pub fn render_worst_users(users: &[BannedUser]) -> String {
+    if users.is_empty() {
+        return "No users have a positive reputation.".to_string();
+    }
+    let mut response = format!("Top {} user(s) by reputation:\n", users.len());
+    for (rank, user) in users.iter().enumerate() {
+        let name = user.username.as_ref().map(|u| format!(" @{}", u)).unwrap_or_default();
+        writeln!(&mut response, "{}. {}{} – reputation: {}, bans: {}", rank + 1, user.user_id, name, user.rep, user.ban_count).unwrap();
+    }
+    response
+}
+
+/// Handles /worstusers [n]: lists the n users with the highest reputation
+pub async fn handle_worst_users(bot: Bot, msg: Message, n: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let limit = match n.trim() {
+        "" => Some(stats::DEFAULT_WORST_USERS),
+        n => n.parse::<usize>().ok().filter(|n| (1..=stats::MAX_WORST_USERS).contains(n)),
+    };
+    let Some(limit) = limit else {
+        bot.send_message(chat_id, format!("Usage: /worstusers [n], at most {}", stats::MAX_WORST_USERS)).await?;
+        return Ok(());
+    };
+
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+
+    let response = match worst_users(&mut redis_conn, limit) {
+        Ok(users) => render_worst_users(&users),
+        Err(e) => format!("Failed to list users: {}", e),
+    };
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /trend command: shows daily spam actions in a chat over the last days
+pub async fn handle_trend(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let mut parts = args.splitn(2, '|').map(str::trim);
+    let target_chat = match parts.next().filter(|c| !c.is_empty()) {
+        None => Some(chat_id.0),
+        Some(chat) => chat.parse::<i64>().ok(),
+    };
+    let days = match parts.next().filter(|d| !d.is_empty()) {
+        None => Some(trend::DEFAULT_DAYS),
+        Some(days) => days.parse::<u64>().ok().filter(|d| (1..=trend::RETENTION_DAYS as u64).contains(d)),
+    };
+    let (Some(target_chat), Some(days)) = (target_chat, days) else {
+        bot.send_message(
+            chat_id,
+            format!("Usage: /trend [chat_id][|<days>] (1-{} days)", trend::RETENTION_DAYS),
+        ).await?;
+        return Ok(());
+    };
+
+    let totals = match daily_totals(&mut redis_conn, ChatId(target_chat), days, chrono::Utc::now().date_naive()) {
+        Ok(totals) => totals,
+        Err(e) => {
+            bot.send_message(chat_id, format!("Failed to load daily stats: {}", e)).await?;
+            return Ok(());
+        }
+    };
+    let chat_name: String = redis_conn
+        .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), target_chat), field::NAME)
+        .unwrap_or_else(|_| target_chat.to_string());
+
+    let mut response = String::new();
+    writeln!(&mut response, "Spam actions in chat {} over the last {} days:", chat_name, days).unwrap();
+    response.push_str(&render_trend(&totals));
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /stats command: shows this chat's stats, or lets an admin chat pick a moderated chat
+pub async fn handle_stats(bot: Bot, msg: Message, user_id: UserId) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let is_admin: bool = redis_conn
+        .sismember(key::ns(format!("{}{}", user_id, suffix::ADMIN_CHATS)), chat_id.0)
+        .expect("Failed to get admin chat");
+    if !is_admin {
+        let stats: HashMap<String, String> = redis_conn
+            .hgetall(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0))
+            .expect("Failed to get chat stats");
+        let mut response = String::new();
+        for (field, value) in stats {
+            if field == field::NAME || field == field::ADMIN_CHAT {
+                continue;
+            }
+            writeln!(&mut response, "{}: {}", field, value).unwrap();
+        }
+        bot.send_message(chat_id, response).await?;
+    } else {
+        let chats: Vec<i64> = redis_conn
+            .smembers(format!("{}{}{}", key::ns(key::ADMIN_PREFIX), chat_id.0, suffix::MODERATED_CHATS))
+            .expect("Failed to get moderated chats");
+        let mut rows: Vec<Vec<InlineKeyboardButton>> = Vec::new();
+        for chat in chats {
+            let chat_name: String = redis_conn
+                .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat), field::NAME)
+                .expect("Failed to get chat name");
+            rows.push(vec![InlineKeyboardButton::callback(
+                format!("Chat: {}", chat_name),
+                format!("stats:{}", chat),
+            )]);
+        }
+        let keyboard = InlineKeyboardMarkup::new(rows);
+        bot.send_message(
+            chat_id,
+            "Which chat's stats do you want to see:",
+        )
+        .reply_markup(keyboard)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Handles the /ratelimitstats command: shows the rate limiting settings and counters
+pub async fn handle_rate_limit_stats(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut conn = redis_client.get_connection().expect("Failed to get Redis connection");
+    
+    // Count rate limiting entries
+    let trusted_rate_pattern = format!("{}*", key::ns(rate_limit::TRUSTED_MESSAGE_RATE_PREFIX));
+    let reply_rate_pattern = format!("{}*", key::ns(rate_limit::REPLY_RATE_PREFIX));
+    
+    let trusted_rate_keys: Vec<String> = conn.keys(&trusted_rate_pattern).unwrap_or_default();
+    let reply_rate_keys: Vec<String> = conn.keys(&reply_rate_pattern).unwrap_or_default();
+    
+    let mut trusted_rate_count = 0;
+    let mut reply_rate_count = 0;
+    
+    // Each key is a sliding-window sorted set of event timestamps
+    for key in &trusted_rate_keys {
+        let count: u32 = conn.zcard(key).unwrap_or(0);
+        trusted_rate_count += count;
+    }
+    
+    for key in &reply_rate_keys {
+        let count: u32 = conn.zcard(key).unwrap_or(0);
+        reply_rate_count += count;
+    }
+    
+    bot.send_message(
+        chat_id,
+        format!(
+            "Rate Limiting Statistics:\n\
+            • Trusted message rate limits: {} ({} total)\n\
+            • Reply rate limits: {} ({} total)\n\
+            • Max trusted messages per hour: {}\n\
+            • Max replies per hour: {}",
+            trusted_rate_keys.len(),
+            trusted_rate_count,
+            reply_rate_keys.len(),
+            reply_rate_count,
+            reply_aware::MAX_TRUSTED_MESSAGES_PER_HOUR,
+            reply_aware::MAX_REPLIES_PER_HOUR
+        ),
+    ).await?;
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use teloxide::prelude::*;
+use crate::config::{field, key, suffix, symbol_feature, ENABLED_FEATURES_KEY, TOGGLEABLE_SYMBOLS};
+use redis::{Commands, RedisResult};
+
+/// Number of symbols shown by `/symbolstats`.
+const SYMBOL_STATS_LIMIT: usize = 10;
+
+/// Switches the feature gating `symbol` on or off in the global
+/// `ENABLED_FEATURES_KEY` set. Returns whether it is enabled now, or `None`
+/// if `symbol` isn't in `TOGGLEABLE_SYMBOLS`.
+pub fn toggle_symbol(conn: &mut redis::Connection, symbol: &str) -> RedisResult<Option<bool>> {
+    let Some(feature) = symbol_feature(symbol) else {
+        return Ok(None);
+    };
+    let enabled_key = key::ns(ENABLED_FEATURES_KEY);
+    let disabled: bool = conn.srem(&enabled_key, feature)?;
+    if !disabled {
+        let _: () = conn.sadd(&enabled_key, feature)?;
+    }
+    Ok(Some(!disabled))
+}
+
+/// Renders the global state of every toggleable symbol for `/togglesymbol`.
+pub fn render_symbol_states(conn: &mut redis::Connection) -> RedisResult<String> {
+    let mut response = String::from("Usage: /togglesymbol <symbol>\nGlobal state of the toggleable symbols:\n");
+    for (symbol, feature) in TOGGLEABLE_SYMBOLS {
+        let enabled: bool = conn.sismember(key::ns(ENABLED_FEATURES_KEY), *feature)?;
+        writeln!(&mut response, "{} {}", if enabled { "✅" } else { "❌" }, symbol).unwrap();
+    }
+    Ok(response)
+}
+
+/// Handles the /togglesymbol command: switches a detection symbol on or off
+/// for every chat that doesn't override its feature
+pub async fn handle_toggle_symbol(bot: Bot, msg: Message, symbol: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+
+    let symbol = symbol.trim().to_uppercase();
+    let response = if symbol.is_empty() {
+        render_symbol_states(&mut redis_conn).unwrap_or_else(|e| format!("Failed to read the symbol states: {}", e))
+    } else {
+        match toggle_symbol(&mut redis_conn, &symbol) {
+            Ok(Some(enabled)) => format!(
+                "{} is now {} globally. Chats that switched `{}` {} themselves keep their setting.",
+                symbol,
+                if enabled { "enabled" } else { "disabled" },
+                symbol_feature(&symbol).unwrap_or_default(),
+                if enabled { "off" } else { "on" },
+            ),
+            Ok(None) => format!("{} can't be toggled. Send /togglesymbol to list the symbols that can.", symbol),
+            Err(e) => format!("Failed to toggle {}: {}", symbol, e),
+        }
+    };
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /symbolstats command: shows the most triggered symbols of a chat
+pub async fn handle_symbol_stats(bot: Bot, msg: Message, chat: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let target_chat = if chat.trim().is_empty() {
+        chat_id.0
+    } else {
+        match chat.trim().parse::<i64>() {
+            Ok(id) => id,
+            Err(_) => {
+                bot.send_message(chat_id, "Usage: /symbolstats [chat_id]").await?;
+                return Ok(());
+            }
+        }
+    };
+
+    let chat_name: String = redis_conn
+        .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), target_chat), field::NAME)
+        .unwrap_or_else(|_| target_chat.to_string());
+    let counts: HashMap<String, i64> = redis_conn
+        .hgetall(format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), target_chat, suffix::SYMBOL_COUNTS))
+        .unwrap_or_default();
+
+    let mut sorted: Vec<(String, i64)> = counts.into_iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let mut response = String::new();
+    writeln!(&mut response, "Top symbols for chat: {}", chat_name).unwrap();
+    if sorted.is_empty() {
+        writeln!(&mut response, "No symbols triggered yet.").unwrap();
+    }
+    for (symbol, count) in sorted.iter().take(SYMBOL_STATS_LIMIT) {
+        writeln!(&mut response, "{}: {}", symbol, count).unwrap();
+    }
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
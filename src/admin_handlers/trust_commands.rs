@@ -0,0 +1,435 @@
+use crate::admin_handlers::lookup_username;
+use crate::config::{key, rate_limit, reply_aware};
+use crate::trust_manager::{TrustManager, TrustedMessageType, TrustedMessageMetadata};
+use redis::Commands;
+use teloxide::prelude::*;
+use teloxide::types::{MessageId, UserId};
+
+/// Handles the /trustuser command: trusts all future messages from a user
+pub async fn handle_trust_user(bot: Bot, msg: Message, user: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let parts: Vec<&str> = user.split('|').map(str::trim).collect();
+    let target = match parts[0].parse::<u64>() {
+        Ok(id) => Some(UserId(id)),
+        Err(_) => lookup_username(&mut redis_conn, parts[0]),
+    };
+    let ttl_hours = match parts.get(1) {
+        Some(hours) => hours.parse::<i64>().ok().filter(|h| *h > 0).map(Some),
+        None => Some(None),
+    };
+    let (target, ttl_hours) = match (target, ttl_hours) {
+        (Some(target), Some(ttl_hours)) if parts.len() <= 2 => (target, ttl_hours),
+        _ => {
+            bot.send_message(
+                chat_id,
+                "Usage: /trustuser <user_id|@username>[|<hours>]\n\
+         - hours: optional number of hours the trust lasts (default: until /untrustuser)",
+            )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let trust_manager = TrustManager::new("redis://127.0.0.1/")
+        .expect("Failed to create trust manager");
+    let reply = match trust_manager.trust_user(target, ttl_hours.map(|h| h * 3600)).await {
+        Ok(()) => match ttl_hours {
+            Some(hours) => format!("User {} is trusted for {} hour(s).", target, hours),
+            None => format!("User {} is trusted until /untrustuser.", target),
+        },
+        Err(e) => format!("Failed to trust user: {}", e),
+    };
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
+
+/// Handles the /untrustuser command: stops trusting a user
+pub async fn handle_untrust_user(bot: Bot, msg: Message, user: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let target = match user.trim().parse::<u64>() {
+        Ok(id) => Some(UserId(id)),
+        Err(_) => lookup_username(&mut redis_conn, user.trim()),
+    };
+    let target = match target {
+        Some(target) => target,
+        None => {
+            bot.send_message(chat_id, "Usage: /untrustuser <user_id|@username>").await?;
+            return Ok(());
+        }
+    };
+
+    let trust_manager = TrustManager::new("redis://127.0.0.1/")
+        .expect("Failed to create trust manager");
+    let reply = match trust_manager.untrust_user(target).await {
+        Ok(true) => format!("User {} is no longer trusted.", target),
+        Ok(false) => format!("User {} was not trusted.", target),
+        Err(e) => format!("Failed to untrust user: {}", e),
+    };
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
+
+/// Handles the /replyconfig command: shows or changes the reply-aware trust settings
+pub async fn handle_reply_config(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let parts: Vec<&str> = args.split('|').collect();
+    if parts.len() != 2 {
+        bot.send_message(
+            chat_id,
+            "Usage: /replyconfig <setting>|<value>\n\
+            Settings: enabled, rate_limit, anti_evasion, selective_trust, max_reduction, min_spam_score",
+        ).await?;
+        return Ok(());
+    }
+    
+    let setting = parts[0];
+    let value = parts[1];
+    
+    match setting {
+        "enabled" => {
+            let response = match value.trim().parse::<bool>() {
+                Ok(enabled) => match TrustManager::new("redis://127.0.0.1/")
+                    .and_then(|trust_manager| trust_manager.set_enabled(enabled))
+                {
+                    Ok(()) => format!("Reply-aware filtering: {}", enabled),
+                    Err(e) => format!("Failed to switch reply-aware filtering: {}", e),
+                },
+                Err(_) => "Invalid value. Use true or false.".to_string(),
+            };
+            bot.send_message(chat_id, response).await?;
+        }
+        "rate_limit" => {
+            let enabled = value.parse::<bool>().unwrap_or(true);
+            bot.send_message(
+                chat_id,
+                format!("Rate limiting for reply-aware filtering: {}", enabled),
+            ).await?;
+        }
+        "anti_evasion" => {
+            let enabled = value.parse::<bool>().unwrap_or(true);
+            bot.send_message(
+                chat_id,
+                format!("Anti-evasion measures: {}", enabled),
+            ).await?;
+        }
+        "selective_trust" => {
+            let enabled = value.parse::<bool>().unwrap_or(true);
+            bot.send_message(
+                chat_id,
+                format!("Selective trusting: {}", enabled),
+            ).await?;
+        }
+        "max_reduction" => {
+            let response = match value.trim().parse::<f64>() {
+                Ok(reduction) => match TrustManager::new("redis://127.0.0.1/")
+                    .and_then(|trust_manager| trust_manager.set_max_score_reduction(reduction))
+                {
+                    Ok(()) => format!("Maximum score reduction: {}", reduction),
+                    Err(e) => format!("Failed to set the maximum score reduction: {}", e),
+                },
+                Err(_) => "Invalid value. Use a number of 0 or below, e.g. -5.".to_string(),
+            };
+            bot.send_message(chat_id, response).await?;
+        }
+        "min_spam_score" => {
+            let score = value.parse::<f64>().unwrap_or(1.0);
+            bot.send_message(
+                chat_id,
+                format!("Minimum spam score in replies: {}", score),
+            ).await?;
+        }
+        _ => {
+            bot.send_message(
+                chat_id,
+                "Invalid setting. Use: enabled, rate_limit, anti_evasion, selective_trust, max_reduction, min_spam_score",
+            ).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /marktrusted command: marks a stored message as trusted
+pub async fn handle_mark_trusted(bot: Bot, msg: Message, user_id: UserId, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    // Parse args: "message_id|trust_type"
+    let parts: Vec<&str> = args.split('|').map(str::trim).collect();
+    if parts.len() != 2 {
+        bot.send_message(
+            chat_id,
+            "Usage: /marktrusted <message_id>|<bot|admin|verified>\n\
+         - message_id: the ID of the message to mark as trusted\n\
+         - trust_type: bot, admin, or verified",
+        )
+            .await?;
+        return Ok(());
+    }
+
+    let (message_id_str, trust_type_str) = (parts[0], parts[1]);
+    
+    // Parse message ID
+    let message_id = match message_id_str.parse::<i32>() {
+        Ok(id) => MessageId(id),
+        Err(_) => {
+            bot.send_message(chat_id, "Invalid message ID. Must be a number.").await?;
+            return Ok(());
+        }
+    };
+
+    // Parse trust type
+    let trust_type = match trust_type_str {
+        "bot" => TrustedMessageType::Bot,
+        "admin" => TrustedMessageType::Admin,
+        "verified" => TrustedMessageType::Verified,
+        _ => {
+            bot.send_message(
+                chat_id,
+                "Invalid trust type. Must be 'bot', 'admin', or 'verified'.",
+            )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // Initialize trust manager
+    let trust_manager = match TrustManager::new("redis://127.0.0.1/") {
+        Ok(tm) => tm,
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("Failed to initialize trust manager: {}", e),
+            )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // Create metadata for the trusted message
+    let metadata = TrustedMessageMetadata::new(
+        message_id,
+        chat_id,
+        user_id,
+        trust_type.clone(),
+    );
+
+    // Mark the message as trusted
+    match trust_manager.mark_trusted(metadata).await {
+        Ok(_) => {
+            bot.send_message(
+                chat_id,
+                format!(
+                    "Message {} marked as trusted ({}) for reply-aware filtering.",
+                    message_id.0,
+                    trust_type.as_str()
+                ),
+            )
+                .await?;
+        }
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("Failed to mark message as trusted: {}", e),
+            )
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /truststats command: shows how many trusted messages and replies are tracked
+pub async fn handle_trust_stats(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let trust_manager = TrustManager::new("redis://127.0.0.1/")
+        .expect("Failed to create trust manager");
+    
+    match trust_manager.get_stats().await {
+        Ok(stats) => {
+            bot.send_message(
+                chat_id,
+                format!(
+                    "Trust Management Statistics:\n\
+                    • Trusted messages: {}\n\
+                    • Reply tracking entries: {}\n\
+                    • Reply-aware filtering enabled: {}\n\
+                    • Rate limiting enabled: {}\n\
+                    • Anti-evasion enabled: {}\n\
+                    • Selective trusting enabled: {}",
+                    stats.trusted_messages,
+                    stats.reply_tracking,
+                    trust_manager.is_enabled().unwrap_or(true),
+                    reply_aware::ENABLE_RATE_LIMITING,
+                    reply_aware::ENABLE_ANTI_EVASION,
+                    reply_aware::ENABLE_SELECTIVE_TRUSTING
+                ),
+            ).await?;
+        }
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("Failed to get trust statistics: {}", e),
+            ).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /resetratelimit command: clears a user's trusted message and reply rate limits
+pub async fn handle_reset_rate_limit(bot: Bot, msg: Message, user: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut conn = redis_client.get_connection().expect("Failed to get Redis connection");
+    
+    let trusted_rate_key = format!("{}{}", key::ns(rate_limit::TRUSTED_MESSAGE_RATE_PREFIX), user);
+    let reply_rate_key = format!("{}{}", key::ns(rate_limit::REPLY_RATE_PREFIX), user);
+    
+    let _: () = conn.del(&trusted_rate_key).unwrap_or(());
+    let _: () = conn.del(&reply_rate_key).unwrap_or(());
+    
+    bot.send_message(
+        chat_id,
+        format!("Rate limiting reset for user: {}", user),
+    ).await?;
+    Ok(())
+}
+
+/// Handles the /spampatterns command: shows the reply spam patterns recorded for a user
+pub async fn handle_spam_patterns(bot: Bot, msg: Message, user: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let trust_manager = TrustManager::new("redis://127.0.0.1/")
+        .expect("Failed to create trust manager");
+    
+    let user_id = user.parse::<u64>().unwrap_or(0);
+    match trust_manager.get_spam_patterns(UserId(user_id)).await {
+        Ok(patterns) => {
+            if patterns.is_empty() {
+                bot.send_message(
+                    chat_id,
+                    format!("No spam patterns found for user: {}", user),
+                ).await?;
+            } else {
+                bot.send_message(
+                    chat_id,
+                    format!(
+                        "Spam patterns for user {}:\n{}",
+                        user,
+                        patterns.join("\n")
+                    ),
+                ).await?;
+            }
+        }
+        Err(e) => {
+            bot.send_message(
+                chat_id,
+                format!("Failed to get spam patterns: {}", e),
+            ).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /selectivetrust command: switches a selective trust rule on or off
+pub async fn handle_selective_trust(bot: Bot, msg: Message, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let parts: Vec<&str> = args.split('|').collect();
+    if parts.len() != 2 {
+        bot.send_message(
+            chat_id,
+            "Usage: /selectivetrust <rule>|<true|false>\n\
+            Rules: trust_bot, trust_admin, trust_verified, trust_good_reputation, trust_recent_only",
+        ).await?;
+        return Ok(());
+    }
+    
+    let rule = parts[0];
+    let enabled = parts[1].parse::<bool>().unwrap_or(true);
+    
+    match rule {
+        "trust_bot" => {
+            bot.send_message(
+                chat_id,
+                format!("Trust bot messages: {}", enabled),
+            ).await?;
+        }
+        "trust_admin" => {
+            bot.send_message(
+                chat_id,
+                format!("Trust admin messages: {}", enabled),
+            ).await?;
+        }
+        "trust_verified" => {
+            bot.send_message(
+                chat_id,
+                format!("Trust verified user messages: {}", enabled),
+            ).await?;
+        }
+        "trust_good_reputation" => {
+            bot.send_message(
+                chat_id,
+                format!("Trust messages from users with good reputation: {}", enabled),
+            ).await?;
+        }
+        "trust_recent_only" => {
+            bot.send_message(
+                chat_id,
+                format!("Trust only recent messages: {}", enabled),
+            ).await?;
+        }
+        _ => {
+            bot.send_message(
+                chat_id,
+                "Invalid rule. Use: trust_bot, trust_admin, trust_verified, trust_good_reputation, trust_recent_only",
+            ).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Handles the /antievasionstats command: shows the recorded spam patterns and their counts
+pub async fn handle_anti_evasion_stats(bot: Bot, msg: Message) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut conn = redis_client.get_connection().expect("Failed to get Redis connection");
+    
+    // Count spam pattern entries
+    let spam_pattern_prefix = format!("{}*", key::ns(rate_limit::SPAM_PATTERN_PREFIX));
+    let spam_pattern_keys: Vec<String> = conn.keys(&spam_pattern_prefix).unwrap_or_default();
+    
+    let mut total_patterns = 0;
+    for key in &spam_pattern_keys {
+        let patterns: Vec<String> = conn.smembers(key).unwrap_or_default();
+        total_patterns += patterns.len();
+    }
+    
+    bot.send_message(
+        chat_id,
+        format!(
+            "Anti-Evasion Statistics:\n\
+            • Users with spam patterns: {}\n\
+            • Total spam patterns tracked: {}\n\
+            • Max links in reply: {}\n\
+            • Max phone numbers in reply: {}\n\
+            • Max invite links in reply: {}\n\
+            • Max caps ratio in reply: {}\n\
+            • Max emoji in reply: {}",
+            spam_pattern_keys.len(),
+            total_patterns,
+            reply_aware::anti_evasion::MAX_LINKS_IN_REPLY,
+            reply_aware::anti_evasion::MAX_PHONE_NUMBERS_IN_REPLY,
+            reply_aware::anti_evasion::MAX_INVITE_LINKS_IN_REPLY,
+            reply_aware::anti_evasion::MAX_CAPS_RATIO_IN_REPLY,
+            reply_aware::anti_evasion::MAX_EMOJI_IN_REPLY
+        ),
+    ).await?;
+    Ok(())
+}
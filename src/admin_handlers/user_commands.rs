@@ -0,0 +1,170 @@
+use crate::admin_handlers::lookup_username;
+use crate::config::{field, key, notes};
+use crate::trust_manager::TrustManager;
+use crate::user_notes::{add_note, user_notes, UserNote};
+use redis::{Commands, RedisResult};
+use std::collections::HashMap;
+use std::fmt::Write;
+use teloxide::prelude::*;
+use teloxide::types::UserId;
+
+/// A note as `/notes` and `/whois` list it, e.g. `"2024-01-02 10:00 UTC by 42: warned about crypto links"`.
+fn format_note(note: &UserNote) -> String {
+    let when = chrono::DateTime::from_timestamp(note.timestamp, 0)
+        .map(|dt| dt.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    format!("{} by {}: {}", when, note.author, note.text)
+}
+
+/// Render a stored Unix timestamp for display, or "unknown" when absent.
+pub fn format_timestamp(value: Option<&String>) -> String {
+    value
+        .and_then(|v| v.parse::<i64>().ok())
+        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Handles the /whois command: shows everything known about a user
+pub async fn handle_whois(bot: Bot, msg: Message, user: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user);
+    let info: HashMap<String, String> =
+        redis_conn.hgetall(&user_key).unwrap_or_default();
+    let get = |name: &str| {
+        info.get(name).cloned().unwrap_or_else(|| "unknown".to_string())
+    };
+
+    let trust_manager = TrustManager::new("redis://127.0.0.1/")
+        .expect("Failed to create trust manager");
+    let target = UserId(user.parse::<u64>().unwrap_or(0));
+    let reputation = match trust_manager.get_user_reputation(target).await {
+        Ok(score) => score.to_string(),
+        Err(_) => "unknown".to_string(),
+    };
+    let patterns = trust_manager.get_spam_patterns(target).await.unwrap_or_default();
+
+    let mut response = String::new();
+    writeln!(&mut response, "User {}:", user).unwrap();
+    writeln!(&mut response, "• Username: {}", get(field::USERNAME)).unwrap();
+    writeln!(&mut response, "• Rep: {}", get(field::REP)).unwrap();
+    writeln!(&mut response, "• Reputation (bad - good): {}", reputation).unwrap();
+    writeln!(&mut response, "• Ban count: {}", get(field::BANNED_Q)).unwrap();
+    writeln!(
+        &mut response,
+        "• Currently banned: {}",
+        if info.contains_key(field::BANNED) { "yes" } else { "no" }
+    ).unwrap();
+    writeln!(&mut response, "• Joined: {}", format_timestamp(info.get(field::JOIN_TIME))).unwrap();
+    writeln!(&mut response, "• Last message: {}", format_timestamp(info.get(field::LAST_MSG_TIME))).unwrap();
+    if patterns.is_empty() {
+        writeln!(&mut response, "• Spam patterns: none").unwrap();
+    } else {
+        writeln!(&mut response, "• Spam patterns: {}", patterns.join(", ")).unwrap();
+    }
+    let notes = user_notes(&mut redis_conn, target).unwrap_or_default();
+    if notes.is_empty() {
+        writeln!(&mut response, "• Notes: none").unwrap();
+    } else {
+        writeln!(&mut response, "• Notes:").unwrap();
+        for note in &notes {
+            writeln!(&mut response, "  {}", format_note(note)).unwrap();
+        }
+    }
+
+    bot.send_message(chat_id, response).await?;
+    Ok(())
+}
+
+/// Handles the /note command: leaves a note on a user for the other moderators
+pub async fn handle_note(bot: Bot, msg: Message, user_id: UserId, args: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let (user, text) = args.split_once('|').unwrap_or((args.as_str(), ""));
+    let target = match user.trim().parse::<u64>() {
+        Ok(id) => Some(UserId(id)),
+        Err(_) => lookup_username(&mut redis_conn, user.trim()),
+    };
+    let text = text.trim();
+    let (Some(target), false) = (target, text.is_empty()) else {
+        bot.send_message(chat_id, "Usage: /note <user_id|@username>|<text>").await?;
+        return Ok(());
+    };
+    if text.chars().count() > notes::MAX_LEN {
+        bot.send_message(chat_id, format!("Notes are limited to {} characters.", notes::MAX_LEN)).await?;
+        return Ok(());
+    }
+
+    let reply = match add_note(&mut redis_conn, target, user_id, text, chrono::Utc::now().timestamp()) {
+        Ok(()) => format!("Note added to user {}.", target),
+        Err(e) => format!("Failed to add the note: {}", e),
+    };
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
+
+/// Handles the /notes command: lists the notes left on a user
+pub async fn handle_notes(bot: Bot, msg: Message, user: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let target = match user.trim().parse::<u64>() {
+        Ok(id) => Some(UserId(id)),
+        Err(_) => lookup_username(&mut redis_conn, user.trim()),
+    };
+    let Some(target) = target else {
+        bot.send_message(chat_id, "Usage: /notes <user_id|@username>").await?;
+        return Ok(());
+    };
+
+    let reply = match user_notes(&mut redis_conn, target) {
+        Ok(notes) if notes.is_empty() => format!("No notes on user {}.", target),
+        Ok(notes) => {
+            let mut reply = format!("Notes on user {}:\n", target);
+            for note in &notes {
+                writeln!(&mut reply, "{}", format_note(note)).unwrap();
+            }
+            reply
+        }
+        Err(e) => format!("Failed to read the notes: {}", e),
+    };
+    bot.send_message(chat_id, reply).await?;
+    Ok(())
+}
+
+/// Handles the /reputation command: shows a user's reputation
+pub async fn handle_reputation(bot: Bot, msg: Message, user: String) -> ResponseResult<()> {
+    let chat_id = msg.chat.id;
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    let key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user);
+
+    let user_rep: RedisResult<i64> = redis_conn.hget(key.clone(), field::REP);
+
+    match user_rep {
+        Ok(rep) => {
+            bot.send_message(chat_id, format!("Reputation for {}: {}", user, rep))
+                .await?;
+        }
+        Err(_) => {
+            bot.send_message(chat_id, format!("Reputation for {}: 0", user))
+                .await?;
+        }
+    }
+    Ok(())
+}
@@ -24,6 +24,8 @@ pub mod settings {
     pub const SESSION_TIMEOUT: u64 = 3600; // 1 hour
     /// Default rate limit for admin commands (commands per minute)
     pub const ADMIN_COMMAND_RATE_LIMIT: u32 = 30;
+    /// Number of entries shown per page in paginated lists
+    pub const PAGE_SIZE: usize = 10;
 }
 
 /// **Admin Panel Status:** possible states of the admin panel.
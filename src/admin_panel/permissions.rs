@@ -42,16 +42,16 @@ impl std::fmt::Display for AdminPermission {
 
 impl AdminPermission {
     /// Convert permission to string for storage
-    pub fn to_string(&self) -> String {
+    pub fn as_str(&self) -> &'static str {
         match self {
-            AdminPermission::ViewStats => "view_stats".to_string(),
-            AdminPermission::ManageChats => "manage_chats".to_string(),
-            AdminPermission::ManageUsers => "manage_users".to_string(),
-            AdminPermission::ConfigureBot => "configure_bot".to_string(),
-            AdminPermission::ViewAuditLog => "view_audit_log".to_string(),
-            AdminPermission::ViewConfig => "view_config".to_string(),
-            AdminPermission::EmergencyControl => "emergency_control".to_string(),
-            AdminPermission::FullAccess => "full_access".to_string(),
+            AdminPermission::ViewStats => "view_stats",
+            AdminPermission::ManageChats => "manage_chats",
+            AdminPermission::ManageUsers => "manage_users",
+            AdminPermission::ConfigureBot => "configure_bot",
+            AdminPermission::ViewAuditLog => "view_audit_log",
+            AdminPermission::ViewConfig => "view_config",
+            AdminPermission::EmergencyControl => "emergency_control",
+            AdminPermission::FullAccess => "full_access",
         }
     }
 
@@ -409,7 +409,7 @@ impl AdminUser {
     /// Get permissions as sorted vector
     pub fn get_permissions_sorted(&self) -> Vec<AdminPermission> {
         let mut permissions: Vec<_> = self.permissions.iter().cloned().collect();
-        permissions.sort_by(|a, b| a.to_string().cmp(&b.to_string()));
+        permissions.sort_by_key(|p| p.as_str());
         permissions
     }
 
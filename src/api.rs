@@ -0,0 +1,203 @@
+//! JSON API for external tooling, served by the same warp server as `/health`.
+//!
+//! Every request must carry `Authorization: Bearer <API_TOKEN>`; the API is
+//! only mounted when `API_TOKEN` is set.
+//!
+//! - `GET /api/user/<id>`: what `/whois` shows, as a `UserInfo`.
+//! - `GET /api/chat/<id>/stats`: the counters `/stats` shows, as a `ChatStats`.
+//! - `POST /api/whitelist`: adds a `WhitelistRequest` entry, like `/whitelist ...|add|...`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+
+use redis::{Commands, RedisResult};
+use serde::{Deserialize, Serialize};
+use teloxide::types::{ChatId, UserId};
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+use crate::config::{api, field, key};
+use crate::lists;
+use crate::trust_manager::TrustManager;
+
+/// A user as returned by `GET /api/user/<id>`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserInfo {
+    pub user_id: u64,
+    pub username: Option<String>,
+    pub rep: i64,
+    /// Bad minus good reputation events, see `TrustManager::get_user_reputation`.
+    pub reputation: i64,
+    pub ban_count: i64,
+    pub banned: bool,
+    /// Unix timestamp the user joined at.
+    pub join_time: Option<i64>,
+    /// Unix timestamp of the user's last message.
+    pub last_msg_time: Option<i64>,
+    pub spam_patterns: Vec<String>,
+}
+
+/// A chat's counters as returned by `GET /api/chat/<id>/stats`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatStats {
+    pub chat_id: i64,
+    pub name: Option<String>,
+    /// Every other field of the chat's hash (`spam_count`, `deleted`, ...).
+    pub stats: BTreeMap<String, String>,
+}
+
+/// Body of `POST /api/whitelist`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WhitelistRequest {
+    /// `user` or `word`.
+    pub kind: String,
+    pub entry: String,
+    /// Scopes the entry to this chat; global when absent.
+    #[serde(default)]
+    pub chat_id: Option<i64>,
+}
+
+/// Reply to `POST /api/whitelist`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WhitelistResponse {
+    /// False when the entry was already whitelisted.
+    pub added: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiError {
+    error: String,
+}
+
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+/// The configured API token, if any.
+pub fn api_token() -> Option<String> {
+    std::env::var(api::TOKEN_ENV).ok().filter(|token| !token.trim().is_empty())
+}
+
+fn error(status: StatusCode, message: impl Into<String>) -> warp::reply::Response {
+    warp::reply::with_status(warp::reply::json(&ApiError { error: message.into() }), status).into_response()
+}
+
+fn redis_connection() -> RedisResult<redis::Connection> {
+    redis::Client::open("redis://127.0.0.1/")?.get_connection()
+}
+
+/// Passes requests carrying `Authorization: Bearer <token>`.
+fn authorized(token: String) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and_then(move |header: Option<String>| {
+            let expected = format!("Bearer {}", token);
+            async move {
+                if header.as_deref() == Some(expected.as_str()) {
+                    Ok(())
+                } else {
+                    Err(warp::reject::custom(Unauthorized))
+                }
+            }
+        })
+        .untuple_one()
+}
+
+/// The `/api` routes, guarded by `token`.
+pub fn routes(token: String) -> impl Filter<Extract = (warp::reply::Response,), Error = Infallible> + Clone {
+    let user = warp::path!("user" / u64)
+        .and(warp::get())
+        .and_then(|user_id| async move { Ok::<_, Rejection>(get_user(UserId(user_id)).await) });
+    let chat_stats = warp::path!("chat" / i64 / "stats")
+        .and(warp::get())
+        .and_then(|chat_id| async move { Ok::<_, Rejection>(get_chat_stats(ChatId(chat_id))) });
+    let whitelist = warp::path!("whitelist")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and_then(|request| async move { Ok::<_, Rejection>(add_to_whitelist(request)) });
+
+    warp::path("api")
+        .and(authorized(token))
+        .and(user.or(chat_stats).unify().or(whitelist).unify())
+        .recover(handle_rejection)
+        .unify()
+}
+
+async fn handle_rejection(rejection: Rejection) -> Result<warp::reply::Response, Infallible> {
+    Ok(if rejection.find::<Unauthorized>().is_some() {
+        error(StatusCode::UNAUTHORIZED, "missing or wrong bearer token")
+    } else if rejection.find::<warp::body::BodyDeserializeError>().is_some() {
+        error(StatusCode::BAD_REQUEST, "invalid JSON body")
+    } else if rejection.find::<warp::reject::MethodNotAllowed>().is_some() {
+        error(StatusCode::METHOD_NOT_ALLOWED, "method not allowed")
+    } else {
+        error(StatusCode::NOT_FOUND, "not found")
+    })
+}
+
+async fn get_user(user_id: UserId) -> warp::reply::Response {
+    let mut conn = match redis_connection() {
+        Ok(conn) => conn,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let info: HashMap<String, String> =
+        match conn.hgetall(format!("{}{}", key::ns(key::TG_USERS_PREFIX), user_id.0)) {
+            Ok(info) => info,
+            Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        };
+    if info.is_empty() {
+        return error(StatusCode::NOT_FOUND, format!("unknown user {}", user_id));
+    }
+    let number = |name: &str| info.get(name).and_then(|value| value.parse::<i64>().ok());
+
+    let (reputation, spam_patterns) = match TrustManager::new("redis://127.0.0.1/") {
+        Ok(trust_manager) => (
+            trust_manager.get_user_reputation(user_id).await.unwrap_or(0),
+            trust_manager.get_spam_patterns(user_id).await.unwrap_or_default(),
+        ),
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    warp::reply::json(&UserInfo {
+        user_id: user_id.0,
+        username: info.get(field::USERNAME).cloned(),
+        rep: number(field::REP).unwrap_or(0),
+        reputation,
+        ban_count: number(field::BANNED_Q).unwrap_or(0),
+        banned: info.contains_key(field::BANNED),
+        join_time: number(field::JOIN_TIME),
+        last_msg_time: number(field::LAST_MSG_TIME),
+        spam_patterns,
+    })
+    .into_response()
+}
+
+fn get_chat_stats(chat_id: ChatId) -> warp::reply::Response {
+    let stored: RedisResult<HashMap<String, String>> = redis_connection()
+        .and_then(|mut conn| conn.hgetall(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0)));
+    let mut stats = match stored {
+        Ok(stats) if stats.is_empty() => return error(StatusCode::NOT_FOUND, format!("unknown chat {}", chat_id)),
+        Ok(stats) => stats,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let name = stats.remove(field::NAME);
+    stats.remove(field::ADMIN_CHAT);
+    warp::reply::json(&ChatStats { chat_id: chat_id.0, name, stats: stats.into_iter().collect() }).into_response()
+}
+
+fn add_to_whitelist(request: WhitelistRequest) -> warp::reply::Response {
+    let list = match request.kind.as_str() {
+        "user" => lists::WHITELIST_USERS,
+        "word" => lists::WHITELIST_WORDS,
+        _ => return error(StatusCode::BAD_REQUEST, "kind must be `user` or `word`"),
+    };
+    let entry = request.entry.trim();
+    if !list.entry.is_valid(entry) {
+        return error(StatusCode::BAD_REQUEST, format!("`{}` is not a valid {} entry", entry, request.kind));
+    }
+    let added = redis_connection()
+        .and_then(|mut conn| list.add(&mut conn, request.chat_id.map(ChatId), entry));
+    match added {
+        Ok(added) => warp::reply::json(&WhitelistResponse { added }).into_response(),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
@@ -0,0 +1,71 @@
+//! Risky document detection behind `TG_ATTACHMENT_SPAM`.
+//!
+//! Spam often arrives as an `.apk` or `.exe` with an enticing caption. A
+//! document is flagged when its file name ends in a risky extension or its
+//! MIME type is one of `attachment::RISKY_MIME_TYPES`. Admins manage the
+//! extensions in `tg:risky_extensions` with `/riskyext`; while that set is
+//! empty `attachment::DEFAULT_EXTENSIONS` apply.
+
+use std::collections::HashSet;
+
+use redis::{Commands, RedisResult};
+use rspamd_client::protocol::{scan::Symbol, RspamdScanReply};
+use teloxide::types::{Document, Message};
+
+use crate::config::{attachment, is_feature_enabled, key, symbol, ATTACHMENT_SPAM_FEATURE};
+
+/// Lowercased extensions that make a document risky.
+pub fn risky_extensions(conn: &mut redis::Connection) -> RedisResult<HashSet<String>> {
+    let configured: HashSet<String> = conn.smembers(key::ns(key::TG_RISKY_EXTENSIONS_KEY))?;
+    if configured.is_empty() {
+        return Ok(attachment::DEFAULT_EXTENSIONS.iter().map(|ext| ext.to_string()).collect());
+    }
+    Ok(configured.into_iter().map(|ext| normalize_extension(&ext)).collect())
+}
+
+/// `ext` lowercased and without a leading dot, as stored in `tg:risky_extensions`.
+pub fn normalize_extension(ext: &str) -> String {
+    ext.trim().trim_start_matches('.').to_lowercase()
+}
+
+/// Why `document` is risky (its file name or MIME type), or `None`.
+pub fn risky_document(document: &Document, extensions: &HashSet<String>) -> Option<String> {
+    // Trailing dots and spaces are ignored by Windows, so they don't hide the extension
+    let file_name = document.file_name.as_deref().map(|name| name.trim_end_matches(['.', ' ']));
+    if let Some((_, ext)) = file_name.and_then(|name| name.rsplit_once('.')) {
+        if extensions.contains(&ext.to_lowercase()) {
+            return file_name.map(str::to_string);
+        }
+    }
+    document
+        .mime_type
+        .as_ref()
+        .map(|mime| mime.essence_str().to_string())
+        .filter(|mime| attachment::RISKY_MIME_TYPES.contains(&mime.as_str()))
+}
+
+/// Adds `TG_ATTACHMENT_SPAM` to the scan of `msg` when it carries a risky
+/// document and the feature is on for the chat.
+pub fn apply_attachment_spam(conn: &mut redis::Connection, reply: &mut RspamdScanReply, msg: &Message) -> RedisResult<()> {
+    let Some(document) = msg.document() else {
+        return Ok(());
+    };
+    if !is_feature_enabled(conn, msg.chat.id.0, ATTACHMENT_SPAM_FEATURE) {
+        return Ok(());
+    }
+    let Some(reason) = risky_document(document, &risky_extensions(conn)?) else {
+        return Ok(());
+    };
+    reply.score += attachment::SCORE;
+    reply.symbols.insert(
+        symbol::TG_ATTACHMENT_SPAM.to_string(),
+        Symbol {
+            name: symbol::TG_ATTACHMENT_SPAM.to_string(),
+            score: attachment::SCORE,
+            metric_score: attachment::SCORE,
+            description: Some("Document of a type commonly used to spread malware".to_string()),
+            options: Some(vec![reason]),
+        },
+    );
+    Ok(())
+}
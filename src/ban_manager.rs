@@ -1,6 +1,9 @@
-use crate::config::{field, key, BAN_COUNTER_REDUCTION_INTERVAL};
-use redis::Commands;
+use crate::config::{field, key, reputation, BAN_COUNTER_REDUCTION_INTERVAL};
+use redis::{Commands, RedisResult};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
 use std::error::Error;
+use teloxide::types::{ChatId, UserId};
 use tokio::time::{sleep, Duration};
 use chrono::Utc;
 
@@ -30,7 +33,7 @@ impl BanManager {
         let current_time = Utc::now().timestamp();
         
         // Get all user keys
-        let user_keys: Vec<String> = redis_conn.keys(&format!("{}*", key::TG_USERS_PREFIX))?;
+        let user_keys: Vec<String> = redis_conn.keys(&format!("{}*", key::ns(key::TG_USERS_PREFIX)))?;
         
         for user_key in user_keys {
             // Check if this user has a ban reduction time set
@@ -64,4 +67,159 @@ impl BanManager {
         
         Ok(())
     }
-} 
\ No newline at end of file
+} 
+/// Forgets a user's ban, temporary or permanent, returning the chat it was
+/// issued in (if any). `banned_q` is kept so later bans still escalate.
+pub fn clear_ban(conn: &mut redis::Connection, user_id: UserId) -> RedisResult<Option<ChatId>> {
+    let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user_id.0);
+    let (banned_in,): (Option<i64>,) = redis::pipe()
+        .hget(&user_key, field::BANNED_IN)
+        .hdel(&user_key, &[field::BANNED, field::BANNED_IN, field::PERM_BANNED]).ignore()
+        .query(conn)?;
+    Ok(banned_in.map(ChatId))
+}
+
+/// A user as listed by `/banlist` and `/worstusers`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BannedUser {
+    pub user_id: u64,
+    pub username: Option<String>,
+    /// Number of bans the user has collected (`banned_q`).
+    pub ban_count: i64,
+    pub rep: i64,
+}
+
+/// Users whose current ban was issued in `chat_id`, most banned first.
+///
+/// User keys are walked with a `SCAN` cursor and each batch is read with a
+/// single pipeline, so Redis is never blocked by `KEYS`.
+pub fn banned_users(conn: &mut redis::Connection, chat_id: ChatId) -> RedisResult<Vec<BannedUser>> {
+    let prefix = key::ns(key::TG_USERS_PREFIX);
+    let pattern = format!("{}*", prefix);
+    let chat = chat_id.0.to_string();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut users = Vec::new();
+    let mut cursor: u64 = 0;
+
+    loop {
+        let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(&pattern)
+            .arg("COUNT")
+            .arg(reputation::SCAN_BATCH_SIZE)
+            .query(conn)?;
+
+        // Only plain `tg:users:<id>` hashes; SCAN may also return a key more than once
+        let keys: Vec<(String, u64)> = batch
+            .into_iter()
+            .filter_map(|k| {
+                let user_id = k[prefix.len()..].parse::<u64>().ok()?;
+                seen.insert(k.clone()).then_some((k, user_id))
+            })
+            .collect();
+
+        if !keys.is_empty() {
+            let mut read = redis::pipe();
+            for (user_key, _) in &keys {
+                read.cmd("HMGET")
+                    .arg(user_key)
+                    .arg(field::BANNED)
+                    .arg(field::BANNED_IN)
+                    .arg(field::USERNAME)
+                    .arg(field::BANNED_Q)
+                    .arg(field::REP);
+            }
+            type BanState = (Option<String>, Option<String>, Option<String>, Option<i64>, Option<i64>);
+            let states: Vec<BanState> = read.query(conn)?;
+
+            for ((_, user_id), (banned, banned_in, username, ban_count, rep)) in keys.into_iter().zip(states) {
+                if banned.as_deref() == Some("1") && banned_in.as_deref() == Some(chat.as_str()) {
+                    users.push(BannedUser {
+                        user_id,
+                        username,
+                        ban_count: ban_count.unwrap_or(0),
+                        rep: rep.unwrap_or(0),
+                    });
+                }
+            }
+        }
+
+        cursor = next_cursor;
+        if cursor == 0 {
+            break;
+        }
+    }
+
+    users.sort_by(|a, b| b.ban_count.cmp(&a.ban_count).then(a.user_id.cmp(&b.user_id)));
+    Ok(users)
+}
+
+/// The `limit` users with the highest `rep`, worst first, as listed by
+/// `/worstusers`. Users without positive reputation are left out.
+///
+/// Keys are walked with `SCAN` like in `banned_users`, but only the current
+/// top `limit` are kept in a min-heap, so memory stays bounded however many
+/// users there are.
+pub fn worst_users(conn: &mut redis::Connection, limit: usize) -> RedisResult<Vec<BannedUser>> {
+    if limit == 0 {
+        return Ok(Vec::new());
+    }
+    let prefix = key::ns(key::TG_USERS_PREFIX);
+    let pattern = format!("{}*", prefix);
+    let mut seen: HashSet<String> = HashSet::new();
+    // Ties on `rep` go to the lower user id, so the order is stable
+    type Ranked = (i64, Reverse<u64>, Option<String>, i64);
+    let mut top: BinaryHeap<Reverse<Ranked>> = BinaryHeap::with_capacity(limit + 1);
+    let mut cursor: u64 = 0;
+
+    loop {
+        let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(&pattern)
+            .arg("COUNT")
+            .arg(reputation::SCAN_BATCH_SIZE)
+            .query(conn)?;
+
+        let keys: Vec<(String, u64)> = batch
+            .into_iter()
+            .filter_map(|k| {
+                let user_id = k[prefix.len()..].parse::<u64>().ok()?;
+                seen.insert(k.clone()).then_some((k, user_id))
+            })
+            .collect();
+
+        if !keys.is_empty() {
+            let mut read = redis::pipe();
+            for (user_key, _) in &keys {
+                read.cmd("HMGET")
+                    .arg(user_key)
+                    .arg(field::REP)
+                    .arg(field::USERNAME)
+                    .arg(field::BANNED_Q);
+            }
+            let states: Vec<(Option<i64>, Option<String>, Option<i64>)> = read.query(conn)?;
+
+            for ((_, user_id), (rep, username, ban_count)) in keys.into_iter().zip(states) {
+                let Some(rep) = rep.filter(|rep| *rep > 0) else { continue };
+                top.push(Reverse((rep, Reverse(user_id), username, ban_count.unwrap_or(0))));
+                if top.len() > limit {
+                    top.pop();
+                }
+            }
+        }
+
+        cursor = next_cursor;
+        if cursor == 0 {
+            break;
+        }
+    }
+
+    // Sorting the `Reverse` entries ascending puts the highest `rep` first
+    Ok(top
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse((rep, Reverse(user_id), username, ban_count))| BannedUser { user_id, username, ban_count, rep })
+        .collect())
+}
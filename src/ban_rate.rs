@@ -0,0 +1,75 @@
+//! Per-chat cap on automated bans.
+//!
+//! During a raid the bot could ban dozens of accounts within seconds, which
+//! runs into Telegram's limits and leaves admins nothing to review. Every
+//! `tg_ban` first asks `admit_ban`: past the chat's limit of bans per
+//! `ban_rate::WINDOW_SECS` (a sliding window kept in the
+//! `tg:chats:<id>:ban_times` sorted set) the ban is deferred, so the message
+//! is only deleted, and the first deferral of a window escalates to a
+//! lockdown alert for the admins.
+
+use redis::{Commands, RedisResult};
+use teloxide::types::ChatId;
+
+use crate::config::{ban_rate, field, key, suffix};
+
+/// What `admit_ban` decided.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BanDecision {
+    /// Under the limit; the ban was counted and may go ahead.
+    Allowed,
+    /// The chat already had `recent` bans this window, reaching its `limit`.
+    /// `escalate` is set for the first deferral of the window only.
+    Deferred { recent: i64, limit: i64, escalate: bool },
+}
+
+fn chat_key(chat_id: ChatId) -> String {
+    format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0)
+}
+
+/// Automated bans per window allowed in `chat_id`.
+pub fn ban_rate_limit(conn: &mut redis::Connection, chat_id: ChatId) -> i64 {
+    conn.hget::<_, _, Option<i64>>(chat_key(chat_id), field::BAN_RATE_MAX)
+        .ok()
+        .flatten()
+        .unwrap_or(ban_rate::DEFAULT_MAX_PER_MINUTE)
+}
+
+/// Sets `chat_id`'s ban rate limit, or goes back to the default with `None`.
+pub fn set_ban_rate_limit(conn: &mut redis::Connection, chat_id: ChatId, limit: Option<i64>) -> RedisResult<()> {
+    match limit {
+        Some(limit) => conn.hset(chat_key(chat_id), field::BAN_RATE_MAX, limit),
+        None => conn.hdel(chat_key(chat_id), field::BAN_RATE_MAX),
+    }
+}
+
+/// Decides whether an automated ban in `chat_id` at `now_ms` (Unix
+/// milliseconds) may go ahead, counting it if so.
+pub fn admit_ban(conn: &mut redis::Connection, chat_id: ChatId, now_ms: i64) -> RedisResult<BanDecision> {
+    let limit = ban_rate_limit(conn, chat_id);
+    let times_key = format!("{}{}", chat_key(chat_id), suffix::BAN_TIMES);
+    let (recent,): (i64,) = redis::pipe()
+        .atomic()
+        .zrembyscore(&times_key, "-inf", now_ms - ban_rate::WINDOW_SECS * 1000).ignore()
+        .zcard(&times_key)
+        .query(conn)?;
+
+    if recent < limit {
+        let member = format!("{}:{}", now_ms, uuid::Uuid::new_v4());
+        redis::pipe()
+            .zadd(&times_key, member, now_ms).ignore()
+            .expire(&times_key, ban_rate::WINDOW_SECS).ignore()
+            .query::<()>(conn)?;
+        return Ok(BanDecision::Allowed);
+    }
+
+    let lockdown_key = format!("{}{}", chat_key(chat_id), suffix::BAN_LOCKDOWN);
+    let first: Option<String> = redis::cmd("SET")
+        .arg(lockdown_key)
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(ban_rate::WINDOW_SECS)
+        .query(conn)?;
+    Ok(BanDecision::Deferred { recent, limit, escalate: first.is_some() })
+}
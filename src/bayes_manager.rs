@@ -1,11 +1,15 @@
-use redis::Commands;
+use redis::aio::MultiplexedConnection;
+use redis::{AsyncCommands, Commands};
 use reqwest::Client;
 use std::collections::HashMap;
 use anyhow::Result;
-use crate::config::{rspamd, bayes, neural};
+use crate::config::{rspamd, bayes, neural, retention};
 use crate::neural_manager::NeuralManager;
+use crate::handlers::count_emoji;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use teloxide::{prelude::Requester, types::ChatId, Bot};
+use tokio::sync::OnceCell;
 
 /// Text features extracted for neural network training.
 #[derive(Debug, Serialize, Deserialize)]
@@ -46,8 +50,12 @@ pub struct DetailedClassifierInfo {
 /// - Monitor classifier readiness
 /// - Manage learned message tracking
 /// - Integrate with neural network training
+///
+/// The async learning methods share one multiplexed Redis connection, so
+/// concurrent calls never block the runtime or wait on each other.
 pub struct BayesManager {
     redis_client: redis::Client,
+    async_conn: OnceCell<MultiplexedConnection>,
     rspamd_client: Client,
     rspamd_url: String,
     rspamd_password: String,
@@ -60,17 +68,40 @@ impl BayesManager {
     /// 
     /// A `Result<Self>` containing the BayesManager or an error if initialization fails.
     pub fn new() -> Result<Self> {
+        Self::with_controller_url(&rspamd::controller_url())
+    }
+    
+    /// Creates a BayesManager that talks to the Rspamd controller at `url`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `url` - Base URL of the Rspamd controller API
+    /// 
+    /// # Returns
+    /// 
+    /// A `Result<Self>` containing the BayesManager or an error if initialization fails.
+    pub fn with_controller_url(url: &str) -> Result<Self> {
         let redis_client = redis::Client::open("redis://127.0.0.1/")?;
         let rspamd_client = Client::new();
         
         Ok(Self {
             redis_client,
+            async_conn: OnceCell::new(),
             rspamd_client,
-            rspamd_url: rspamd::CONTROLLER_URL.to_string(),
-            rspamd_password: rspamd::PASSWORD.to_string(),
+            rspamd_url: url.to_string(),
+            rspamd_password: rspamd::password(),
         })
     }
     
+    /// Returns a handle to the shared multiplexed Redis connection, opening it on first use.
+    async fn async_connection(&self) -> Result<MultiplexedConnection> {
+        let conn = self
+            .async_conn
+            .get_or_try_init(|| self.redis_client.get_multiplexed_async_connection())
+            .await?;
+        Ok(conn.clone())
+    }
+    
     /// Learns a message as spam via Rspamd HTTP API and triggers neural network training.
     /// 
     /// # Arguments
@@ -107,12 +138,12 @@ impl BayesManager {
         let status = response.status();
         if status.is_success() {
             // Store learning record in Redis
-            let mut conn = self.redis_client.get_connection()?;
+            let mut conn = self.async_connection().await?;
             let key = format!("{}spam:{}", bayes::BAYES_LEARNED_PREFIX, message_id);
-            let _: () = conn.set_ex(&key, "1", bayes::LEARNED_EXPIRY)?;
+            let _: () = conn.set_ex(&key, "1", retention::bayes_learned() as u64).await?;
             
             // Increment spam message counter
-            let _: i64 = conn.incr(bayes::BAYES_SPAM_MESSAGES_KEY, 1)?;
+            let _: i64 = conn.incr(bayes::BAYES_SPAM_MESSAGES_KEY, 1).await?;
             
             // Integrate with neural network training
             self.update_neural_training_stats("spam", message_id, content).await?;
@@ -169,12 +200,12 @@ impl BayesManager {
         let status = response.status();
         if status.is_success() {
             // Store learning record in Redis
-            let mut conn = self.redis_client.get_connection()?;
+            let mut conn = self.async_connection().await?;
             let key = format!("{}ham:{}", bayes::BAYES_LEARNED_PREFIX, message_id);
-            let _: () = conn.set_ex(&key, "1", bayes::LEARNED_EXPIRY)?;
+            let _: () = conn.set_ex(&key, "1", retention::bayes_learned() as u64).await?;
             
             // Increment ham message counter
-            let _: i64 = conn.incr(bayes::BAYES_HAM_MESSAGES_KEY, 1)?;
+            let _: i64 = conn.incr(bayes::BAYES_HAM_MESSAGES_KEY, 1).await?;
             
             // Integrate with neural network training
             self.update_neural_training_stats("ham", message_id, content).await?;
@@ -208,22 +239,22 @@ impl BayesManager {
     /// A `Result<()>` indicating success or failure of the update operation.
     async fn update_neural_training_stats(&self, learning_type: &str, message_id: &str, content: &str) -> Result<()> {
         let neural_manager = NeuralManager::new()?;
-        let mut conn = self.redis_client.get_connection()?;
+        let mut conn = self.async_connection().await?;
         
         // Update neural network statistics
         let now = Utc::now().to_rfc3339();
         
         // Increment total messages
-        let _: i64 = conn.hincr(neural::NEURAL_STATS_KEY, "total_messages", 1)?;
+        let _: i64 = conn.hincr(neural::NEURAL_STATS_KEY, "total_messages", 1).await?;
         
         // Increment specific message type counter
         match learning_type {
             "spam" => {
-                let _: i64 = conn.hincr(neural::NEURAL_STATS_KEY, "spam_messages", 1)?;
+                let _: i64 = conn.hincr(neural::NEURAL_STATS_KEY, "spam_messages", 1).await?;
                 log::info!("Neural network: Added spam message to training dataset");
             }
             "ham" => {
-                let _: i64 = conn.hincr(neural::NEURAL_STATS_KEY, "ham_messages", 1)?;
+                let _: i64 = conn.hincr(neural::NEURAL_STATS_KEY, "ham_messages", 1).await?;
                 log::info!("Neural network: Added ham message to training dataset");
             }
             _ => {
@@ -233,7 +264,7 @@ impl BayesManager {
         }
         
         // Update last training timestamp
-        let _: () = conn.hset(neural::NEURAL_STATS_KEY, "last_training", &now)?;
+        let _: () = conn.hset(neural::NEURAL_STATS_KEY, "last_training", &now).await?;
         
         // Store message features for neural network training
         self.store_neural_features(message_id, content, learning_type).await?;
@@ -244,7 +275,7 @@ impl BayesManager {
                 log::info!("Neural network is ready, {} learning will contribute to model training", learning_type);
                 
                 // Increment training iterations when network is ready
-                let _: i64 = conn.hincr(neural::NEURAL_STATS_KEY, "training_iterations", 1)?;
+                let _: i64 = conn.hincr(neural::NEURAL_STATS_KEY, "training_iterations", 1).await?;
             }
             Ok(false) => {
                 log::info!("Neural network is still training, {} learning will help build dataset", learning_type);
@@ -269,7 +300,7 @@ impl BayesManager {
     /// 
     /// A `Result<()>` indicating success or failure of the storage operation.
     async fn store_neural_features(&self, message_id: &str, content: &str, learning_type: &str) -> Result<()> {
-        let mut conn = self.redis_client.get_connection()?;
+        let mut conn = self.async_connection().await?;
         
         // Extract basic text features
         let features = self.extract_text_features(content);
@@ -288,8 +319,8 @@ impl BayesManager {
             "features": features
         });
         
-        // Store features with expiration (7 days)
-        let _: () = conn.set_ex(&feature_key, feature_data.to_string(), 7 * 24 * 60 * 60)?;
+        // Store features until the retention window passes
+        let _: () = conn.set_ex(&feature_key, feature_data.to_string(), retention::neural_features() as u64).await?;
         
         log::debug!("Stored neural features for message {}: {:?}", message_id, features);
         Ok(())
@@ -307,16 +338,7 @@ impl BayesManager {
     fn extract_text_features(&self, content: &str) -> TextFeatures {
         let word_count = content.split_whitespace().count();
         let link_count = content.matches("http").count() + content.matches("www").count();
-        let emoji_count = content.chars().filter(|c| {
-            // Simple emoji detection - check for emoji ranges
-            let code = *c as u32;
-            (code >= 0x1F600 && code <= 0x1F64F) || // Emoticons
-            (code >= 0x1F300 && code <= 0x1F5FF) || // Miscellaneous Symbols and Pictographs
-            (code >= 0x1F680 && code <= 0x1F6FF) || // Transport and Map Symbols
-            (code >= 0x1F1E0 && code <= 0x1F1FF) || // Regional Indicator Symbols
-            (code >= 0x2600 && code <= 0x26FF) ||   // Miscellaneous Symbols
-            (code >= 0x2700 && code <= 0x27BF)      // Dingbats
-        }).count();
+        let emoji_count = count_emoji(content);
         
         let caps_count = content.chars().filter(|c| c.is_uppercase()).count();
         let total_chars = content.chars().filter(|c| c.is_alphabetic()).count();
@@ -388,6 +410,18 @@ impl BayesManager {
         Ok(*spam_messages >= bayes::MIN_SPAM_MESSAGES && *ham_messages >= bayes::MIN_HAM_MESSAGES)
     }
     
+    /// Claims the one-time "classifier ready" notification: true only for the
+    /// first call after the classifier reached its thresholds, until the
+    /// classifier is reset.
+    pub fn claim_ready_notification(&self) -> Result<bool> {
+        if !self.is_ready()? {
+            return Ok(false);
+        }
+        let mut conn = self.redis_client.get_connection()?;
+        let claimed: bool = conn.set_nx(bayes::READY_NOTIFIED_KEY, 1)?;
+        Ok(claimed)
+    }
+
     /// Checks if a message has already been learned.
     /// 
     /// # Arguments
@@ -496,6 +530,7 @@ impl BayesManager {
         // Clear message counters
         let _: () = conn.del(bayes::BAYES_SPAM_MESSAGES_KEY)?;
         let _: () = conn.del(bayes::BAYES_HAM_MESSAGES_KEY)?;
+        let _: () = conn.del(bayes::READY_NOTIFIED_KEY)?;
         
         // Clear learning records (this will clear all keys with the learned prefix)
         // Note: This is a simplified approach. In production, you might want to
@@ -624,6 +659,23 @@ impl BayesManager {
     }
 }
 
+/// Tells `chat_id` once that the classifier reached its training thresholds,
+/// after a learn operation. Does nothing if it isn't ready or was announced.
+pub async fn announce_bayes_ready(bot: &Bot, bayes_manager: &BayesManager, chat_id: ChatId) -> Result<()> {
+    if !bayes_manager.claim_ready_notification()? {
+        return Ok(());
+    }
+    bot.send_message(
+        chat_id,
+        format!(
+            "🎉 The Bayesian classifier is ready: it has learned at least {} spam and {} ham messages.",
+            bayes::MIN_SPAM_MESSAGES, bayes::MIN_HAM_MESSAGES
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,43 @@
+//! Letter case counting used by `TG_CAPS`.
+//!
+//! Mirrors `case_counts` in `telegram_simple.lua`. Only letters that have a
+//! case count, so scripts without one (CJK, Arabic, Hebrew, ...) neither
+//! dilute nor inflate the caps ratio.
+
+/// Upper- and lowercase letters in a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CaseCounts {
+    /// Uppercase letters.
+    pub upper: usize,
+    /// Lowercase letters.
+    pub lower: usize,
+}
+
+impl CaseCounts {
+    /// Letters that have a case.
+    pub fn letters(&self) -> usize {
+        self.upper + self.lower
+    }
+
+    /// Share of cased letters that are uppercase.
+    pub fn caps_ratio(&self) -> f64 {
+        if self.letters() == 0 {
+            0.0
+        } else {
+            self.upper as f64 / self.letters() as f64
+        }
+    }
+}
+
+/// Counts the upper- and lowercase letters in `text`, in any script.
+pub fn case_counts(text: &str) -> CaseCounts {
+    let mut counts = CaseCounts::default();
+    for c in text.chars() {
+        if c.is_uppercase() {
+            counts.upper += 1;
+        } else if c.is_lowercase() {
+            counts.lower += 1;
+        }
+    }
+    counts
+}
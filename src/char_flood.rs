@@ -0,0 +1,73 @@
+//! Repeated-character run detection used by `TG_CHAR_FLOOD`.
+//!
+//! Mirrors `char_runs` in `telegram_simple.lua`.
+
+/// Runs at least this long count towards the flooded share of a message.
+pub const MIN_COUNTED_RUN: usize = 3;
+
+/// Messages shorter than this (in non-whitespace characters) skip the ratio check.
+pub const MIN_RATIO_LENGTH: usize = 20;
+
+/// Runs of one repeated character in a message; whitespace breaks runs and
+/// isn't counted, ASCII letters compare case-insensitively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CharRuns {
+    /// Length of the longest run.
+    pub longest: usize,
+    /// Characters that sit in runs of `MIN_COUNTED_RUN` or more.
+    pub flooded: usize,
+    /// Non-whitespace characters in the message.
+    pub total: usize,
+}
+
+impl CharRuns {
+    /// Share of characters that sit in long runs.
+    pub fn ratio(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.flooded as f64 / self.total as f64
+        }
+    }
+
+    /// Returns true if a run exceeds `max_run` or, for long enough messages,
+    /// the flooded share reaches `max_ratio`.
+    pub fn is_flood(&self, max_run: f64, max_ratio: f64) -> bool {
+        self.longest as f64 > max_run || (self.total >= MIN_RATIO_LENGTH && self.ratio() >= max_ratio)
+    }
+}
+
+/// Measures the repeated-character runs in `text`.
+pub fn char_runs(text: &str) -> CharRuns {
+    let mut runs = CharRuns::default();
+    let mut prev: Option<char> = None;
+    let mut run = 0;
+
+    for c in text.chars() {
+        if c.is_whitespace() {
+            close_run(&mut runs, run);
+            prev = None;
+            run = 0;
+            continue;
+        }
+        runs.total += 1;
+        let c = c.to_ascii_lowercase();
+        if prev == Some(c) {
+            run += 1;
+        } else {
+            close_run(&mut runs, run);
+            prev = Some(c);
+            run = 1;
+        }
+    }
+    close_run(&mut runs, run);
+
+    runs
+}
+
+fn close_run(runs: &mut CharRuns, run: usize) {
+    runs.longest = runs.longest.max(run);
+    if run >= MIN_COUNTED_RUN {
+        runs.flooded += run;
+    }
+}
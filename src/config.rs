@@ -16,10 +16,78 @@ pub mod key {
     pub const TG_WHITELIST_WORD_KEY: &str = "tg:whitelist:words";
     /// Key for blacklist of words
     pub const TG_BLACKLIST_WORD_KEY: &str = "tg:blacklist:words";
+    /// Set of domains whose links don't count towards `TG_LINK_SPAM` / `TG_SHORTENER`
+    pub const TG_TRUSTED_DOMAINS_KEY: &str = "tg:trusted_domains";
+    /// Set of URL shortener domains behind `TG_SHORTENER` (defaults apply while empty)
+    pub const TG_SHORTENERS_KEY: &str = "tg:shorteners";
     /// Prefix for trusted message IDs (e.g. `"tg:trusted:<message_id>"`)
     pub const TG_TRUSTED_PREFIX: &str = "tg:trusted:";
+    /// Sorted set of trusted user ids, scored by expiry timestamp (`+inf` never expires)
+    pub const TG_TRUSTED_USERS_KEY: &str = "tg:trusted_users";
     /// Prefix for reply tracking (e.g. `"tg:replies:<chat_id>:<message_id>"`)
     pub const TG_REPLIES_PREFIX: &str = "tg:replies:";
+    /// Prefix for cross-chat duplicate tracking (e.g. `"tg:crosspost:<user_id>:<hash>"`)
+    pub const TG_CROSS_POST_PREFIX: &str = "tg:crosspost:";
+    /// Hash of admin-configurable detection thresholds and reputation gates
+    pub const TG_THRESHOLDS_KEY: &str = "tg:thresholds";
+    /// Hash mapping lowercase usernames to user IDs
+    pub const TG_USERNAMES_KEY: &str = "tg:usernames";
+    /// Sorted set of `<chat_id>:<user_id>` owing the join challenge, scored by deadline
+    pub const TG_PENDING_VERIFY_KEY: &str = "tg:pending_verify";
+    /// Hash mapping `<chat_id>:<user_id>` to the id of their challenge message
+    pub const TG_VERIFY_MESSAGES_KEY: &str = "tg:verify_messages";
+    /// Flag set by the admin panel to pause all scanning and moderation
+    pub const EMERGENCY_STOP_KEY: &str = "admin:emergency_stop";
+    /// Prefix for member spam reports (e.g. `"tg:reports:<chat_id>:<message_id>"`)
+    pub const TG_REPORTS_PREFIX: &str = "tg:reports:";
+    /// Prefix for a banned user's latest appeal (e.g. `"tg:appeals:<user_id>"`)
+    pub const TG_APPEALS_PREFIX: &str = "tg:appeals:";
+    /// Prefix for Rspamd-side user reputation hashes (e.g. `"tg:reputation:user:<user_id>"`)
+    pub const TG_REPUTATION_USER_PREFIX: &str = "tg:reputation:user:";
+    /// Prefix for stored message text used by the learning commands (e.g. `"tg:message:<message_id>"`)
+    pub const TG_MESSAGE_PREFIX: &str = "tg:message:";
+    /// List of stored message ids, newest first, used to cap `tg:message:*`
+    pub const TG_MESSAGE_INDEX_KEY: &str = "tg:messages:recent";
+    /// Prefix for the chat and time of a stored message, used by `/searchmessages` (e.g. `"tg:message_info:<message_id>"`)
+    pub const TG_MESSAGE_INFO_PREFIX: &str = "tg:message_info:";
+    /// Prefix for logged spam events read by the dashboard (e.g. `"spam:<uuid>"`)
+    pub const SPAM_EVENT_PREFIX: &str = "spam:";
+    /// Last Redis schema migration applied by the bot
+    pub const SCHEMA_VERSION_KEY: &str = "tg:schema_version";
+    /// Hash of domain reputation scores behind `TG_URL_REPUTATION` (host -> score)
+    pub const TG_DOMAIN_REP_KEY: &str = "tg:domain_rep";
+    /// Prefix for messages already scanned (e.g. `"tg:scanned:<chat_id>:<message_id>"`)
+    pub const TG_SCANNED_PREFIX: &str = "tg:scanned:";
+    /// Set of file extensions behind `TG_ATTACHMENT_SPAM` (defaults apply while empty)
+    pub const TG_RISKY_EXTENSIONS_KEY: &str = "tg:risky_extensions";
+    /// Hash counting failed Rspamd scans by failure class (`connection`, `timeout`, `http_status`, `parse`, `other`)
+    pub const TG_RSPAMD_ERRORS_KEY: &str = "tg:rspamd:errors";
+    /// Prefix for posts of one media item by a member behind `TG_MEDIA_REPEAT` (e.g. `"tg:media:<chat_id>:<user_id>:<file_unique_id>"`)
+    pub const TG_MEDIA_PREFIX: &str = "tg:media:";
+
+    /// Environment variable holding an optional namespace for every key, so
+    /// several bots can share one Redis
+    pub const NAMESPACE_ENV: &str = "BOT_NAMESPACE";
+
+    /// The bot's key namespace from `BOT_NAMESPACE`, if set.
+    pub fn namespace() -> Option<String> {
+        std::env::var(NAMESPACE_ENV)
+            .ok()
+            .map(|namespace| namespace.trim().to_string())
+            .filter(|namespace| !namespace.is_empty())
+    }
+
+    /// Prefixes `key` with the bot's namespace (`"<namespace>:<key>"`); `key`
+    /// is returned as-is when `BOT_NAMESPACE` isn't set.
+    ///
+    /// Every Redis key is built through this, e.g.
+    /// `format!("{}{}", key::ns(key::TG_USERS_PREFIX), user_id)`.
+    pub fn ns(key: impl std::fmt::Display) -> String {
+        match namespace() {
+            Some(namespace) => format!("{}:{}", namespace, key),
+            None => key.to_string(),
+        }
+    }
 }
 
 /// **Redis Key Suffixes:** common endings for composite Redis keys.
@@ -34,6 +102,44 @@ pub mod suffix {
     pub const ADMINS: &str = ":admins";
     /// Suffix for trusted message metadata (e.g. `"<message_id>:metadata"`)
     pub const TRUSTED_METADATA: &str = ":metadata";
+    /// Suffix for a chat's set of allowed scripts (e.g. `"tg:chats:<id>:allowed_scripts"`)
+    pub const ALLOWED_SCRIPTS: &str = ":allowed_scripts";
+    /// Suffix for a chat's per-symbol trigger counters (e.g. `"tg:chats:<id>:symbol_counts"`)
+    pub const SYMBOL_COUNTS: &str = ":symbol_counts";
+    /// Suffix for a chat's anti-evasion threshold overrides (e.g. `"tg:chats:<id>:anti_evasion"`)
+    pub const ANTI_EVASION: &str = ":anti_evasion";
+    /// Suffix for a chat's score-to-action map (e.g. `"tg:chats:<id>:actions"`)
+    pub const ACTIONS: &str = ":actions";
+    /// Suffix for a chat's list of dry-run alerts (e.g. `"tg:chats:<id>:dry_run_log"`)
+    pub const DRY_RUN_LOG: &str = ":dry_run_log";
+    /// Suffix for a user's recent message ids in a chat (e.g. `"tg:chats:<id>:recent_messages:<user_id>"`)
+    pub const RECENT_MESSAGES: &str = ":recent_messages";
+    /// Suffix for a chat's join timing windows (e.g. `"tg:chats:<id>:join_gate"`)
+    pub const JOIN_GATE: &str = ":join_gate";
+    /// Suffix for a chat's admin display names by user id (e.g. `"tg:chats:<id>:admin_names"`)
+    pub const ADMIN_NAMES: &str = ":admin_names";
+    /// Suffix for a chat's recent automated bans, scored by time (e.g. `"tg:chats:<id>:ban_times"`)
+    pub const BAN_TIMES: &str = ":ban_times";
+    /// Suffix for the marker of a chat's lockdown alert in the current window (e.g. `"tg:chats:<id>:ban_lockdown"`)
+    pub const BAN_LOCKDOWN: &str = ":ban_lockdown";
+    /// Suffix for a chat's recent joins and first messages, scored by time (e.g. `"tg:chats:<id>:raid_events"`)
+    pub const RAID_EVENTS: &str = ":raid_events";
+    /// Suffix for the members muted by a chat's lockdown (e.g. `"tg:chats:<id>:lockdown_muted"`)
+    pub const LOCKDOWN_MUTED: &str = ":lockdown_muted";
+    /// Suffix for a chat's own whitelisted users (e.g. `"tg:chats:<id>:whitelist:users"`)
+    pub const WHITELIST_USERS: &str = ":whitelist:users";
+    /// Suffix for a chat's own whitelisted words (e.g. `"tg:chats:<id>:whitelist:words"`)
+    pub const WHITELIST_WORDS: &str = ":whitelist:words";
+    /// Suffix for a chat's own blacklisted users (e.g. `"tg:chats:<id>:blacklist:users"`)
+    pub const BLACKLIST_USERS: &str = ":blacklist:users";
+    /// Suffix for a chat's own blacklisted words (e.g. `"tg:chats:<id>:blacklist:words"`)
+    pub const BLACKLIST_WORDS: &str = ":blacklist:words";
+    /// Suffix for a user's moderator notes, oldest first (e.g. `"tg:users:<id>:notes"`)
+    pub const NOTES: &str = ":notes";
+    /// Prefix for a chat's per-day action counters (e.g. `"tg:chats:<id>:daily:2024-05-01"`)
+    pub const DAILY: &str = ":daily:";
+    /// Suffix for a chat's per-symbol counters of one day (e.g. `"tg:chats:<id>:daily_symbols:2024-05-01"`)
+    pub const DAILY_SYMBOLS: &str = ":daily_symbols:";
 }
 
 /// **Redis Hash Field Names:** keys within Redis hashes for user/chat properties.
@@ -54,6 +160,8 @@ pub mod field {
     pub const EQ_MSG_COUNT: &str = "eq_msg_count";
     /// Field indicating a user has been banned (in user hash), or count of bans (in chat hash).
     pub const BANNED: &str = "banned";
+    /// Field storing the chat a user's current ban was issued in (in user hash, expires with `banned`).
+    pub const BANNED_IN: &str = "banned_in";
     /// Field storing the last message content seen (for repeat detection logic).
     pub const LAST_MSG: &str = "last_msg";
     /// Field storing the username of the sender
@@ -62,6 +170,44 @@ pub mod field {
     pub const BANNED_Q: &str = "banned_q";
     /// Field storing the quantity of permanently banned users in the chat
     pub const PERM_BANNED: &str = "perm_banned";
+    /// Field storing how a chat enforces `TG_PERM_BAN` (in chat hash, see `perm_ban`).
+    pub const PERM_BAN_ACTION: &str = "perm_ban_action";
+    /// Field storing which forwarded messages a chat accepts (in chat hash, see `forward_policy`).
+    pub const FORWARDS: &str = "forwards";
+    /// Field storing the Unix timestamp when the user joined the chat
+    pub const JOIN_TIME: &str = "join_time";
+    /// Field storing the Unix timestamp of the user's last message
+    pub const LAST_MSG_TIME: &str = "last_msg_time";
+    /// Field counting messages the bot has seen from a user (in user hash).
+    pub const MSG_COUNT: &str = "msg_count";
+    /// Field storing the Unix timestamp a user's read-only mute ends at (in user hash).
+    pub const MUTED_UNTIL: &str = "muted_until";
+    /// Field storing the chat a user's current mute was issued in (in user hash).
+    pub const MUTED_IN: &str = "muted_in";
+    /// Field storing how many minutes `tg_mute` mutes for (in chat hash).
+    pub const MUTE_MINUTES: &str = "mute_minutes";
+    /// Field storing how many messages per flood window a user may send before
+    /// `TG_FLOOD` fires (in chat hash, falls back to `threshold::FLOOD_MAX`).
+    pub const FLOOD_MAX: &str = "flood_max";
+    /// Field storing how many automated bans per minute a chat allows before
+    /// further ones are deferred (in chat hash, falls back to `ban_rate::DEFAULT_MAX_PER_MINUTE`).
+    pub const BAN_RATE_MAX: &str = "ban_rate_max";
+    /// Field storing the Unix timestamp a chat's lockdown started at (in chat hash, unset when not locked down).
+    pub const LOCKDOWN_SINCE: &str = "lockdown_since";
+    /// Field counting a chat's messages since its last rate sample (in chat hash).
+    pub const MSG_TICK: &str = "msg_tick";
+    /// Field storing a chat's message rate in messages per minute, as an EWMA (in chat hash).
+    pub const MSG_RATE: &str = "msg_rate";
+    /// Field storing the Unix timestamp of a chat's last rate sample (in chat hash).
+    pub const MSG_RATE_AT: &str = "msg_rate_at";
+    /// Fields storing the bounds of a chat's adaptive threshold scale (in chat
+    /// hash, fall back to `adaptive::DEFAULT_MIN_SCALE` and `DEFAULT_MAX_SCALE`).
+    pub const ADAPTIVE_MIN: &str = "adaptive_min";
+    pub const ADAPTIVE_MAX: &str = "adaptive_max";
+    /// Fields storing a chat's flood and repeat limits scaled to its message
+    /// rate (in chat hash, set only while `adaptive_thresholds` is on).
+    pub const ADAPTIVE_FLOOD_MAX: &str = "adaptive_flood_max";
+    pub const ADAPTIVE_REPEAT_MAX: &str = "adaptive_repeat_max";
     /// Field storing trusted message sender ID
     pub const TRUSTED_SENDER: &str = "trusted_sender";
     /// Field storing trusted message chat ID
@@ -73,19 +219,697 @@ pub mod field {
 }
 
 
+/// **Detection Thresholds:** field names in the `tg:thresholds` hash and their defaults,
+/// covering content detection and the reputation gates.
+pub mod threshold {
+    /// Maximum number of links before `TG_LINK_SPAM` fires.
+    pub const LINK_SPAM_MAX: &str = "link_spam_max";
+    /// Maximum number of mentions before `TG_MENTIONS` fires.
+    pub const MENTIONS_MAX: &str = "mentions_max";
+    /// Maximum number of emoji before `TG_EMOJI_SPAM` fires.
+    pub const EMOJI_MAX: &str = "emoji_max";
+    /// Ratio of capital letters at which `TG_CAPS` fires.
+    pub const CAPS_RATIO: &str = "caps_ratio";
+    /// Longest allowed run of one repeated character before `TG_CHAR_FLOOD` fires.
+    pub const CHAR_RUN_MAX: &str = "char_run_max";
+    /// Share of characters in repeated runs at which `TG_CHAR_FLOOD` fires.
+    pub const CHAR_FLOOD_RATIO: &str = "char_flood_ratio";
+    /// Share of consonants among Latin letters above which `TG_GIBBERISH` fires.
+    pub const GIBBERISH_RATIO: &str = "gibberish_ratio";
+    /// Fewest Latin letters a message needs before `TG_GIBBERISH` looks at it.
+    pub const GIBBERISH_MIN_LENGTH: &str = "gibberish_min_length";
+    /// Seconds between identical messages after which the `TG_REPEAT` count starts over.
+    /// Messages a user may send per flood window before `TG_FLOOD` fires,
+    /// unless the chat sets its own `flood_max`.
+    pub const FLOOD_MAX: &str = "flood_max";
+    pub const REPEAT_WINDOW: &str = "repeat_window";
+    /// Similarity (0-1) at which a normalized message repeats the previous one
+    /// for `TG_REPEAT`; 1 requires the normalized texts to be equal.
+    pub const REPEAT_SIMILARITY: &str = "repeat_similarity";
+    /// Shortest message (in characters, ignoring surrounding whitespace) that caps,
+    /// emoji and gibberish detection look at.
+    pub const MIN_CONTENT_LENGTH: &str = "min_content_length";
+    /// Reputation above which `TG_SUSPICIOUS` fires.
+    pub const SUSPICIOUS_REP: &str = "suspicious_rep";
+    /// Reputation above which `TG_BAN` bans the user.
+    pub const BAN_REP: &str = "ban_rep";
+    /// Reputation `TG_BAN` takes off the user it bans.
+    pub const BAN_REP_PENALTY: &str = "ban_rep_penalty";
+    /// Ban count (`banned_q`) at which `TG_PERM_BAN` fires.
+    pub const PERM_BAN_BANS: &str = "perm_ban_bans";
+
+    /// Default link limit.
+    pub const DEFAULT_LINK_SPAM_MAX: f64 = 3.0;
+    /// Default mention limit.
+    pub const DEFAULT_MENTIONS_MAX: f64 = 5.0;
+    /// Default emoji limit.
+    pub const DEFAULT_EMOJI_MAX: f64 = 10.0;
+    /// Default caps ratio.
+    pub const DEFAULT_CAPS_RATIO: f64 = 0.7;
+    /// Default repeated-character run limit.
+    pub const DEFAULT_CHAR_RUN_MAX: f64 = 4.0;
+    /// Default repeated-character ratio.
+    pub const DEFAULT_CHAR_FLOOD_RATIO: f64 = 0.3;
+    /// Default gibberish consonant ratio.
+    pub const DEFAULT_GIBBERISH_RATIO: f64 = 0.8;
+    /// Default gibberish letter count.
+    pub const DEFAULT_GIBBERISH_MIN_LENGTH: f64 = 50.0;
+    /// Default repeat window (1 hour).
+    /// Default flood limit.
+    pub const DEFAULT_FLOOD_MAX: f64 = 30.0;
+    pub const DEFAULT_REPEAT_WINDOW: f64 = 3600.0;
+    /// Default repeat similarity (normalized texts must be equal).
+    pub const DEFAULT_REPEAT_SIMILARITY: f64 = 1.0;
+    /// Default content length floor.
+    pub const DEFAULT_MIN_CONTENT_LENGTH: f64 = 5.0;
+    /// Default suspicious reputation gate.
+    pub const DEFAULT_SUSPICIOUS_REP: f64 = 10.0;
+    /// Default ban reputation gate.
+    pub const DEFAULT_BAN_REP: f64 = 20.0;
+    /// Default reputation taken off on a ban.
+    pub const DEFAULT_BAN_REP_PENALTY: f64 = 4.0;
+    /// Default permanent ban count.
+    pub const DEFAULT_PERM_BAN_BANS: f64 = 3.0;
+
+    /// All configurable thresholds paired with their default values.
+    pub const ALL: &[(&str, f64)] = &[
+        (LINK_SPAM_MAX, DEFAULT_LINK_SPAM_MAX),
+        (MENTIONS_MAX, DEFAULT_MENTIONS_MAX),
+        (EMOJI_MAX, DEFAULT_EMOJI_MAX),
+        (CAPS_RATIO, DEFAULT_CAPS_RATIO),
+        (CHAR_RUN_MAX, DEFAULT_CHAR_RUN_MAX),
+        (CHAR_FLOOD_RATIO, DEFAULT_CHAR_FLOOD_RATIO),
+        (GIBBERISH_RATIO, DEFAULT_GIBBERISH_RATIO),
+        (GIBBERISH_MIN_LENGTH, DEFAULT_GIBBERISH_MIN_LENGTH),
+        (FLOOD_MAX, DEFAULT_FLOOD_MAX),
+        (REPEAT_WINDOW, DEFAULT_REPEAT_WINDOW),
+        (REPEAT_SIMILARITY, DEFAULT_REPEAT_SIMILARITY),
+        (MIN_CONTENT_LENGTH, DEFAULT_MIN_CONTENT_LENGTH),
+        (SUSPICIOUS_REP, DEFAULT_SUSPICIOUS_REP),
+        (BAN_REP, DEFAULT_BAN_REP),
+        (BAN_REP_PENALTY, DEFAULT_BAN_REP_PENALTY),
+        (PERM_BAN_BANS, DEFAULT_PERM_BAN_BANS),
+    ];
+
+    /// Thresholds expressed as a ratio between 0 and 1.
+    pub const RATIOS: &[&str] = &[CAPS_RATIO, CHAR_FLOOD_RATIO, GIBBERISH_RATIO, REPEAT_SIMILARITY];
+}
+
+/// **Moderation Actions:** per-chat score-to-action map in `tg:chats:<id>:actions`.
+pub mod action {
+    /// Warn about the message.
+    pub const WARN: &str = "tg_warn";
+    /// Delete the message.
+    pub const DELETE: &str = "tg_delete";
+    /// Delete the message and ban the sender.
+    pub const BAN: &str = "tg_ban";
+    /// Delete the message and mute the sender (read-only) for a while.
+    pub const MUTE: &str = "tg_mute";
+    /// No action.
+    pub const NONE: &str = "none";
+
+    /// All actions paired with their default score thresholds, most severe first.
+    /// Mute is off until a chat sets a threshold for it.
+    pub const ALL: &[(&str, f64)] = &[
+        (BAN, 15.0),
+        (MUTE, f64::INFINITY),
+        (DELETE, 10.0),
+        (WARN, 5.0),
+    ];
+}
+
+/// **Permanent Bans:** how a chat enforces `TG_PERM_BAN`, see `perm_ban`.
+pub mod perm_ban {
+    /// Ban the user from the chat for good (the default).
+    pub const BAN: &str = "ban";
+    /// Remove the user from the chat but let them join again.
+    pub const KICK: &str = "kick";
+    /// Only tell the admins.
+    pub const REPORT: &str = "report";
+}
+
+/// **Spam Events:** ban/suspicious detections logged for the dashboard.
+pub mod spam_event {
+    /// Symbols whose presence in a scan is logged as a spam event.
+    pub const SYMBOLS: &[&str] = &[super::symbol::TG_BAN, super::symbol::TG_PERM_BAN, super::symbol::TG_SUSPICIOUS];
+    /// Seconds an event is kept; the dashboard counts the last 24 hours.
+    pub const TTL: i64 = 24 * 60 * 60;
+}
+
+/// **Spam Webhook:** optional JSON push of spam events, see `spam_webhook`.
+pub mod webhook {
+    /// Environment variable holding the URL events are POSTed to; unset disables the webhook.
+    pub const URL_ENV: &str = "SPAM_WEBHOOK_URL";
+    /// Seconds a single delivery attempt may take.
+    pub const TIMEOUT_SECS: u64 = 5;
+    /// Delivery attempts per event before it is dropped.
+    pub const ATTEMPTS: u32 = 3;
+    /// Delay before the first retry in milliseconds; doubles on every further retry.
+    pub const BACKOFF_MS: u64 = 500;
+}
+
+/// **API:** the JSON API served next to the health check, see `api`.
+pub mod api {
+    /// Environment variable holding the bearer token API requests must carry;
+    /// unset disables the API.
+    pub const TOKEN_ENV: &str = "API_TOKEN";
+}
+
+/// **Outgoing:** rate limits for the bot's own Telegram requests, see `outgoing`.
+pub mod outgoing {
+    /// Environment variable overriding `DEFAULT_GLOBAL_PER_SECOND`.
+    pub const GLOBAL_RATE_ENV: &str = "TELEGRAM_GLOBAL_RATE";
+    /// Environment variable overriding `DEFAULT_CHAT_PER_MINUTE`.
+    pub const CHAT_RATE_ENV: &str = "TELEGRAM_CHAT_RATE";
+    /// Requests per second across all chats; Telegram allows about 30.
+    pub const DEFAULT_GLOBAL_PER_SECOND: f64 = 30.0;
+    /// Requests per minute to a single chat; Telegram allows 20 in groups.
+    pub const DEFAULT_CHAT_PER_MINUTE: f64 = 20.0;
+}
+
+/// **Command menu:** the commands Telegram suggests when typing `/`, see `admin_handlers::commands`.
+pub mod command_menu {
+    /// Most commands Telegram accepts in one menu.
+    pub const MAX_COMMANDS: usize = 100;
+    /// Commands open to every member, the only ones in the menu of non-admins.
+    pub const MEMBER_COMMANDS: &[&str] = &["reportspam", "appeal"];
+}
+
+/// **Notifications:** filtering of proactive admin alerts by severity.
+pub mod notification {
+    /// Admin panel setting holding the configured level (`/configure notification_level`).
+    pub const LEVEL: &str = "notification_level";
+    /// Notify about everything.
+    pub const ALL: &str = "all";
+    /// Notify about low severity alerts and above (same as `all`).
+    pub const LOW: &str = "low";
+    /// Notify about medium and high severity alerts.
+    pub const MEDIUM: &str = "medium";
+    /// Notify about high severity alerts only.
+    pub const HIGH: &str = "high";
+    /// Suppress all proactive alerts.
+    pub const NONE: &str = "none";
+    /// Level used when none is configured.
+    pub const DEFAULT_LEVEL: &str = ALL;
+}
+
+/// **Join Gating:** per-chat windows (in seconds since joining) for first messages.
+pub mod join_gate {
+    /// A first message sooner than this after joining fires `TG_FIRST_FAST`.
+    pub const FIRST_FAST: &str = "first_fast";
+    /// A first message later than this after joining fires `TG_FIRST_SLOW`.
+    pub const FIRST_SLOW: &str = "first_slow";
+    /// Messages sooner than this after joining are deleted outright (0 disables probation).
+    pub const PROBATION: &str = "probation";
+    /// Links posted sooner than this after joining fire `TG_NEW_USER_LINK`, and contact cards score `contact_card::NEW_USER_SCORE`.
+    pub const NEW_USER_LINK: &str = "new_user_link";
+    /// Forwards sooner than this after joining are deleted in chats that restrict them to established members.
+    pub const NEW_USER_FORWARD: &str = "new_user_forward";
+
+    /// All windows paired with their defaults.
+    pub const ALL: &[(&str, i64)] = &[
+        (FIRST_FAST, 10),
+        (FIRST_SLOW, 86_400),
+        (PROBATION, 0),
+        (NEW_USER_LINK, 3600),
+        (NEW_USER_FORWARD, 86_400),
+    ];
+}
+
+/// **Forwarded Messages:** scoring of forwards from brand-new users and the per-chat forward policy.
+pub mod forward {
+    /// Feature toggling forward detection (`TG_FORWARDED` and the channel-forward penalty).
+    pub const FEATURE: &str = "forwarded";
+    /// Users with at most this many messages seen are treated as new.
+    pub const NEW_USER_MAX_MESSAGES: i64 = 3;
+    /// Score added when a new user without good reputation forwards from a channel.
+    pub const CHANNEL_FORWARD_PENALTY: f64 = 3.0;
+    /// Origin reported in `X-Telegram-Forward` for channel posts.
+    pub const ORIGIN_CHANNEL: &str = "channel";
+    /// Origin reported in `X-Telegram-Forward` for messages from a visible user.
+    pub const ORIGIN_USER: &str = "user";
+    /// Origin reported in `X-Telegram-Forward` for users who hide their account.
+    pub const ORIGIN_HIDDEN_USER: &str = "hidden_user";
+    /// Origin reported in `X-Telegram-Forward` for anonymous group admins.
+    pub const ORIGIN_CHAT: &str = "chat";
+    /// Policy accepting every forward (the default).
+    pub const ALLOW: &str = "allow";
+    /// Policy deleting forwards from members within their `new_user_forward` join window.
+    pub const RESTRICT_NEW: &str = "restrict_new";
+    /// Policy deleting every forward.
+    pub const DENY: &str = "deny";
+}
+
+/// **Symbol Weights:** how much each content symbol adds to a user's bad reputation.
+pub mod symbol_weight {
+    use super::symbol;
+
+    /// Hash of admin overrides (symbol name -> weight).
+    pub const WEIGHTS_KEY: &str = "tg:symbol_weights";
+    /// How long `tg:reputation:user:<id>` hashes live (7 days), matching `telegram_simple.lua`.
+    pub const REPUTATION_TTL: i64 = 604800;
+
+    /// Weighted symbols and their default weights; all 1 so a message counts
+    /// once per symbol, like the flat per-rule bump it replaces.
+    pub const ALL: &[(&str, i64)] = &[
+        (symbol::TG_LINK_SPAM, 1),
+        (symbol::TG_MENTIONS, 1),
+        (symbol::TG_CAPS, 1),
+        (symbol::TG_EMOJI_SPAM, 1),
+        (symbol::TG_CHAR_FLOOD, 1),
+        (symbol::TG_INVITE_LINK, 1),
+        (symbol::TG_PHONE_SPAM, 1),
+        (symbol::TG_SHORTENER, 1),
+        (symbol::TG_GIBBERISH, 1),
+        (symbol::TG_FOREIGN_SCRIPT, 1),
+    ];
+}
+
+/// **Reputation Decay:** settings controlling the periodic `rep` decay.
+pub mod reputation {
+    /// Hash holding admin panel settings (shared with the admin panel).
+    pub const SETTINGS_KEY: &str = "admin:panel:settings";
+    /// Setting for how many points `rep` decays per cycle.
+    pub const DECAY_RATE: &str = "reputation_decay_rate";
+    /// Setting for the lowest value decay will bring `rep` down to.
+    pub const DECAY_FLOOR: &str = "reputation_decay_floor";
+    /// Default decay per cycle.
+    pub const DEFAULT_DECAY_RATE: i64 = 1;
+    /// Default decay floor.
+    pub const DEFAULT_DECAY_FLOOR: i64 = 0;
+    /// Largest decay rate `/setthreshold` accepts.
+    pub const MAX_DECAY_RATE: i64 = 100;
+    /// Largest decay floor `/setthreshold` accepts.
+    pub const MAX_DECAY_FLOOR: i64 = 100;
+    /// Number of keys requested per `SCAN` call during decay.
+    pub const SCAN_BATCH_SIZE: usize = 500;
+    /// Setting for how many points a benign message takes off a `TG_SUSPICIOUS` user's `rep`.
+    pub const SUSPICIOUS_DECAY_STEP: &str = "suspicious_decay_step";
+    /// Default step; `TG_SUSPICIOUS` adds 1 per message, so each benign message nets -2.
+    pub const DEFAULT_SUSPICIOUS_DECAY_STEP: i64 = 3;
+    /// Largest step `/setthreshold` accepts.
+    pub const MAX_SUSPICIOUS_DECAY_STEP: i64 = 10;
+    /// Settings `/setthreshold` accepts, with their default and largest value; none goes below 0.
+    pub const ALL: &[(&str, i64, i64)] = &[
+        (DECAY_RATE, DEFAULT_DECAY_RATE, MAX_DECAY_RATE),
+        (DECAY_FLOOR, DEFAULT_DECAY_FLOOR, MAX_DECAY_FLOOR),
+        (SUSPICIOUS_DECAY_STEP, DEFAULT_SUSPICIOUS_DECAY_STEP, MAX_SUSPICIOUS_DECAY_STEP),
+    ];
+    /// Symbols besides the weighted ones in `symbol_weight::ALL` that make a message an offence.
+    pub const OFFENCE_SYMBOLS: &[&str] = &[
+        super::symbol::TG_FLOOD,
+        super::symbol::TG_REPEAT,
+        super::symbol::TG_CROSS_POST,
+        super::symbol::TG_BAN,
+        super::symbol::TG_PERM_BAN,
+        super::symbol::BLACKLIST_USER,
+        super::symbol::BLACKLIST_WORD,
+    ];
+}
+
+/// **Member Reports:** settings for the `/reportspam` command.
+pub mod report {
+    /// Number of distinct reporters that must be exceeded before escalation.
+    pub const ESCALATION_THRESHOLD: u32 = 2;
+    /// Reputation penalty applied to the sender on escalation.
+    pub const REP_PENALTY: i64 = 2;
+    /// How long reports for a message are kept (24 hours in seconds).
+    pub const REPORT_TTL: i64 = 86400;
+}
+
+/// **Appeals:** `/appeal` requests of banned users, see `admin_handlers::appeal_commands`.
+pub mod appeal {
+    /// Field of the appeal hash holding the appealing user's id.
+    pub const USER_ID: &str = "user_id";
+    /// Field of the appeal hash holding the chat the user was banned in.
+    pub const CHAT_ID: &str = "chat_id";
+    /// Field of the appeal hash holding the user's reason.
+    pub const REASON: &str = "reason";
+    /// Field of the appeal hash holding when the appeal was made (Unix timestamp).
+    pub const TIMESTAMP: &str = "timestamp";
+    /// Field of the appeal hash holding `pending`, `approved` or `rejected`.
+    pub const STATUS: &str = "status";
+    /// Field of the appeal hash holding the admin who decided it.
+    pub const DECIDED_BY: &str = "decided_by";
+    /// Seconds a user has to wait between appeals (24 hours).
+    pub const COOLDOWN_SECS: i64 = 86400;
+    /// How long an appeal is kept (30 days in seconds).
+    pub const TTL: i64 = 30 * 86400;
+    /// Longest reason kept, in characters.
+    pub const MAX_REASON_CHARS: usize = 500;
+}
+
+/// **Message Store:** limits for message text kept for `/learnspam` and `/learnham`.
+pub mod message_store {
+    /// Maximum number of messages kept; the oldest are evicted first.
+    pub const MAX_MESSAGES: isize = 10_000;
+    /// Field of `tg:message_info:<id>` holding the chat the message was posted in.
+    pub const CHAT_ID: &str = "chat_id";
+    /// Field of `tg:message_info:<id>` holding when the message was stored (unix seconds).
+    pub const TIMESTAMP: &str = "timestamp";
+    /// Matches `/searchmessages` lists at most.
+    pub const SEARCH_LIMIT: usize = 20;
+    /// Hours `/searchmessages` looks back when none are given.
+    pub const SEARCH_HOURS: i64 = 24;
+    /// Characters of context shown around a match.
+    pub const SNIPPET_CHARS: usize = 60;
+}
+
+/// **Ban List:** settings for the `/banlist` command.
+pub mod ban_list {
+    /// Banned users shown per page.
+    pub const PAGE_SIZE: usize = 20;
+}
+
+/// **Mutes:** read-only restrictions issued by `tg_mute` and `/mute`, see `mutes`.
+pub mod mute {
+    /// Minutes `tg_mute` mutes for when the chat hasn't set `mute_minutes`.
+    pub const DEFAULT_MINUTES: i64 = 60;
+    /// Longest mute in minutes; Telegram treats restrictions over 366 days as permanent.
+    pub const MAX_MINUTES: i64 = 366 * 24 * 60;
+    /// Seconds between checks for expired mutes.
+    pub const CHECK_INTERVAL_SECS: u64 = 60;
+}
+
+/// **Ban rate:** cap on automated bans per chat during a raid, see `ban_rate`.
+pub mod ban_rate {
+    /// Automated bans per window a chat allows when it hasn't set `ban_rate_max`.
+    pub const DEFAULT_MAX_PER_MINUTE: i64 = 10;
+    /// Length of the sliding window in seconds.
+    pub const WINDOW_SECS: i64 = 60;
+}
+
+/// **Lockdown:** raid detection and the lockdown it triggers, see `lockdown`.
+pub mod lockdown {
+    /// Joins plus first messages within `WINDOW_SECS` that count as a raid.
+    pub const RAID_EVENTS: i64 = 20;
+    /// Length of the sliding raid window in seconds.
+    pub const WINDOW_SECS: i64 = 60;
+}
+
+/// **Raid simulation:** `/simulateraid`, rehearsing a raid in staging chats, see `raid_commands`.
+pub mod raid_simulation {
+    /// Environment variable listing the comma-separated chat ids `/simulateraid`
+    /// may run in; unset disables the command everywhere.
+    pub const CHATS_ENV: &str = "SIMULATE_RAID_CHATS";
+    /// Id of the first synthetic member, far above real Telegram user ids.
+    pub const FIRST_USER_ID: u64 = 9_000_000_000_000;
+    /// Members simulated when no count is given, enough to trip `lockdown::RAID_EVENTS`.
+    pub const DEFAULT_MEMBERS: usize = 15;
+    /// Upper bound on the members of a single run.
+    pub const MAX_MEMBERS: usize = 100;
+    /// Text every synthetic member posts.
+    pub const MESSAGE_TEXT: &str = "hello everyone";
+}
+
+/// **Adaptive thresholds:** flood and repeat limits scaled to a chat's message rate, see `adaptive`.
+pub mod adaptive {
+    /// Message rate (per minute) at which the limits are used unscaled.
+    pub const REFERENCE_RATE: f64 = 10.0;
+    /// Weight of the newest sample in the rate's moving average.
+    pub const EWMA_ALPHA: f64 = 0.3;
+    /// Default bounds of the scale applied to the limits.
+    pub const DEFAULT_MIN_SCALE: f64 = 0.5;
+    pub const DEFAULT_MAX_SCALE: f64 = 3.0;
+    /// Identical messages in a row before `TG_REPEAT` fires, mirroring
+    /// `settings.repeated` in `telegram_simple.lua`.
+    pub const BASE_REPEAT_MAX: i64 = 6;
+    /// Seconds between rate samples.
+    pub const SAMPLE_SECS: u64 = 60;
+}
+
+/// **Join verification:** the challenge new members pass before posting, see `join_verify`.
+pub mod join_verify {
+    /// Seconds a new member has to pass the challenge before being kicked.
+    pub const TIMEOUT_SECS: i64 = 5 * 60;
+    /// Minutes a new member stays muted should the kick fail.
+    pub const MUTE_MINUTES: i64 = 24 * 60;
+    /// Seconds between checks for challenges past their deadline.
+    pub const CHECK_INTERVAL_SECS: u64 = 30;
+}
+
+/// **Domain reputation:** scoring of `TG_URL_REPUTATION`, see `domain_rep`.
+pub mod domain_rep {
+    /// Reputation a domain gains each time it is linked from a banned message.
+    pub const BAN_INCREMENT: f64 = 1.0;
+    /// Symbol score per point of the worst linked domain's reputation.
+    pub const WEIGHT: f64 = 1.0;
+    /// Highest score `TG_URL_REPUTATION` adds.
+    pub const MAX_SCORE: f64 = 10.0;
+}
+
+/// **Emoji:** codepoints counted as emoji by `handlers::count_emoji`.
+pub mod emoji {
+    /// Inclusive codepoint ranges of emoji.
+    pub const RANGES: &[(u32, u32)] = &[
+        (0x2600, 0x26FF),   // Miscellaneous Symbols
+        (0x2700, 0x27BF),   // Dingbats
+        (0x2B50, 0x2B55),   // Stars and circles
+        (0x1F004, 0x1F004), // Mahjong tile
+        (0x1F0CF, 0x1F0CF), // Joker
+        (0x1F170, 0x1F251), // Enclosed Alphanumeric and Ideographic Supplements
+        (0x1F1E6, 0x1F1FF), // Regional Indicators (flags)
+        (0x1F300, 0x1F5FF), // Miscellaneous Symbols and Pictographs
+        (0x1F600, 0x1F64F), // Emoticons
+        (0x1F680, 0x1F6FF), // Transport and Map Symbols
+        (0x1F7E0, 0x1F7FF), // Geometric Shapes Extended
+        (0x1F900, 0x1F9FF), // Supplemental Symbols and Pictographs
+        (0x1FA00, 0x1FAFF), // Chess Symbols, Symbols and Pictographs Extended-A
+    ];
+    /// Codepoints that modify the preceding emoji rather than being one:
+    /// variation selector 16, keycap, skin tones and tag characters.
+    pub const MODIFIERS: &[(u32, u32)] = &[(0xFE0F, 0xFE0F), (0x20E3, 0x20E3), (0x1F3FB, 0x1F3FF), (0xE0020, 0xE007F)];
+    /// Zero-width joiner gluing emoji into a single one (e.g. a family).
+    pub const ZWJ: char = '\u{200D}';
+    /// First and last regional indicator; two of them make a flag.
+    pub const REGIONAL_INDICATORS: (u32, u32) = (0x1F1E6, 0x1F1FF);
+}
+
+/// **Shorteners:** URL shortener domains behind `TG_SHORTENER`, see `shortener`.
+pub mod shortener {
+    /// Shorteners flagged while `tg:shorteners` is empty, mirroring
+    /// `settings.shorteners` in `telegram_simple.lua`.
+    pub const DEFAULT_DOMAINS: &[&str] = &[
+        "bit.ly", "t.co", "goo.gl", "tinyurl.com", "is.gd", "ow.ly", "cutt.ly", "rebrand.ly", "shorturl.at", "rb.gy",
+        "tiny.cc", "buff.ly",
+    ];
+}
+
+/// **Attachments:** risky document types behind `TG_ATTACHMENT_SPAM`, see `attachment_spam`.
+pub mod attachment {
+    /// File extensions flagged while `tg:risky_extensions` is empty.
+    pub const DEFAULT_EXTENSIONS: &[&str] = &[
+        "apk", "exe", "scr", "bat", "cmd", "com", "pif", "msi", "jar", "js", "vbs", "ps1", "hta", "lnk",
+    ];
+    /// MIME types flagged whatever the file is called.
+    pub const RISKY_MIME_TYPES: &[&str] = &[
+        "application/vnd.android.package-archive",
+        "application/x-msdownload",
+        "application/x-msdos-program",
+        "application/x-ms-installer",
+        "application/java-archive",
+    ];
+    /// Score `TG_ATTACHMENT_SPAM` adds.
+    pub const SCORE: f64 = 6.0;
+}
+
+/// **Impersonation:** display names mimicking a chat admin, see `impersonation`.
+pub mod impersonation {
+    /// Score `TG_IMPERSONATION` adds.
+    pub const SCORE: f64 = 8.0;
+}
+
+/// **New user links:** links from members who just joined, see `new_user_link`.
+pub mod new_user_link {
+    /// Score `TG_NEW_USER_LINK` adds, above `TG_LINK_SPAM`'s 3.
+    pub const SCORE: f64 = 5.0;
+}
+
+/// **Contact cards:** shared contacts, see `contact_card`.
+pub mod contact_card {
+    /// Score `TG_CONTACT_CARD` adds.
+    pub const SCORE: f64 = 3.0;
+    /// Score `TG_CONTACT_CARD` adds when the sender just joined.
+    pub const NEW_USER_SCORE: f64 = 6.0;
+}
+
+/// **Media repeats:** the same photo, sticker or file posted again, see `media_repeat`.
+pub mod media_repeat {
+    /// Seconds from a member's first post of a media item during which
+    /// reposts of it count.
+    pub const WINDOW_SECS: i64 = 3600;
+    /// Posts of the same media item within `WINDOW_SECS` from which
+    /// `TG_MEDIA_REPEAT` fires.
+    pub const MIN_POSTS: i64 = 3;
+    /// Score `TG_MEDIA_REPEAT` adds.
+    pub const SCORE: f64 = 4.0;
+}
+
+/// **Mixed languages:** whole passages in unrelated scripts, see `mixed_language`.
+pub mod mixed_language {
+    /// Letters a script's longest run must have to count; punctuation, digits,
+    /// emoji and whitespace don't end a run, letters of another script do.
+    pub const MIN_RUN_LETTERS: usize = 15;
+    /// Share of the message's letters each counted script must have.
+    pub const MIN_SHARE: f64 = 0.2;
+    /// Score `TG_MIXED_LANGUAGE_SPAM` adds.
+    pub const SCORE: f64 = 3.0;
+}
+
+/// **Import:** files bulk-loaded into lists by `/importwhitelist`.
+pub mod import {
+    /// Largest file accepted, in bytes.
+    pub const MAX_FILE_BYTES: u32 = 1024 * 1024;
+}
+
+/// **Trend:** daily action buckets behind the `/trend` command.
+pub mod trend {
+    /// Days a daily action bucket is kept.
+    pub const RETENTION_DAYS: i64 = 90;
+    /// Days shown by `/trend` when none are given.
+    pub const DEFAULT_DAYS: u64 = 14;
+}
+
+/// **Retention:** how long stored message text, neural features and
+/// learned-message records are kept, in seconds. Each window can be
+/// overridden with its environment variable to tune the storage footprint.
+pub mod retention {
+    /// How long message text is kept (24 hours), unless `MESSAGE_CONTENT_ENV` is set.
+    pub const MESSAGE_CONTENT: i64 = 24 * 60 * 60;
+    /// How long the features of a learned message are kept (7 days), unless `NEURAL_FEATURES_ENV` is set.
+    pub const NEURAL_FEATURES: i64 = 7 * 24 * 60 * 60;
+    /// How long a message is remembered as learned (24 hours), unless `BAYES_LEARNED_ENV` is set.
+    pub const BAYES_LEARNED: i64 = 24 * 60 * 60;
+    /// Environment variable overriding `MESSAGE_CONTENT`.
+    pub const MESSAGE_CONTENT_ENV: &str = "MESSAGE_RETENTION_SECS";
+    /// Environment variable overriding `NEURAL_FEATURES`.
+    pub const NEURAL_FEATURES_ENV: &str = "NEURAL_FEATURES_RETENTION_SECS";
+    /// Environment variable overriding `BAYES_LEARNED`.
+    pub const BAYES_LEARNED_ENV: &str = "BAYES_LEARNED_RETENTION_SECS";
+
+    fn from_env(name: &str, default: i64) -> i64 {
+        std::env::var(name)
+            .ok()
+            .and_then(|value| value.trim().parse::<i64>().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(default)
+    }
+
+    /// Seconds message text is kept, from `MESSAGE_RETENTION_SECS` or `MESSAGE_CONTENT`.
+    pub fn message_content() -> i64 {
+        from_env(MESSAGE_CONTENT_ENV, MESSAGE_CONTENT)
+    }
+
+    /// Seconds neural features are kept, from `NEURAL_FEATURES_RETENTION_SECS` or `NEURAL_FEATURES`.
+    pub fn neural_features() -> i64 {
+        from_env(NEURAL_FEATURES_ENV, NEURAL_FEATURES)
+    }
+
+    /// Seconds learned-message records are kept, from `BAYES_LEARNED_RETENTION_SECS` or `BAYES_LEARNED`.
+    pub fn bayes_learned() -> i64 {
+        from_env(BAYES_LEARNED_ENV, BAYES_LEARNED)
+    }
+}
+
+/// **Stats:** the `/stats` reports.
+pub mod stats {
+    /// Argument of `/stats` summing every moderated chat.
+    pub const GLOBAL_FLAG: &str = "--global";
+    /// Chats listed as the busiest by `/stats --global`.
+    pub const BUSIEST_CHATS: usize = 5;
+    /// Users `/worstusers` lists when no count is given.
+    pub const DEFAULT_WORST_USERS: usize = 10;
+    /// Upper bound on the users `/worstusers` lists.
+    pub const MAX_WORST_USERS: usize = 50;
+}
+
+/// **Daily Summary:** the digest sent to admin chats once a day, see `daily_summary`.
+pub mod summary {
+    /// Environment variable holding the UTC time (`HH:MM`) the digest is sent at.
+    pub const TIME_ENV: &str = "DAILY_SUMMARY_TIME";
+    /// Time the digest is sent at when `DAILY_SUMMARY_TIME` isn't set.
+    pub const DEFAULT_TIME: &str = "08:00";
+    /// Symbols listed per chat.
+    pub const TOP_SYMBOLS: usize = 5;
+}
+
+/// **Purge:** settings for the `/purge` command.
+pub mod purge {
+    /// Number of recent message ids kept per user and chat.
+    pub const RECENT_MESSAGES_LIMIT: isize = 50;
+    /// How long recent message ids are kept; bots can't delete older messages (48 hours in seconds).
+    pub const RECENT_MESSAGES_TTL: i64 = 48 * 60 * 60;
+}
+
+/// **User Notes:** moderator notes on users, see `user_notes`.
+pub mod notes {
+    /// Notes kept per user; adding one more drops the oldest.
+    pub const MAX_NOTES: isize = 20;
+    /// Longest note text accepted, in characters.
+    pub const MAX_LEN: usize = 500;
+}
 
 /// **Rspamd Configuration:** settings for Rspamd fuzzy storage integration.
 pub mod rspamd {
-    /// URL for the Rspamd controller API.
+    /// URL for the Rspamd controller API, unless `RSPAMD_CONTROLLER_URL` is set.
     pub const CONTROLLER_URL: &str = "http://127.0.0.1:11334";
-    /// Password for authenticating with the Rspamd controller.
+    /// Password for authenticating with the Rspamd controller, unless `RSPAMD_PASSWORD` is set.
     pub const PASSWORD: &str = "superSecret";
+    /// Environment variable overriding `CONTROLLER_URL`.
+    pub const CONTROLLER_URL_ENV: &str = "RSPAMD_CONTROLLER_URL";
+    /// Environment variable overriding `PASSWORD`.
+    pub const PASSWORD_ENV: &str = "RSPAMD_PASSWORD";
+
+    fn from_env(name: &str, default: &str) -> String {
+        std::env::var(name)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    /// The controller URL from `RSPAMD_CONTROLLER_URL`, or `CONTROLLER_URL`.
+    pub fn controller_url() -> String {
+        from_env(CONTROLLER_URL_ENV, CONTROLLER_URL)
+    }
+
+    /// The controller password from `RSPAMD_PASSWORD`, or `PASSWORD`.
+    pub fn password() -> String {
+        from_env(PASSWORD_ENV, PASSWORD)
+    }
     /// Flag value for fuzzy storage entries.
     pub const FUZZY_FLAG: u8 = 1;
     /// Weight value for fuzzy storage entries.
     pub const FUZZY_WEIGHT: i32 = 10;
     /// Minimum text length required for fuzzy training.
     pub const MIN_TEXT_LENGTH: usize = 8;
+    /// Seconds a single scan request may take (overridden by `RSPAMD_TIMEOUT`).
+    pub const SCAN_TIMEOUT_SECS: f64 = 5.0;
+    /// Scan retries after the first failed attempt (overridden by `RSPAMD_RETRIES`).
+    pub const SCAN_RETRIES: u32 = 2;
+    /// Delay before the first retry; doubled for every further retry.
+    pub const SCAN_BACKOFF_MS: u64 = 200;
+    /// Seconds the client's own timeout trails the bot's, so a slow Rspamd is
+    /// reported as a timeout rather than a request error.
+    pub const CLIENT_TIMEOUT_SLACK_SECS: f64 = 1.0;
+    /// Seconds a scanned message is remembered, so scanning it again doesn't update any state.
+    pub const SCANNED_TTL_SECS: u64 = 600;
+    /// Seconds `/health` waits for Rspamd to answer its ping.
+    pub const HEALTH_TIMEOUT_SECS: u64 = 3;
+}
+
+/// Local-only scanning used when Rspamd is unavailable.
+pub mod local_scan {
+    use super::symbol;
+
+    /// Scores of the locally checked symbols, matching `rspamd-config/scores.d`.
+    pub const SCORES: &[(&str, f64)] = &[
+        (symbol::TG_LINK_SPAM, 3.0),
+        (symbol::TG_LOOKALIKE_DOMAIN, 4.0),
+        (symbol::TG_MENTIONS, 2.0),
+        (symbol::TG_CAPS, 2.0),
+        (symbol::TG_CHAR_FLOOD, 1.5),
+    ];
 }
 
 /// **Bayes Configuration:** settings for Bayesian classifier integration.
@@ -100,12 +924,12 @@ pub mod bayes {
     pub const BAYES_HAM_MESSAGES_KEY: &str = "bayes_ham_messages";
     /// Prefix for learned message tracking in Redis.
     pub const BAYES_LEARNED_PREFIX: &str = "bayes:learned:";
+    /// Flag set once admins were told the classifier became ready.
+    pub const READY_NOTIFIED_KEY: &str = "bayes:ready_notified";
     /// Minimum spam messages required for classifier readiness.
     pub const MIN_SPAM_MESSAGES: i64 = 200;
     /// Minimum ham messages required for classifier readiness.
     pub const MIN_HAM_MESSAGES: i64 = 200;
-    /// Expiration time for learned message tracking (24 hours in seconds).
-    pub const LEARNED_EXPIRY: u64 = 86400;
     /// Auto-learning threshold for spam (messages with score >= this value are learned as spam).
     pub const AUTOLEARN_SPAM_THRESHOLD: f64 = 6.0;
     /// Auto-learning threshold for ham (messages with score <= this value are learned as ham).
@@ -142,6 +966,8 @@ pub mod symbol {
     pub const TG_BAN: &str = "TG_BAN";
     /// Symbol for permanently banned user (`TG_PERM_BAN`)
     pub const TG_PERM_BAN: &str = "TG_PERM_BAN";
+    /// Symbol for identical message posted to several chats (`TG_CROSS_POST`).
+    pub const TG_CROSS_POST: &str = "TG_CROSS_POST";
     
     // Timing-based symbols
     /// Symbol for first message too soon after joining (`TG_FIRST_FAST`).
@@ -158,6 +984,8 @@ pub mod symbol {
     pub const TG_MENTIONS: &str = "TG_MENTIONS";
     /// Symbol for excessive capital letters (`TG_CAPS`).
     pub const TG_CAPS: &str = "TG_CAPS";
+    /// Symbol for long runs of one repeated character (`TG_CHAR_FLOOD`).
+    pub const TG_CHAR_FLOOD: &str = "TG_CHAR_FLOOD";
     /// Symbol for excessive emoji usage (`TG_EMOJI_SPAM`).
     pub const TG_EMOJI_SPAM: &str = "TG_EMOJI_SPAM";
     /// Symbol for invite link spam (`TG_INVITE_LINK`).
@@ -168,8 +996,28 @@ pub mod symbol {
     pub const TG_SPAM_CHAT: &str = "TG_SPAM_CHAT";
     /// Symbol for URL shortener spam (`TG_SHORTENER`).
     pub const TG_SHORTENER: &str = "TG_SHORTENER";
+    /// Symbol for a link whose host mixes scripts once punycode is decoded (`TG_LOOKALIKE_DOMAIN`).
+    pub const TG_LOOKALIKE_DOMAIN: &str = "TG_LOOKALIKE_DOMAIN";
+    /// Symbol for a link to a domain with bad reputation in `tg:domain_rep` (`TG_URL_REPUTATION`).
+    pub const TG_URL_REPUTATION: &str = "TG_URL_REPUTATION";
     /// Symbol for gibberish text detection (`TG_GIBBERISH`).
     pub const TG_GIBBERISH: &str = "TG_GIBBERISH";
+    /// Symbol for text in a script the chat doesn't allow (`TG_FOREIGN_SCRIPT`).
+    pub const TG_FOREIGN_SCRIPT: &str = "TG_FOREIGN_SCRIPT";
+    /// Symbol for a forwarded message (`TG_FORWARDED`).
+    pub const TG_FORWARDED: &str = "TG_FORWARDED";
+    /// Symbol for a document of a type commonly used to spread malware (`TG_ATTACHMENT_SPAM`).
+    pub const TG_ATTACHMENT_SPAM: &str = "TG_ATTACHMENT_SPAM";
+    /// Symbol for a non-admin whose display name mimics a chat admin's (`TG_IMPERSONATION`).
+    pub const TG_IMPERSONATION: &str = "TG_IMPERSONATION";
+    /// Symbol for a link posted soon after its sender joined (`TG_NEW_USER_LINK`).
+    pub const TG_NEW_USER_LINK: &str = "TG_NEW_USER_LINK";
+    /// Symbol for a shared contact card (`TG_CONTACT_CARD`).
+    pub const TG_CONTACT_CARD: &str = "TG_CONTACT_CARD";
+    /// Symbol for the same media posted repeatedly (`TG_MEDIA_REPEAT`).
+    pub const TG_MEDIA_REPEAT: &str = "TG_MEDIA_REPEAT";
+    /// Symbol for passages in two or more unrelated scripts (`TG_MIXED_LANGUAGE_SPAM`).
+    pub const TG_MIXED_LANGUAGE_SPAM: &str = "TG_MIXED_LANGUAGE_SPAM";
     
     // Whitelist/Blacklist symbols
     /// Symbol for whitelisted user (`WHITELIST_USER`).
@@ -198,7 +1046,13 @@ pub mod symbol {
     pub const TG_REPLY_ADMIN: &str = "TG_REPLY_ADMIN";
     /// Symbol for reply to verified user message (`TG_REPLY_VERIFIED`).
     pub const TG_REPLY_VERIFIED: &str = "TG_REPLY_VERIFIED";
-    
+    /// Symbol for a reply to the user's own trusted message, which earns no reduction (`TG_REPLY_SELF`).
+    pub const TG_REPLY_SELF: &str = "TG_REPLY_SELF";
+    /// Symbol for a message from a user trusted via `/trustuser` (`TG_TRUSTED_USER`).
+    pub const TG_TRUSTED_USER: &str = "TG_TRUSTED_USER";
+    /// Symbol for a message from a user in good standing (`TG_GOOD_STANDING`).
+    pub const TG_GOOD_STANDING: &str = "TG_GOOD_STANDING";
+
     // Fuzzy storage symbol
     /// Symbol for fuzzy storage detection (`FUZZY_DENIED`).
     pub const FUZZY_DENIED: &str = "FUZZY_DENIED";
@@ -229,12 +1083,14 @@ pub const DEFAULT_FEATURES: &[&str] = &[
     "suspicious",
     "ban",
     "perm_ban",
+    "cross_post",
     
     // Content features (from content.lua)
     "link_spam",
     "mentions",
     "caps",
     "emoji_spam",
+    "char_flood",
     
     // Timing features (from timing.lua)
     "first_fast",
@@ -251,15 +1107,139 @@ pub const DEFAULT_FEATURES: &[&str] = &[
     "spam_chat",
     "shortener",
     "gibberish",
+    "foreign_script",
+    "forwarded",
+    ATTACHMENT_SPAM_FEATURE,
+    IMPERSONATION_FEATURE,
+    NEW_USER_LINK_FEATURE,
+    CONTACT_CARD_FEATURE,
+    MEDIA_REPEAT_FEATURE,
+    MIXED_LANGUAGE_FEATURE,
     
     // Reply-aware filtering features
     "reply_aware",
     "trusted_replies",
 ];
 
+/// Feature that flags documents of risky types with `TG_ATTACHMENT_SPAM`.
+pub const ATTACHMENT_SPAM_FEATURE: &str = "attachment_spam";
+
+/// Feature that flags non-admins whose display name mimics an admin's with `TG_IMPERSONATION`.
+pub const IMPERSONATION_FEATURE: &str = "impersonation";
+
+/// Feature that flags links posted soon after joining with `TG_NEW_USER_LINK`.
+pub const NEW_USER_LINK_FEATURE: &str = "new_user_link";
+
+/// Feature that flags shared contact cards with `TG_CONTACT_CARD`.
+pub const CONTACT_CARD_FEATURE: &str = "contact_card";
+
+/// Feature that flags media posted again and again with `TG_MEDIA_REPEAT`.
+pub const MEDIA_REPEAT_FEATURE: &str = "media_repeat";
+
+/// Feature that flags passages in unrelated scripts with `TG_MIXED_LANGUAGE_SPAM`.
+pub const MIXED_LANGUAGE_FEATURE: &str = "mixed_language";
+
+/// Feature that mutes new members until they press the button of a welcome challenge.
+pub const JOIN_VERIFY_FEATURE: &str = "join_verify";
+
+/// Feature that scales the flood and repeat limits to a chat's message rate.
+pub const ADAPTIVE_FEATURE: &str = "adaptive_thresholds";
+
+/// Feature that reports would-be enforcement without deleting, banning or penalizing.
+pub const DRY_RUN_FEATURE: &str = "dry_run";
+
+/// Feature that deletes messages flagged `TG_GIBBERISH` even when their score alone wouldn't.
+pub const GIBBERISH_DELETE_FEATURE: &str = "gibberish_delete";
+
+/// Feature that keeps the bot from posting in a chat it still moderates; alerts
+/// only reach the admin chat and new members get no welcome challenge.
+pub const SILENT_MODE_FEATURE: &str = "silent_mode";
+
+/// Features offered in the toggle menu that stay off until enabled for a chat.
+pub const OPT_IN_FEATURES: &[&str] = &[DRY_RUN_FEATURE, GIBBERISH_DELETE_FEATURE, JOIN_VERIFY_FEATURE, ADAPTIVE_FEATURE, SILENT_MODE_FEATURE];
+
+/// Number of dry-run alerts kept per chat.
+pub const DRY_RUN_LOG_LIMIT: isize = 100;
+
 /// Redis key storing the global set of features enabled by default.
 pub const ENABLED_FEATURES_KEY: &str = "tg:enabled_features";
 
+/// Redis key storing every default feature ever added to `ENABLED_FEATURES_KEY`,
+/// so a feature switched off globally isn't switched back on at startup.
+pub const SEEDED_FEATURES_KEY: &str = "tg:seeded_features";
+
+/// Detection symbols `/togglesymbol` switches on and off globally, with the
+/// feature gating each (`when_enabled` in `telegram_simple.lua`, or the
+/// feature check of the `apply_*` step adding it).
+pub const TOGGLEABLE_SYMBOLS: &[(&str, &str)] = &[
+    (symbol::TG_LINK_SPAM, "link_spam"),
+    (symbol::TG_MENTIONS, "mentions"),
+    (symbol::TG_CAPS, "caps"),
+    (symbol::TG_EMOJI_SPAM, "emoji_spam"),
+    (symbol::TG_CHAR_FLOOD, "char_flood"),
+    (symbol::TG_FIRST_FAST, "first_fast"),
+    (symbol::TG_FIRST_SLOW, "first_slow"),
+    (symbol::TG_INVITE_LINK, "invite_link"),
+    (symbol::TG_PHONE_SPAM, "phone_spam"),
+    (symbol::TG_SHORTENER, "shortener"),
+    (symbol::TG_GIBBERISH, "gibberish"),
+    (symbol::TG_FOREIGN_SCRIPT, "foreign_script"),
+    (symbol::TG_FORWARDED, "forwarded"),
+    (symbol::TG_ATTACHMENT_SPAM, ATTACHMENT_SPAM_FEATURE),
+    (symbol::TG_IMPERSONATION, IMPERSONATION_FEATURE),
+    (symbol::TG_NEW_USER_LINK, NEW_USER_LINK_FEATURE),
+    (symbol::TG_CONTACT_CARD, CONTACT_CARD_FEATURE),
+    (symbol::TG_MEDIA_REPEAT, MEDIA_REPEAT_FEATURE),
+    (symbol::TG_MIXED_LANGUAGE_SPAM, MIXED_LANGUAGE_FEATURE),
+];
+
+/// The feature gating `symbol` (case-insensitive), if `/togglesymbol` can switch it.
+pub fn symbol_feature(symbol: &str) -> Option<&'static str> {
+    TOGGLEABLE_SYMBOLS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(symbol.trim()))
+        .map(|(_, feature)| *feature)
+}
+
+/// Where a chat's effective feature state comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureSource {
+    /// The chat's own `feat:<name>` flag.
+    ChatOverride,
+    /// The global `ENABLED_FEATURES_KEY` set.
+    GlobalDefault,
+}
+
+/// Effective state of a feature in a chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureState {
+    pub enabled: bool,
+    pub source: FeatureSource,
+}
+
+/// Resolves `feature` for a chat: the chat's `feat:<name>` flag wins, then the global set.
+pub fn feature_state(conn: &mut redis::Connection, chat_id: i64, feature: &str) -> FeatureState {
+    use redis::Commands;
+
+    let chat_key = format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id);
+    let chat_val: Option<String> = conn
+        .hget(&chat_key, format!("feat:{}", feature))
+        .unwrap_or(None);
+    match chat_val.as_deref() {
+        Some("1") => FeatureState { enabled: true, source: FeatureSource::ChatOverride },
+        Some("0") => FeatureState { enabled: false, source: FeatureSource::ChatOverride },
+        _ => FeatureState {
+            enabled: conn.sismember(key::ns(ENABLED_FEATURES_KEY), feature).unwrap_or(false),
+            source: FeatureSource::GlobalDefault,
+        },
+    }
+}
+
+/// Whether `feature` is on for a chat, see `feature_state`.
+pub fn is_feature_enabled(conn: &mut redis::Connection, chat_id: i64, feature: &str) -> bool {
+    feature_state(conn, chat_id, feature).enabled
+}
+
 /// Ban counter reduction interval in seconds (48 hours)
 pub const BAN_COUNTER_REDUCTION_INTERVAL: i64 = 48 * 60 * 60; // 48 hours in seconds
 
@@ -269,6 +1249,54 @@ pub const TRUSTED_MESSAGE_TTL: i64 = 24 * 60 * 60; // 24 hours in seconds
 /// Reply tracking TTL in seconds (7 days)
 pub const REPLY_TRACKING_TTL: i64 = 7 * 24 * 60 * 60; // 7 days in seconds
 
+/// Users vouched for with `/trustuser`
+pub mod trusted_user {
+    /// Score added to every message from a trusted user
+    pub const SCORE_REDUCTION: f64 = -10.0;
+}
+
+/// **Diagnosis:** how `/diagnose` sorts the symbols of a scan.
+pub mod diagnose {
+    use super::symbol;
+
+    /// Symbols listed as reductions even when Rspamd scores them 0.
+    pub const REDUCTIONS: &[&str] = &[
+        symbol::WHITELIST_USER,
+        symbol::WHITELIST_WORD,
+        symbol::TG_TRUSTED_USER,
+        symbol::TG_GOOD_STANDING,
+        symbol::TG_REPLY,
+        symbol::TG_REPLY_BOT,
+        symbol::TG_REPLY_ADMIN,
+        symbol::TG_REPLY_VERIFIED,
+    ];
+}
+
+/// Score reductions for members whose good reputation outweighs their bad one
+pub mod good_standing {
+    /// A level of good standing, reached at `min_net_good` (good minus bad reputation)
+    pub struct Tier {
+        pub min_net_good: i64,
+        /// Score added to every message from the user
+        pub score: f64,
+        /// How far above the usual suspicious threshold the user's `rep` may
+        /// go before `TG_SUSPICIOUS` fires, sent as `X-Telegram-Rep-Credit`
+        pub credit: i64,
+    }
+
+    /// Tiers from the highest standing down; the first one reached applies
+    pub const TIERS: &[Tier] = &[
+        Tier { min_net_good: 50, score: -2.0, credit: 3 },
+        Tier { min_net_good: 20, score: -1.0, credit: 2 },
+        Tier { min_net_good: 10, score: -0.5, credit: 1 },
+    ];
+
+    /// The tier reached with `net_good` reputation, if any.
+    pub fn tier(net_good: i64) -> Option<&'static Tier> {
+        TIERS.iter().find(|tier| net_good >= tier.min_net_good)
+    }
+}
+
 /// Advanced Reply-Aware Filtering Configuration
 pub mod reply_aware {
     /// Maximum number of trusted messages a user can create per hour
@@ -286,9 +1314,20 @@ pub mod reply_aware {
     /// Rate limiting window for replies (seconds)
     pub const REPLY_RATE_WINDOW: u64 = 3600; // 1 hour
     
-    /// Maximum score reduction for replies (prevents abuse)
+    /// Maximum score reduction for replies (prevents abuse), unless set at
+    /// runtime with `/replyconfig max_reduction|<value>`
     pub const MAX_SCORE_REDUCTION: f64 = -5.0;
     
+    /// Hash of reply-aware settings changed at runtime with `/replyconfig`
+    pub const CONFIG_KEY: &str = "tg:reply_config";
+    
+    /// Field of `CONFIG_KEY` overriding `MAX_SCORE_REDUCTION`
+    pub const MAX_REDUCTION_FIELD: &str = "max_reduction";
+    
+    /// Field of `CONFIG_KEY` switching reply-aware filtering off in every
+    /// chat while false; unset means on
+    pub const ENABLED_FIELD: &str = "enabled";
+    
     /// Minimum score for spam patterns in replies (even to trusted messages)
     pub const MIN_SPAM_SCORE_IN_REPLIES: f64 = 1.0;
     
@@ -335,6 +1374,13 @@ pub mod reply_aware {
         
         /// Maximum emoji count allowed in reply to trusted message
         pub const MAX_EMOJI_IN_REPLY: u32 = 5;
+
+        /// Per-chat override field names in `tg:chats:<id>:anti_evasion`
+        pub const LINKS_FIELD: &str = "max_links_in_reply";
+        pub const PHONES_FIELD: &str = "max_phone_numbers_in_reply";
+        pub const INVITES_FIELD: &str = "max_invite_links_in_reply";
+        pub const CAPS_RATIO_FIELD: &str = "max_caps_ratio_in_reply";
+        pub const EMOJI_FIELD: &str = "max_emoji_in_reply";
     }
 }
 
@@ -0,0 +1,303 @@
+//! Snapshot and restore of all tunable bot state, used by `/exportconfig` and `/importconfig`.
+//!
+//! A snapshot covers the globally enabled features, content thresholds, admin
+//! panel settings, the user/word white- and blacklists, the trusted link
+//! domains, and per chat the
+//! feature flags, reply-aware anti-evasion overrides, action map, join timing
+//! windows, allowed scripts and chat-scoped white- and blacklists.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use redis::{Commands, RedisResult};
+use serde::{Deserialize, Serialize};
+use teloxide::types::ChatId;
+
+use crate::config::{
+    action, join_gate, key, reply_aware::anti_evasion, reputation, suffix, threshold, DEFAULT_FEATURES,
+    ENABLED_FEATURES_KEY, OPT_IN_FEATURES,
+};
+use crate::lists::{self, List};
+use crate::script_filter;
+
+/// Format version written to every snapshot.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Anti-evasion override fields that may appear in a snapshot.
+const ANTI_EVASION_FIELDS: &[&str] = &[
+    anti_evasion::LINKS_FIELD,
+    anti_evasion::PHONES_FIELD,
+    anti_evasion::INVITES_FIELD,
+    anti_evasion::CAPS_RATIO_FIELD,
+    anti_evasion::EMOJI_FIELD,
+];
+
+/// Tunable state of a single chat.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatConfig {
+    /// Per-chat feature flags (`"1"` or `"0"`), keyed by feature name.
+    pub features: BTreeMap<String, String>,
+    /// Reply-aware anti-evasion overrides.
+    pub anti_evasion: BTreeMap<String, f64>,
+    /// Score threshold per action.
+    pub actions: BTreeMap<String, f64>,
+    /// First-message timing windows and probation, in seconds.
+    pub join_gate: BTreeMap<String, i64>,
+    /// Scripts allowed in the chat (empty allows all).
+    pub allowed_scripts: BTreeSet<String>,
+    /// User ids whitelisted in this chat only.
+    pub whitelist_users: BTreeSet<String>,
+    /// Words whitelisted in this chat only.
+    pub whitelist_words: BTreeSet<String>,
+    /// User ids blacklisted in this chat only.
+    pub blacklist_users: BTreeSet<String>,
+    /// Words blacklisted in this chat only.
+    pub blacklist_words: BTreeSet<String>,
+}
+
+impl ChatConfig {
+    fn is_empty(&self) -> bool {
+        self.features.is_empty()
+            && self.anti_evasion.is_empty()
+            && self.actions.is_empty()
+            && self.join_gate.is_empty()
+            && self.allowed_scripts.is_empty()
+            && self.whitelist_users.is_empty()
+            && self.whitelist_words.is_empty()
+            && self.blacklist_users.is_empty()
+            && self.blacklist_words.is_empty()
+    }
+
+    /// The chat-scoped lists paired with the sets they are stored in.
+    fn lists(&self) -> [(List, &BTreeSet<String>); 4] {
+        [
+            (lists::WHITELIST_USERS, &self.whitelist_users),
+            (lists::WHITELIST_WORDS, &self.whitelist_words),
+            (lists::BLACKLIST_USERS, &self.blacklist_users),
+            (lists::BLACKLIST_WORDS, &self.blacklist_words),
+        ]
+    }
+}
+
+/// Tunable bot state as exported by `/exportconfig`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigSnapshot {
+    /// Snapshot format version, see `SNAPSHOT_VERSION`.
+    pub version: u32,
+    /// Features enabled for every chat unless overridden.
+    #[serde(default)]
+    pub enabled_features: BTreeSet<String>,
+    /// Content detection thresholds from `tg:thresholds`.
+    #[serde(default)]
+    pub thresholds: BTreeMap<String, f64>,
+    /// Admin panel settings (including reputation decay).
+    #[serde(default)]
+    pub settings: BTreeMap<String, String>,
+    /// Whitelisted user ids.
+    #[serde(default)]
+    pub whitelist_users: BTreeSet<String>,
+    /// Whitelisted words.
+    #[serde(default)]
+    pub whitelist_words: BTreeSet<String>,
+    /// Blacklisted user ids.
+    #[serde(default)]
+    pub blacklist_users: BTreeSet<String>,
+    /// Blacklisted words.
+    #[serde(default)]
+    pub blacklist_words: BTreeSet<String>,
+    /// Domains exempt from link spam checks.
+    #[serde(default)]
+    pub trusted_domains: BTreeSet<String>,
+    /// Per-chat state, keyed by chat id.
+    #[serde(default)]
+    pub chats: BTreeMap<i64, ChatConfig>,
+}
+
+impl ConfigSnapshot {
+    /// Parses a snapshot and checks every name and value before anything is applied.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let snapshot: ConfigSnapshot = serde_json::from_str(json)?;
+        snapshot.validate()?;
+        Ok(snapshot)
+    }
+
+    /// Checks the snapshot version and that every feature, threshold, action,
+    /// anti-evasion field and script is one the bot knows about.
+    pub fn validate(&self) -> Result<()> {
+        if self.version != SNAPSHOT_VERSION {
+            anyhow::bail!("Unsupported snapshot version {} (expected {})", self.version, SNAPSHOT_VERSION);
+        }
+
+        let is_feature = |name: &str| DEFAULT_FEATURES.contains(&name) || OPT_IN_FEATURES.contains(&name);
+        for feature in &self.enabled_features {
+            if !is_feature(feature) {
+                anyhow::bail!("Unknown feature `{}`", feature);
+            }
+        }
+        for (name, value) in &self.thresholds {
+            if !threshold::ALL.iter().any(|(known, _)| known == name) {
+                anyhow::bail!("Unknown threshold `{}`", name);
+            }
+            if *value < 0.0 || (threshold::RATIOS.contains(&name.as_str()) && *value > 1.0) {
+                anyhow::bail!("Invalid value {} for threshold `{}`", value, name);
+            }
+        }
+
+        for (chat, config) in &self.chats {
+            for (feature, flag) in &config.features {
+                if !is_feature(feature) || (flag != "1" && flag != "0") {
+                    anyhow::bail!("Invalid feature flag `{}`={} for chat {}", feature, flag, chat);
+                }
+            }
+            for (field, value) in &config.anti_evasion {
+                if !ANTI_EVASION_FIELDS.contains(&field.as_str()) || *value < 0.0 {
+                    anyhow::bail!("Invalid anti-evasion override `{}`={} for chat {}", field, value, chat);
+                }
+            }
+            for (name, value) in &config.actions {
+                if !action::ALL.iter().any(|(known, _)| known == name) || *value < 0.0 {
+                    anyhow::bail!("Invalid action threshold `{}`={} for chat {}", name, value, chat);
+                }
+            }
+            for (name, value) in &config.join_gate {
+                if !join_gate::ALL.iter().any(|(known, _)| known == name) || *value < 0 {
+                    anyhow::bail!("Invalid join window `{}`={} for chat {}", name, value, chat);
+                }
+            }
+            for script in &config.allowed_scripts {
+                if !script_filter::is_known_script(script) {
+                    anyhow::bail!("Unknown script `{}` for chat {}", script, chat);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn chat_key(chat_id: i64, suffix: &str) -> String {
+    format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id, suffix)
+}
+
+/// Collects all tunable state from Redis.
+pub fn export_config(conn: &mut redis::Connection) -> RedisResult<ConfigSnapshot> {
+    let mut snapshot = ConfigSnapshot {
+        version: SNAPSHOT_VERSION,
+        enabled_features: conn.smembers(key::ns(ENABLED_FEATURES_KEY))?,
+        thresholds: conn.hgetall(key::ns(key::TG_THRESHOLDS_KEY))?,
+        settings: conn.hgetall(key::ns(reputation::SETTINGS_KEY))?,
+        whitelist_users: conn.smembers(key::ns(key::TG_WHITELIST_USER_KEY))?,
+        whitelist_words: conn.smembers(key::ns(key::TG_WHITELIST_WORD_KEY))?,
+        blacklist_users: conn.smembers(key::ns(key::TG_BLACKLIST_USER_KEY))?,
+        blacklist_words: conn.smembers(key::ns(key::TG_BLACKLIST_WORD_KEY))?,
+        trusted_domains: conn.smembers(key::ns(key::TG_TRUSTED_DOMAINS_KEY))?,
+        chats: BTreeMap::new(),
+    };
+
+    // Chats show up as `tg:chats:<id>` and `tg:chats:<id>:<suffix>` keys
+    let chat_ids: BTreeSet<i64> = conn
+        .scan_match::<_, String>(format!("{}*", key::ns(key::TG_CHATS_PREFIX)))?
+        .filter_map(|chat_key| {
+            let rest = &chat_key[key::ns(key::TG_CHATS_PREFIX).len()..];
+            rest.split(':').next()?.parse::<i64>().ok()
+        })
+        .collect();
+    for chat_id in chat_ids {
+        let fields: BTreeMap<String, String> = conn.hgetall(chat_key(chat_id, ""))?;
+        let config = ChatConfig {
+            features: fields
+                .into_iter()
+                .filter_map(|(field, value)| field.strip_prefix("feat:").map(|name| (name.to_string(), value)))
+                .collect(),
+            anti_evasion: conn.hgetall(chat_key(chat_id, suffix::ANTI_EVASION))?,
+            actions: conn.hgetall(chat_key(chat_id, suffix::ACTIONS))?,
+            join_gate: conn.hgetall(chat_key(chat_id, suffix::JOIN_GATE))?,
+            allowed_scripts: conn.smembers(chat_key(chat_id, suffix::ALLOWED_SCRIPTS))?,
+            whitelist_users: conn.smembers(chat_key(chat_id, suffix::WHITELIST_USERS))?,
+            whitelist_words: conn.smembers(chat_key(chat_id, suffix::WHITELIST_WORDS))?,
+            blacklist_users: conn.smembers(chat_key(chat_id, suffix::BLACKLIST_USERS))?,
+            blacklist_words: conn.smembers(chat_key(chat_id, suffix::BLACKLIST_WORDS))?,
+        };
+        if !config.is_empty() {
+            snapshot.chats.insert(chat_id, config);
+        }
+    }
+
+    Ok(snapshot)
+}
+
+/// Replaces the tunable state in Redis with `snapshot` in a single transaction.
+///
+/// Every section in the snapshot overwrites its Redis counterpart; chats not
+/// mentioned in the snapshot are left untouched.
+pub fn import_config(conn: &mut redis::Connection, snapshot: &ConfigSnapshot) -> RedisResult<()> {
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+
+    let sets = [
+        (key::ns(ENABLED_FEATURES_KEY), &snapshot.enabled_features),
+        (key::ns(key::TG_WHITELIST_USER_KEY), &snapshot.whitelist_users),
+        (key::ns(key::TG_WHITELIST_WORD_KEY), &snapshot.whitelist_words),
+        (key::ns(key::TG_BLACKLIST_USER_KEY), &snapshot.blacklist_users),
+        (key::ns(key::TG_BLACKLIST_WORD_KEY), &snapshot.blacklist_words),
+        (key::ns(key::TG_TRUSTED_DOMAINS_KEY), &snapshot.trusted_domains),
+    ];
+    for (set_key, members) in sets {
+        pipe.del(&set_key).ignore();
+        for member in members {
+            pipe.sadd(&set_key, member).ignore();
+        }
+    }
+
+    pipe.del(key::ns(key::TG_THRESHOLDS_KEY)).ignore();
+    for (name, value) in &snapshot.thresholds {
+        pipe.hset(key::ns(key::TG_THRESHOLDS_KEY), name, *value).ignore();
+    }
+    pipe.del(key::ns(reputation::SETTINGS_KEY)).ignore();
+    for (name, value) in &snapshot.settings {
+        pipe.hset(key::ns(reputation::SETTINGS_KEY), name, value).ignore();
+    }
+
+    for (chat_id, config) in &snapshot.chats {
+        let chat_hash = chat_key(*chat_id, "");
+        for (feature, flag) in &config.features {
+            pipe.hset(&chat_hash, format!("feat:{}", feature), flag).ignore();
+        }
+
+        let anti_evasion_key = chat_key(*chat_id, suffix::ANTI_EVASION);
+        pipe.del(&anti_evasion_key).ignore();
+        for (field, value) in &config.anti_evasion {
+            pipe.hset(&anti_evasion_key, field, *value).ignore();
+        }
+
+        let actions_key = chat_key(*chat_id, suffix::ACTIONS);
+        pipe.del(&actions_key).ignore();
+        for (name, value) in &config.actions {
+            pipe.hset(&actions_key, name, *value).ignore();
+        }
+
+        let gate_key = chat_key(*chat_id, suffix::JOIN_GATE);
+        pipe.del(&gate_key).ignore();
+        for (name, value) in &config.join_gate {
+            pipe.hset(&gate_key, name, *value).ignore();
+        }
+
+        let scripts_key = chat_key(*chat_id, suffix::ALLOWED_SCRIPTS);
+        pipe.del(&scripts_key).ignore();
+        for script in &config.allowed_scripts {
+            pipe.sadd(&scripts_key, script).ignore();
+        }
+
+        for (list, members) in config.lists() {
+            let list_key = list.key(Some(ChatId(*chat_id)));
+            pipe.del(&list_key).ignore();
+            for member in members {
+                pipe.sadd(&list_key, member).ignore();
+            }
+        }
+    }
+
+    pipe.query(conn)
+}
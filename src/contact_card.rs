@@ -0,0 +1,48 @@
+//! Shared contact cards behind `TG_CONTACT_CARD`.
+//!
+//! Scammers share contact cards to harvest numbers or to pose as support,
+//! and a card carries no text for the content rules to look at. Every
+//! shared contact is flagged; one shared within the chat's `new_user_link`
+//! join window (see `/setjoinwindow`) scores `contact_card::NEW_USER_SCORE`
+//! instead, as a fresh account handing out a number is the typical case.
+
+use redis::{Commands, RedisResult};
+use rspamd_client::protocol::{scan::Symbol, RspamdScanReply};
+use teloxide::types::Message;
+
+use crate::config::{contact_card, field, is_feature_enabled, key, symbol, CONTACT_CARD_FEATURE};
+use crate::join_gate::join_windows;
+use crate::new_user_link::is_new_member;
+
+/// Adds `TG_CONTACT_CARD` to the scan of `msg` when it shares a contact and
+/// the feature is on for the chat; a sender who joined within the chat's
+/// window before `now` gets the higher score.
+pub fn apply_contact_card(conn: &mut redis::Connection, reply: &mut RspamdScanReply, msg: &Message, now: i64) -> RedisResult<()> {
+    let Some(contact) = msg.contact() else {
+        return Ok(());
+    };
+    if !is_feature_enabled(conn, msg.chat.id.0, CONTACT_CARD_FEATURE) {
+        return Ok(());
+    }
+    let join_time: Option<i64> = match msg.from.as_ref() {
+        Some(user) => conn.hget(format!("{}{}", key::ns(key::TG_USERS_PREFIX), user.id.0), field::JOIN_TIME)?,
+        None => None,
+    };
+    let (score, description) = if is_new_member(join_time, now, join_windows(conn, msg.chat.id).new_user_link) {
+        (contact_card::NEW_USER_SCORE, "Contact card shared soon after joining")
+    } else {
+        (contact_card::SCORE, "Contact card shared")
+    };
+    reply.score += score;
+    reply.symbols.insert(
+        symbol::TG_CONTACT_CARD.to_string(),
+        Symbol {
+            name: symbol::TG_CONTACT_CARD.to_string(),
+            score,
+            metric_score: score,
+            description: Some(description.to_string()),
+            options: Some(vec![contact.phone_number.clone()]),
+        },
+    );
+    Ok(())
+}
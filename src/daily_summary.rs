@@ -0,0 +1,155 @@
+//! Once-a-day digest of moderation activity sent to admin chats.
+//!
+//! For every admin chat (`admin:<id>:moderated_chats`) the summary covers
+//! each moderated chat's actions and top symbols from the daily buckets of
+//! `spam_trend`, and the users banned there according to the spam event log.
+//! It is sent at `DAILY_SUMMARY_TIME` (UTC, `HH:MM`).
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Write;
+
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, Utc};
+use redis::{Commands, RedisResult};
+use teloxide::prelude::*;
+
+use crate::config::{action, field, key, suffix, summary, symbol};
+use crate::outgoing::throttle;
+use crate::spam_events::users_with_events_since;
+use crate::spam_trend::{daily_key, daily_symbols_key};
+
+/// One moderated chat's activity over a day.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatSummary {
+    pub chat_id: ChatId,
+    pub name: Option<String>,
+    /// Actions taken, by action name.
+    pub actions: HashMap<String, i64>,
+    /// Most triggered symbols, most frequent first.
+    pub top_symbols: Vec<(String, i64)>,
+    /// Users banned since the start of the period.
+    pub banned_users: Vec<u64>,
+}
+
+/// Time of day the summary is sent at, from `DAILY_SUMMARY_TIME`.
+pub fn summary_time() -> NaiveTime {
+    std::env::var(summary::TIME_ENV)
+        .ok()
+        .and_then(|time| NaiveTime::parse_from_str(time.trim(), "%H:%M").ok())
+        .unwrap_or_else(|| NaiveTime::parse_from_str(summary::DEFAULT_TIME, "%H:%M").unwrap())
+}
+
+/// How long to wait from `now` until the next `at` (UTC).
+pub fn until_next_run(now: DateTime<Utc>, at: NaiveTime) -> std::time::Duration {
+    let today = now.date_naive().and_time(at).and_utc();
+    let next = if today > now { today } else { today + chrono::Duration::days(1) };
+    (next - now).to_std().unwrap_or_default()
+}
+
+/// Admin chats paired with the chats they moderate.
+pub fn admin_chats(conn: &mut redis::Connection) -> RedisResult<Vec<(ChatId, Vec<i64>)>> {
+    let prefix = key::ns(key::ADMIN_PREFIX);
+    let keys: Vec<String> = conn
+        .scan_match::<_, String>(format!("{}*{}", prefix, suffix::MODERATED_CHATS))?
+        .collect();
+    let mut chats = Vec::new();
+    for moderated_key in keys {
+        let Some(admin_chat) = moderated_key
+            .strip_prefix(prefix.as_str())
+            .and_then(|rest| rest.strip_suffix(suffix::MODERATED_CHATS))
+            .and_then(|id| id.parse::<i64>().ok())
+        else {
+            continue;
+        };
+        let mut moderated: Vec<i64> = conn.smembers(&moderated_key)?;
+        moderated.sort_unstable();
+        chats.push((ChatId(admin_chat), moderated));
+    }
+    chats.sort_by_key(|(admin_chat, _)| admin_chat.0);
+    Ok(chats)
+}
+
+/// Activity of `chat_id` on `day`; bans are those logged at or after `since`.
+pub fn chat_summary(conn: &mut redis::Connection, chat_id: ChatId, day: NaiveDate, since: i64) -> RedisResult<ChatSummary> {
+    let name: Option<String> = conn.hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0), field::NAME)?;
+    let actions: HashMap<String, i64> = conn.hgetall(daily_key(chat_id, day))?;
+    let symbols: HashMap<String, i64> = conn.hgetall(daily_symbols_key(chat_id, day))?;
+    let mut top_symbols: Vec<(String, i64)> = symbols.into_iter().collect();
+    top_symbols.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top_symbols.truncate(summary::TOP_SYMBOLS);
+    let banned_users = users_with_events_since(conn, chat_id, &[symbol::TG_BAN, symbol::TG_PERM_BAN], since)?;
+
+    Ok(ChatSummary {
+        chat_id,
+        name,
+        actions,
+        top_symbols,
+        banned_users,
+    })
+}
+
+/// Renders the summary of several chats for `day`.
+pub fn render_summary(day: NaiveDate, chats: &[ChatSummary]) -> String {
+    let mut out = format!("Daily summary for {}\n", day.format("%Y-%m-%d"));
+    for chat in chats {
+        match &chat.name {
+            Some(name) => writeln!(&mut out, "\n{} ({}):", name, chat.chat_id).unwrap(),
+            None => writeln!(&mut out, "\nChat {}:", chat.chat_id).unwrap(),
+        }
+
+        let actions: Vec<String> = action::ALL
+            .iter()
+            .filter_map(|(name, _)| chat.actions.get(*name).filter(|count| **count > 0).map(|count| format!("{} {}", name, count)))
+            .collect();
+        if actions.is_empty() {
+            writeln!(&mut out, "• Actions: none").unwrap();
+        } else {
+            writeln!(&mut out, "• Actions: {}", actions.join(", ")).unwrap();
+        }
+
+        if !chat.top_symbols.is_empty() {
+            let symbols: Vec<String> = chat.top_symbols.iter().map(|(name, count)| format!("{} ({})", name, count)).collect();
+            writeln!(&mut out, "• Top symbols: {}", symbols.join(", ")).unwrap();
+        }
+
+        if !chat.banned_users.is_empty() {
+            let users: Vec<String> = chat.banned_users.iter().map(|id| id.to_string()).collect();
+            writeln!(&mut out, "• Newly banned: {}", users.join(", ")).unwrap();
+        }
+    }
+    out
+}
+
+/// Builds the summary an admin chat gets for `moderated` chats on `day`.
+pub fn build_summary(conn: &mut redis::Connection, moderated: &[i64], day: NaiveDate, since: i64) -> RedisResult<String> {
+    let chats = moderated
+        .iter()
+        .map(|chat| chat_summary(conn, ChatId(*chat), day, since))
+        .collect::<RedisResult<Vec<_>>>()?;
+    Ok(render_summary(day, &chats))
+}
+
+/// Sends yesterday's summary to every admin chat that moderates at least one
+/// chat; returns how many were sent.
+pub async fn send_daily_summaries(bot: &Bot) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+    let now = Utc::now();
+    let Some(yesterday) = now.date_naive().checked_sub_days(Days::new(1)) else {
+        return Ok(0);
+    };
+    let since = (now - chrono::Duration::days(1)).timestamp();
+
+    let mut sent = 0;
+    for (admin_chat, moderated) in admin_chats(&mut conn)? {
+        if moderated.is_empty() {
+            continue;
+        }
+        let text = build_summary(&mut conn, &moderated, yesterday, since)?;
+        throttle(admin_chat).await;
+        match bot.send_message(admin_chat, text).await {
+            Ok(_) => sent += 1,
+            Err(e) => log::warn!("Failed to send daily summary to {}: {}", admin_chat, e),
+        }
+    }
+    Ok(sent)
+}
@@ -0,0 +1,103 @@
+//! Domain reputation store behind `TG_URL_REPUTATION`.
+//!
+//! `tg:domain_rep` maps a host to a score. Admins adjust it with `/domainrep`,
+//! and every domain linked from a banned message gains `domain_rep::BAN_INCREMENT`.
+//! A host inherits the score of its parent domains, so `login.evil.example`
+//! is scored like `evil.example`. Trusted domains are never scored.
+
+use std::collections::HashSet;
+
+use once_cell::sync::Lazy;
+use redis::{Commands, RedisResult};
+use regex::Regex;
+use rspamd_client::protocol::{scan::Symbol, RspamdScanReply};
+
+use crate::config::{domain_rep, key, symbol};
+
+static LINK_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"https?://([^/\s:]+)[^\s]*").unwrap());
+
+/// Lowercased hosts linked from `text`, without duplicates, in order of appearance.
+pub fn linked_hosts(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    LINK_REGEX
+        .captures_iter(text)
+        .map(|c| c[1].trim_end_matches('.').to_lowercase())
+        .filter(|host| seen.insert(host.clone()))
+        .collect()
+}
+
+/// `host` followed by each of its parent domains, e.g. `a.b.c`, `b.c`, `c`.
+fn domain_chain(host: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(host), |h| h.split_once('.').map(|(_, parent)| parent))
+}
+
+/// Linked hosts of `text` that aren't trusted.
+pub fn untrusted_hosts(conn: &mut redis::Connection, text: &str) -> RedisResult<Vec<String>> {
+    let trusted: HashSet<String> = conn.smembers(key::ns(key::TG_TRUSTED_DOMAINS_KEY))?;
+    Ok(linked_hosts(text)
+        .into_iter()
+        .filter(|host| !domain_chain(host).any(|d| trusted.contains(d)))
+        .collect())
+}
+
+/// Reputation of a single domain (0 if unknown).
+pub fn domain_score(conn: &mut redis::Connection, domain: &str) -> RedisResult<f64> {
+    let score: Option<f64> = conn.hget(key::ns(key::TG_DOMAIN_REP_KEY), domain.to_lowercase())?;
+    Ok(score.unwrap_or(0.0))
+}
+
+/// Adds `delta` to a domain's reputation and returns the new score.
+pub fn adjust_domain(conn: &mut redis::Connection, domain: &str, delta: f64) -> RedisResult<f64> {
+    conn.hincr(key::ns(key::TG_DOMAIN_REP_KEY), domain.to_lowercase(), delta)
+}
+
+/// The untrusted host in `text` with the highest positive reputation, and that reputation.
+///
+/// A host's reputation is the highest score among it and its parent domains.
+pub fn worst_domain(conn: &mut redis::Connection, text: &str) -> RedisResult<Option<(String, f64)>> {
+    let mut worst: Option<(String, f64)> = None;
+    for host in untrusted_hosts(conn, text)? {
+        let chain: Vec<&str> = domain_chain(&host).collect();
+        let scores: Vec<Option<f64>> = redis::cmd("HMGET").arg(key::ns(key::TG_DOMAIN_REP_KEY)).arg(&chain).query(conn)?;
+        let score = scores.into_iter().flatten().fold(f64::MIN, f64::max);
+        if score > 0.0 && worst.as_ref().is_none_or(|(_, w)| score > *w) {
+            worst = Some((host, score));
+        }
+    }
+    Ok(worst)
+}
+
+/// Adds `TG_URL_REPUTATION` to the scan when `text` links to a domain with bad
+/// reputation, scored by the worst domain's reputation times
+/// `domain_rep::WEIGHT`, capped at `domain_rep::MAX_SCORE`.
+pub fn apply_url_reputation(conn: &mut redis::Connection, reply: &mut RspamdScanReply, text: &str) -> RedisResult<()> {
+    let Some((host, rep)) = worst_domain(conn, text)? else {
+        return Ok(());
+    };
+    let score = (rep * domain_rep::WEIGHT).min(domain_rep::MAX_SCORE);
+    reply.score += score;
+    reply.symbols.insert(
+        symbol::TG_URL_REPUTATION.to_string(),
+        Symbol {
+            name: symbol::TG_URL_REPUTATION.to_string(),
+            score,
+            metric_score: score,
+            description: Some("Link to a domain with bad reputation".to_string()),
+            options: Some(vec![host]),
+        },
+    );
+    Ok(())
+}
+
+/// Raises the reputation of every untrusted domain linked from a banned message.
+pub fn record_banned_domains(conn: &mut redis::Connection, text: &str) -> RedisResult<()> {
+    let hosts = untrusted_hosts(conn, text)?;
+    if hosts.is_empty() {
+        return Ok(());
+    }
+    let mut pipe = redis::pipe();
+    for host in &hosts {
+        pipe.hincr(key::ns(key::TG_DOMAIN_REP_KEY), host, domain_rep::BAN_INCREMENT).ignore();
+    }
+    pipe.query(conn)
+}
@@ -0,0 +1,42 @@
+//! Per-chat flood limit behind `TG_FLOOD`.
+//!
+//! A chat's limit lives in the `flood_max` field of its `tg:chats:<id>` hash
+//! and falls back to the global `flood_max` threshold, then to
+//! `config::threshold::DEFAULT_FLOOD_MAX`; `telegram_simple.lua` reads the same values.
+
+use redis::{Commands, RedisResult};
+use teloxide::types::ChatId;
+
+use crate::config::{field, key, threshold};
+
+/// Where a chat's flood limit comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloodLimitSource {
+    /// Set for this chat with `/setflood`.
+    Chat,
+    /// The global `flood_max` threshold or its default.
+    Global,
+}
+
+/// Messages a user may send per flood window in `chat_id` before `TG_FLOOD` fires.
+pub fn flood_limit(conn: &mut redis::Connection, chat_id: ChatId) -> (i64, FloodLimitSource) {
+    let chat_key = format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0);
+    if let Ok(Some(limit)) = conn.hget::<_, _, Option<i64>>(&chat_key, field::FLOOD_MAX) {
+        return (limit, FloodLimitSource::Chat);
+    }
+    let global = conn
+        .hget::<_, _, Option<f64>>(key::ns(key::TG_THRESHOLDS_KEY), threshold::FLOOD_MAX)
+        .ok()
+        .flatten()
+        .unwrap_or(threshold::DEFAULT_FLOOD_MAX);
+    (global as i64, FloodLimitSource::Global)
+}
+
+/// Sets `chat_id`'s flood limit, or goes back to the global one with `None`.
+pub fn set_flood_limit(conn: &mut redis::Connection, chat_id: ChatId, limit: Option<i64>) -> RedisResult<()> {
+    let chat_key = format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0);
+    match limit {
+        Some(limit) => conn.hset(&chat_key, field::FLOOD_MAX, limit),
+        None => conn.hdel(&chat_key, field::FLOOD_MAX),
+    }
+}
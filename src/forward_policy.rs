@@ -0,0 +1,85 @@
+//! Which forwarded messages a chat accepts.
+//!
+//! Some chats get their channel ads as forwards and would rather have none,
+//! others rely on them. Each chat picks a `ForwardPolicy` in the `forwards`
+//! field of its `tg:chats:<id>` hash (see `/forwards`): `allow` keeps every
+//! forward, `restrict_new` deletes forwards from members within their
+//! `new_user_forward` join window and `deny` deletes them all.
+
+use redis::{Commands, RedisResult};
+use teloxide::types::{ChatId, Message};
+
+use crate::config::{field, forward, key};
+use crate::handlers::forward_origin_kind;
+use crate::join_gate::join_windows;
+use crate::new_user_link::is_new_member;
+
+/// Forwarded messages a chat accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardPolicy {
+    /// Every forward.
+    Allow,
+    /// Forwards from members past their `new_user_forward` join window.
+    RestrictNew,
+    /// No forwards at all.
+    Deny,
+}
+
+impl ForwardPolicy {
+    /// Name stored in the chat hash and used by `/forwards`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ForwardPolicy::Allow => forward::ALLOW,
+            ForwardPolicy::RestrictNew => forward::RESTRICT_NEW,
+            ForwardPolicy::Deny => forward::DENY,
+        }
+    }
+
+    /// The policy called `name`, if any.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            forward::ALLOW => Some(ForwardPolicy::Allow),
+            forward::RESTRICT_NEW => Some(ForwardPolicy::RestrictNew),
+            forward::DENY => Some(ForwardPolicy::Deny),
+            _ => None,
+        }
+    }
+}
+
+fn chat_key(chat_id: ChatId) -> String {
+    format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0)
+}
+
+/// The forward policy of `chat_id`, `allow` unless the chat chose otherwise.
+pub fn forward_policy(conn: &mut redis::Connection, chat_id: ChatId) -> ForwardPolicy {
+    conn.hget::<_, _, Option<String>>(chat_key(chat_id), field::FORWARDS)
+        .ok()
+        .flatten()
+        .and_then(|name| ForwardPolicy::parse(&name))
+        .unwrap_or(ForwardPolicy::Allow)
+}
+
+/// Sets the forward policy of `chat_id`, or goes back to the default with `None`.
+pub fn set_forward_policy(conn: &mut redis::Connection, chat_id: ChatId, policy: Option<ForwardPolicy>) -> RedisResult<()> {
+    match policy {
+        Some(policy) => conn.hset(chat_key(chat_id), field::FORWARDS, policy.as_str()),
+        None => conn.hdel(chat_key(chat_id), field::FORWARDS),
+    }
+}
+
+/// The policy `msg` breaks if it is a forward its chat doesn't accept at
+/// `now`, or `None` when it may stay.
+pub fn forward_blocked(conn: &mut redis::Connection, msg: &Message, now: i64) -> Option<ForwardPolicy> {
+    forward_origin_kind(msg)?;
+    match forward_policy(conn, msg.chat.id) {
+        ForwardPolicy::Allow => None,
+        ForwardPolicy::Deny => Some(ForwardPolicy::Deny),
+        ForwardPolicy::RestrictNew => {
+            let user = msg.from.as_ref()?;
+            let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user.id.0);
+            let join_time: Option<i64> = conn.hget(&user_key, field::JOIN_TIME).unwrap_or(None);
+            let window = join_windows(conn, msg.chat.id).new_user_forward;
+            is_new_member(join_time, now, window).then_some(ForwardPolicy::RestrictNew)
+        }
+    }
+}
@@ -18,8 +18,8 @@ impl FuzzyTrainer {
     pub fn new() -> Self {
         Self {
             client: Client::new(),
-            controller_url: rspamd::CONTROLLER_URL.to_string(),
-            password: rspamd::PASSWORD.to_string(),
+            controller_url: rspamd::controller_url(),
+            password: rspamd::password(),
         }
     }
 
@@ -53,4 +53,77 @@ impl FuzzyTrainer {
         response.error_for_status()?;
         Ok(())
     }
+
+    /// Adds a stored message to the fuzzy storage with an explicit flag and weight.
+    /// 
+    /// Unlike `teach_fuzzy`, this is an explicit admin action, so the content is
+    /// always submitted regardless of length.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `message_id` - The unique identifier for the message
+    /// * `content` - The message content to hash
+    /// * `flag` - The fuzzy storage flag (list) to add the hash to
+    /// * `weight` - The weight assigned to the hash
+    pub async fn add_hash(&self, message_id: &str, content: &str, flag: u8, weight: i32) -> Result<()> {
+        let response = self.client
+            .post(format!("{}/fuzzyadd", self.controller_url))
+            .header("Password", &self.password)
+            .header("Content-Type", "message/rfc822")
+            .header("Flag", flag.to_string())
+            .header("Weight", weight.to_string())
+            .body(fuzzy_message(message_id, content))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            log::info!("Added fuzzy hash for message {} (flag {}, weight {})", message_id, flag, weight);
+            Ok(())
+        } else {
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            Err(anyhow::anyhow!("Failed to add fuzzy hash: {} - {}", status, error_text))
+        }
+    }
+
+    /// Removes a stored message's hash from the fuzzy storage.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `message_id` - The unique identifier for the message
+    /// * `content` - The message content whose hash should be removed
+    /// * `flag` - The fuzzy storage flag (list) to remove the hash from
+    pub async fn del_hash(&self, message_id: &str, content: &str, flag: u8) -> Result<()> {
+        let response = self.client
+            .post(format!("{}/fuzzydel", self.controller_url))
+            .header("Password", &self.password)
+            .header("Content-Type", "message/rfc822")
+            .header("Flag", flag.to_string())
+            .body(fuzzy_message(message_id, content))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status.is_success() {
+            log::info!("Removed fuzzy hash for message {} (flag {})", message_id, flag);
+            Ok(())
+        } else {
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            Err(anyhow::anyhow!("Failed to delete fuzzy hash: {} - {}", status, error_text))
+        }
+    }
+}
+
+/// Wraps message content in the same minimal RFC 822 envelope used for Bayes learning.
+fn fuzzy_message(message_id: &str, content: &str) -> String {
+    format!(
+        "Message-ID: <{}@telegram.bot>\r\n\
+         From: telegram-bot@local\r\n\
+         To: rspamd@local\r\n\
+         Subject: Telegram message {}\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\
+         \r\n\
+         {}",
+        message_id, message_id, content
+    )
 }
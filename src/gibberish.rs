@@ -0,0 +1,43 @@
+//! Random-letter text detection used by `TG_GIBBERISH`.
+//!
+//! Mirrors `tg_gibberish_cb` in `telegram_simple.lua`. Every Latin letter of
+//! the message counts wherever the spaces fall, so gibberish split into
+//! several "words" is caught just like one long token.
+
+/// Latin letters and vowels in a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LetterCounts {
+    /// Latin letters.
+    pub letters: usize,
+    /// Vowels among them (`y` included).
+    pub vowels: usize,
+}
+
+impl LetterCounts {
+    /// Share of letters that are consonants.
+    pub fn consonant_ratio(&self) -> f64 {
+        if self.letters == 0 {
+            0.0
+        } else {
+            1.0 - self.vowels as f64 / self.letters as f64
+        }
+    }
+
+    /// Whether there are at least `min_letters` letters and more than
+    /// `max_ratio` of them are consonants.
+    pub fn is_gibberish(&self, min_letters: f64, max_ratio: f64) -> bool {
+        self.letters > 0 && self.letters as f64 >= min_letters && self.consonant_ratio() > max_ratio
+    }
+}
+
+/// Counts the Latin letters and vowels in `text`.
+pub fn letter_counts(text: &str) -> LetterCounts {
+    let mut counts = LetterCounts::default();
+    for c in text.chars().filter(char::is_ascii_alphabetic) {
+        counts.letters += 1;
+        if matches!(c.to_ascii_lowercase(), 'a' | 'e' | 'i' | 'o' | 'u' | 'y') {
+            counts.vowels += 1;
+        }
+    }
+    counts
+}
@@ -0,0 +1,51 @@
+//! Emoji counting used by `TG_EMOJI_SPAM`, Bayes features and the reply
+//! anti-evasion checks.
+//!
+//! Mirrors `count_emoji` in `telegram_simple.lua`. A flag (two regional
+//! indicators), an emoji with a skin tone and a ZWJ sequence such as a family
+//! each count as one emoji, as they are displayed as one.
+
+use crate::config::emoji;
+
+fn in_ranges(c: char, ranges: &[(u32, u32)]) -> bool {
+    let code = c as u32;
+    ranges.iter().any(|(first, last)| (*first..=*last).contains(&code))
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    in_ranges(c, &[emoji::REGIONAL_INDICATORS])
+}
+
+/// Whether `c` is an emoji on its own, per `emoji::RANGES`.
+pub fn is_emoji(c: char) -> bool {
+    !in_ranges(c, emoji::MODIFIERS) && in_ranges(c, emoji::RANGES)
+}
+
+/// Counts the emoji in `text` as displayed: flags, modifier and ZWJ sequences
+/// count once.
+pub fn count_emoji(text: &str) -> usize {
+    let mut count = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if !is_emoji(c) {
+            continue;
+        }
+        count += 1;
+        if is_regional_indicator(c) {
+            chars.next_if(|next| is_regional_indicator(*next));
+            continue;
+        }
+        // Modifiers and joined emoji belong to this one
+        loop {
+            if chars.next_if(|next| in_ranges(*next, emoji::MODIFIERS)).is_some() {
+                continue;
+            }
+            if chars.next_if_eq(&emoji::ZWJ).is_some() {
+                chars.next_if(|next| is_emoji(*next));
+                continue;
+            }
+            break;
+        }
+    }
+    count
+}
@@ -1,13 +1,28 @@
-use crate::config::{field, key, symbol, bayes};
-use crate::handlers::scan_msg;
+use crate::config::{action, field, forward, key, lockdown, message_store, retention, suffix, symbol, bayes, is_feature_enabled, DRY_RUN_FEATURE, DRY_RUN_LOG_LIMIT, GIBBERISH_DELETE_FEATURE};
+use crate::admin_handlers::record_recent_message;
+use crate::handlers::{claim_scan, forward_origin_kind, message_sender, release_scan, scan_claimed_msg, scan_msg, Sender};
+use crate::join_gate::probation_remaining;
+use crate::forward_policy::forward_blocked;
+use crate::lockdown::{announce_lockdown, held_by_lockdown, mute_for_lockdown, record_raid_event};
+use crate::notifications::{action_severity, alert_enabled, send_alert, Severity};
+use crate::spam_events::describe_reason;
+use crate::spam_trend::record_daily_action;
+use crate::domain_rep::record_banned_domains;
+use crate::adaptive::record_message;
+use crate::media_repeat::media_unique_id;
+use crate::ban_rate::{admit_ban, BanDecision};
+use crate::perm_ban::{enforce_perm_ban, escalate_perm_ban, perm_ban_action, PermBanAction};
+use crate::mutes::{mute_minutes, mute_user};
+use crate::outgoing::{throttle, throttle_request};
 use crate::trust_manager::TrustManager;
 use crate::fuzzy_trainer::FuzzyTrainer;
-use crate::bayes_manager::BayesManager;
-use chrono::{Duration, Utc};
+use crate::bayes_manager::{announce_bayes_ready, BayesManager};
+use chrono::Utc;
 use redis::Commands;
+use rspamd_client::protocol::RspamdScanReply;
 use std::error::Error;
 use teloxide::prelude::*;
-use teloxide::types::{ChatPermissions, ChatMemberStatus};
+use teloxide::types::{ChatMemberStatus, MessageId};
 use once_cell::sync::Lazy;
 
 static FUZZY_TRAINER: Lazy<FuzzyTrainer> = Lazy::new(|| FuzzyTrainer::new());
@@ -15,14 +30,14 @@ static FUZZY_TRAINER: Lazy<FuzzyTrainer> = Lazy::new(|| FuzzyTrainer::new());
 async fn check_bot_permissions(bot: &Bot, chat_id: ChatId) -> anyhow::Result<()> {
     match bot.get_chat_member(chat_id, bot.get_me().await?.id).await {
         Ok(member) => {
-            println!("Bot member status: {:?}", member.status());
+            tracing::info!("Bot member status: {:?}", member.status());
             match member.status() {
                 ChatMemberStatus::Administrator => {
-                    println!("Bot is admin - should have restrict permissions");
+                    tracing::info!("Bot is admin - should have restrict permissions");
                     Ok(())
                 },
                 ChatMemberStatus::Owner => {
-                    println!("Bot is owner - has all permissions");
+                    tracing::info!("Bot is owner - has all permissions");
                     Ok(())
                 },
                 _ => {
@@ -36,16 +51,149 @@ async fn check_bot_permissions(bot: &Bot, chat_id: ChatId) -> anyhow::Result<()>
     }
 }
 
+#[tracing::instrument(skip_all, fields(chat_id = message.chat.id.0, user_id = message_sender(&message).map(|sender| sender.id()), message_id = message.id.0))]
 pub async fn handle_message(
     bot: Bot,
     message: Message,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // Documents and other media are scanned by their caption, so risky files raise
+    // TG_ATTACHMENT_SPAM and reposted media TG_MEDIA_REPEAT, and shared contacts by
+    // their name, so they raise TG_CONTACT_CARD
     let text = if let Some(text) = message.text() {
         text.to_string()
+    } else if media_unique_id(&message).is_some() {
+        message.caption().unwrap_or_default().to_string()
+    } else if let Some(contact) = message.contact() {
+        match &contact.last_name {
+            Some(last_name) => format!("{} {}", contact.first_name, last_name),
+            None => contact.first_name.clone(),
+        }
     } else {
         return Ok(());
     };
     
+    let redis_client = redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client.get_connection().expect("Failed to get Redis connection");
+    
+    // Skip detection and moderation entirely while an emergency stop is active
+    let emergency_stop: bool = redis_conn.exists(key::ns(key::EMERGENCY_STOP_KEY)).unwrap_or(false);
+    if emergency_stop {
+        tracing::info!("Emergency stop active, skipping message {} in chat {}", message.id, message.chat.id);
+        return Ok(());
+    }
+    
+    // Only messages from user accounts carry reputation; posts made as a chat are handled apart
+    let user_id = match message_sender(&message) {
+        Some(Sender::User(user_id)) => user_id,
+        Some(Sender::AnonymousAdmin(_)) => {
+            tracing::info!("Skipping message {} from an anonymous admin in chat {}", message.id, message.chat.id);
+            return Ok(());
+        }
+        Some(Sender::Channel(channel_id)) => {
+            return handle_channel_post(&bot, &mut redis_conn, &message, channel_id, text).await;
+        }
+        None => return Ok(()),
+    };
+    
+    // A redelivered message was handled already; counting, learning or acting
+    // on it again would move its sender's counters twice
+    if !claim_scan(&mut redis_conn, message.chat.id, message.id) {
+        tracing::info!("Message {} in chat {} was already handled, leaving state untouched", message.id, message.chat.id);
+        return Ok(());
+    }
+    
+    // Count messages per chat for its rate, see `adaptive`
+    if let Err(e) = record_message(&mut redis_conn, message.chat.id) {
+        tracing::warn!("Failed to count message for the chat's rate: {}", e);
+    }
+
+    // Count messages per user so forwards from brand-new accounts stand out
+    let mut msg_count = None;
+    if let Some(user) = message.from.as_ref() {
+        let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user.id);
+        msg_count = redis_conn.hincr::<_, _, _, i64>(&user_key, field::MSG_COUNT, 1).ok();
+        
+        // Remember recent message ids so /purge can clean up after a spammer
+        if let Err(e) = record_recent_message(&mut redis_conn, message.chat.id, user.id, message.id) {
+            tracing::warn!("Failed to record recent message: {}", e);
+        }
+    }
+    
+    // Chats with a probation window delete anything new members post before it passes
+    if let Some(user) = message.from.as_ref() {
+        let chat_id = message.chat.id;
+        if let Some(remaining) = probation_remaining(&mut redis_conn, chat_id, user.id, Utc::now().timestamp()) {
+            if is_feature_enabled(&mut redis_conn, chat_id.0, DRY_RUN_FEATURE) {
+                let alert = format!(
+                    "[dry-run] Would delete message {} from user {} in chat {} (probation, {}s left)",
+                    message.id, user.id, chat_id, remaining
+                );
+                tracing::info!("{}", alert);
+                record_dry_run_alert(&mut redis_conn, chat_id, &alert);
+            } else {
+                tracing::info!(
+                    "Deleting message {} from user {} in chat {}: on probation for {}s more.",
+                    message.id, user.id, chat_id, remaining
+                );
+                throttle_request().await;
+                let _ = bot.delete_message(chat_id, message.id).await;
+            }
+            return Ok(());
+        }
+    }
+    
+    // First messages of new members count towards raid detection, and members
+    // who joined during a lockdown are muted until it is lifted
+    if let Some(user) = message.from.as_ref() {
+        let chat_id = message.chat.id;
+        let now = Utc::now().timestamp();
+        let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user.id);
+        let join_time: Option<i64> = redis_conn.hget(&user_key, field::JOIN_TIME).unwrap_or(None);
+        let recently_joined = join_time.is_some_and(|joined| now - joined <= lockdown::WINDOW_SECS);
+        if msg_count == Some(1) && recently_joined && record_raid_event(&mut redis_conn, chat_id, now).unwrap_or(false) {
+            let _ = announce_lockdown(&bot, &mut redis_conn, chat_id).await;
+        }
+        if held_by_lockdown(&mut redis_conn, chat_id, join_time) {
+            if is_feature_enabled(&mut redis_conn, chat_id.0, DRY_RUN_FEATURE) {
+                let alert = format!(
+                    "[dry-run] Would delete message {} and mute user {} in chat {} (lockdown)",
+                    message.id, user.id, chat_id
+                );
+                tracing::info!("{}", alert);
+                record_dry_run_alert(&mut redis_conn, chat_id, &alert);
+            } else {
+                tracing::info!("Deleting message {} and muting user {} in chat {}: chat is locked down.", message.id, user.id, chat_id);
+                throttle_request().await;
+                let _ = bot.delete_message(chat_id, message.id).await;
+                if let Err(e) = mute_for_lockdown(&bot, &mut redis_conn, chat_id, user.id).await {
+                    tracing::warn!("Failed to mute user {} during the lockdown of chat {}: {}", user.id, chat_id, e);
+                }
+            }
+            return Ok(());
+        }
+    }
+    
+    // Chats may refuse forwards altogether or from members who just joined
+    if let Some(policy) = forward_blocked(&mut redis_conn, &message, Utc::now().timestamp()) {
+        let chat_id = message.chat.id;
+        if is_feature_enabled(&mut redis_conn, chat_id.0, DRY_RUN_FEATURE) {
+            let alert = format!(
+                "[dry-run] Would delete forwarded message {} from user {} in chat {} (forwards: {})",
+                message.id, user_id, chat_id, policy.as_str()
+            );
+            tracing::info!("{}", alert);
+            record_dry_run_alert(&mut redis_conn, chat_id, &alert);
+        } else {
+            tracing::info!(
+                "Deleting forwarded message {} from user {} in chat {}: forwards are {}.",
+                message.id, user_id, chat_id, policy.as_str()
+            );
+            throttle_request().await;
+            let _ = bot.delete_message(chat_id, message.id).await;
+        }
+        return Ok(());
+    }
+    
     // Store text for fuzzy training
     let text_for_fuzzy = text.clone();
     
@@ -53,21 +201,26 @@ pub async fn handle_message(
     let _trust_manager = TrustManager::new("redis://127.0.0.1/")
         .map_err(|e| format!("Failed to create trust manager: {}", e))?;
     
-    let result = scan_msg(message.clone(), text.clone()).await;
+    // Store message content in Redis for learning commands
+    if let Err(e) = store_message_content(&mut redis_conn, message.chat.id, message.id, &text) {
+        tracing::warn!("Failed to store message content in Redis: {}", e);
+    }
+    
+    let result = scan_claimed_msg(message.clone(), text.clone()).await;
     let scan_result = match result {
         Ok(scan_result) => scan_result,
         Err(e) => {
-            eprintln!("Failed to scan message: {}", e);
+            tracing::warn!("Failed to scan message: {}", e);
+            release_scan(&mut redis_conn, message.chat.id, message.id);
             return Ok(());
         }
     };
     
-    // Store message content in Redis for learning commands
-    let redis_client = redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
-    let mut redis_conn = redis_client.get_connection().expect("Failed to get Redis connection");
-    let message_key = format!("tg:message:{}", message.id.0);
-    if let Err(e) = redis_conn.set_ex::<_, _, ()>(&message_key, &text, 86400) { // 24 hour TTL
-        eprintln!("Failed to store message content in Redis: {}", e);
+    // Timing rules treat a user without a last message time as posting their first message,
+    // and TG_REPEAT starts over when identical messages are further apart than its window
+    if let Some(user) = message.from.as_ref() {
+        let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user.id);
+        let _: redis::RedisResult<()> = redis_conn.hset(&user_key, field::LAST_MSG_TIME, Utc::now().timestamp());
     }
     
     // Auto-learning integration for Bayesian classifier
@@ -75,14 +228,16 @@ pub async fn handle_message(
     if let Ok(bayes) = bayes_manager {
         let score = scan_result.score;
         let message_id = message.id.0.to_string();
+        let mut learned = false;
         // Auto-learn high-scoring messages as spam
         if score >= bayes::AUTOLEARN_SPAM_THRESHOLD {
             match bayes.learn_spam(&message_id, &text).await {
                 Ok(()) => {
-                    println!("Auto-learned message {} as spam (score: {})", message_id, score);
+                    learned = true;
+                    tracing::info!("Auto-learned message {} as spam (score: {})", message_id, score);
                 }
                 Err(e) => {
-                    eprintln!("Failed to auto-learn message {} as spam: {}", message_id, e);
+                    tracing::warn!("Failed to auto-learn message {} as spam: {}", message_id, e);
                 }
             }
         }
@@ -90,15 +245,26 @@ pub async fn handle_message(
         else if score <= bayes::AUTOLEARN_HAM_THRESHOLD {
             match bayes.learn_ham(&message_id, &text).await {
                 Ok(()) => {
-                    println!("Auto-learned message {} as ham (score: {})", message_id, score);
+                    learned = true;
+                    tracing::info!("Auto-learned message {} as ham (score: {})", message_id, score);
                 }
                 Err(e) => {
-                    eprintln!("Failed to auto-learn message {} as ham: {}", message_id, e);
+                    tracing::warn!("Failed to auto-learn message {} as ham: {}", message_id, e);
+                }
+            }
+        }
+        // Tell the chat's admins once auto-learning completes the training
+        if learned {
+            let chat_key = format!("{}{}", key::ns(key::TG_CHATS_PREFIX), message.chat.id.0);
+            let admin_chat: Option<i64> = redis_conn.hget(&chat_key, field::ADMIN_CHAT).unwrap_or(None);
+            if let Some(admin_chat) = admin_chat {
+                if let Err(e) = announce_bayes_ready(&bot, &bayes, ChatId(admin_chat)).await {
+                    tracing::warn!("Failed to announce that the classifier is ready: {}", e);
                 }
             }
         }
     } else {
-        eprintln!("Failed to create Bayes manager for auto-learning");
+        tracing::warn!("Failed to create Bayes manager for auto-learning");
     }
     
     // Check for reputation symbols and adjust score
@@ -115,48 +281,57 @@ pub async fn handle_message(
     // Adjust score based on reputation and reply context
     let mut adjusted_score = scan_result.score;
     if has_user_reputation {
-        println!("User reputation symbol detected");
+        tracing::debug!("User reputation symbol detected");
     }
     if has_bad_reputation {
         adjusted_score += 5.0; // Add penalty for bad reputation
-        println!("User has bad reputation, adjusting score by +5.0");
+        tracing::debug!("User has bad reputation, adjusting score by +5.0");
     } else if has_good_reputation {
         adjusted_score -= 1.0; // Reduce score for good reputation
-        println!("User has good reputation, adjusting score by -1.0");
+        tracing::debug!("User has good reputation, adjusting score by -1.0");
     }
     
     // Apply reply-aware filtering adjustments
     if has_reply_symbol {
-        println!("Reply symbol detected - applying contextual filtering");
+        tracing::debug!("Reply symbol detected - applying contextual filtering");
         if has_reply_bot {
             adjusted_score -= 3.0; // Highest trust for replies to bot messages
-            println!("Reply to bot message detected, adjusting score by -3.0");
+            tracing::debug!("Reply to bot message detected, adjusting score by -3.0");
         } else if has_reply_admin {
             adjusted_score -= 2.0; // Medium trust for replies to admin messages
-            println!("Reply to admin message detected, adjusting score by -2.0");
+            tracing::debug!("Reply to admin message detected, adjusting score by -2.0");
         } else if has_reply_verified {
             adjusted_score -= 1.0; // Lower trust for replies to verified users
-            println!("Reply to verified user message detected, adjusting score by -1.0");
+            tracing::debug!("Reply to verified user message detected, adjusting score by -1.0");
         } else {
             adjusted_score -= 0.5; // Small reduction for any reply
-            println!("General reply detected, adjusting score by -0.5");
+            tracing::debug!("General reply detected, adjusting score by -0.5");
         }
     }
     
-    // Determine action based on adjusted score
-    let action = if adjusted_score >= 15.0 {
-        "tg_ban"
-    } else if adjusted_score >= 10.0 {
-        "tg_delete"
-    } else if adjusted_score >= 5.0 {
-        "tg_warn"
-    } else {
-        "none"
-    };
+    // Channel forwards are a common spam vector for freshly joined accounts
+    if let Some(origin) = forward_origin_kind(&message) {
+        tracing::info!("Forwarded message detected (origin: {})", origin);
+        let penalty = forward_penalty(&mut redis_conn, &message, has_good_reputation);
+        if penalty > 0.0 {
+            adjusted_score += penalty;
+            tracing::info!("New user forwarded from a channel, adjusting score by +{}", penalty);
+        }
+    }
     
-    let user_id = message.from.unwrap().id;
     let chat_id = message.chat.id;
-    let key = format!("{}{}", key::TG_CHATS_PREFIX, chat_id);
+    
+    // Determine action based on adjusted score and the chat's action map
+    let action = resolve_action(&mut redis_conn, chat_id, adjusted_score);
+    let action = escalate_gibberish(&mut redis_conn, chat_id, &scan_result, action);
+    // Repeat offenders are removed as the chat configured, whatever the score
+    let perm_ban = scan_result
+        .symbols
+        .contains_key(symbol::TG_PERM_BAN)
+        .then(|| perm_ban_action(&mut redis_conn, chat_id));
+    let action = escalate_perm_ban(action, perm_ban);
+    
+    let key = format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id);
     let admin_chat_exists: bool = redis_conn
         .hexists(key.clone(), field::ADMIN_CHAT)
         .expect("Failed to check if admin chat exists");
@@ -166,30 +341,101 @@ pub async fn handle_message(
             .hget(key.clone(), field::ADMIN_CHAT)
             .expect("Failed to get admin chat");
     }
+    let admin_target = admin_chat.first().copied().map(ChatId);
 
+    // In dry-run mode report what would have happened, but don't enforce it
+    if is_feature_enabled(&mut redis_conn, chat_id.0, DRY_RUN_FEATURE) {
+        if action != action::NONE {
+            let mut fired: Vec<&str> = scan_result.symbols.keys().map(String::as_str).collect();
+            fired.sort_unstable();
+            let alert = format!(
+                "[dry-run] Would {} message {} from user {} in chat {} (score {:.2}). Symbols: {}",
+                action, message.id, user_id, chat_id, adjusted_score, fired.join(", ")
+            );
+            tracing::info!("{}", alert);
+            record_dry_run_alert(&mut redis_conn, chat_id, &alert);
+            
+            if admin_chat_exists && alert_enabled(&mut redis_conn, action_severity(action)) {
+                throttle(ChatId(admin_chat[0])).await;
+                bot.send_message(ChatId(admin_chat[0]), alert).await?;
+            }
+        }
+        return Ok(());
+    }
+    
     // -------------------------------------------------------------
-    // Map Rspamd actions to Telegram bot actions:
-    // - add_header (score 5.0) -> tg_warn
-    // - greylist (score 10.0) -> tg_delete
-    // - reject (score 15.0) -> tg_ban (temporary ban, permanent after 3rd)
+    // Map scores to Telegram bot actions (per-chat overridable):
+    // - score >= 5.0 (default) -> tg_warn
+    // - score >= 10.0 (default) -> tg_delete
+    // - score >= 15.0 (default) -> tg_ban (temporary ban, permanent after 3rd)
     // -------------------------------------------------------------
     
+    // Past the chat's ban rate further bans only delete the message, and the
+    // admins are alerted once per window that the chat may need locking down
+    let action = match action {
+        action::BAN => match admit_ban(&mut redis_conn, chat_id, Utc::now().timestamp_millis()) {
+            Ok(BanDecision::Allowed) => action,
+            Ok(BanDecision::Deferred { recent, limit, escalate }) => {
+                tracing::info!(
+                    "Deferring ban of user {} in chat {}: {} automated bans already this minute (limit {}).",
+                    user_id, chat_id, recent, limit
+                );
+                if escalate {
+                    let notify_text = format!(
+                        "Lockdown alert: chat {} hit its limit of {} automated bans per minute. \
+                        Further bans are deferred and their messages only deleted; consider restricting the chat.",
+                        chat_id, limit
+                    );
+                    send_alert(&bot, &mut redis_conn, chat_id, admin_target, Severity::High, notify_text).await?;
+                }
+                action::DELETE
+            }
+            Err(e) => {
+                tracing::warn!("Failed to check the ban rate of chat {}: {}", chat_id, e);
+                action
+            }
+        },
+        _ => action,
+    };
+    
+    // Chats that only report permanent bans hear about them even when the
+    // score alone calls for less than a ban
+    if perm_ban == Some(PermBanAction::Report) && action != action::BAN {
+        let notify_text = format!(
+            "User {} in chat {} reached TG_PERM_BAN (message {}); reported only, as the chat's permanent ban action is report",
+            user_id, chat_id, message.id
+        );
+        send_alert(&bot, &mut redis_conn, chat_id, admin_target, Severity::High, notify_text).await?;
+    }
+    
+    if action != action::NONE {
+        if let Err(e) = record_daily_action(&mut redis_conn, chat_id, action, Utc::now().date_naive()) {
+            tracing::warn!("Failed to record daily action for chat {}: {}", chat_id, e);
+        }
+    }
+    
     match action {
         // Temporarily mute the user (ban) and delete the offending message
         "tg_ban" => {
+            throttle_request().await;
             let _ = bot.delete_message(chat_id, message.id).await;
-            println!(
+            tracing::info!(
                 "Deleting message {} from chat {} and muting user {}.",
                 message.id, chat_id, user_id
             );
 
             // Teach fuzzy storage after deletion
             if let Err(e) = FUZZY_TRAINER.teach_fuzzy(&text_for_fuzzy).await {
-                eprintln!("Failed to teach fuzzy storage: {}", e);
+                tracing::warn!("Failed to teach fuzzy storage: {}", e);
+            }
+
+            // Domains linked from banned messages lose reputation
+            if let Err(e) = record_banned_domains(&mut redis_conn, &text) {
+                tracing::warn!("Failed to update domain reputation: {}", e);
             }
 
             // Get user key for Redis operations
-            let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+            let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user_id);
             
             // Check current ban count and handle ban logic
             let banned_q: i64 = redis_conn
@@ -206,37 +452,84 @@ pub async fn handle_message(
                 let _: () = redis_conn
                     .hset(&user_key, field::PERM_BANNED, "1")
                     .expect("Failed to set permanent ban");
-                println!("User {} permanently banned after 3rd violation.", user_id);
+                tracing::info!("User {} permanently banned after 3rd violation.", user_id);
             } else {
                 // Set temporary ban with expiration
                 let _: () = redis_conn
                     .expire(&user_key, 3600) // 1 hour ban
                     .expect("Failed to set ban expiration");
-                println!("User {} temporarily banned for 1 hour.", user_id);
+                tracing::info!("User {} temporarily banned for 1 hour.", user_id);
             }
 
+            // TG_PERM_BAN removes the user from the chat, as the chat configured
+            let outcome = match perm_ban {
+                Some(perm_ban @ (PermBanAction::Ban | PermBanAction::Kick)) => {
+                    throttle_request().await;
+                    match enforce_perm_ban(&bot, chat_id, user_id, perm_ban).await {
+                        Ok(()) => perm_ban.describe(),
+                        Err(e) => {
+                            tracing::warn!("Failed to {} user {} in chat {}: {}", perm_ban.as_str(), user_id, chat_id, e);
+                            "Banned"
+                        }
+                    }
+                }
+                _ => "Banned",
+            };
+
+            let who = match message.from.as_ref().and_then(|u| u.username.as_ref()) {
+                Some(username) => format!("@{}", username),
+                None => format!("user {}", user_id),
+            };
+            let rep: i64 = redis_conn.hget(&user_key, field::REP).unwrap_or(0);
             let notify_text = format!(
-                "Banned user {} from chat {} for spam (message {}).",
-                user_id, chat_id, message.id
+                "{} {} from chat {} for spam (message {}) — {}",
+                outcome, who, chat_id, message.id, describe_reason(&scan_result.symbols, rep)
             );
-            if admin_chat_exists {
-                bot.send_message(ChatId(admin_chat[0]), notify_text).await?;
-            } else {
-                bot.send_message(chat_id, notify_text).await?;
+            send_alert(&bot, &mut redis_conn, chat_id, admin_target, action_severity(action), notify_text).await?;
+        }
+
+        // Delete the message and make the user read-only for a while
+        "tg_mute" => {
+            throttle_request().await;
+            let _ = bot.delete_message(chat_id, message.id).await;
+
+            if let Err(e) = FUZZY_TRAINER.teach_fuzzy(&text_for_fuzzy).await {
+                tracing::warn!("Failed to teach fuzzy storage: {}", e);
             }
+
+            let minutes = mute_minutes(&mut redis_conn, chat_id);
+            throttle_request().await;
+            let notify_text = match mute_user(&bot, &mut redis_conn, chat_id, user_id, minutes).await {
+                Ok(until) => {
+                    tracing::info!("Muted user {} in chat {} until {}.", user_id, chat_id, until);
+                    let rep: i64 = redis_conn
+                        .hget(format!("{}{}", key::ns(key::TG_USERS_PREFIX), user_id), field::REP)
+                        .unwrap_or(0);
+                    format!(
+                        "Muted user {} in chat {} for {} minute(s) for spam (message {}) — {}",
+                        user_id, chat_id, minutes, message.id, describe_reason(&scan_result.symbols, rep)
+                    )
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to mute user {} in chat {}: {}", user_id, chat_id, e);
+                    format!("Deleted message {} from user {} in chat {} for spam; muting failed: {}", message.id, user_id, chat_id, e)
+                }
+            };
+            send_alert(&bot, &mut redis_conn, chat_id, admin_target, action_severity(action), notify_text).await?;
         }
 
         // Delete message but do not ban the user
         "tg_delete" => {
-            println!(
+            tracing::info!(
                 "Deleting message {} from chat {} due to spam.",
                 message.id, chat_id
             );
+            throttle_request().await;
             bot.delete_message(chat_id, message.id).await?;
             
             // Teach fuzzy storage after deletion
             if let Err(e) = FUZZY_TRAINER.teach_fuzzy(&text_for_fuzzy).await {
-                eprintln!("Failed to teach fuzzy storage: {}", e);
+                tracing::warn!("Failed to teach fuzzy storage: {}", e);
             }
             let _: () = redis_conn
                 .hincr(key.clone(), field::DELETED, 1)
@@ -246,16 +539,12 @@ pub async fn handle_message(
                 "Deleted message {} from user {} in chat {} for spam.",
                 message.id, user_id, chat_id
             );
-            if admin_chat_exists {
-                bot.send_message(ChatId(admin_chat[0]), notify_text).await?;
-            } else {
-                bot.send_message(chat_id, notify_text).await?;
-            }
+            send_alert(&bot, &mut redis_conn, chat_id, admin_target, action_severity(action), notify_text).await?;
         }
 
         // Just warn the user
         "tg_warn" => {
-            println!(
+            tracing::info!(
                 "Warning user {} in chat {} about spammy behavior.",
                 user_id, chat_id
             );
@@ -263,51 +552,164 @@ pub async fn handle_message(
                 "Warning: message {} from user {} in chat {} looks like spam.",
                 message.id, user_id, chat_id
             );
-            if admin_chat_exists {
-                bot.send_message(ChatId(admin_chat[0]), notify_text).await?;
-            } else {
-                bot.send_message(chat_id, notify_text).await?;
-            }
+            send_alert(&bot, &mut redis_conn, chat_id, admin_target, action_severity(action), notify_text).await?;
         }
 
         // Any other action: do nothing special
         _ => {
-            println!("Message is ok");
+            tracing::info!("Message is ok");
         }
     }
 
-    println!("Your score is {} and the action is {}", scan_result.score, scan_result.action);
+    tracing::info!("Your score is {} and the action is {}", scan_result.score, scan_result.action);
     if adjusted_score != scan_result.score {
-        println!("Adjusted score after reputation: {} (original: {})", adjusted_score, scan_result.score);
+        tracing::info!("Adjusted score after reputation: {} (original: {})", adjusted_score, scan_result.score);
     }
     for symbol in scan_result.symbols {
-        println!("Symbol: {} Score: {}", symbol.0, symbol.1.score);
+        tracing::debug!("Symbol: {} Score: {}", symbol.0, symbol.1.score);
     }
     Ok(())
 }
 
-async fn mute_user_for(
-    bot: Bot,
-    chat_id: ChatId,
-    user_id: UserId,
-    seconds: i64,
-) -> anyhow::Result<()> {
-    let until_ts = Utc::now() + Duration::seconds(seconds);
-    println!("Setting mute until: {}", until_ts);
+/// Moderates a post made as a channel. There is no user behind it to warn,
+/// ban or track, so only the content is scanned and the post is deleted when
+/// the chat's action map calls for a delete or ban.
+async fn handle_channel_post(
+    bot: &Bot,
+    redis_conn: &mut redis::Connection,
+    message: &Message,
+    channel_id: ChatId,
+    text: String,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let chat_id = message.chat.id;
+    let scan_result = match scan_msg(message.clone(), text).await {
+        Ok(scan_result) => scan_result,
+        Err(e) => {
+            tracing::warn!("Failed to scan channel post: {}", e);
+            return Ok(());
+        }
+    };
+    let action = resolve_action(redis_conn, chat_id, scan_result.score);
+    let action = escalate_gibberish(redis_conn, chat_id, &scan_result, action);
+    if action == action::NONE || action == action::WARN {
+        return Ok(());
+    }
 
-    let perms = ChatPermissions::empty();
-    println!("Using empty permissions for mute");
+    if is_feature_enabled(redis_conn, chat_id.0, DRY_RUN_FEATURE) {
+        let alert = format!(
+            "[dry-run] Would delete message {} posted as channel {} in chat {} (score {:.2})",
+            message.id, channel_id, chat_id, scan_result.score
+        );
+        tracing::info!("{}", alert);
+        record_dry_run_alert(redis_conn, chat_id, &alert);
+        return Ok(());
+    }
 
-    match bot.restrict_chat_member(chat_id, user_id, perms)
-        .until_date(until_ts)
-        .await {
-        Ok(_) => {
-            println!("Restrict chat member API call successful");
-            Ok(())
-        },
-        Err(e) => {
-            eprintln!("Restrict chat member API call failed: {}", e);
-            Err(anyhow::anyhow!("Failed to restrict chat member: {}", e))
+    tracing::info!("Deleting message {} posted as channel {} in chat {} due to spam.", message.id, channel_id, chat_id);
+    if let Err(e) = record_daily_action(redis_conn, chat_id, action::DELETE, Utc::now().date_naive()) {
+        tracing::warn!("Failed to record daily action for chat {}: {}", chat_id, e);
+    }
+    throttle_request().await;
+    bot.delete_message(chat_id, message.id).await?;
+    Ok(())
+}
+
+/// Appends a dry-run alert to the chat's log, keeping the newest `DRY_RUN_LOG_LIMIT`.
+fn record_dry_run_alert(conn: &mut redis::Connection, chat_id: ChatId, alert: &str) {
+    let log_key = format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0, suffix::DRY_RUN_LOG);
+    let _: redis::RedisResult<()> = redis::pipe()
+        .lpush(&log_key, alert).ignore()
+        .ltrim(&log_key, 0, DRY_RUN_LOG_LIMIT - 1).ignore()
+        .query(conn);
+}
+
+/// Picks the most severe action whose threshold `score` reaches.
+///
+/// Thresholds come from the chat's `tg:chats:<id>:actions` hash, falling back
+/// to the defaults in `config::action::ALL` for actions the chat hasn't set.
+pub fn resolve_action(redis_conn: &mut redis::Connection, chat_id: ChatId, score: f64) -> &'static str {
+    let actions_key = format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0, suffix::ACTIONS);
+    let overrides: std::collections::HashMap<String, f64> = redis_conn
+        .hgetall(&actions_key)
+        .unwrap_or_default();
+    
+    // `action::ALL` is ordered most severe first
+    action::ALL
+        .iter()
+        .find(|(name, default)| score >= overrides.get(*name).copied().unwrap_or(*default))
+        .map(|(name, _)| *name)
+        .unwrap_or(action::NONE)
+}
+
+/// Raises `action` to a delete when the message is gibberish and the chat has
+/// `GIBBERISH_DELETE_FEATURE` enabled; more severe actions are kept.
+fn escalate_gibberish(redis_conn: &mut redis::Connection, chat_id: ChatId, scan_result: &RspamdScanReply, action: &'static str) -> &'static str {
+    if (action == action::NONE || action == action::WARN)
+        && scan_result.symbols.contains_key(symbol::TG_GIBBERISH)
+        && is_feature_enabled(redis_conn, chat_id.0, GIBBERISH_DELETE_FEATURE)
+    {
+        action::DELETE
+    } else {
+        action
+    }
+}
+
+/// Stores `text` under `tg:message:<id>` so `/learnspam` and `/learnham` can find it,
+/// and the chat and time in `tg:message_info:<id>` for `/searchmessages`.
+///
+/// Entries expire after `retention::message_content()`, and only the newest
+/// `message_store::MAX_MESSAGES` are kept; older ones are deleted on insert.
+pub fn store_message_content(redis_conn: &mut redis::Connection, chat_id: ChatId, message_id: MessageId, text: &str) -> redis::RedisResult<()> {
+    let message_key = format!("{}{}", key::ns(key::TG_MESSAGE_PREFIX), message_id.0);
+    let info_key = format!("{}{}", key::ns(key::TG_MESSAGE_INFO_PREFIX), message_id.0);
+    let ttl = retention::message_content();
+    redis::pipe()
+        .set_ex(&message_key, text, ttl as u64).ignore()
+        .hset_multiple(&info_key, &[(message_store::CHAT_ID, chat_id.0), (message_store::TIMESTAMP, Utc::now().timestamp())]).ignore()
+        .expire(&info_key, ttl).ignore()
+        .lrem(key::ns(key::TG_MESSAGE_INDEX_KEY), 0, message_id.0).ignore()
+        .lpush(key::ns(key::TG_MESSAGE_INDEX_KEY), message_id.0).ignore()
+        .query::<()>(redis_conn)?;
+    
+    let evicted: Vec<i32> = redis_conn.lrange(key::ns(key::TG_MESSAGE_INDEX_KEY), message_store::MAX_MESSAGES, -1)?;
+    if !evicted.is_empty() {
+        let mut pipe = redis::pipe();
+        for id in evicted {
+            pipe.del(format!("{}{}", key::ns(key::TG_MESSAGE_PREFIX), id)).ignore();
+            pipe.del(format!("{}{}", key::ns(key::TG_MESSAGE_INFO_PREFIX), id)).ignore();
         }
+        pipe.ltrim(key::ns(key::TG_MESSAGE_INDEX_KEY), 0, message_store::MAX_MESSAGES - 1).ignore();
+        pipe.query::<()>(redis_conn)?;
+    }
+    Ok(())
+}
+
+/// Text stored for `message_id` by `store_message_content`, if it hasn't expired.
+pub fn stored_message_content(redis_conn: &mut redis::Connection, message_id: &str) -> redis::RedisResult<Option<String>> {
+    redis_conn.get(format!("{}{}", key::ns(key::TG_MESSAGE_PREFIX), message_id))
+}
+
+/// Extra score for a channel forward sent by a brand-new user.
+///
+/// Applies while the sender has at most `forward::NEW_USER_MAX_MESSAGES`
+/// messages seen and hasn't earned a good reputation; zero otherwise or when
+/// the chat has the `forwarded` feature turned off.
+pub fn forward_penalty(redis_conn: &mut redis::Connection, message: &Message, has_good_reputation: bool) -> f64 {
+    if forward_origin_kind(message) != Some(forward::ORIGIN_CHANNEL) || has_good_reputation {
+        return 0.0;
+    }
+    if !is_feature_enabled(redis_conn, message.chat.id.0, forward::FEATURE) {
+        return 0.0;
+    }
+    let Some(user) = message.from.as_ref() else {
+        return 0.0;
+    };
+    
+    let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user.id);
+    let msg_count: i64 = redis_conn.hget(&user_key, field::MSG_COUNT).unwrap_or(0);
+    if msg_count <= forward::NEW_USER_MAX_MESSAGES {
+        forward::CHANNEL_FORWARD_PENALTY
+    } else {
+        0.0
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,7 @@
+mod emoji;
 mod handle_message;
 mod scan_msg;
 
+pub use emoji::*;
 pub use handle_message::*;
 pub use scan_msg::*;
@@ -1,18 +1,82 @@
 use chrono::Utc;
-use rspamd_client::{config::Config, error::RspamdError, protocol::RspamdScanReply, scan_async};
+use rspamd_client::{config::Config, error::RspamdError, protocol::RspamdScanReply, protocol::scan::Symbol, scan_async};
 use teloxide::prelude::*;
+use teloxide::types::{MessageId, MessageOrigin};
 use get_if_addrs::{get_if_addrs, IfAddr};
 use crate::trust_manager::{TrustManager, TrustedMessageType};
 use crate::neural_manager::NeuralManager;
-use crate::config::{neural, symbol};
-use log;
+use crate::config::{diagnose, field, forward, good_standing, is_feature_enabled, key, neural, rspamd, spam_event, suffix, symbol, symbol_weight, trusted_user, DRY_RUN_FEATURE};
+use crate::spam_events::{describe_reason, record_spam_event};
+use crate::spam_trend::record_daily_symbols;
+use crate::spam_webhook::{notify_spam_event, SpamWebhookPayload};
+use crate::local_scan::local_scan;
+use crate::attachment_spam::apply_attachment_spam;
+use crate::impersonation::apply_impersonation;
+use crate::new_user_link::apply_new_user_link;
+use crate::contact_card::apply_contact_card;
+use crate::media_repeat::apply_media_repeat;
+use crate::mixed_language::apply_mixed_language;
+use crate::domain_rep::apply_url_reputation;
+use crate::suspicious_decay::apply_suspicious_decay;
 use std::collections::HashMap;
+use std::time::Duration;
+use redis::Commands;
 
 /// Scan a Telegram message: real Rspamd first, heuristic fallback.
+#[tracing::instrument(skip_all, fields(chat_id = msg.chat.id.0, user_id = message_sender(&msg).map(|sender| sender.id()), message_id = msg.id.0))]
 pub async fn scan_msg(msg: Message, text: String) -> Result<RspamdScanReply, RspamdError> {
-    let user = msg.from.as_ref().ok_or_else(|| RspamdError::ConfigError("Message has no sender".to_string()))?;
-    let user_id = user.id.to_string();
-    let user_name = user.username.as_deref().unwrap_or("anonymous").to_string();
+    scan(msg, text, false, false).await
+}
+
+/// Scan like `scan_msg` a message whose first scan the caller already claimed
+/// with `claim_scan`. Used by `handle_message`, which skips redelivered
+/// messages before updating any state of its own.
+#[tracing::instrument(name = "scan_msg", skip_all, fields(chat_id = msg.chat.id.0, user_id = message_sender(&msg).map(|sender| sender.id()), message_id = msg.id.0))]
+pub async fn scan_claimed_msg(msg: Message, text: String) -> Result<RspamdScanReply, RspamdError> {
+    scan(msg, text, false, true).await
+}
+
+/// Scan like `scan_msg` without touching any state: no reply tracking, symbol
+/// counts or reputation here, and Rspamd skips its stateful rules
+/// (`X-Telegram-Preview`). Used by `/testmessage`.
+#[tracing::instrument(skip_all, fields(chat_id = msg.chat.id.0, user_id = message_sender(&msg).map(|sender| sender.id()), message_id = msg.id.0))]
+pub async fn preview_scan(msg: Message, text: String) -> Result<RspamdScanReply, RspamdError> {
+    scan(msg, text, true, false).await
+}
+
+async fn scan(msg: Message, text: String, dry_run: bool, claimed: bool) -> Result<RspamdScanReply, RspamdError> {
+    let sender = message_sender(&msg).ok_or_else(|| RspamdError::ConfigError("Message has no sender".to_string()))?;
+    // Posts made as a chat have no user history to check or update, so like
+    // previews they only get the stateless rules
+    let user = match sender {
+        Sender::User(_) => msg.from.as_ref(),
+        Sender::AnonymousAdmin(_) | Sender::Channel(_) => None,
+    };
+    let mut conn = match redis::Client::open("redis://127.0.0.1/").and_then(|client| client.get_connection()) {
+        Ok(conn) => Some(conn),
+        Err(e) => {
+            tracing::warn!("Failed to connect to Redis to scan message {} in chat {}: {}", msg.id, msg.chat.id, e);
+            None
+        }
+    };
+    let dry_run = dry_run || user.is_none();
+    // A message scanned again (a redelivered update, a re-scan) gets the same
+    // result but must not move its sender's reputation or counters twice.
+    // Rspamd's stateful rules can't run without writing, so their part of the
+    // result comes from the first scan. Without Redis there is no telling,
+    // so the scan writes nothing either.
+    let rescan = !dry_run && !claimed && conn.as_mut().is_none_or(|conn| !claim_scan(conn, msg.chat.id, msg.id));
+    let first_reply = if rescan {
+        conn.as_mut().and_then(|conn| scanned_reply(conn, msg.chat.id, msg.id))
+    } else {
+        None
+    };
+    let user_id = sender.id().to_string();
+    let user_name = user
+        .and_then(|u| u.username.as_deref())
+        .or_else(|| msg.sender_chat.as_ref().and_then(|c| c.username()))
+        .unwrap_or("anonymous")
+        .to_string();
     let chat_id = msg.chat.id;
     let msg_id = msg.id.0.to_string();
     let date = Utc::now().to_rfc2822();
@@ -23,20 +87,26 @@ pub async fn scan_msg(msg: Message, text: String) -> Result<RspamdScanReply, Rsp
     let trust_manager = TrustManager::new("redis://127.0.0.1/")
         .unwrap_or_else(|_| panic!("Failed to create trust manager"));
     
-    // Check if this is a reply to a trusted message
-    let in_reply_to_header = if let Some(reply_to_message) = msg.reply_to_message() {
-        // Check rate limiting for replies
-        if !trust_manager.can_reply_to_trusted(user.id).await.unwrap_or(true) {
+    // Check if this is a reply to a trusted message (tracking it records state)
+    let in_reply_to_header = if dry_run || rescan {
+        String::new()
+    } else if let (Some(user), Some(reply_to_message)) = (user, msg.reply_to_message()) {
+        if !trust_manager.is_enabled().unwrap_or(true) {
+            // Reply-aware filtering is switched off, treat as regular reply
+            format!("<reply.{}.{}@telegram.com>", reply_to_message.id.0, chat_id.0)
+        } else if !trust_manager.can_reply_to_trusted(user.id).await.unwrap_or(true) {
             // User is rate limited, treat as regular message
             format!("<rate_limited.{}.{}@telegram.com>", reply_to_message.id.0, chat_id.0)
         } else {
             // Check if the replied-to message is trusted
-            match trust_manager.is_trusted(reply_to_message.id).await {
-                Ok(true) => {
-                    // Get metadata to determine the type of trusted message
-                    if let Ok(Some(metadata)) = trust_manager.get_trusted_metadata(reply_to_message.id).await {
+            match trust_manager.trusted_parent(reply_to_message).await {
+                Ok(Some(metadata)) => {
+                    if metadata.is_self_reply(user.id) {
+                        // Replying to your own trusted message earns no reduction and isn't tracked
+                        format!("<self_reply.{}.{}@telegram.com>", reply_to_message.id.0, chat_id.0)
+                    } else {
                         // Check for spam patterns in reply content
-                        let spam_patterns = trust_manager.check_reply_spam_patterns(&text, user.id).await.unwrap_or_default();
+                        let spam_patterns = trust_manager.check_reply_spam_patterns(&text, user.id, chat_id).await.unwrap_or_default();
                         
                         // Calculate adjusted score reduction
                         let score_reduction = trust_manager.calculate_score_reduction(&metadata, user.id).await.unwrap_or(metadata.message_type.score_reduction());
@@ -55,11 +125,9 @@ pub async fn scan_msg(msg: Message, text: String) -> Result<RspamdScanReply, Rsp
                             // Include spam pattern info in header
                             format!("<spam_reply.{}.{}.{}@telegram.com>", reply_to_message.id.0, chat_id.0, spam_patterns.join(","))
                         }
-                    } else {
-                        format!("<unknown.{}.{}@telegram.com>", reply_to_message.id.0, chat_id.0)
                     }
                 }
-                Ok(false) => {
+                Ok(None) => {
                     // Not a trusted message, but still a reply
                     format!("<reply.{}.{}@telegram.com>", reply_to_message.id.0, chat_id.0)
                 }
@@ -74,6 +142,11 @@ pub async fn scan_msg(msg: Message, text: String) -> Result<RspamdScanReply, Rsp
         String::new()
     };
     
+    let standing = match user {
+        Some(user) => good_standing_tier(&trust_manager, user.id).await,
+        None => None,
+    };
+    
     // Build email headers
     let mut headers = format!(
         "Received: from {ip} ({ip}) by localhost.localdomain with HTTP; {date}\r\n\
@@ -98,6 +171,31 @@ pub async fn scan_msg(msg: Message, text: String) -> Result<RspamdScanReply, Rsp
         headers.push_str(&format!("In-Reply-To: {}\r\n", in_reply_to_header));
     }
     
+    // Add X-Telegram-Forward header if this is a forward
+    if let Some(origin) = forward_origin_kind(&msg) {
+        headers.push_str(&format!("X-Telegram-Forward: {}\r\n", origin));
+    }
+
+    // Let Rspamd match the username against `re:` entries of the user lists
+    if let Some(username) = user.and_then(|u| u.username.as_deref()) {
+        headers.push_str(&format!("X-Telegram-Username: {}\r\n", username));
+    }
+
+    // Let Rspamd build its keys in the bot's namespace
+    if let Some(namespace) = key::namespace() {
+        headers.push_str(&format!("X-Telegram-Namespace: {}\r\n", namespace));
+    }
+    
+    // Give members in good standing headroom before TG_SUSPICIOUS
+    if let Some(tier) = standing {
+        headers.push_str(&format!("X-Telegram-Rep-Credit: {}\r\n", tier.credit));
+    }
+    
+    // Ask Rspamd to skip rules that update user history
+    if dry_run || rescan {
+        headers.push_str("X-Telegram-Preview: 1\r\n");
+    }
+    
     // Complete email format with headers and content
     let email = format!(
         "{headers}\
@@ -110,10 +208,474 @@ pub async fn scan_msg(msg: Message, text: String) -> Result<RspamdScanReply, Rsp
         text = text.replace("\n", "\r\n")
     );
     
+    let mut reply = match first_reply {
+        Some(reply) => reply,
+        None => match scan_with_retry(email).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                tracing::warn!("Rspamd scan failed, scanning message {} in chat {} locally: {}", msg_id, chat_id, e);
+                let Some(conn) = conn.as_mut() else {
+                    return Err(e);
+                };
+                local_scan(conn, chat_id, &text)
+            }
+        },
+    };
+    if !dry_run && !rescan {
+        if let Some(conn) = conn.as_mut() {
+            remember_scan_reply(conn, chat_id, msg.id, &reply);
+        }
+    }
+    // Only reads the reputation store, so previews get it too
+    if let Some(conn) = conn.as_mut() {
+        if let Err(e) = apply_content_checks(conn, &mut reply, &msg, &text) {
+            tracing::warn!("Failed to check domain reputation, attachments, impersonation, new user links, contact cards or mixed scripts for chat {}: {}", chat_id, e);
+        }
+    }
+    let Some(user) = user.filter(|_| !dry_run) else {
+        return Ok(reply);
+    };
+    // Counts the post, so unlike the checks above previews skip it and rescans only read the count
+    if let Some(conn) = conn.as_mut() {
+        if let Err(e) = apply_media_repeat(conn, &mut reply, &msg, !rescan) {
+            tracing::warn!("Failed to check repeated media for chat {}: {}", chat_id, e);
+        }
+    }
+    let trusted_user = apply_sender_reductions(&trust_manager, &mut reply, user.id, standing).await;
+    if rescan {
+        tracing::info!("Message {} in chat {} was already scanned, leaving state untouched", msg.id, chat_id);
+        return Ok(reply);
+    }
+    record_symbol_counts(chat_id, &reply);
+    // Members vouched for by an admin don't accumulate bad reputation
+    if !trusted_user {
+        record_reputation_delta(chat_id, user.id, &reply);
+    }
+    record_suspicious_decay(chat_id, user.id, &reply);
+    record_spam_events(chat_id, user.id, &reply);
+    Ok(reply)
+}
+
+/// Marks message `message_id` of `chat_id` as scanned for
+/// `rspamd::SCANNED_TTL_SECS`. Returns false if it already was.
+pub fn first_scan(conn: &mut redis::Connection, chat_id: ChatId, message_id: MessageId) -> redis::RedisResult<bool> {
+    let claimed: Option<String> = redis::cmd("SET")
+        .arg(scanned_key(chat_id, message_id))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(rspamd::SCANNED_TTL_SECS)
+        .query(conn)?;
+    Ok(claimed.is_some())
+}
+
+fn scanned_key(chat_id: ChatId, message_id: MessageId) -> String {
+    key::ns(format!("{}{}:{}", key::TG_SCANNED_PREFIX, chat_id.0, message_id.0))
+}
+
+/// Claims the first scan of message `message_id` of `chat_id`, see
+/// `first_scan`. Returns false if it was already scanned, and on Redis errors,
+/// so a message never updates state twice.
+pub fn claim_scan(conn: &mut redis::Connection, chat_id: ChatId, message_id: MessageId) -> bool {
+    first_scan(conn, chat_id, message_id).unwrap_or_else(|e| {
+        tracing::warn!("Failed to check whether message {} in chat {} was scanned: {}", message_id, chat_id, e);
+        false
+    })
+}
+
+/// Drops the claim of a scan that failed, so a redelivery of the message is
+/// scanned instead of skipped.
+pub fn release_scan(conn: &mut redis::Connection, chat_id: ChatId, message_id: MessageId) {
+    let result: redis::RedisResult<()> = conn.del(scanned_key(chat_id, message_id));
+    if let Err(e) = result {
+        tracing::warn!("Failed to release the scan of message {} in chat {}: {}", message_id, chat_id, e);
+    }
+}
+
+/// Keeps the Rspamd reply of message `message_id`'s first scan for its
+/// rescans, in the key `first_scan` set.
+fn remember_scan_reply(conn: &mut redis::Connection, chat_id: ChatId, message_id: MessageId, reply: &RspamdScanReply) {
+    let Ok(json) = serde_json::to_string(reply) else {
+        return;
+    };
+    let result: redis::RedisResult<()> = redis::cmd("SET")
+        .arg(scanned_key(chat_id, message_id))
+        .arg(json)
+        .arg("XX")
+        .arg("KEEPTTL")
+        .query(conn);
+    if let Err(e) = result {
+        tracing::warn!("Failed to keep the scan of message {} in chat {}: {}", message_id, chat_id, e);
+    }
+}
+
+/// The Rspamd reply `remember_scan_reply` kept for message `message_id`, or
+/// `None` while its first scan is still running or once it expired.
+fn scanned_reply(conn: &mut redis::Connection, chat_id: ChatId, message_id: MessageId) -> Option<RspamdScanReply> {
+    let json: Option<String> = conn.get(scanned_key(chat_id, message_id)).ok()?;
+    serde_json::from_str(&json?).ok()
+}
+
+/// Adds the symbols of the checks that only read state: domain reputation,
+/// attachments, impersonation, new user links, contact cards and mixed scripts.
+fn apply_content_checks(conn: &mut redis::Connection, reply: &mut RspamdScanReply, msg: &Message, text: &str) -> redis::RedisResult<()> {
+    apply_url_reputation(conn, reply, text)?;
+    apply_attachment_spam(conn, reply, msg)?;
+    apply_impersonation(conn, reply, msg)?;
+    apply_new_user_link(conn, reply, msg, text, Utc::now().timestamp())?;
+    apply_contact_card(conn, reply, msg, Utc::now().timestamp())?;
+    apply_mixed_language(conn, reply, msg, text)
+}
+
+/// Base URL of the Rspamd worker that scans messages (`RSPAMD_URL`).
+pub fn rspamd_url() -> String {
+    std::env::var("RSPAMD_URL").unwrap_or_else(|_| "http://localhost:11333".to_string())
+}
+
+/// Why a scan request to Rspamd failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanFailure {
+    /// Rspamd couldn't be reached.
+    Connection,
+    /// Rspamd didn't answer within `RSPAMD_TIMEOUT`.
+    Timeout,
+    /// Rspamd answered with a non-success HTTP status.
+    HttpStatus(u16),
+    /// Rspamd's reply wasn't valid scan JSON.
+    Parse,
+    /// Anything else, e.g. a malformed `RSPAMD_URL`.
+    Other,
+}
+
+impl ScanFailure {
+    /// Classifies an error of `scan_async`. Timeouts never reach here, see
+    /// `scan_with_retry`.
+    pub fn classify(error: &RspamdError) -> Self {
+        match error {
+            // `scan_async` wraps the status error once more: "HTTP request failed: Status: 500 ..."
+            RspamdError::HttpError(message) => match message.split_once("Status: ").map(|(_, status)| status) {
+                Some(status) => status
+                    .split_whitespace()
+                    .next()
+                    .and_then(|code| code.parse().ok())
+                    .map_or(ScanFailure::Other, ScanFailure::HttpStatus),
+                None => ScanFailure::Connection,
+            },
+            RspamdError::IOError(_) => ScanFailure::Connection,
+            RspamdError::SerdeError(_) => ScanFailure::Parse,
+            RspamdError::ConfigError(_) | RspamdError::ParseError(_) | RspamdError::Unknown => ScanFailure::Other,
+        }
+    }
+
+    /// Field of `key::TG_RSPAMD_ERRORS_KEY` counting this failure.
+    pub fn label(&self) -> &'static str {
+        match self {
+            ScanFailure::Connection => "connection",
+            ScanFailure::Timeout => "timeout",
+            ScanFailure::HttpStatus(_) => "http_status",
+            ScanFailure::Parse => "parse",
+            ScanFailure::Other => "other",
+        }
+    }
+
+    /// Whether another attempt may succeed: Rspamd being down, slow or failing
+    /// internally, but not a reply it will send again or a bad request.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ScanFailure::Connection | ScanFailure::Timeout => true,
+            ScanFailure::HttpStatus(status) => *status >= 500,
+            ScanFailure::Parse | ScanFailure::Other => false,
+        }
+    }
+}
+
+/// Counts a failed scan in `key::TG_RSPAMD_ERRORS_KEY`.
+fn record_scan_failure(failure: ScanFailure) {
+    let result = redis::Client::open("redis://127.0.0.1/")
+        .and_then(|client| client.get_connection())
+        .and_then(|mut conn| conn.hincr::<_, _, _, i64>(key::ns(key::TG_RSPAMD_ERRORS_KEY), failure.label(), 1));
+    if let Err(e) = result {
+        tracing::warn!("Failed to count {} Rspamd error: {}", failure.label(), e);
+    }
+}
+
+/// Sends `email` to Rspamd, retrying failed attempts with exponential backoff.
+///
+/// Each attempt is bounded by `RSPAMD_TIMEOUT` seconds and up to `RSPAMD_RETRIES`
+/// retries follow the first, defaulting to `config::rspamd::SCAN_TIMEOUT_SECS` and
+/// `SCAN_RETRIES`. Only transient failures are retried. Returns the last error
+/// once every attempt has failed, after logging its class and counting it.
+async fn scan_with_retry(email: String) -> Result<RspamdScanReply, RspamdError> {
+    let timeout = std::env::var("RSPAMD_TIMEOUT")
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .filter(|secs| *secs > 0.0)
+        .unwrap_or(rspamd::SCAN_TIMEOUT_SECS);
+    let retries = std::env::var("RSPAMD_RETRIES")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .unwrap_or(rspamd::SCAN_RETRIES);
+    // The client's own retry sleeps a whole timeout between attempts, so retry
+    // here instead. Its timeout would look like any other request error, so each
+    // attempt is bounded here and the client's trails it.
     let options = Config::builder()
-        .base_url(std::env::var("RSPAMD_URL").unwrap_or_else(|_| "http://localhost:11333".to_string()))
+        .base_url(rspamd_url())
+        .timeout(timeout + rspamd::CLIENT_TIMEOUT_SLACK_SECS)
+        .retries(1)
         .build();
-    scan_async(&options, email).await
+
+    let mut backoff = Duration::from_millis(rspamd::SCAN_BACKOFF_MS);
+    let mut attempt = 0;
+    loop {
+        let (failure, e) = match tokio::time::timeout(Duration::from_secs_f64(timeout), scan_async(&options, email.clone())).await {
+            Ok(Ok(reply)) => return Ok(reply),
+            Ok(Err(e)) => (ScanFailure::classify(&e), e),
+            Err(_) => (ScanFailure::Timeout, RspamdError::HttpError(format!("no reply within {}s", timeout))),
+        };
+        if failure.is_transient() && attempt < retries {
+            attempt += 1;
+            tracing::warn!("Rspamd scan failed (attempt {} of {}), retrying in {:?}: {}", attempt, retries + 1, backoff, e);
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            continue;
+        }
+        match failure {
+            ScanFailure::Connection => tracing::warn!("Rspamd unreachable at {}: {}", rspamd_url(), e),
+            ScanFailure::Timeout => tracing::warn!("Rspamd timed out after {}s", timeout),
+            ScanFailure::HttpStatus(status) => tracing::error!("Rspamd answered HTTP {}: {}", status, e),
+            ScanFailure::Parse => tracing::error!("Rspamd returned a malformed scan reply: {}", e),
+            ScanFailure::Other => tracing::error!("Rspamd scan failed: {}", e),
+        }
+        record_scan_failure(failure);
+        return Err(e);
+    }
+}
+
+/// Logs a dashboard spam event if a ban/suspicious symbol fired, and pushes
+/// it to the spam webhook when one is configured.
+fn record_spam_events(chat_id: ChatId, user_id: UserId, reply: &RspamdScanReply) {
+    let fired: Vec<&str> = spam_event::SYMBOLS
+        .iter()
+        .copied()
+        .filter(|name| reply.symbols.contains_key(*name))
+        .collect();
+    if fired.is_empty() {
+        return;
+    }
+
+    let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user_id);
+    let result = redis::Client::open("redis://127.0.0.1/")
+        .and_then(|client| client.get_connection())
+        .and_then(|mut conn| {
+            let rep: i64 = conn.hget(&user_key, field::REP).unwrap_or(0);
+            let reason = describe_reason(&reply.symbols, rep);
+            record_spam_event(&mut conn, chat_id, user_id, &fired, &reason)
+        });
+    if let Err(e) = result {
+        tracing::warn!("Failed to record spam event for chat {}: {}", chat_id, e);
+    }
+
+    let mut symbols: Vec<String> = reply.symbols.keys().cloned().collect();
+    symbols.sort();
+    notify_spam_event(SpamWebhookPayload {
+        chat_id: chat_id.0,
+        user_id: user_id.0,
+        events: fired.iter().map(|name| name.to_string()).collect(),
+        symbols,
+        score: reply.score,
+        timestamp: Utc::now().timestamp(),
+    });
+}
+
+/// Adds `TG_TRUSTED_USER` and its score reduction to a scan of a trusted user's message.
+fn apply_trusted_user(reply: &mut RspamdScanReply) {
+    reply.score += trusted_user::SCORE_REDUCTION;
+    reply.symbols.insert(
+        symbol::TG_TRUSTED_USER.to_string(),
+        Symbol {
+            name: symbol::TG_TRUSTED_USER.to_string(),
+            score: trusted_user::SCORE_REDUCTION,
+            metric_score: trusted_user::SCORE_REDUCTION,
+            description: Some("Sender is trusted by an admin".to_string()),
+            options: None,
+        },
+    );
+}
+
+/// Adds `TG_TRUSTED_USER` to the scan of a user trusted by an admin, or else
+/// `TG_GOOD_STANDING` if they reached a tier; returns whether they are trusted.
+async fn apply_sender_reductions(
+    trust_manager: &TrustManager,
+    reply: &mut RspamdScanReply,
+    user_id: UserId,
+    standing: Option<&'static good_standing::Tier>,
+) -> bool {
+    let trusted_user = trust_manager.is_trusted_user(user_id).await.unwrap_or(false);
+    if trusted_user {
+        apply_trusted_user(reply);
+    } else if let Some(tier) = standing {
+        apply_good_standing(reply, tier);
+    }
+    trusted_user
+}
+
+/// The good standing tier `user_id`'s reputation reaches, if any. Reputation
+/// that can't be read counts as no standing.
+async fn good_standing_tier(trust_manager: &TrustManager, user_id: UserId) -> Option<&'static good_standing::Tier> {
+    let reputation = trust_manager.get_user_reputation(user_id).await.ok()?;
+    // Negative reputation is good
+    good_standing::tier(-reputation)
+}
+
+/// Adds `TG_GOOD_STANDING` and the tier's score reduction to a scan of a member in good standing.
+fn apply_good_standing(reply: &mut RspamdScanReply, tier: &good_standing::Tier) {
+    reply.score += tier.score;
+    reply.symbols.insert(
+        symbol::TG_GOOD_STANDING.to_string(),
+        Symbol {
+            name: symbol::TG_GOOD_STANDING.to_string(),
+            score: tier.score,
+            metric_score: tier.score,
+            description: Some("Sender's good reputation outweighs their bad one".to_string()),
+            options: None,
+        },
+    );
+}
+
+/// Weight of every weighted content symbol, from the `tg:symbol_weights`
+/// hash, falling back to `config::symbol_weight::ALL`.
+fn symbol_weights(conn: &mut redis::Connection) -> HashMap<&'static str, i64> {
+    let overrides: HashMap<String, i64> = conn
+        .hgetall(key::ns(symbol_weight::WEIGHTS_KEY))
+        .unwrap_or_default();
+    symbol_weight::ALL
+        .iter()
+        .map(|(name, default)| (*name, overrides.get(*name).copied().unwrap_or(*default)))
+        .collect()
+}
+
+/// Sum of the weights of the weighted content symbols in the scan result,
+/// so several weak symbols compound.
+pub fn reputation_delta(conn: &mut redis::Connection, reply: &RspamdScanReply) -> i64 {
+    symbol_weights(conn)
+        .into_iter()
+        .filter(|(name, _)| reply.symbols.contains_key(*name))
+        .map(|(_, weight)| weight)
+        .sum()
+}
+
+/// A symbol of a `ScanTrace`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TracedSymbol {
+    pub name: String,
+    pub score: f64,
+    /// What the symbol adds to the sender's bad reputation, 0 if it isn't weighted.
+    pub weight: i64,
+}
+
+/// Why a message scored as it did, see `trace_scan`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanTrace {
+    /// Symbols that fired, highest score first.
+    pub symbols: Vec<TracedSymbol>,
+    /// Whitelist, trust and reply reductions among them, largest first.
+    pub reductions: Vec<TracedSymbol>,
+    pub score: f64,
+    /// What the scan adds to the sender's bad reputation; trusted users get none.
+    pub reputation_delta: i64,
+}
+
+impl ScanTrace {
+    fn new(conn: &mut redis::Connection, reply: &RspamdScanReply, trusted_user: bool) -> Self {
+        let weights = symbol_weights(conn);
+        let (mut reductions, mut symbols): (Vec<TracedSymbol>, Vec<TracedSymbol>) = reply
+            .symbols
+            .values()
+            .map(|s| TracedSymbol {
+                name: s.name.clone(),
+                score: s.score,
+                weight: weights.get(s.name.as_str()).copied().unwrap_or(0),
+            })
+            .partition(|s| s.score < 0.0 || diagnose::REDUCTIONS.contains(&s.name.as_str()));
+        symbols.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+        reductions.sort_by(|a, b| a.score.total_cmp(&b.score).then_with(|| a.name.cmp(&b.name)));
+        let reputation_delta = if trusted_user { 0 } else { symbols.iter().map(|s| s.weight).sum() };
+        ScanTrace { symbols, reductions, score: reply.score, reputation_delta }
+    }
+}
+
+/// Scans like `preview_scan`, recording nothing, but with the sender's own
+/// trust reductions applied as a real scan would, and explains the result.
+/// Used by `/diagnose`.
+pub async fn trace_scan(msg: Message, text: String) -> Result<ScanTrace, RspamdError> {
+    let mut reply = scan(msg.clone(), text, true, false).await?;
+    let mut trusted_user = false;
+    if let (Some(Sender::User(user_id)), Ok(trust_manager)) = (message_sender(&msg), TrustManager::new("redis://127.0.0.1/")) {
+        let standing = good_standing_tier(&trust_manager, user_id).await;
+        trusted_user = apply_sender_reductions(&trust_manager, &mut reply, user_id, standing).await;
+    }
+    let mut conn = redis::Client::open("redis://127.0.0.1/")
+        .and_then(|client| client.get_connection())
+        .map_err(|e| RspamdError::ConfigError(e.to_string()))?;
+    Ok(ScanTrace::new(&mut conn, &reply, trusted_user))
+}
+
+/// Add the weighted reputation delta to the sender's bad reputation, unless the chat is in dry-run.
+fn record_reputation_delta(chat_id: ChatId, user_id: UserId, reply: &RspamdScanReply) {
+    let result = redis::Client::open("redis://127.0.0.1/")
+        .and_then(|client| client.get_connection())
+        .and_then(|mut conn| {
+            let delta = reputation_delta(&mut conn, reply);
+            if delta == 0 || is_feature_enabled(&mut conn, chat_id.0, DRY_RUN_FEATURE) {
+                return Ok(());
+            }
+            let reputation_key = format!("{}{}", key::ns(key::TG_REPUTATION_USER_PREFIX), user_id.0);
+            redis::pipe()
+                .hincr(&reputation_key, "bad", delta).ignore()
+                .expire(&reputation_key, symbol_weight::REPUTATION_TTL).ignore()
+                .query::<()>(&mut conn)
+        });
+    if let Err(e) = result {
+        tracing::warn!("Failed to update reputation for user {}: {}", user_id, e);
+    }
+}
+
+/// Take the suspicious decay off the sender's `rep` after a benign message, unless the chat is in dry-run.
+fn record_suspicious_decay(chat_id: ChatId, user_id: UserId, reply: &RspamdScanReply) {
+    let result = redis::Client::open("redis://127.0.0.1/")
+        .and_then(|client| client.get_connection())
+        .and_then(|mut conn| {
+            if is_feature_enabled(&mut conn, chat_id.0, DRY_RUN_FEATURE) {
+                return Ok(None);
+            }
+            apply_suspicious_decay(&mut conn, user_id, &reply.symbols)
+        });
+    match result {
+        Ok(Some(rep)) => tracing::info!("Suspicious user {} behaved, rep down to {}", user_id, rep),
+        Ok(None) => {}
+        Err(e) => tracing::warn!("Failed to apply suspicious decay for user {}: {}", user_id, e),
+    }
+}
+
+/// Increment the per-chat trigger counters, all-time and for today, for every symbol in the scan result.
+fn record_symbol_counts(chat_id: ChatId, reply: &RspamdScanReply) {
+    if reply.symbols.is_empty() {
+        return;
+    }
+    let counts_key = format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0, suffix::SYMBOL_COUNTS);
+    let mut pipe = redis::pipe();
+    for name in reply.symbols.keys() {
+        pipe.hincr(&counts_key, name, 1).ignore();
+    }
+    let result = redis::Client::open("redis://127.0.0.1/")
+        .and_then(|client| client.get_connection())
+        .and_then(|mut conn| {
+            pipe.query::<()>(&mut conn)?;
+            record_daily_symbols(&mut conn, chat_id, reply.symbols.keys(), Utc::now().date_naive())
+        });
+    if let Err(e) = result {
+        tracing::warn!("Failed to record symbol counts for chat {}: {}", chat_id, e);
+    }
 }
 
 /// Enhanced scan function that also returns reply information and advanced metrics
@@ -130,10 +692,18 @@ pub async fn scan_msg_with_advanced_info(msg: Message, text: String) -> Result<(
     let trust_manager = TrustManager::new("redis://127.0.0.1/")
         .unwrap_or_else(|_| panic!("Failed to create trust manager"));
     
+    let standing = good_standing_tier(&trust_manager, user.id).await;
+    
     // Check if this is a reply to a trusted message
     let (in_reply_to_header, reply_type, spam_patterns) = if let Some(reply_to_message) = msg.reply_to_message() {
-        // Check rate limiting for replies
-        if !trust_manager.can_reply_to_trusted(user.id).await.unwrap_or(true) {
+        if !trust_manager.is_enabled().unwrap_or(true) {
+            // Reply-aware filtering is switched off, treat as regular reply
+            (
+                format!("<reply.{}.{}@telegram.com>", reply_to_message.id.0, chat_id.0),
+                Some("disabled".to_string()),
+                Vec::new()
+            )
+        } else if !trust_manager.can_reply_to_trusted(user.id).await.unwrap_or(true) {
             // User is rate limited, treat as regular message
             (
                 format!("<rate_limited.{}.{}@telegram.com>", reply_to_message.id.0, chat_id.0),
@@ -142,12 +712,18 @@ pub async fn scan_msg_with_advanced_info(msg: Message, text: String) -> Result<(
             )
         } else {
             // Check if the replied-to message is trusted
-            match trust_manager.is_trusted(reply_to_message.id).await {
-                Ok(true) => {
-                    // Get metadata to determine the type of trusted message
-                    if let Ok(Some(metadata)) = trust_manager.get_trusted_metadata(reply_to_message.id).await {
+            match trust_manager.trusted_parent(reply_to_message).await {
+                Ok(Some(metadata)) => {
+                    if metadata.is_self_reply(user.id) {
+                        // Replying to your own trusted message earns no reduction and isn't tracked
+                        (
+                            format!("<self_reply.{}.{}@telegram.com>", reply_to_message.id.0, chat_id.0),
+                            Some("self_reply".to_string()),
+                            Vec::new()
+                        )
+                    } else {
                         // Check for spam patterns in reply content
-                        let spam_patterns = trust_manager.check_reply_spam_patterns(&text, user.id).await.unwrap_or_default();
+                        let spam_patterns = trust_manager.check_reply_spam_patterns(&text, user.id, chat_id).await.unwrap_or_default();
                         
                         // Calculate adjusted score reduction
                         let score_reduction = trust_manager.calculate_score_reduction(&metadata, user.id).await.unwrap_or(metadata.message_type.score_reduction());
@@ -179,15 +755,9 @@ pub async fn scan_msg_with_advanced_info(msg: Message, text: String) -> Result<(
                         };
                         
                         (header, reply_type, spam_patterns)
-                    } else {
-                        (
-                            format!("<unknown.{}.{}@telegram.com>", reply_to_message.id.0, chat_id.0),
-                            Some("unknown".to_string()),
-                            Vec::new()
-                        )
                     }
                 }
-                Ok(false) => {
+                Ok(None) => {
                     // Not a trusted message, but still a reply
                     (
                         format!("<reply.{}.{}@telegram.com>", reply_to_message.id.0, chat_id.0),
@@ -233,6 +803,21 @@ pub async fn scan_msg_with_advanced_info(msg: Message, text: String) -> Result<(
         headers.push_str(&format!("In-Reply-To: {}\r\n", in_reply_to_header));
     }
     
+    // Add X-Telegram-Forward header if this is a forward
+    if let Some(origin) = forward_origin_kind(&msg) {
+        headers.push_str(&format!("X-Telegram-Forward: {}\r\n", origin));
+    }
+
+    // Let Rspamd build its keys in the bot's namespace
+    if let Some(namespace) = key::namespace() {
+        headers.push_str(&format!("X-Telegram-Namespace: {}\r\n", namespace));
+    }
+    
+    // Give members in good standing headroom before TG_SUSPICIOUS
+    if let Some(tier) = standing {
+        headers.push_str(&format!("X-Telegram-Rep-Credit: {}\r\n", tier.credit));
+    }
+    
     // Complete email format with headers and content
     let email = format!(
         "{headers}\
@@ -245,11 +830,12 @@ pub async fn scan_msg_with_advanced_info(msg: Message, text: String) -> Result<(
         text = text.replace("\n", "\r\n")
     );
     
-    let options = Config::builder()
-        .base_url(std::env::var("RSPAMD_URL").unwrap_or_else(|_| "http://localhost:11333".to_string()))
-        .build();
-    
-    let scan_result = scan_async(&options, email).await?;
+    let mut scan_result = scan_with_retry(email).await?;
+    if trust_manager.is_trusted_user(user.id).await.unwrap_or(false) {
+        apply_trusted_user(&mut scan_result);
+    } else if let Some(tier) = standing {
+        apply_good_standing(&mut scan_result, tier);
+    }
     
     // Process neural network results if available
     let neural_manager = NeuralManager::new();
@@ -257,19 +843,19 @@ pub async fn scan_msg_with_advanced_info(msg: Message, text: String) -> Result<(
         // Check for neural network symbols
         if manager.has_neural_symbols(&scan_result) {
             if let Some(classification) = manager.get_neural_classification(&scan_result) {
-                log::info!("Neural network classification: {}", classification);
+                tracing::info!("Neural network classification: {}", classification);
                 
                 // Extract features for analysis
                 let features = manager.extract_features(&scan_result);
-                log::debug!("Neural features: {:?}", features);
+                tracing::debug!("Neural features: {:?}", features);
                 
                 // Check confidence
                 if let Some(confidence) = manager.get_neural_confidence(&scan_result) {
                     if confidence >= neural::CONFIDENCE_THRESHOLD {
-                        log::info!("High confidence neural classification: {} (confidence: {:.2})", 
+                        tracing::info!("High confidence neural classification: {} (confidence: {:.2})", 
                                   classification, confidence);
                     } else {
-                        log::info!("Low confidence neural classification: {} (confidence: {:.2})", 
+                        tracing::info!("Low confidence neural classification: {} (confidence: {:.2})", 
                                   classification, confidence);
                     }
                 }
@@ -280,6 +866,50 @@ pub async fn scan_msg_with_advanced_info(msg: Message, text: String) -> Result<(
     Ok((scan_result, reply_type, spam_patterns))
 }
 
+/// Kind of origin a forwarded message came from, as sent in `X-Telegram-Forward`.
+pub fn forward_origin_kind(msg: &Message) -> Option<&'static str> {
+    msg.forward_origin().map(|origin| match origin {
+        MessageOrigin::Channel { .. } => forward::ORIGIN_CHANNEL,
+        MessageOrigin::User { .. } => forward::ORIGIN_USER,
+        MessageOrigin::HiddenUser { .. } => forward::ORIGIN_HIDDEN_USER,
+        MessageOrigin::Chat { .. } => forward::ORIGIN_CHAT,
+    })
+}
+
+/// Who a message was posted as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sender {
+    /// A regular user account.
+    User(UserId),
+    /// A chat admin posting anonymously as the chat itself.
+    AnonymousAdmin(ChatId),
+    /// A channel posting into the chat, e.g. a linked channel's auto-forward
+    /// or a member posting as their channel.
+    Channel(ChatId),
+}
+
+impl Sender {
+    /// Id sent in `X-Telegram-User`: the user's id, or the (negative) id of the
+    /// chat the message was posted as.
+    pub fn id(&self) -> i64 {
+        match self {
+            Sender::User(user_id) => user_id.0 as i64,
+            Sender::AnonymousAdmin(chat_id) | Sender::Channel(chat_id) => chat_id.0,
+        }
+    }
+}
+
+/// Classifies who posted `msg`. `sender_chat` takes precedence over `from`,
+/// which Telegram fills with a placeholder bot for posts made as a chat.
+pub fn message_sender(msg: &Message) -> Option<Sender> {
+    match (&msg.sender_chat, &msg.from) {
+        (Some(sender_chat), _) if sender_chat.id == msg.chat.id => Some(Sender::AnonymousAdmin(sender_chat.id)),
+        (Some(sender_chat), _) => Some(Sender::Channel(sender_chat.id)),
+        (None, Some(user)) => Some(Sender::User(user.id)),
+        (None, None) => None,
+    }
+}
+
 /// Helper function to check if a message has reply symbols (for testing)
 pub async fn check_reply_symbols(msg: &Message) -> HashMap<String, f64> {
     let mut reply_symbols = HashMap::new();
@@ -288,23 +918,32 @@ pub async fn check_reply_symbols(msg: &Message) -> HashMap<String, f64> {
         let trust_manager = TrustManager::new("redis://127.0.0.1/")
             .unwrap_or_else(|_| panic!("Failed to create trust manager"));
         
+        if !trust_manager.is_enabled().unwrap_or(true) {
+            return reply_symbols;
+        }
+        
         // Check if the replied-to message is trusted
-        if let Ok(true) = trust_manager.is_trusted(reply_to_message.id).await {
-            if let Ok(Some(metadata)) = trust_manager.get_trusted_metadata(reply_to_message.id).await {
-                // Add reply symbols based on message type
-                reply_symbols.insert(symbol::TG_REPLY.to_string(), metadata.message_type.score_reduction());
-                match metadata.message_type {
-                    TrustedMessageType::Bot => {
-                        reply_symbols.insert(symbol::TG_REPLY_BOT.to_string(), -3.0);
-                    },
-                    TrustedMessageType::Admin => {
-                        reply_symbols.insert(symbol::TG_REPLY_ADMIN.to_string(), -2.0);
-                    },
-                    TrustedMessageType::Verified => {
-                        reply_symbols.insert(symbol::TG_REPLY_VERIFIED.to_string(), -1.0);
-                    },
+        if let Ok(Some(metadata)) = trust_manager.trusted_parent(reply_to_message).await {
+            // Self-replies are flagged but earn no reduction
+            if let Some(Sender::User(user_id)) = message_sender(msg) {
+                if metadata.is_self_reply(user_id) {
+                    reply_symbols.insert(symbol::TG_REPLY_SELF.to_string(), 0.0);
+                    return reply_symbols;
                 }
             }
+            // Add reply symbols based on message type
+            reply_symbols.insert(symbol::TG_REPLY.to_string(), metadata.message_type.score_reduction());
+            match metadata.message_type {
+                TrustedMessageType::Bot => {
+                    reply_symbols.insert(symbol::TG_REPLY_BOT.to_string(), -3.0);
+                },
+                TrustedMessageType::Admin => {
+                    reply_symbols.insert(symbol::TG_REPLY_ADMIN.to_string(), -2.0);
+                },
+                TrustedMessageType::Verified => {
+                    reply_symbols.insert(symbol::TG_REPLY_VERIFIED.to_string(), -1.0);
+                },
+            }
         }
     }
     
@@ -0,0 +1,150 @@
+//! Admin impersonation detection behind `TG_IMPERSONATION`.
+//!
+//! Impersonators copy an admin's display name with lookalike characters
+//! ("Αdmin" with a Greek Alpha) or invisible ones to trick members. Each
+//! chat keeps its admins' display names in `tg:chats:<id>:admin_names`; a
+//! sender who isn't one of them is flagged when the skeleton of their name
+//! (see `skeleton`) equals an admin's.
+
+use std::collections::HashMap;
+
+use redis::{Commands, RedisResult};
+use rspamd_client::protocol::{scan::Symbol, RspamdScanReply};
+use teloxide::types::{ChatId, Message, UserId};
+
+use crate::config::{impersonation, is_feature_enabled, key, suffix, symbol, IMPERSONATION_FEATURE};
+
+/// Characters that render as nothing: soft hyphen, zero-width and
+/// directional marks, variation selectors, Hangul fillers and the BOM.
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}' | '\u{034F}' | '\u{061C}' | '\u{115F}' | '\u{1160}' | '\u{17B4}' | '\u{17B5}' | '\u{180E}'
+            | '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2060}'..='\u{206F}' | '\u{3164}'
+            | '\u{FE00}'..='\u{FE0F}' | '\u{FEFF}' | '\u{FFA0}'
+    ) || ('\u{0300}'..='\u{036F}').contains(&c)
+}
+
+/// Latin letter an uppercase Greek or Cyrillic letter looks like. Checked
+/// before lowercasing, since e.g. Greek "Η" looks like "H" but "η" like "n".
+fn confusable_upper(c: char) -> Option<char> {
+    Some(match c {
+        'Α' | 'А' => 'a',
+        'Β' | 'В' => 'b',
+        'Ε' | 'Е' => 'e',
+        'Ζ' => 'z',
+        'Η' | 'Н' => 'h',
+        'Ι' | 'І' | 'Ӏ' => 'l',
+        'Κ' | 'К' => 'k',
+        'Μ' | 'М' => 'm',
+        'Ν' => 'n',
+        'Ο' | 'О' => 'o',
+        'Ρ' | 'Р' => 'p',
+        'С' => 'c',
+        'Τ' | 'Т' => 't',
+        'Υ' | 'У' => 'y',
+        'Χ' | 'Х' => 'x',
+        'Ј' => 'j',
+        'Ѕ' => 's',
+        _ => return None,
+    })
+}
+
+/// Latin letter a lowercase character looks like, folding `i`, `l`, `1` and
+/// `|` together since many fonts barely tell them apart.
+fn confusable_lower(c: char) -> char {
+    match c {
+        'а' | 'α' => 'a',
+        'в' | 'β' => 'b',
+        'с' | 'ϲ' => 'c',
+        'ԁ' => 'd',
+        'е' | 'ε' => 'e',
+        'һ' => 'h',
+        'i' | 'і' | 'ι' | 'ӏ' | 'l' | '1' | '|' | 'ı' => 'l',
+        'ј' => 'j',
+        'κ' | 'к' => 'k',
+        'м' => 'm',
+        'η' => 'n',
+        'о' | 'ο' | '0' => 'o',
+        'р' | 'ρ' => 'p',
+        'ѕ' => 's',
+        'т' | 'τ' => 't',
+        'υ' => 'u',
+        'ν' => 'v',
+        'ԝ' => 'w',
+        'х' | 'χ' => 'x',
+        'у' => 'y',
+        // Fullwidth ASCII
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).map(|c| confusable_lower(c.to_ascii_lowercase())).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// `name` reduced to what it looks like: invisible characters, whitespace
+/// and separators dropped, lookalike letters mapped to Latin, lowercased,
+/// and "rn" read as "m". Names that look alike get equal skeletons.
+pub fn skeleton(name: &str) -> String {
+    let folded: String = name
+        .chars()
+        .filter(|c| !is_invisible(*c) && !c.is_whitespace() && !matches!(c, '_' | '-' | '.' | '·'))
+        .flat_map(|c| match confusable_upper(c) {
+            Some(latin) => vec![latin],
+            None => c.to_lowercase().map(confusable_lower).collect(),
+        })
+        .collect();
+    folded.replace("rn", "m")
+}
+
+fn admin_names_key(chat_id: ChatId) -> String {
+    format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0, suffix::ADMIN_NAMES)
+}
+
+/// Remembers `name` as the display name of `chat_id`'s admin `user_id`.
+pub fn record_admin_name(conn: &mut redis::Connection, chat_id: ChatId, user_id: UserId, name: &str) -> RedisResult<()> {
+    conn.hset(admin_names_key(chat_id), user_id.0, name)
+}
+
+/// Forgets `user_id`'s display name once they stop being an admin of `chat_id`.
+pub fn forget_admin_name(conn: &mut redis::Connection, chat_id: ChatId, user_id: UserId) -> RedisResult<()> {
+    conn.hdel(admin_names_key(chat_id), user_id.0)
+}
+
+/// The display name of the `chat_id` admin that `name` mimics, if `user_id`
+/// isn't an admin themselves.
+pub fn impersonated_admin(conn: &mut redis::Connection, chat_id: ChatId, user_id: UserId, name: &str) -> RedisResult<Option<String>> {
+    let admins: HashMap<u64, String> = conn.hgetall(admin_names_key(chat_id))?;
+    if admins.contains_key(&user_id.0) {
+        return Ok(None);
+    }
+    let sender = skeleton(name);
+    if sender.is_empty() {
+        return Ok(None);
+    }
+    Ok(admins.into_values().find(|admin| skeleton(admin) == sender))
+}
+
+/// Adds `TG_IMPERSONATION` to the scan of `msg` when its sender's display
+/// name mimics a chat admin's and the feature is on for the chat.
+pub fn apply_impersonation(conn: &mut redis::Connection, reply: &mut RspamdScanReply, msg: &Message) -> RedisResult<()> {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    if !is_feature_enabled(conn, msg.chat.id.0, IMPERSONATION_FEATURE) {
+        return Ok(());
+    }
+    let Some(admin) = impersonated_admin(conn, msg.chat.id, user.id, &user.full_name())? else {
+        return Ok(());
+    };
+    reply.score += impersonation::SCORE;
+    reply.symbols.insert(
+        symbol::TG_IMPERSONATION.to_string(),
+        Symbol {
+            name: symbol::TG_IMPERSONATION.to_string(),
+            score: impersonation::SCORE,
+            metric_score: impersonation::SCORE,
+            description: Some("Display name mimics a chat admin's".to_string()),
+            options: Some(vec![admin]),
+        },
+    );
+    Ok(())
+}
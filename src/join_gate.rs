@@ -0,0 +1,73 @@
+//! Per-chat join timing windows behind `TG_FIRST_FAST`, `TG_FIRST_SLOW`,
+//! `TG_NEW_USER_LINK`, probation and the `restrict_new` forward policy.
+//!
+//! Windows live in the `tg:chats:<id>:join_gate` hash and fall back to the
+//! defaults in `config::join_gate::ALL`; `telegram_simple.lua` reads the same hash.
+
+use std::collections::HashMap;
+
+use redis::Commands;
+use teloxide::types::{ChatId, UserId};
+
+use crate::config::{field, join_gate, key, suffix, symbol};
+
+/// Join timing windows of a single chat, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JoinWindows {
+    pub first_fast: i64,
+    pub first_slow: i64,
+    pub probation: i64,
+    pub new_user_link: i64,
+    pub new_user_forward: i64,
+}
+
+impl JoinWindows {
+    /// Symbol fired by a first message sent `age` seconds after joining, if any.
+    pub fn first_message_symbol(&self, age: i64) -> Option<&'static str> {
+        if age < self.first_fast {
+            Some(symbol::TG_FIRST_FAST)
+        } else if age > self.first_slow {
+            Some(symbol::TG_FIRST_SLOW)
+        } else {
+            None
+        }
+    }
+}
+
+fn default_window(name: &str) -> i64 {
+    join_gate::ALL
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, default)| *default)
+        .unwrap_or(0)
+}
+
+/// Reads the chat's join timing windows, using the defaults for unset ones.
+pub fn join_windows(conn: &mut redis::Connection, chat_id: ChatId) -> JoinWindows {
+    let gate_key = format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0, suffix::JOIN_GATE);
+    let stored: HashMap<String, i64> = conn.hgetall(&gate_key).unwrap_or_default();
+    let window = |name: &str| stored.get(name).copied().unwrap_or_else(|| default_window(name));
+    JoinWindows {
+        first_fast: window(join_gate::FIRST_FAST),
+        first_slow: window(join_gate::FIRST_SLOW),
+        probation: window(join_gate::PROBATION),
+        new_user_link: window(join_gate::NEW_USER_LINK),
+        new_user_forward: window(join_gate::NEW_USER_FORWARD),
+    }
+}
+
+/// Seconds `user_id` still has to wait before posting in `chat_id`, or `None`
+/// if the chat has no probation or the user's join time is unknown or past it.
+pub fn probation_remaining(conn: &mut redis::Connection, chat_id: ChatId, user_id: UserId, now: i64) -> Option<i64> {
+    let probation = join_windows(conn, chat_id).probation;
+    if probation <= 0 {
+        return None;
+    }
+    let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user_id.0);
+    let join_time: i64 = conn.hget(&user_key, field::JOIN_TIME).unwrap_or(0);
+    if join_time == 0 {
+        return None;
+    }
+    let remaining = probation - (now - join_time);
+    (remaining > 0).then_some(remaining)
+}
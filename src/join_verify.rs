@@ -0,0 +1,173 @@
+//! Welcome challenge for new members, behind the opt-in `join_verify` feature.
+//!
+//! A member joining a chat with the feature on is muted and greeted with a
+//! button; pressing it within `join_verify::TIMEOUT_SECS` proves they're not
+//! a bot and lifts the mute, otherwise the periodic task kicks them. Pending
+//! challenges live in the `tg:pending_verify` sorted set as
+//! `<chat_id>:<user_id>`, scored by their deadline.
+
+use std::error::Error;
+
+use chrono::Utc;
+use redis::{Commands, RedisResult};
+use teloxide::prelude::*;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageId, User};
+
+use crate::config::{join_verify, key};
+use crate::mutes::{clear_mute, mute_user, unmute_user};
+
+/// Prefix of the challenge button's callback data: `verify:<chat_id>:<user_id>`
+pub const VERIFY_CALLBACK: &str = "verify:";
+
+fn member(chat_id: ChatId, user_id: UserId) -> String {
+    format!("{}:{}", chat_id.0, user_id.0)
+}
+
+/// Records that `user_id` owes `chat_id`'s challenge until `deadline` (Unix
+/// timestamp), posted as `message_id`.
+pub fn record_challenge(
+    conn: &mut redis::Connection,
+    chat_id: ChatId,
+    user_id: UserId,
+    deadline: i64,
+    message_id: Option<MessageId>,
+) -> RedisResult<()> {
+    let member = member(chat_id, user_id);
+    let mut pipe = redis::pipe();
+    pipe.zadd(key::ns(key::TG_PENDING_VERIFY_KEY), &member, deadline).ignore();
+    if let Some(message_id) = message_id {
+        pipe.hset(key::ns(key::TG_VERIFY_MESSAGES_KEY), &member, message_id.0).ignore();
+    }
+    pipe.query(conn)
+}
+
+/// Whether `user_id` still owes `chat_id`'s challenge.
+pub fn is_pending(conn: &mut redis::Connection, chat_id: ChatId, user_id: UserId) -> RedisResult<bool> {
+    let deadline: Option<i64> = conn.zscore(key::ns(key::TG_PENDING_VERIFY_KEY), member(chat_id, user_id))?;
+    Ok(deadline.is_some())
+}
+
+/// Marks `user_id`'s challenge in `chat_id` as settled (passed, timed out, or
+/// moot because they left). Returns the challenge message's id if one was
+/// pending, so it can be cleaned up.
+pub fn clear_pending(conn: &mut redis::Connection, chat_id: ChatId, user_id: UserId) -> RedisResult<Option<Option<MessageId>>> {
+    let member = member(chat_id, user_id);
+    let (removed, message_id): (i64, Option<i32>) = redis::pipe()
+        .atomic()
+        .zrem(key::ns(key::TG_PENDING_VERIFY_KEY), &member)
+        .hget(key::ns(key::TG_VERIFY_MESSAGES_KEY), &member)
+        .hdel(key::ns(key::TG_VERIFY_MESSAGES_KEY), &member).ignore()
+        .query(conn)?;
+    Ok((removed > 0).then_some(message_id.map(MessageId)))
+}
+
+/// Challenges whose deadline is at or before `now`.
+pub fn expired_challenges(conn: &mut redis::Connection, now: i64) -> RedisResult<Vec<(ChatId, UserId)>> {
+    let members: Vec<String> = conn.zrangebyscore(key::ns(key::TG_PENDING_VERIFY_KEY), "-inf", now)?;
+    Ok(members
+        .iter()
+        .filter_map(|member| {
+            let (chat, user) = member.split_once(':')?;
+            Some((ChatId(chat.parse().ok()?), UserId(user.parse().ok()?)))
+        })
+        .collect())
+}
+
+/// Mutes `user` in `chat_id` and posts the welcome challenge.
+pub async fn challenge_new_member(
+    bot: &Bot,
+    conn: &mut redis::Connection,
+    chat_id: ChatId,
+    user: &User,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    mute_user(bot, conn, chat_id, user.id, join_verify::MUTE_MINUTES).await?;
+    let deadline = Utc::now().timestamp() + join_verify::TIMEOUT_SECS;
+    // Recorded before posting, so a failed post still ends in a kick
+    record_challenge(conn, chat_id, user.id, deadline, None)?;
+    let keyboard = InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+        "I'm not a bot",
+        format!("{}{}:{}", VERIFY_CALLBACK, chat_id.0, user.id.0),
+    )]]);
+    let challenge = bot
+        .send_message(
+            chat_id,
+            format!(
+                "Welcome, {}! Press the button below within {} minute(s) to be able to post.",
+                user.full_name(),
+                join_verify::TIMEOUT_SECS / 60
+            ),
+        )
+        .reply_markup(keyboard)
+        .await?;
+    record_challenge(conn, chat_id, user.id, deadline, Some(challenge.id))?;
+    Ok(())
+}
+
+/// Called when a challenge button is pressed; only the greeted member can pass it
+pub async fn verify_handler(bot: Bot, query: CallbackQuery) -> ResponseResult<()> {
+    let parsed = query
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix(VERIFY_CALLBACK))
+        .and_then(|rest| rest.split_once(':'))
+        .and_then(|(chat, user)| Some((ChatId(chat.parse().ok()?), UserId(user.parse().ok()?))));
+    let Some((chat_id, user_id)) = parsed else {
+        return Ok(());
+    };
+    if query.from.id != user_id {
+        bot.answer_callback_query(query.id.clone()).text("This button is for the new member.").await?;
+        return Ok(());
+    }
+
+    let redis_client =
+        redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut redis_conn = redis_client
+        .get_connection()
+        .expect("Failed to get Redis connection");
+    if let Ok(Some(_)) = clear_pending(&mut redis_conn, chat_id, user_id) {
+        if let Err(e) = unmute_user(&bot, &mut redis_conn, chat_id, user_id).await {
+            log::warn!("Failed to unmute verified user {} in chat {}: {}", user_id, chat_id, e);
+        }
+    }
+    bot.answer_callback_query(query.id.clone()).text("Thanks, you can post now.").await?;
+    if let Some(challenge) = query.message.as_ref() {
+        let _ = bot.delete_message(challenge.chat().id, challenge.id()).await;
+    }
+    Ok(())
+}
+
+/// Kicks every member whose challenge ran out, removing their challenge
+/// message. Kicked members may join again. Returns how many were kicked.
+pub async fn kick_unverified(bot: &Bot, conn: &mut redis::Connection, now: i64) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let mut kicked = 0;
+    for (chat_id, user_id) in expired_challenges(conn, now)? {
+        let Some(message_id) = clear_pending(conn, chat_id, user_id)? else {
+            continue;
+        };
+        if let Some(message_id) = message_id {
+            let _ = bot.delete_message(chat_id, message_id).await;
+        }
+        // A ban followed by an unban removes the member without keeping them out
+        let kick = async {
+            bot.ban_chat_member(chat_id, user_id).await?;
+            bot.unban_chat_member(chat_id, user_id).only_if_banned(true).await
+        };
+        match kick.await {
+            Ok(_) => {
+                kicked += 1;
+                // The kick already happened; a stale mute only lingers until it expires
+                if let Err(e) = clear_mute(conn, user_id) {
+                    log::warn!("Failed to clear the mute of kicked user {} in chat {}: {}", user_id, chat_id, e);
+                }
+            }
+            Err(e) => log::warn!("Failed to kick unverified user {} from chat {}: {}", user_id, chat_id, e),
+        }
+    }
+    Ok(kicked)
+}
+
+/// Runs `kick_unverified` for the periodic task.
+pub async fn kick_expired_challenges(bot: &Bot) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+    kick_unverified(bot, &mut conn, Utc::now().timestamp()).await
+}
@@ -5,7 +5,49 @@ pub mod fuzzy_trainer;
 pub mod trust_manager;
 pub mod migration;
 pub mod ban_manager;
+pub mod ban_rate;
+pub mod perm_ban;
+pub mod mutes;
+pub mod user_notes;
+pub mod reputation_decay;
+pub mod reputation_update;
+pub mod suspicious_decay;
+pub mod script_filter;
+pub mod lists;
+pub mod lookalike;
+pub mod domain_rep;
+pub mod shortener;
+pub mod attachment_spam;
+pub mod impersonation;
+pub mod new_user_link;
+pub mod contact_card;
+pub mod media_repeat;
+pub mod mixed_language;
+pub mod forward_policy;
+pub mod char_flood;
+pub mod caps;
+pub mod repeat;
+pub mod gibberish;
+pub mod local_scan;
+pub mod join_gate;
+pub mod join_verify;
+pub mod lockdown;
+pub mod flood;
+pub mod adaptive;
+pub mod spam_events;
+pub mod spam_webhook;
+pub mod spam_trend;
+pub mod daily_summary;
+pub mod notifications;
+pub mod outgoing;
+pub mod config_backup;
+pub mod api;
 pub mod admin_handlers;
+/// The admin panel's permission model, which also gates the admin commands.
+pub mod admin_panel {
+    pub mod config;
+    pub mod permissions;
+}
 pub mod handlers;
 
 use anyhow::Result;
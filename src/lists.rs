@@ -0,0 +1,184 @@
+//! White- and blacklists, either global or scoped to a single chat.
+//!
+//! Global lists live in `tg:whitelist:*` and `tg:blacklist:*`; a chat's own
+//! entries live in `tg:chats:<id>:whitelist:*` and `tg:chats:<id>:blacklist:*`.
+//! Detection (`telegram_simple.lua`) checks the global list and the list of
+//! the chat the message was posted in, so a chat-scoped entry has no effect
+//! elsewhere.
+//!
+//! An entry prefixed with `re:` is a regex rather than a literal: in a user
+//! list it matches usernames (e.g. `re:_official$`), in a word list words.
+
+use std::collections::HashSet;
+
+use redis::{Commands, RedisResult};
+use regex::Regex;
+use teloxide::types::ChatId;
+
+use crate::config::{key, suffix};
+
+/// Prefix of a list entry that is a regex pattern.
+pub const PATTERN_PREFIX: &str = "re:";
+
+/// The regex of a `re:` entry; `None` for literals and invalid patterns.
+pub fn pattern(entry: &str) -> Option<Regex> {
+    entry
+        .strip_prefix(PATTERN_PREFIX)
+        .filter(|pattern| !pattern.is_empty())
+        .and_then(|pattern| Regex::new(pattern).ok())
+}
+
+/// What a list holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    /// Numeric user ids.
+    User,
+    /// Words.
+    Word,
+}
+
+impl Entry {
+    /// Whether `entry` can be listed: a valid `re:` pattern, or else a user id
+    /// for users and anything non-empty for words.
+    pub fn is_valid(&self, entry: &str) -> bool {
+        if entry.starts_with(PATTERN_PREFIX) {
+            return pattern(entry).is_some();
+        }
+        match self {
+            Entry::User => entry.parse::<u64>().is_ok(),
+            Entry::Word => !entry.is_empty(),
+        }
+    }
+}
+
+/// A white- or blacklist of users or words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct List {
+    /// Key of the global set.
+    pub global_key: &'static str,
+    /// Suffix of the chat-scoped set under `tg:chats:<id>`.
+    pub chat_suffix: &'static str,
+    /// What the list holds.
+    pub entry: Entry,
+}
+
+/// Whitelisted user ids.
+pub const WHITELIST_USERS: List = List { global_key: key::TG_WHITELIST_USER_KEY, chat_suffix: suffix::WHITELIST_USERS, entry: Entry::User };
+/// Whitelisted words.
+pub const WHITELIST_WORDS: List = List { global_key: key::TG_WHITELIST_WORD_KEY, chat_suffix: suffix::WHITELIST_WORDS, entry: Entry::Word };
+/// Blacklisted user ids.
+pub const BLACKLIST_USERS: List = List { global_key: key::TG_BLACKLIST_USER_KEY, chat_suffix: suffix::BLACKLIST_USERS, entry: Entry::User };
+/// Blacklisted words.
+pub const BLACKLIST_WORDS: List = List { global_key: key::TG_BLACKLIST_WORD_KEY, chat_suffix: suffix::BLACKLIST_WORDS, entry: Entry::Word };
+
+/// Result of importing a file into a list.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ImportOutcome {
+    /// Entries added to the list
+    pub added: usize,
+    /// Entries that were already listed
+    pub duplicates: usize,
+    /// Lines that aren't a valid entry (e.g. a user line that isn't a number)
+    pub invalid: usize,
+}
+
+impl List {
+    /// Key of the set for `chat`, or of the global set when `chat` is `None`.
+    pub fn key(&self, chat: Option<ChatId>) -> String {
+        match chat {
+            Some(chat_id) => format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0, self.chat_suffix),
+            None => key::ns(self.global_key),
+        }
+    }
+
+    /// How many of `members` are listed globally or for `chat_id`, literally or
+    /// through a pattern; mirrors `count_listed_words` in `telegram_simple.lua`.
+    pub fn count_listed(&self, conn: &mut redis::Connection, chat_id: ChatId, members: &[&str]) -> RedisResult<usize> {
+        let listed: HashSet<String> = conn.sunion(&[self.key(None), self.key(Some(chat_id))])?;
+        let patterns: Vec<Regex> = listed.iter().filter_map(|entry| pattern(entry)).collect();
+        Ok(members
+            .iter()
+            .filter(|member| listed.contains(**member) || patterns.iter().any(|p| p.is_match(member)))
+            .count())
+    }
+
+    /// Whether `literal` is listed globally or for `chat_id`, or `name` matches
+    /// one of the list's patterns. Literals are a plain `SISMEMBER`; only the
+    /// `re:` entries are scanned.
+    pub fn is_listed(&self, conn: &mut redis::Connection, chat_id: ChatId, literal: &str, name: Option<&str>) -> RedisResult<bool> {
+        let keys = [self.key(None), self.key(Some(chat_id))];
+        for list_key in &keys {
+            if conn.sismember(list_key, literal)? {
+                return Ok(true);
+            }
+        }
+        let Some(name) = name.filter(|name| !name.is_empty()) else {
+            return Ok(false);
+        };
+        for list_key in &keys {
+            let entries: Vec<String> = conn
+                .sscan_match::<_, _, String>(list_key, format!("{}*", PATTERN_PREFIX))?
+                .collect();
+            if entries.iter().filter_map(|entry| pattern(entry)).any(|p| p.is_match(name)) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Adds `entry` to the list for `chat` (the global list when `None`).
+    /// Returns whether it was newly added.
+    pub fn add(&self, conn: &mut redis::Connection, chat: Option<ChatId>, entry: &str) -> RedisResult<bool> {
+        conn.sadd(self.key(chat), entry)
+    }
+
+    /// Entries of the list for `chat` (the global list when `None`), sorted.
+    pub fn entries(&self, conn: &mut redis::Connection, chat: Option<ChatId>) -> RedisResult<Vec<String>> {
+        let mut entries: Vec<String> = conn.smembers(self.key(chat))?;
+        entries.sort_unstable();
+        Ok(entries)
+    }
+
+    /// Adds every line of `content` to the list for `chat` (the global list
+    /// when `None`). Blank lines are ignored.
+    pub fn import(&self, conn: &mut redis::Connection, chat: Option<ChatId>, content: &str) -> RedisResult<ImportOutcome> {
+        let mut outcome = ImportOutcome::default();
+        let mut entries = Vec::new();
+        for line in content.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if self.entry.is_valid(line) {
+                entries.push(line);
+            } else {
+                outcome.invalid += 1;
+            }
+        }
+        if entries.is_empty() {
+            return Ok(outcome);
+        }
+
+        let list_key = self.key(chat);
+        let mut pipe = redis::pipe();
+        for entry in &entries {
+            pipe.sadd(&list_key, *entry);
+        }
+        let added: Vec<i64> = pipe.query(conn)?;
+        outcome.added = added.iter().filter(|added| **added == 1).count();
+        outcome.duplicates = entries.len() - outcome.added;
+        Ok(outcome)
+    }
+}
+
+/// Renders `lists` for `chat` (the global lists when `None`) as text: a
+/// `# <title>` line per list followed by its entries, one per line, so each
+/// section can be fed back to `/importwhitelist`.
+pub fn export(conn: &mut redis::Connection, lists: &[(&str, List)], chat: Option<ChatId>) -> RedisResult<String> {
+    let mut text = String::new();
+    for (title, list) in lists {
+        text.push_str(&format!("# {}\n", title));
+        for entry in list.entries(conn, chat)? {
+            text.push_str(&entry);
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    Ok(text)
+}
@@ -0,0 +1,116 @@
+//! Local-only content checks used when Rspamd can't be reached.
+//!
+//! Mirrors the stateless content rules of `telegram_simple.lua` (`TG_LINK_SPAM`,
+//! `TG_LOOKALIKE_DOMAIN`, `TG_MENTIONS`, `TG_CAPS`, `TG_CHAR_FLOOD`) with the
+//! thresholds and trusted domains from Redis, so a message still gets a score
+//! while Rspamd is down. History-based rules (flood, repeat, timing) and the
+//! classifiers are Rspamd-only and don't fire here.
+
+use std::collections::{HashMap, HashSet};
+
+use once_cell::sync::Lazy;
+use redis::Commands;
+use regex::Regex;
+use rspamd_client::protocol::{scan::Symbol, RspamdScanReply};
+use teloxide::types::ChatId;
+
+use crate::caps::case_counts;
+use crate::char_flood::char_runs;
+use crate::config::{is_feature_enabled, key, local_scan, symbol, threshold};
+use crate::lookalike::is_lookalike_host;
+
+/// Messages shorter than this (in bytes) skip the caps check, as in `tg_caps_cb`.
+const MIN_CAPS_LENGTH: usize = 20;
+
+static LINK_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"https?://([^/\s:]+)[^\s]*").unwrap());
+static MENTION_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"@[A-Za-z0-9_]+").unwrap());
+
+/// Scans `text` without Rspamd; the reply carries the symbols that fired and their summed score.
+pub fn local_scan(conn: &mut redis::Connection, chat_id: ChatId, text: &str) -> RspamdScanReply {
+    let thresholds: HashMap<String, f64> = conn.hgetall(key::ns(key::TG_THRESHOLDS_KEY)).unwrap_or_default();
+    let limit = |name: &str, default: f64| thresholds.get(name).copied().unwrap_or(default);
+    let trusted_domains: HashSet<String> = conn.smembers(key::ns(key::TG_TRUSTED_DOMAINS_KEY)).unwrap_or_default();
+    let is_trusted = |host: &str| {
+        let host = host.to_lowercase();
+        trusted_domains.iter().any(|d| host == *d || host.ends_with(&format!(".{}", d)))
+    };
+
+    let mut fired = Vec::new();
+
+    let hosts: Vec<&str> = LINK_REGEX
+        .captures_iter(text)
+        .filter_map(|c| c.get(1).map(|m| m.as_str()))
+        .filter(|host| !is_trusted(host))
+        .collect();
+    if hosts.len() as f64 > limit(threshold::LINK_SPAM_MAX, threshold::DEFAULT_LINK_SPAM_MAX) {
+        fired.push(symbol::TG_LINK_SPAM);
+    }
+    if hosts.iter().any(|host| is_lookalike_host(host)) {
+        fired.push(symbol::TG_LOOKALIKE_DOMAIN);
+    }
+
+    if MENTION_REGEX.find_iter(text).count() as f64 > limit(threshold::MENTIONS_MAX, threshold::DEFAULT_MENTIONS_MAX) {
+        fired.push(symbol::TG_MENTIONS);
+    }
+
+    let long_enough = text.trim().chars().count() as f64
+        >= limit(threshold::MIN_CONTENT_LENGTH, threshold::DEFAULT_MIN_CONTENT_LENGTH);
+    let cases = case_counts(text);
+    if text.len() >= MIN_CAPS_LENGTH
+        && long_enough
+        && cases.letters() > 0
+        && cases.caps_ratio() >= limit(threshold::CAPS_RATIO, threshold::DEFAULT_CAPS_RATIO)
+    {
+        fired.push(symbol::TG_CAPS);
+    }
+
+    if is_feature_enabled(conn, chat_id.0, "char_flood")
+        && char_runs(text).is_flood(
+            limit(threshold::CHAR_RUN_MAX, threshold::DEFAULT_CHAR_RUN_MAX),
+            limit(threshold::CHAR_FLOOD_RATIO, threshold::DEFAULT_CHAR_FLOOD_RATIO),
+        )
+    {
+        fired.push(symbol::TG_CHAR_FLOOD);
+    }
+
+    let symbols: HashMap<String, Symbol> = fired
+        .into_iter()
+        .map(|name| {
+            let score = symbol_score(name);
+            let symbol = Symbol {
+                name: name.to_string(),
+                score,
+                metric_score: score,
+                description: Some("Local check, Rspamd unavailable".to_string()),
+                options: None,
+            };
+            (name.to_string(), symbol)
+        })
+        .collect();
+
+    RspamdScanReply {
+        is_skipped: false,
+        score: symbols.values().map(|s| s.score).sum(),
+        required_score: 0.0,
+        action: "no action".to_string(),
+        thresholds: HashMap::new(),
+        symbols,
+        messages: HashMap::new(),
+        urls: Vec::new(),
+        emails: Vec::new(),
+        message_id: String::new(),
+        time_real: 0.0,
+        milter: None,
+        filename: String::new(),
+        scan_time: 0.0,
+    }
+}
+
+/// Score of a locally checked symbol, as configured in `scores.d`.
+fn symbol_score(name: &str) -> f64 {
+    local_scan::SCORES
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, score)| *score)
+        .unwrap_or(0.0)
+}
@@ -0,0 +1,121 @@
+//! Chat-wide lockdown triggered by raid detection.
+//!
+//! Joins and first messages of new members are recorded in the
+//! `tg:chats:<id>:raid_events` sorted set; `lockdown::RAID_EVENTS` of them
+//! within `lockdown::WINDOW_SECS` count as a raid and lock the chat down.
+//! While locked down, members joining after the lockdown started are muted
+//! (and remembered in `tg:chats:<id>:lockdown_muted`) until an admin lifts it
+//! with `/lockdown off`, which unmutes them again.
+
+use std::error::Error;
+
+use redis::{Commands, RedisResult};
+use teloxide::prelude::*;
+
+use crate::config::{field, key, lockdown, mute, suffix};
+use crate::mutes::{mute_user, unmute_user};
+use crate::notifications::{send_alert, Severity};
+
+fn chat_key(chat_id: ChatId) -> String {
+    format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0)
+}
+
+fn muted_key(chat_id: ChatId) -> String {
+    format!("{}{}", chat_key(chat_id), suffix::LOCKDOWN_MUTED)
+}
+
+/// Unix timestamp `chat_id`'s lockdown started at, or `None` if it isn't locked down.
+pub fn lockdown_since(conn: &mut redis::Connection, chat_id: ChatId) -> Option<i64> {
+    conn.hget(chat_key(chat_id), field::LOCKDOWN_SINCE).ok().flatten()
+}
+
+/// Locks `chat_id` down from `now` on. Returns false if it already was.
+pub fn activate_lockdown(conn: &mut redis::Connection, chat_id: ChatId, now: i64) -> RedisResult<bool> {
+    conn.hset_nx(chat_key(chat_id), field::LOCKDOWN_SINCE, now)
+}
+
+/// Lifts `chat_id`'s lockdown, returning the members it muted.
+pub fn lift_lockdown(conn: &mut redis::Connection, chat_id: ChatId) -> RedisResult<Vec<UserId>> {
+    let muted_key = muted_key(chat_id);
+    let (muted,): (Vec<u64>,) = redis::pipe()
+        .atomic()
+        .smembers(&muted_key)
+        .del(&muted_key).ignore()
+        .hdel(chat_key(chat_id), field::LOCKDOWN_SINCE).ignore()
+        .del(format!("{}{}", chat_key(chat_id), suffix::RAID_EVENTS)).ignore()
+        .query(conn)?;
+    Ok(muted.into_iter().map(UserId).collect())
+}
+
+/// Records a join or first message in `chat_id` at `now` (Unix seconds) and
+/// locks the chat down once the window holds a raid's worth of them. Returns
+/// true only when this event started the lockdown.
+pub fn record_raid_event(conn: &mut redis::Connection, chat_id: ChatId, now: i64) -> RedisResult<bool> {
+    let events_key = format!("{}{}", chat_key(chat_id), suffix::RAID_EVENTS);
+    let member = format!("{}:{}", now, uuid::Uuid::new_v4());
+    let (recent,): (i64,) = redis::pipe()
+        .atomic()
+        .zrembyscore(&events_key, "-inf", now - lockdown::WINDOW_SECS).ignore()
+        .zadd(&events_key, member, now).ignore()
+        .expire(&events_key, lockdown::WINDOW_SECS).ignore()
+        .zcard(&events_key)
+        .query(conn)?;
+    if recent < lockdown::RAID_EVENTS {
+        return Ok(false);
+    }
+    activate_lockdown(conn, chat_id, now)
+}
+
+/// Whether a member who joined at `join_time` is held by `chat_id`'s
+/// lockdown: only members who joined after it started are.
+pub fn held_by_lockdown(conn: &mut redis::Connection, chat_id: ChatId, join_time: Option<i64>) -> bool {
+    match (lockdown_since(conn, chat_id), join_time) {
+        (Some(since), Some(joined)) => joined >= since,
+        _ => false,
+    }
+}
+
+/// Mutes `user_id` in `chat_id` until the lockdown is lifted. Does nothing
+/// if the lockdown already muted them.
+pub async fn mute_for_lockdown(
+    bot: &Bot,
+    conn: &mut redis::Connection,
+    chat_id: ChatId,
+    user_id: UserId,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let newly_held: bool = conn.sadd(muted_key(chat_id), user_id.0)?;
+    if !newly_held {
+        return Ok(());
+    }
+    if let Err(e) = mute_user(bot, conn, chat_id, user_id, mute::MAX_MINUTES).await {
+        let _: RedisResult<()> = conn.srem(muted_key(chat_id), user_id.0);
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Lifts `chat_id`'s lockdown and unmutes the members it held. Returns how
+/// many were unmuted.
+pub async fn end_lockdown(bot: &Bot, conn: &mut redis::Connection, chat_id: ChatId) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let held = lift_lockdown(conn, chat_id)?;
+    let mut unmuted = 0;
+    for user_id in held {
+        match unmute_user(bot, conn, chat_id, user_id).await {
+            Ok(()) => unmuted += 1,
+            Err(e) => log::warn!("Failed to unmute user {} after the lockdown of chat {}: {}", user_id, chat_id, e),
+        }
+    }
+    Ok(unmuted)
+}
+
+/// Tells `chat_id`'s admins (its admin chat, or the chat itself unless it is
+/// silent) that a raid locked it down.
+pub async fn announce_lockdown(bot: &Bot, conn: &mut redis::Connection, chat_id: ChatId) -> ResponseResult<()> {
+    let admin_chat: Option<i64> = conn.hget(chat_key(chat_id), field::ADMIN_CHAT).unwrap_or(None);
+    let notify_text = format!(
+        "Raid detected in chat {}: {} joins and first messages within {} seconds. \
+        The chat is locked down and new members are muted until an admin sends /lockdown off|{}.",
+        chat_id, lockdown::RAID_EVENTS, lockdown::WINDOW_SECS, chat_id
+    );
+    send_alert(bot, conn, chat_id, admin_chat.map(ChatId), Severity::High, notify_text).await
+}
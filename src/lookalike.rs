@@ -0,0 +1,35 @@
+//! Lookalike (homograph) domain detection used by `TG_LOOKALIKE_DOMAIN`.
+//!
+//! Mirrors `is_lookalike_host` in `telegram_simple.lua`: punycode labels
+//! (`xn--...`) are decoded, and a host is a lookalike when one of its labels
+//! mixes letters from more than one script, like `xn--pple-43d.com`
+//! ("аpple.com" with a Cyrillic "а").
+
+use std::collections::HashSet;
+
+use crate::script_filter::script_of;
+
+/// Prefix marking a punycode-encoded label.
+const PUNYCODE_PREFIX: &str = "xn--";
+
+/// Decodes the punycode labels of `host`; labels that fail to decode are kept as they are.
+pub fn decode_host(host: &str) -> String {
+    host.split('.')
+        .map(|label| {
+            let lower = label.to_lowercase();
+            lower
+                .strip_prefix(PUNYCODE_PREFIX)
+                .and_then(idna::punycode::decode_to_string)
+                .unwrap_or(lower)
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Returns true if a label of `host` mixes letters from different scripts once decoded.
+pub fn is_lookalike_host(host: &str) -> bool {
+    decode_host(host).split('.').any(|label| {
+        let scripts: HashSet<&str> = label.chars().filter_map(script_of).collect();
+        scripts.len() > 1
+    })
+}
@@ -1,15 +1,25 @@
+use std::future::Future;
 use std::time::Duration;
 use std::error::Error; 
-use redis::Commands;
 use teloxide::prelude::*;
+use tokio::task::JoinHandle;
 use tokio::time;
-use rspamd_telegram_bot::config::{field, key};
+use tokio_util::sync::CancellationToken;
 use rspamd_telegram_bot::admin_handlers;
+use rspamd_telegram_bot::api;
 use rspamd_telegram_bot::ban_manager::BanManager;
+use rspamd_telegram_bot::adaptive::refresh_adaptive_limits;
+use rspamd_telegram_bot::config::{adaptive, join_verify, mute};
+use rspamd_telegram_bot::join_verify::kick_expired_challenges;
+use rspamd_telegram_bot::mutes::lift_expired_mutes;
+use rspamd_telegram_bot::daily_summary::{send_daily_summaries, summary_time, until_next_run};
+use rspamd_telegram_bot::reputation_decay::ReputationDecay;
+use rspamd_telegram_bot::trust_manager::TrustManager;
 use rspamd_telegram_bot::bayes_manager::BayesManager;
 use rspamd_telegram_bot::neural_manager::NeuralManager;
 use rspamd_telegram_bot::migration;
 use std::env;
+use chrono::{NaiveTime, Utc};
 
 #[tokio::main]
 async fn main() {
@@ -18,14 +28,19 @@ async fn main() {
     // Load environment variables from .env file
     dotenv::dotenv().ok();
     
-    pretty_env_logger::init();
+    // Spans carry the chat, user and message a log line belongs to; `log`
+    // records, from this crate and its dependencies, are bridged into them
+    tracing_log::LogTracer::init().expect("Failed to bridge log records");
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .finish();
+    tracing::subscriber::set_global_default(subscriber).expect("Failed to install the tracing subscriber");
     log::info!("Starting the spam detection bot...");
 
-    // Run reputation data migration
-    if let Err(err) = migration::migrate_reputation_data().await {
-        log::error!("Migration failed: {:?}", err);
-    } else {
-        log::info!("Reputation migration completed successfully");
+    // Bring the Redis schema up to date before handling any updates
+    match migration::run_migrations().await {
+        Ok(version) => log::info!("Redis schema at version {}", version),
+        Err(err) => log::error!("Migration failed: {:?}", err),
     }
 
     let bot = Bot::from_env();
@@ -46,45 +61,149 @@ async fn main() {
         eprintln!("Failed to initialize ban manager");
     }
 
+    // Cancelled on SIGTERM/Ctrl-C; loops finish their current iteration before exiting
+    let shutdown = CancellationToken::new();
     tokio::spawn({
+        let shutdown = shutdown.clone();
         async move {
-            let mut interval = time::interval(Duration::from_secs(3600));
-            loop {
-                interval.tick().await;
-                if let Err(err) = do_periodic().await {
-                    log::error!("Periodic task failed: {:?}", err);
-                }
-            }
+            wait_for_shutdown_signal().await;
+            log::info!("Shutdown signal received, stopping...");
+            shutdown.cancel();
         }
     });
 
-    // Start Bayes performance monitoring
-    tokio::spawn({
-        async move {
-            let mut interval = time::interval(Duration::from_secs(7200)); // Every 2 hours
-            loop {
-                interval.tick().await;
-                if let Err(err) = monitor_bayes_performance().await {
-                    log::error!("Bayes monitoring failed: {:?}", err);
+    let periodic_tasks = vec![
+        spawn_periodic(Duration::from_secs(3600), shutdown.clone(), || async {
+            if let Err(err) = do_periodic().await {
+                log::error!("Periodic task failed: {:?}", err);
+            }
+        }),
+        // Bayes performance monitoring, every 2 hours
+        spawn_periodic(Duration::from_secs(7200), shutdown.clone(), || async {
+            if let Err(err) = monitor_bayes_performance().await {
+                log::error!("Bayes monitoring failed: {:?}", err);
+            }
+        }),
+        // Give muted users their permissions back once the mute runs out
+        spawn_periodic(Duration::from_secs(mute::CHECK_INTERVAL_SECS), shutdown.clone(), {
+            let bot = bot.clone();
+            move || {
+                let bot = bot.clone();
+                async move {
+                    match lift_expired_mutes(&bot).await {
+                        Ok(0) => {}
+                        Ok(lifted) => log::info!("Lifted {} expired mute(s)", lifted),
+                        Err(err) => log::error!("Lifting expired mutes failed: {:?}", err),
+                    }
+                }
+            }
+        }),
+        // Sample chats' message rates and rescale their flood and repeat limits
+        spawn_periodic(Duration::from_secs(adaptive::SAMPLE_SECS), shutdown.clone(), || async {
+            if let Err(err) = refresh_adaptive_limits() {
+                log::error!("Refreshing adaptive limits failed: {:?}", err);
+            }
+        }),
+        // Kick new members who didn't pass the join challenge in time
+        spawn_periodic(Duration::from_secs(join_verify::CHECK_INTERVAL_SECS), shutdown.clone(), {
+            let bot = bot.clone();
+            move || {
+                let bot = bot.clone();
+                async move {
+                    match kick_expired_challenges(&bot).await {
+                        Ok(0) => {}
+                        Ok(kicked) => log::info!("Kicked {} unverified member(s)", kicked),
+                        Err(err) => log::error!("Kicking unverified members failed: {:?}", err),
+                    }
                 }
             }
+        }),
+        // Morning digest for admin chats, at DAILY_SUMMARY_TIME (UTC)
+        spawn_daily(summary_time(), shutdown.clone(), {
+            let bot = bot.clone();
+            move || {
+                let bot = bot.clone();
+                async move {
+                    match send_daily_summaries(&bot).await {
+                        Ok(sent) => log::info!("Sent {} daily summary(ies)", sent),
+                        Err(err) => log::error!("Daily summary failed: {:?}", err),
+                    }
+                }
+            }
+        }),
+        // Neural Network performance monitoring, every 2 hours
+        spawn_periodic(Duration::from_secs(7200), shutdown.clone(), || async {
+            if let Err(err) = monitor_neural_performance().await {
+                log::error!("Neural monitoring failed: {:?}", err);
+            }
+        }),
+    ];
+
+    admin_handlers::run_dispatcher(bot, shutdown.clone()).await;
+
+    // Stop the periodic loops even if the dispatcher exited on its own
+    shutdown.cancel();
+    for task in periodic_tasks {
+        if let Err(err) = task.await {
+            log::error!("Periodic task panicked: {:?}", err);
         }
-    });
+    }
+    log::info!("Bot stopped cleanly");
+}
 
-    // Start Neural Network performance monitoring
-    tokio::spawn({
-        async move {
-            let mut interval = time::interval(Duration::from_secs(7200)); // Every 2 hours
-            loop {
-                interval.tick().await;
-                if let Err(err) = monitor_neural_performance().await {
-                    log::error!("Neural monitoring failed: {:?}", err);
+/// Resolves on SIGTERM (sent by most hosting platforms on restart) or Ctrl-C.
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = sigterm.recv() => {}
+                    _ = tokio::signal::ctrl_c() => {}
                 }
+                return;
             }
+            Err(err) => log::error!("Failed to listen for SIGTERM: {:?}", err),
         }
-    });
+    }
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        log::error!("Failed to listen for Ctrl-C: {:?}", err);
+        std::future::pending::<()>().await;
+    }
+}
 
-    admin_handlers::run_dispatcher(bot).await;
+/// Runs `task` every `period` until `shutdown` is cancelled, checking between iterations.
+fn spawn_periodic<F, Fut>(period: Duration, shutdown: CancellationToken, task: F) -> JoinHandle<()>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    tokio::spawn(async move {
+        let mut interval = time::interval(period);
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = interval.tick() => task().await,
+            }
+        }
+    })
+}
+
+/// Runs `task` once a day at `at` (UTC) until `shutdown` is cancelled.
+fn spawn_daily<F, Fut>(at: NaiveTime, shutdown: CancellationToken, task: F) -> JoinHandle<()>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                _ = time::sleep(until_next_run(Utc::now(), at)) => task().await,
+            }
+        }
+    })
 }
 
 async fn start_health_server(port: String) {
@@ -96,46 +215,30 @@ async fn start_health_server(port: String) {
     let root = warp::path::end()
         .map(|| warp::reply::with_status("Telegram Bot Running", warp::http::StatusCode::OK));
     
-    let routes = health.or(root);
-    
     let port: u16 = port.parse().unwrap_or(3000);
     println!("Health server starting on 0.0.0.0:{}", port);
     
-    warp::serve(routes)
-        .run(([0, 0, 0, 0], port))
-        .await;
+    match api::api_token() {
+        Some(token) => {
+            let routes = health.or(root).or(api::routes(token));
+            warp::serve(routes).run(([0, 0, 0, 0], port)).await;
+        }
+        None => {
+            let routes = health.or(root);
+            warp::serve(routes).run(([0, 0, 0, 0], port)).await;
+        }
+    }
 }
 
 async fn do_periodic() -> Result<(), Box<dyn Error + Send + Sync>> {
-    let redis_client = redis::Client::open("redis://127.0.0.1/")
-        .expect("Failed to connect to Redis");
-    let mut redis_conn = redis_client
-        .get_connection()
-        .expect("Failed to get Redis connection");
-
-    let keys: Vec<String> = redis_conn
-        .keys(format!("{}*", key::TG_USERS_PREFIX))
-        .expect("Failed to get users keys");
-
-    for key in keys {
-        let rep: Option<i64> = redis_conn
-            .hget(key.clone(), field::REP)
-            .expect("Failed to get user's reputation");
-
-        if let Some(rep_value) = rep {
-            if rep_value > 0 {
-                let _: () = redis_conn
-                    .hincr(key.clone(), field::REP, -1)
-                    .expect("Failed to decrease user's reputation");
-            }
-        } else {
-            // User doesn't have a reputation field yet, initialize it to 0
-            let _: () = redis_conn
-                .hset(key.clone(), field::REP, 0)
-                .expect("Failed to initialize user's reputation");
-        }
-    }
+    let reputation_decay = ReputationDecay::new()?;
+    let processed = reputation_decay.run_decay_cycle().await?;
+    log::info!("Reputation decay processed {} users", processed);
 
+    let removed = TrustManager::new("redis://127.0.0.1/")?.cleanup_expired().await?;
+    if removed > 0 {
+        log::info!("Removed {} orphaned trusted message and reply tracking entries", removed);
+    }
     Ok(())
 }
 
@@ -0,0 +1,91 @@
+//! Repeated media behind `TG_MEDIA_REPEAT`.
+//!
+//! Spammers post the same image or sticker again and again, which
+//! `TG_REPEAT` can't see as it only compares text. Telegram gives every
+//! file a `file_unique_id` that stays the same across messages and chats,
+//! so the posts of each one are counted per member and chat; from the
+//! `media_repeat::MIN_POSTS`th within `media_repeat::WINDOW_SECS` of the
+//! first the symbol fires.
+
+use redis::{Commands, RedisResult};
+use rspamd_client::protocol::{scan::Symbol, RspamdScanReply};
+use teloxide::types::Message;
+
+use crate::config::{is_feature_enabled, key, media_repeat, symbol, MEDIA_REPEAT_FEATURE};
+
+/// The `file_unique_id` of the media `msg` carries: its photo (largest
+/// size), sticker, animation, video, video note, voice message, audio or
+/// document.
+pub fn media_unique_id(msg: &Message) -> Option<&str> {
+    let file = if let Some(sizes) = msg.photo() {
+        &sizes.last()?.file
+    } else if let Some(sticker) = msg.sticker() {
+        &sticker.file
+    } else if let Some(animation) = msg.animation() {
+        &animation.file
+    } else if let Some(video) = msg.video() {
+        &video.file
+    } else if let Some(video_note) = msg.video_note() {
+        &video_note.file
+    } else if let Some(voice) = msg.voice() {
+        &voice.file
+    } else if let Some(audio) = msg.audio() {
+        &audio.file
+    } else {
+        &msg.document()?.file
+    };
+    Some(file.unique_id.as_str())
+}
+
+fn media_key(chat_id: i64, user_id: u64, unique_id: &str) -> String {
+    format!("{}{}:{}:{}", key::ns(key::TG_MEDIA_PREFIX), chat_id, user_id, unique_id)
+}
+
+/// Counts a post of `unique_id` by `user_id` in `chat_id` and returns how
+/// often they posted it since the window started with the first post.
+pub fn record_media_post(conn: &mut redis::Connection, chat_id: i64, user_id: u64, unique_id: &str) -> RedisResult<i64> {
+    let media_key = media_key(chat_id, user_id, unique_id);
+    let posts: i64 = conn.incr(&media_key, 1)?;
+    if posts == 1 {
+        conn.expire::<_, ()>(&media_key, media_repeat::WINDOW_SECS)?;
+    }
+    Ok(posts)
+}
+
+/// How often `user_id` posted `unique_id` in `chat_id` in the current window.
+pub fn media_posts(conn: &mut redis::Connection, chat_id: i64, user_id: u64, unique_id: &str) -> RedisResult<i64> {
+    Ok(conn.get::<_, Option<i64>>(media_key(chat_id, user_id, unique_id))?.unwrap_or(0))
+}
+
+/// Adds `TG_MEDIA_REPEAT` to the scan of `msg` once the sender posted its
+/// media `media_repeat::MIN_POSTS` times, if the feature is on for the chat.
+/// With `count` the post is recorded first; a rescan of a message leaves it
+/// off so the post isn't counted twice.
+pub fn apply_media_repeat(conn: &mut redis::Connection, reply: &mut RspamdScanReply, msg: &Message, count: bool) -> RedisResult<()> {
+    let (Some(unique_id), Some(user)) = (media_unique_id(msg), msg.from.as_ref()) else {
+        return Ok(());
+    };
+    if !is_feature_enabled(conn, msg.chat.id.0, MEDIA_REPEAT_FEATURE) {
+        return Ok(());
+    }
+    let posts = if count {
+        record_media_post(conn, msg.chat.id.0, user.id.0, unique_id)?
+    } else {
+        media_posts(conn, msg.chat.id.0, user.id.0, unique_id)?
+    };
+    if posts < media_repeat::MIN_POSTS {
+        return Ok(());
+    }
+    reply.score += media_repeat::SCORE;
+    reply.symbols.insert(
+        symbol::TG_MEDIA_REPEAT.to_string(),
+        Symbol {
+            name: symbol::TG_MEDIA_REPEAT.to_string(),
+            score: media_repeat::SCORE,
+            metric_score: media_repeat::SCORE,
+            description: Some(format!("Same media posted {} times", posts)),
+            options: Some(vec![unique_id.to_string()]),
+        },
+    );
+    Ok(())
+}
@@ -10,7 +10,7 @@ pub async fn migrate_reputation_data() -> Result<(), Box<dyn Error + Send + Sync
     println!("Starting reputation data migration...");
     
     // Get all user keys
-    let user_keys: Vec<String> = redis_conn.keys(format!("{}*", key::TG_USERS_PREFIX))?;
+    let user_keys: Vec<String> = redis_conn.keys(format!("{}*", key::ns(key::TG_USERS_PREFIX)))?;
     println!("Found {} user keys to migrate", user_keys.len());
     
     let mut migrated_count = 0;
@@ -18,7 +18,7 @@ pub async fn migrate_reputation_data() -> Result<(), Box<dyn Error + Send + Sync
     
     for user_key in user_keys {
         // Extract user ID from key (remove the prefix)
-        let user_id = user_key.replace(&format!("{}", key::TG_USERS_PREFIX), "");
+        let user_id = user_key.replace(&key::ns(key::TG_USERS_PREFIX), "");
         
         // Get existing reputation
         let rep: Option<i64> = redis_conn.hget(&user_key, field::REP)?;
@@ -26,7 +26,7 @@ pub async fn migrate_reputation_data() -> Result<(), Box<dyn Error + Send + Sync
         if let Some(rep_value) = rep {
             if rep_value != 0 {
                 // Convert to Rspamd reputation format
-                let reputation_key = format!("tg:reputation:user:{}", user_id);
+                let reputation_key = format!("{}{}", key::ns(key::TG_REPUTATION_USER_PREFIX), user_id);
                 
                 if rep_value > 0 {
                     // Positive reputation becomes bad reputation (spam behavior)
@@ -65,13 +65,13 @@ pub async fn verify_migration() -> Result<(), Box<dyn Error + Send + Sync>> {
     println!("Verifying migration...");
     
     // Get a sample of user keys
-    let user_keys: Vec<String> = redis_conn.keys(format!("{}*", key::TG_USERS_PREFIX))?;
+    let user_keys: Vec<String> = redis_conn.keys(format!("{}*", key::ns(key::TG_USERS_PREFIX)))?;
     let sample_size = std::cmp::min(10, user_keys.len());
     let sample_keys = &user_keys[..sample_size];
     
     for user_key in sample_keys {
-        let user_id = user_key.replace(&format!("{}", key::TG_USERS_PREFIX), "");
-        let reputation_key = format!("tg:reputation:user:{}", user_id);
+        let user_id = user_key.replace(&key::ns(key::TG_USERS_PREFIX), "");
+        let reputation_key = format!("{}{}", key::ns(key::TG_REPUTATION_USER_PREFIX), user_id);
         
         // Check if reputation key exists
         let exists: bool = redis_conn.exists(&reputation_key)?;
@@ -96,7 +96,7 @@ pub async fn cleanup_old_reputation_data() -> Result<(), Box<dyn Error + Send +
     println!("Cleaning up old reputation data...");
     
     // Get all user keys
-    let user_keys: Vec<String> = redis_conn.keys(format!("{}*", key::TG_USERS_PREFIX))?;
+    let user_keys: Vec<String> = redis_conn.keys(format!("{}*", key::ns(key::TG_USERS_PREFIX)))?;
     
     let mut cleaned_count = 0;
     
@@ -111,4 +111,112 @@ pub async fn cleanup_old_reputation_data() -> Result<(), Box<dyn Error + Send +
     println!("Cleanup completed: {} users cleaned", cleaned_count);
     
     Ok(())
-} 
\ No newline at end of file
+} 
+
+/// A single ordered step in the Redis schema history
+struct Migration {
+    version: u32,
+    description: &'static str,
+    apply: fn(&mut redis::Connection) -> redis::RedisResult<()>,
+}
+
+/// All schema migrations, in the order they must be applied
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "reconcile tg:users rep with tg:reputation:user good/bad",
+        apply: reconcile_reputation_layouts,
+    },
+];
+
+/// Latest schema version known to this build
+pub fn latest_schema_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Schema version currently recorded in Redis (0 if never migrated)
+pub fn current_schema_version(conn: &mut redis::Connection) -> redis::RedisResult<u32> {
+    let version: Option<u32> = conn.get(key::ns(key::SCHEMA_VERSION_KEY))?;
+    Ok(version.unwrap_or(0))
+}
+
+/// Apply every migration newer than the recorded schema version
+pub fn apply_pending_migrations(conn: &mut redis::Connection) -> redis::RedisResult<u32> {
+    let mut version = current_schema_version(conn)?;
+    let pending: Vec<&Migration> = MIGRATIONS.iter().filter(|m| m.version > version).collect();
+
+    for migration in pending {
+        log::info!("Applying schema migration {}: {}", migration.version, migration.description);
+        (migration.apply)(conn)?;
+        conn.set::<_, _, ()>(key::ns(key::SCHEMA_VERSION_KEY), migration.version)?;
+        version = migration.version;
+    }
+
+    Ok(version)
+}
+
+/// Bring the Redis schema up to date; safe to call on every startup
+pub async fn run_migrations() -> Result<u32, Box<dyn Error + Send + Sync>> {
+    let redis_client = redis::Client::open("redis://127.0.0.1/")?;
+    let mut redis_conn = redis_client.get_connection()?;
+
+    Ok(apply_pending_migrations(&mut redis_conn)?)
+}
+
+/// Migration 1: give every user with a legacy `rep` field a matching
+/// `good`/`bad` reputation hash, and fill in missing fields on existing ones.
+fn reconcile_reputation_layouts(conn: &mut redis::Connection) -> redis::RedisResult<()> {
+    let prefix = key::ns(key::TG_USERS_PREFIX);
+    let pattern = format!("{}*", prefix);
+    let mut cursor: u64 = 0;
+
+    loop {
+        let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(&pattern)
+            .arg("COUNT")
+            .arg(500)
+            .query(conn)?;
+
+        for user_key in keys {
+            // Skip auxiliary keys such as `tg:users:<id>:bot_chats`
+            let user_id = &user_key[prefix.len()..];
+            if user_id.parse::<i64>().is_err() {
+                continue;
+            }
+
+            let reputation_key = format!("{}{}", key::ns(key::TG_REPUTATION_USER_PREFIX), user_id);
+            let exists: bool = conn.exists(&reputation_key)?;
+
+            if exists {
+                // Only fill gaps so live Rspamd counters are never clobbered
+                redis::pipe()
+                    .hset_nx(&reputation_key, "bad", 0).ignore()
+                    .hset_nx(&reputation_key, "good", 0).ignore()
+                    .query::<()>(conn)?;
+                continue;
+            }
+
+            let rep: Option<i64> = conn.hget(&user_key, field::REP).unwrap_or(None);
+            let rep = match rep {
+                Some(rep) if rep != 0 => rep,
+                _ => continue,
+            };
+
+            // Positive rep means spam behaviour, negative means legitimate
+            let (bad, good) = if rep > 0 { (rep, 0) } else { (0, rep.abs()) };
+            redis::pipe()
+                .hset(&reputation_key, "bad", bad).ignore()
+                .hset(&reputation_key, "good", good).ignore()
+                .query::<()>(conn)?;
+        }
+
+        cursor = next;
+        if cursor == 0 {
+            break;
+        }
+    }
+
+    Ok(())
+}
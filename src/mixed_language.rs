@@ -0,0 +1,73 @@
+//! Passages in unrelated scripts behind `TG_MIXED_LANGUAGE_SPAM`.
+//!
+//! Some ads put whole sentences in two scripts (an English pitch with a
+//! Chinese call to action) to reach several audiences at once and slip past
+//! word filters written for one language. Unlike homoglyph spoofing, which
+//! swaps single letters inside a word, each passage is in one script. The
+//! letters of each script in `script_filter::SCRIPTS` are counted in runs
+//! that only a letter of another script ends, so punctuation, digits and
+//! emoji between words don't matter. A message is flagged when two or more
+//! scripts each have a run of `mixed_language::MIN_RUN_LETTERS` and at least
+//! `mixed_language::MIN_SHARE` of its letters; a short bilingual greeting
+//! has neither.
+
+use redis::RedisResult;
+use rspamd_client::protocol::{scan::Symbol, RspamdScanReply};
+use teloxide::types::Message;
+
+use crate::config::{is_feature_enabled, mixed_language, symbol, MIXED_LANGUAGE_FEATURE};
+use crate::script_filter::{script_of, SCRIPTS};
+
+/// The scripts `text` has substantial passages in, in `SCRIPTS` order, when
+/// there are two or more of them; empty otherwise.
+pub fn mixed_scripts(text: &str) -> Vec<&'static str> {
+    let mut letters = vec![0usize; SCRIPTS.len()];
+    let mut longest_run = vec![0usize; SCRIPTS.len()];
+    let mut run: Option<(usize, usize)> = None;
+    for c in text.chars() {
+        let Some(script) = script_of(c).and_then(|script| SCRIPTS.iter().position(|(name, _)| *name == script)) else {
+            continue;
+        };
+        letters[script] += 1;
+        let length = match run {
+            Some((current, length)) if current == script => length + 1,
+            _ => 1,
+        };
+        run = Some((script, length));
+        longest_run[script] = longest_run[script].max(length);
+    }
+
+    let total: usize = letters.iter().sum();
+    let scripts: Vec<&'static str> = (0..SCRIPTS.len())
+        .filter(|&i| {
+            longest_run[i] >= mixed_language::MIN_RUN_LETTERS
+                && letters[i] as f64 >= mixed_language::MIN_SHARE * total as f64
+        })
+        .map(|i| SCRIPTS[i].0)
+        .collect();
+    if scripts.len() < 2 {
+        return Vec::new();
+    }
+    scripts
+}
+
+/// Adds `TG_MIXED_LANGUAGE_SPAM` to the scan of `msg` when `text` mixes
+/// passages in unrelated scripts and the feature is on for the chat.
+pub fn apply_mixed_language(conn: &mut redis::Connection, reply: &mut RspamdScanReply, msg: &Message, text: &str) -> RedisResult<()> {
+    let scripts = mixed_scripts(text);
+    if scripts.is_empty() || !is_feature_enabled(conn, msg.chat.id.0, MIXED_LANGUAGE_FEATURE) {
+        return Ok(());
+    }
+    reply.score += mixed_language::SCORE;
+    reply.symbols.insert(
+        symbol::TG_MIXED_LANGUAGE_SPAM.to_string(),
+        Symbol {
+            name: symbol::TG_MIXED_LANGUAGE_SPAM.to_string(),
+            score: mixed_language::SCORE,
+            metric_score: mixed_language::SCORE,
+            description: Some("Passages in unrelated scripts".to_string()),
+            options: Some(scripts.into_iter().map(str::to_string).collect()),
+        },
+    );
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! Read-only mutes, a softer alternative to bans.
+//!
+//! A mute takes away every permission of a user in one chat for a while,
+//! via `tg_mute` in the chat's action map or `/mute`. It is tracked in the
+//! user's `tg:users:<id>` hash (`muted_until`, `muted_in`) so `/mutelist` can
+//! show it and the periodic task can lift it once `muted_until` has passed.
+
+use std::collections::HashSet;
+use std::error::Error;
+
+use chrono::{DateTime, Utc};
+use redis::{Commands, RedisResult};
+use teloxide::prelude::*;
+use teloxide::types::ChatPermissions;
+
+use crate::config::{field, key, mute, reputation};
+
+/// A user currently muted, as listed by `/mutelist`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MutedUser {
+    pub user_id: u64,
+    pub username: Option<String>,
+    /// Chat the mute was issued in.
+    pub chat_id: ChatId,
+    /// Unix timestamp the mute ends at.
+    pub muted_until: i64,
+}
+
+/// Minutes `tg_mute` mutes for in `chat_id`.
+pub fn mute_minutes(conn: &mut redis::Connection, chat_id: ChatId) -> i64 {
+    let minutes: Option<i64> = conn
+        .hget(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0), field::MUTE_MINUTES)
+        .unwrap_or(None);
+    minutes.unwrap_or(mute::DEFAULT_MINUTES).clamp(1, mute::MAX_MINUTES)
+}
+
+/// Records that `user_id` is muted in `chat_id` until `until` (Unix timestamp).
+pub fn record_mute(conn: &mut redis::Connection, user_id: UserId, chat_id: ChatId, until: i64) -> RedisResult<()> {
+    conn.hset_multiple(
+        format!("{}{}", key::ns(key::TG_USERS_PREFIX), user_id),
+        &[(field::MUTED_UNTIL, until), (field::MUTED_IN, chat_id.0)],
+    )
+}
+
+/// Forgets a user's mute, returning the chat it was issued in (if any).
+pub fn clear_mute(conn: &mut redis::Connection, user_id: UserId) -> RedisResult<Option<ChatId>> {
+    let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user_id);
+    let (muted_in,): (Option<i64>,) = redis::pipe()
+        .hget(&user_key, field::MUTED_IN)
+        .hdel(&user_key, &[field::MUTED_UNTIL, field::MUTED_IN]).ignore()
+        .query(conn)?;
+    Ok(muted_in.map(ChatId))
+}
+
+/// Every recorded mute, soonest to end first.
+///
+/// User keys are walked with a `SCAN` cursor like `banned_users`.
+pub fn muted_users(conn: &mut redis::Connection) -> RedisResult<Vec<MutedUser>> {
+    let prefix = key::ns(key::TG_USERS_PREFIX);
+    let pattern = format!("{}*", prefix);
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut users = Vec::new();
+    let mut cursor: u64 = 0;
+
+    loop {
+        let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(&pattern)
+            .arg("COUNT")
+            .arg(reputation::SCAN_BATCH_SIZE)
+            .query(conn)?;
+
+        let keys: Vec<(String, u64)> = batch
+            .into_iter()
+            .filter_map(|k| {
+                let user_id = k[prefix.len()..].parse::<u64>().ok()?;
+                seen.insert(k.clone()).then_some((k, user_id))
+            })
+            .collect();
+
+        if !keys.is_empty() {
+            let mut read = redis::pipe();
+            for (user_key, _) in &keys {
+                read.cmd("HMGET")
+                    .arg(user_key)
+                    .arg(field::MUTED_UNTIL)
+                    .arg(field::MUTED_IN)
+                    .arg(field::USERNAME);
+            }
+            let states: Vec<(Option<i64>, Option<i64>, Option<String>)> = read.query(conn)?;
+
+            for ((_, user_id), (muted_until, muted_in, username)) in keys.into_iter().zip(states) {
+                if let (Some(muted_until), Some(muted_in)) = (muted_until, muted_in) {
+                    users.push(MutedUser { user_id, username, chat_id: ChatId(muted_in), muted_until });
+                }
+            }
+        }
+
+        cursor = next_cursor;
+        if cursor == 0 {
+            break;
+        }
+    }
+
+    users.sort_by(|a, b| a.muted_until.cmp(&b.muted_until).then(a.user_id.cmp(&b.user_id)));
+    Ok(users)
+}
+
+/// Mutes whose end time is at or before `now`.
+pub fn expired_mutes(conn: &mut redis::Connection, now: i64) -> RedisResult<Vec<MutedUser>> {
+    Ok(muted_users(conn)?.into_iter().filter(|m| m.muted_until <= now).collect())
+}
+
+/// Makes `user_id` read-only in `chat_id` for `minutes` and records the mute.
+/// Returns when the mute ends.
+pub async fn mute_user(
+    bot: &Bot,
+    conn: &mut redis::Connection,
+    chat_id: ChatId,
+    user_id: UserId,
+    minutes: i64,
+) -> Result<DateTime<Utc>, Box<dyn Error + Send + Sync>> {
+    let until = Utc::now() + chrono::Duration::minutes(minutes.clamp(1, mute::MAX_MINUTES));
+    bot.restrict_chat_member(chat_id, user_id, ChatPermissions::empty())
+        .until_date(until)
+        .await?;
+    record_mute(conn, user_id, chat_id, until.timestamp())?;
+    Ok(until)
+}
+
+/// Gives `user_id` back their permissions in `chat_id` and forgets the mute.
+pub async fn unmute_user(
+    bot: &Bot,
+    conn: &mut redis::Connection,
+    chat_id: ChatId,
+    user_id: UserId,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    bot.restrict_chat_member(chat_id, user_id, ChatPermissions::all()).await?;
+    clear_mute(conn, user_id)?;
+    Ok(())
+}
+
+/// Lifts every mute that has run out; returns how many were lifted.
+pub async fn lift_expired_mutes(bot: &Bot) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let mut conn = redis::Client::open("redis://127.0.0.1/")?.get_connection()?;
+    let mut lifted = 0;
+    for muted in expired_mutes(&mut conn, Utc::now().timestamp())? {
+        let user_id = UserId(muted.user_id);
+        match unmute_user(bot, &mut conn, muted.chat_id, user_id).await {
+            Ok(()) => lifted += 1,
+            Err(e) => {
+                // Telegram lifts the restriction on its own; don't retry forever
+                log::warn!("Failed to unmute user {} in chat {}: {}", user_id, muted.chat_id, e);
+                clear_mute(&mut conn, user_id)?;
+            }
+        }
+    }
+    Ok(lifted)
+}
@@ -22,6 +22,18 @@ pub struct NeuralFeatures {
     pub text_features: HashMap<String, f64>,
 }
 
+/// Result of a manual neural network training run.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrainOutcome {
+    /// Not enough samples collected yet; `missing` more are needed.
+    NotReady { samples: i64, missing: i64 },
+    /// Model was trained on `samples` stored feature records.
+    Trained { samples: usize, iterations: i64, accuracy: f64 },
+}
+
+/// Feature names used by the nearest-centroid model, in vector order.
+const MODEL_FEATURES: [&str; 4] = ["word_count", "link_count", "emoji_count", "caps_ratio"];
+
 /// Manages Neural Network operations for the Rspamd Telegram bot.
 /// 
 /// This struct provides functionality to:
@@ -45,8 +57,8 @@ impl NeuralManager {
         Ok(Self {
             redis_client,
             rspamd_client,
-            rspamd_url: rspamd::CONTROLLER_URL.to_string(),
-            rspamd_password: rspamd::PASSWORD.to_string(),
+            rspamd_url: rspamd::controller_url(),
+            rspamd_password: rspamd::password(),
         })
     }
     
@@ -81,6 +93,118 @@ impl NeuralManager {
            stats.training_iterations > 0)
     }
     
+    /// Trains the model on the stored feature records.
+    ///
+    /// Computes a per-class centroid over the stored text features, saves it to
+    /// the model key and records the resulting training accuracy. Training is
+    /// skipped until `MIN_SAMPLES_REQUIRED` messages have been learned.
+    pub fn train(&self) -> Result<TrainOutcome> {
+        let stats = self.get_neural_stats()?;
+        if stats.total_messages < neural::MIN_SAMPLES_REQUIRED {
+            return Ok(TrainOutcome::NotReady {
+                samples: stats.total_messages,
+                missing: neural::MIN_SAMPLES_REQUIRED - stats.total_messages,
+            });
+        }
+
+        let samples = self.load_training_samples()?;
+        if samples.is_empty() {
+            return Err(anyhow::anyhow!("no stored feature records to train on"));
+        }
+
+        // Per-class centroids
+        let mut sums: HashMap<bool, ([f64; 4], usize)> = HashMap::new();
+        for (is_spam, vector) in &samples {
+            let entry = sums.entry(*is_spam).or_insert(([0.0; 4], 0));
+            for (acc, value) in entry.0.iter_mut().zip(vector) {
+                *acc += value;
+            }
+            entry.1 += 1;
+        }
+        let centroids: HashMap<bool, [f64; 4]> = sums
+            .into_iter()
+            .map(|(class, (sum, count))| (class, sum.map(|v| v / count as f64)))
+            .collect();
+
+        // Training accuracy of the nearest-centroid rule
+        let correct = samples
+            .iter()
+            .filter(|(is_spam, vector)| Self::nearest_class(&centroids, vector) == Some(*is_spam))
+            .count();
+        let accuracy = correct as f64 / samples.len() as f64;
+
+        let mut conn = self.redis_client.get_connection()?;
+        let mut model: Vec<(String, f64)> = Vec::new();
+        for (class, centroid) in &centroids {
+            let label = if *class { "spam" } else { "ham" };
+            for (name, value) in MODEL_FEATURES.iter().zip(centroid) {
+                model.push((format!("{}:{}", label, name), *value));
+            }
+        }
+        let _: () = conn.del(neural::NEURAL_MODEL_KEY)?;
+        let _: () = conn.hset_multiple(neural::NEURAL_MODEL_KEY, &model)?;
+
+        let iterations: i64 = conn.hincr(neural::NEURAL_STATS_KEY, "training_iterations", 1)?;
+        let _: () = conn.hset_multiple(neural::NEURAL_STATS_KEY, &[
+            ("model_accuracy", accuracy.to_string()),
+            ("last_training", chrono::Utc::now().to_rfc3339()),
+        ])?;
+
+        Ok(TrainOutcome::Trained { samples: samples.len(), iterations, accuracy })
+    }
+
+    /// Loads `(is_spam, feature_vector)` pairs from the stored feature records.
+    fn load_training_samples(&self) -> Result<Vec<(bool, [f64; 4])>> {
+        let mut conn = self.redis_client.get_connection()?;
+        let pattern = format!("{}:*", neural::NEURAL_FEATURES_KEY);
+        let mut samples = Vec::new();
+        let mut cursor: u64 = 0;
+
+        loop {
+            let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(500)
+                .query(&mut conn)?;
+
+            for feature_key in keys {
+                let raw: Option<String> = conn.get(&feature_key).unwrap_or(None);
+                let record: serde_json::Value = match raw.and_then(|r| serde_json::from_str(&r).ok()) {
+                    Some(record) => record,
+                    None => continue,
+                };
+                let is_spam = match record["learning_type"].as_str() {
+                    Some("spam") => true,
+                    Some("ham") => false,
+                    _ => continue,
+                };
+                let vector = MODEL_FEATURES.map(|name| record[name].as_f64().unwrap_or(0.0));
+                samples.push((is_spam, vector));
+            }
+
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+
+        Ok(samples)
+    }
+
+    /// Returns the class whose centroid is closest to `vector`.
+    fn nearest_class(centroids: &HashMap<bool, [f64; 4]>, vector: &[f64; 4]) -> Option<bool> {
+        centroids
+            .iter()
+            .map(|(class, centroid)| {
+                let distance: f64 = centroid.iter().zip(vector).map(|(c, v)| (c - v).powi(2)).sum();
+                (*class, distance)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(class, _)| class)
+    }
+
     /// Gets neural network model accuracy.
     pub fn get_accuracy(&self) -> Result<f64> {
         let stats = self.get_neural_stats()?;
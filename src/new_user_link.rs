@@ -0,0 +1,58 @@
+//! Links from members who just joined, behind `TG_NEW_USER_LINK`.
+//!
+//! Established members post links all the time, but a link within the
+//! chat's `new_user_link` join window (see `/setjoinwindow`) is a typical
+//! spam bot's first move. The join time is the one recorded by
+//! `chat_member_handler`; links to trusted domains don't count.
+
+use redis::{Commands, RedisResult};
+use rspamd_client::protocol::{scan::Symbol, RspamdScanReply};
+use teloxide::types::Message;
+
+use crate::config::{field, is_feature_enabled, key, new_user_link, symbol, NEW_USER_LINK_FEATURE};
+use crate::domain_rep::untrusted_hosts;
+use crate::join_gate::join_windows;
+
+/// Whether a message sent at `now` by a member who joined at `join_time`
+/// falls within a `window` of seconds after joining.
+pub fn is_new_member(join_time: Option<i64>, now: i64, window: i64) -> bool {
+    join_time.is_some_and(|joined| joined > 0 && now - joined < window)
+}
+
+/// Adds `TG_NEW_USER_LINK` to the scan of `msg` when `text` links to an
+/// untrusted domain and its sender joined within the chat's window before
+/// `now`, if the feature is on for the chat.
+pub fn apply_new_user_link(
+    conn: &mut redis::Connection,
+    reply: &mut RspamdScanReply,
+    msg: &Message,
+    text: &str,
+    now: i64,
+) -> RedisResult<()> {
+    let Some(user) = msg.from.as_ref() else {
+        return Ok(());
+    };
+    if !is_feature_enabled(conn, msg.chat.id.0, NEW_USER_LINK_FEATURE) {
+        return Ok(());
+    }
+    let Some(host) = untrusted_hosts(conn, text)?.into_iter().next() else {
+        return Ok(());
+    };
+    let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), user.id.0);
+    let join_time: Option<i64> = conn.hget(&user_key, field::JOIN_TIME)?;
+    if !is_new_member(join_time, now, join_windows(conn, msg.chat.id).new_user_link) {
+        return Ok(());
+    }
+    reply.score += new_user_link::SCORE;
+    reply.symbols.insert(
+        symbol::TG_NEW_USER_LINK.to_string(),
+        Symbol {
+            name: symbol::TG_NEW_USER_LINK.to_string(),
+            score: new_user_link::SCORE,
+            metric_score: new_user_link::SCORE,
+            description: Some("Link posted soon after joining".to_string()),
+            options: Some(vec![host]),
+        },
+    );
+    Ok(())
+}
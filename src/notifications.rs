@@ -0,0 +1,105 @@
+//! Severity filter for proactive admin alerts.
+//!
+//! Every alert the bot sends on its own (moderation actions, dry-run reports,
+//! escalated member reports) carries a `Severity`. It is only sent when the
+//! severity reaches the `notification_level` configured in the admin panel
+//! settings; `none` suppresses all of them.
+//!
+//! Alerts go to the chat's admin chat, or to the chat itself if it has none.
+//! Chats in silent mode (the opt-in `silent_mode` feature) never get a
+//! message from the bot: their alerts only reach the admin chat, and
+//! `send_alert` and `post_in_chat` log what they would have posted instead.
+
+use redis::Commands;
+use teloxide::prelude::*;
+
+use crate::config::{action, is_feature_enabled, key, notification, reputation, SILENT_MODE_FEATURE};
+use crate::outgoing::throttle;
+
+/// How important an alert is, least important first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+/// Severity of alerts about `action` being taken (or, in dry-run, proposed).
+pub fn action_severity(action: &str) -> Severity {
+    match action {
+        action::BAN | action::MUTE => Severity::High,
+        action::DELETE => Severity::Medium,
+        _ => Severity::Low,
+    }
+}
+
+/// Whether an alert of `severity` passes the configured `level`.
+///
+/// Unknown levels behave like the default, so a bad setting never silences alerts.
+pub fn should_notify(severity: Severity, level: &str) -> bool {
+    let minimum = match level {
+        notification::NONE => return false,
+        notification::HIGH => Severity::High,
+        notification::MEDIUM => Severity::Medium,
+        _ => Severity::Low,
+    };
+    severity >= minimum
+}
+
+/// Reads the configured notification level, falling back to `notification::DEFAULT_LEVEL`.
+pub fn notification_level(conn: &mut redis::Connection) -> String {
+    conn.hget::<_, _, Option<String>>(key::ns(reputation::SETTINGS_KEY), notification::LEVEL)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| notification::DEFAULT_LEVEL.to_string())
+}
+
+/// Whether an alert of `severity` should be sent under the configured level.
+pub fn alert_enabled(conn: &mut redis::Connection, severity: Severity) -> bool {
+    should_notify(severity, &notification_level(conn))
+}
+
+/// Whether the bot must not post in `chat_id`.
+pub fn is_silent(conn: &mut redis::Connection, chat_id: ChatId) -> bool {
+    is_feature_enabled(conn, chat_id.0, SILENT_MODE_FEATURE)
+}
+
+/// Where an alert about `chat_id` goes: its admin chat, else the chat itself
+/// unless it is silent.
+pub fn alert_target(conn: &mut redis::Connection, chat_id: ChatId, admin_chat: Option<ChatId>) -> Option<ChatId> {
+    admin_chat.or_else(|| (!is_silent(conn, chat_id)).then_some(chat_id))
+}
+
+/// Sends an alert of `severity` about `chat_id` to `alert_target`, if the
+/// notification level lets it through.
+pub async fn send_alert(
+    bot: &Bot,
+    conn: &mut redis::Connection,
+    chat_id: ChatId,
+    admin_chat: Option<ChatId>,
+    severity: Severity,
+    text: String,
+) -> ResponseResult<()> {
+    if !alert_enabled(conn, severity) {
+        tracing::info!("Alert suppressed by notification level: {}", text);
+        return Ok(());
+    }
+    let Some(target) = alert_target(conn, chat_id, admin_chat) else {
+        tracing::info!("Alert not posted, chat {} is silent and has no admin chat: {}", chat_id, text);
+        return Ok(());
+    };
+    throttle(target).await;
+    bot.send_message(target, text).await?;
+    Ok(())
+}
+
+/// Posts `text` in `chat_id` unless the chat is silent.
+pub async fn post_in_chat(bot: &Bot, conn: &mut redis::Connection, chat_id: ChatId, text: String) -> ResponseResult<()> {
+    if is_silent(conn, chat_id) {
+        tracing::info!("Message not posted, chat {} is silent: {}", chat_id, text);
+        return Ok(());
+    }
+    throttle(chat_id).await;
+    bot.send_message(chat_id, text).await?;
+    Ok(())
+}
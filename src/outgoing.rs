@@ -0,0 +1,111 @@
+//! Throttling of the bot's own Telegram requests.
+//!
+//! Telegram answers with 429 once a bot makes more than about 30 requests a
+//! second overall or sends 20 messages a minute to one group, and a spam
+//! burst easily makes the bot's deletions, warnings and admin alerts exceed
+//! that. Moderation paths therefore call `throttle` before sending a message
+//! and `throttle_request` before other requests: both wait for the next free
+//! slot, so a burst is spread out in order instead of being dropped.
+//! The rates come from `TELEGRAM_GLOBAL_RATE` (per second) and
+//! `TELEGRAM_CHAT_RATE` (per minute).
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use teloxide::types::ChatId;
+use tokio::time::Instant;
+
+use crate::config::outgoing;
+
+static THROTTLE: Lazy<Throttle> = Lazy::new(Throttle::from_env);
+
+/// Hands out send slots no closer than the global and per-chat intervals.
+#[derive(Debug)]
+pub struct Throttle {
+    global_interval: Duration,
+    chat_interval: Duration,
+    slots: Mutex<Slots>,
+}
+
+#[derive(Debug, Default)]
+struct Slots {
+    /// Earliest instant of the next request to any chat.
+    global: Option<Instant>,
+    /// Earliest instant of the next request per chat.
+    chats: HashMap<ChatId, Instant>,
+}
+
+impl Throttle {
+    /// A throttle allowing `global_per_second` requests a second overall and
+    /// `chat_per_minute` a minute per chat. Non-positive rates don't limit.
+    pub fn new(global_per_second: f64, chat_per_minute: f64) -> Self {
+        let interval = |per_second: f64| {
+            if per_second > 0.0 {
+                Duration::from_secs_f64(1.0 / per_second)
+            } else {
+                Duration::ZERO
+            }
+        };
+        Self {
+            global_interval: interval(global_per_second),
+            chat_interval: interval(chat_per_minute / 60.0),
+            slots: Mutex::new(Slots::default()),
+        }
+    }
+
+    /// A throttle with the rates from the environment or the defaults.
+    pub fn from_env() -> Self {
+        let rate = |name: &str, default: f64| {
+            std::env::var(name).ok().and_then(|rate| rate.trim().parse::<f64>().ok()).unwrap_or(default)
+        };
+        Self::new(
+            rate(outgoing::GLOBAL_RATE_ENV, outgoing::DEFAULT_GLOBAL_PER_SECOND),
+            rate(outgoing::CHAT_RATE_ENV, outgoing::DEFAULT_CHAT_PER_MINUTE),
+        )
+    }
+
+    /// Reserves the next free slot of `chat_id`.
+    fn reserve_chat(&self, chat_id: ChatId) -> Instant {
+        let now = Instant::now();
+        let mut slots = self.slots.lock().unwrap();
+        // Chats whose slot has passed are as good as new
+        slots.chats.retain(|_, next| *next > now);
+        let slot = slots.chats.get(&chat_id).copied().map_or(now, |next| next.max(now));
+        slots.chats.insert(chat_id, slot + self.chat_interval);
+        slot
+    }
+
+    /// Reserves the next free slot across all chats.
+    fn reserve_global(&self) -> Instant {
+        let now = Instant::now();
+        let mut slots = self.slots.lock().unwrap();
+        let slot = slots.global.map_or(now, |next| next.max(now));
+        slots.global = Some(slot + self.global_interval);
+        slot
+    }
+
+    /// Waits until a message may be sent to `chat_id`.
+    pub async fn wait(&self, chat_id: ChatId) {
+        // The global slot is only taken once the chat's turn has come, so a
+        // burst in one chat doesn't hold up the others
+        tokio::time::sleep_until(self.reserve_chat(chat_id)).await;
+        self.wait_request().await;
+    }
+
+    /// Waits until a request other than sending a message may be made.
+    pub async fn wait_request(&self) {
+        tokio::time::sleep_until(self.reserve_global()).await;
+    }
+}
+
+/// Waits until the bot may send another message to `chat_id`.
+pub async fn throttle(chat_id: ChatId) {
+    THROTTLE.wait(chat_id).await;
+}
+
+/// Waits until the bot may make another request such as a deletion or a mute.
+pub async fn throttle_request() {
+    THROTTLE.wait_request().await;
+}
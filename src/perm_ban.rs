@@ -0,0 +1,99 @@
+//! What happens to a repeat offender once `TG_PERM_BAN` fires.
+//!
+//! Each chat picks a `PermBanAction` in the `perm_ban_action` field of its
+//! `tg:chats:<id>` hash (see `/permbanaction`): `ban` removes the user for
+//! good, `kick` removes them but lets them join again, and `report` only
+//! tells the admins. Rspamd counts the chat's permanent bans in its
+//! `perm_banned` field whatever the action.
+
+use redis::{Commands, RedisResult};
+use teloxide::prelude::*;
+
+use crate::config::{action, field, key, perm_ban};
+
+/// How a chat enforces `TG_PERM_BAN`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermBanAction {
+    /// Ban the user from the chat for good.
+    Ban,
+    /// Remove the user from the chat; they may join again.
+    Kick,
+    /// Only tell the admins.
+    Report,
+}
+
+impl PermBanAction {
+    /// Name stored in the chat hash and used by `/permbanaction`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermBanAction::Ban => perm_ban::BAN,
+            PermBanAction::Kick => perm_ban::KICK,
+            PermBanAction::Report => perm_ban::REPORT,
+        }
+    }
+
+    /// The action called `name`, if any.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            perm_ban::BAN => Some(PermBanAction::Ban),
+            perm_ban::KICK => Some(PermBanAction::Kick),
+            perm_ban::REPORT => Some(PermBanAction::Report),
+            _ => None,
+        }
+    }
+
+    /// What was done, for admin alerts.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            PermBanAction::Ban => "Permanently banned",
+            PermBanAction::Kick => "Kicked",
+            PermBanAction::Report => "Reported",
+        }
+    }
+}
+
+fn chat_key(chat_id: ChatId) -> String {
+    format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0)
+}
+
+/// How `chat_id` enforces `TG_PERM_BAN`, `ban` unless the chat chose otherwise.
+pub fn perm_ban_action(conn: &mut redis::Connection, chat_id: ChatId) -> PermBanAction {
+    conn.hget::<_, _, Option<String>>(chat_key(chat_id), field::PERM_BAN_ACTION)
+        .ok()
+        .flatten()
+        .and_then(|name| PermBanAction::parse(&name))
+        .unwrap_or(PermBanAction::Ban)
+}
+
+/// Sets how `chat_id` enforces `TG_PERM_BAN`, or goes back to the default with `None`.
+pub fn set_perm_ban_action(conn: &mut redis::Connection, chat_id: ChatId, perm_ban_action: Option<PermBanAction>) -> RedisResult<()> {
+    match perm_ban_action {
+        Some(perm_ban_action) => conn.hset(chat_key(chat_id), field::PERM_BAN_ACTION, perm_ban_action.as_str()),
+        None => conn.hdel(chat_key(chat_id), field::PERM_BAN_ACTION),
+    }
+}
+
+/// `action` for a message whose scan fired `TG_PERM_BAN` (`perm_ban` is the
+/// chat's action then): raised to a ban unless the chat only reports them.
+pub fn escalate_perm_ban(action: &'static str, perm_ban: Option<PermBanAction>) -> &'static str {
+    match perm_ban {
+        Some(PermBanAction::Ban | PermBanAction::Kick) => action::BAN,
+        _ => action,
+    }
+}
+
+/// Removes `user_id` from `chat_id` as `perm_ban_action` says; `Report` leaves them be.
+pub async fn enforce_perm_ban(bot: &Bot, chat_id: ChatId, user_id: UserId, perm_ban_action: PermBanAction) -> ResponseResult<()> {
+    match perm_ban_action {
+        PermBanAction::Ban => {
+            bot.ban_chat_member(chat_id, user_id).await?;
+        }
+        PermBanAction::Kick => {
+            // A ban followed by an unban removes the member without keeping them out
+            bot.ban_chat_member(chat_id, user_id).await?;
+            bot.unban_chat_member(chat_id, user_id).only_if_banned(true).await?;
+        }
+        PermBanAction::Report => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! Message comparison used by `TG_REPEAT`.
+//!
+//! Mirrors `normalize_repeat` and `is_repeat` in `telegram_simple.lua`.
+//! Messages are compared in normalized form so appending a counter
+//! ("buy now 1", "buy now 2") or toggling spaces doesn't start a new count.
+
+/// Messages longer than this (in characters, normalized) only repeat when
+/// equal, keeping the edit distance cheap.
+pub const MAX_FUZZY_CHARS: usize = 500;
+
+/// Lowercases `text`, collapses whitespace runs into one space and strips
+/// leading whitespace and trailing digits, whitespace and punctuation.
+pub fn normalize(text: &str) -> String {
+    let collapsed = text.to_lowercase().split_ascii_whitespace().collect::<Vec<_>>().join(" ");
+    collapsed
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c.is_ascii_whitespace() || c.is_ascii_punctuation())
+        .to_string()
+}
+
+/// Similarity of two strings between 0 and 1: one minus their edit distance
+/// (in characters) over the longer one's length.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+/// Whether `text` repeats the previous message, stored normalized as `last`.
+/// With `min_similarity` below 1, near-identical normalized messages count
+/// too, unless either is longer than `MAX_FUZZY_CHARS`. Messages that
+/// normalize to nothing (just digits or punctuation) never repeat.
+pub fn is_repeat(last: &str, text: &str, min_similarity: f64) -> bool {
+    let text = normalize(text);
+    if text.is_empty() {
+        return false;
+    }
+    if text == last {
+        return true;
+    }
+    if min_similarity >= 1.0
+        || last.chars().count() > MAX_FUZZY_CHARS
+        || text.chars().count() > MAX_FUZZY_CHARS
+    {
+        return false;
+    }
+    similarity(last, &text) >= min_similarity
+}
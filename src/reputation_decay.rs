@@ -0,0 +1,103 @@
+use crate::config::{field, key, reputation};
+use redis::Commands;
+use std::collections::HashSet;
+use std::error::Error;
+
+/// Periodically lowers users' `rep` so past offences fade over time.
+pub struct ReputationDecay {
+    redis_client: redis::Client,
+}
+
+impl ReputationDecay {
+    pub fn new() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let redis_client = redis::Client::open("redis://127.0.0.1/")?;
+        Ok(ReputationDecay { redis_client })
+    }
+
+    /// Read the decay rate and floor set with `/setthreshold`.
+    ///
+    /// # Returns
+    /// `(rate, floor)`, falling back to the defaults for missing or invalid values
+    pub fn decay_settings(&self) -> Result<(i64, i64), Box<dyn Error + Send + Sync>> {
+        let mut redis_conn = self.redis_client.get_connection()?;
+        let rate: Option<String> = redis_conn.hget(key::ns(reputation::SETTINGS_KEY), reputation::DECAY_RATE)?;
+        let floor: Option<String> = redis_conn.hget(key::ns(reputation::SETTINGS_KEY), reputation::DECAY_FLOOR)?;
+
+        let rate = rate
+            .and_then(|v| v.parse::<i64>().ok())
+            .filter(|v| *v >= 0)
+            .unwrap_or(reputation::DEFAULT_DECAY_RATE);
+        let floor = floor
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(reputation::DEFAULT_DECAY_FLOOR);
+
+        Ok((rate, floor))
+    }
+
+    /// Run one decay cycle over all users.
+    ///
+    /// Users above the floor lose `rate` points (never dropping below the floor),
+    /// users without a `rep` field are initialized to 0, and currently banned
+    /// users are skipped so decay does not lift their ban.
+    ///
+    /// User keys are walked with a `SCAN` cursor and each batch is read and
+    /// updated with a single pipeline, so Redis is never blocked by `KEYS`.
+    ///
+    /// # Returns
+    /// The number of user keys visited
+    pub async fn run_decay_cycle(&self) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let (rate, floor) = self.decay_settings()?;
+        let mut redis_conn = self.redis_client.get_connection()?;
+
+        let pattern = format!("{}*", key::ns(key::TG_USERS_PREFIX));
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut cursor: u64 = 0;
+
+        loop {
+            let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(reputation::SCAN_BATCH_SIZE)
+                .query(&mut redis_conn)?;
+
+            // SCAN may return a key more than once; only decay each user once per cycle
+            let keys: Vec<String> = batch.into_iter().filter(|k| seen.insert(k.clone())).collect();
+
+            if !keys.is_empty() {
+                let mut read = redis::pipe();
+                for key in &keys {
+                    read.cmd("HMGET").arg(key).arg(field::REP).arg(field::BANNED);
+                }
+                let states: Vec<(Option<i64>, Option<String>)> = read.query(&mut redis_conn)?;
+
+                let mut write = redis::pipe();
+                for (key, (rep, banned)) in keys.iter().zip(states) {
+                    if banned.is_some() {
+                        continue;
+                    }
+                    match rep {
+                        Some(rep_value) if rep_value > floor && rate > 0 => {
+                            let decrement = rate.min(rep_value - floor);
+                            write.hincr(key, field::REP, -decrement).ignore();
+                        }
+                        Some(_) => {}
+                        // User doesn't have a reputation field yet, initialize it to 0
+                        None => {
+                            write.hset(key, field::REP, 0).ignore();
+                        }
+                    }
+                }
+                let _: () = write.query(&mut redis_conn)?;
+            }
+
+            cursor = next_cursor;
+            if cursor == 0 {
+                break;
+            }
+        }
+
+        Ok(seen.len())
+    }
+}
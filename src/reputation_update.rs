@@ -0,0 +1,83 @@
+//! Atomic updates of a user's `rep` field.
+//!
+//! Several scans of one user can run at the same time, so `rep` is never read,
+//! changed and written back: plain changes use `HINCRBY`, and changes that
+//! depend on the current value (the ban decrement, the suspicious bump) run
+//! as one Redis script each, so no update is lost and a reputation that
+//! crosses the ban threshold bans only once. `BAN_SCRIPT` is also used by
+//! `tg_ban_cb` in `telegram_simple.lua`.
+
+use once_cell::sync::Lazy;
+use redis::{Commands, RedisResult, Script};
+
+use crate::config::{field, key};
+
+/// Bans the user if `rep` is above `ARGV[1]`: lowers `rep` by `ARGV[2]`,
+/// sets `banned`/`banned_in` (`ARGV[3]` is the chat) and counts the ban for
+/// the user and the chat. Returns the user's new ban count, or nil if `rep`
+/// wasn't above the threshold.
+///
+/// `KEYS[1]` is the user hash, `KEYS[2]` the chat hash.
+pub const BAN_SCRIPT: &str = r#"
+local rep = tonumber(redis.call('HGET', KEYS[1], 'rep')) or 0
+if rep <= tonumber(ARGV[1]) then
+    return false
+end
+redis.call('HINCRBY', KEYS[1], 'rep', -tonumber(ARGV[2]))
+redis.call('HSET', KEYS[1], 'banned', '1', 'banned_in', ARGV[3])
+redis.call('HINCRBY', KEYS[2], 'banned', 1)
+return redis.call('HINCRBY', KEYS[1], 'banned_q', 1)
+"#;
+
+/// Adds `ARGV[2]` to `rep` if it is above `ARGV[1]`. Returns the new
+/// reputation, or nil if `rep` wasn't above the threshold.
+///
+/// `KEYS[1]` is the user hash.
+pub const ADD_IF_ABOVE_SCRIPT: &str = r#"
+local rep = tonumber(redis.call('HGET', KEYS[1], 'rep')) or 0
+if rep <= tonumber(ARGV[1]) then
+    return false
+end
+return redis.call('HINCRBY', KEYS[1], 'rep', ARGV[2])
+"#;
+
+static BAN: Lazy<Script> = Lazy::new(|| Script::new(BAN_SCRIPT));
+static ADD_IF_ABOVE: Lazy<Script> = Lazy::new(|| Script::new(ADD_IF_ABOVE_SCRIPT));
+
+fn user_key(user_id: u64) -> String {
+    format!("{}{}", key::ns(key::TG_USERS_PREFIX), user_id)
+}
+
+/// Adds `delta` to the user's reputation and returns the new value.
+pub fn add_rep(conn: &mut redis::Connection, user_id: u64, delta: i64) -> RedisResult<i64> {
+    conn.hincr(user_key(user_id), field::REP, delta)
+}
+
+/// Sets the user's reputation to 0 unless it is already set, without
+/// touching a value a concurrent scan may have written.
+pub fn init_rep(conn: &mut redis::Connection, user_id: u64) -> RedisResult<bool> {
+    conn.hset_nx(user_key(user_id), field::REP, 0)
+}
+
+/// Adds `delta` to the user's reputation if it is above `threshold`;
+/// returns the new reputation when it was.
+pub fn add_rep_if_above(conn: &mut redis::Connection, user_id: u64, threshold: i64, delta: i64) -> RedisResult<Option<i64>> {
+    ADD_IF_ABOVE.key(user_key(user_id)).arg(threshold).arg(delta).invoke(conn)
+}
+
+/// Bans the user in `chat_id` if their reputation is above `threshold`,
+/// lowering it by `penalty`; returns the user's new ban count when banned.
+pub fn ban_if_above(
+    conn: &mut redis::Connection,
+    user_id: u64,
+    chat_id: i64,
+    threshold: i64,
+    penalty: i64,
+) -> RedisResult<Option<i64>> {
+    BAN.key(user_key(user_id))
+        .key(format!("{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id))
+        .arg(threshold)
+        .arg(penalty)
+        .arg(chat_id)
+        .invoke(conn)
+}
@@ -0,0 +1,50 @@
+//! Unicode script detection used by the per-chat script allowlist (`TG_FOREIGN_SCRIPT`).
+//!
+//! The ranges mirror `settings.scripts` in `telegram_simple.lua`.
+
+/// Script names accepted by `/allowscript`, paired with their Unicode ranges.
+pub const SCRIPTS: &[(&str, &[(u32, u32)])] = &[
+    ("latin", &[(0x0041, 0x005A), (0x0061, 0x007A), (0x00C0, 0x024F)]),
+    ("greek", &[(0x0370, 0x03FF)]),
+    ("cyrillic", &[(0x0400, 0x052F)]),
+    ("hebrew", &[(0x0590, 0x05FF)]),
+    ("arabic", &[(0x0600, 0x06FF), (0x0750, 0x077F)]),
+    ("devanagari", &[(0x0900, 0x097F)]),
+    ("thai", &[(0x0E00, 0x0E7F)]),
+    ("hangul", &[(0x1100, 0x11FF), (0xAC00, 0xD7AF)]),
+    ("cjk", &[(0x3040, 0x30FF), (0x4E00, 0x9FFF)]),
+];
+
+/// Returns true if `name` is a known script.
+pub fn is_known_script(name: &str) -> bool {
+    SCRIPTS.iter().any(|(script, _)| *script == name)
+}
+
+/// Returns the known script `c` belongs to, if any.
+pub fn script_of(c: char) -> Option<&'static str> {
+    let cp = c as u32;
+    SCRIPTS
+        .iter()
+        .find(|(_, ranges)| ranges.iter().any(|(lo, hi)| cp >= *lo && cp <= *hi))
+        .map(|(script, _)| *script)
+}
+
+/// Returns the script most characters of `text` belong to, or `None` if no
+/// character falls into a known script (e.g. digits and emoji only).
+pub fn dominant_script(text: &str) -> Option<&'static str> {
+    let mut counts = vec![0usize; SCRIPTS.len()];
+    for c in text.chars() {
+        if let Some(script) = script_of(c) {
+            if let Some(i) = SCRIPTS.iter().position(|(name, _)| *name == script) {
+                counts[i] += 1;
+            }
+        }
+    }
+
+    counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .max_by_key(|(i, count)| (**count, std::cmp::Reverse(*i)))
+        .map(|(i, _)| SCRIPTS[i].0)
+}
@@ -0,0 +1,54 @@
+//! URL shorteners behind `TG_SHORTENER`.
+//!
+//! `telegram_simple.lua` flags a link whose host is a shortener or one of its
+//! subdomains, so `notbit.ly.example.com` doesn't count as `bit.ly`. Admins
+//! manage the shorteners in `tg:shorteners` with `/shortener`; while that set
+//! is empty `shortener::DEFAULT_DOMAINS` apply, and the first edit seeds it
+//! with them so adding a shortener doesn't drop the defaults.
+
+use std::collections::HashSet;
+
+use redis::{Commands, RedisResult};
+
+use crate::config::{key, shortener};
+
+/// Lowercased shortener domains.
+pub fn shorteners(conn: &mut redis::Connection) -> RedisResult<HashSet<String>> {
+    let configured: HashSet<String> = conn.smembers(key::ns(key::TG_SHORTENERS_KEY))?;
+    if configured.is_empty() {
+        return Ok(shortener::DEFAULT_DOMAINS.iter().map(|domain| domain.to_string()).collect());
+    }
+    Ok(configured.into_iter().map(|domain| normalize_domain(&domain)).collect())
+}
+
+/// Fills an empty `tg:shorteners` with `shortener::DEFAULT_DOMAINS`. Returns
+/// whether it did.
+pub fn seed_defaults(conn: &mut redis::Connection) -> RedisResult<bool> {
+    let shorteners_key = key::ns(key::TG_SHORTENERS_KEY);
+    if conn.exists(&shorteners_key)? {
+        return Ok(false);
+    }
+    let _: () = conn.sadd(&shorteners_key, shortener::DEFAULT_DOMAINS)?;
+    Ok(true)
+}
+
+/// `domain` lowercased and without a trailing dot, as stored in `tg:shorteners`.
+pub fn normalize_domain(domain: &str) -> String {
+    domain.trim().trim_end_matches('.').to_lowercase()
+}
+
+/// Whether `host` is one of `shorteners` or a subdomain of one; mirrors
+/// `host_in_set` in `telegram_simple.lua`.
+pub fn is_shortener_host(shorteners: &HashSet<String>, host: &str) -> bool {
+    let host = normalize_domain(host);
+    let mut domain = host.as_str();
+    loop {
+        if shorteners.contains(domain) {
+            return true;
+        }
+        match domain.split_once('.') {
+            Some((_, parent)) => domain = parent,
+            None => return false,
+        }
+    }
+}
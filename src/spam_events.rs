@@ -0,0 +1,101 @@
+//! Timestamped log of ban/suspicious detections (`spam:<uuid>` hashes).
+//!
+//! `scan_msg` records an event whenever one of `config::spam_event::SYMBOLS`
+//! fires, and the admin panel dashboard counts the recent ones. Every event
+//! expires after `spam_event::TTL`, so the log prunes itself. Events carry a
+//! `reason` (see `describe_reason`) that ban notifications repeat.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use redis::{Commands, RedisResult};
+use rspamd_client::protocol::scan::Symbol;
+use teloxide::types::{ChatId, UserId};
+use uuid::Uuid;
+
+use crate::config::{key, spam_event};
+
+/// Describes why a user was acted on: the symbols that fired besides the
+/// reputation verdicts in `spam_event::SYMBOLS`, highest score first, followed
+/// by the user's reputation, e.g. `"TG_LINK_SPAM, TG_CAPS, rep 21"`.
+pub fn describe_reason(symbols: &HashMap<String, Symbol>, rep: i64) -> String {
+    let mut fired: Vec<&Symbol> = symbols
+        .values()
+        .filter(|s| !spam_event::SYMBOLS.contains(&s.name.as_str()))
+        .collect();
+    fired.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+
+    let mut parts: Vec<String> = fired.into_iter().map(|s| s.name.clone()).collect();
+    parts.push(format!("rep {}", rep));
+    parts.join(", ")
+}
+
+/// Logs a spam event and returns its key.
+pub fn record_spam_event(
+    conn: &mut redis::Connection,
+    chat_id: ChatId,
+    user_id: UserId,
+    symbols: &[&str],
+    reason: &str,
+) -> RedisResult<String> {
+    let event_key = format!("{}{}", key::ns(key::SPAM_EVENT_PREFIX), Uuid::new_v4());
+    redis::pipe()
+        .hset_multiple(
+            &event_key,
+            &[
+                ("timestamp", Utc::now().timestamp().to_string()),
+                ("chat_id", chat_id.0.to_string()),
+                ("user_id", user_id.0.to_string()),
+                ("symbols", symbols.join(",")),
+                ("reason", reason.to_string()),
+            ],
+        )
+        .ignore()
+        .expire(&event_key, spam_event::TTL)
+        .ignore()
+        .query::<()>(conn)?;
+    Ok(event_key)
+}
+
+/// Counts the logged spam events with a timestamp at or after `since`.
+pub fn count_spam_events_since(conn: &mut redis::Connection, since: i64) -> RedisResult<usize> {
+    let event_keys: Vec<String> = conn
+        .scan_match::<_, String>(format!("{}*", key::ns(key::SPAM_EVENT_PREFIX)))?
+        .collect();
+    let mut count = 0;
+    for event_key in event_keys {
+        let timestamp: Option<i64> = conn.hget(&event_key, "timestamp")?;
+        if timestamp.is_some_and(|ts| ts >= since) {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Users with a logged event in `chat_id` at or after `since` that includes
+/// one of `symbols`, without duplicates, in ascending order.
+pub fn users_with_events_since(
+    conn: &mut redis::Connection,
+    chat_id: ChatId,
+    symbols: &[&str],
+    since: i64,
+) -> RedisResult<Vec<u64>> {
+    let event_keys: Vec<String> = conn
+        .scan_match::<_, String>(format!("{}*", key::ns(key::SPAM_EVENT_PREFIX)))?
+        .collect();
+    let chat = chat_id.0.to_string();
+    let mut users = Vec::new();
+    for event_key in event_keys {
+        let event: HashMap<String, String> = conn.hgetall(&event_key)?;
+        let recent = event.get("timestamp").and_then(|ts| ts.parse::<i64>().ok()).is_some_and(|ts| ts >= since);
+        let matches = event.get("symbols").is_some_and(|fired| fired.split(',').any(|s| symbols.contains(&s)));
+        if recent && matches && event.get("chat_id") == Some(&chat) {
+            if let Some(user_id) = event.get("user_id").and_then(|id| id.parse::<u64>().ok()) {
+                users.push(user_id);
+            }
+        }
+    }
+    users.sort_unstable();
+    users.dedup();
+    Ok(users)
+}
@@ -0,0 +1,88 @@
+//! Daily spam action counters behind `/trend`.
+//!
+//! `handle_message` bumps the action's field in the chat's
+//! `tg:chats:<id>:daily:<YYYY-MM-DD>` hash whenever it warns, deletes or bans,
+//! and `scan_msg` counts the symbols of every scan in
+//! `tg:chats:<id>:daily_symbols:<YYYY-MM-DD>` for the daily summary.
+//! Buckets expire after `trend::RETENTION_DAYS`, so old days drop out on their own.
+
+use std::collections::HashMap;
+
+use chrono::{Days, NaiveDate};
+use redis::RedisResult;
+use teloxide::types::ChatId;
+
+use crate::config::{key, suffix, trend};
+
+/// Sparkline levels, lowest first.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Key of a chat's counters for `day`.
+pub fn daily_key(chat_id: ChatId, day: NaiveDate) -> String {
+    format!("{}{}{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0, suffix::DAILY, day.format("%Y-%m-%d"))
+}
+
+/// Key of a chat's per-symbol counters for `day`.
+pub fn daily_symbols_key(chat_id: ChatId, day: NaiveDate) -> String {
+    format!("{}{}{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0, suffix::DAILY_SYMBOLS, day.format("%Y-%m-%d"))
+}
+
+/// Counts one `action` taken in the chat on `day`.
+pub fn record_daily_action(conn: &mut redis::Connection, chat_id: ChatId, action: &str, day: NaiveDate) -> RedisResult<()> {
+    let bucket = daily_key(chat_id, day);
+    redis::pipe()
+        .hincr(&bucket, action, 1).ignore()
+        .expire(&bucket, trend::RETENTION_DAYS * 24 * 60 * 60).ignore()
+        .query(conn)
+}
+
+/// Counts each of `symbols` once in the chat's bucket for `day`.
+pub fn record_daily_symbols<'a>(
+    conn: &mut redis::Connection,
+    chat_id: ChatId,
+    symbols: impl IntoIterator<Item = &'a String>,
+    day: NaiveDate,
+) -> RedisResult<()> {
+    let bucket = daily_symbols_key(chat_id, day);
+    let mut pipe = redis::pipe();
+    for name in symbols {
+        pipe.hincr(&bucket, name, 1).ignore();
+    }
+    pipe.expire(&bucket, trend::RETENTION_DAYS * 24 * 60 * 60).ignore();
+    pipe.query(conn)
+}
+
+/// Actions taken per day over the `days` days ending with `today`, oldest first.
+/// Days without a bucket count as zero.
+pub fn daily_totals(conn: &mut redis::Connection, chat_id: ChatId, days: u64, today: NaiveDate) -> RedisResult<Vec<(NaiveDate, i64)>> {
+    let dates: Vec<NaiveDate> = (0..days)
+        .rev()
+        .filter_map(|back| today.checked_sub_days(Days::new(back)))
+        .collect();
+    let mut pipe = redis::pipe();
+    for day in &dates {
+        pipe.hgetall(daily_key(chat_id, *day));
+    }
+    let buckets: Vec<HashMap<String, i64>> = pipe.query(conn)?;
+    Ok(dates
+        .into_iter()
+        .zip(buckets)
+        .map(|(day, counts)| (day, counts.values().sum()))
+        .collect())
+}
+
+/// Renders daily totals as a sparkline followed by one `date bar count` line per day.
+pub fn render_trend(totals: &[(NaiveDate, i64)]) -> String {
+    let max = totals.iter().map(|(_, count)| *count).max().unwrap_or(0).max(1);
+    let spark = |count: i64| {
+        let level = (count.max(0) * (SPARKS.len() as i64 - 1) + max - 1) / max;
+        SPARKS[level as usize]
+    };
+
+    let mut out: String = totals.iter().map(|(_, count)| spark(*count)).collect();
+    out.push('\n');
+    for (day, count) in totals {
+        out.push_str(&format!("{} {} {}\n", day.format("%Y-%m-%d"), spark(*count), count));
+    }
+    out
+}
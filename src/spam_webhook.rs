@@ -0,0 +1,78 @@
+//! Optional push of spam events to an external HTTP endpoint.
+//!
+//! When `SPAM_WEBHOOK_URL` is set, every event `scan_msg` logs (see
+//! `spam_events`) is also POSTed there as a `SpamWebhookPayload` JSON body.
+//! Delivery runs in its own task and gives up after `webhook::ATTEMPTS`
+//! tries, so a slow or unreachable endpoint never holds up scanning.
+
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::config::webhook;
+
+static CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .timeout(Duration::from_secs(webhook::TIMEOUT_SECS))
+        .build()
+        .unwrap_or_default()
+});
+
+/// JSON body sent for a spam event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpamWebhookPayload {
+    pub chat_id: i64,
+    pub user_id: u64,
+    /// Event symbols that fired (`TG_BAN`, `TG_PERM_BAN`, `TG_SUSPICIOUS`).
+    pub events: Vec<String>,
+    /// Every symbol of the scan, sorted by name.
+    pub symbols: Vec<String>,
+    pub score: f64,
+    /// Unix timestamp of the scan.
+    pub timestamp: i64,
+}
+
+/// The configured webhook URL, if any.
+pub fn webhook_url() -> Option<String> {
+    std::env::var(webhook::URL_ENV).ok().filter(|url| !url.trim().is_empty())
+}
+
+/// POSTs `payload` to `url`, retrying failures with exponential backoff.
+/// Returns the last error once every attempt has failed.
+pub async fn deliver(url: &str, payload: &SpamWebhookPayload) -> Result<(), reqwest::Error> {
+    let mut backoff = Duration::from_millis(webhook::BACKOFF_MS);
+    let mut attempt = 1;
+    loop {
+        let result = CLIENT
+            .post(url)
+            .json(payload)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match result {
+            Ok(_) => return Ok(()),
+            Err(e) if attempt < webhook::ATTEMPTS => {
+                log::warn!("Spam webhook failed (attempt {} of {}), retrying in {:?}: {}", attempt, webhook::ATTEMPTS, backoff, e);
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Sends `payload` to the configured webhook in the background; does nothing
+/// when `SPAM_WEBHOOK_URL` isn't set.
+pub fn notify_spam_event(payload: SpamWebhookPayload) {
+    let Some(url) = webhook_url() else {
+        return;
+    };
+    tokio::spawn(async move {
+        if let Err(e) = deliver(&url, &payload).await {
+            log::warn!("Dropping spam webhook for chat {} after {} attempts: {}", payload.chat_id, webhook::ATTEMPTS, e);
+        }
+    });
+}
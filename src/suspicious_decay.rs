@@ -0,0 +1,56 @@
+//! Faster way back for suspicious users who behave.
+//!
+//! Once `rep` is above the suspicious threshold, `TG_SUSPICIOUS` adds a
+//! point on every message, so a user who stopped misbehaving still drifts
+//! towards a ban while the hourly decay takes off only one point. Every
+//! benign message that fires `TG_SUSPICIOUS` therefore takes off the
+//! `suspicious_decay_step` setting (see `/setthreshold`) as well, bringing the user back
+//! below the threshold within a few messages.
+
+use std::collections::HashMap;
+
+use redis::{Commands, RedisResult};
+use rspamd_client::protocol::scan::Symbol;
+use teloxide::types::UserId;
+
+use crate::config::{key, reputation, symbol, symbol_weight};
+use crate::reputation_update::add_rep;
+
+/// Points a benign message takes off a suspicious user's `rep`, falling back
+/// to the default for a missing or negative setting; 0 turns the decay off.
+pub fn decay_step(conn: &mut redis::Connection) -> i64 {
+    conn.hget::<_, _, Option<String>>(key::ns(reputation::SETTINGS_KEY), reputation::SUSPICIOUS_DECAY_STEP)
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|v| *v >= 0)
+        .unwrap_or(reputation::DEFAULT_SUSPICIOUS_DECAY_STEP)
+}
+
+/// Whether a scan that fired `symbols` shows nothing but the suspicion itself.
+pub fn is_benign(symbols: &HashMap<String, Symbol>) -> bool {
+    let offence = symbol_weight::ALL
+        .iter()
+        .map(|(name, _)| *name)
+        .chain(reputation::OFFENCE_SYMBOLS.iter().copied())
+        .any(|name| symbols.contains_key(name));
+    !offence
+}
+
+/// Lowers `user_id`'s `rep` by the decay step if the scan fired
+/// `TG_SUSPICIOUS` and nothing else against them; returns the new reputation
+/// when it was lowered.
+pub fn apply_suspicious_decay(
+    conn: &mut redis::Connection,
+    user_id: UserId,
+    symbols: &HashMap<String, Symbol>,
+) -> RedisResult<Option<i64>> {
+    if !symbols.contains_key(symbol::TG_SUSPICIOUS) || !is_benign(symbols) {
+        return Ok(None);
+    }
+    let step = decay_step(conn);
+    if step == 0 {
+        return Ok(None);
+    }
+    add_rep(conn, user_id.0, -step).map(Some)
+}
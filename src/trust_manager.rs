@@ -1,8 +1,11 @@
 use crate::config::{field, key, suffix, TRUSTED_MESSAGE_TTL, REPLY_TRACKING_TTL, reply_aware, rate_limit, selective_trust};
+use crate::config::reply_aware::anti_evasion;
+use crate::handlers::count_emoji;
 use chrono::{DateTime, Utc};
 use redis::Commands;
+use std::collections::HashMap;
 use std::error::Error;
-use teloxide::types::{ChatId, MessageId, UserId};
+use teloxide::types::{ChatId, Message, MessageId, UserId};
 
 /// Types of trusted messages that can be replied to
 #[derive(Debug, Clone, PartialEq)]
@@ -72,9 +75,14 @@ impl TrustedMessageMetadata {
         }
     }
 
+    /// Whether `user_id` replying to this message would be replying to themselves
+    pub fn is_self_reply(&self, user_id: UserId) -> bool {
+        self.sender_id == user_id
+    }
+
     /// Get the Redis key for this trusted message
     pub fn redis_key(&self) -> String {
-        format!("{}{}", key::TG_TRUSTED_PREFIX, self.message_id.0)
+        format!("{}{}", key::ns(key::TG_TRUSTED_PREFIX), self.message_id.0)
     }
 
     /// Get the metadata Redis key for this trusted message
@@ -83,6 +91,28 @@ impl TrustedMessageMetadata {
     }
 }
 
+/// Anti-evasion thresholds applied to replies in a single chat
+#[derive(Debug, Clone, PartialEq)]
+pub struct AntiEvasionLimits {
+    pub max_links: u32,
+    pub max_phone_numbers: u32,
+    pub max_invite_links: u32,
+    pub max_caps_ratio: f64,
+    pub max_emoji: u32,
+}
+
+impl Default for AntiEvasionLimits {
+    fn default() -> Self {
+        Self {
+            max_links: anti_evasion::MAX_LINKS_IN_REPLY,
+            max_phone_numbers: anti_evasion::MAX_PHONE_NUMBERS_IN_REPLY,
+            max_invite_links: anti_evasion::MAX_INVITE_LINKS_IN_REPLY,
+            max_caps_ratio: anti_evasion::MAX_CAPS_RATIO_IN_REPLY,
+            max_emoji: anti_evasion::MAX_EMOJI_IN_REPLY,
+        }
+    }
+}
+
 /// Manager for handling trusted messages and reply tracking
 pub struct TrustManager {
     redis_client: redis::Client,
@@ -95,6 +125,43 @@ impl TrustManager {
         Ok(Self { redis_client })
     }
 
+    /// Sliding-window rate check backed by a sorted set of event timestamps.
+    ///
+    /// Entries older than `window` seconds are pruned before counting, so the
+    /// limit applies to any rolling window rather than to fixed hourly buckets.
+    /// When `record` is set and the caller is under the limit, the current
+    /// event is added to the window.
+    fn check_sliding_window(
+        conn: &mut redis::Connection,
+        rate_key: &str,
+        max_events: u32,
+        window: i64,
+        record: bool,
+    ) -> redis::RedisResult<bool> {
+        let now = Utc::now();
+        let now_ms = now.timestamp_millis();
+        let window_start = now_ms - window * 1000;
+
+        // Drop events that fell out of the window
+        conn.zrembyscore::<_, _, _, ()>(rate_key, "-inf", window_start)?;
+        let current_count: u32 = conn.zcard(rate_key)?;
+
+        if current_count >= max_events {
+            return Ok(false);
+        }
+
+        if record {
+            // Members must be unique, the score carries the timestamp
+            let member = now.timestamp_nanos_opt().unwrap_or(now_ms).to_string();
+            redis::pipe()
+                .zadd(rate_key, member, now_ms).ignore()
+                .expire(rate_key, window).ignore()
+                .query::<()>(conn)?;
+        }
+
+        Ok(true)
+    }
+
     /// Check if a user can create a trusted message (rate limiting)
     pub async fn can_create_trusted_message(&self, user_id: UserId) -> Result<bool, Box<dyn Error + Send + Sync>> {
         if !reply_aware::ENABLE_RATE_LIMITING {
@@ -102,34 +169,33 @@ impl TrustManager {
         }
         
         let mut conn = self.redis_client.get_connection()?;
-        let rate_key = format!("{}{}", rate_limit::TRUSTED_MESSAGE_RATE_PREFIX, user_id.0);
-        
-        // Check current count
-        let current_count: u32 = conn.get(&rate_key).unwrap_or(0);
-        
-        if current_count >= reply_aware::MAX_TRUSTED_MESSAGES_PER_HOUR {
-            return Ok(false);
-        }
-        
-        // Increment count with TTL
-        conn.set_ex::<_, _, ()>(&rate_key, current_count + 1, reply_aware::TRUSTED_MESSAGE_RATE_WINDOW as u64)?;
-        
-        Ok(true)
+        let rate_key = format!("{}{}", key::ns(rate_limit::TRUSTED_MESSAGE_RATE_PREFIX), user_id.0);
+        
+        Ok(Self::check_sliding_window(
+            &mut conn,
+            &rate_key,
+            reply_aware::MAX_TRUSTED_MESSAGES_PER_HOUR,
+            reply_aware::TRUSTED_MESSAGE_RATE_WINDOW as i64,
+            true,
+        )?)
     }
 
-    /// Check if a user can create a trusted message without incrementing (for testing)
+    /// Check if a user can create a trusted message without recording it (for testing)
     pub async fn can_create_trusted_message_check_only(&self, user_id: UserId) -> Result<bool, Box<dyn Error + Send + Sync>> {
         if !reply_aware::ENABLE_RATE_LIMITING {
             return Ok(true);
         }
         
         let mut conn = self.redis_client.get_connection()?;
-        let rate_key = format!("{}{}", rate_limit::TRUSTED_MESSAGE_RATE_PREFIX, user_id.0);
-        
-        // Check current count without incrementing
-        let current_count: u32 = conn.get(&rate_key).unwrap_or(0);
-        
-        Ok(current_count < reply_aware::MAX_TRUSTED_MESSAGES_PER_HOUR)
+        let rate_key = format!("{}{}", key::ns(rate_limit::TRUSTED_MESSAGE_RATE_PREFIX), user_id.0);
+        
+        Ok(Self::check_sliding_window(
+            &mut conn,
+            &rate_key,
+            reply_aware::MAX_TRUSTED_MESSAGES_PER_HOUR,
+            reply_aware::TRUSTED_MESSAGE_RATE_WINDOW as i64,
+            false,
+        )?)
     }
 
     /// Check if a user can reply to trusted messages (rate limiting)
@@ -139,19 +205,15 @@ impl TrustManager {
         }
         
         let mut conn = self.redis_client.get_connection()?;
-        let rate_key = format!("{}{}", rate_limit::REPLY_RATE_PREFIX, user_id.0);
-        
-        // Check current count
-        let current_count: u32 = conn.get(&rate_key).unwrap_or(0);
-        
-        if current_count >= reply_aware::MAX_REPLIES_PER_HOUR {
-            return Ok(false);
-        }
-        
-        // Increment count with TTL
-        conn.set_ex::<_, _, ()>(&rate_key, current_count + 1, reply_aware::REPLY_RATE_WINDOW as u64)?;
-        
-        Ok(true)
+        let rate_key = format!("{}{}", key::ns(rate_limit::REPLY_RATE_PREFIX), user_id.0);
+        
+        Ok(Self::check_sliding_window(
+            &mut conn,
+            &rate_key,
+            reply_aware::MAX_REPLIES_PER_HOUR,
+            reply_aware::REPLY_RATE_WINDOW as i64,
+            true,
+        )?)
     }
 
     /// Check if a message should be trusted based on selective trusting rules
@@ -201,7 +263,7 @@ impl TrustManager {
     /// Get user reputation score
     pub async fn get_user_reputation(&self, user_id: UserId) -> Result<i64, Box<dyn Error + Send + Sync>> {
         let mut conn = self.redis_client.get_connection()?;
-        let reputation_key = format!("tg:reputation:user:{}", user_id.0);
+        let reputation_key = format!("{}{}", key::ns(key::TG_REPUTATION_USER_PREFIX), user_id.0);
         
         let bad: i64 = conn.hget(&reputation_key, "bad").unwrap_or(0);
         let good: i64 = conn.hget(&reputation_key, "good").unwrap_or(0);
@@ -226,30 +288,57 @@ impl TrustManager {
         Ok(true)
     }
 
+    /// Load anti-evasion thresholds for a chat, falling back to the global defaults
+    pub async fn get_anti_evasion_limits(&self, chat_id: ChatId) -> Result<AntiEvasionLimits, Box<dyn Error + Send + Sync>> {
+        let mut conn = self.redis_client.get_connection()?;
+        let overrides_key = format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), chat_id.0, suffix::ANTI_EVASION);
+        let overrides: HashMap<String, String> = conn.hgetall(&overrides_key).unwrap_or_default();
+
+        let mut limits = AntiEvasionLimits::default();
+        if let Some(v) = overrides.get(anti_evasion::LINKS_FIELD).and_then(|v| v.parse().ok()) {
+            limits.max_links = v;
+        }
+        if let Some(v) = overrides.get(anti_evasion::PHONES_FIELD).and_then(|v| v.parse().ok()) {
+            limits.max_phone_numbers = v;
+        }
+        if let Some(v) = overrides.get(anti_evasion::INVITES_FIELD).and_then(|v| v.parse().ok()) {
+            limits.max_invite_links = v;
+        }
+        if let Some(v) = overrides.get(anti_evasion::CAPS_RATIO_FIELD).and_then(|v| v.parse().ok()) {
+            limits.max_caps_ratio = v;
+        }
+        if let Some(v) = overrides.get(anti_evasion::EMOJI_FIELD).and_then(|v| v.parse().ok()) {
+            limits.max_emoji = v;
+        }
+
+        Ok(limits)
+    }
+
     /// Check for spam patterns in reply content
-    pub async fn check_reply_spam_patterns(&self, text: &str, user_id: UserId) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+    pub async fn check_reply_spam_patterns(&self, text: &str, user_id: UserId, chat_id: ChatId) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
         if !reply_aware::ENABLE_SPAM_MONITORING {
             return Ok(Vec::new());
         }
         
+        let limits = self.get_anti_evasion_limits(chat_id).await?;
         let mut spam_patterns = Vec::new();
         
         // Check for excessive links
         let link_count = text.matches("http").count() + text.matches("https").count();
-        if link_count > reply_aware::anti_evasion::MAX_LINKS_IN_REPLY as usize {
+        if link_count > limits.max_links as usize {
             spam_patterns.push("TG_REPLY_LINK_SPAM".to_string());
         }
         
         // Check for phone numbers
         let phone_regex = regex::Regex::new(r#"\+\d[\d\-\s\(\)]\d\d\d\d"#).unwrap();
         let phone_count = phone_regex.find_iter(text).count();
-        if phone_count > reply_aware::anti_evasion::MAX_PHONE_NUMBERS_IN_REPLY as usize {
+        if phone_count > limits.max_phone_numbers as usize {
             spam_patterns.push("TG_REPLY_PHONE_SPAM".to_string());
         }
         
         // Check for invite links
         let invite_count = text.matches("t.me/joinchat").count() + text.matches("telegram.me/joinchat").count();
-        if invite_count > reply_aware::anti_evasion::MAX_INVITE_LINKS_IN_REPLY as usize {
+        if invite_count > limits.max_invite_links as usize {
             spam_patterns.push("TG_REPLY_INVITE_SPAM".to_string());
         }
         
@@ -258,20 +347,14 @@ impl TrustManager {
         let caps_chars = text.chars().filter(|c| c.is_alphabetic() && c.is_uppercase()).count();
         if total_chars > 0 {
             let caps_ratio = caps_chars as f64 / total_chars as f64;
-            if caps_ratio > reply_aware::anti_evasion::MAX_CAPS_RATIO_IN_REPLY {
+            if caps_ratio > limits.max_caps_ratio {
                 spam_patterns.push("TG_REPLY_CAPS_SPAM".to_string());
             }
         }
         
         // Check for excessive emoji
-        let emoji_count = text.chars().filter(|c| {
-            let code = *c as u32;
-            (code >= 0x1F600 && code <= 0x1F64F) || // Emoticons
-            (code >= 0x1F300 && code <= 0x1F5FF) || // Misc Symbols and Pictographs
-            (code >= 0x1F680 && code <= 0x1F6FF) || // Transport and Map Symbols
-            (code >= 0x1F1E0 && code <= 0x1F1FF)    // Regional Indicator Symbols
-        }).count();
-        if emoji_count > reply_aware::anti_evasion::MAX_EMOJI_IN_REPLY as usize {
+        let emoji_count = count_emoji(text);
+        if emoji_count > limits.max_emoji as usize {
             spam_patterns.push("TG_REPLY_EMOJI_SPAM".to_string());
         }
         
@@ -286,7 +369,7 @@ impl TrustManager {
     /// Track spam patterns for a user
     pub async fn track_spam_patterns(&self, user_id: UserId, patterns: &[String]) -> Result<(), Box<dyn Error + Send + Sync>> {
         let mut conn = self.redis_client.get_connection()?;
-        let spam_key = format!("{}{}", rate_limit::SPAM_PATTERN_PREFIX, user_id.0);
+        let spam_key = format!("{}{}", key::ns(rate_limit::SPAM_PATTERN_PREFIX), user_id.0);
         
         for pattern in patterns {
             conn.sadd::<_, _, ()>(&spam_key, pattern)?;
@@ -301,7 +384,7 @@ impl TrustManager {
     /// Get spam pattern history for a user
     pub async fn get_spam_patterns(&self, user_id: UserId) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
         let mut conn = self.redis_client.get_connection()?;
-        let spam_key = format!("{}{}", rate_limit::SPAM_PATTERN_PREFIX, user_id.0);
+        let spam_key = format!("{}{}", key::ns(rate_limit::SPAM_PATTERN_PREFIX), user_id.0);
         
         let patterns: Vec<String> = conn.smembers(&spam_key).unwrap_or_default();
         Ok(patterns)
@@ -309,6 +392,11 @@ impl TrustManager {
 
     /// Calculate adjusted score reduction based on trust level and spam patterns
     pub async fn calculate_score_reduction(&self, metadata: &TrustedMessageMetadata, user_id: UserId) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        // Replying to your own trusted message must not lower your score
+        if metadata.is_self_reply(user_id) {
+            return Ok(0.0);
+        }
+
         let mut reduction = metadata.message_type.score_reduction();
         
         // Check for spam patterns in user history
@@ -319,21 +407,55 @@ impl TrustManager {
         }
         
         // Ensure we don't exceed maximum reduction
-        if reduction < reply_aware::MAX_SCORE_REDUCTION {
-            reduction = reply_aware::MAX_SCORE_REDUCTION;
+        Ok(reduction.max(self.max_score_reduction()?))
+    }
+
+    /// Largest reduction a reply may get: the `/replyconfig max_reduction`
+    /// setting, or `reply_aware::MAX_SCORE_REDUCTION` while unset.
+    pub fn max_score_reduction(&self) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        let mut conn = self.redis_client.get_connection()?;
+        let configured: Option<f64> = conn.hget(key::ns(reply_aware::CONFIG_KEY), reply_aware::MAX_REDUCTION_FIELD)?;
+        Ok(configured.unwrap_or(reply_aware::MAX_SCORE_REDUCTION))
+    }
+
+    /// Sets the largest reduction a reply may get; `cap` must not be positive.
+    pub fn set_max_score_reduction(&self, cap: f64) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if cap.is_nan() || cap > 0.0 {
+            return Err(format!("maximum reduction must be 0 or negative, got {}", cap).into());
         }
-        
-        Ok(reduction)
+        let mut conn = self.redis_client.get_connection()?;
+        conn.hset::<_, _, _, ()>(key::ns(reply_aware::CONFIG_KEY), reply_aware::MAX_REDUCTION_FIELD, cap)?;
+        Ok(())
+    }
+
+    /// Whether replies to trusted messages get any score reduction at all:
+    /// the `/replyconfig enabled` switch, on while unset.
+    pub fn is_enabled(&self) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let mut conn = self.redis_client.get_connection()?;
+        let configured: Option<bool> = conn.hget(key::ns(reply_aware::CONFIG_KEY), reply_aware::ENABLED_FIELD)?;
+        Ok(configured.unwrap_or(true))
+    }
+
+    /// Switches reply-aware filtering on or off in every chat.
+    pub fn set_enabled(&self, enabled: bool) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut conn = self.redis_client.get_connection()?;
+        conn.hset::<_, _, _, ()>(key::ns(reply_aware::CONFIG_KEY), reply_aware::ENABLED_FIELD, enabled)?;
+        Ok(())
     }
 
     /// Mark a message as trusted
     pub async fn mark_trusted(&self, metadata: TrustedMessageMetadata) -> Result<(), Box<dyn Error + Send + Sync>> {
         let mut conn = self.redis_client.get_connection()?;
         
-        // Store the trusted message with TTL
+        // Store the trusted message with TTL; the marker keeps the type so the
+        // metadata can be rebuilt from the message itself if it goes missing
         let key = metadata.redis_key();
-        conn.set_ex::<_, _, ()>(&key, "1", TRUSTED_MESSAGE_TTL as u64)?;
+        conn.set_ex::<_, _, ()>(&key, metadata.message_type.as_str(), TRUSTED_MESSAGE_TTL as u64)?;
         
+        Self::store_metadata(&mut conn, &metadata, TRUSTED_MESSAGE_TTL)
+    }
+
+    fn store_metadata(conn: &mut redis::Connection, metadata: &TrustedMessageMetadata, ttl: i64) -> Result<(), Box<dyn Error + Send + Sync>> {
         // Store metadata in a hash
         let metadata_key = metadata.metadata_key();
         let _: () = conn.hset_multiple(
@@ -347,7 +469,7 @@ impl TrustManager {
         )?;
         
         // Set TTL for metadata
-        conn.expire::<_, ()>(&metadata_key, TRUSTED_MESSAGE_TTL as i64)?;
+        conn.expire::<_, ()>(&metadata_key, ttl)?;
         
         Ok(())
     }
@@ -355,7 +477,7 @@ impl TrustManager {
     /// Check if a message is trusted
     pub async fn is_trusted(&self, message_id: MessageId) -> Result<bool, Box<dyn Error + Send + Sync>> {
         let mut conn = self.redis_client.get_connection()?;
-        let key = format!("{}{}", key::TG_TRUSTED_PREFIX, message_id.0);
+        let key = format!("{}{}", key::ns(key::TG_TRUSTED_PREFIX), message_id.0);
         let exists: bool = conn.exists(&key)?;
         Ok(exists)
     }
@@ -363,7 +485,7 @@ impl TrustManager {
     /// Get trusted message metadata
     pub async fn get_trusted_metadata(&self, message_id: MessageId) -> Result<Option<TrustedMessageMetadata>, Box<dyn Error + Send + Sync>> {
         let mut conn = self.redis_client.get_connection()?;
-        let metadata_key = format!("{}{}{}{}", key::TG_TRUSTED_PREFIX, message_id.0, suffix::TRUSTED_METADATA, message_id.0);
+        let metadata_key = format!("{}{}{}{}", key::ns(key::TG_TRUSTED_PREFIX), message_id.0, suffix::TRUSTED_METADATA, message_id.0);
         
         // Check if metadata exists
         let exists: bool = conn.exists(&metadata_key)?;
@@ -395,10 +517,46 @@ impl TrustManager {
         Ok(Some(metadata))
     }
 
+    /// Metadata of `parent` if it is trusted, for a message replying to it.
+    /// When only the trusted marker is left (its metadata expired or was never
+    /// stored), the metadata is rebuilt from `parent` and stored again for the
+    /// marker's remaining TTL, so the reply can still be tracked.
+    pub async fn trusted_parent(&self, parent: &Message) -> Result<Option<TrustedMessageMetadata>, Box<dyn Error + Send + Sync>> {
+        let mut conn = self.redis_client.get_connection()?;
+        let key = format!("{}{}", key::ns(key::TG_TRUSTED_PREFIX), parent.id.0);
+        let marker: Option<String> = conn.get(&key)?;
+        let Some(marker) = marker else {
+            return Ok(None);
+        };
+        if let Some(metadata) = self.get_trusted_metadata(parent.id).await? {
+            return Ok(Some(metadata));
+        }
+        let Some(sender) = parent.from.as_ref() else {
+            return Ok(None);
+        };
+        
+        // Older markers hold "1" rather than the type; only bots can be told apart then
+        let message_type = TrustedMessageType::from_str(&marker).unwrap_or(if sender.is_bot {
+            TrustedMessageType::Bot
+        } else {
+            TrustedMessageType::Verified
+        });
+        let metadata = TrustedMessageMetadata {
+            message_id: parent.id,
+            chat_id: parent.chat.id,
+            sender_id: sender.id,
+            message_type,
+            timestamp: parent.date,
+        };
+        let ttl: i64 = conn.ttl(&key)?;
+        Self::store_metadata(&mut conn, &metadata, if ttl > 0 { ttl } else { TRUSTED_MESSAGE_TTL })?;
+        Ok(Some(metadata))
+    }
+
     /// Track a reply to a trusted message
     pub async fn track_reply(&self, chat_id: ChatId, reply_message_id: MessageId, trusted_message_id: MessageId) -> Result<(), Box<dyn Error + Send + Sync>> {
         let mut conn = self.redis_client.get_connection()?;
-        let reply_key = format!("{}{}:{}:{}", key::TG_REPLIES_PREFIX, chat_id.0, trusted_message_id.0, reply_message_id.0);
+        let reply_key = format!("{}{}:{}:{}", key::ns(key::TG_REPLIES_PREFIX), chat_id.0, trusted_message_id.0, reply_message_id.0);
         
         // Store reply tracking with TTL
         conn.set_ex::<_, _, ()>(&reply_key, "1", REPLY_TRACKING_TTL as u64)?;
@@ -412,7 +570,7 @@ impl TrustManager {
         
         // Check if this message ID is tracked as a reply
         // Key format: tg:replies:<chat_id>:<trusted_message_id>:<reply_message_id>
-        let pattern = format!("{}{}:*:{}", key::TG_REPLIES_PREFIX, chat_id.0, message_id.0);
+        let pattern = format!("{}{}:*:{}", key::ns(key::TG_REPLIES_PREFIX), chat_id.0, message_id.0);
         let keys: Vec<String> = conn.keys(&pattern)?;
         
         if keys.is_empty() {
@@ -431,26 +589,92 @@ impl TrustManager {
         Ok(None)
     }
 
-    /// Clean up expired trusted messages (called periodically)
-    pub async fn cleanup_expired(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
-        // Redis automatically handles TTL cleanup, but we can add additional cleanup logic here
-        // For example, cleaning up orphaned metadata entries
+    /// Trust every future message from `user_id`, for `ttl` seconds or until untrusted
+    pub async fn trust_user(&self, user_id: UserId, ttl: Option<i64>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut conn = self.redis_client.get_connection()?;
+        let expires_at = match ttl {
+            Some(ttl) => (Utc::now().timestamp() + ttl) as f64,
+            None => f64::INFINITY,
+        };
+        conn.zadd::<_, _, _, ()>(key::ns(key::TG_TRUSTED_USERS_KEY), user_id.0, expires_at)?;
         Ok(())
     }
 
+    /// Stop trusting `user_id`; returns whether the user was trusted
+    pub async fn untrust_user(&self, user_id: UserId) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let mut conn = self.redis_client.get_connection()?;
+        let removed: i64 = conn.zrem(key::ns(key::TG_TRUSTED_USERS_KEY), user_id.0)?;
+        Ok(removed > 0)
+    }
+
+    /// Check if `user_id` is trusted, dropping the entry once its TTL has passed
+    pub async fn is_trusted_user(&self, user_id: UserId) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let mut conn = self.redis_client.get_connection()?;
+        let expires_at: Option<f64> = conn.zscore(key::ns(key::TG_TRUSTED_USERS_KEY), user_id.0)?;
+        match expires_at {
+            Some(expires_at) if expires_at > Utc::now().timestamp() as f64 => Ok(true),
+            Some(_) => {
+                conn.zrem::<_, _, ()>(key::ns(key::TG_TRUSTED_USERS_KEY), user_id.0)?;
+                Ok(false)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Remove metadata and reply tracking entries whose trusted message is
+    /// gone (called periodically); returns how many keys were removed
+    pub async fn cleanup_expired(&self) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let mut conn = self.redis_client.get_connection()?;
+        let trusted_prefix = key::ns(key::TG_TRUSTED_PREFIX);
+        let replies_prefix = key::ns(key::TG_REPLIES_PREFIX);
+
+        // Metadata key format: tg:trusted:<message_id>:metadata<message_id>
+        let metadata_keys: Vec<String> = conn
+            .scan_match::<_, String>(format!("{}*{}*", trusted_prefix, suffix::TRUSTED_METADATA))?
+            .collect();
+        // Reply key format: tg:replies:<chat_id>:<trusted_message_id>:<reply_message_id>
+        let reply_keys: Vec<String> = conn
+            .scan_match::<_, String>(format!("{}*", replies_prefix))?
+            .collect();
+
+        let mut orphaned = Vec::new();
+        for metadata_key in metadata_keys {
+            if let Some((trusted_key, _)) = metadata_key.rsplit_once(suffix::TRUSTED_METADATA) {
+                if !conn.exists::<_, bool>(trusted_key)? {
+                    orphaned.push(metadata_key);
+                }
+            }
+        }
+        for reply_key in reply_keys {
+            let trusted_message_id = reply_key
+                .strip_prefix(&replies_prefix)
+                .and_then(|rest| rest.split(':').nth(1));
+            if let Some(trusted_message_id) = trusted_message_id {
+                if !conn.exists::<_, bool>(format!("{}{}", trusted_prefix, trusted_message_id))? {
+                    orphaned.push(reply_key);
+                }
+            }
+        }
+
+        if !orphaned.is_empty() {
+            conn.del::<_, ()>(&orphaned)?;
+        }
+        Ok(orphaned.len())
+    }
+
     /// Get statistics about trusted messages
     pub async fn get_stats(&self) -> Result<TrustStats, Box<dyn Error + Send + Sync>> {
         let mut conn = self.redis_client.get_connection()?;
         
         // Count trusted messages (only the main keys, not metadata keys)
-        let trusted_pattern = format!("{}*", key::TG_TRUSTED_PREFIX);
+        let trusted_pattern = format!("{}*", key::ns(key::TG_TRUSTED_PREFIX));
         let all_trusted_keys: Vec<String> = conn.keys(&trusted_pattern)?;
         let trusted_messages = all_trusted_keys.iter()
             .filter(|key| !key.contains("metadata"))
             .count();
         
         // Count reply tracking entries
-        let reply_pattern = format!("{}*", key::TG_REPLIES_PREFIX);
+        let reply_pattern = format!("{}*", key::ns(key::TG_REPLIES_PREFIX));
         let reply_keys: Vec<String> = conn.keys(&reply_pattern)?;
         
         Ok(TrustStats {
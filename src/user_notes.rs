@@ -0,0 +1,59 @@
+//! Moderator notes on users.
+//!
+//! `/note` appends a timestamped note to the user's `tg:users:<id>:notes`
+//! list, `/notes` lists them and `/whois` shows them with everything else
+//! known about the user. Only the newest `notes::MAX_NOTES` are kept.
+
+use redis::{Commands, RedisResult};
+use teloxide::types::UserId;
+
+use crate::config::{key, notes, suffix};
+
+/// A note left on a user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserNote {
+    /// Unix timestamp the note was added at.
+    pub timestamp: i64,
+    /// Moderator who added it.
+    pub author: u64,
+    pub text: String,
+}
+
+impl UserNote {
+    /// Stored as `<timestamp>|<author>|<text>`; the text may contain `|`.
+    fn encode(&self) -> String {
+        format!("{}|{}|{}", self.timestamp, self.author, self.text)
+    }
+
+    fn decode(entry: &str) -> Option<Self> {
+        let mut parts = entry.splitn(3, '|');
+        Some(UserNote {
+            timestamp: parts.next()?.parse().ok()?,
+            author: parts.next()?.parse().ok()?,
+            text: parts.next()?.to_string(),
+        })
+    }
+}
+
+fn notes_key(user_id: UserId) -> String {
+    format!("{}{}{}", key::ns(key::TG_USERS_PREFIX), user_id.0, suffix::NOTES)
+}
+
+/// Appends a note by `author` on `user_id`, dropping the oldest ones past
+/// `notes::MAX_NOTES`.
+pub fn add_note(conn: &mut redis::Connection, user_id: UserId, author: UserId, text: &str, now: i64) -> RedisResult<()> {
+    let note = UserNote { timestamp: now, author: author.0, text: text.to_string() };
+    let notes_key = notes_key(user_id);
+    redis::pipe()
+        .rpush(&notes_key, note.encode())
+        .ignore()
+        .ltrim(&notes_key, -notes::MAX_NOTES, -1)
+        .ignore()
+        .query(conn)
+}
+
+/// The notes on `user_id`, oldest first.
+pub fn user_notes(conn: &mut redis::Connection, user_id: UserId) -> RedisResult<Vec<UserNote>> {
+    let entries: Vec<String> = conn.lrange(notes_key(user_id), 0, -1)?;
+    Ok(entries.iter().filter_map(|entry| UserNote::decode(entry)).collect())
+}
@@ -0,0 +1,145 @@
+use redis::Commands;
+use rspamd_telegram_bot::api::{routes, ChatStats, UserInfo, WhitelistResponse};
+use rspamd_telegram_bot::config::{field, key, suffix};
+use serial_test::serial;
+use std::error::Error;
+use warp::http::StatusCode;
+
+const TOKEN: &str = "test-token";
+
+#[tokio::test]
+#[serial]
+async fn test_api_serves_seeded_state_to_authorized_clients() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let redis_client = redis::Client::open("redis://127.0.0.1/")?;
+    let mut redis_conn = redis_client.get_connection()?;
+    let _: () = redis::cmd("FLUSHDB").query(&mut redis_conn)?;
+
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, 930001);
+    let _: () = redis_conn.hset_multiple(&user_key, &[
+        (field::USERNAME, "spammer"),
+        (field::REP, "4"),
+        (field::BANNED_Q, "2"),
+        (field::BANNED, "1"),
+        (field::JOIN_TIME, "1700000000"),
+    ])?;
+    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, -930002);
+    let _: () = redis_conn.hset_multiple(&chat_key, &[
+        (field::NAME, "Test chat"),
+        (field::ADMIN_CHAT, "930003"),
+        (field::SPAM_COUNT, "7"),
+        (field::DELETED, "5"),
+    ])?;
+    let api = routes(TOKEN.to_string());
+
+    // Requests without the token are refused
+    let response = warp::test::request().path("/api/user/930001").reply(&api).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = warp::test::request()
+        .path("/api/user/930001")
+        .header("authorization", "Bearer wrong")
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = warp::test::request()
+        .path("/api/user/930001")
+        .header("authorization", format!("Bearer {}", TOKEN))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let user: UserInfo = serde_json::from_slice(response.body())?;
+    assert_eq!(user.username.as_deref(), Some("spammer"));
+    assert_eq!(user.rep, 4);
+    assert_eq!(user.ban_count, 2);
+    assert!(user.banned);
+    assert_eq!(user.join_time, Some(1700000000));
+    assert_eq!(user.last_msg_time, None);
+
+    let response = warp::test::request()
+        .path("/api/user/930009")
+        .header("authorization", format!("Bearer {}", TOKEN))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Chat stats leave out the name and admin chat, like /stats
+    let response = warp::test::request()
+        .path("/api/chat/-930002/stats")
+        .header("authorization", format!("Bearer {}", TOKEN))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let stats: ChatStats = serde_json::from_slice(response.body())?;
+    assert_eq!(stats.name.as_deref(), Some("Test chat"));
+    assert_eq!(stats.stats.get(field::SPAM_COUNT).map(String::as_str), Some("7"));
+    assert_eq!(stats.stats.get(field::DELETED).map(String::as_str), Some("5"));
+    assert!(!stats.stats.contains_key(field::ADMIN_CHAT));
+
+    let _: () = redis::cmd("FLUSHDB").query(&mut redis_conn)?;
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_api_whitelist_adds_global_and_chat_entries() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let redis_client = redis::Client::open("redis://127.0.0.1/")?;
+    let mut redis_conn = redis_client.get_connection()?;
+    let _: () = redis::cmd("FLUSHDB").query(&mut redis_conn)?;
+    let api = routes(TOKEN.to_string());
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/api/whitelist")
+        .header("authorization", format!("Bearer {}", TOKEN))
+        .json(&serde_json::json!({ "kind": "user", "entry": "930004" }))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let added: WhitelistResponse = serde_json::from_slice(response.body())?;
+    assert!(added.added);
+    let whitelisted: bool = redis_conn.sismember(key::TG_WHITELIST_USER_KEY, "930004")?;
+    assert!(whitelisted, "User should be on the global whitelist");
+
+    // Adding it again reports it was already there
+    let response = warp::test::request()
+        .method("POST")
+        .path("/api/whitelist")
+        .header("authorization", format!("Bearer {}", TOKEN))
+        .json(&serde_json::json!({ "kind": "user", "entry": "930004" }))
+        .reply(&api)
+        .await;
+    let added: WhitelistResponse = serde_json::from_slice(response.body())?;
+    assert!(!added.added);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/api/whitelist")
+        .header("authorization", format!("Bearer {}", TOKEN))
+        .json(&serde_json::json!({ "kind": "word", "entry": "invoice", "chat_id": -930002 }))
+        .reply(&api)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let scoped: bool = redis_conn.sismember(
+        format!("{}{}{}", key::TG_CHATS_PREFIX, -930002, suffix::WHITELIST_WORDS),
+        "invoice",
+    )?;
+    assert!(scoped, "Word should be whitelisted in the given chat");
+
+    // User entries must be numeric ids and kinds must be known
+    for body in [
+        serde_json::json!({ "kind": "user", "entry": "not-an-id" }),
+        serde_json::json!({ "kind": "domain", "entry": "example.com" }),
+    ] {
+        let response = warp::test::request()
+            .method("POST")
+            .path("/api/whitelist")
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .json(&body)
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let _: () = redis::cmd("FLUSHDB").query(&mut redis_conn)?;
+    Ok(())
+}
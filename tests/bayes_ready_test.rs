@@ -0,0 +1,41 @@
+use rspamd_telegram_bot::bayes_manager::BayesManager;
+use rspamd_telegram_bot::config::bayes;
+use warp::Filter;
+
+/// Starts a mock Rspamd controller accepting every learn request.
+fn start_mock_controller() -> String {
+    let learn = warp::post().map(|| warp::reply::json(&serde_json::json!({"success": true})));
+    let (addr, server) = warp::serve(learn).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn test_ready_notification_triggers_once_when_thresholds_are_crossed() {
+    let bayes_manager = BayesManager::with_controller_url(&start_mock_controller()).unwrap();
+    bayes_manager.reset_all_data().unwrap();
+
+    // Claimed after every learn, like the learn commands and auto-learning do
+    let mut announced_after = Vec::new();
+    for i in 0..bayes::MIN_SPAM_MESSAGES {
+        bayes_manager.learn_spam(&format!("ready_spam_{}", i), &format!("Ready spam message number {}", i)).await.unwrap();
+        if bayes_manager.claim_ready_notification().unwrap() {
+            announced_after.push(format!("spam {}", i));
+        }
+    }
+    for i in 0..bayes::MIN_HAM_MESSAGES + 5 {
+        bayes_manager.learn_ham(&format!("ready_ham_{}", i), &format!("Ready ham message number {}", i)).await.unwrap();
+        if bayes_manager.claim_ready_notification().unwrap() {
+            announced_after.push(format!("ham {}", i));
+        }
+    }
+    assert_eq!(announced_after, vec![format!("ham {}", bayes::MIN_HAM_MESSAGES - 1)]);
+    assert!(bayes_manager.is_ready().unwrap());
+
+    // Resetting the classifier arms the notification again
+    bayes_manager.reset_all_data().unwrap();
+    assert!(!bayes_manager.claim_ready_notification().unwrap());
+    let mut conn = redis::Client::open("redis://127.0.0.1/").unwrap().get_connection().unwrap();
+    let notified: bool = redis::Commands::exists(&mut conn, bayes::READY_NOTIFIED_KEY).unwrap();
+    assert!(!notified);
+}
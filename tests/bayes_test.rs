@@ -268,3 +268,76 @@ async fn test_bayes_concurrent_operations() {
     let stats = bayes_manager.get_bayes_stats().unwrap();
     assert!(stats.contains_key("total_messages"), "Stats should contain total_messages");
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_concurrent_learn_spam_does_not_serialize() {
+    use redis::Commands;
+    use rspamd_telegram_bot::config::bayes;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use warp::Filter;
+
+    setup();
+
+    // Mock controller that takes a while to answer every learn request
+    const DELAY: Duration = Duration::from_millis(300);
+    const BATCH: usize = 10;
+    let learn = warp::post().and(warp::path("learnspam")).and_then(|| async {
+        tokio::time::sleep(DELAY).await;
+        Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({"success": true})))
+    });
+    let (addr, server) = warp::serve(learn).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    let mut conn = redis::Client::open("redis://127.0.0.1/").unwrap().get_connection().unwrap();
+    let before: i64 = conn.get(bayes::BAYES_SPAM_MESSAGES_KEY).unwrap_or(0);
+
+    let bayes_manager = Arc::new(BayesManager::with_controller_url(&format!("http://{}", addr)).unwrap());
+    let started = Instant::now();
+    let handles: Vec<_> = (0..BATCH)
+        .map(|i| {
+            let bayes_manager = Arc::clone(&bayes_manager);
+            tokio::spawn(async move {
+                bayes_manager
+                    .learn_spam(&format!("async_batch_{}", i), &format!("Async batch spam message {}", i))
+                    .await
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.await.unwrap().expect("learn_spam should succeed against the mock controller");
+    }
+    let elapsed = started.elapsed();
+
+    assert!(
+        elapsed < DELAY * (BATCH as u32) / 2,
+        "{} concurrent learn_spam calls took {:?}; they should overlap, not run one after another",
+        BATCH, elapsed
+    );
+    let after: i64 = conn.get(bayes::BAYES_SPAM_MESSAGES_KEY).unwrap();
+    assert_eq!(after - before, BATCH as i64, "Every call should record its learning on the shared connection");
+}
+
+#[tokio::test]
+async fn test_neural_features_follow_the_configured_retention() {
+    use redis::Commands;
+    use rspamd_telegram_bot::config::{neural, retention};
+    use warp::Filter;
+
+    setup();
+
+    let learn = warp::post().map(|| warp::reply::json(&serde_json::json!({"success": true})));
+    let (addr, server) = warp::serve(learn).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    std::env::set_var(retention::NEURAL_FEATURES_ENV, "1234");
+    let bayes_manager = BayesManager::with_controller_url(&format!("http://{}", addr)).unwrap();
+    let learned = bayes_manager.learn_spam("retention_test", "Cheap pills, order today").await;
+    std::env::remove_var(retention::NEURAL_FEATURES_ENV);
+    learned.expect("learn_spam should succeed against the mock controller");
+
+    let mut conn = redis::Client::open("redis://127.0.0.1/").unwrap().get_connection().unwrap();
+    let ttl: i64 = conn.ttl(format!("{}:{}", neural::NEURAL_FEATURES_KEY, "retention_test")).unwrap();
+    assert!(ttl > 1200 && ttl <= 1234, "Features should expire after the configured 1234s, got TTL {}", ttl);
+    assert_eq!(retention::neural_features(), retention::NEURAL_FEATURES, "The default applies again once unset");
+}
@@ -0,0 +1,65 @@
+use redis::Commands;
+use rspamd_telegram_bot::config::{action, key, suffix, threshold, ENABLED_FEATURES_KEY};
+use rspamd_telegram_bot::config_backup::{export_config, import_config, ConfigSnapshot};
+use serial_test::serial;
+use std::error::Error;
+
+#[tokio::test]
+#[serial]
+async fn test_config_export_import_round_trip() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let redis_client = redis::Client::open("redis://127.0.0.1/")?;
+    let mut redis_conn = redis_client.get_connection()?;
+    let _: () = redis::cmd("FLUSHDB").query(&mut redis_conn)?;
+
+    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, 920001);
+    let _: () = redis_conn.sadd(ENABLED_FEATURES_KEY, &["flood", "caps"])?;
+    let _: () = redis_conn.hset(&chat_key, "feat:caps", "0")?;
+    let _: () = redis_conn.hset(&chat_key, "name", "Test chat")?;
+    let _: () = redis_conn.hset(format!("{}{}", chat_key, suffix::ACTIONS), action::BAN, 8.0)?;
+    let _: () = redis_conn.sadd(format!("{}{}", chat_key, suffix::ALLOWED_SCRIPTS), "cyrillic")?;
+    let _: () = redis_conn.hset(key::TG_THRESHOLDS_KEY, threshold::EMOJI_MAX, 20.0)?;
+    let _: () = redis_conn.sadd(key::TG_WHITELIST_USER_KEY, "920002")?;
+    let _: () = redis_conn.sadd(key::TG_TRUSTED_DOMAINS_KEY, "docs.rs")?;
+    let _: () = redis_conn.sadd(format!("{}{}", chat_key, suffix::BLACKLIST_WORDS), "casino")?;
+
+    let json = serde_json::to_string(&export_config(&mut redis_conn)?)?;
+
+    let _: () = redis::cmd("FLUSHDB").query(&mut redis_conn)?;
+    let snapshot = ConfigSnapshot::from_json(&json)?;
+    import_config(&mut redis_conn, &snapshot)?;
+
+    let caps_enabled: bool = redis_conn.sismember(ENABLED_FEATURES_KEY, "caps")?;
+    let caps_flag: String = redis_conn.hget(&chat_key, "feat:caps")?;
+    assert!(caps_enabled, "Global feature should survive the round trip");
+    assert_eq!(caps_flag, "0", "Per-chat feature flag should survive the round trip");
+
+    let whitelisted: bool = redis_conn.sismember(key::TG_WHITELIST_USER_KEY, "920002")?;
+    assert!(whitelisted, "Whitelist entry should survive the round trip");
+    let trusted: bool = redis_conn.sismember(key::TG_TRUSTED_DOMAINS_KEY, "docs.rs")?;
+    assert!(trusted, "Trusted domain should survive the round trip");
+    let scoped: bool = redis_conn.sismember(format!("{}{}", chat_key, suffix::BLACKLIST_WORDS), "casino")?;
+    assert!(scoped, "Chat-scoped blacklist entry should survive the round trip");
+
+    let ban_threshold: f64 = redis_conn.hget(format!("{}{}", chat_key, suffix::ACTIONS), action::BAN)?;
+    let emoji_max: f64 = redis_conn.hget(key::TG_THRESHOLDS_KEY, threshold::EMOJI_MAX)?;
+    let scripts: Vec<String> = redis_conn.smembers(format!("{}{}", chat_key, suffix::ALLOWED_SCRIPTS))?;
+    assert_eq!(ban_threshold, 8.0);
+    assert_eq!(emoji_max, 20.0);
+    assert_eq!(scripts, vec!["cyrillic".to_string()]);
+
+    let name: Option<String> = redis_conn.hget(&chat_key, "name")?;
+    assert_eq!(name, None, "Non-configuration chat data is not part of the snapshot");
+
+    Ok(())
+}
+
+#[test]
+fn test_config_import_rejects_invalid_documents() {
+    assert!(ConfigSnapshot::from_json("not json").is_err());
+    assert!(ConfigSnapshot::from_json(r#"{"version": 99}"#).is_err(), "Unknown versions are rejected");
+    assert!(ConfigSnapshot::from_json(r#"{"version": 1, "enabled_features": ["bogus"]}"#).is_err());
+    assert!(ConfigSnapshot::from_json(r#"{"version": 1, "thresholds": {"caps_ratio": 2.0}}"#).is_err());
+    assert!(ConfigSnapshot::from_json(r#"{"version": 1, "chats": {"1": {"actions": {"tg_nuke": 1.0}}}}"#).is_err());
+    assert!(ConfigSnapshot::from_json(r#"{"version": 1, "unexpected": true}"#).is_err());
+    assert!(ConfigSnapshot::from_json(r#"{"version": 1, "chats": {"1": {"features": {"caps": "1"}}}}"#).is_ok());
+}
@@ -1,5 +1,32 @@
 use rspamd_telegram_bot::fuzzy_trainer::FuzzyTrainer;
 use rspamd_telegram_bot::config::rspamd;
+use bytes::Bytes;
+use std::sync::{Arc, Mutex};
+use warp::Filter;
+
+/// Requests captured by the mock fuzzy controller: (path, headers, body).
+type Captured = Arc<Mutex<Vec<(String, warp::http::HeaderMap, Bytes)>>>;
+
+/// Starts a mock Rspamd controller accepting `/fuzzyadd` and `/fuzzydel`.
+fn start_mock_fuzzy_controller() -> (String, Captured) {
+    let captured: Captured = Arc::new(Mutex::new(Vec::new()));
+    let sink = captured.clone();
+
+    let route = warp::post()
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::bytes())
+        .map(move |path: String, headers: warp::http::HeaderMap, body: Bytes| {
+            sink.lock().unwrap().push((path, headers, body));
+            warp::reply::json(&serde_json::json!({ "success": true }))
+        });
+
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+
+    (format!("http://{}", addr), captured)
+}
 
 #[tokio::test]
 async fn test_fuzzy_trainer_creation() {
@@ -151,3 +178,43 @@ async fn test_fuzzy_trainer_integration() {
         println!("Integration test skipped - Rspamd not running");
     }
 }
+
+#[tokio::test]
+async fn test_fuzzy_add_hash_sends_flag_and_weight() {
+    let (url, captured) = start_mock_fuzzy_controller();
+    let mut trainer = FuzzyTrainer::new();
+    trainer.controller_url = url;
+
+    trainer.add_hash("42", "Buy cheap crypto now", 7, 15).await.unwrap();
+
+    let requests = captured.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    let (path, headers, body) = &requests[0];
+    assert_eq!(path, "fuzzyadd");
+    assert_eq!(headers["Password"], rspamd::PASSWORD);
+    assert_eq!(headers["Flag"], "7");
+    assert_eq!(headers["Weight"], "15");
+    assert_eq!(headers["Content-Type"], "message/rfc822");
+
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    assert!(body.contains("Message-ID: <42@telegram.bot>"));
+    assert!(body.ends_with("Buy cheap crypto now"), "Body should carry the stored message content");
+}
+
+#[tokio::test]
+async fn test_fuzzy_del_hash_sends_flag_without_weight() {
+    let (url, captured) = start_mock_fuzzy_controller();
+    let mut trainer = FuzzyTrainer::new();
+    trainer.controller_url = url;
+
+    trainer.del_hash("43", "Buy cheap crypto now", rspamd::FUZZY_FLAG).await.unwrap();
+
+    let requests = captured.lock().unwrap();
+    assert_eq!(requests.len(), 1);
+    let (path, headers, body) = &requests[0];
+    assert_eq!(path, "fuzzydel");
+    assert_eq!(headers["Password"], rspamd::PASSWORD);
+    assert_eq!(headers["Flag"], rspamd::FUZZY_FLAG.to_string().as_str());
+    assert!(headers.get("Weight").is_none());
+    assert!(String::from_utf8(body.to_vec()).unwrap().contains("Buy cheap crypto now"));
+}
@@ -13,22 +13,72 @@ use std::sync::atomic::{AtomicU16, Ordering};
 
 use chrono::Utc;
 use redis::Commands;
-use rspamd_telegram_bot::admin_handlers::{handle_admin_command, AdminCommand};
-use rspamd_telegram_bot::handlers::scan_msg;
+use rspamd_telegram_bot::admin_handlers::{
+    admin_command_menu, admin_panel_user, audit_entries_since, command_access, moderates_chat, emergency_stop_since, export_audit_log_jsonl, record_audit_entry, AuditLogEntry, paginate, panel_page_handler, render_admin_list_page, save_panel_admin, LIST_ADMINS_CALLBACK, MONITORED_CHATS_CALLBACK, handle_admin_command, member_command_menu, index_username, message_handler, lookup_username, purge_messages, recent_message_ids, record_recent_message,
+    chat_state_keys, check_health, record_spam_report, render_health, render_trace, reset_chat, search_messages, AdminCommand, CommandAccess, HealthState, PurgeOutcome,
+    ReportOutcome, SearchPattern, handle_report_spam, global_stats, render_global_stats, render_simulation, simulate_raid, simulation_allowed, SubsystemHealth, appeal_handler, chat_member_handler, decide_appeal, get_appeal, record_appeal, AppealOutcome, APPEAL_CALLBACK,
+};
+use rspamd_telegram_bot::admin_handlers::commands::ALIASES;
+use rspamd_telegram_bot::admin_panel::config::{key as panel_key, settings as panel_settings};
+use rspamd_telegram_bot::admin_panel::permissions::{AdminPermission, AdminUser, PermissionGroup};
+use rspamd_telegram_bot::handlers::{
+    claim_scan, count_emoji, forward_penalty, handle_message, trace_scan, message_sender, preview_scan, release_scan, reputation_delta, resolve_action, scan_msg, store_message_content, ScanFailure, stored_message_content, Sender,
+};
 use rspamd_telegram_bot::config::{
-    field, key, suffix, symbol, DEFAULT_FEATURES, ENABLED_FEATURES_KEY,
+    action, appeal, attachment, ban_rate, command_menu, contact_card, domain_rep, mixed_language, feature_state, field, forward, good_standing, impersonation, is_feature_enabled, new_user_link, join_gate, key, lockdown, mute, notes, purge, raid_simulation, report, reputation, retention, spam_event, suffix, symbol, symbol_weight, threshold, trend, trusted_user, webhook, symbol_feature, FeatureSource, FeatureState, ADAPTIVE_FEATURE, DEFAULT_FEATURES, DRY_RUN_FEATURE, ENABLED_FEATURES_KEY, JOIN_VERIFY_FEATURE, MIXED_LANGUAGE_FEATURE, SILENT_MODE_FEATURE,
 };
 use serial_test::serial;
-use teloxide::types::{Chat, ChatId, ChatKind, ChatPrivate, MediaKind, MediaText, Message, MessageCommon, MessageId, MessageKind, User, UserId};
+use teloxide::types::{
+    CallbackQuery, Chat, ChatId, ChatMemberUpdated, ChatKind, ChatPrivate, ChatPublic, Contact, Document, FileMeta, MediaContact, MediaDocument, MediaKind, MediaPhoto, MediaSticker, MediaText, Message, MessageCommon, MessageId, MessageKind,
+    MessageOrigin, PhotoSize, PublicChatChannel, PublicChatKind, Sticker, StickerFormatFlags, StickerKind, User, UserId,
+};
 use teloxide::Bot;
+use teloxide::utils::command::BotCommands;
 use once_cell::sync::Lazy;
-use std::{collections::HashMap, fs, io, path::Path};
+use std::{collections::{HashMap, HashSet}, fs, io, path::Path};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 use warp::Filter;
 use serde_json::json;
 use regex::Regex;
 use bytes::Bytes;
 use rspamd_telegram_bot::trust_manager::{TrustManager, TrustedMessageMetadata, TrustedMessageType};
+use rspamd_telegram_bot::script_filter::dominant_script;
+use rspamd_telegram_bot::lookalike::{decode_host, is_lookalike_host};
+use rspamd_telegram_bot::shortener::{is_shortener_host, shorteners};
+use rspamd_telegram_bot::media_repeat::media_unique_id;
+use rspamd_telegram_bot::mixed_language::mixed_scripts;
+use rspamd_telegram_bot::notifications::alert_target;
+use rspamd_telegram_bot::impersonation::{record_admin_name, skeleton};
+use rspamd_telegram_bot::gibberish::letter_counts;
+use rspamd_telegram_bot::char_flood::char_runs;
+use rspamd_telegram_bot::caps::case_counts;
+use rspamd_telegram_bot::repeat::{is_repeat, normalize as normalize_repeat, similarity};
+use rspamd_telegram_bot::join_gate::{join_windows, probation_remaining};
+use rspamd_telegram_bot::lockdown::{end_lockdown, lockdown_since};
+use rspamd_telegram_bot::join_verify::{is_pending, kick_unverified, record_challenge, verify_handler, VERIFY_CALLBACK};
+use rspamd_telegram_bot::flood::{flood_limit, set_flood_limit, FloodLimitSource};
+use rspamd_telegram_bot::spam_events::{count_spam_events_since, describe_reason, record_spam_event};
+use rspamd_telegram_bot::ban_manager::{banned_users, worst_users};
+use rspamd_telegram_bot::forward_policy::{forward_blocked, forward_policy, set_forward_policy, ForwardPolicy};
+use rspamd_telegram_bot::reputation_decay::ReputationDecay;
+use rspamd_telegram_bot::suspicious_decay::{apply_suspicious_decay, decay_step, is_benign};
+use rspamd_telegram_bot::perm_ban::{perm_ban_action, PermBanAction};
+use rspamd_telegram_bot::ban_rate::{admit_ban, set_ban_rate_limit, BanDecision};
+use rspamd_telegram_bot::adaptive::{effective_limits, record_message, refresh_chat, sample_rate, set_scale_bounds};
+use rspamd_telegram_bot::spam_webhook::SpamWebhookPayload;
+use rspamd_telegram_bot::reputation_update::{add_rep, add_rep_if_above, ban_if_above};
+use rspamd_telegram_bot::outgoing::Throttle;
+use rspamd_telegram_bot::user_notes::{add_note, user_notes, UserNote};
+use rspamd_telegram_bot::mutes::{clear_mute, expired_mutes, mute_minutes, muted_users, record_mute};
+use rspamd_telegram_bot::lists;
+use rspamd_telegram_bot::spam_trend::{daily_key, daily_totals, record_daily_action, record_daily_symbols, render_trend};
+use rspamd_telegram_bot::daily_summary::{admin_chats, build_summary};
+use rspamd_client::protocol::scan::Symbol;
+use rspamd_client::error::RspamdError;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::layer::{Context, SubscriberExt};
 
 
 static MOCK_SERVER_INIT: Once = Once::new();
@@ -54,10 +104,10 @@ fn start_mock_server() {
                         // Parse the email body to extract message content and headers
                         let email_str = String::from_utf8_lossy(&body);
                         let text = extract_message_text(&email_str);
-                        let (user_id, chat_id, message_id) = extract_telegram_headers(&email_str);
+                        let headers = MockHeaders::parse(&email_str);
                         
                         // Run heuristic detection
-                        let symbols = detect_symbols(&text, user_id, chat_id, message_id);
+                        let symbols = detect_symbols(&text, &headers);
                         
                         let response = json!({
                             "is_skipped": false,
@@ -104,18 +154,22 @@ fn start_mock_server() {
                         };
                         
                         // If we can't parse the email properly, try to extract from headers
-                        let (text, user_id, chat_id, message_id) = if email_str.contains("X-Telegram-User") {
-                            let text = extract_message_text(&email_str);
-                            let (user_id, chat_id, message_id) = extract_telegram_headers(&email_str);
-                            (text, user_id, chat_id, message_id)
+                        let (text, headers) = if email_str.contains("X-Telegram-User") {
+                            (extract_message_text(&email_str), MockHeaders::parse(&email_str))
                         } else {
                             // Fallback: this shouldn't happen with proper decompression
                             eprintln!("Could not find Telegram headers in email");
-                            ("".to_string(), 0u64, 0i64, 0i32)
+                            let headers = MockHeaders {
+                                user_id: 0,
+                                chat_id: 0,
+                                message_id: 0,
+                                ..MockHeaders::parse(&email_str)
+                            };
+                            ("".to_string(), headers)
                         };
                         
                         // Run heuristic detection
-                        let symbols = detect_symbols(&text, user_id, chat_id, message_id);
+                        let symbols = detect_symbols(&text, &headers);
                         
                         let response = json!({
                             "is_skipped": false,
@@ -137,6 +191,11 @@ fn start_mock_server() {
                         warp::reply::json(&response)
                     });
 
+                // Rspamd's liveness check, used by /health
+                let ping = warp::path("ping")
+                    .and(warp::get())
+                    .map(|| "pong\r\n");
+
                 // Catch-all route for debugging
                 let debug = warp::any()
                     .and(warp::path::full())
@@ -147,7 +206,7 @@ fn start_mock_server() {
                     });
 
                 let port = MOCK_SERVER_PORT.load(Ordering::Relaxed);
-                warp::serve(symbols.or(checkv2).or(debug))
+                warp::serve(symbols.or(checkv2).or(ping).or(debug))
                     .run(([127, 0, 0, 1], port))
                     .await;
             });
@@ -182,6 +241,33 @@ fn extract_message_text(email: &str) -> String {
     }
 }
 
+/// The Telegram headers the bot adds to a scanned message, as the mock
+/// Rspamd sees them.
+struct MockHeaders {
+    user_id: u64,
+    username: Option<String>,
+    chat_id: i64,
+    message_id: i32,
+    forward: Option<String>,
+    preview: bool,
+    rep_credit: i64,
+}
+
+impl MockHeaders {
+    fn parse(email: &str) -> Self {
+        let (user_id, chat_id, message_id) = extract_telegram_headers(email);
+        MockHeaders {
+            user_id,
+            username: extract_username(email),
+            chat_id,
+            message_id,
+            forward: extract_forward_origin(email),
+            preview: email.contains("X-Telegram-Preview:"),
+            rep_credit: extract_rep_credit(email),
+        }
+    }
+}
+
 fn extract_telegram_headers(email: &str) -> (u64, i64, i32) {
     let mut user_id = 0;
     let mut chat_id = 0;
@@ -209,6 +295,28 @@ fn extract_telegram_headers(email: &str) -> (u64, i64, i32) {
     (user_id, chat_id, message_id)
 }
 
+fn extract_forward_origin(email: &str) -> Option<String> {
+    email
+        .lines()
+        .find_map(|line| line.strip_prefix("X-Telegram-Forward:"))
+        .map(|origin| origin.trim().to_string())
+}
+
+fn extract_username(email: &str) -> Option<String> {
+    email
+        .lines()
+        .find_map(|line| line.strip_prefix("X-Telegram-Username:"))
+        .map(|username| username.trim().to_string())
+}
+
+fn extract_rep_credit(email: &str) -> i64 {
+    email
+        .lines()
+        .find_map(|line| line.strip_prefix("X-Telegram-Rep-Credit:"))
+        .and_then(|credit| credit.trim().parse().ok())
+        .unwrap_or(0)
+}
+
 fn flush_redis() {
     start_mock_server();
     
@@ -231,7 +339,10 @@ fn flush_redis() {
     }
 }
 
-fn detect_symbols(text: &str, user_id: u64, chat_id: i64, message_id: i32) -> serde_json::Value {
+fn detect_symbols(text: &str, headers: &MockHeaders) -> serde_json::Value {
+    let MockHeaders { user_id, chat_id, message_id, preview, rep_credit, .. } = *headers;
+    let username = headers.username.as_deref();
+    let forward = headers.forward.as_deref();
     let mut symbols = serde_json::Map::new();
     
     // Connect to Redis to get/update state
@@ -289,87 +400,132 @@ fn detect_symbols(text: &str, user_id: u64, chat_id: i64, message_id: i32) -> se
     let now_ts = chrono::Utc::now().timestamp();
     
     // Get current state
-    let flood: i64 = conn.hget(&user_key, "flood").unwrap_or(0);
     let eq_msg_count: i64 = conn.hget(&user_key, "eq_msg_count").unwrap_or(0);
     let last_msg: String = conn.hget(&user_key, "last_msg").unwrap_or_default();
-    let mut rep: i64 = conn.hget(&user_key, "rep").unwrap_or(0);
+    // Reputation changes are collected and applied atomically below
+    let mut rep_delta: i64 = 0;
     let banned_q: i64 = conn.hget(&user_key, "banned_q").unwrap_or(0);
     let join_time: i64 = conn.hget(&user_key, "join_time").unwrap_or(0);
     let last_msg_time: i64 = conn.hget(&user_key, "last_msg_time").unwrap_or(0);
     
-    // 1. Flood detection
-    let new_flood = flood + 1;
-    let _: () = conn.hset(&user_key, "flood", new_flood).unwrap();
-    if new_flood > 30 {
-        symbols.insert("TG_FLOOD".to_string(), json!({"name": "TG_FLOOD", "score": 0.0, "metric_score": 0.0}));
-        let _: () = conn.hset(&user_key, "flood", 0).unwrap();
-        rep += 1;
-    }
+    // Previews skip the rules that update user history
+    if !preview {
+        // 1. Flood detection
+        let new_flood: i64 = conn.hincr(&user_key, "flood", 1).unwrap();
+        let (flood_max, _) = flood_limit(&mut conn, ChatId(chat_id));
+        if new_flood > flood_max {
+            symbols.insert("TG_FLOOD".to_string(), json!({"name": "TG_FLOOD", "score": 0.0, "metric_score": 0.0}));
+            let _: () = conn.hset(&user_key, "flood", 0).unwrap();
+            rep_delta += 1;
+        }
     
-    // 2. Repeat detection
-    let new_eq_msg_count = if last_msg == text {
-        eq_msg_count + 1
-    } else {
-        let _: () = conn.hset(&user_key, "last_msg", text).unwrap();
-        1
-    };
-    let _: () = conn.hset(&user_key, "eq_msg_count", new_eq_msg_count).unwrap();
+        // 2. Repeat detection (identical messages outside the window start over)
+        let repeat_window: f64 = conn
+            .hget::<_, _, Option<f64>>(key::TG_THRESHOLDS_KEY, threshold::REPEAT_WINDOW)
+            .unwrap()
+            .unwrap_or(threshold::DEFAULT_REPEAT_WINDOW);
+        let stale = last_msg_time != 0 && (now_ts - last_msg_time) as f64 > repeat_window;
+        let repeat_similarity: f64 = conn
+            .hget::<_, _, Option<f64>>(key::TG_THRESHOLDS_KEY, threshold::REPEAT_SIMILARITY)
+            .unwrap()
+            .unwrap_or(threshold::DEFAULT_REPEAT_SIMILARITY);
+        let repeated = is_repeat(&last_msg, text, repeat_similarity) && !stale;
+        let new_eq_msg_count = if repeated {
+            eq_msg_count + 1
+        } else {
+            1
+        };
+        let _: () = conn.hset(&user_key, "last_msg", normalize_repeat(text)).unwrap();
+        let _: () = conn.hset(&user_key, "eq_msg_count", new_eq_msg_count).unwrap();
     
-    if eq_msg_count == 7 { // threshold + 1
-        symbols.insert("TG_REPEAT".to_string(), json!({"name": "TG_REPEAT", "score": 0.0, "metric_score": 0.0}));
-        rep += 1;
-    }
+        if repeated && eq_msg_count == 7 { // threshold + 1
+            symbols.insert("TG_REPEAT".to_string(), json!({"name": "TG_REPEAT", "score": 0.0, "metric_score": 0.0}));
+            rep_delta += 1;
+        }
     
-    // 3. Timing-based detections
-    if join_time != 0 && last_msg_time == 0 {
-        let diff = now_ts - join_time;
-        if diff < 10 {
-            symbols.insert("TG_FIRST_FAST".to_string(), json!({"name": "TG_FIRST_FAST", "score": 0.0, "metric_score": 0.0}));
-        } else if diff > 86_400 {
-            symbols.insert("TG_FIRST_SLOW".to_string(), json!({"name": "TG_FIRST_SLOW", "score": 0.0, "metric_score": 0.0}));
+        // 2b. Cross-chat duplicate detection
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let cross_key = format!("{}{}:{:x}", key::TG_CROSS_POST_PREFIX, user_id, hasher.finish());
+        let _: () = conn.sadd(&cross_key, chat_id).unwrap();
+        let _: () = conn.expire(&cross_key, CONFIG.cross_post_window as i64).unwrap();
+        let chats: u32 = conn.scard(&cross_key).unwrap_or(0);
+        if chats > CONFIG.cross_post {
+            symbols.insert("TG_CROSS_POST".to_string(), json!({"name": "TG_CROSS_POST", "score": 0.0, "metric_score": 0.0}));
+            rep_delta += 1;
         }
-    }
     
-    if last_msg_time != 0 {
-        let diff = now_ts - last_msg_time;
-        if diff > 2_592_000 {
-            symbols.insert("TG_SILENT".to_string(), json!({"name": "TG_SILENT", "score": 0.0, "metric_score": 0.0}));
+        // 3. Timing-based detections
+        if join_time != 0 && last_msg_time == 0 {
+            let diff = now_ts - join_time;
+            if let Some(name) = join_windows(&mut conn, ChatId(chat_id)).first_message_symbol(diff) {
+                symbols.insert(name.to_string(), json!({"name": name, "score": 0.0, "metric_score": 0.0}));
+            }
+        }
+    
+        if last_msg_time != 0 {
+            let diff = now_ts - last_msg_time;
+            if diff > 2_592_000 {
+                symbols.insert("TG_SILENT".to_string(), json!({"name": "TG_SILENT", "score": 0.0, "metric_score": 0.0}));
+            }
         }
-    }
     
-    let _: () = conn.hset(&user_key, "last_msg_time", now_ts).unwrap();
+        let _: () = conn.hset(&user_key, "last_msg_time", now_ts).unwrap();
+    }
     
     // 4. Content-based detections
-    let link_regex = Regex::new(r"https?://[^\s]+").unwrap();
-    if link_regex.find_iter(text).count() > 3 {
+    let thresholds: HashMap<String, f64> = conn.hgetall(key::TG_THRESHOLDS_KEY).unwrap_or_default();
+    let limit = |name: &str, default: f64| thresholds.get(name).copied().unwrap_or(default);
+    
+    // Links to trusted domains (or their subdomains) aren't counted
+    let trusted_domains: HashSet<String> = conn.smembers(key::TG_TRUSTED_DOMAINS_KEY).unwrap_or_default();
+    let is_trusted = |host: &str| {
+        let host = host.to_lowercase();
+        trusted_domains.iter().any(|d| host == *d || host.ends_with(&format!(".{}", d)))
+    };
+    let link_regex = Regex::new(r"https?://([^/\s:]+)[^\s]*").unwrap();
+    let untrusted_hosts: Vec<String> = link_regex
+        .captures_iter(text)
+        .map(|c| c[1].to_string())
+        .filter(|host| !is_trusted(host))
+        .collect();
+    if untrusted_hosts.len() as f64 > limit(threshold::LINK_SPAM_MAX, threshold::DEFAULT_LINK_SPAM_MAX) {
         symbols.insert("TG_LINK_SPAM".to_string(), json!({"name": "TG_LINK_SPAM", "score": 0.0, "metric_score": 0.0}));
     }
+    if untrusted_hosts.iter().any(|host| is_lookalike_host(host)) {
+        symbols.insert("TG_LOOKALIKE_DOMAIN".to_string(), json!({"name": "TG_LOOKALIKE_DOMAIN", "score": 0.0, "metric_score": 0.0}));
+    }
     
     let mention_regex = Regex::new(r"@[A-Za-z0-9_]+").unwrap();
-    if mention_regex.find_iter(text).count() > 5 {
+    if mention_regex.find_iter(text).count() as f64 > limit(threshold::MENTIONS_MAX, threshold::DEFAULT_MENTIONS_MAX) {
         symbols.insert("TG_MENTIONS".to_string(), json!({"name": "TG_MENTIONS", "score": 0.0, "metric_score": 0.0}));
     }
     
+    // Ratio-based checks skip messages under the content length floor
+    let long_enough = text.trim().chars().count() as f64
+        >= limit(threshold::MIN_CONTENT_LENGTH, threshold::DEFAULT_MIN_CONTENT_LENGTH);
+    
     // Caps detection
-    let letters: Vec<char> = text.chars().filter(|c| c.is_ascii_alphabetic()).collect();
-    let caps: usize = letters.iter().filter(|c| c.is_ascii_uppercase()).count();
-    if !letters.is_empty() {
-        let ratio = caps as f64 / letters.len() as f64;
-        if (ratio > 0.5 && caps > 10) || caps >= 15 {
+    let cases = case_counts(text);
+    if long_enough && cases.letters() > 0 {
+        let caps = cases.upper;
+        if (cases.caps_ratio() > 0.5 && caps > 10) || caps >= 15 {
             symbols.insert("TG_CAPS".to_string(), json!({"name": "TG_CAPS", "score": 0.0, "metric_score": 0.0}));
         }
     }
     
-    // Emoji spam - fix detection range
-    let emoji_count = text.chars().filter(|c| {
-        let code = *c as u32;
-        (code >= 0x1F600 && code <= 0x1F64F) || // emoticons
-        (code >= 0x1F300 && code <= 0x1F5FF) || // misc symbols
-        (code >= 0x1F680 && code <= 0x1F6FF) || // transport
-        (code >= 0x2600 && code <= 0x26FF) ||   // misc symbols
-        (code >= 0x2700 && code <= 0x27BF)      // dingbats
-    }).count();
-    if emoji_count > 10 {
+    // Stretched text
+    let char_flood_on = is_feature_enabled(&mut conn, chat_id, "char_flood");
+    if char_flood_on && char_runs(text).is_flood(
+        limit(threshold::CHAR_RUN_MAX, threshold::DEFAULT_CHAR_RUN_MAX),
+        limit(threshold::CHAR_FLOOD_RATIO, threshold::DEFAULT_CHAR_FLOOD_RATIO),
+    ) {
+        symbols.insert("TG_CHAR_FLOOD".to_string(), json!({"name": "TG_CHAR_FLOOD", "score": 0.0, "metric_score": 0.0}));
+    }
+    
+    // Emoji spam
+    let emoji_count = count_emoji(text);
+    if long_enough && emoji_count as f64 > limit(threshold::EMOJI_MAX, threshold::DEFAULT_EMOJI_MAX) {
         symbols.insert("TG_EMOJI_SPAM".to_string(), json!({"name": "TG_EMOJI_SPAM", "score": 0.0, "metric_score": 0.0}));
     }
     
@@ -382,8 +538,13 @@ fn detect_symbols(text: &str, user_id: u64, chat_id: i64, message_id: i32) -> se
         }
     }
     
-    // URL shortener
-    if text.contains("bit.ly") || text.contains("tinyurl.com") {
+    // URL shortener, matched by host; like Rspamd's URL extraction, links without a scheme count
+    let shortener_domains = shorteners(&mut conn).unwrap_or_default();
+    let host_regex = Regex::new(r"(?i)(?:https?://)?\b([a-z0-9-]+(?:\.[a-z0-9-]+)+)").unwrap();
+    if host_regex
+        .captures_iter(text)
+        .any(|c| !is_trusted(&c[1]) && is_shortener_host(&shortener_domains, &c[1]))
+    {
         symbols.insert("TG_SHORTENER".to_string(), json!({"name": "TG_SHORTENER", "score": 0.0, "metric_score": 0.0}));
     }
     
@@ -393,44 +554,89 @@ fn detect_symbols(text: &str, user_id: u64, chat_id: i64, message_id: i32) -> se
         symbols.insert("TG_PHONE_SPAM".to_string(), json!({"name": "TG_PHONE_SPAM", "score": 0.0, "metric_score": 0.0}));
     }
     
-    // Gibberish detection
-    let no_space = text.split_whitespace().count() <= 1;
-    let len_ge_50 = text.len() > 50;
-    if len_ge_50 && no_space {
-        let vowels = ['a', 'e', 'i', 'o', 'u', 'y'];
-        let letters_only: Vec<char> = text.chars().filter(|c| c.is_ascii_alphabetic()).collect();
-        if !letters_only.is_empty() {
-            let vowel_count = letters_only.iter().filter(|c| vowels.contains(&c.to_ascii_lowercase())).count();
-            let consonant_ratio = 1.0 - (vowel_count as f64 / letters_only.len() as f64);
-            if consonant_ratio > 0.8 {
-                symbols.insert("TG_GIBBERISH".to_string(), json!({"name": "TG_GIBBERISH", "score": 0.0, "metric_score": 0.0}));
+    // Gibberish detection, across all words of the message
+    let gibberish = letter_counts(text).is_gibberish(
+        limit(threshold::GIBBERISH_MIN_LENGTH, threshold::DEFAULT_GIBBERISH_MIN_LENGTH),
+        limit(threshold::GIBBERISH_RATIO, threshold::DEFAULT_GIBBERISH_RATIO),
+    );
+    if long_enough && gibberish {
+        symbols.insert("TG_GIBBERISH".to_string(), json!({"name": "TG_GIBBERISH", "score": 0.0, "metric_score": 0.0}));
+    }
+    
+    // Whitelisted users, by id or username pattern
+    if lists::WHITELIST_USERS.is_listed(&mut conn, ChatId(chat_id), &user_id.to_string(), username).unwrap_or(false) {
+        symbols.insert("WHITELIST_USER".to_string(), json!({"name": "WHITELIST_USER", "score": -5.0, "metric_score": -5.0}));
+    }
+    
+    // White- and blacklisted words, global or scoped to this chat
+    let words: Vec<&str> = Regex::new(r"[A-Za-z0-9]+").unwrap().find_iter(text).map(|m| m.as_str()).collect();
+    for (list, name) in [(lists::WHITELIST_WORDS, "WHITELIST_WORD"), (lists::BLACKLIST_WORDS, "BLACKLIST_WORD")] {
+        let count = list.count_listed(&mut conn, ChatId(chat_id), &words).unwrap_or(0);
+        if count > 0 {
+            symbols.insert(name.to_string(), json!({"name": name, "score": 0.0, "metric_score": 0.0}));
+        }
+    }
+    
+    // Script allowlist
+    let script_feature_on = is_feature_enabled(&mut conn, chat_id, "foreign_script");
+    if script_feature_on {
+        if let Some(script) = dominant_script(text) {
+            let allowed: Vec<String> = conn
+                .smembers(format!("{}{}", chat_key, suffix::ALLOWED_SCRIPTS))
+                .unwrap_or_default();
+            if !allowed.is_empty() && !allowed.iter().any(|s| s == script) {
+                symbols.insert("TG_FOREIGN_SCRIPT".to_string(), json!({"name": "TG_FOREIGN_SCRIPT", "score": 0.0, "metric_score": 0.0}));
             }
         }
     }
     
-    // Update reputation
-    let _: () = conn.hset(&user_key, "rep", rep).unwrap();
+    // Detection symbols switched off for the chat or globally (`when_enabled` in the Lua)
+    symbols.retain(|name, _| symbol_feature(name).is_none_or(|feature| is_feature_enabled(&mut conn, chat_id, feature)));
+    
+    // Forwarded messages
+    if let Some(origin) = forward {
+        let forward_feature_on = is_feature_enabled(&mut conn, chat_id, "forwarded");
+        if forward_feature_on {
+            symbols.insert("TG_FORWARDED".to_string(), json!({"name": "TG_FORWARDED", "score": 0.0, "metric_score": 0.0, "options": [origin]}));
+        }
+    }
+    
+    if preview {
+        return json!(symbols);
+    }
+    
+    // Dry-run chats still get symbols but no penalties
+    let dry_run: Option<String> = conn.hget(&chat_key, "feat:dry_run").unwrap_or(None);
+    let dry_run = dry_run.as_deref() == Some("1");
+    
+    // Update reputation; dry-run chats only look at what it would be
+    let rep = if dry_run {
+        conn.hget::<_, _, Option<i64>>(&user_key, "rep").unwrap().unwrap_or(0) + rep_delta
+    } else {
+        add_rep(&mut conn, user_id, rep_delta).unwrap()
+    };
     
-    // Reputation-based symbols
+    // Reputation-based symbols, re-checked atomically so concurrent scans don't double-count
+    let gate = |conn: &mut redis::Connection, name: &str, default: f64| {
+        conn.hget::<_, _, Option<f64>>(key::TG_THRESHOLDS_KEY, name).unwrap().unwrap_or(default) as i64
+    };
+    let ban = gate(&mut conn, threshold::BAN_REP, threshold::DEFAULT_BAN_REP);
+    let penalty = gate(&mut conn, threshold::BAN_REP_PENALTY, threshold::DEFAULT_BAN_REP_PENALTY);
     let mut ban_triggered = false;
-    if banned_q > 3 {
+    if banned_q > gate(&mut conn, threshold::PERM_BAN_BANS, threshold::DEFAULT_PERM_BAN_BANS) {
         symbols.insert("TG_PERM_BAN".to_string(), json!({"name": "TG_PERM_BAN", "score": 0.0, "metric_score": 0.0}));
-        let _: () = conn.hincr(&chat_key, "perm_banned", 1).unwrap();
+        if !dry_run {
+            let _: () = conn.hincr(&chat_key, "perm_banned", 1).unwrap();
+        }
         ban_triggered = true;
-    } else if rep > 20 {
+    } else if (dry_run && rep > ban) || (!dry_run && ban_if_above(&mut conn, user_id, chat_id, ban, penalty).unwrap().is_some()) {
         symbols.insert("TG_BAN".to_string(), json!({"name": "TG_BAN", "score": 0.0, "metric_score": 0.0}));
-        rep -= 4;
-        let _: () = conn.hset(&user_key, "rep", rep).unwrap();
-        let _: () = conn.hset(&user_key, "banned", 1).unwrap();
-        let _: () = conn.hincr(&user_key, "banned_q", 1).unwrap();
-        let _: () = conn.hincr(&chat_key, "banned", 1).unwrap();
         ban_triggered = true;
     }
     
-    if !ban_triggered && rep > 10 {
+    let suspicious = gate(&mut conn, threshold::SUSPICIOUS_REP, threshold::DEFAULT_SUSPICIOUS_REP) + rep_credit;
+    if !ban_triggered && ((dry_run && rep > suspicious) || (!dry_run && add_rep_if_above(&mut conn, user_id, suspicious, 1).unwrap().is_some())) {
         symbols.insert("TG_SUSPICIOUS".to_string(), json!({"name": "TG_SUSPICIOUS", "score": 0.0, "metric_score": 0.0}));
-        rep += 1;
-        let _: () = conn.hset(&user_key, "rep", rep).unwrap();
     }
     
     json!(symbols)
@@ -448,6 +654,8 @@ pub struct TelegramConfig {
     pub exp_flood: u64,
     pub exp_ban:   u64,
     pub banned_q:   u64,
+    pub cross_post: u32,
+    pub cross_post_window: u64,
 }
 
 impl TelegramConfig {
@@ -488,6 +696,8 @@ impl TelegramConfig {
             exp_flood:  map.get("exp_flood") .and_then(|v| v.parse().ok()).unwrap_or_default(),
             exp_ban:    map.get("exp_ban")   .and_then(|v| v.parse().ok()).unwrap_or_default(),
             banned_q:    map.get("banned_q")   .and_then(|v| v.parse().ok()).unwrap_or_default(),
+            cross_post: map.get("cross_post").and_then(|v| v.parse().ok()).unwrap_or_default(),
+            cross_post_window: map.get("cross_post_window").and_then(|v| v.parse().ok()).unwrap_or_default(),
         })
     }
 }
@@ -600,6 +810,159 @@ fn make_message_with_reply(chat_id: i64, user_id: u64, username: &str, text: &st
     }
 }
 
+fn make_channel_origin(channel_id: i64) -> MessageOrigin {
+    MessageOrigin::Channel {
+        date: Utc::now(),
+        chat: Chat {
+            id: ChatId(channel_id),
+            kind: ChatKind::Public(ChatPublic {
+                title: Some("Promo".into()),
+                kind: PublicChatKind::Channel(PublicChatChannel { username: Some("promo".into()) }),
+            }),
+        },
+        message_id: MessageId(1),
+        author_signature: None,
+    }
+}
+
+/// A message posted as `sender_chat` (a channel, or the chat itself for anonymous admins) with no user.
+fn make_message_as_chat(chat_id: i64, sender_chat: Chat, text: &str, msg_id: u32) -> Message {
+    let mut msg = make_message(chat_id, 0, "", text, msg_id);
+    msg.from = None;
+    msg.sender_chat = Some(sender_chat);
+    msg
+}
+
+fn make_forwarded_message(chat_id: i64, user_id: u64, username: &str, text: &str, msg_id: u32, origin: MessageOrigin) -> Message {
+    let mut msg = make_message(chat_id, user_id, username, text, msg_id);
+    if let MessageKind::Common(common) = &mut msg.kind {
+        common.forward_origin = Some(origin);
+    }
+    msg
+}
+
+/// A document named `file_name` sent with `caption`.
+fn make_document_message(chat_id: i64, user_id: u64, username: &str, caption: &str, file_name: &str, msg_id: u32) -> Message {
+    let mut msg = make_message(chat_id, user_id, username, caption, msg_id);
+    if let MessageKind::Common(common) = &mut msg.kind {
+        common.media_kind = MediaKind::Document(MediaDocument {
+            document: Document {
+                file: FileMeta { id: format!("file{}", msg_id), unique_id: format!("unique{}", msg_id), size: 1024 },
+                thumbnail: None,
+                file_name: Some(file_name.into()),
+                mime_type: None,
+            },
+            caption: Some(caption.into()),
+            caption_entities: Vec::new(),
+            media_group_id: None,
+        });
+    }
+    msg
+}
+
+/// Metadata of a file Telegram knows as `unique_id`.
+fn make_file(unique_id: &str, msg_id: u32) -> FileMeta {
+    FileMeta { id: format!("file{}", msg_id), unique_id: unique_id.into(), size: 1024 }
+}
+
+/// A photo, in two sizes, whose largest size is the file `unique_id`.
+fn make_photo_message(chat_id: i64, user_id: u64, username: &str, caption: &str, unique_id: &str, msg_id: u32) -> Message {
+    let mut msg = make_message(chat_id, user_id, username, caption, msg_id);
+    if let MessageKind::Common(common) = &mut msg.kind {
+        common.media_kind = MediaKind::Photo(MediaPhoto {
+            photo: vec![
+                PhotoSize { file: make_file(&format!("{}_thumb", unique_id), msg_id), width: 90, height: 90 },
+                PhotoSize { file: make_file(unique_id, msg_id), width: 800, height: 800 },
+            ],
+            caption: Some(caption.into()),
+            caption_entities: Vec::new(),
+            show_caption_above_media: false,
+            has_media_spoiler: false,
+            media_group_id: None,
+        });
+    }
+    msg
+}
+
+/// A sticker that is the file `unique_id`.
+fn make_sticker_message(chat_id: i64, user_id: u64, username: &str, unique_id: &str, msg_id: u32) -> Message {
+    let mut msg = make_message(chat_id, user_id, username, "", msg_id);
+    if let MessageKind::Common(common) = &mut msg.kind {
+        common.media_kind = MediaKind::Sticker(MediaSticker {
+            sticker: Sticker {
+                file: make_file(unique_id, msg_id),
+                width: 512,
+                height: 512,
+                kind: StickerKind::Regular { premium_animation: None },
+                flags: StickerFormatFlags { is_animated: false, is_video: false },
+                thumbnail: None,
+                emoji: Some("🔥".into()),
+                set_name: None,
+                needs_repainting: false,
+            },
+        });
+    }
+    msg
+}
+
+/// A contact card for `phone_number` shared by the user.
+fn make_contact_message(chat_id: i64, user_id: u64, username: &str, phone_number: &str, msg_id: u32) -> Message {
+    let mut msg = make_message(chat_id, user_id, username, "", msg_id);
+    if let MessageKind::Common(common) = &mut msg.kind {
+        common.media_kind = MediaKind::Contact(MediaContact {
+            contact: Contact {
+                phone_number: phone_number.into(),
+                first_name: "Support".into(),
+                last_name: None,
+                user_id: None,
+                vcard: None,
+            },
+        });
+    }
+    msg
+}
+
+
+#[test]
+fn message_sender_classifies_posts_made_as_a_chat() {
+    let from_user = make_message(4020, 1040, "member", "hi", 1);
+    assert_eq!(message_sender(&from_user), Some(Sender::User(UserId(1040))));
+
+    let anonymous = make_message_as_chat(4020, make_chat(4020), "hi", 2);
+    assert_eq!(message_sender(&anonymous), Some(Sender::AnonymousAdmin(ChatId(4020))));
+
+    let channel = make_message_as_chat(4020, make_chat(-1004021), "hi", 3);
+    assert_eq!(message_sender(&channel), Some(Sender::Channel(ChatId(-1004021))));
+    assert_eq!(Sender::Channel(ChatId(-1004021)).id(), -1004021);
+}
+
+#[tokio::test]
+#[serial]
+async fn messages_without_a_user_are_handled_without_reputation() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4022;
+    let channel_id: i64 = -1004023;
+    let bot = Bot::new("DUMMY");
+
+    let channel_post = make_message_as_chat(chat_id, make_chat(channel_id), "News from our channel", 1);
+    assert!(message_handler(bot.clone(), channel_post.clone()).await.is_ok());
+    assert!(handle_message(bot.clone(), channel_post.clone()).await.is_ok());
+    let reply = scan_msg(channel_post, "News from our channel".into()).await.unwrap();
+    assert!(!reply.symbols.contains_key(symbol::TG_FLOOD), "Channel posts skip the stateful rules");
+
+    let anonymous = make_message_as_chat(chat_id, make_chat(chat_id), "Pinned the rules", 2);
+    assert!(handle_message(bot.clone(), anonymous.clone()).await.is_ok());
+    // Refused politely instead of panicking; the dummy bot can't send the refusal
+    let res = handle_admin_command(bot, anonymous, AdminCommand::Stats { args: String::new() }).await;
+    assert!(res.is_err(), "Expected dummy send_message to fail");
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let user_keys: Vec<String> = conn.keys(format!("{}*", key::TG_USERS_PREFIX)).unwrap();
+    assert!(user_keys.is_empty(), "No user reputation should be tracked, found {:?}", user_keys);
+}
 
 #[tokio::test]
 #[serial]
@@ -620,10 +983,11 @@ async fn tg_flood_sets_symbol_and_increments_stats() {
         .hset(key.clone(), field::REP, 0)
         .expect("Failed to set user reputation");
 
+    // The counter leads the text: TG_REPEAT ignores trailing digits, and a repeat would add rep too
     for i in 1..=CONFIG.flood {
         scan_msg(
-            make_message(chat_id, user_id, "test", &format!("msg{i}"), i),
-            format!("msg{i}"),
+            make_message(chat_id, user_id, "test", &format!("{i} msg"), i),
+            format!("{i} msg"),
         )
             .await
             .ok()
@@ -702,457 +1066,3631 @@ async fn tg_repeat_sets_symbol_and_increments_rep() {
 
 #[tokio::test]
 #[serial]
-async fn tg_suspicious_sets_symbol() {
+async fn adaptive_limits_follow_each_chats_message_rate() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let chat_id = 3003;
-    let user_id = 123;
-    let key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let (quiet, busy) = (ChatId(-1004080), ChatId(-1004081));
+    let now = 1_700_000_000;
 
-    // Manually bump reputation above the threshold
-    let client = redis::Client::open("redis://127.0.0.1/")
-        .expect("Failed to connect to Redis");
-    let mut conn = client
-        .get_connection()
-        .expect("Failed to connect to Redis");
-    let _: () = conn
-        .hset(key.clone(), field::REP, CONFIG.suspicious + 1)
-        .expect("Failed to set user reputation");
-    tokio::time::sleep(Duration::from_millis(50)).await;
-    let reply = scan_msg(
-        make_message(chat_id, user_id, "test", "Hello", 1),
-        "Hello".into(),
-    )
-        .await
-        .ok()
-        .unwrap();
-    tokio::time::sleep(Duration::from_millis(50)).await;
-    let rep: u32 = conn
-        .hget(key.clone(), field::REP)
-        .expect("Failed to get user reputation");
-    assert!(
-        reply.symbols.contains_key(symbol::TG_SUSPICIOUS),
-        "Expected TG_SUSPICIOUS for high-rep user"
-    );
-    assert_eq!(rep, CONFIG.suspicious + 2);
+    // A minute with 2 messages in one chat and 60 in the other
+    for _ in 0..2 {
+        record_message(&mut conn, quiet).unwrap();
+    }
+    for _ in 0..60 {
+        record_message(&mut conn, busy).unwrap();
+    }
+    assert_eq!(sample_rate(&mut conn, quiet, now).unwrap(), 2.0);
+    assert_eq!(sample_rate(&mut conn, busy, now).unwrap(), 60.0);
+
+    // Scale = clamp(rate / 10, 0.5, 3) applied to the flood limit (30) and repeat limit (6)
+    let (quiet_flood, quiet_repeat) = effective_limits(&mut conn, quiet);
+    let (busy_flood, busy_repeat) = effective_limits(&mut conn, busy);
+    assert_eq!((quiet_flood, quiet_repeat), (15, 3));
+    assert_eq!((busy_flood, busy_repeat), (90, 18));
+
+    // Admin bounds narrow the scale
+    set_scale_bounds(&mut conn, busy, Some((1.0, 2.0))).unwrap();
+    assert_eq!(effective_limits(&mut conn, busy), (60, 12));
+
+    // The rate is a moving average: a silent minute only pulls it down partway
+    let rate = sample_rate(&mut conn, busy, now + 60).unwrap();
+    assert!((rate - 42.0).abs() < 1e-9, "rate was {}", rate);
+
+    // Detection only sees the scaled limits while the feature is on
+    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, quiet.0);
+    refresh_chat(&mut conn, quiet, now + 60).unwrap();
+    let scaled: Option<i64> = conn.hget(&chat_key, field::ADAPTIVE_FLOOD_MAX).unwrap();
+    assert_eq!(scaled, None);
+    let _: () = conn.hset(&chat_key, format!("feat:{}", ADAPTIVE_FEATURE), "1").unwrap();
+    refresh_chat(&mut conn, quiet, now + 120).unwrap();
+    let scaled: (Option<i64>, Option<i64>) = conn.hget(&chat_key, &[field::ADAPTIVE_FLOOD_MAX, field::ADAPTIVE_REPEAT_MAX]).unwrap();
+    assert_eq!(scaled, (Some(15), Some(3)));
 }
 
 #[tokio::test]
 #[serial]
-async fn tg_ban_sets_symbol_and_updates_ban_state() {
+async fn ban_rate_defers_bans_past_the_chats_limit() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let chat_id: i64 = 4004;
-    let user_id: u64 = 777;
-    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
-    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, chat_id);
-
     let client = redis::Client::open("redis://127.0.0.1/").unwrap();
     let mut conn = client.get_connection().unwrap();
-    let _: () = conn.hset(user_key.clone(), field::REP, CONFIG.ban + 1).expect("Failed to set rep");
-    tokio::time::sleep(Duration::from_millis(50)).await;
-
-    let reply = scan_msg(
-        make_message(chat_id, user_id, "tester", "Test message", 1),
-        "Test message".into()
-    ).await.ok().unwrap();
-    tokio::time::sleep(Duration::from_millis(50)).await;
-
-    assert!(reply.symbols.contains_key(symbol::TG_BAN), "Expected TG_BAN symbol for high-rep user");
-    let new_rep: i64 = conn.hget(user_key.clone(), field::REP).expect("Failed to get rep");
-    assert_eq!(new_rep, (CONFIG.ban + 1) as i64 - 4, "Rep should decrease by 5 on TG_BAN"); // TG_SUSPICIOUS updates rep on message
-    let banned_flag: i64 = conn.hget(user_key.clone(), field::BANNED).expect("Failed to get 'banned' field");
-    assert_eq!(banned_flag, 1, "User 'banned' flag should be set");
-    let ban_count: i64 = conn.hget(user_key.clone(), "banned_q").expect("Failed to get 'banned_q'");
-    assert_eq!(ban_count, 1, "User ban count should increment");
-    let chat_bans: i64 = conn.hget(chat_key.clone(), field::BANNED).expect("Failed to get chat banned count");
-    assert_eq!(chat_bans, 1, "Chat's banned count should increment by 1");
+    let chat = ChatId(4053);
+    set_ban_rate_limit(&mut conn, chat, Some(3)).unwrap();
+    let now = Utc::now().timestamp_millis();
+
+    // A burst of five bans within a second
+    let decisions: Vec<BanDecision> = (0..5).map(|i| admit_ban(&mut conn, chat, now + i * 100).unwrap()).collect();
+    assert_eq!(&decisions[..3], &[BanDecision::Allowed; 3]);
+    assert_eq!(decisions[3], BanDecision::Deferred { recent: 3, limit: 3, escalate: true });
+    assert_eq!(decisions[4], BanDecision::Deferred { recent: 3, limit: 3, escalate: false }, "Admins are alerted once per window");
+
+    // Other chats are unaffected
+    assert_eq!(admit_ban(&mut conn, ChatId(4054), now).unwrap(), BanDecision::Allowed);
+
+    // Once the burst leaves the window bans go ahead again
+    let later = now + (ban_rate::WINDOW_SECS + 1) * 1000;
+    assert_eq!(admit_ban(&mut conn, chat, later).unwrap(), BanDecision::Allowed);
 }
 
 #[tokio::test]
 #[serial]
-async fn tg_perm_ban_sets_symbol_and_updates_perm_ban_count() {
+async fn tg_flood_uses_the_chats_own_limit() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let chat_id: i64 = 5005;
-    let user_id: u64 = 888;
-    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
-    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, chat_id);
-
-    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
-    let mut conn = client.get_connection().unwrap();
-    let _: () = conn.hset(user_key.clone(), field::BANNED_Q, CONFIG.banned_q + 1).expect("Failed to set banned_q");
-    let _: () = conn.hset(user_key.clone(), field::REP, 0).expect("Failed to set rep");
-    tokio::time::sleep(Duration::from_millis(50)).await;
+    let quiet_chat = 1002;
+    let busy_chat = 1003;
+    let client = redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut conn = client.get_connection().expect("Failed to connect to Redis");
+    assert_eq!(flood_limit(&mut conn, ChatId(quiet_chat)), (30, FloodLimitSource::Global));
+    set_flood_limit(&mut conn, ChatId(quiet_chat), Some(5)).unwrap();
+    assert_eq!(flood_limit(&mut conn, ChatId(quiet_chat)), (5, FloodLimitSource::Chat));
+
+    // The quiet chat floods on the sixth message
+    for i in 1..=6u32 {
+        let text = format!("quiet {}", i);
+        let reply = scan_msg(make_message(quiet_chat, 43, "test", &text, i), text).await.unwrap();
+        assert_eq!(reply.symbols.contains_key(symbol::TG_FLOOD), i == 6, "message {}", i);
+    }
 
-    let reply = scan_msg(
-        make_message(chat_id, user_id, "tester", "Another message", 1),
-        "Another message".into()
-    ).await.ok().unwrap();
-    tokio::time::sleep(Duration::from_millis(50)).await;
+    // Other chats keep the global limit
+    for i in 1..=6u32 {
+        let text = format!("busy {}", i);
+        let reply = scan_msg(make_message(busy_chat, 44, "test", &text, i), text).await.unwrap();
+        assert!(!reply.symbols.contains_key(symbol::TG_FLOOD), "message {}", i);
+    }
 
-    assert!(reply.symbols.contains_key(symbol::TG_PERM_BAN), "Expected TG_PERM_BAN symbol for frequent offender");
-    assert!(!reply.symbols.contains_key(symbol::TG_BAN), "TG_BAN should not be present when only perm ban triggers");
-    let perm_bans: i64 = conn.hget(chat_key.clone(), "perm_banned").expect("Failed to get perm_banned count");
-    assert_eq!(perm_bans, 1, "Chat's permanent ban count should increment by 1");
-    let final_ban_count: i64 = conn.hget(user_key.clone(), field::BANNED_Q).expect("Failed to get 'banned_q'");
-    assert_eq!(final_ban_count, 4, "User banned_q should remain at 4 (perm ban triggered)");
+    set_flood_limit(&mut conn, ChatId(quiet_chat), None).unwrap();
+    assert_eq!(flood_limit(&mut conn, ChatId(quiet_chat)), (30, FloodLimitSource::Global));
 }
 
 #[tokio::test]
 #[serial]
-async fn makeadmin_adds_admin_chat_and_generates_keyboard() {
+async fn tg_repeat_resets_outside_the_repeat_window() {
     flush_redis();
-    tokio::time::sleep(Duration::from_millis(50)).await;
+    let chat_id = 2468;
+    let user_id = 1357;
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let client = redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut conn = client.get_connection().expect("Failed to connect to Redis");
+
+    // The same phrase once a day never accumulates towards TG_REPEAT
+    let a_day_ago = Utc::now().timestamp() - 86_400;
+    for i in 0..=CONFIG.repeated + 1 {
+        let _: () = conn.hset(&user_key, field::LAST_MSG_TIME, a_day_ago).unwrap();
+        let reply = scan_msg(
+            make_message(chat_id, user_id, "test", "Good morning", i),
+            "Good morning".into(),
+        )
+            .await
+            .unwrap();
+        assert!(
+            !reply.symbols.contains_key(symbol::TG_REPEAT),
+            "Identical messages spaced beyond the window must not trigger TG_REPEAT (message {})", i
+        );
+    }
+    let count: i64 = conn.hget(&user_key, field::EQ_MSG_COUNT).unwrap();
+    assert_eq!(count, 1, "Each stale repeat starts a new count");
 
-    let user_id: u64 = 42;
-    let current_chat: i64 = 1000;
-    let other_chat: i64 = 2000;
+    // A repeat within the window still counts
+    let _ = scan_msg(
+        make_message(chat_id, user_id, "test", "Good morning", 100),
+        "Good morning".into(),
+    )
+        .await
+        .unwrap();
+    let count: i64 = conn.hget(&user_key, field::EQ_MSG_COUNT).unwrap();
+    assert_eq!(count, 2);
+}
 
-    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
-    let mut conn = client.get_connection().unwrap();
-    let _: () = conn.sadd(format!("{}{}", user_id, suffix::BOT_CHATS), current_chat).unwrap();
-    let _: () = conn.sadd(format!("{}{}", user_id, suffix::BOT_CHATS), other_chat).unwrap();
-    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, current_chat), field::NAME, "CurrentChat").unwrap();
-    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, other_chat), field::NAME, "OtherChat").unwrap();
+#[tokio::test]
+#[serial]
+async fn tg_repeat_counts_messages_that_only_differ_in_a_trailing_counter() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let chat_id = 2469;
+    let user_id = 1358;
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let client = redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut conn = client.get_connection().expect("Failed to connect to Redis");
 
-    let bot = Bot::new("DUMMY");
-    let msg = make_message(current_chat, user_id, "tester", "/makeadmin", 1);
-    let result = handle_admin_command(bot, msg, AdminCommand::MakeAdmin).await;
-    assert!(result.is_err(), "Expected send_message to fail with dummy token");
+    let mut reply = None;
+    for i in 0..=CONFIG.repeated + 1 {
+        // Counters, punctuation, case and extra spaces don't make a message new
+        let text = if i % 2 == 0 { format!("Buy now {}", i) } else { format!("buy  NOW!! #{}", i) };
+        reply = Some(scan_msg(make_message(chat_id, user_id, "test", &text, i), text).await.unwrap());
+    }
+    assert!(
+        reply.unwrap().symbols.contains_key(symbol::TG_REPEAT),
+        "Near-identical messages should accumulate towards TG_REPEAT"
+    );
+    let last_msg: String = conn.hget(&user_key, field::LAST_MSG).unwrap();
+    assert_eq!(last_msg, "buy now", "The normalized form is stored");
 
-    let admin_chats: Vec<i64> = conn.smembers(format!("{}{}", user_id, suffix::ADMIN_CHATS)).unwrap();
-    assert!(admin_chats.contains(&current_chat), "Admin chat not set in Redis");
-    let bot_chats: Vec<i64> = conn.smembers(format!("{}{}", user_id, suffix::BOT_CHATS)).unwrap();
-    assert!(bot_chats.contains(&current_chat) && bot_chats.contains(&other_chat));
-    let expected_buttons = bot_chats.len() - 1;
-    assert_eq!(expected_buttons, 1, "Inline keyboard should list exactly one other chat");
+    // A different message starts over
+    let _ = scan_msg(make_message(chat_id, user_id, "test", "Buy later 1", 50), "Buy later 1".into())
+        .await
+        .unwrap();
+    let count: i64 = conn.hget(&user_key, field::EQ_MSG_COUNT).unwrap();
+    assert_eq!(count, 1);
 }
 
-
 #[tokio::test]
 #[serial]
-async fn reputation_command_returns_value_or_zero() {
+async fn tg_repeat_similarity_counts_near_identical_messages() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
+    let chat_id = 2470;
+    let user_id = 1359;
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let client = redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut conn = client.get_connection().expect("Failed to connect to Redis");
 
-    let user_id: u64 = 100;
-    let target_username = "someuser";
-    let rep_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
-
-    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
-    let mut conn = client.get_connection().unwrap();
-    let res: bool = conn.hset(rep_key.clone(), field::REP, 0).expect("Failed to set rep");
-    assert!(res);
-
-    let bot = Bot::new("DUMMY");
-    let chat_id: i64 = 1111;
-    let msg1 = make_message(chat_id, user_id, "tester", &format!("/reputation {}", target_username), 1);
-    let res1 = handle_admin_command(bot.clone(), msg1, AdminCommand::Reputation { user: target_username.into() }).await;
-    assert!(res1.is_err(), "Expected dummy send_message to fail");
-    let rep: bool = conn.hexists(rep_key.clone(), field::REP).expect("Failed to get rep");
-    assert!(rep, "Reputation key should not exist for new user");
+    // By default a changed letter makes a new message
+    for (i, text) in ["Cheap crypto signals here", "Cheap crypt0 signals here"].into_iter().enumerate() {
+        let _ = scan_msg(make_message(chat_id, user_id, "test", text, i as u32), text.into()).await.unwrap();
+    }
+    let count: i64 = conn.hget(&user_key, field::EQ_MSG_COUNT).unwrap();
+    assert_eq!(count, 1);
 
-    let _:() = conn.hset(rep_key.clone(), field::REP, 5).expect("Failed to set rep");
-    let msg2 = make_message(chat_id, user_id, "tester", &format!("/reputation {}", target_username), 2);
-    let res2 = handle_admin_command(bot, msg2, AdminCommand::Reputation { user: target_username.into() }).await;
-    assert!(res2.is_err());
-    let stored: i64 = conn.hget(rep_key.clone(), field::REP).expect("Failed to get rep");
-    assert_eq!(stored, 5, "Reputation value should remain 5 in Redis");
+    let _: () = conn.hset(key::TG_THRESHOLDS_KEY, threshold::REPEAT_SIMILARITY, 0.9).unwrap();
+    for (i, text) in ["Cheap crypto signals here", "Cheap crypt0 signals hera"].into_iter().enumerate() {
+        let _ = scan_msg(make_message(chat_id, user_id, "test", text, 10 + i as u32), text.into()).await.unwrap();
+    }
+    let count: i64 = conn.hget(&user_key, field::EQ_MSG_COUNT).unwrap();
+    assert_eq!(count, 3, "Messages at least 90% similar count as repeats");
 }
 
 #[tokio::test]
 #[serial]
-async fn stats_command_shows_chat_stats_or_list() {
+async fn health_reports_whether_rspamd_is_reachable() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let user_id: u64 = 555;
-    let admin_chat_id: i64 = 9000;
-    let group_chat1: i64 = 9001;
-    let group_chat2: i64 = 9002;
-
-    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
-    let mut conn = client.get_connection().unwrap();
-    let _: () = conn.sadd(format!("{}{}", user_id, suffix::ADMIN_CHATS), admin_chat_id).unwrap();
-    let _: () = conn.sadd(format!("{}{}{}", key::ADMIN_PREFIX, admin_chat_id, suffix::MODERATED_CHATS), group_chat1).unwrap();
-    let _: () = conn.sadd(format!("{}{}{}", key::ADMIN_PREFIX, admin_chat_id, suffix::MODERATED_CHATS), group_chat2).unwrap();
-    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, group_chat1), field::NAME, "GroupChat1").unwrap();
-    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, group_chat1), field::SPAM_COUNT, 5).unwrap();
-    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, group_chat1), field::ADMIN_CHAT, admin_chat_id).unwrap();
-    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, group_chat2), field::NAME, "GroupChat2").unwrap();
-    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, group_chat2), field::SPAM_COUNT, 3).unwrap();
-    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, group_chat2), field::ADMIN_CHAT, admin_chat_id).unwrap();
+    let state = |subsystems: &[SubsystemHealth], name: &str| {
+        subsystems.iter().find(|s| s.name == name).map(|s| s.state.clone())
+    };
 
-    let bot = Bot::new("DUMMY");
-    let msg1 = make_message(group_chat1, user_id, "tester", "/stats", 1);
-    let res1 = handle_admin_command(bot.clone(), msg1, AdminCommand::Stats).await;
-    assert!(res1.is_err());
-    let stats1: HashMap<String, String> = conn.hgetall(format!("{}{}", key::TG_CHATS_PREFIX, group_chat1)).unwrap();
-    assert!(stats1.get(field::NAME).is_some() && stats1.get(field::ADMIN_CHAT).is_some());
-    assert_eq!(stats1.get(field::SPAM_COUNT), Some(&"5".to_string()));
+    let subsystems = check_health().await;
+    assert_eq!(state(&subsystems, "Redis"), Some(HealthState::Up));
+    assert_eq!(state(&subsystems, "Rspamd"), Some(HealthState::Up), "The mock Rspamd answers /ping");
+    assert!(state(&subsystems, "Bayes").is_some() && state(&subsystems, "Neural").is_some());
+    let reply = render_health(&subsystems);
+    assert_eq!(reply.lines().count(), 5, "A header and a line per subsystem: {}", reply);
+    assert!(reply.contains("✅ Rspamd: reachable"), "{}", reply);
+
+    // Nothing listens on a port we just released
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let closed_port = listener.local_addr().unwrap().port();
+    drop(listener);
+    let mock_url = std::env::var("RSPAMD_URL").unwrap();
+    std::env::set_var("RSPAMD_URL", format!("http://127.0.0.1:{}", closed_port));
+    let subsystems = check_health().await;
+    std::env::set_var("RSPAMD_URL", mock_url);
+    assert_eq!(state(&subsystems, "Rspamd"), Some(HealthState::Down));
+    assert_eq!(state(&subsystems, "Redis"), Some(HealthState::Up));
+    assert!(render_health(&subsystems).contains("❌ Rspamd: unreachable"));
+}
 
-    // 3. Admin chat: user sends /stats in the admin control chat
-    let msg2 = make_message(admin_chat_id, user_id, "tester", "/stats", 2);
-    let res2 = handle_admin_command(bot, msg2, AdminCommand::Stats).await;
-    assert!(res2.is_err());
-    let moderated: Vec<i64> = conn.smembers(format!("{}{}{}", key::ADMIN_PREFIX, admin_chat_id, suffix::MODERATED_CHATS)).unwrap();
-    assert_eq!(moderated.len(), 2);
-    assert!(moderated.contains(&group_chat1) && moderated.contains(&group_chat2));
-    let name1: String = conn.hget(format!("{}{}", key::TG_CHATS_PREFIX, group_chat1), field::NAME).unwrap();
-    let name2: String = conn.hget(format!("{}{}", key::TG_CHATS_PREFIX, group_chat2), field::NAME).unwrap();
-    assert_eq!(name1, "GroupChat1");
-    assert_eq!(name2, "GroupChat2");
+#[test]
+fn normalize_repeat_strips_counters_case_and_spacing() {
+    assert_eq!(normalize_repeat("Buy now 1"), "buy now");
+    assert_eq!(normalize_repeat("  BUY   now!!! 23 "), "buy now");
+    assert_eq!(normalize_repeat("Куплю  СЕЙЧАС 7"), "куплю сейчас");
+    // Only trailing counters go
+    assert_eq!(normalize_repeat("2 for 1 deal"), "2 for 1 deal");
+    assert!(!is_repeat("", "12345", 1.0), "Messages of only digits never repeat");
+    assert_eq!(similarity("abcd", "abcf"), 0.75);
+    assert!(is_repeat("cheap signals", "Cheap signal 5", 0.9));
+    assert!(!is_repeat("cheap signals", "Cheap signal 5", 1.0));
 }
 
 #[tokio::test]
 #[serial]
-async fn addregex_command_parses_and_writes_rule() {
+async fn tg_cross_post_sets_symbol_across_chats() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let symbol = "TESTSYM";
-    
-    let file_path = PathBuf::from(format!("/etc/rspamd/lua.local.d/telegram_regex_{}.lua", symbol));
-    let _ = fs::remove_file(&file_path);
+    let user_id = 321;
+    let chats: [i64; 3] = [5001, 5002, 5003];
+    let text = "Join my channel for free crypto";
 
-    let bot = Bot::new("DUMMY");
+    for (i, chat_id) in chats.iter().enumerate() {
+        let reply = scan_msg(
+            make_message(*chat_id, user_id, "test", text, i as u32 + 1),
+            text.into(),
+        )
+            .await
+            .ok()
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        if (i as u32 + 1) > CONFIG.cross_post {
+            assert!(
+                reply.symbols.contains_key(symbol::TG_CROSS_POST),
+                "Expected TG_CROSS_POST once text reached {} chats",
+                i + 1
+            );
+        } else {
+            assert!(
+                !reply.symbols.contains_key(symbol::TG_CROSS_POST),
+                "TG_CROSS_POST should not fire for {} chat(s)",
+                i + 1
+            );
+        }
+    }
+}
 
-    // Invalid: two parts → usage error, no file
-    let bad = format!("{}|{}", symbol, "[0-9]+");
-    let msg1 = make_message(1, 999, "t", &format!("/addregex {}", bad), 1);
-    let _ = handle_admin_command(bot.clone(), msg1, AdminCommand::AddRegex { pattern: bad }).await;
-    assert!(!file_path.exists(), "No file for bad input");
+/// Name and fields of each recorded span
+type RecordedSpans = Arc<Mutex<Vec<(String, HashMap<String, String>)>>>;
 
-    // Valid: three parts → file created
-    let good = format!("{}|{}|{}", symbol, "[0-9]+", 5);
-    let msg2 = make_message(1, 999, "t", &format!("/addregex {}", good), 2);
-    let _ = handle_admin_command(bot, msg2, AdminCommand::AddRegex { pattern: good }).await;
-    // now the file should exist
-    assert!(file_path.exists(), "File should be created for valid input");
+/// Name and fields of every span opened while it is the default subscriber
+#[derive(Clone, Default)]
+struct SpanRecorder {
+    spans: RecordedSpans,
+}
 
-    let contents = fs::read_to_string(&file_path).unwrap();
-    assert!(contents.contains(&format!("config['regexp']['{}']", symbol)));
-    assert!(contents.contains("re = '[0-9]+'"));
-    assert!(contents.contains("score = 5"));
+struct FieldRecorder<'a>(&'a mut HashMap<String, String>);
 
-    // cleanup
-    let _ = fs::remove_file(&file_path);
+impl tracing::field::Visit for FieldRecorder<'_> {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
 
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanRecorder {
+    fn on_new_span(&self, attrs: &tracing::span::Attributes<'_>, _id: &tracing::span::Id, _ctx: Context<'_, S>) {
+        let mut fields = HashMap::new();
+        attrs.record(&mut FieldRecorder(&mut fields));
+        self.spans.lock().unwrap().push((attrs.metadata().name().to_string(), fields));
+    }
 }
+
 #[tokio::test]
 #[serial]
-async fn whitelist_command_adds_entries() {
+async fn message_and_command_spans_carry_chat_user_and_message_ids() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let chat_id: i64 = 6006;
-    let user_id: u64 = 101;
+    let chat_id: i64 = 4031;
+    let user_id: u64 = 781;
+    let recorder = SpanRecorder::default();
+    let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
     let bot = Bot::new("DUMMY");
-    let msg_user = make_message(chat_id, user_id, "tester", "/whitelist user|add|101", 1);
-    let _ = handle_admin_command(bot.clone(), msg_user, AdminCommand::Whitelist { pattern: "user|add|101".into() }).await;
+    let _ = handle_message(bot.clone(), make_message(chat_id, user_id, "alice", "Hello everyone", 3)).await;
+    let _ = handle_admin_command(bot, make_message(chat_id, user_id, "alice", "/stats", 4), AdminCommand::Stats { args: String::new() }).await;
+
+    let spans = recorder.spans.lock().unwrap();
+    for (name, message_id) in [("handle_message", 3), ("scan_msg", 3), ("handle_admin_command", 4)] {
+        let fields = spans
+            .iter()
+            .find(|(span, _)| span == name)
+            .map(|(_, fields)| fields)
+            .unwrap_or_else(|| panic!("No {} span among {:?}", name, spans));
+        assert_eq!(fields.get("chat_id"), Some(&chat_id.to_string()), "{} span", name);
+        assert_eq!(fields.get("user_id"), Some(&user_id.to_string()), "{} span", name);
+        assert_eq!(fields.get("message_id"), Some(&message_id.to_string()), "{} span", name);
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_media_repeat_flags_the_same_media_posted_again() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4032;
+    let user_id: u64 = 782;
+    let photo = make_photo_message(chat_id, user_id, "reposter", "Hot deal", "photo-a", 1);
+    assert_eq!(media_unique_id(&photo), Some("photo-a"), "Photos are identified by their largest size");
 
+    let bot = Bot::new("DUMMY");
+    assert!(handle_message(bot, photo).await.is_ok());
     let client = redis::Client::open("redis://127.0.0.1/").unwrap();
     let mut conn = client.get_connection().unwrap();
-    let has_user: bool = conn.sismember(key::TG_WHITELIST_USER_KEY, "101").unwrap();
-    assert!(has_user, "User should be in whitelist set");
+    let posts: i64 = conn.get(format!("{}{}:{}:photo-a", key::TG_MEDIA_PREFIX, chat_id, user_id)).unwrap();
+    assert_eq!(posts, 1, "handle_message should scan photos and count their media");
+
+    let reply = scan_msg(make_photo_message(chat_id, user_id, "reposter", "Hot deal", "photo-a", 2), "Hot deal".into()).await.unwrap();
+    assert!(!reply.symbols.contains_key(symbol::TG_MEDIA_REPEAT), "A second post is not yet a repeat");
+    // Media posted by someone else, or another photo, counts apart
+    for (user, unique_id, msg_id) in [(783, "photo-a", 3), (user_id, "photo-b", 4)] {
+        let reply = scan_msg(make_photo_message(chat_id, user, "member", "Hot deal", unique_id, msg_id), "Hot deal".into()).await.unwrap();
+        assert!(!reply.symbols.contains_key(symbol::TG_MEDIA_REPEAT));
+    }
+    let reply = scan_msg(make_photo_message(chat_id, user_id, "reposter", "Hot deal", "photo-a", 5), "Hot deal".into()).await.unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_MEDIA_REPEAT), "The third post of the same photo should be flagged");
+    assert_eq!(reply.symbols[symbol::TG_MEDIA_REPEAT].options, Some(vec!["photo-a".to_string()]));
 
-    let msg_word = make_message(chat_id, user_id, "tester", "/whitelist word|add|hello", 2);
-    let _ = handle_admin_command(bot, msg_word, AdminCommand::Whitelist { pattern: "word|add|hello".into() }).await;
-    let words: Vec<String> = conn.smembers(key::TG_WHITELIST_WORD_KEY).unwrap();
-    assert!(words.contains(&"hello".to_string()));
+    // Stickers repeat the same way, and a rescan doesn't count twice
+    for msg_id in 6..=7 {
+        scan_msg(make_sticker_message(chat_id, user_id, "reposter", "sticker-a", msg_id), String::new()).await.unwrap();
+    }
+    let rescan = scan_msg(make_sticker_message(chat_id, user_id, "reposter", "sticker-a", 7), String::new()).await.unwrap();
+    assert!(!rescan.symbols.contains_key(symbol::TG_MEDIA_REPEAT), "Rescanning a post must not count it again");
+    let reply = scan_msg(make_sticker_message(chat_id, user_id, "reposter", "sticker-a", 8), String::new()).await.unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_MEDIA_REPEAT), "The third post of the same sticker should be flagged");
 }
 
 #[tokio::test]
 #[serial]
-async fn blacklist_command_adds_entries() {
+async fn message_handler_scans_photos_and_stickers() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let chat_id: i64 = 7007;
-    let user_id: u64 = 202;
+    let chat_id: i64 = 4033;
+    let user_id: u64 = 784;
     let bot = Bot::new("DUMMY");
-    let msg_user = make_message(chat_id, user_id, "tester", "/blacklist user|add|202", 1);
-    let _ = handle_admin_command(bot.clone(), msg_user, AdminCommand::Blacklist { pattern: "user|add|202".into() }).await;
+    for msg_id in 1..=3 {
+        let photo = make_photo_message(chat_id, user_id, "reposter", "Hot deal", "photo-a", msg_id);
+        assert!(message_handler(bot.clone(), photo).await.is_ok());
+    }
+    for msg_id in 4..=6 {
+        let sticker = make_sticker_message(chat_id, user_id, "reposter", "sticker-a", msg_id);
+        assert!(message_handler(bot.clone(), sticker).await.is_ok());
+    }
 
     let client = redis::Client::open("redis://127.0.0.1/").unwrap();
     let mut conn = client.get_connection().unwrap();
-    let has_user: bool = conn.sismember(key::TG_BLACKLIST_USER_KEY, "202").unwrap();
-    assert!(has_user, "User should be in blacklist set");
+    let counts: HashMap<String, i64> = conn.hgetall(format!("{}{}{}", key::TG_CHATS_PREFIX, chat_id, suffix::SYMBOL_COUNTS)).unwrap();
+    assert_eq!(counts.get(symbol::TG_MEDIA_REPEAT), Some(&2), "The third photo and sticker should be flagged, got {:?}", counts);
+}
 
-    let msg_word = make_message(chat_id, user_id, "tester", "/blacklist word|add|spam", 2);
-    let _ = handle_admin_command(bot, msg_word, AdminCommand::Blacklist { pattern: "word|add|spam".into() }).await;
-    let words: Vec<String> = conn.smembers(key::TG_BLACKLIST_WORD_KEY).unwrap();
-    assert!(words.contains(&"spam".to_string()));
+#[tokio::test]
+#[serial]
+async fn emergency_stop_skips_detection_and_moderation() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4005;
+    let user_id: u64 = 778;
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.hset(&user_key, field::REP, CONFIG.ban + 1).unwrap();
+    let _: () = conn.set(key::EMERGENCY_STOP_KEY, "true").unwrap();
+
+    let spam_text = "BUY NOW!!! t.me/joinchat/spam +1 555 123 4567 bit.ly/free";
+    let bot = Bot::new("DUMMY");
+    let res = handle_message(bot, make_message(chat_id, user_id, "spammer", spam_text, 1)).await;
+    assert!(res.is_ok(), "handle_message should return early during emergency stop");
+
+    let banned: bool = conn.hexists(&user_key, field::BANNED).unwrap();
+    let banned_q: bool = conn.hexists(&user_key, field::BANNED_Q).unwrap();
+    let stored: bool = conn.exists(format!("tg:message:{}", 1)).unwrap();
+    assert!(!banned && !banned_q, "No ban state should be written during emergency stop");
+    assert!(!stored, "Message should not be processed during emergency stop");
+
+    let rep: u32 = conn.hget(&user_key, field::REP).unwrap();
+    assert_eq!(rep, CONFIG.ban + 1, "Reputation should be untouched");
 }
 
-// ============================================================================
-// NEW MODULAR SYMBOL INTEGRATION TESTS
-// ============================================================================
+#[tokio::test]
+#[serial]
+async fn emergency_stop_command_pauses_and_resumes_monitoring() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let (api_url, sent) = start_file_bot_api("");
+    let bot = Bot::new("TOKEN").set_api_url(api_url);
+    let run = |mode: &str, msg_id: u32| {
+        let command = make_message(4007, 780, "owner", &format!("/emergencystop {}", mode), msg_id);
+        handle_admin_command(bot.clone(), command, AdminCommand::EmergencyStop { mode: mode.into() })
+    };
+    let last_reply = || sent.lock().unwrap().last().cloned().unwrap_or_default();
+
+    run("on", 1).await.expect("emergencystop failed");
+    assert!(emergency_stop_since(&mut conn).is_some());
+    run("on", 2).await.expect("emergencystop failed");
+    assert_eq!(last_reply(), "The emergency stop already is active.");
+    run("status", 3).await.expect("emergencystop failed");
+    assert!(last_reply().starts_with("The emergency stop is active since "));
+
+    // The flag the command sets is the one the message handler checks
+    let spam_text = "BUY NOW!!! t.me/joinchat/spam +1 555 123 4567 bit.ly/free";
+    handle_message(Bot::new("DUMMY"), make_message(4008, 781, "spammer", spam_text, 4)).await.unwrap();
+    assert!(!conn.exists::<_, bool>("tg:message:4").unwrap(), "Messages are skipped during an emergency stop");
+
+    run("off", 5).await.expect("emergencystop failed");
+    assert_eq!(last_reply(), "Monitoring resumed.");
+    assert_eq!(emergency_stop_since(&mut conn), None);
+    let logged: Vec<String> = audit_entries_since(&mut conn, Utc::now() - chrono::Duration::hours(1))
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.action)
+        .collect();
+    assert_eq!(logged, ["Resume Monitoring", "Emergency Stop"]);
+}
 
 #[tokio::test]
 #[serial]
-async fn tg_first_fast_sets_symbol_for_quick_first_message() {
+async fn dry_run_reports_would_be_ban_without_enforcing() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let chat_id = 8001;
-    let user_id = 1001;
-    let key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let chat_id: i64 = 4006;
+    let user_id: u64 = 779;
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, chat_id);
 
     let client = redis::Client::open("redis://127.0.0.1/").unwrap();
     let mut conn = client.get_connection().unwrap();
-    
-    // Set user as newly joined (within 10 seconds)
-    let join_time = Utc::now().timestamp() - 5; // 5 seconds ago
-    let _: () = conn.hset(key.clone(), "join_time", join_time).unwrap();
+    let _: () = conn.hset(&user_key, field::REP, CONFIG.ban + 1).unwrap();
+    let _: () = conn.hset(&chat_key, format!("feat:{}", DRY_RUN_FEATURE), "1").unwrap();
+    // Ban at any score so the mock's zero score maps to a would-be ban
+    let _: () = conn.hset(format!("{}{}", chat_key, suffix::ACTIONS), action::BAN, 0.0).unwrap();
+
+    let bot = Bot::new("DUMMY");
+    let res = handle_message(bot, make_message(chat_id, user_id, "spammer", "Test message", 1)).await;
+    assert!(res.is_ok(), "Dry-run should not try to delete or ban");
+
+    let alerts: Vec<String> = conn.lrange(format!("{}{}", chat_key, suffix::DRY_RUN_LOG), 0, -1).unwrap();
+    assert_eq!(alerts.len(), 1, "Would-be ban should produce an alert");
+    assert!(alerts[0].contains(action::BAN) && alerts[0].contains(symbol::TG_BAN),
+        "Alert should name the action and symbols: {}", alerts[0]);
+
+    let banned: bool = conn.hexists(&user_key, field::BANNED).unwrap();
+    let banned_q: bool = conn.hexists(&user_key, field::BANNED_Q).unwrap();
+    assert!(!banned && !banned_q, "No ban state should be written in dry-run");
+    let rep: u32 = conn.hget(&user_key, field::REP).unwrap();
+    assert_eq!(rep, CONFIG.ban + 1, "Reputation should not be penalized in dry-run");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_suspicious_sets_symbol() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 3003;
+    let user_id = 123;
+    let key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
 
+    // Manually bump reputation above the threshold
+    let client = redis::Client::open("redis://127.0.0.1/")
+        .expect("Failed to connect to Redis");
+    let mut conn = client
+        .get_connection()
+        .expect("Failed to connect to Redis");
+    let _: () = conn
+        .hset(key.clone(), field::REP, CONFIG.suspicious + 1)
+        .expect("Failed to set user reputation");
+    // Without the suspicious decay TG_SUSPICIOUS only ever adds
+    let _: () = conn.hset(reputation::SETTINGS_KEY, reputation::SUSPICIOUS_DECAY_STEP, 0).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
     let reply = scan_msg(
-        make_message(chat_id, user_id, "newuser", "Hello everyone!", 1),
-        "Hello everyone!".into(),
-    ).await.ok().unwrap();
+        make_message(chat_id, user_id, "test", "Hello", 1),
+        "Hello".into(),
+    )
+        .await
+        .ok()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let rep: u32 = conn
+        .hget(key.clone(), field::REP)
+        .expect("Failed to get user reputation");
+    assert!(
+        reply.symbols.contains_key(symbol::TG_SUSPICIOUS),
+        "Expected TG_SUSPICIOUS for high-rep user"
+    );
+    assert_eq!(rep, CONFIG.suspicious + 2);
+}
 
-    assert!(reply.symbols.contains_key(symbol::TG_FIRST_FAST), 
-        "Expected TG_FIRST_FAST for message within 10 seconds of joining");
+#[tokio::test]
+#[serial]
+async fn suspicious_user_sending_benign_messages_drops_below_the_threshold() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 3004;
+    let user_id: u64 = 124;
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let start_rep = CONFIG.suspicious as i64 + 10;
+    let _: () = conn.hset(&user_key, field::REP, start_rep).unwrap();
+
+    // TG_SUSPICIOUS adds one point and the decay takes off the default step,
+    // so every benign message brings the user this much closer
+    let net = reputation::DEFAULT_SUSPICIOUS_DECAY_STEP - 1;
+    let bound = (start_rep - CONFIG.suspicious as i64 + net - 1) / net + 1;
+    let mut scans = 0;
+    loop {
+        scans += 1;
+        assert!(scans <= bound, "Still suspicious after {} benign messages", bound);
+        let text = format!("Thanks everyone, see you at meetup number {}", scans);
+        let reply = scan_msg(make_message(chat_id, user_id, "reformed", &text, scans as u32), text).await.expect("scan failed");
+        if !reply.symbols.contains_key(symbol::TG_SUSPICIOUS) {
+            break;
+        }
+    }
+    let rep: i64 = conn.hget(&user_key, field::REP).unwrap();
+    assert!(rep <= CONFIG.suspicious as i64, "rep {} should be back below the threshold", rep);
+
+    // Offences don't count as behaving
+    let mut symbols = HashMap::new();
+    for name in [symbol::TG_SUSPICIOUS, symbol::TG_FLOOD] {
+        symbols.insert(name.to_string(), Symbol { name: name.to_string(), score: 0.0, metric_score: 0.0, description: None, options: None });
+    }
+    assert!(!is_benign(&symbols));
+    assert_eq!(apply_suspicious_decay(&mut conn, UserId(user_id), &symbols).unwrap(), None);
+    symbols.remove(symbol::TG_FLOOD);
+    assert_eq!(apply_suspicious_decay(&mut conn, UserId(user_id), &symbols).unwrap(), Some(rep - net - 1));
 }
 
 #[tokio::test]
 #[serial]
-async fn tg_first_slow_sets_symbol_for_delayed_first_message() {
+async fn setthreshold_sets_the_suspicious_decay_step() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let chat_id = 8002;
-    let user_id = 1002;
-    let key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let chat_id = 3005;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    assert_eq!(decay_step(&mut conn), reputation::DEFAULT_SUSPICIOUS_DECAY_STEP);
+
+    for (i, (args, expected)) in [
+        ("suspicious_decay_step|5", 5),
+        ("suspicious_decay_step|0", 0),
+        ("suspicious_decay_step|11", 0),
+        ("suspicious_decay_step|-1", 0),
+    ].into_iter().enumerate() {
+        let text = format!("/setthreshold {}", args);
+        let res = handle_admin_command(
+            Bot::new("DUMMY"),
+            make_message(chat_id, 1, "admin", &text, i as u32 + 1),
+            AdminCommand::SetThreshold { args: args.into() },
+        ).await;
+        assert!(res.is_err(), "Expected dummy send_message to fail");
+        assert_eq!(decay_step(&mut conn), expected, "after {}", args);
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn setthreshold_sets_the_reputation_decay_rate_and_floor() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 3006;
+    let decay = ReputationDecay::new().unwrap();
+    assert_eq!(decay.decay_settings().unwrap(), (reputation::DEFAULT_DECAY_RATE, reputation::DEFAULT_DECAY_FLOOR));
+
+    for (i, (args, expected)) in [
+        ("reputation_decay_rate|3", (3, 0)),
+        ("reputation_decay_floor|2", (3, 2)),
+        ("reputation_decay_rate|-1", (3, 2)),
+        ("reputation_decay_floor|101", (3, 2)),
+        ("reputation_decay_floor|low", (3, 2)),
+    ].into_iter().enumerate() {
+        let text = format!("/setthreshold {}", args);
+        let res = handle_admin_command(
+            Bot::new("DUMMY"),
+            make_message(chat_id, 1, "admin", &text, i as u32 + 1),
+            AdminCommand::SetThreshold { args: args.into() },
+        ).await;
+        assert!(res.is_err(), "Expected dummy send_message to fail");
+        assert_eq!(decay.decay_settings().unwrap(), expected, "after {}", args);
+    }
+}
+
+#[tokio::test]
+#[serial]
+async fn diagnose_trace_lists_symbols_reductions_and_reputation_delta() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
 
+    let chat_id = 3005;
+    let user_id: u64 = 125;
     let client = redis::Client::open("redis://127.0.0.1/").unwrap();
     let mut conn = client.get_connection().unwrap();
-    
-    // Set user as joined long ago (more than 24 hours)
-    let join_time = Utc::now().timestamp() - 90000; // 25 hours ago
-    let _: () = conn.hset(key.clone(), "join_time", join_time).unwrap();
+    lists::WHITELIST_WORDS.import(&mut conn, Some(ChatId(chat_id)), "meetup\n").unwrap();
+    let text = "meetup venues http://a.example http://b.example http://c.example http://d.example";
+
+    let trace = trace_scan(make_message(chat_id, user_id, "organizer", text, 1), text.to_string())
+        .await.expect("trace failed");
+    let link_spam = trace.symbols.iter().find(|s| s.name == symbol::TG_LINK_SPAM).expect("TG_LINK_SPAM should be traced");
+    assert_eq!(link_spam.weight, 1);
+    assert!(trace.reductions.iter().any(|s| s.name == symbol::WHITELIST_WORD), "The whitelisted word is a reduction");
+    assert!(!trace.symbols.iter().any(|s| s.name == symbol::WHITELIST_WORD));
+    assert_eq!(trace.reputation_delta, 1);
+
+    let rendered = render_trace("1", ChatId(chat_id), &trace, action::NONE);
+    assert!(rendered.contains("• TG_LINK_SPAM (") && rendered.contains("rep +1"), "{}", rendered);
+    assert!(rendered.contains("• WHITELIST_WORD ("), "{}", rendered);
+    assert!(rendered.contains("Reputation delta: +1"), "{}", rendered);
+
+    // Trusted senders get the trust reduction and no bad reputation
+    let trust_manager = TrustManager::new("redis://127.0.0.1/").unwrap();
+    trust_manager.trust_user(UserId(user_id), None).await.unwrap();
+    let trace = trace_scan(make_message(chat_id, user_id, "organizer", text, 2), text.to_string())
+        .await.expect("trace failed");
+    let trusted = trace.reductions.iter().find(|s| s.name == symbol::TG_TRUSTED_USER).expect("trust should be a reduction");
+    assert_eq!(trusted.score, trusted_user::SCORE_REDUCTION);
+    assert_eq!(trace.reputation_delta, 0);
+
+    // Tracing records nothing
+    let counts: HashMap<String, i64> = conn.hgetall(format!("{}{}{}", key::TG_CHATS_PREFIX, chat_id, suffix::SYMBOL_COUNTS)).unwrap();
+    assert!(counts.is_empty());
+}
 
-    let reply = scan_msg(
-        make_message(chat_id, user_id, "olduser", "Finally saying hello!", 1),
-        "Finally saying hello!".into(),
-    ).await.ok().unwrap();
+#[tokio::test]
+#[serial]
+async fn reputation_gates_follow_the_configured_thresholds() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
 
-    assert!(reply.symbols.contains_key(symbol::TG_FIRST_SLOW), 
-        "Expected TG_FIRST_SLOW for first message after 24 hours of joining");
+    let chat_id: i64 = 4005;
+    let user_id: u64 = 778;
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+
+    let (bot, _) = telegram_stand_in();
+    for (i, args) in ["suspicious_rep|3", "ban_rep|6", "ban_rep_penalty|2", "perm_ban_bans|1"].into_iter().enumerate() {
+        let command = make_message(chat_id, 1, "admin", &format!("/setthreshold {}", args), i as u32 + 1);
+        handle_admin_command(bot.clone(), command, AdminCommand::SetThreshold { args: args.into() })
+            .await
+            .expect("setthreshold failed");
+    }
+
+    // Far below the default gates, but above the configured ones (with the suspicious decay off)
+    let _: () = conn.hset(reputation::SETTINGS_KEY, reputation::SUSPICIOUS_DECAY_STEP, 0).unwrap();
+    let _: () = conn.hset(&user_key, field::REP, 6).unwrap();
+    let reply = scan_msg(make_message(chat_id, user_id, "tester", "Test message", 10), "Test message".into()).await.unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_SUSPICIOUS), "rep 6 is above suspicious_rep 3");
+    assert!(!reply.symbols.contains_key(symbol::TG_BAN), "rep 6 is not above ban_rep 6");
+    let rep: i64 = conn.hget(&user_key, field::REP).unwrap();
+    assert_eq!(rep, 7);
+
+    let reply = scan_msg(make_message(chat_id, user_id, "tester", "Test message", 11), "Test message".into()).await.unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_BAN), "rep 7 is above ban_rep 6");
+    let rep: i64 = conn.hget(&user_key, field::REP).unwrap();
+    assert_eq!(rep, 5, "The ban takes ban_rep_penalty off");
+    assert!(!reply.symbols.contains_key(symbol::TG_PERM_BAN), "One ban isn't above perm_ban_bans 1");
+
+    let _: () = conn.hset(&user_key, field::BANNED_Q, 2).unwrap();
+    let reply = scan_msg(make_message(chat_id, user_id, "tester", "Test message", 12), "Test message".into()).await.unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_PERM_BAN), "Two bans are above perm_ban_bans 1");
 }
 
 #[tokio::test]
 #[serial]
-async fn tg_silent_sets_symbol_for_dormant_user() {
+async fn tg_ban_sets_symbol_and_updates_ban_state() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let chat_id = 8003;
-    let user_id = 1003;
-    let key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let chat_id: i64 = 4004;
+    let user_id: u64 = 777;
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, chat_id);
 
     let client = redis::Client::open("redis://127.0.0.1/").unwrap();
     let mut conn = client.get_connection().unwrap();
-    
-    // Set user's last message time to 30+ days ago
-    let last_msg_time = Utc::now().timestamp() - 2600000; // 30+ days ago
-    let _: () = conn.hset(key.clone(), "last_msg_time", last_msg_time).unwrap();
+    let _: () = conn.hset(user_key.clone(), field::REP, CONFIG.ban + 1).expect("Failed to set rep");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "tester", "Test message", 1),
+        "Test message".into()
+    ).await.ok().unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(reply.symbols.contains_key(symbol::TG_BAN), "Expected TG_BAN symbol for high-rep user");
+    let new_rep: i64 = conn.hget(user_key.clone(), field::REP).expect("Failed to get rep");
+    assert_eq!(new_rep, (CONFIG.ban + 1) as i64 - 4, "Rep should decrease by 5 on TG_BAN"); // TG_SUSPICIOUS updates rep on message
+    let banned_flag: i64 = conn.hget(user_key.clone(), field::BANNED).expect("Failed to get 'banned' field");
+    assert_eq!(banned_flag, 1, "User 'banned' flag should be set");
+    let ban_count: i64 = conn.hget(user_key.clone(), "banned_q").expect("Failed to get 'banned_q'");
+    assert_eq!(ban_count, 1, "User ban count should increment");
+    let chat_bans: i64 = conn.hget(chat_key.clone(), field::BANNED).expect("Failed to get chat banned count");
+    assert_eq!(chat_bans, 1, "Chat's banned count should increment by 1");
+}
+
+#[tokio::test]
+#[serial]
+async fn banlist_lists_users_banned_in_the_chat() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4011;
+    let other_chat: i64 = 4012;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+
+    // Every message gets its own id, or later ones would count as rescans of the first
+    for (chat, user_id, message_id) in [(chat_id, 781u64, 1), (chat_id, 782, 2), (other_chat, 783, 3)] {
+        let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, user_id), field::REP, CONFIG.ban + 1).unwrap();
+        let reply = scan_msg(
+            make_message(chat, user_id, "tester", "Test message", message_id),
+            "Test message".into(),
+        ).await.unwrap();
+        assert!(reply.symbols.contains_key(symbol::TG_BAN));
+    }
+    // A second ban puts user 782 at the top of the list
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, 782), field::REP, CONFIG.ban + 1).unwrap();
+    let _ = scan_msg(make_message(chat_id, 782, "tester", "Test message", 4), "Test message".into())
+        .await
+        .unwrap();
+
+    let users = banned_users(&mut conn, ChatId(chat_id)).unwrap();
+    let ids: Vec<u64> = users.iter().map(|u| u.user_id).collect();
+    assert_eq!(ids, vec![782, 781], "Both users banned in the chat are listed, most banned first");
+    assert_eq!(users[0].ban_count, 2);
+    assert_eq!(users[1].ban_count, 1);
+
+    let others = banned_users(&mut conn, ChatId(other_chat)).unwrap();
+    assert_eq!(others.iter().map(|u| u.user_id).collect::<Vec<_>>(), vec![783]);
+}
+
+#[test]
+#[serial]
+fn worst_users_lists_the_highest_reputations_first() {
+    flush_redis();
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    for (user_id, rep, ban_count) in [(901u64, 3i64, 0i64), (902, 12, 2), (903, 7, 1), (904, 12, 3), (905, 0, 4), (906, -2, 0)] {
+        let _: () = conn
+            .hset_multiple(
+                format!("{}{}", key::TG_USERS_PREFIX, user_id),
+                &[(field::REP, rep.to_string()), (field::USERNAME, format!("user{}", user_id)), (field::BANNED_Q, ban_count.to_string())],
+            )
+            .unwrap();
+    }
+    // Sub-keys of a user aren't users themselves
+    let _: () = conn.sadd(format!("{}{}:notes", key::TG_USERS_PREFIX, 907), 99).unwrap();
+
+    let users = worst_users(&mut conn, 10).unwrap();
+    let ids: Vec<u64> = users.iter().map(|u| u.user_id).collect();
+    assert_eq!(ids, vec![902, 904, 903, 901], "Highest reputation first, ties by user id, no users without positive reputation");
+    assert_eq!(users[0].rep, 12);
+    assert_eq!(users[0].ban_count, 2);
+    assert_eq!(users[1].username.as_deref(), Some("user904"));
+
+    let top: Vec<u64> = worst_users(&mut conn, 2).unwrap().iter().map(|u| u.user_id).collect();
+    assert_eq!(top, vec![902, 904], "Only the top n are returned");
+    assert!(worst_users(&mut conn, 0).unwrap().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[serial]
+async fn concurrent_scans_dont_lose_reputation_updates() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4031;
+    let user_id: u64 = 801;
+    let scans: i64 = 8;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    // Above the suspicious threshold, so every scan adds one point (with the suspicious decay off)
+    let _: () = conn.hset(reputation::SETTINGS_KEY, reputation::SUSPICIOUS_DECAY_STEP, 0).unwrap();
+    let start_rep = CONFIG.suspicious as i64 + 1;
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, user_id), field::REP, start_rep).unwrap();
+
+    let handles: Vec<_> = (0..scans)
+        .map(|i| {
+            // Leading counter, so TG_REPEAT (which ignores trailing digits) doesn't fire
+            let text = format!("{} concurrent message", i);
+            tokio::spawn(scan_msg(make_message(chat_id, user_id, "racer", &text, i as u32 + 1), text))
+        })
+        .collect();
+    for handle in handles {
+        let reply = handle.await.unwrap().unwrap();
+        assert!(reply.symbols.contains_key(symbol::TG_SUSPICIOUS));
+        assert!(!reply.symbols.contains_key(symbol::TG_BAN), "Reputation stays below the ban threshold");
+    }
+
+    let rep: i64 = conn.hget(format!("{}{}", key::TG_USERS_PREFIX, user_id), field::REP).unwrap();
+    assert_eq!(rep, start_rep + scans, "Every concurrent scan's reputation update is kept");
+}
+
+#[tokio::test]
+#[serial]
+async fn scanning_a_message_twice_moves_reputation_once() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4061;
+    let user_id: u64 = 861;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    // Above the suspicious threshold, so a scan adds one point (with the suspicious decay off)
+    let _: () = conn.hset(reputation::SETTINGS_KEY, reputation::SUSPICIOUS_DECAY_STEP, 0).unwrap();
+    let start_rep = CONFIG.suspicious as i64 + 1;
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, user_id), field::REP, start_rep).unwrap();
+
+    let text = "Same message delivered twice";
+    let first = scan_msg(make_message(chat_id, user_id, "twice", text, 7), text.into()).await.expect("scan failed");
+    assert!(first.symbols.contains_key(symbol::TG_SUSPICIOUS));
+    let again = scan_msg(make_message(chat_id, user_id, "twice", text, 7), text.into()).await.expect("scan failed");
+    assert!(again.symbols.contains_key(symbol::TG_SUSPICIOUS), "A repeated scan still reports the symbols of the first");
+    assert_eq!(again.score, first.score);
+
+    let rep: i64 = conn.hget(format!("{}{}", key::TG_USERS_PREFIX, user_id), field::REP).unwrap();
+    assert_eq!(rep, start_rep + 1, "Only the first scan moves reputation");
+
+    // Another message of the same user is scanned normally
+    let other = "A different message";
+    scan_msg(make_message(chat_id, user_id, "twice", other, 8), other.into()).await.expect("scan failed");
+    let rep: i64 = conn.hget(format!("{}{}", key::TG_USERS_PREFIX, user_id), field::REP).unwrap();
+    assert_eq!(rep, start_rep + 2);
+}
+
+#[tokio::test]
+#[serial]
+async fn handling_a_message_twice_bans_once() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4063;
+    let user_id: u64 = 863;
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, chat_id);
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    // Ban at any score so the mock's zero score maps to a ban
+    let _: () = conn.hset(format!("{}{}", chat_key, suffix::ACTIONS), action::BAN, 0.0).unwrap();
+
+    let text = "Same spam delivered twice";
+    let bot = Bot::new("DUMMY");
+    let _ = handle_message(bot.clone(), make_message(chat_id, user_id, "twice", text, 9)).await;
+    let banned_q: i64 = conn.hget(&user_key, field::BANNED_Q).unwrap();
+    let msg_count: i64 = conn.hget(&user_key, field::MSG_COUNT).unwrap();
+    assert_eq!(banned_q, 1);
+
+    let _ = handle_message(bot, make_message(chat_id, user_id, "twice", text, 9)).await;
+    assert_eq!(conn.hget::<_, _, i64>(&user_key, field::BANNED_Q).unwrap(), banned_q, "Only the first delivery bans");
+    assert_eq!(conn.hget::<_, _, i64>(&user_key, field::MSG_COUNT).unwrap(), msg_count, "Only the first delivery is counted");
+}
+
+#[test]
+#[serial]
+fn a_released_scan_can_be_claimed_again() {
+    flush_redis();
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let (chat_id, message_id) = (ChatId(4064), MessageId(1));
+
+    assert!(claim_scan(&mut conn, chat_id, message_id));
+    assert!(!claim_scan(&mut conn, chat_id, message_id), "A claimed scan can't be claimed twice");
+    release_scan(&mut conn, chat_id, message_id);
+    assert!(claim_scan(&mut conn, chat_id, message_id), "A failed scan is retried on redelivery");
+}
+
+#[tokio::test]
+#[serial]
+async fn feature_state_reports_chat_overrides_and_global_defaults() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4041;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, chat_id);
+
+    // Globally on, switched off for the chat
+    let _: () = conn.hset(&chat_key, "feat:whitelist", "0").unwrap();
+    assert_eq!(
+        feature_state(&mut conn, chat_id, "whitelist"),
+        FeatureState { enabled: false, source: FeatureSource::ChatOverride }
+    );
+    // Globally off, switched on for the chat
+    let _: () = conn.srem(ENABLED_FEATURES_KEY, "blacklist").unwrap();
+    let _: () = conn.hset(&chat_key, "feat:blacklist", "1").unwrap();
+    assert_eq!(
+        feature_state(&mut conn, chat_id, "blacklist"),
+        FeatureState { enabled: true, source: FeatureSource::ChatOverride }
+    );
+    // No override: the global set decides
+    assert_eq!(
+        feature_state(&mut conn, chat_id, "gibberish"),
+        FeatureState { enabled: true, source: FeatureSource::GlobalDefault }
+    );
+    assert_eq!(
+        feature_state(&mut conn, chat_id + 1, "blacklist"),
+        FeatureState { enabled: false, source: FeatureSource::GlobalDefault }
+    );
+    assert!(is_feature_enabled(&mut conn, chat_id, "blacklist"));
+    assert!(!is_feature_enabled(&mut conn, chat_id, "whitelist"));
+}
+
+#[tokio::test]
+#[serial]
+async fn mutes_are_recorded_and_expire() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = ChatId(4021);
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let now = Utc::now().timestamp();
+
+    assert_eq!(mute_minutes(&mut conn, chat_id), mute::DEFAULT_MINUTES);
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, chat_id.0), field::MUTE_MINUTES, 15).unwrap();
+    assert_eq!(mute_minutes(&mut conn, chat_id), 15);
+
+    record_mute(&mut conn, UserId(791), chat_id, now + 600).unwrap();
+    record_mute(&mut conn, UserId(792), chat_id, now - 5).unwrap();
+    let until: i64 = conn.hget(format!("{}{}", key::TG_USERS_PREFIX, 791), field::MUTED_UNTIL).unwrap();
+    assert_eq!(until, now + 600);
+    let muted_in: i64 = conn.hget(format!("{}{}", key::TG_USERS_PREFIX, 791), field::MUTED_IN).unwrap();
+    assert_eq!(muted_in, chat_id.0);
+
+    let ids: Vec<u64> = muted_users(&mut conn).unwrap().iter().map(|m| m.user_id).collect();
+    assert_eq!(ids, vec![792, 791], "Both mutes are listed, soonest to end first");
+    let expired: Vec<u64> = expired_mutes(&mut conn, now).unwrap().iter().map(|m| m.user_id).collect();
+    assert_eq!(expired, vec![792], "Only the mute that ran out has expired");
+
+    assert_eq!(clear_mute(&mut conn, UserId(792)).unwrap(), Some(chat_id));
+    assert!(expired_mutes(&mut conn, now).unwrap().is_empty());
+    assert_eq!(expired_mutes(&mut conn, now + 600).unwrap().len(), 1, "The other mute expires later");
+}
+
+#[tokio::test]
+#[serial]
+async fn trend_shows_daily_spam_actions() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = ChatId(4013);
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+
+    let today = chrono::NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+    let yesterday = today.pred_opt().unwrap();
+    record_daily_action(&mut conn, chat_id, action::BAN, yesterday).unwrap();
+    record_daily_action(&mut conn, chat_id, action::DELETE, yesterday).unwrap();
+    record_daily_action(&mut conn, chat_id, action::WARN, today).unwrap();
+
+    let ttl: i64 = conn.ttl(daily_key(chat_id, today)).unwrap();
+    assert!(ttl > 0 && ttl <= trend::RETENTION_DAYS * 86400, "Buckets should expire, got TTL {}", ttl);
+
+    let totals = daily_totals(&mut conn, chat_id, 2, today).unwrap();
+    assert_eq!(totals, vec![(yesterday, 2), (today, 1)], "Expected one data point per day, oldest first");
+
+    let rendered = render_trend(&totals);
+    let rows: Vec<&str> = rendered.lines().skip(1).collect();
+    assert_eq!(rows, vec!["2024-05-01 █ 2", "2024-05-02 ▅ 1"]);
+    assert_eq!(rendered.lines().next(), Some("█▅"));
+}
+
+#[test]
+fn command_menu_lists_every_command_and_alias() {
+    let menu = admin_command_menu();
+    assert!(menu.len() <= command_menu::MAX_COMMANDS);
+    let names: HashSet<&str> = menu.iter().map(|entry| entry.command.as_str()).collect();
+    assert_eq!(names.len(), menu.len(), "duplicate menu entries");
+    // Telegram only accepts 1-32 lowercase letters, digits and underscores with a description
+    for entry in &menu {
+        assert!(!entry.command.is_empty() && entry.command.len() <= 32, "{}", entry.command);
+        assert!(entry.command.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'), "{}", entry.command);
+        assert!(!entry.description.is_empty() && entry.description.chars().count() <= 256, "{}", entry.command);
+    }
+    for command in AdminCommand::bot_commands() {
+        assert!(names.contains(command.command.trim_start_matches('/')), "{} missing", command.command);
+    }
+    // Aliases past MAX_COMMANDS would be cut from the menu, so they must all fit
+    for (alias, _) in ALIASES {
+        assert!(names.contains(alias), "alias {} cut from the menu", alias);
+    }
+    assert!(names.contains("worstusers"));
+    let aes = menu.iter().find(|entry| entry.command == "aes").expect("alias in the menu");
+    assert_eq!(aes.description, "short for /antievasionstats.");
+
+    // Each alias runs the command it stands for
+    for (alias, command) in ALIASES {
+        let by_alias = AdminCommand::parse(&format!("/{}", alias), "test_bot").unwrap_or_else(|_| panic!("/{} doesn't parse", alias));
+        let by_name = AdminCommand::parse(&format!("/{}", command), "test_bot").unwrap();
+        assert_eq!(std::mem::discriminant(&by_alias), std::mem::discriminant(&by_name), "/{} isn't /{}", alias, command);
+    }
+
+    let members: HashSet<String> = member_command_menu().into_iter().map(|entry| entry.command).collect();
+    assert_eq!(members, HashSet::from(["reportspam".to_string(), "appeal".to_string(), "report".to_string()]));
+}
+
+#[tokio::test]
+#[serial]
+async fn global_stats_sum_every_moderated_chat() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(matches!(
+        AdminCommand::parse("/stats --global", "test_bot"),
+        Ok(AdminCommand::Stats { args }) if args == "--global"
+    ));
+
+    let admin_chat: i64 = -4101;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    // (chat, spam, bans, permanent bans); 4019 is moderated from another admin chat
+    let seeded: [(i64, i64, i64, i64); 4] = [(4016, 3, 1, 0), (4017, 12, 4, 2), (4018, 0, 0, 0), (4019, 50, 9, 9)];
+    for (chat, spam, bans, perm_bans) in seeded {
+        let _: () = conn
+            .hset_multiple(
+                format!("{}{}", key::TG_CHATS_PREFIX, chat),
+                &[(field::NAME, format!("Chat {}", chat)), (field::SPAM_COUNT, spam.to_string()), (field::BANNED, bans.to_string()), (field::PERM_BANNED, perm_bans.to_string())],
+            )
+            .unwrap();
+    }
+    let _: () = conn.sadd(format!("{}{}{}", key::ADMIN_PREFIX, admin_chat, suffix::MODERATED_CHATS), &[4016, 4017, 4018]).unwrap();
+    let _: () = conn.sadd(format!("{}{}{}", key::ADMIN_PREFIX, -4102, suffix::MODERATED_CHATS), 4019).unwrap();
+    record_spam_event(&mut conn, ChatId(4017), UserId(824), &[symbol::TG_BAN], "rep 21").unwrap();
+    record_spam_event(&mut conn, ChatId(4019), UserId(825), &[symbol::TG_SUSPICIOUS], "rep 11").unwrap();
+
+    let stats = global_stats(&mut conn, ChatId(admin_chat)).unwrap();
+    let moderated = &seeded[..3];
+    assert_eq!(stats.spam, moderated.iter().map(|chat| chat.1).sum::<i64>());
+    assert_eq!(stats.bans, moderated.iter().map(|chat| chat.2).sum::<i64>());
+    assert_eq!(stats.perm_bans, moderated.iter().map(|chat| chat.3).sum::<i64>());
+    assert_eq!(stats.chats.iter().map(|chat| chat.chat_id).collect::<Vec<_>>(), vec![4017, 4016, 4018], "Busiest chat first");
+
+    let report = render_global_stats(&stats);
+    assert!(report.contains("Stats across 3 moderated chat(s)"), "{}", report);
+    assert!(report.contains("• Spam messages: 15") && report.contains("• Bans: 5") && report.contains("• Permanent bans: 2"), "{}", report);
+    assert!(report.contains("1. Chat 4017: 12 spam, 4 ban(s), 2 permanent"), "{}", report);
+    assert_eq!(stats.recent_spam_events, 2);
+    assert!(report.contains("• Spam events in the last 24 hours, across every chat: 2"), "{}", report);
+    assert!(!report.contains("Chat 4019"), "{}", report);
+}
+
+#[tokio::test]
+#[serial]
+async fn daily_summary_reports_actions_symbols_and_bans() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let admin_chat: i64 = -4100;
+    let (chat, quiet_chat) = (ChatId(4014), ChatId(4015));
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.sadd(format!("{}{}{}", key::ADMIN_PREFIX, admin_chat, suffix::MODERATED_CHATS), &[chat.0, quiet_chat.0]).unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, chat.0), field::NAME, "Rust Lovers").unwrap();
+
+    let day = chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+    record_daily_action(&mut conn, chat, action::BAN, day).unwrap();
+    record_daily_action(&mut conn, chat, action::DELETE, day).unwrap();
+    record_daily_action(&mut conn, chat, action::DELETE, day).unwrap();
+    record_daily_action(&mut conn, chat, action::WARN, day.succ_opt().unwrap()).unwrap();
+    let links = vec!["TG_LINK_SPAM".to_string()];
+    let caps = vec!["TG_LINK_SPAM".to_string(), "TG_CAPS".to_string()];
+    record_daily_symbols(&mut conn, chat, &links, day).unwrap();
+    record_daily_symbols(&mut conn, chat, &caps, day).unwrap();
+    record_spam_event(&mut conn, chat, UserId(821), &[symbol::TG_BAN], "TG_LINK_SPAM, rep 21").unwrap();
+    record_spam_event(&mut conn, chat, UserId(822), &[symbol::TG_SUSPICIOUS], "rep 11").unwrap();
+    record_spam_event(&mut conn, quiet_chat, UserId(823), &[symbol::TG_BAN], "rep 21").unwrap();
+
+    let chats = admin_chats(&mut conn).unwrap();
+    assert_eq!(chats, vec![(ChatId(admin_chat), vec![chat.0, quiet_chat.0])]);
+
+    let since = Utc::now().timestamp() - 86400;
+    let summary = build_summary(&mut conn, &chats[0].1, day, since).unwrap();
+    let lines: Vec<&str> = summary.lines().collect();
+    assert_eq!(lines, vec![
+        "Daily summary for 2024-05-01",
+        "",
+        "Rust Lovers (4014):",
+        "• Actions: tg_ban 1, tg_delete 2",
+        "• Top symbols: TG_LINK_SPAM (2), TG_CAPS (1)",
+        "• Newly banned: 821",
+        "",
+        "Chat 4015:",
+        "• Actions: none",
+        "• Newly banned: 823",
+    ]);
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_attachment_spam_flags_risky_documents() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4051;
+    let caption = "Free premium app, install now";
+
+    let apk = scan_msg(make_document_message(chat_id, 841, "sender", caption, "Premium.APK", 1), caption.into())
+        .await.expect("scan failed");
+    let flagged = apk.symbols.get(symbol::TG_ATTACHMENT_SPAM).expect("apk should be flagged");
+    assert_eq!(flagged.score, attachment::SCORE);
+    assert_eq!(flagged.options, Some(vec!["Premium.APK".to_string()]));
+
+    let pdf = scan_msg(make_document_message(chat_id, 842, "sender", caption, "invoice.pdf", 2), caption.into())
+        .await.expect("scan failed");
+    assert!(!pdf.symbols.contains_key(symbol::TG_ATTACHMENT_SPAM), "pdf is not a risky type");
+
+    // Switched off for the chat
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, chat_id), "feat:attachment_spam", "0").unwrap();
+    let apk = scan_msg(make_document_message(chat_id, 843, "sender", caption, "Premium.apk", 3), caption.into())
+        .await.expect("scan failed");
+    assert!(!apk.symbols.contains_key(symbol::TG_ATTACHMENT_SPAM));
+}
+
+#[tokio::test]
+#[serial]
+async fn message_handler_scans_documents_by_their_caption() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4056;
+    let caption = "Free premium app, install now";
+    let document = make_document_message(chat_id, 844, "sender", caption, "Premium.apk", 1);
+    assert!(message_handler(Bot::new("DUMMY"), document).await.is_ok());
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let counts: HashMap<String, i64> = conn.hgetall(format!("{}{}{}", key::TG_CHATS_PREFIX, chat_id, suffix::SYMBOL_COUNTS)).unwrap();
+    assert_eq!(counts.get(symbol::TG_ATTACHMENT_SPAM), Some(&1), "Documents should reach the scan, got {:?}", counts);
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_impersonation_flags_lookalikes_of_admin_names() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4052;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    record_admin_name(&mut conn, ChatId(chat_id), UserId(844), "Chat Admin").unwrap();
+    let text = "Hello everyone";
+    let with_name = |user_id: u64, name: &str, msg_id: u32| {
+        let mut msg = make_message(chat_id, user_id, "sender", text, msg_id);
+        if let Some(user) = msg.from.as_mut() {
+            user.first_name = name.into();
+        }
+        msg
+    };
+
+    // The admin themselves
+    let genuine = scan_msg(with_name(844, "Chat Admin", 1), text.into()).await.expect("scan failed");
+    assert!(!genuine.symbols.contains_key(symbol::TG_IMPERSONATION), "The admin doesn't impersonate themselves");
+
+    // Greek Alpha plus a zero-width space
+    let impostor = scan_msg(with_name(845, "Chat \u{0391}d\u{200B}min", 2), text.into()).await.expect("scan failed");
+    let flagged = impostor.symbols.get(symbol::TG_IMPERSONATION).expect("lookalike name should be flagged");
+    assert_eq!(flagged.score, impersonation::SCORE);
+    assert_eq!(flagged.options, Some(vec!["Chat Admin".to_string()]));
+
+    let unrelated = scan_msg(with_name(846, "Chad Adams", 3), text.into()).await.expect("scan failed");
+    assert!(!unrelated.symbols.contains_key(symbol::TG_IMPERSONATION));
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_new_user_link_flags_links_from_recent_joiners() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4053;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let now = Utc::now().timestamp();
+    let (newcomer, veteran) = (847u64, 848u64);
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, newcomer), field::JOIN_TIME, now - 60).unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, veteran), field::JOIN_TIME, now - 30 * 86_400).unwrap();
+    let link = "Check this out https://promo.example/offer";
+
+    // A new user with a link
+    let reply = scan_msg(make_message(chat_id, newcomer, "newcomer", link, 1), link.into()).await.expect("scan failed");
+    let flagged = reply.symbols.get(symbol::TG_NEW_USER_LINK).expect("link from a new user should be flagged");
+    assert_eq!(flagged.score, new_user_link::SCORE);
+    assert_eq!(flagged.options, Some(vec!["promo.example".to_string()]));
+
+    // An old user with a link
+    let reply = scan_msg(make_message(chat_id, veteran, "veteran", link, 2), link.into()).await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_NEW_USER_LINK), "Established members may post links");
+
+    // A new user without links
+    let text = "Hi all, glad to be here";
+    let reply = scan_msg(make_message(chat_id, newcomer, "newcomer", text, 3), text.into()).await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_NEW_USER_LINK));
+
+    // A shorter window lets the newcomer post links
+    let gate_key = format!("{}{}{}", key::TG_CHATS_PREFIX, chat_id, suffix::JOIN_GATE);
+    let _: () = conn.hset(&gate_key, join_gate::NEW_USER_LINK, 30).unwrap();
+    let reply = scan_msg(make_message(chat_id, newcomer, "newcomer", link, 4), link.into()).await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_NEW_USER_LINK));
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_contact_card_flags_shared_contacts() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4054;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let now = Utc::now().timestamp();
+    let (newcomer, veteran) = (849u64, 850u64);
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, newcomer), field::JOIN_TIME, now - 60).unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, veteran), field::JOIN_TIME, now - 30 * 86_400).unwrap();
+
+    let reply = scan_msg(make_contact_message(chat_id, veteran, "veteran", "+15551234567", 1), "Support".into())
+        .await.expect("scan failed");
+    let flagged = reply.symbols.get(symbol::TG_CONTACT_CARD).expect("a shared contact should be flagged");
+    assert_eq!(flagged.score, contact_card::SCORE);
+    assert_eq!(flagged.options, Some(vec!["+15551234567".to_string()]));
+
+    // New users sharing a contact score higher
+    let reply = scan_msg(make_contact_message(chat_id, newcomer, "newcomer", "+15551234567", 2), "Support".into())
+        .await.expect("scan failed");
+    assert_eq!(reply.symbols.get(symbol::TG_CONTACT_CARD).map(|s| s.score), Some(contact_card::NEW_USER_SCORE));
+
+    // Plain text is not a contact
+    let text = "Call support at +1 555 123 4567";
+    let reply = scan_msg(make_message(chat_id, veteran, "veteran", text, 3), text.into()).await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_CONTACT_CARD));
+}
+
+#[tokio::test]
+#[serial]
+async fn message_handler_scans_shared_contacts() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4057;
+    let contact = make_contact_message(chat_id, 851, "sharer", "+15551234567", 1);
+    assert!(message_handler(Bot::new("DUMMY"), contact).await.is_ok());
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let counts: HashMap<String, i64> = conn.hgetall(format!("{}{}{}", key::TG_CHATS_PREFIX, chat_id, suffix::SYMBOL_COUNTS)).unwrap();
+    assert_eq!(counts.get(symbol::TG_CONTACT_CARD), Some(&1), "Contacts should reach the scan, got {:?}", counts);
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_mixed_language_spam_flags_passages_in_unrelated_scripts() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4055;
+    let greeting = "Hello everyone! Привет всем! 👋";
+    let ad = "Make $500 a day from home with our trading bot!!! 立即加入我们的电报频道领取免费信号 👉";
+    assert!(mixed_scripts(greeting).is_empty(), "A short bilingual greeting stays below the run length");
+    assert!(mixed_scripts("The Russian word for peace is мир, as in Мир вам").is_empty(), "A few foreign words are not a passage");
+    assert_eq!(mixed_scripts(ad), vec!["latin", "cjk"]);
+
+    let reply = scan_msg(make_message(chat_id, 851, "member", greeting, 1), greeting.into()).await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_MIXED_LANGUAGE_SPAM));
+
+    let reply = scan_msg(make_message(chat_id, 852, "advertiser", ad, 2), ad.into()).await.expect("scan failed");
+    let flagged = reply.symbols.get(symbol::TG_MIXED_LANGUAGE_SPAM).expect("the script-mixed ad should be flagged");
+    assert_eq!(flagged.score, mixed_language::SCORE);
+    assert_eq!(flagged.options, Some(vec!["latin".to_string(), "cjk".to_string()]));
+
+    // Switched off for the chat
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, chat_id), format!("feat:{}", MIXED_LANGUAGE_FEATURE), "0").unwrap();
+    let reply = scan_msg(make_message(chat_id, 852, "advertiser", ad, 3), ad.into()).await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_MIXED_LANGUAGE_SPAM));
+}
+
+#[test]
+fn impersonation_skeleton_folds_lookalike_characters() {
+    assert_eq!(skeleton("Admin"), skeleton("\u{0391}dmin"), "Greek capital Alpha");
+    assert_eq!(skeleton("Admin"), skeleton("Аdmіn"), "Cyrillic a and i");
+    assert_eq!(skeleton("Admin"), skeleton("Ad\u{200D}min"), "zero-width joiner");
+    assert_eq!(skeleton("Admin"), skeleton("Adrnin"), "rn read as m");
+    assert_eq!(skeleton("Admin"), skeleton("AdmIn"));
+    assert_eq!(skeleton("Admin"), skeleton("Ａｄｍｉｎ"), "fullwidth letters");
+    assert_ne!(skeleton("Admin"), skeleton("Adam"));
+    assert!(skeleton("\u{200B}\u{3164}").is_empty());
+}
+
+#[tokio::test]
+#[serial]
+async fn admin_panel_permissions_gate_admin_commands() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (viewer, outsider) = (UserId(851), UserId(852));
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+
+    // Without an admin panel every chat admin may run everything
+    assert_eq!(command_access(&mut conn, viewer, &AdminCommand::BayesReset).unwrap(), CommandAccess::Unrestricted);
+
+    let mut viewer_user = AdminUser::new(viewer, Some("viewer".into()), "Viewer".into(), UserId(1));
+    for permission in PermissionGroup::Viewer.permissions() {
+        viewer_user.add_permission(permission);
+    }
+    let _: () = conn.set(panel_key::ADMIN_PANEL_CHAT_KEY, "-4200").unwrap();
+    let _: () = conn.sadd(panel_key::ADMIN_PANEL_MEMBERS_KEY, viewer.0.to_string()).unwrap();
+    let _: () = conn
+        .hset(panel_key::ADMIN_PANEL_PERMISSIONS_KEY, viewer.0.to_string(), serde_json::to_string(&viewer_user).unwrap())
+        .unwrap();
+
+    assert_eq!(
+        command_access(&mut conn, viewer, &AdminCommand::BayesReset).unwrap(),
+        CommandAccess::Denied(AdminPermission::ConfigureBot)
+    );
+    assert_eq!(
+        command_access(&mut conn, viewer, &AdminCommand::MarkTrusted { args: "1|admin".into() }).unwrap(),
+        CommandAccess::Denied(AdminPermission::ManageChats)
+    );
+    assert_eq!(command_access(&mut conn, viewer, &AdminCommand::BayesStats).unwrap(), CommandAccess::Granted);
+    assert_eq!(command_access(&mut conn, viewer, &AdminCommand::Help).unwrap(), CommandAccess::Unrestricted);
+    assert_eq!(
+        command_access(&mut conn, outsider, &AdminCommand::Stats { args: String::new() }).unwrap(),
+        CommandAccess::Denied(AdminPermission::ViewStats)
+    );
+}
+
+#[test]
+#[serial]
+fn only_admins_of_a_chat_may_act_on_it_from_elsewhere() {
+    flush_redis();
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let (group, other_group, admin_group) = (ChatId(-4210), ChatId(-4211), ChatId(-4212));
+
+    // A DM passes the chat admin check and, without a panel, command_access too
+    let dm = make_message(855, 855, "stranger", "/resetchat -4210", 1);
+    assert_eq!(
+        command_access(&mut conn, UserId(855), &AdminCommand::ResetChat { chat: "-4210".into() }).unwrap(),
+        CommandAccess::Unrestricted
+    );
+    assert!(moderates_chat(&mut conn, &dm, ChatId(855)).unwrap(), "The chat the command was sent in");
+    assert!(!moderates_chat(&mut conn, &dm, group).unwrap(), "Strangers can't act on other chats");
+
+    // Admins of a chat may act on it from their DM
+    let _: () = conn.sadd(format!("{}{}", 855, suffix::BOT_CHATS), group.0).unwrap();
+    assert!(moderates_chat(&mut conn, &dm, group).unwrap());
+    assert!(!moderates_chat(&mut conn, &dm, other_group).unwrap());
+
+    // Chats assigned to an admin chat with /makeadmin are managed from there
+    let _: () = conn.sadd(format!("{}{}{}", key::ADMIN_PREFIX, admin_group.0, suffix::MODERATED_CHATS), other_group.0).unwrap();
+    let from_admin_chat = make_message(admin_group.0, 856, "moderator", "/resetchat -4211", 2);
+    assert!(moderates_chat(&mut conn, &from_admin_chat, other_group).unwrap());
+    assert!(!moderates_chat(&mut conn, &from_admin_chat, group).unwrap());
+}
+
+#[tokio::test]
+#[serial]
+async fn admin_panel_members_are_managed_by_username() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (api_url, sent) = start_file_bot_api("");
+    let bot = Bot::new("TOKEN").set_api_url(api_url);
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    index_username(&mut conn, "PanelMod", UserId(854)).unwrap();
+
+    let add = make_message(4200, 853, "owner", "/addadmin @panelmod|moderator", 1);
+    handle_admin_command(bot.clone(), add, AdminCommand::AddAdmin { args: "@panelmod|moderator".into() })
+        .await
+        .expect("addadmin failed");
+    let member = admin_panel_user(&mut conn, UserId(854)).unwrap().expect("the user should be a panel member");
+    assert_eq!(member.username.as_deref(), Some("panelmod"));
+    assert_eq!(member.added_by, UserId(853));
+    assert!(member.has_permission(&AdminPermission::ManageChats));
+    assert!(!member.has_permission(&AdminPermission::ConfigureBot));
+
+    let set = make_message(4200, 853, "owner", "/setpermissions @PanelMod|configure_bot, view_stats", 2);
+    handle_admin_command(bot.clone(), set, AdminCommand::SetPermissions { args: "@PanelMod|configure_bot, view_stats".into() })
+        .await
+        .expect("setpermissions failed");
+    let member = admin_panel_user(&mut conn, UserId(854)).unwrap().unwrap();
+    assert!(member.has_permission(&AdminPermission::ConfigureBot));
+    assert!(!member.has_permission(&AdminPermission::ManageChats));
+
+    // Users the bot has never seen can't be resolved
+    let unknown = make_message(4200, 853, "owner", "/addadmin @nobody", 3);
+    handle_admin_command(bot.clone(), unknown, AdminCommand::AddAdmin { args: "@nobody".into() })
+        .await
+        .expect("addadmin failed");
+    assert!(sent.lock().unwrap().last().unwrap().starts_with("Usage: /addadmin"));
+
+    let remove = make_message(4200, 853, "owner", "/removeadmin @panelmod", 4);
+    handle_admin_command(bot, remove, AdminCommand::RemoveAdmin { user: "@panelmod".into() })
+        .await
+        .expect("removeadmin failed");
+    assert!(admin_panel_user(&mut conn, UserId(854)).unwrap().is_none());
+    assert_eq!(sent.lock().unwrap().last().map(String::as_str), Some("Removed user 854 from the admin panel."));
+}
+
+fn make_panel_admin(id: u64) -> AdminUser {
+    let mut admin = AdminUser::new(UserId(id), Some(format!("admin{}", id)), format!("Admin {}", id), UserId(1));
+    admin.add_permission(AdminPermission::ViewStats);
+    admin
+}
+
+#[test]
+fn admin_list_pages_hold_one_page_of_admins() {
+    let admins: Vec<AdminUser> = (1..=50).map(make_panel_admin).collect();
+
+    let (entries, pages) = paginate(&admins, 0);
+    assert_eq!(entries.len(), panel_settings::PAGE_SIZE);
+    assert_eq!(pages, 5);
+
+    let (first, _) = render_admin_list_page(&admins, 0);
+    assert!(first.starts_with("Admin panel members (page 1/5):"));
+    assert!(first.contains("\n10 @admin10 – view_stats\n"));
+    assert!(!first.contains("@admin11 "));
+    assert!(first.len() < 4096);
+
+    // Pages past the end show the last one
+    let items: Vec<u32> = (0..25).collect();
+    let (entries, pages) = paginate(&items, 99);
+    assert_eq!(pages, 3);
+    assert_eq!(entries, &[20, 21, 22, 23, 24]);
+    let (last, _) = render_admin_list_page(&admins, 99);
+    assert!(last.starts_with("Admin panel members (page 5/5):"));
+}
+
+#[tokio::test]
+#[serial]
+async fn admin_list_buttons_turn_the_page() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    for id in 1..=25 {
+        save_panel_admin(&mut conn, &make_panel_admin(id)).unwrap();
+    }
+    let _: () = conn.set(panel_key::ADMIN_PANEL_CHAT_KEY, "-4201").unwrap();
+    let _: () = conn.sadd(format!("{}{}{}", key::ADMIN_PREFIX, 4201, suffix::MODERATED_CHATS), &[-100401, -100402]).unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, -100401), field::NAME, "Crypto Chat").unwrap();
+
+    // Telegram stand-in that records the edited texts
+    let edited = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let recorded = edited.clone();
+    let api = warp::path::full().and(warp::body::bytes()).map(move |path: warp::path::FullPath, body: Bytes| {
+        let path = path.as_str().to_lowercase();
+        let request: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+        let result = if path.ends_with("/answercallbackquery") {
+            json!(true)
+        } else {
+            recorded.lock().unwrap().push(request["text"].as_str().unwrap_or_default().to_string());
+            json!({"message_id": 5, "date": 0, "chat": {"id": request["chat_id"], "type": "private", "first_name": "Admin"}, "text": "ok"})
+        };
+        warp::http::Response::new(serde_json::to_vec(&json!({"ok": true, "result": result})).unwrap())
+    });
+    let (addr, server) = warp::serve(api).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    let bot = Bot::new("TOKEN").set_api_url(reqwest::Url::parse(&format!("http://{}/", addr)).unwrap());
+    let press = |from: u64, data: String| -> CallbackQuery {
+        serde_json::from_value(json!({
+            "id": "page-1",
+            "from": {"id": from, "is_bot": false, "first_name": "Admin"},
+            "message": {"message_id": 5, "date": 1, "chat": {"id": 4201, "type": "private", "first_name": "Admin"}, "text": "Admin panel members"},
+            "chat_instance": "panel",
+            "data": data,
+        })).unwrap()
+    };
+
+    panel_page_handler(bot.clone(), press(1, format!("{}2", LIST_ADMINS_CALLBACK))).await.expect("page callback failed");
+    let page = edited.lock().unwrap().last().cloned().unwrap();
+    assert!(page.starts_with("Admin panel members (page 3/3):"));
+    assert!(page.contains("\n21 @admin21 – view_stats\n"));
+
+    // Admin 1 only views stats, so the chats list stays closed to them
+    panel_page_handler(bot.clone(), press(1, format!("{}0", MONITORED_CHATS_CALLBACK))).await.expect("page callback failed");
+    assert_eq!(edited.lock().unwrap().len(), 1);
+
+    let mut manager = make_panel_admin(26);
+    manager.add_permission(AdminPermission::ManageChats);
+    save_panel_admin(&mut conn, &manager).unwrap();
+    panel_page_handler(bot, press(26, format!("{}0", MONITORED_CHATS_CALLBACK))).await.expect("page callback failed");
+    assert_eq!(
+        edited.lock().unwrap().last().map(String::as_str),
+        Some("Moderated chats (page 1/1):\n-100402\n-100401 – Crypto Chat\n")
+    );
+}
+
+#[tokio::test]
+#[serial]
+async fn audit_log_export_has_one_line_per_entry() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let now = Utc::now();
+    let entry = |i: u64, age: chrono::Duration| AuditLogEntry {
+        timestamp: now - age,
+        user_id: UserId(i),
+        user_name: format!("admin{}", i),
+        action: "Test Action".into(),
+        details: Some("line\nbreak".into()),
+    };
+    record_audit_entry(&mut conn, &entry(0, chrono::Duration::hours(48))).unwrap();
+    for i in 1..=100 {
+        record_audit_entry(&mut conn, &entry(i, chrono::Duration::seconds(100 - i as i64))).unwrap();
+    }
+
+    let entries = audit_entries_since(&mut conn, now - chrono::Duration::hours(24)).unwrap();
+    assert_eq!(entries.len(), 100, "The entry from two days ago is left out");
+    assert_eq!(entries[0].user_id, UserId(100), "Newest first");
+    let export = export_audit_log_jsonl(&entries);
+    assert_eq!(export.lines().count(), 100);
+    let parsed: Vec<AuditLogEntry> = export.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert_eq!(parsed, entries);
+
+    // Panel changes made through the admin commands are logged
+    flush_redis();
+    let (api_url, sent) = start_file_bot_api("");
+    let bot = Bot::new("TOKEN").set_api_url(api_url);
+    let add = make_message(4202, 855, "owner", "/addadmin 856|manager", 1);
+    handle_admin_command(bot.clone(), add, AdminCommand::AddAdmin { args: "856|manager".into() })
+        .await
+        .expect("addadmin failed");
+    let logged = audit_entries_since(&mut conn, now - chrono::Duration::hours(1)).unwrap();
+    assert_eq!(logged.len(), 1);
+    assert_eq!((logged[0].user_id, logged[0].action.as_str()), (UserId(855), "Add Admin User"));
+    assert_eq!(logged[0].details.as_deref(), Some("Added user 856 as Manager"));
+
+    let export = make_message(4202, 855, "owner", "/exportauditlog 0", 2);
+    handle_admin_command(bot, export, AdminCommand::ExportAuditLog { hours: "0".into() })
+        .await
+        .expect("exportauditlog failed");
+    assert_eq!(sent.lock().unwrap().last().map(String::as_str), Some("Usage: /exportauditlog [hours]"));
+}
+
+/// Starts a Bot API stand-in that serves `contents` as every downloaded file
+/// and records the text of each sent message.
+fn start_file_bot_api(contents: &'static str) -> (reqwest::Url, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+    let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = sent.clone();
+    let api = warp::path::full().and(warp::body::bytes()).map(move |path: warp::path::FullPath, body: Bytes| {
+        let path = path.as_str().to_lowercase();
+        if path.starts_with("/file/") {
+            return warp::http::Response::new(contents.as_bytes().to_vec());
+        }
+        let result = if path.ends_with("/getfile") {
+            json!({"file_id": "file1", "file_unique_id": "unique1", "file_size": contents.len(), "file_path": "documents/list.txt"})
+        } else {
+            let request: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+            recorded.lock().unwrap().push(request["text"].as_str().unwrap_or_default().to_string());
+            json!({"message_id": 1, "date": 0, "chat": {"id": request["chat_id"], "type": "private"}, "text": request["text"]})
+        };
+        warp::http::Response::new(serde_json::to_vec(&json!({"ok": true, "result": result})).unwrap())
+    });
+    let (addr, server) = warp::serve(api).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    (reqwest::Url::parse(&format!("http://{}/", addr)).unwrap(), sent)
+}
+
+#[tokio::test]
+#[serial]
+async fn import_whitelist_adds_every_line_of_the_uploaded_file() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (api_url, sent) = start_file_bot_api("1001\n1002\nnot-a-user\n\n 1003 \n1001\n");
+    let bot = Bot::new("TOKEN").set_api_url(api_url);
+    let upload = make_document_message(4060, 861, "admin", "", "users.txt", 1);
+    let command = make_message_with_reply(4060, 861, "admin", "/importwhitelist user", 2, upload);
+    handle_admin_command(bot, command, AdminCommand::ImportWhitelist { args: "user".into() })
+        .await
+        .expect("import failed");
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let listed: HashSet<String> = conn.smembers(key::TG_WHITELIST_USER_KEY).unwrap();
+    assert_eq!(listed, ["1001", "1002", "1003"].iter().map(|id| id.to_string()).collect());
+    assert_eq!(
+        sent.lock().unwrap().last().map(String::as_str),
+        Some("Imported 3 user(s) into the whitelist, skipped 1 already listed and 1 invalid.")
+    );
+
+    let outcome = lists::WHITELIST_WORDS.import(&mut conn, Some(ChatId(4060)), "casino\n\n  \nbonus\n").unwrap();
+    assert_eq!(outcome, lists::ImportOutcome { added: 2, duplicates: 0, invalid: 0 });
+    let words: HashSet<String> = conn.smembers(lists::WHITELIST_WORDS.key(Some(ChatId(4060)))).unwrap();
+    assert_eq!(words, ["casino", "bonus"].iter().map(|word| word.to_string()).collect());
+}
+
+#[tokio::test]
+#[serial]
+async fn import_config_restores_the_replied_to_export() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (api_url, sent) = start_file_bot_api(r#"{"version": 1, "enabled_features": ["caps"], "trusted_domains": ["docs.rs"]}"#);
+    let bot = Bot::new("TOKEN").set_api_url(api_url);
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let upload = make_document_message(4062, 861, "admin", "", "bot_config.json", 1);
+    let command = make_message_with_reply(4062, 861, "admin", "/importconfig", 2, upload);
+
+    // Without an admin panel a DM only imports for an admin of a moderated chat
+    handle_admin_command(bot.clone(), command.clone(), AdminCommand::ImportConfig).await.expect("import failed");
+    assert_eq!(
+        sent.lock().unwrap().last().map(String::as_str),
+        Some("Importing the configuration needs an admin of a chat the bot moderates.")
+    );
+    assert!(!conn.sismember::<_, _, bool>(key::TG_TRUSTED_DOMAINS_KEY, "docs.rs").unwrap());
+
+    let _: () = conn.sadd(format!("{}{}", 861, suffix::BOT_CHATS), -4063).unwrap();
+    handle_admin_command(bot, command, AdminCommand::ImportConfig).await.expect("import failed");
+    let features: HashSet<String> = conn.smembers(ENABLED_FEATURES_KEY).unwrap();
+    assert_eq!(features, HashSet::from(["caps".to_string()]));
+    assert!(conn.sismember::<_, _, bool>(key::TG_TRUSTED_DOMAINS_KEY, "docs.rs").unwrap());
+    assert_eq!(
+        sent.lock().unwrap().last().map(String::as_str),
+        Some("Configuration imported: 1 enabled features and the settings of 0 chats restored.")
+    );
+
+    // Nothing of an invalid document is applied
+    let (api_url, sent) = start_file_bot_api(r#"{"version": 1, "enabled_features": ["flood"], "thresholds": {"bogus": 1.0}}"#);
+    let bot = Bot::new("TOKEN").set_api_url(api_url);
+    let upload = make_document_message(4062, 861, "admin", "", "bot_config.json", 3);
+    let command = make_message_with_reply(4062, 861, "admin", "/importconfig", 4, upload);
+    handle_admin_command(bot, command, AdminCommand::ImportConfig).await.expect("import failed");
+    assert!(sent.lock().unwrap().last().unwrap().starts_with("Invalid configuration document: "));
+    let features: HashSet<String> = conn.smembers(ENABLED_FEATURES_KEY).unwrap();
+    assert_eq!(features, HashSet::from(["caps".to_string()]));
+}
+
+#[tokio::test]
+#[serial]
+async fn list_export_contains_every_entry_of_the_four_lists() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.sadd(key::TG_WHITELIST_USER_KEY, &["1002", "1001"]).unwrap();
+    let _: () = conn.sadd(key::TG_WHITELIST_WORD_KEY, "invoice").unwrap();
+    let _: () = conn.sadd(key::TG_BLACKLIST_USER_KEY, "2001").unwrap();
+    let _: () = conn.sadd(key::TG_BLACKLIST_WORD_KEY, &["casino", "airdrop"]).unwrap();
+    let _: () = conn.sadd(lists::BLACKLIST_WORDS.key(Some(ChatId(4061))), "bonus").unwrap();
+
+    let whitelist = lists::export(&mut conn, &[("users", lists::WHITELIST_USERS), ("words", lists::WHITELIST_WORDS)], None).unwrap();
+    assert_eq!(whitelist, "# users\n1001\n1002\n\n# words\ninvoice\n\n");
+    let blacklist = lists::export(&mut conn, &[("users", lists::BLACKLIST_USERS), ("words", lists::BLACKLIST_WORDS)], None).unwrap();
+    assert_eq!(blacklist, "# users\n2001\n\n# words\nairdrop\ncasino\n\n");
+
+    // A chat's export only holds that chat's own entries
+    let scoped = lists::export(&mut conn, &[("users", lists::BLACKLIST_USERS), ("words", lists::BLACKLIST_WORDS)], Some(ChatId(4061))).unwrap();
+    assert_eq!(scoped, "# users\n\n# words\nbonus\n\n");
+}
+
+#[tokio::test]
+#[serial]
+async fn appeal_is_recorded_and_approving_it_clears_the_ban() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let banned_chat = 4070;
+    let (admin_chat, admin_id, user_id) = (4071, 861u64, UserId(862));
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id.0);
+    let _: () = conn.hset_multiple(&user_key, &[(field::BANNED, "1"), (field::BANNED_IN, "4070"), (field::PERM_BANNED, "1")]).unwrap();
+    let _: () = conn.hset(&user_key, field::BANNED_Q, 3).unwrap();
+
+    let now = Utc::now().timestamp();
+    assert_eq!(record_appeal(&mut conn, UserId(863), "not banned", now).unwrap(), AppealOutcome::NotBanned);
+    let AppealOutcome::Recorded(recorded) = record_appeal(&mut conn, user_id, " My account was hacked ", now).unwrap() else {
+        panic!("A banned user's appeal should be recorded");
+    };
+    assert_eq!(recorded.chat_id, ChatId(banned_chat));
+    assert_eq!(recorded.reason, "My account was hacked");
+    assert_eq!(get_appeal(&mut conn, user_id).unwrap(), Some(recorded.clone()));
+    assert_eq!(
+        record_appeal(&mut conn, user_id, "again", now + 60).unwrap(),
+        AppealOutcome::CoolingDown(appeal::COOLDOWN_SECS - 60)
+    );
+
+    // An admin of the banned chat presses Approve
+    let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let recorded_texts = sent.clone();
+    let api = warp::path::full().and(warp::body::bytes()).map(move |path: warp::path::FullPath, body: Bytes| {
+        let path = path.as_str().to_lowercase();
+        let request: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+        let result = if path.ends_with("/getchatmember") {
+            json!({"status": "creator", "user": {"id": request["user_id"], "is_bot": false, "first_name": "Admin"}, "is_anonymous": false})
+        } else if path.ends_with("/answercallbackquery") || path.ends_with("/unbanchatmember") {
+            json!(true)
+        } else {
+            if let Some(text) = request["text"].as_str() {
+                recorded_texts.lock().unwrap().push(text.to_string());
+            }
+            json!({"message_id": 1, "date": 0, "chat": {"id": request["chat_id"], "type": "private", "first_name": "Admin"}, "text": "ok"})
+        };
+        warp::http::Response::new(serde_json::to_vec(&json!({"ok": true, "result": result})).unwrap())
+    });
+    let (addr, server) = warp::serve(api).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    let bot = Bot::new("TOKEN").set_api_url(reqwest::Url::parse(&format!("http://{}/", addr)).unwrap());
+    let query: CallbackQuery = serde_json::from_value(json!({
+        "id": "appeal-1",
+        "from": {"id": admin_id, "is_bot": false, "first_name": "Admin"},
+        "message": {"message_id": 5, "date": 1, "chat": {"id": admin_chat, "type": "private", "first_name": "Admin"}, "text": "Ban appeal"},
+        "chat_instance": "appeals",
+        "data": format!("{}approve:{}", APPEAL_CALLBACK, user_id.0),
+    })).unwrap();
+    appeal_handler(bot, query).await.expect("appeal callback failed");
+
+    let ban: HashMap<String, String> = conn.hgetall(&user_key).unwrap();
+    assert!(!ban.contains_key(field::BANNED) && !ban.contains_key(field::BANNED_IN) && !ban.contains_key(field::PERM_BANNED));
+    assert_eq!(ban.get(field::BANNED_Q).map(String::as_str), Some("3"), "Past bans still count");
+    assert_eq!(get_appeal(&mut conn, user_id).unwrap().unwrap().status, "approved");
+    assert!(sent.lock().unwrap().iter().any(|text| text.contains("approved")));
+
+    // A decided appeal can't be decided again
+    assert_eq!(decide_appeal(&mut conn, user_id, false, UserId(admin_id)).unwrap(), None);
+}
+
+#[tokio::test]
+#[serial]
+async fn join_burst_locks_the_chat_down_until_lifted() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let chat = ChatId(-1004072);
+
+    // Telegram stand-in that records restrictions and sent messages
+    let restricted = std::sync::Arc::new(std::sync::Mutex::new(Vec::<(u64, bool)>::new()));
+    let sent = std::sync::Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+    let (recorded_restrictions, recorded_texts) = (restricted.clone(), sent.clone());
+    let api = warp::path::full().and(warp::body::bytes()).map(move |path: warp::path::FullPath, body: Bytes| {
+        let path = path.as_str().to_lowercase();
+        let request: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+        let result = if path.ends_with("/restrictchatmember") {
+            let can_send = request["permissions"]["can_send_messages"].as_bool().unwrap_or(false);
+            recorded_restrictions.lock().unwrap().push((request["user_id"].as_u64().unwrap_or(0), can_send));
+            json!(true)
+        } else {
+            if let Some(text) = request["text"].as_str() {
+                recorded_texts.lock().unwrap().push(text.to_string());
+            }
+            json!({"message_id": 1, "date": 0, "chat": {"id": request["chat_id"], "type": "supergroup", "title": "Raid"}, "text": "ok"})
+        };
+        warp::http::Response::new(serde_json::to_vec(&json!({"ok": true, "result": result})).unwrap())
+    });
+    let (addr, server) = warp::serve(api).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    let bot = Bot::new("TOKEN").set_api_url(reqwest::Url::parse(&format!("http://{}/", addr)).unwrap());
+
+    let join = |user_id: u64| -> ChatMemberUpdated {
+        let user = json!({"id": user_id, "is_bot": false, "first_name": "Raider", "username": format!("raider{}", user_id)});
+        serde_json::from_value(json!({
+            "chat": {"id": chat.0, "type": "supergroup", "title": "Raid"},
+            "from": user,
+            "date": Utc::now().timestamp(),
+            "old_chat_member": {"user": user, "status": "left"},
+            "new_chat_member": {"user": user, "status": "member"},
+        })).unwrap()
+    };
+
+    // A burst of joins one short of a raid leaves the chat open
+    let raiders: Vec<u64> = (0..lockdown::RAID_EVENTS as u64 + 1).map(|i| 870 + i).collect();
+    for user_id in &raiders[..raiders.len() - 2] {
+        chat_member_handler(bot.clone(), join(*user_id)).await.expect("join failed");
+    }
+    assert_eq!(lockdown_since(&mut conn, chat), None);
+    assert!(restricted.lock().unwrap().is_empty());
+
+    // The join completing the raid locks the chat down and mutes from then on
+    for user_id in &raiders[raiders.len() - 2..] {
+        chat_member_handler(bot.clone(), join(*user_id)).await.expect("join failed");
+    }
+    assert!(lockdown_since(&mut conn, chat).is_some(), "A join burst should lock the chat down");
+    let muted: Vec<u64> = restricted.lock().unwrap().iter().filter(|(_, can_send)| !can_send).map(|(user, _)| *user).collect();
+    assert_eq!(muted, raiders[raiders.len() - 2..].to_vec());
+    assert!(sent.lock().unwrap().iter().any(|text| text.starts_with("Raid detected")));
+
+    // /lockdown off lifts it and unmutes the newcomers
+    assert_eq!(end_lockdown(&bot, &mut conn, chat).await.unwrap(), 2);
+    assert_eq!(lockdown_since(&mut conn, chat), None);
+    let unmuted = restricted.lock().unwrap().iter().filter(|(_, can_send)| *can_send).count();
+    assert_eq!(unmuted, 2);
+}
+
+#[tokio::test]
+#[serial]
+async fn lockdown_from_a_dm_needs_an_admin_of_the_target_chat() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (api_url, sent) = start_file_bot_api("");
+    let bot = Bot::new("TOKEN").set_api_url(api_url);
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let chat = ChatId(-1004074);
+    let lock = |user_id: u64| {
+        let command = make_message(user_id as i64, user_id, "user", "/lockdown on|-1004074", 1);
+        handle_admin_command(bot.clone(), command, AdminCommand::Lockdown { args: format!("on|{}", chat) })
+    };
+
+    lock(880).await.expect("lockdown failed");
+    assert_eq!(sent.lock().unwrap().last().map(String::as_str), Some("You are not an admin of chat -1004074."));
+    assert_eq!(lockdown_since(&mut conn, chat), None);
+
+    let _: () = conn.sadd(format!("{}{}", 881, suffix::BOT_CHATS), chat.0).unwrap();
+    lock(881).await.expect("lockdown failed");
+    assert!(lockdown_since(&mut conn, chat).is_some(), "An admin of the chat may lock it down");
+}
+
+#[tokio::test]
+#[serial]
+async fn mute_from_a_dm_needs_an_admin_of_the_target_chat() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (bot, calls) = telegram_stand_in();
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let (chat, member) = (ChatId(-1004075), 882u64);
+    let restrictions = || calls.lock().unwrap().iter().filter(|(method, _)| method == "restrictchatmember").count();
+    let last_reply = || {
+        calls.lock().unwrap().iter().rev()
+            .find(|(method, _)| method == "sendmessage")
+            .and_then(|(_, request)| request["text"].as_str().map(str::to_string))
+            .unwrap_or_default()
+    };
+    let mute = |user_id: u64| {
+        let args = format!("{}|10|{}", member, chat);
+        let command = make_message(user_id as i64, user_id, "user", &format!("/mute {}", args), 1);
+        handle_admin_command(bot.clone(), command, AdminCommand::Mute { args })
+    };
+    let unmute = |user_id: u64| {
+        let command = make_message(user_id as i64, user_id, "user", &format!("/unmute {}", member), 2);
+        handle_admin_command(bot.clone(), command, AdminCommand::Unmute { args: member.to_string() })
+    };
+
+    mute(883).await.expect("mute failed");
+    assert_eq!(last_reply(), "You are not an admin of chat -1004075.");
+    assert_eq!(restrictions(), 0);
+
+    let _: () = conn.sadd(format!("{}{}", 884, suffix::BOT_CHATS), chat.0).unwrap();
+    mute(884).await.expect("mute failed");
+    assert_eq!(restrictions(), 1, "An admin of the chat may mute its members");
+
+    // Unmuting where the mute was issued needs an admin of that chat as well
+    unmute(883).await.expect("unmute failed");
+    assert_eq!(last_reply(), "You are not an admin of chat -1004075.");
+    assert_eq!(restrictions(), 1);
+    unmute(884).await.expect("unmute failed");
+    assert_eq!(restrictions(), 2);
+}
+
+#[tokio::test]
+#[serial]
+async fn simulated_raid_locks_a_staging_chat_down() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let staging = make_chat(-1004073);
+    let (bot, calls) = telegram_stand_in();
+
+    // Only chats listed in SIMULATE_RAID_CHATS may run a simulation
+    std::env::remove_var(raid_simulation::CHATS_ENV);
+    assert!(!simulation_allowed(staging.id));
+    std::env::set_var(raid_simulation::CHATS_ENV, format!("-1000000000001, {}", staging.id.0));
+    assert!(simulation_allowed(staging.id));
+    assert!(!simulation_allowed(ChatId(-1004074)));
+
+    // Every member joins and posts, so half of RAID_EVENTS members trip the lockdown
+    let members = lockdown::RAID_EVENTS as usize / 2;
+    let outcome = simulate_raid(&bot, &mut conn, &staging, members).await.expect("simulation failed");
+    std::env::remove_var(raid_simulation::CHATS_ENV);
+    assert!(!outcome.already_locked);
+    assert_eq!(outcome.lockdown_at, Some(lockdown::RAID_EVENTS as usize));
+    assert!(lockdown_since(&mut conn, staging.id).is_some(), "The simulated raid should lock the chat down");
+    assert!(calls.lock().unwrap().iter().any(|(method, _)| method == "restrictchatmember"));
+    assert!(render_simulation(staging.id, &outcome).contains("Lockdown: started at event"));
+
+    // The synthetic members are gone again and won't be unmuted on lift
+    let first = UserId(raid_simulation::FIRST_USER_ID);
+    let user_key = format!("{}{}", key::ns(key::TG_USERS_PREFIX), first.0);
+    assert!(!conn.exists::<_, bool>(&user_key).unwrap());
+    let held: Vec<u64> = conn.smembers(format!("{}{}{}", key::ns(key::TG_CHATS_PREFIX), staging.id.0, suffix::LOCKDOWN_MUTED)).unwrap();
+    assert!(held.is_empty());
+}
+
+/// Method and request of each call made to the Bot API stand-in
+type RecordedCalls = std::sync::Arc<std::sync::Mutex<Vec<(String, serde_json::Value)>>>;
+
+/// Bot API stand-in answering every call with success; records the method and
+/// request of each call.
+fn telegram_stand_in() -> (Bot, RecordedCalls) {
+    let calls = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = calls.clone();
+    let api = warp::path::full().and(warp::body::bytes()).map(move |path: warp::path::FullPath, body: Bytes| {
+        let method = path.as_str().rsplit('/').next().unwrap_or_default().to_lowercase();
+        let request: serde_json::Value = serde_json::from_slice(&body).unwrap_or_default();
+        let result = if method == "sendmessage" {
+            json!({"message_id": 1, "date": 0, "chat": {"id": request["chat_id"], "type": "supergroup", "title": "Chat"}, "text": "ok"})
+        } else {
+            json!(true)
+        };
+        recorded.lock().unwrap().push((method, request));
+        warp::http::Response::new(serde_json::to_vec(&json!({"ok": true, "result": result})).unwrap())
+    });
+    let (addr, server) = warp::serve(api).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    (Bot::new("TOKEN").set_api_url(reqwest::Url::parse(&format!("http://{}/", addr)).unwrap()), calls)
+}
+
+fn member_update(chat_id: i64, user_id: u64, username: Option<&str>, old_status: &str, new_status: &str) -> ChatMemberUpdated {
+    let mut user = json!({"id": user_id, "is_bot": false, "first_name": "Newcomer"});
+    if let Some(username) = username {
+        user["username"] = json!(username);
+    }
+    serde_json::from_value(json!({
+        "chat": {"id": chat_id, "type": "supergroup", "title": "Chat"},
+        "from": user,
+        "date": Utc::now().timestamp(),
+        "old_chat_member": chat_member(&user, old_status),
+        "new_chat_member": chat_member(&user, new_status),
+    })).unwrap()
+}
+
+fn chat_member(user: &serde_json::Value, status: &str) -> serde_json::Value {
+    let mut member = json!({"user": user, "status": status});
+    // Telegram always sends until_date for banned members, 0 meaning forever
+    if status == "kicked" {
+        member["until_date"] = json!(0);
+    }
+    member
+}
+
+#[tokio::test]
+#[serial]
+async fn chat_member_handler_records_joins_and_cleans_up_on_leave() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let (bot, calls) = telegram_stand_in();
+    let chat = ChatId(-1004074);
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, 880);
+
+    let before = Utc::now().timestamp();
+    chat_member_handler(bot.clone(), member_update(chat.0, 880, Some("Newcomer880"), "left", "member")).await.expect("join failed");
+    let join_time: Option<i64> = conn.hget(&user_key, field::JOIN_TIME).unwrap();
+    assert!(join_time.is_some_and(|joined| joined >= before), "A join should record join_time");
+    assert_eq!(lookup_username(&mut conn, "@newcomer880"), Some(UserId(880)));
+    assert!(calls.lock().unwrap().is_empty(), "Without join_verify newcomers aren't challenged");
+
+    // Users without a username join too
+    chat_member_handler(bot.clone(), member_update(chat.0, 881, None, "kicked", "member")).await.expect("join failed");
+    let join_time: Option<i64> = conn.hget(format!("{}{}", key::TG_USERS_PREFIX, 881), field::JOIN_TIME).unwrap();
+    assert!(join_time.is_some());
+
+    // With join_verify on, a newcomer is muted and greeted with the challenge
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, chat.0), format!("feat:{}", JOIN_VERIFY_FEATURE), "1").unwrap();
+    chat_member_handler(bot.clone(), member_update(chat.0, 882, None, "left", "member")).await.expect("join failed");
+    assert!(is_pending(&mut conn, chat, UserId(882)).unwrap());
+    let methods: Vec<String> = calls.lock().unwrap().iter().map(|(method, _)| method.clone()).collect();
+    assert_eq!(methods, vec!["restrictchatmember", "sendmessage"]);
+    let greeting = calls.lock().unwrap()[1].1.clone();
+    assert_eq!(
+        greeting["reply_markup"]["inline_keyboard"][0][0]["callback_data"],
+        json!(format!("{}{}:{}", VERIFY_CALLBACK, chat.0, 882))
+    );
+
+    // Leaving drops the user's state and the pending challenge
+    chat_member_handler(bot.clone(), member_update(chat.0, 882, None, "member", "left")).await.expect("leave failed");
+    let exists: bool = conn.exists(format!("{}{}", key::TG_USERS_PREFIX, 882)).unwrap();
+    assert!(!exists);
+    assert!(!is_pending(&mut conn, chat, UserId(882)).unwrap());
+}
+
+fn challenge_press(chat_id: i64, user_id: u64, presser: u64) -> CallbackQuery {
+    serde_json::from_value(json!({
+        "id": "press",
+        "from": {"id": presser, "is_bot": false, "first_name": "Presser"},
+        "chat_instance": "instance",
+        "data": format!("{}{}:{}", VERIFY_CALLBACK, chat_id, user_id),
+        "message": {
+            "message_id": 7,
+            "date": 0,
+            "chat": {"id": chat_id, "type": "supergroup", "title": "Chat"},
+            "text": "Welcome",
+        },
+    })).unwrap()
+}
+
+#[tokio::test]
+#[serial]
+async fn pressing_the_join_challenge_lifts_the_mute() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let (bot, calls) = telegram_stand_in();
+    let chat = ChatId(-1004075);
+    let deadline = Utc::now().timestamp() + 300;
+    record_challenge(&mut conn, chat, UserId(890), deadline, Some(MessageId(7))).unwrap();
+
+    // Someone else pressing the button doesn't pass the challenge
+    verify_handler(bot.clone(), challenge_press(chat.0, 890, 891)).await.expect("press failed");
+    assert!(is_pending(&mut conn, chat, UserId(890)).unwrap());
+    let methods: Vec<String> = calls.lock().unwrap().iter().map(|(method, _)| method.clone()).collect();
+    assert_eq!(methods, vec!["answercallbackquery"]);
+    calls.lock().unwrap().clear();
+
+    verify_handler(bot.clone(), challenge_press(chat.0, 890, 890)).await.expect("press failed");
+    assert!(!is_pending(&mut conn, chat, UserId(890)).unwrap());
+    let calls = calls.lock().unwrap().clone();
+    let (_, unmute) = calls.iter().find(|(method, _)| method == "restrictchatmember").expect("member should be unmuted");
+    assert_eq!(unmute["user_id"], json!(890));
+    assert_eq!(unmute["permissions"]["can_send_messages"], json!(true));
+    assert!(calls.iter().any(|(method, _)| method == "deletemessage"), "The challenge should be removed");
+
+    // Nothing is left for the periodic task to kick
+    assert_eq!(kick_unverified(&bot, &mut conn, deadline + 1).await.unwrap(), 0);
+}
+
+#[tokio::test]
+#[serial]
+async fn unanswered_join_challenges_are_kicked_after_the_timeout() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let (bot, calls) = telegram_stand_in();
+    let chat = ChatId(-1004076);
+    let now = Utc::now().timestamp();
+    record_challenge(&mut conn, chat, UserId(892), now - 1, Some(MessageId(8))).unwrap();
+    record_challenge(&mut conn, chat, UserId(893), now + 300, Some(MessageId(9))).unwrap();
+
+    assert_eq!(kick_unverified(&bot, &mut conn, now).await.unwrap(), 1);
+    assert!(!is_pending(&mut conn, chat, UserId(892)).unwrap());
+    assert!(is_pending(&mut conn, chat, UserId(893)).unwrap(), "Challenges within their time stay pending");
+
+    let calls = calls.lock().unwrap().clone();
+    let methods: Vec<&str> = calls.iter().map(|(method, _)| method.as_str()).collect();
+    assert_eq!(methods, vec!["deletemessage", "banchatmember", "unbanchatmember"]);
+    assert_eq!(calls[0].1["message_id"], json!(8));
+    assert_eq!(calls[1].1["user_id"], json!(892));
+    assert_eq!(calls[2].1["only_if_banned"], json!(true));
+}
+
+#[tokio::test]
+#[serial]
+async fn bot_namespaces_keep_user_reputation_independent() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let user_id: u64 = 831;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+
+    std::env::set_var(key::NAMESPACE_ENV, "bot_a");
+    assert_eq!(add_rep(&mut conn, user_id, 5).unwrap(), 5);
+    std::env::set_var(key::NAMESPACE_ENV, "bot_b");
+    assert_eq!(add_rep(&mut conn, user_id, -3).unwrap(), -3);
+    assert_eq!(add_rep(&mut conn, user_id, -1).unwrap(), -4);
+    std::env::remove_var(key::NAMESPACE_ENV);
+
+    let rep_a: i64 = conn.hget(format!("bot_a:{}{}", key::TG_USERS_PREFIX, user_id), field::REP).unwrap();
+    let rep_b: i64 = conn.hget(format!("bot_b:{}{}", key::TG_USERS_PREFIX, user_id), field::REP).unwrap();
+    assert_eq!((rep_a, rep_b), (5, -4));
+    let unprefixed: bool = conn.exists(key::ns(format!("{}{}", key::TG_USERS_PREFIX, user_id))).unwrap();
+    assert!(!unprefixed, "Namespaced bots leave the shared keys alone");
+}
+
+#[tokio::test]
+#[serial]
+async fn ban_logs_spam_event_for_dashboard() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4010;
+    let user_id: u64 = 780;
+    let since = Utc::now().timestamp() - 24 * 60 * 60;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    assert_eq!(count_spam_events_since(&mut conn, since).unwrap(), 0);
+
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, user_id), field::REP, CONFIG.ban + 1).unwrap();
+    let reply = scan_msg(make_message(chat_id, user_id, "tester", "Test message", 1), "Test message".into())
+        .await.expect("scan failed");
+    assert!(reply.symbols.contains_key(symbol::TG_BAN));
+
+    assert_eq!(count_spam_events_since(&mut conn, since).unwrap(), 1, "Ban should be logged as a spam event");
+    let event_keys: Vec<String> = conn.keys(format!("{}*", key::SPAM_EVENT_PREFIX)).unwrap();
+    let event: HashMap<String, String> = conn.hgetall(&event_keys[0]).unwrap();
+    assert_eq!(event.get("chat_id"), Some(&chat_id.to_string()));
+    assert_eq!(event.get("user_id"), Some(&user_id.to_string()));
+    assert!(event["symbols"].contains(symbol::TG_BAN));
+    let ttl: i64 = conn.ttl(&event_keys[0]).unwrap();
+    assert!(ttl > 0 && ttl <= spam_event::TTL, "Events should expire on their own");
+
+    // Clean messages aren't logged
+    let _ = scan_msg(make_message(chat_id, user_id + 1, "clean", "Hello there", 2), "Hello there".into())
+        .await.expect("scan failed");
+    assert_eq!(count_spam_events_since(&mut conn, since).unwrap(), 1);
+}
+
+#[tokio::test]
+#[serial]
+async fn spam_event_records_the_symbols_behind_a_ban() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 4013;
+    let user_id: u64 = 784;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, user_id), field::REP, CONFIG.ban + 1).unwrap();
+
+    let text = "Deals at http://a.com http://b.com http://c.com http://d.com";
+    let reply = scan_msg(make_message(chat_id, user_id, "tester", text, 1), text.into())
+        .await.expect("scan failed");
+    assert!(reply.symbols.contains_key(symbol::TG_BAN));
+    assert!(reply.symbols.contains_key(symbol::TG_LINK_SPAM));
+
+    let event_keys: Vec<String> = conn.keys(format!("{}*", key::SPAM_EVENT_PREFIX)).unwrap();
+    assert_eq!(event_keys.len(), 1);
+    let reason: String = conn.hget(&event_keys[0], "reason").unwrap();
+    assert!(reason.contains(symbol::TG_LINK_SPAM), "Reason should name the content symbols, got: {}", reason);
+    assert!(!reason.contains(symbol::TG_BAN), "The ban verdict itself isn't part of the reason");
+    assert!(reason.contains("rep "), "Reason should include the user's reputation, got: {}", reason);
+}
+
+#[test]
+fn describe_reason_lists_highest_scoring_symbols_first() {
+    let fired = |name: &str, score: f64| {
+        (name.to_string(), Symbol { name: name.to_string(), score, metric_score: score, description: None, options: None })
+    };
+    let symbols: HashMap<String, Symbol> = [
+        fired(symbol::TG_CAPS, 2.0),
+        fired(symbol::TG_LINK_SPAM, 3.0),
+        fired(symbol::TG_BAN, 10.0),
+    ]
+    .into_iter()
+    .collect();
+
+    assert_eq!(describe_reason(&symbols, 21), "TG_LINK_SPAM, TG_CAPS, rep 21");
+    assert_eq!(describe_reason(&HashMap::new(), 3), "rep 3");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_perm_ban_sets_symbol_and_updates_perm_ban_count() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 5005;
+    let user_id: u64 = 888;
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, chat_id);
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.hset(user_key.clone(), field::BANNED_Q, CONFIG.banned_q + 1).expect("Failed to set banned_q");
+    let _: () = conn.hset(user_key.clone(), field::REP, 0).expect("Failed to set rep");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "tester", "Another message", 1),
+        "Another message".into()
+    ).await.ok().unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    assert!(reply.symbols.contains_key(symbol::TG_PERM_BAN), "Expected TG_PERM_BAN symbol for frequent offender");
+    assert!(!reply.symbols.contains_key(symbol::TG_BAN), "TG_BAN should not be present when only perm ban triggers");
+    let perm_bans: i64 = conn.hget(chat_key.clone(), "perm_banned").expect("Failed to get perm_banned count");
+    assert_eq!(perm_bans, 1, "Chat's permanent ban count should increment by 1");
+    let final_ban_count: i64 = conn.hget(user_key.clone(), field::BANNED_Q).expect("Failed to get 'banned_q'");
+    assert_eq!(final_ban_count, 4, "User banned_q should remain at 4 (perm ban triggered)");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_perm_ban_applies_the_chats_action_and_still_counts() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let kick_chat: i64 = 5006;
+    let report_chat: i64 = 5007;
+    assert_eq!(perm_ban_action(&mut conn, ChatId(kick_chat)), PermBanAction::Ban, "Chats ban by default");
+    assert_eq!(PermBanAction::parse("kick"), Some(PermBanAction::Kick));
+    assert_eq!(PermBanAction::parse("mute"), None);
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, kick_chat), field::PERM_BAN_ACTION, "kick").unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, report_chat), field::PERM_BAN_ACTION, "report").unwrap();
+    assert_eq!(perm_ban_action(&mut conn, ChatId(kick_chat)), PermBanAction::Kick);
+    assert_eq!(perm_ban_action(&mut conn, ChatId(report_chat)), PermBanAction::Report);
+
+    // Kicking removes the offender but lets them join again
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, 889), field::BANNED_Q, CONFIG.banned_q + 1).unwrap();
+    let (bot, calls) = telegram_stand_in();
+    handle_message(bot, make_message(kick_chat, 889, "offender", "Another message", 1)).await.expect("handle_message failed");
+    let calls = calls.lock().unwrap().clone();
+    let methods: Vec<&str> = calls.iter().map(|(method, _)| method.as_str()).collect();
+    let ban = methods.iter().position(|method| *method == "banchatmember").expect("offender should be removed");
+    assert_eq!(methods[ban + 1], "unbanchatmember");
+    assert_eq!(calls[ban + 1].1["only_if_banned"], json!(true));
+    let perm_bans: i64 = conn.hget(format!("{}{}", key::TG_CHATS_PREFIX, kick_chat), "perm_banned").unwrap();
+    assert_eq!(perm_bans, 1);
+
+    // Reporting leaves the offender in the chat but tells the admins
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, 890), field::BANNED_Q, CONFIG.banned_q + 1).unwrap();
+    let (bot, calls) = telegram_stand_in();
+    handle_message(bot, make_message(report_chat, 890, "offender", "Another message", 2)).await.expect("handle_message failed");
+    let calls = calls.lock().unwrap().clone();
+    assert!(!calls.iter().any(|(method, _)| method == "banchatmember"), "Report-only chats keep the offender");
+    let (_, alert) = calls.iter().find(|(method, _)| method == "sendmessage").expect("admins should be told");
+    assert!(alert["text"].as_str().unwrap().contains("TG_PERM_BAN"));
+    let perm_bans: i64 = conn.hget(format!("{}{}", key::TG_CHATS_PREFIX, report_chat), "perm_banned").unwrap();
+    assert_eq!(perm_bans, 1);
+}
+
+#[tokio::test]
+#[serial]
+async fn user_notes_are_kept_in_order_up_to_the_cap() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let user = UserId(894);
+    assert!(user_notes(&mut conn, user).unwrap().is_empty());
+
+    add_note(&mut conn, user, UserId(42), "warned about crypto links", 1_704_189_600).unwrap();
+    add_note(&mut conn, user, UserId(43), "second warning | last one", 1_704_276_000).unwrap();
+    let notes = user_notes(&mut conn, user).unwrap();
+    assert_eq!(notes, vec![
+        UserNote { timestamp: 1_704_189_600, author: 42, text: "warned about crypto links".to_string() },
+        UserNote { timestamp: 1_704_276_000, author: 43, text: "second warning | last one".to_string() },
+    ]);
+    let stored: Vec<String> = conn.lrange(format!("{}{}{}", key::TG_USERS_PREFIX, user.0, suffix::NOTES), 0, -1).unwrap();
+    assert_eq!(stored.len(), 2);
+
+    // Past the cap the oldest notes go first
+    for i in 0..notes::MAX_NOTES {
+        add_note(&mut conn, user, UserId(42), &format!("note {}", i), 1_704_300_000 + i as i64).unwrap();
+    }
+    let notes = user_notes(&mut conn, user).unwrap();
+    assert_eq!(notes.len(), notes::MAX_NOTES as usize);
+    assert_eq!(notes[0].text, "note 0");
+    assert_eq!(notes.last().unwrap().text, format!("note {}", notes::MAX_NOTES - 1));
+}
+
+#[tokio::test]
+#[serial]
+async fn makeadmin_adds_admin_chat_and_generates_keyboard() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let user_id: u64 = 42;
+    let current_chat: i64 = 1000;
+    let other_chat: i64 = 2000;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.sadd(format!("{}{}", user_id, suffix::BOT_CHATS), current_chat).unwrap();
+    let _: () = conn.sadd(format!("{}{}", user_id, suffix::BOT_CHATS), other_chat).unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, current_chat), field::NAME, "CurrentChat").unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, other_chat), field::NAME, "OtherChat").unwrap();
+
+    let bot = Bot::new("DUMMY");
+    let msg = make_message(current_chat, user_id, "tester", "/makeadmin", 1);
+    let result = handle_admin_command(bot, msg, AdminCommand::MakeAdmin).await;
+    assert!(result.is_err(), "Expected send_message to fail with dummy token");
+
+    let admin_chats: Vec<i64> = conn.smembers(format!("{}{}", user_id, suffix::ADMIN_CHATS)).unwrap();
+    assert!(admin_chats.contains(&current_chat), "Admin chat not set in Redis");
+    let bot_chats: Vec<i64> = conn.smembers(format!("{}{}", user_id, suffix::BOT_CHATS)).unwrap();
+    assert!(bot_chats.contains(&current_chat) && bot_chats.contains(&other_chat));
+    let expected_buttons = bot_chats.len() - 1;
+    assert_eq!(expected_buttons, 1, "Inline keyboard should list exactly one other chat");
+}
+
+
+#[tokio::test]
+#[serial]
+async fn reputation_command_returns_value_or_zero() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let user_id: u64 = 100;
+    let target_username = "someuser";
+    let rep_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let res: bool = conn.hset(rep_key.clone(), field::REP, 0).expect("Failed to set rep");
+    assert!(res);
+
+    let bot = Bot::new("DUMMY");
+    let chat_id: i64 = 1111;
+    let msg1 = make_message(chat_id, user_id, "tester", &format!("/reputation {}", target_username), 1);
+    let res1 = handle_admin_command(bot.clone(), msg1, AdminCommand::Reputation { user: target_username.into() }).await;
+    assert!(res1.is_err(), "Expected dummy send_message to fail");
+    let rep: bool = conn.hexists(rep_key.clone(), field::REP).expect("Failed to get rep");
+    assert!(rep, "Reputation key should not exist for new user");
+
+    let _:() = conn.hset(rep_key.clone(), field::REP, 5).expect("Failed to set rep");
+    let msg2 = make_message(chat_id, user_id, "tester", &format!("/reputation {}", target_username), 2);
+    let res2 = handle_admin_command(bot, msg2, AdminCommand::Reputation { user: target_username.into() }).await;
+    assert!(res2.is_err());
+    let stored: i64 = conn.hget(rep_key.clone(), field::REP).expect("Failed to get rep");
+    assert_eq!(stored, 5, "Reputation value should remain 5 in Redis");
+}
+
+#[tokio::test]
+#[serial]
+async fn username_index_resolves_real_username() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+
+    index_username(&mut conn, "RealUser", UserId(987654)).expect("Failed to index username");
+
+    assert_eq!(lookup_username(&mut conn, "realuser"), Some(UserId(987654)));
+    assert_eq!(lookup_username(&mut conn, "@RealUser"), Some(UserId(987654)));
+    assert_eq!(lookup_username(&mut conn, "unknown_user"), None);
+}
+
+#[tokio::test]
+#[serial]
+async fn reportspam_escalates_after_threshold() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = ChatId(6006);
+    let message_id = 77;
+    let sender = UserId(600);
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, sender.0);
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.hset(&user_key, field::REP, 0).unwrap();
+
+    assert_eq!(
+        record_spam_report(&mut conn, chat_id, message_id, sender, sender).unwrap(),
+        ReportOutcome::SelfReport
+    );
+    assert_eq!(
+        record_spam_report(&mut conn, chat_id, message_id, UserId(601), sender).unwrap(),
+        ReportOutcome::Recorded(1)
+    );
+    assert_eq!(
+        record_spam_report(&mut conn, chat_id, message_id, UserId(601), sender).unwrap(),
+        ReportOutcome::Duplicate
+    );
+    assert_eq!(
+        record_spam_report(&mut conn, chat_id, message_id, UserId(602), sender).unwrap(),
+        ReportOutcome::Recorded(2)
+    );
+    let rep: i64 = conn.hget(&user_key, field::REP).unwrap();
+    assert_eq!(rep, 0, "No escalation before the threshold is exceeded");
+
+    assert_eq!(
+        record_spam_report(&mut conn, chat_id, message_id, UserId(603), sender).unwrap(),
+        ReportOutcome::Escalated(3)
+    );
+    assert_eq!(
+        record_spam_report(&mut conn, chat_id, message_id, UserId(604), sender).unwrap(),
+        ReportOutcome::Recorded(4)
+    );
+    let rep: i64 = conn.hget(&user_key, field::REP).unwrap();
+    assert_eq!(rep, report::REP_PENALTY, "Sender should be penalized exactly once");
+}
+
+#[tokio::test]
+#[serial]
+async fn silent_chats_get_no_bot_messages_but_admins_are_still_alerted() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (chat_id, admin_chat) = (6016i64, 6017i64);
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, chat_id);
+    let _: () = conn.hset(&chat_key, format!("feat:{}", SILENT_MODE_FEATURE), "1").unwrap();
+    let _: () = conn.hset(&chat_key, field::ADMIN_CHAT, admin_chat).unwrap();
+
+    let (bot, calls) = telegram_stand_in();
+    let spam = make_message(chat_id, 610, "spammer", "Buy cheap followers", 90);
+    for reporter in 611..=611 + report::ESCALATION_THRESHOLD as u64 {
+        let report = make_message_with_reply(chat_id, reporter, "member", "/reportspam", reporter as u32, spam.clone());
+        handle_report_spam(bot.clone(), report).await.expect("report failed");
+    }
+
+    let sent: Vec<(i64, String)> = calls
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(method, _)| method == "sendmessage")
+        .map(|(_, request)| (request["chat_id"].as_i64().unwrap(), request["text"].as_str().unwrap_or_default().to_string()))
+        .collect();
+    assert!(sent.iter().all(|(chat, _)| *chat != chat_id), "Nothing is posted in the silent chat: {:?}", sent);
+    assert_eq!(sent.len(), 1, "Only the escalation is sent: {:?}", sent);
+    assert_eq!(sent[0].0, admin_chat);
+    assert!(sent[0].1.contains("reported as spam"));
+
+    // Without an admin chat the alert has nowhere to go
+    let _: () = conn.hdel(&chat_key, field::ADMIN_CHAT).unwrap();
+    assert_eq!(alert_target(&mut conn, ChatId(chat_id), None), None);
+    let _: () = conn.hset(&chat_key, format!("feat:{}", SILENT_MODE_FEATURE), "0").unwrap();
+    assert_eq!(alert_target(&mut conn, ChatId(chat_id), None), Some(ChatId(chat_id)));
+}
+
+#[tokio::test]
+#[serial]
+async fn handled_message_content_is_stored_for_learning() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let text = "Limited offer, reply to claim your prize";
+    let res = handle_message(Bot::new("DUMMY"), make_message(5020, 1060, "sender", text, 42)).await;
+    assert!(res.is_ok());
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let stored = stored_message_content(&mut conn, "42").unwrap();
+    assert_eq!(stored.as_deref(), Some(text), "Scanned message text should be retrievable by id");
+    assert_eq!(stored_message_content(&mut conn, "43").unwrap(), None);
+
+    let ttl: i64 = conn.ttl(format!("{}{}", key::TG_MESSAGE_PREFIX, 42)).unwrap();
+    assert!(ttl > 0 && ttl <= retention::message_content(), "Stored text should expire, got TTL {}", ttl);
+    let index: Vec<i32> = conn.lrange(key::TG_MESSAGE_INDEX_KEY, 0, -1).unwrap();
+    assert_eq!(index, vec![42], "Stored ids should be indexed for the cap");
+}
+
+#[tokio::test]
+#[serial]
+async fn purge_records_recent_messages_and_counts_deletions() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = ChatId(5010);
+    let spammer = UserId(1050);
+    let bystander = UserId(1051);
+
+    for msg_id in 1..=3 {
+        let res = handle_message(Bot::new("DUMMY"), make_message(chat_id.0, spammer.0, "spammer", "hello", msg_id)).await;
+        assert!(res.is_ok());
+    }
+    let res = handle_message(Bot::new("DUMMY"), make_message(chat_id.0, bystander.0, "bystander", "hi", 4)).await;
+    assert!(res.is_ok());
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let ids = recent_message_ids(&mut conn, chat_id, spammer).unwrap();
+    assert_eq!(ids, vec![MessageId(3), MessageId(2), MessageId(1)], "Ids should be recorded newest first");
+    let ids = recent_message_ids(&mut conn, ChatId(5011), spammer).unwrap();
+    assert!(ids.is_empty(), "Ids are tracked per chat");
+
+    for msg_id in 0..(purge::RECENT_MESSAGES_LIMIT + 10) {
+        record_recent_message(&mut conn, chat_id, bystander, MessageId(100 + msg_id as i32)).unwrap();
+    }
+    let ids = recent_message_ids(&mut conn, chat_id, bystander).unwrap();
+    assert_eq!(ids.len(), purge::RECENT_MESSAGES_LIMIT as usize, "Only the newest ids are kept");
+
+    // Telegram refuses to delete the oldest message
+    let ids = recent_message_ids(&mut conn, chat_id, spammer).unwrap();
+    let outcome = purge_messages(&ids, |id| async move {
+        if id == MessageId(1) { Err("message can't be deleted") } else { Ok(()) }
+    }).await;
+    assert_eq!(outcome, PurgeOutcome { deleted: 2, failed: 1 });
+
+    let msg = make_message(chat_id.0, 1, "admin", "/purge 1050", 10);
+    let res = handle_admin_command(Bot::new("DUMMY"), msg, AdminCommand::Purge { user: spammer.0.to_string() }).await;
+    assert!(res.is_err(), "Expected dummy send_message to fail");
+}
+
+#[tokio::test]
+#[serial]
+async fn reset_chat_removes_only_the_target_chats_keys() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let (target, other) = (ChatId(5020), ChatId(50201));
+    let today = Utc::now().date_naive();
+    for chat in [target, other] {
+        let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, chat.0);
+        let _: () = conn.hset(&chat_key, field::NAME, "chat").unwrap();
+        let _: () = conn.hset(&chat_key, "feat:flood", "0").unwrap();
+        let _: () = conn.hincr(format!("{}{}", chat_key, suffix::SYMBOL_COUNTS), "TG_FLOOD", 1).unwrap();
+        record_daily_action(&mut conn, chat, action::DELETE, today).unwrap();
+    }
+    let _: () = conn.sadd(key::TG_WHITELIST_WORD_KEY, "hello").unwrap();
+
+    let keys = chat_state_keys(&mut conn, target).unwrap();
+    assert_eq!(keys.len(), 3, "hash, symbol counts and daily bucket: {:?}", keys);
+    assert_eq!(reset_chat(&mut conn, target).unwrap(), 3);
+
+    assert!(chat_state_keys(&mut conn, target).unwrap().is_empty());
+    assert!(!conn.exists::<_, bool>(daily_key(target, today)).unwrap());
+    assert_eq!(chat_state_keys(&mut conn, other).unwrap().len(), 3, "Other chats keep their state");
+    assert!(conn.sismember::<_, _, bool>(key::TG_WHITELIST_WORD_KEY, "hello").unwrap(), "Global state is kept");
+    assert_eq!(reset_chat(&mut conn, target).unwrap(), 0);
+}
+
+#[tokio::test]
+#[serial]
+async fn resetchat_from_a_dm_needs_an_admin_of_the_target_chat() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let (api_url, sent) = start_file_bot_api("");
+    let bot = Bot::new("TOKEN").set_api_url(api_url);
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let target = -5021i64;
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, target), field::NAME, "victim").unwrap();
+    let reset = |user_id: u64| {
+        let command = make_message(user_id as i64, user_id, "user", "/resetchat -5021", 1);
+        handle_admin_command(bot.clone(), command, AdminCommand::ResetChat { chat: target.to_string() })
+    };
+
+    reset(5022).await.expect("resetchat failed");
+    assert_eq!(sent.lock().unwrap().last().map(String::as_str), Some("You are not an admin of chat -5021."));
+
+    let _: () = conn.sadd(format!("{}{}", 5023, suffix::BOT_CHATS), target).unwrap();
+    reset(5023).await.expect("resetchat failed");
+    assert!(sent.lock().unwrap().last().unwrap().starts_with("Reset all bot state for chat victim?"));
+}
+
+#[tokio::test]
+#[serial]
+async fn stats_command_shows_chat_stats_or_list() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let user_id: u64 = 555;
+    let admin_chat_id: i64 = 9000;
+    let group_chat1: i64 = 9001;
+    let group_chat2: i64 = 9002;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.sadd(format!("{}{}", user_id, suffix::ADMIN_CHATS), admin_chat_id).unwrap();
+    let _: () = conn.sadd(format!("{}{}{}", key::ADMIN_PREFIX, admin_chat_id, suffix::MODERATED_CHATS), group_chat1).unwrap();
+    let _: () = conn.sadd(format!("{}{}{}", key::ADMIN_PREFIX, admin_chat_id, suffix::MODERATED_CHATS), group_chat2).unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, group_chat1), field::NAME, "GroupChat1").unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, group_chat1), field::SPAM_COUNT, 5).unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, group_chat1), field::ADMIN_CHAT, admin_chat_id).unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, group_chat2), field::NAME, "GroupChat2").unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, group_chat2), field::SPAM_COUNT, 3).unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, group_chat2), field::ADMIN_CHAT, admin_chat_id).unwrap();
+
+    let bot = Bot::new("DUMMY");
+    let msg1 = make_message(group_chat1, user_id, "tester", "/stats", 1);
+    let res1 = handle_admin_command(bot.clone(), msg1, AdminCommand::Stats { args: String::new() }).await;
+    assert!(res1.is_err());
+    let stats1: HashMap<String, String> = conn.hgetall(format!("{}{}", key::TG_CHATS_PREFIX, group_chat1)).unwrap();
+    assert!(stats1.get(field::NAME).is_some() && stats1.get(field::ADMIN_CHAT).is_some());
+    assert_eq!(stats1.get(field::SPAM_COUNT), Some(&"5".to_string()));
+
+    // 3. Admin chat: user sends /stats in the admin control chat
+    let msg2 = make_message(admin_chat_id, user_id, "tester", "/stats", 2);
+    let res2 = handle_admin_command(bot, msg2, AdminCommand::Stats { args: String::new() }).await;
+    assert!(res2.is_err());
+    let moderated: Vec<i64> = conn.smembers(format!("{}{}{}", key::ADMIN_PREFIX, admin_chat_id, suffix::MODERATED_CHATS)).unwrap();
+    assert_eq!(moderated.len(), 2);
+    assert!(moderated.contains(&group_chat1) && moderated.contains(&group_chat2));
+    let name1: String = conn.hget(format!("{}{}", key::TG_CHATS_PREFIX, group_chat1), field::NAME).unwrap();
+    let name2: String = conn.hget(format!("{}{}", key::TG_CHATS_PREFIX, group_chat2), field::NAME).unwrap();
+    assert_eq!(name1, "GroupChat1");
+    assert_eq!(name2, "GroupChat2");
+}
+
+#[tokio::test]
+#[serial]
+async fn addregex_command_parses_and_writes_rule() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let symbol = "TESTSYM";
+    
+    let file_path = PathBuf::from(format!("/etc/rspamd/lua.local.d/telegram_regex_{}.lua", symbol));
+    let _ = fs::remove_file(&file_path);
+
+    let bot = Bot::new("DUMMY");
+
+    // Invalid: two parts → usage error, no file
+    let bad = format!("{}|{}", symbol, "[0-9]+");
+    let msg1 = make_message(1, 999, "t", &format!("/addregex {}", bad), 1);
+    let _ = handle_admin_command(bot.clone(), msg1, AdminCommand::AddRegex { pattern: bad }).await;
+    assert!(!file_path.exists(), "No file for bad input");
+
+    // Valid: three parts → file created
+    let good = format!("{}|{}|{}", symbol, "[0-9]+", 5);
+    let msg2 = make_message(1, 999, "t", &format!("/addregex {}", good), 2);
+    let _ = handle_admin_command(bot, msg2, AdminCommand::AddRegex { pattern: good }).await;
+    // now the file should exist
+    assert!(file_path.exists(), "File should be created for valid input");
+
+    let contents = fs::read_to_string(&file_path).unwrap();
+    assert!(contents.contains(&format!("config['regexp']['{}']", symbol)));
+    assert!(contents.contains("re = '[0-9]+'"));
+    assert!(contents.contains("score = 5"));
+
+    // cleanup
+    let _ = fs::remove_file(&file_path);
+
+}
+#[tokio::test]
+#[serial]
+async fn whitelist_command_adds_entries() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 6006;
+    let user_id: u64 = 101;
+    let bot = Bot::new("DUMMY");
+    let msg_user = make_message(chat_id, user_id, "tester", "/whitelist user|add|101", 1);
+    let _ = handle_admin_command(bot.clone(), msg_user, AdminCommand::Whitelist { pattern: "user|add|101".into() }).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let has_user: bool = conn.sismember(key::TG_WHITELIST_USER_KEY, "101").unwrap();
+    assert!(has_user, "User should be in whitelist set");
+
+    let msg_word = make_message(chat_id, user_id, "tester", "/whitelist word|add|hello", 2);
+    let _ = handle_admin_command(bot, msg_word, AdminCommand::Whitelist { pattern: "word|add|hello".into() }).await;
+    let words: Vec<String> = conn.smembers(key::TG_WHITELIST_WORD_KEY).unwrap();
+    assert!(words.contains(&"hello".to_string()));
+}
+
+#[tokio::test]
+#[serial]
+async fn blacklist_command_adds_entries() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 7007;
+    let user_id: u64 = 202;
+    let bot = Bot::new("DUMMY");
+    let msg_user = make_message(chat_id, user_id, "tester", "/blacklist user|add|202", 1);
+    let _ = handle_admin_command(bot.clone(), msg_user, AdminCommand::Blacklist { pattern: "user|add|202".into() }).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let has_user: bool = conn.sismember(key::TG_BLACKLIST_USER_KEY, "202").unwrap();
+    assert!(has_user, "User should be in blacklist set");
+
+    let msg_word = make_message(chat_id, user_id, "tester", "/blacklist word|add|spam", 2);
+    let _ = handle_admin_command(bot, msg_word, AdminCommand::Blacklist { pattern: "word|add|spam".into() }).await;
+    let words: Vec<String> = conn.smembers(key::TG_BLACKLIST_WORD_KEY).unwrap();
+    assert!(words.contains(&"spam".to_string()));
+}
+
+#[tokio::test]
+#[serial]
+async fn chat_scoped_whitelist_word_is_ignored_in_other_chats() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 6008;
+    let other_chat: i64 = 6009;
+    let bot = Bot::new("DUMMY");
+    let msg = make_message(chat_id, 303, "tester", "/whitelist word|add|giveaway|6008", 1);
+    let _ = handle_admin_command(bot, msg, AdminCommand::Whitelist { pattern: format!("word|add|giveaway|{}", chat_id) }).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let scoped: bool = conn.sismember(lists::WHITELIST_WORDS.key(Some(ChatId(chat_id))), "giveaway").unwrap();
+    let global: bool = conn.sismember(key::TG_WHITELIST_WORD_KEY, "giveaway").unwrap();
+    assert!(scoped, "Word should be in the chat's own whitelist");
+    assert!(!global, "A chat-scoped entry must not reach the global whitelist");
+
+    let text = "Weekly giveaway thread";
+    let reply = scan_msg(make_message(chat_id, 304, "member", text, 2), text.into()).await.unwrap();
+    assert!(reply.symbols.contains_key(symbol::WHITELIST_WORD), "Whitelisted in its own chat");
+    let reply = scan_msg(make_message(other_chat, 304, "member", text, 3), text.into()).await.unwrap();
+    assert!(!reply.symbols.contains_key(symbol::WHITELIST_WORD), "Chat-scoped entries don't apply in other chats");
+
+    // Global entries still apply everywhere
+    let _: () = conn.sadd(key::TG_WHITELIST_WORD_KEY, "thread").unwrap();
+    let reply = scan_msg(make_message(other_chat, 304, "member", text, 4), text.into()).await.unwrap();
+    assert!(reply.symbols.contains_key(symbol::WHITELIST_WORD));
+}
+
+#[tokio::test]
+#[serial]
+async fn whitelisted_username_pattern_matches_users() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 6010;
+    let bot = Bot::new("DUMMY");
+    let msg = make_message(chat_id, 305, "tester", "/whitelist user|add|re:_official$", 1);
+    let _ = handle_admin_command(bot.clone(), msg, AdminCommand::Whitelist { pattern: "user|add|re:_official$".into() }).await;
+    let msg = make_message(chat_id, 305, "tester", "/whitelist user|add|re:(unclosed", 2);
+    let _ = handle_admin_command(bot, msg, AdminCommand::Whitelist { pattern: "user|add|re:(unclosed".into() }).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let users: HashSet<String> = conn.smembers(key::TG_WHITELIST_USER_KEY).unwrap();
+    assert_eq!(users, HashSet::from(["re:_official$".to_string()]), "Invalid patterns are rejected");
+    assert!(lists::Entry::User.is_valid("re:_official$"));
+    assert!(!lists::Entry::User.is_valid("re:(unclosed"));
+
+    let text = "Order status updates are posted here";
+    let reply = scan_msg(make_message(chat_id, 306, "acme_official", text, 3), text.into()).await.unwrap();
+    assert!(reply.symbols.contains_key(symbol::WHITELIST_USER), "Matching usernames are whitelisted");
+    let reply = scan_msg(make_message(chat_id, 307, "acme_officially", text, 4), text.into()).await.unwrap();
+    assert!(!reply.symbols.contains_key(symbol::WHITELIST_USER));
+
+    // Literal ids still match without a username
+    let _: () = conn.sadd(key::TG_WHITELIST_USER_KEY, "308").unwrap();
+    assert!(lists::WHITELIST_USERS.is_listed(&mut conn, ChatId(chat_id), "308", None).unwrap());
+    assert!(!lists::WHITELIST_USERS.is_listed(&mut conn, ChatId(chat_id), "309", Some("acme")).unwrap());
+}
+
+// ============================================================================
+// NEW MODULAR SYMBOL INTEGRATION TESTS
+// ============================================================================
+
+#[tokio::test]
+#[serial]
+async fn tg_first_fast_sets_symbol_for_quick_first_message() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8001;
+    let user_id = 1001;
+    let key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    
+    // Set user as newly joined (within 10 seconds)
+    let join_time = Utc::now().timestamp() - 5; // 5 seconds ago
+    let _: () = conn.hset(key.clone(), "join_time", join_time).unwrap();
+
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "newuser", "Hello everyone!", 1),
+        "Hello everyone!".into(),
+    ).await.ok().unwrap();
+
+    assert!(reply.symbols.contains_key(symbol::TG_FIRST_FAST), 
+        "Expected TG_FIRST_FAST for message within 10 seconds of joining");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_first_slow_sets_symbol_for_delayed_first_message() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8002;
+    let user_id = 1002;
+    let key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    
+    // Set user as joined long ago (more than 24 hours)
+    let join_time = Utc::now().timestamp() - 90000; // 25 hours ago
+    let _: () = conn.hset(key.clone(), "join_time", join_time).unwrap();
+
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "olduser", "Finally saying hello!", 1),
+        "Finally saying hello!".into(),
+    ).await.ok().unwrap();
+
+    assert!(reply.symbols.contains_key(symbol::TG_FIRST_SLOW), 
+        "Expected TG_FIRST_SLOW for first message after 24 hours of joining");
+}
+
+#[tokio::test]
+#[serial]
+async fn join_windows_are_configurable_per_chat() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8043;
+    let fast_user = 1045;
+    let slow_user = 1046;
+    let gate_key = format!("{}{}{}", key::TG_CHATS_PREFIX, chat_id, suffix::JOIN_GATE);
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.hset(&gate_key, join_gate::FIRST_FAST, 120).unwrap();
+    let _: () = conn.hset(&gate_key, join_gate::FIRST_SLOW, 3600).unwrap();
+
+    // 60 seconds is past the default 10s window but inside the chat's 120s one
+    let join_time = Utc::now().timestamp() - 60;
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, fast_user), field::JOIN_TIME, join_time).unwrap();
+    let reply = scan_msg(make_message(chat_id, fast_user, "fast", "Hi all", 1), "Hi all".into())
+        .await.expect("scan failed");
+    assert!(reply.symbols.contains_key(symbol::TG_FIRST_FAST), "Custom fast window should apply");
+
+    // Two hours is well under the default 24h but past the chat's 1h window
+    let join_time = Utc::now().timestamp() - 7200;
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, slow_user), field::JOIN_TIME, join_time).unwrap();
+    let reply = scan_msg(make_message(chat_id, slow_user, "slow", "Hi all", 2), "Hi all".into())
+        .await.expect("scan failed");
+    assert!(reply.symbols.contains_key(symbol::TG_FIRST_SLOW), "Custom slow window should apply");
+    assert!(!reply.symbols.contains_key(symbol::TG_FIRST_FAST));
+
+    // Other chats keep the defaults
+    let windows = join_windows(&mut conn, ChatId(chat_id + 1));
+    assert_eq!((windows.first_fast, windows.first_slow, windows.probation), (10, 86_400, 0));
+}
+
+#[tokio::test]
+#[serial]
+async fn probation_deletes_messages_from_new_members() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 8044;
+    let user_id: u64 = 1047;
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, chat_id);
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.hset(format!("{}{}", chat_key, suffix::JOIN_GATE), join_gate::PROBATION, 300).unwrap();
+    let _: () = conn.hset(&chat_key, format!("feat:{}", DRY_RUN_FEATURE), "1").unwrap();
+    let _: () = conn.hset(&user_key, field::JOIN_TIME, Utc::now().timestamp() - 30).unwrap();
+
+    let now = Utc::now().timestamp();
+    let remaining = probation_remaining(&mut conn, ChatId(chat_id), UserId(user_id), now).expect("User should be on probation");
+    assert!(remaining > 0 && remaining <= 270);
+    assert_eq!(probation_remaining(&mut conn, ChatId(chat_id), UserId(user_id), now + 300), None);
+
+    let res = handle_message(Bot::new("DUMMY"), make_message(chat_id, user_id, "newbie", "Hello!", 1)).await;
+    assert!(res.is_ok(), "Dry-run probation should not try to delete");
+    let alerts: Vec<String> = conn.lrange(format!("{}{}", chat_key, suffix::DRY_RUN_LOG), 0, -1).unwrap();
+    assert_eq!(alerts.len(), 1);
+    assert!(alerts[0].contains("probation"), "Alert should mention probation: {}", alerts[0]);
+
+    // Messages held back by probation don't count as the user's first message
+    let last_msg_time: Option<i64> = conn.hget(&user_key, field::LAST_MSG_TIME).unwrap();
+    assert_eq!(last_msg_time, None);
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_silent_sets_symbol_for_dormant_user() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8003;
+    let user_id = 1003;
+    let key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    
+    // Set user's last message time to 30+ days ago
+    let last_msg_time = Utc::now().timestamp() - 2600000; // 30+ days ago
+    let _: () = conn.hset(key.clone(), "last_msg_time", last_msg_time).unwrap();
 
     let reply = scan_msg(
         make_message(chat_id, user_id, "dormantuser", "I'm back!", 1),
         "I'm back!".into(),
     ).await.ok().unwrap();
 
-    assert!(reply.symbols.contains_key(symbol::TG_SILENT), 
-        "Expected TG_SILENT for dormant user returning after 30 days");
+    assert!(reply.symbols.contains_key(symbol::TG_SILENT), 
+        "Expected TG_SILENT for dormant user returning after 30 days");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_link_spam_sets_symbol_for_excessive_links() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8004;
+    let user_id = 1004;
+
+    // Message with 4 links (above threshold of 3)
+    let spam_text = "Check out these amazing sites: https://example1.com https://example2.com https://example3.com https://example4.com";
+
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "linkuser", spam_text, 1),
+        spam_text.into(),
+    ).await.ok().unwrap();
+
+    assert!(reply.symbols.contains_key(symbol::TG_LINK_SPAM), 
+        "Expected TG_LINK_SPAM for message with excessive links");
+}
+
+#[tokio::test]
+#[serial]
+async fn trusted_domains_are_not_counted_as_link_spam() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8041;
+    let user_id = 1042;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.sadd(key::TG_TRUSTED_DOMAINS_KEY, "docs.rs").unwrap();
+
+    let trusted_text = "Docs: https://docs.rs/a https://docs.rs/b https://www.docs.rs/c https://docs.rs/d";
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "linkuser", trusted_text, 1),
+        trusted_text.into(),
+    ).await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_LINK_SPAM),
+        "Links to a trusted domain should not count towards TG_LINK_SPAM");
+
+    let mixed_text = "Links: https://example1.com https://docs.example2.org https://example3.net https://example4.io";
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "linkuser", mixed_text, 2),
+        mixed_text.into(),
+    ).await.expect("scan failed");
+    assert!(reply.symbols.contains_key(symbol::TG_LINK_SPAM),
+        "Untrusted links should still trigger TG_LINK_SPAM");
+}
+
+#[tokio::test]
+#[serial]
+async fn good_standing_user_avoids_suspicious_on_borderline_message() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8043;
+    let regular_id = 1045;
+    let established_id = 1046;
+    let text = "Does anyone know when the next meetup is?";
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    // Both users sit just above the suspicious threshold of 10
+    for user_id in [regular_id, established_id] {
+        let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, user_id), "rep", 11).unwrap();
+    }
+    let _: () = conn.hset(format!("{}{}", key::TG_REPUTATION_USER_PREFIX, established_id), "good", 20).unwrap();
+    let tier = good_standing::tier(20).expect("20 net good reputation should reach a tier");
+
+    let reply = scan_msg(make_message(chat_id, regular_id, "regular", text, 1), text.to_string())
+        .await.expect("scan failed");
+    assert!(reply.symbols.contains_key(symbol::TG_SUSPICIOUS), "A regular user over the threshold is suspicious");
+    assert!(!reply.symbols.contains_key(symbol::TG_GOOD_STANDING));
+
+    let reply = scan_msg(make_message(chat_id, established_id, "established", text, 2), text.to_string())
+        .await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_SUSPICIOUS),
+        "A user in good standing gets headroom above the suspicious threshold");
+    let standing = reply.symbols.get(symbol::TG_GOOD_STANDING)
+        .expect("Message from a user in good standing should carry TG_GOOD_STANDING");
+    assert_eq!(standing.score, tier.score);
+    assert!(reply.score <= tier.score, "Score should be reduced, got {}", reply.score);
+}
+
+#[tokio::test]
+#[serial]
+async fn trusted_user_messages_get_score_reduction() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8042;
+    let trusted_id = 1043;
+    let other_id = 1044;
+    let spam_text = "FREE MONEY!!! Join t.me/joinchat/abc and call +1 555 123 4567 now";
+
+    let trust_manager = TrustManager::new("redis://127.0.0.1/").unwrap();
+    trust_manager.trust_user(UserId(trusted_id), None).await.unwrap();
+    assert!(trust_manager.is_trusted_user(UserId(trusted_id)).await.unwrap());
+    assert!(!trust_manager.is_trusted_user(UserId(other_id)).await.unwrap());
+
+    let reply = scan_msg(make_message(chat_id, trusted_id, "trusted", spam_text, 1), spam_text.to_string())
+        .await.expect("scan failed");
+    let trusted_symbol = reply.symbols.get(symbol::TG_TRUSTED_USER)
+        .expect("Trusted user's message should carry TG_TRUSTED_USER");
+    assert_eq!(trusted_symbol.score, trusted_user::SCORE_REDUCTION);
+    assert!(reply.symbols.contains_key(symbol::TG_INVITE_LINK), "Content rules still run for trusted users");
+    assert!(reply.score <= trusted_user::SCORE_REDUCTION, "Score should be reduced, got {}", reply.score);
+
+    let reply = scan_msg(make_message(chat_id, other_id, "other", spam_text, 2), spam_text.to_string())
+        .await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_TRUSTED_USER));
+
+    // Untrusting and expired trust both stop the reduction
+    assert!(trust_manager.untrust_user(UserId(trusted_id)).await.unwrap());
+    trust_manager.trust_user(UserId(other_id), Some(-1)).await.unwrap();
+    assert!(!trust_manager.is_trusted_user(UserId(trusted_id)).await.unwrap());
+    assert!(!trust_manager.is_trusted_user(UserId(other_id)).await.unwrap());
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_mentions_sets_symbol_for_excessive_mentions() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8005;
+    let user_id = 1005;
+
+    // Message with 6 mentions (above threshold of 5)
+    let spam_text = "@user1 @user2 @user3 @user4 @user5 @user6 Hey everyone!";
+
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "mentionuser", spam_text, 1),
+        spam_text.into(),
+    ).await.ok().unwrap();
+
+    assert!(reply.symbols.contains_key(symbol::TG_MENTIONS), 
+        "Expected TG_MENTIONS for message with excessive user mentions");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_caps_sets_symbol_for_excessive_capitalization() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8006;
+    let user_id = 1006;
+
+    // Message with 80% caps (above threshold of 70%)
+    let spam_text = "HELLO EVERYONE THIS IS VERY IMPORTANT NEWS YOU MUST READ THIS NOW!";
+
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "capsuser", spam_text, 1),
+        spam_text.into(),
+    ).await.ok().unwrap();
+
+    assert!(reply.symbols.contains_key(symbol::TG_CAPS), 
+        "Expected TG_CAPS for message with excessive capitalization");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_caps_counts_non_ascii_uppercase() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8016;
+    let user_id = 1016;
+
+    // Cyrillic shouting has no ASCII letters at all
+    let spam_text = "ВНИМАНИЕ ВСЕМ СРОЧНО ЧИТАЙТЕ ЭТО СООБЩЕНИЕ СЕЙЧАС!";
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "capsuser", spam_text, 1),
+        spam_text.into(),
+    ).await.unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_CAPS),
+        "Expected TG_CAPS for an all-caps Cyrillic message");
+
+    // CJK has no case and must not count towards the ratio
+    let cjk_text = "大家好，今天我们在公园见面，然后一起去吃午饭。";
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "capsuser", cjk_text, 2),
+        cjk_text.into(),
+    ).await.unwrap();
+    assert!(!reply.symbols.contains_key(symbol::TG_CAPS),
+        "CJK text must not trip TG_CAPS");
+}
+
+#[tokio::test]
+#[serial]
+async fn content_checks_skip_messages_under_the_length_floor() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8017;
+    let user_id = 1017;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+
+    // A 3-character all-caps word sits under the default floor
+    let reply = scan_msg(make_message(chat_id, user_id, "capsuser", "WOW", 1), "WOW".into())
+        .await
+        .unwrap();
+    assert!(!reply.symbols.contains_key(symbol::TG_CAPS), "Short all-caps words must not trip TG_CAPS");
+
+    // Raising the floor skips caps detection for longer messages too, but
+    // count-based checks like link spam keep running
+    let _: () = conn.hset(key::TG_THRESHOLDS_KEY, threshold::MIN_CONTENT_LENGTH, 500.0).unwrap();
+    let shouting = "HELLO EVERYONE THIS IS VERY IMPORTANT NEWS http://a.com http://b.com http://c.com http://d.com";
+    let reply = scan_msg(make_message(chat_id, user_id, "capsuser", shouting, 2), shouting.into())
+        .await
+        .unwrap();
+    assert!(!reply.symbols.contains_key(symbol::TG_CAPS), "TG_CAPS is skipped under the configured floor");
+    assert!(reply.symbols.contains_key(symbol::TG_LINK_SPAM), "Link spam detection ignores the floor");
+
+    let _: () = conn.hdel(key::TG_THRESHOLDS_KEY, threshold::MIN_CONTENT_LENGTH).unwrap();
+    let reply = scan_msg(make_message(chat_id, user_id, "capsuser", shouting, 3), shouting.into())
+        .await
+        .unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_CAPS), "TG_CAPS fires again with the default floor");
+}
+
+#[test]
+fn case_counts_ignores_uncased_scripts() {
+    let cyrillic = case_counts("ПРИВЕТ мир");
+    assert_eq!((cyrillic.upper, cyrillic.lower), (6, 3));
+
+    let accented = case_counts("ÉCOUTEZ ÇA");
+    assert_eq!((accented.upper, accented.lower), (9, 0));
+
+    let cjk = case_counts("你好世界");
+    assert_eq!(cjk.letters(), 0);
+    assert_eq!(cjk.caps_ratio(), 0.0);
+
+    let mixed = case_counts("你好世界 HI");
+    assert_eq!(mixed.caps_ratio(), 1.0, "Only cased letters make up the ratio");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_emoji_spam_sets_symbol_for_excessive_emoji() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8007;
+    let user_id = 1007;
+
+    // Message with 12 emojis (above threshold of 10)
+    let spam_text = "Hello! 😀😃😄😁😆😅😂🤣😊😇🙂🙃";
+
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "emojiuser", spam_text, 1),
+        spam_text.into(),
+    ).await.expect("scan_msg should succeed");
+
+    assert!(reply.symbols.contains_key(symbol::TG_EMOJI_SPAM), 
+        "Expected TG_EMOJI_SPAM for message with excessive emoji usage");
+}
+
+#[test]
+fn count_emoji_counts_sequences_as_displayed() {
+    assert_eq!(count_emoji("Go 🇩🇪!"), 1, "A flag is two regional indicators");
+    assert_eq!(count_emoji("🇺🇦🇵🇱"), 2);
+    assert_eq!(count_emoji("👨‍👩‍👧‍👦"), 1, "A family is one ZWJ sequence");
+    assert_eq!(count_emoji("👍🏽 ❤️"), 2, "Skin tones and variation selectors belong to their emoji");
+    assert_eq!(count_emoji("🪿 and 🫠"), 2, "Symbols and Pictographs Extended-A");
+    assert_eq!(count_emoji("plain text, 100%"), 0);
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_char_flood_sets_symbol_for_stretched_text() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8030;
+    let user_id = 1030;
+
+    let enthusiastic = "Great job everyone, see you all tomorrow!!!";
+    let msg = make_message(chat_id, user_id, "fan", enthusiastic, 1);
+    let reply = scan_msg(msg, enthusiastic.to_string()).await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_CHAR_FLOOD),
+        "Enthusiastic but normal message should not trigger TG_CHAR_FLOOD");
+
+    let stretched = "buyyyyy nowwwww!!!!!";
+    let msg = make_message(chat_id, user_id, "fan", stretched, 2);
+    let reply = scan_msg(msg, stretched.to_string()).await.expect("scan failed");
+    assert!(reply.symbols.contains_key(symbol::TG_CHAR_FLOOD),
+        "Stretched message should trigger TG_CHAR_FLOOD");
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, chat_id), "feat:char_flood", "0").unwrap();
+    let msg = make_message(chat_id, user_id, "fan", stretched, 3);
+    let reply = scan_msg(msg, stretched.to_string()).await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_CHAR_FLOOD),
+        "TG_CHAR_FLOOD should respect the feature toggle");
+}
+
+#[test]
+fn char_runs_measures_repeated_characters() {
+    let runs = char_runs("Soooo cool!!!");
+    assert_eq!(runs.longest, 4);
+    assert_eq!(runs.flooded, 7);
+    assert_eq!(runs.total, 12);
+
+    assert_eq!(char_runs("NOOooo").longest, 5, "ASCII letters compare case-insensitively");
+    assert_eq!(char_runs("a   a").longest, 1, "Whitespace breaks runs");
+    assert!(!char_runs("").is_flood(4.0, 0.3));
+
+    // No single run is too long, but most of the message is stretched
+    let heavy = char_runs("heeey gooood fooood yummm meee");
+    assert!(heavy.longest <= 4);
+    assert!(heavy.is_flood(4.0, 0.3));
+}
+
+#[tokio::test]
+#[serial]
+async fn setthreshold_raises_emoji_limit() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8008;
+    let user_id = 1008;
+    let spam_text = "Hello! 😀😃😄😁😆😅😂🤣😊😇🙂🙃";
+
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "emojiuser", spam_text, 1),
+        spam_text.into(),
+    ).await.expect("scan_msg should succeed");
+    assert!(reply.symbols.contains_key(symbol::TG_EMOJI_SPAM),
+        "Expected TG_EMOJI_SPAM with the default emoji limit");
+
+    let bot = Bot::new("DUMMY");
+    let msg = make_message(chat_id, user_id, "emojiuser", "/setthreshold emoji_max|20", 2);
+    let res = handle_admin_command(
+        bot,
+        msg,
+        AdminCommand::SetThreshold { args: "emoji_max|20".into() },
+    ).await;
+    assert!(res.is_err(), "Expected dummy send_message to fail");
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let stored: f64 = conn.hget(key::TG_THRESHOLDS_KEY, threshold::EMOJI_MAX).unwrap();
+    assert_eq!(stored, 20.0);
+
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "emojiuser", spam_text, 3),
+        spam_text.into(),
+    ).await.expect("scan_msg should succeed");
+    assert!(!reply.symbols.contains_key(symbol::TG_EMOJI_SPAM),
+        "TG_EMOJI_SPAM should not fire after raising emoji_max");
+}
+
+#[tokio::test]
+#[serial]
+async fn allowscript_flags_only_disallowed_scripts() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8009;
+    let user_id = 1009;
+    let russian = "Привет всем, как дела сегодня?";
+    let thai = "สวัสดีครับ ราคาพิเศษวันนี้เท่านั้น";
+
+    // No allowlist yet: nothing is restricted
+    let reply = scan_msg(make_message(chat_id, user_id, "scriptuser", thai, 1), thai.into())
+        .await.expect("scan_msg should succeed");
+    assert!(!reply.symbols.contains_key(symbol::TG_FOREIGN_SCRIPT),
+        "Without an allowlist no script should be flagged");
+
+    let bot = Bot::new("DUMMY");
+    let msg = make_message(chat_id, user_id, "scriptuser", "/allowscript", 2);
+    let res = handle_admin_command(
+        bot,
+        msg,
+        AdminCommand::AllowScript { args: format!("{}|cyrillic", chat_id) },
+    ).await;
+    assert!(res.is_err(), "Expected dummy send_message to fail");
+
+    let reply = scan_msg(make_message(chat_id, user_id, "scriptuser", russian, 3), russian.into())
+        .await.expect("scan_msg should succeed");
+    assert!(!reply.symbols.contains_key(symbol::TG_FOREIGN_SCRIPT),
+        "Allowed script should not be flagged");
+
+    let reply = scan_msg(make_message(chat_id, user_id, "scriptuser", thai, 4), thai.into())
+        .await.expect("scan_msg should succeed");
+    assert!(reply.symbols.contains_key(symbol::TG_FOREIGN_SCRIPT),
+        "Disallowed script should be flagged");
+}
+
+#[test]
+fn dominant_script_detects_common_scripts() {
+    assert_eq!(dominant_script("Hello world"), Some("latin"));
+    assert_eq!(dominant_script("Привет, hi"), Some("cyrillic"));
+    assert_eq!(dominant_script("สวัสดีครับ"), Some("thai"));
+    assert_eq!(dominant_script("12345 !!!"), None);
+}
+
+#[tokio::test]
+#[serial]
+async fn setaction_overrides_score_thresholds_per_chat() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8010;
+    let other_chat = 8011;
+    let user_id = 1010;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+
+    // Defaults apply until the chat configures its own map
+    assert_eq!(resolve_action(&mut conn, ChatId(chat_id), 6.0), action::WARN);
+    assert_eq!(resolve_action(&mut conn, ChatId(chat_id), 12.0), action::DELETE);
+
+    let bot = Bot::new("DUMMY");
+    let msg = make_message(chat_id, user_id, "admin", "/setaction 8010|8|ban", 1);
+    let res = handle_admin_command(
+        bot,
+        msg,
+        AdminCommand::SetAction { args: format!("{}|8|ban", chat_id) },
+    ).await;
+    assert!(res.is_err(), "Expected dummy send_message to fail");
+
+    let stored: f64 = conn
+        .hget(format!("{}{}{}", key::TG_CHATS_PREFIX, chat_id, suffix::ACTIONS), action::BAN)
+        .unwrap();
+    assert_eq!(stored, 8.0);
+
+    assert_eq!(resolve_action(&mut conn, ChatId(chat_id), 7.9), action::WARN);
+    assert_eq!(resolve_action(&mut conn, ChatId(chat_id), 8.0), action::BAN,
+        "Ban should fire at the configured threshold");
+    assert_eq!(resolve_action(&mut conn, ChatId(chat_id), 12.0), action::BAN);
+    assert_eq!(resolve_action(&mut conn, ChatId(other_chat), 8.0), action::WARN,
+        "Other chats keep the default map");
+    assert_eq!(resolve_action(&mut conn, ChatId(chat_id), 1.0), action::NONE);
+
+    // A stranger's DM can't reconfigure the chat
+    let stranger = make_message(1011, 1011, "stranger", "/setaction 8010|0|ban", 2);
+    let res = handle_admin_command(
+        Bot::new("DUMMY"),
+        stranger,
+        AdminCommand::SetAction { args: format!("{}|0|ban", chat_id) },
+    ).await;
+    assert!(res.is_err(), "Expected dummy send_message to fail");
+    assert_eq!(resolve_action(&mut conn, ChatId(chat_id), 1.0), action::NONE, "The action map is unchanged");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_forwarded_sets_symbol_only_for_forwards() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8020;
+    let user_id = 1020;
+    let text = "Check out this channel post";
+
+    let plain = make_message(chat_id, user_id, "reader", text, 1);
+    let reply = scan_msg(plain.clone(), text.to_string()).await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_FORWARDED),
+        "Plain message should not trigger TG_FORWARDED");
+
+    let forwarded = make_forwarded_message(chat_id, user_id, "reader", text, 2, make_channel_origin(-100500));
+    let reply = scan_msg(forwarded, text.to_string()).await.expect("scan failed");
+    let sym = reply.symbols.get(symbol::TG_FORWARDED).expect("Expected TG_FORWARDED for forwarded message");
+    assert_eq!(sym.options.as_deref(), Some(&[forward::ORIGIN_CHANNEL.to_string()][..]));
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, chat_id), format!("feat:{}", forward::FEATURE), "0").unwrap();
+    let forwarded = make_forwarded_message(chat_id, user_id, "reader", text, 3, make_channel_origin(-100500));
+    let reply = scan_msg(forwarded, text.to_string()).await.expect("scan failed");
+    assert!(!reply.symbols.contains_key(symbol::TG_FORWARDED),
+        "TG_FORWARDED should respect the feature toggle");
+}
+
+#[tokio::test]
+#[serial]
+async fn channel_forward_from_new_user_raises_score() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8021;
+    let user_id = 1021;
+    let user_key = format!("{}{}", key::TG_USERS_PREFIX, user_id);
+    let chat_key = format!("{}{}", key::TG_CHATS_PREFIX, chat_id);
+    let text = "Join our channel for free signals";
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+
+    let plain = make_message(chat_id, user_id, "newbie", text, 1);
+    let channel_forward = make_forwarded_message(chat_id, user_id, "newbie", text, 2, make_channel_origin(-100501));
+    let user_forward = make_forwarded_message(
+        chat_id, user_id, "newbie", text, 3,
+        MessageOrigin::User { date: Utc::now(), sender_user: make_user(2021, "friend") },
+    );
+
+    let _: () = conn.hset(&user_key, field::MSG_COUNT, 1).unwrap();
+    assert_eq!(forward_penalty(&mut conn, &channel_forward, false), forward::CHANNEL_FORWARD_PENALTY);
+    assert_eq!(forward_penalty(&mut conn, &plain, false), 0.0, "Plain messages are not penalized");
+    assert_eq!(forward_penalty(&mut conn, &user_forward, false), 0.0, "Only channel forwards are penalized");
+    assert_eq!(forward_penalty(&mut conn, &channel_forward, true), 0.0, "Good reputation skips the penalty");
+
+    let _: () = conn.hset(&user_key, field::MSG_COUNT, forward::NEW_USER_MAX_MESSAGES + 1).unwrap();
+    assert_eq!(forward_penalty(&mut conn, &channel_forward, false), 0.0, "Established users are not penalized");
+
+    // End to end: report through dry-run so a warning at the penalty score is observable
+    flush_redis();
+    let _: () = conn.hset(&chat_key, format!("feat:{}", DRY_RUN_FEATURE), "1").unwrap();
+    let _: () = conn.hset(format!("{}{}", chat_key, suffix::ACTIONS), action::WARN, forward::CHANNEL_FORWARD_PENALTY).unwrap();
+    let log_key = format!("{}{}", chat_key, suffix::DRY_RUN_LOG);
+
+    let res = handle_message(Bot::new("DUMMY"), plain).await;
+    assert!(res.is_ok());
+    let alerts: Vec<String> = conn.lrange(&log_key, 0, -1).unwrap();
+    assert!(alerts.is_empty(), "Plain first message should not be actioned: {:?}", alerts);
+
+    let res = handle_message(Bot::new("DUMMY"), channel_forward).await;
+    assert!(res.is_ok());
+    let alerts: Vec<String> = conn.lrange(&log_key, 0, -1).unwrap();
+    assert_eq!(alerts.len(), 1, "Channel forward from a new user should be actioned");
+    assert!(alerts[0].contains(action::WARN) && alerts[0].contains(symbol::TG_FORWARDED),
+        "Alert should name the action and symbol: {}", alerts[0]);
+
+    let msg_count: i64 = conn.hget(&user_key, field::MSG_COUNT).unwrap();
+    assert_eq!(msg_count, 2, "Every handled message should be counted");
+}
+
+#[tokio::test]
+#[serial]
+async fn forwards_policy_allow_keeps_every_forward() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let now = Utc::now().timestamp();
+    let forwarded = make_forwarded_message(8022, 1022, "newbie", "Fresh signals", 1, make_channel_origin(-100502));
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, 1022), field::JOIN_TIME, now - 10).unwrap();
+
+    assert_eq!(forward_policy(&mut conn, ChatId(8022)), ForwardPolicy::Allow, "Chats allow forwards by default");
+    assert_eq!(forward_blocked(&mut conn, &forwarded, now), None);
+    set_forward_policy(&mut conn, ChatId(8022), Some(ForwardPolicy::Allow)).unwrap();
+    assert_eq!(forward_blocked(&mut conn, &forwarded, now), None);
 }
 
 #[tokio::test]
 #[serial]
-async fn tg_link_spam_sets_symbol_for_excessive_links() {
+async fn forwards_policy_restrict_new_only_stops_recent_joiners() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let chat_id = 8004;
-    let user_id = 1004;
-
-    // Message with 4 links (above threshold of 3)
-    let spam_text = "Check out these amazing sites: https://example1.com https://example2.com https://example3.com https://example4.com";
-
-    let reply = scan_msg(
-        make_message(chat_id, user_id, "linkuser", spam_text, 1),
-        spam_text.into(),
-    ).await.ok().unwrap();
-
-    assert!(reply.symbols.contains_key(symbol::TG_LINK_SPAM), 
-        "Expected TG_LINK_SPAM for message with excessive links");
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let chat_id = 8023;
+    let now = Utc::now().timestamp();
+    let _: () = conn.hset(format!("{}{}", key::TG_CHATS_PREFIX, chat_id), field::FORWARDS, "restrict_new").unwrap();
+    assert_eq!(forward_policy(&mut conn, ChatId(chat_id)), ForwardPolicy::RestrictNew);
+    let window = join_windows(&mut conn, ChatId(chat_id)).new_user_forward;
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, 1023), field::JOIN_TIME, now - 10).unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, 1024), field::JOIN_TIME, now - window - 10).unwrap();
+
+    let newbie = make_forwarded_message(chat_id, 1023, "newbie", "Fresh signals", 1, make_channel_origin(-100503));
+    let regular = make_forwarded_message(chat_id, 1024, "regular", "Fresh signals", 2, make_channel_origin(-100503));
+    assert_eq!(forward_blocked(&mut conn, &newbie, now), Some(ForwardPolicy::RestrictNew));
+    assert_eq!(forward_blocked(&mut conn, &regular, now), None, "Established members may forward");
+    assert_eq!(forward_blocked(&mut conn, &make_message(chat_id, 1023, "newbie", "Hi all", 3), now), None, "Only forwards are restricted");
+
+    let (bot, calls) = telegram_stand_in();
+    handle_message(bot, newbie).await.expect("handle_message failed");
+    let calls = calls.lock().unwrap().clone();
+    let (_, deleted) = calls.iter().find(|(method, _)| method == "deletemessage").expect("forward should be deleted");
+    assert_eq!(deleted["message_id"], json!(1));
 }
 
 #[tokio::test]
 #[serial]
-async fn tg_mentions_sets_symbol_for_excessive_mentions() {
+async fn forwards_policy_deny_deletes_every_forward() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let chat_id = 8005;
-    let user_id = 1005;
-
-    // Message with 6 mentions (above threshold of 5)
-    let spam_text = "@user1 @user2 @user3 @user4 @user5 @user6 Hey everyone!";
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let chat_id = 8024;
+    let now = Utc::now().timestamp();
+    set_forward_policy(&mut conn, ChatId(chat_id), Some(ForwardPolicy::Deny)).unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, 1025), field::JOIN_TIME, now - 365 * 86_400).unwrap();
+    let forwarded = make_forwarded_message(
+        chat_id, 1025, "regular", "Look at this", 4,
+        MessageOrigin::User { date: Utc::now(), sender_user: make_user(2025, "friend") },
+    );
+    assert_eq!(forward_blocked(&mut conn, &forwarded, now), Some(ForwardPolicy::Deny));
 
-    let reply = scan_msg(
-        make_message(chat_id, user_id, "mentionuser", spam_text, 1),
-        spam_text.into(),
-    ).await.ok().unwrap();
+    let (bot, calls) = telegram_stand_in();
+    handle_message(bot, forwarded).await.expect("handle_message failed");
+    let calls = calls.lock().unwrap().clone();
+    let methods: Vec<&str> = calls.iter().map(|(method, _)| method.as_str()).collect();
+    assert_eq!(methods, vec!["deletemessage"], "Denied forwards are deleted without a scan");
 
-    assert!(reply.symbols.contains_key(symbol::TG_MENTIONS), 
-        "Expected TG_MENTIONS for message with excessive user mentions");
+    // Going back to the default allows them again
+    set_forward_policy(&mut conn, ChatId(chat_id), None).unwrap();
+    assert_eq!(forward_policy(&mut conn, ChatId(chat_id)), ForwardPolicy::Allow);
 }
 
 #[tokio::test]
 #[serial]
-async fn tg_caps_sets_symbol_for_excessive_capitalization() {
+async fn weighted_symbols_compound_reputation() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let chat_id = 8006;
-    let user_id = 1006;
+    let chat_id = 8040;
+    let single_user = 1040;
+    let multi_user = 1041;
+    let bad = |conn: &mut redis::Connection, user_id: u64| -> i64 {
+        conn.hget(format!("{}{}", key::TG_REPUTATION_USER_PREFIX, user_id), "bad").unwrap_or(0)
+    };
 
-    // Message with 80% caps (above threshold of 70%)
-    let spam_text = "HELLO EVERYONE THIS IS VERY IMPORTANT NEWS YOU MUST READ THIS NOW!";
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
 
-    let reply = scan_msg(
-        make_message(chat_id, user_id, "capsuser", spam_text, 1),
-        spam_text.into(),
-    ).await.ok().unwrap();
+    let single_text = "Call me at +1 555 123 4567";
+    let reply = scan_msg(make_message(chat_id, single_user, "single", single_text, 1), single_text.to_string())
+        .await.expect("scan failed");
+    assert_eq!(reputation_delta(&mut conn, &reply), 1);
+    assert_eq!(bad(&mut conn, single_user), 1, "One weak symbol adds one weight");
+
+    let multi_text = "Join t.me/joinchat/abc now, call +1 555 123 4567, details at bit.ly/deal";
+    let reply = scan_msg(make_message(chat_id, multi_user, "multi", multi_text, 2), multi_text.to_string())
+        .await.expect("scan failed");
+    for sym in [symbol::TG_INVITE_LINK, symbol::TG_PHONE_SPAM, symbol::TG_SHORTENER] {
+        assert!(reply.symbols.contains_key(sym), "Expected {}", sym);
+    }
+    assert_eq!(bad(&mut conn, multi_user), 3, "Several weak symbols should compound");
 
-    assert!(reply.symbols.contains_key(symbol::TG_CAPS), 
-        "Expected TG_CAPS for message with excessive capitalization");
+    // Admin overrides take precedence over the defaults
+    let _: () = conn.hset(symbol_weight::WEIGHTS_KEY, symbol::TG_PHONE_SPAM, 4).unwrap();
+    let _ = scan_msg(make_message(chat_id, single_user, "single", single_text, 3), single_text.to_string())
+        .await.expect("scan failed");
+    assert_eq!(bad(&mut conn, single_user), 5);
 }
 
 #[tokio::test]
 #[serial]
-async fn tg_emoji_spam_sets_symbol_for_excessive_emoji() {
+async fn preview_scan_lists_symbols_without_touching_redis() {
     flush_redis();
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    let chat_id = 8007;
-    let user_id = 1007;
-
-    // Message with 12 emojis (above threshold of 10)
-    let spam_text = "Hello! 😀😃😄😁😆😅😂🤣😊😇🙂🙃";
+    let chat_id = 8045;
+    let user_id = 1048;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, user_id), field::REP, 3).unwrap();
+
+    let snapshot = |conn: &mut redis::Connection| -> Vec<(String, Vec<u8>)> {
+        let mut keys: Vec<String> = conn.keys("*").unwrap();
+        keys.sort();
+        keys.into_iter()
+            .map(|k| {
+                let dump: Vec<u8> = redis::cmd("DUMP").arg(&k).query(conn).unwrap();
+                (k, dump)
+            })
+            .collect()
+    };
+    let before = snapshot(&mut conn);
 
-    let reply = scan_msg(
-        make_message(chat_id, user_id, "emojiuser", spam_text, 1),
-        spam_text.into(),
-    ).await.expect("scan_msg should succeed");
+    let text = "Join t.me/joinchat/abc now, call +1 555 123 4567, details at bit.ly/deal";
+    let reply = preview_scan(make_message(chat_id, user_id, "admin", text, 1), text.to_string())
+        .await.expect("preview failed");
+    for sym in [symbol::TG_INVITE_LINK, symbol::TG_PHONE_SPAM, symbol::TG_SHORTENER] {
+        assert!(reply.symbols.contains_key(sym), "Expected {}", sym);
+    }
 
-    assert!(reply.symbols.contains_key(symbol::TG_EMOJI_SPAM), 
-        "Expected TG_EMOJI_SPAM for message with excessive emoji usage");
+    assert_eq!(snapshot(&mut conn), before, "Preview must not write to Redis");
 }
 
 #[tokio::test]
@@ -1239,6 +4777,314 @@ async fn tg_shortener_sets_symbol_for_url_shorteners() {
         "Expected TG_SHORTENER for message with URL shortener");
 }
 
+#[tokio::test]
+#[serial]
+async fn shortener_command_adds_shorteners_on_top_of_the_defaults() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8012;
+    let user_id = 1012;
+    let text = "Grab it here: https://sho.rt/deal";
+    let reply = scan_msg(make_message(chat_id, user_id, "shorteneruser", text, 1), text.into()).await.unwrap();
+    assert!(!reply.symbols.contains_key(symbol::TG_SHORTENER), "sho.rt isn't a shortener yet");
+
+    let cmd = make_message(chat_id, user_id, "admin", "/shortener add|SHO.RT", 2);
+    let _ = handle_admin_command(Bot::new("DUMMY"), cmd, AdminCommand::Shortener { pattern: "add|SHO.RT".into() }).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let listed: HashSet<String> = conn.smembers(key::TG_SHORTENERS_KEY).unwrap();
+    assert!(listed.contains("sho.rt"), "Domains are stored lowercased");
+    assert!(listed.contains("bit.ly"), "Adding a shortener keeps the defaults");
+
+    let reply = scan_msg(make_message(chat_id, user_id, "shorteneruser", text, 3), text.into()).await.unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_SHORTENER), "Expected TG_SHORTENER for an added shortener");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_shortener_matches_hosts_not_substrings() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8013;
+    let user_id = 1013;
+    let text = "Docs are at https://notbit.ly.example.com/guide";
+    let reply = scan_msg(make_message(chat_id, user_id, "reader", text, 1), text.into()).await.unwrap();
+    assert!(!reply.symbols.contains_key(symbol::TG_SHORTENER), "A shortener's name inside another host isn't a shortener");
+
+    let defaults: HashSet<String> = ["bit.ly".to_string()].into();
+    assert!(is_shortener_host(&defaults, "bit.ly"));
+    assert!(is_shortener_host(&defaults, "WWW.Bit.ly"));
+    assert!(!is_shortener_host(&defaults, "notbit.ly"));
+    assert!(!is_shortener_host(&defaults, "bit.ly.example.com"));
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_lookalike_domain_flags_mixed_script_punycode_hosts() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8018;
+    let user_id = 1018;
+
+    let legit = "Sign in at https://apple.com/account to continue";
+    let reply = scan_msg(make_message(chat_id, user_id, "phisher", legit, 1), legit.into())
+        .await
+        .unwrap();
+    assert!(!reply.symbols.contains_key(symbol::TG_LOOKALIKE_DOMAIN), "Plain ASCII domains are not lookalikes");
+
+    // xn--pple-43d is "аpple" with a Cyrillic "а"
+    let phishing = "Sign in at https://xn--pple-43d.com/account to continue";
+    let reply = scan_msg(make_message(chat_id, user_id, "phisher", phishing, 2), phishing.into())
+        .await
+        .unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_LOOKALIKE_DOMAIN), "Expected TG_LOOKALIKE_DOMAIN for a mixed-script host");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_url_reputation_ignores_clean_domains() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let _: () = conn.hset(key::TG_DOMAIN_REP_KEY, "docs.rs", -2.0).unwrap();
+
+    let text = "Docs are at https://docs.rs/redis and https://example.org";
+    let reply = scan_msg(make_message(8019, 1019, "reader", text, 1), text.into()).await.unwrap();
+    assert!(!reply.symbols.contains_key(symbol::TG_URL_REPUTATION), "Unknown and well-reputed domains don't score");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_url_reputation_scores_the_worst_linked_domain() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let bot = Bot::new("DUMMY");
+    let msg = make_message(8020, 1, "admin", "/domainrep scam.example|3", 1);
+    let _ = handle_admin_command(bot.clone(), msg, AdminCommand::DomainRep { args: "Scam.Example|3".into() }).await;
+    let msg = make_message(8020, 1, "admin", "/domainrep shady.example|1.5", 2);
+    let _ = handle_admin_command(bot, msg, AdminCommand::DomainRep { args: "shady.example|1.5".into() }).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let score: f64 = conn.hget(key::TG_DOMAIN_REP_KEY, "scam.example").unwrap();
+    assert_eq!(score, 3.0, "/domainrep should store the domain lowercased");
+
+    // Subdomains inherit the reputation of their parent domain
+    let text = "Claim at https://login.scam.example/win or https://shady.example";
+    let reply = scan_msg(make_message(8020, 1020, "spammer", text, 3), text.into()).await.unwrap();
+    let fired = reply.symbols.get(symbol::TG_URL_REPUTATION).expect("Expected TG_URL_REPUTATION for a bad domain");
+    assert_eq!(fired.score, 3.0 * domain_rep::WEIGHT, "Score should follow the worst domain");
+    assert_eq!(fired.options.as_deref(), Some(&["login.scam.example".to_string()][..]));
+
+    // Trusted domains are never scored
+    let _: () = conn.sadd(key::TG_TRUSTED_DOMAINS_KEY, "scam.example").unwrap();
+    let text = "Claim at https://scam.example/win";
+    let reply = scan_msg(make_message(8020, 1020, "spammer", text, 4), text.into()).await.unwrap();
+    assert!(!reply.symbols.contains_key(symbol::TG_URL_REPUTATION));
+}
+
+#[tokio::test]
+#[serial]
+async fn banned_messages_raise_linked_domain_reputation() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id: i64 = 8021;
+    let user_id: u64 = 1021;
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    // Ban at any score so the mock's zero score maps to a ban
+    let _: () = conn.hset(format!("{}{}{}", key::TG_CHATS_PREFIX, chat_id, suffix::ACTIONS), action::BAN, 0.0).unwrap();
+
+    let text = "Free coins at https://coins.example/claim";
+    let _ = handle_message(Bot::new("DUMMY"), make_message(chat_id, user_id, "spammer", text, 1)).await;
+
+    let score: f64 = conn.hget(key::TG_DOMAIN_REP_KEY, "coins.example").unwrap();
+    assert_eq!(score, domain_rep::BAN_INCREMENT, "Domains in a banned message should lose reputation");
+
+    let reply = scan_msg(make_message(chat_id, user_id + 1, "other", text, 2), text.into()).await.unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_URL_REPUTATION), "The domain now scores in later messages");
+}
+
+#[test]
+fn lookalike_host_decodes_punycode_labels() {
+    assert_eq!(decode_host("xn--pple-43d.com"), "аpple.com");
+    assert_eq!(decode_host("Docs.RS"), "docs.rs");
+    assert!(is_lookalike_host("login.xn--pple-43d.com"));
+    assert!(!is_lookalike_host("apple.com"));
+    // A label written entirely in one script is not a lookalike
+    assert!(!is_lookalike_host("xn--e1afmkfd.com"), "пример.com only uses Cyrillic within its label");
+    assert!(!is_lookalike_host("xn--invalid-.com"));
+}
+
+/// Starts an Rspamd stand-in whose first `/checkv2` request stalls for two seconds
+/// and, if `always_fail` is set, answers every request with a 503.
+fn start_flaky_rspamd(always_fail: bool) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    let attempts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = attempts.clone();
+    let checkv2 = warp::path("checkv2").and(warp::post()).and_then(move || {
+        let attempt = counter.fetch_add(1, Ordering::SeqCst);
+        async move {
+            if attempt == 0 {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+            }
+            let reply = json!({
+                "score": 1.0,
+                "action": "no action",
+                "symbols": {"MOCK_RSPAMD": {"name": "MOCK_RSPAMD", "score": 1.0, "metric_score": 1.0}}
+            });
+            let status = if always_fail {
+                warp::http::StatusCode::SERVICE_UNAVAILABLE
+            } else {
+                warp::http::StatusCode::OK
+            };
+            Ok::<_, warp::Rejection>(warp::reply::with_status(warp::reply::json(&reply), status))
+        }
+    });
+    let (addr, server) = warp::serve(checkv2).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    (format!("http://{}", addr), attempts)
+}
+
+#[tokio::test]
+#[serial]
+async fn scan_retries_after_a_timed_out_rspamd_request() {
+    flush_redis();
+    let (url, attempts) = start_flaky_rspamd(false);
+    std::env::set_var("RSPAMD_URL", url);
+    std::env::set_var("RSPAMD_TIMEOUT", "0.5");
+
+    let text = "Anyone up for lunch later today?";
+    let reply = scan_msg(make_message(8030, 1030, "patient", text, 1), text.into()).await;
+    std::env::remove_var("RSPAMD_TIMEOUT");
+
+    let reply = reply.expect("Retry should reach Rspamd after the first attempt times out");
+    assert!(reply.symbols.contains_key("MOCK_RSPAMD"), "Reply should come from Rspamd, not the local checks");
+    assert_eq!(attempts.load(Ordering::SeqCst), 2, "Expected exactly one retry");
+}
+
+#[tokio::test]
+#[serial]
+async fn scan_falls_back_to_local_checks_when_rspamd_is_down() {
+    flush_redis();
+    let (url, attempts) = start_flaky_rspamd(true);
+    std::env::set_var("RSPAMD_URL", url);
+    std::env::set_var("RSPAMD_TIMEOUT", "0.5");
+    std::env::set_var("RSPAMD_RETRIES", "1");
+
+    let text = "BUY CHEAP FOLLOWERS NOW AT OUR STORE";
+    let reply = scan_msg(make_message(8031, 1031, "shouter", text, 1), text.into()).await;
+    std::env::remove_var("RSPAMD_TIMEOUT");
+    std::env::remove_var("RSPAMD_RETRIES");
+
+    let reply = reply.expect("Scan should fall back to local checks instead of failing");
+    assert_eq!(attempts.load(Ordering::SeqCst), 2, "Expected the first attempt plus one retry");
+    assert!(!reply.symbols.contains_key("MOCK_RSPAMD"));
+    assert!(reply.symbols.contains_key(symbol::TG_CAPS), "Expected TG_CAPS from the local checks");
+    assert!(reply.score > 0.0, "Local symbols should carry their configured score");
+}
+
+/// Rspamd stand-in answering every scan with `status` and `body`; counts requests.
+fn start_broken_rspamd(status: warp::http::StatusCode, body: &'static str) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    let attempts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = attempts.clone();
+    let checkv2 = warp::path("checkv2").and(warp::post()).map(move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        warp::reply::with_status(body, status)
+    });
+    let (addr, server) = warp::serve(checkv2).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    (format!("http://{}", addr), attempts)
+}
+
+#[tokio::test]
+#[serial]
+async fn failed_rspamd_scans_are_classified_and_counted() {
+    flush_redis();
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    std::env::set_var("RSPAMD_TIMEOUT", "0.5");
+    std::env::set_var("RSPAMD_RETRIES", "1");
+
+    // A 500 is retried, then the local checks take over
+    let (url, attempts) = start_broken_rspamd(warp::http::StatusCode::INTERNAL_SERVER_ERROR, "oops");
+    std::env::set_var("RSPAMD_URL", url);
+    let text = "BUY CHEAP FOLLOWERS NOW AT OUR STORE";
+    let reply = scan_msg(make_message(8032, 1032, "shouter", text, 1), text.into()).await;
+    let reply = reply.expect("Scan should fall back to local checks on a 500");
+    assert_eq!(attempts.load(Ordering::SeqCst), 2, "Expected the first attempt plus one retry");
+    assert!(reply.symbols.contains_key(symbol::TG_CAPS), "Expected TG_CAPS from the local checks");
+    let errors: HashMap<String, i64> = conn.hgetall(key::ns(key::TG_RSPAMD_ERRORS_KEY)).unwrap();
+    assert_eq!(errors, HashMap::from([("http_status".to_string(), 1)]));
+
+    // Malformed JSON won't get better on retry
+    let (url, attempts) = start_broken_rspamd(warp::http::StatusCode::OK, "{not json");
+    std::env::set_var("RSPAMD_URL", url);
+    let reply = scan_msg(make_message(8032, 1032, "shouter", text, 2), text.into()).await;
+    std::env::remove_var("RSPAMD_TIMEOUT");
+    std::env::remove_var("RSPAMD_RETRIES");
+    assert!(reply.expect("Scan should fall back to local checks on a malformed reply").symbols.contains_key(symbol::TG_CAPS));
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    let parse_errors: Option<i64> = conn.hget(key::ns(key::TG_RSPAMD_ERRORS_KEY), "parse").unwrap();
+    assert_eq!(parse_errors, Some(1));
+
+    assert_eq!(ScanFailure::classify(&RspamdError::HttpError("HTTP request failed: Status: 503 Service Unavailable".into())), ScanFailure::HttpStatus(503));
+    assert_eq!(ScanFailure::classify(&RspamdError::HttpError("error sending request".into())), ScanFailure::Connection);
+    assert!(ScanFailure::HttpStatus(503).is_transient() && !ScanFailure::HttpStatus(400).is_transient());
+}
+
+#[tokio::test]
+#[serial]
+async fn spam_webhook_receives_ban_events() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // The first delivery fails so the retry is exercised
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<serde_json::Value>();
+    let attempts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = attempts.clone();
+    let hook = warp::path("hook").and(warp::post()).and(warp::body::json()).map(move |body: serde_json::Value| {
+        if counter.fetch_add(1, Ordering::SeqCst) == 0 {
+            return warp::http::StatusCode::INTERNAL_SERVER_ERROR;
+        }
+        tx.send(body).unwrap();
+        warp::http::StatusCode::OK
+    });
+    let (addr, server) = warp::serve(hook).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    std::env::set_var(webhook::URL_ENV, format!("http://{}/hook", addr));
+
+    let chat_id: i64 = 4051;
+    let user_id: u64 = 811;
+    let mut conn = redis::Client::open("redis://127.0.0.1/").unwrap().get_connection().unwrap();
+    let _: () = conn.hset(format!("{}{}", key::TG_USERS_PREFIX, user_id), field::REP, CONFIG.ban + 1).unwrap();
+    let reply = scan_msg(make_message(chat_id, user_id, "hooked", "Test message", 1), "Test message".into()).await;
+    std::env::remove_var(webhook::URL_ENV);
+    assert!(reply.unwrap().symbols.contains_key(symbol::TG_BAN));
+
+    let body = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("Webhook wasn't called")
+        .unwrap();
+    assert_eq!(attempts.load(Ordering::SeqCst), 2, "Expected the failed attempt plus one retry");
+    let payload: SpamWebhookPayload = serde_json::from_value(body.clone()).expect("Unexpected payload shape");
+    assert_eq!(payload.chat_id, chat_id);
+    assert_eq!(payload.user_id, user_id);
+    assert_eq!(payload.events, vec![symbol::TG_BAN.to_string()]);
+    assert!(payload.symbols.contains(&symbol::TG_BAN.to_string()));
+    assert!(payload.timestamp > 0);
+    for field in ["chat_id", "user_id", "events", "symbols", "score", "timestamp"] {
+        assert!(body.get(field).is_some(), "Payload is missing `{}`", field);
+    }
+}
+
 #[tokio::test]
 #[serial]
 async fn tg_gibberish_sets_symbol_for_random_consonants() {
@@ -1260,6 +5106,82 @@ async fn tg_gibberish_sets_symbol_for_random_consonants() {
         "Expected TG_GIBBERISH for message with gibberish text");
 }
 
+#[tokio::test]
+#[serial]
+async fn togglesymbol_disables_tg_gibberish_in_every_chat() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let spam_text = "KXJQZWVBNMLPQRSTUVWXYZKXJQZWVBNMLPQRSTUVWXYZKXJQZWVBNMLPQRSTUVWXYZ";
+    let toggle = |msg_id: u32| {
+        let cmd = make_message(8014, 1014, "admin", "/togglesymbol tg_gibberish", msg_id);
+        handle_admin_command(Bot::new("DUMMY"), cmd, AdminCommand::ToggleSymbol { symbol: "tg_gibberish".into() })
+    };
+    let _ = toggle(1).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let enabled: bool = conn.sismember(ENABLED_FEATURES_KEY, "gibberish").unwrap();
+    assert!(!enabled, "TG_GIBBERISH's feature should be gone from the global set");
+    for (i, chat_id) in [8014, 8015, 8016].into_iter().enumerate() {
+        let reply = scan_msg(make_message(chat_id, 1015 + i as u64, "gibberishuser", spam_text, i as u32 + 2), spam_text.into()).await.unwrap();
+        assert!(!reply.symbols.contains_key(symbol::TG_GIBBERISH), "TG_GIBBERISH fired in chat {} while disabled", chat_id);
+    }
+
+    // Toggling again switches it back on
+    let _ = toggle(5).await;
+    let reply = scan_msg(make_message(8016, 1018, "gibberishuser", spam_text, 6), spam_text.into()).await.unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_GIBBERISH));
+    assert_eq!(symbol_feature("TG_BAN"), None, "Only detection symbols can be toggled");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_gibberish_sets_symbol_for_multi_word_gibberish() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8013;
+    let user_id = 1013;
+    let spam_text = "xkcd qwrtp zxvbn mlkjh gfdsw trnbv pqlmz kjhgf dcxzw vbnml srtkp";
+
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "gibberishwords", spam_text, 1),
+        spam_text.into(),
+    ).await.ok().unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_GIBBERISH),
+        "Gibberish split into words should still set TG_GIBBERISH");
+
+    let normal_text = "Does anyone know when the next community meetup is going to happen this year?";
+    let reply = scan_msg(
+        make_message(chat_id, user_id, "gibberishwords", normal_text, 2),
+        normal_text.into(),
+    ).await.ok().unwrap();
+    assert!(!reply.symbols.contains_key(symbol::TG_GIBBERISH),
+        "Ordinary sentences shouldn't set TG_GIBBERISH");
+}
+
+#[tokio::test]
+#[serial]
+async fn tg_gibberish_ratio_is_tunable() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let chat_id = 8014;
+    let user_id = 1014;
+    // 48 consonants out of 56 letters, about 0.86
+    let text = "strength rhythm crwth nymphs glyph trysts sphynx lynx crypts xkcd";
+
+    let mut conn = redis::Client::open("redis://127.0.0.1/").unwrap().get_connection().unwrap();
+    let _: () = conn.hset(key::TG_THRESHOLDS_KEY, threshold::GIBBERISH_RATIO, 0.95).unwrap();
+    let reply = scan_msg(make_message(chat_id, user_id, "tunable", text, 1), text.into()).await.ok().unwrap();
+    assert!(!reply.symbols.contains_key(symbol::TG_GIBBERISH), "A stricter ratio shouldn't fire");
+
+    let _: () = conn.hset(key::TG_THRESHOLDS_KEY, threshold::GIBBERISH_RATIO, 0.5).unwrap();
+    let reply = scan_msg(make_message(chat_id, user_id, "tunable", text, 2), text.into()).await.ok().unwrap();
+    assert!(reply.symbols.contains_key(symbol::TG_GIBBERISH), "A looser ratio should fire");
+}
+
 #[tokio::test]
 #[serial]
 async fn multiple_symbols_can_trigger_simultaneously() {
@@ -1608,6 +5530,65 @@ async fn test_reply_aware_filtering_integration() {
     let _: () = conn.del(format!("{}{}", key::TG_TRUSTED_PREFIX, trusted_message_id.0)).unwrap_or_default();
 }
 
+#[serial]
+#[tokio::test]
+async fn replies_to_trusted_messages_without_metadata_are_still_tracked() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    // Only the trusted marker is left: no metadata and no tracked replies
+    let trusted_message_id = MessageId(12360);
+    let chat_id = 67900;
+    let client = redis::Client::open("redis://127.0.0.1/").expect("Failed to connect to Redis");
+    let mut conn = client.get_connection().expect("Failed to get Redis connection");
+    let _: () = conn.set_ex(format!("{}{}", key::TG_TRUSTED_PREFIX, trusted_message_id.0), "admin", 3600).unwrap();
+
+    let original_admin_message = make_message(chat_id, 11120, "admin", "Please read the rules", trusted_message_id.0 as u32);
+    let reply = make_message_with_reply(chat_id, 22230, "test", "Thanks, will do", 7, original_admin_message);
+    let scan_reply = scan_msg(reply, "Thanks, will do".to_string()).await.expect("Scan should succeed");
+
+    assert!(scan_reply.symbols.contains_key(symbol::TG_REPLY_ADMIN),
+            "Reply to an admin message should have TG_REPLY_ADMIN even without prior tracking");
+    let tracked: bool = conn.exists(format!("{}{}:{}:{}", key::TG_REPLIES_PREFIX, chat_id, trusted_message_id.0, 7)).unwrap();
+    assert!(tracked, "The reply should be tracked");
+
+    let trust_manager = TrustManager::new("redis://127.0.0.1/").expect("Failed to create trust manager");
+    let metadata = trust_manager.get_trusted_metadata(trusted_message_id).await.unwrap()
+        .expect("Metadata should be rebuilt from the replied-to message");
+    assert_eq!(metadata.message_type, TrustedMessageType::Admin);
+    assert_eq!(metadata.sender_id, UserId(11120));
+    assert_eq!(metadata.chat_id, ChatId(chat_id));
+}
+
+#[serial]
+#[tokio::test]
+async fn disabling_reply_aware_filtering_drops_reply_reductions() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let trusted_message_id = MessageId(12361);
+    let chat_id = 67901;
+    let trust_manager = TrustManager::new("redis://127.0.0.1/").expect("Failed to create trust manager");
+    trust_manager
+        .mark_trusted(TrustedMessageMetadata::new(trusted_message_id, ChatId(chat_id), UserId(11121), TrustedMessageType::Bot))
+        .await
+        .expect("Failed to mark message as trusted");
+    trust_manager.set_enabled(false).unwrap();
+    assert!(!trust_manager.is_enabled().unwrap());
+
+    let original_bot_message = make_message(chat_id, 11121, "bot", "Welcome!", trusted_message_id.0 as u32);
+    let reply = make_message_with_reply(chat_id, 22231, "test", "Thanks for the welcome", 1, original_bot_message.clone());
+    let scan_reply = scan_msg(reply, "Thanks for the welcome".to_string()).await.expect("Scan should succeed");
+    assert!(!scan_reply.symbols.contains_key(symbol::TG_REPLY_BOT),
+            "Replies get no TG_REPLY_BOT while reply-aware filtering is off");
+
+    trust_manager.set_enabled(true).unwrap();
+    let reply = make_message_with_reply(chat_id, 22231, "test", "Thanks for the welcome", 2, original_bot_message);
+    let scan_reply = scan_msg(reply, "Thanks for the welcome".to_string()).await.expect("Scan should succeed");
+    assert!(scan_reply.symbols.contains_key(symbol::TG_REPLY_BOT),
+            "Replies get TG_REPLY_BOT again once reply-aware filtering is back on");
+}
+
 #[serial]
 #[tokio::test]
 async fn test_reply_spam_detection_integration() {
@@ -1693,9 +5674,9 @@ async fn test_different_trust_levels_integration() {
         
         trust_manager.mark_trusted(metadata).await.expect("Failed to mark message as trusted");
         
-        // Create reply to this trusted message
+        // Create reply to this trusted message; each reply is a message of its own
         let original_message = make_message(chat_id, user_id, "user", "Original trusted message", message_id.0 as u32);
-        let reply_message = make_message_with_reply(chat_id, 22224, "test", "This is a reply to a trusted message", 1, original_message);
+        let reply_message = make_message_with_reply(chat_id, 22224, "test", "This is a reply to a trusted message", message_id.0 as u32 + 1000, original_message);
         
         let scan_result = scan_msg(reply_message, "This is a reply to a trusted message".to_string()).await;
         assert!(scan_result.is_ok(), "Scan should succeed");
@@ -1714,3 +5695,71 @@ async fn test_different_trust_levels_integration() {
         let _: () = conn.del(format!("{}{}", key::TG_TRUSTED_PREFIX, message_id.0)).unwrap_or_default();
     }
 }
+
+#[tokio::test]
+async fn throttle_serializes_bursts_without_dropping_messages() {
+    // 10ms between any two requests, 50ms between messages to one chat
+    let throttle = std::sync::Arc::new(Throttle::new(100.0, 1200.0));
+    let start = tokio::time::Instant::now();
+    let burst: Vec<_> = (0..6)
+        .map(|_| {
+            let throttle = throttle.clone();
+            tokio::spawn(async move {
+                throttle.wait(ChatId(4070)).await;
+                start.elapsed()
+            })
+        })
+        .collect();
+    let other_chat = {
+        let throttle = throttle.clone();
+        tokio::spawn(async move {
+            throttle.wait(ChatId(4071)).await;
+            start.elapsed()
+        })
+    };
+
+    let mut sent = Vec::new();
+    for message in burst {
+        sent.push(message.await.expect("send task failed"));
+    }
+    sent.sort();
+    assert_eq!(sent.len(), 6, "Every message of the burst is sent");
+    for pair in sent.windows(2) {
+        assert!(pair[1] - pair[0] >= Duration::from_millis(45), "Messages to one chat are spaced out: {:?}", sent);
+    }
+    let other = other_chat.await.expect("send task failed");
+    assert!(other < Duration::from_millis(100), "Other chats don't wait for the burst: {:?}", other);
+}
+
+#[tokio::test]
+#[serial]
+async fn search_messages_finds_stored_messages_by_keyword() {
+    flush_redis();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let (chat, other_chat) = (ChatId(4080), ChatId(4081));
+    store_message_content(&mut conn, chat, MessageId(9101), "Join our CRYPTO giveaway today").unwrap();
+    store_message_content(&mut conn, chat, MessageId(9102), "See you at the meetup").unwrap();
+    store_message_content(&mut conn, chat, MessageId(9103), "free crypto for everyone").unwrap();
+    store_message_content(&mut conn, other_chat, MessageId(9104), "crypto in another chat").unwrap();
+    let since = Utc::now().timestamp() - 3600;
+
+    let found = search_messages(&mut conn, Some(chat), since, &SearchPattern::parse("crypto").unwrap(), 10).unwrap();
+    let mut ids: Vec<&str> = found.iter().map(|found| found.message_id.as_str()).collect();
+    ids.sort();
+    assert_eq!(ids, ["9101", "9103"]);
+    assert!(found.iter().all(|found| found.chat_id == chat.0));
+    assert!(found.iter().any(|found| found.snippet.contains("CRYPTO giveaway")));
+
+    let everywhere = search_messages(&mut conn, None, since, &SearchPattern::parse("crypto").unwrap(), 10).unwrap();
+    assert_eq!(everywhere.len(), 3);
+    assert_eq!(search_messages(&mut conn, None, since, &SearchPattern::parse("crypto").unwrap(), 2).unwrap().len(), 2, "Results are capped");
+
+    let regex = search_messages(&mut conn, Some(chat), since, &SearchPattern::parse(r"/meet\w+/").unwrap(), 10).unwrap();
+    assert_eq!(regex.iter().map(|found| found.message_id.as_str()).collect::<Vec<_>>(), ["9102"]);
+
+    let future = Utc::now().timestamp() + 60;
+    assert!(search_messages(&mut conn, None, future, &SearchPattern::parse("crypto").unwrap(), 10).unwrap().is_empty());
+}
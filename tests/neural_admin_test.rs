@@ -137,3 +137,19 @@ async fn test_neural_stats_formatting() {
     assert!(formatted.contains("100"));
     assert!(formatted.contains("5"));
 }
+
+#[test]
+fn test_train_outcome_formatting_is_plain_text() {
+    use rspamd_telegram_bot::admin_handlers::neural_commands::format_train_outcome;
+    use rspamd_telegram_bot::neural_manager::TrainOutcome;
+
+    let trained = format_train_outcome(&TrainOutcome::Trained { samples: 40, iterations: 2, accuracy: 0.925 });
+    assert!(trained.contains("Model Accuracy: 92.5%"));
+    let not_ready = format_train_outcome(&TrainOutcome::NotReady { samples: 5, missing: 15 });
+    assert!(not_ready.contains("Still Needed: 15"));
+
+    // Sent without a parse mode, so no Markdown markup may leak into the reply
+    for reply in [trained, not_ready] {
+        assert!(!reply.contains('*') && !reply.contains('`'), "unexpected markup in {:?}", reply);
+    }
+}
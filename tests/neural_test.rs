@@ -240,3 +240,83 @@ async fn test_neural_manager_error_handling() {
     let _accuracy_result = manager.get_accuracy();
     // We don't assert success here since it depends on Redis availability
 }
+
+/// Replaces the neural stats and feature records with a known fixture.
+fn seed_training_data(total_messages: i64, feature_records: i64) -> redis::Connection {
+    use redis::Commands;
+
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let old_features: Vec<String> = conn.keys(format!("{}:*", neural::NEURAL_FEATURES_KEY)).unwrap();
+    for key in old_features {
+        let _: () = conn.del(key).unwrap();
+    }
+    let _: () = conn.del(neural::NEURAL_STATS_KEY).unwrap();
+    let _: () = conn.del(neural::NEURAL_MODEL_KEY).unwrap();
+    let _: () = conn.hset(neural::NEURAL_STATS_KEY, "total_messages", total_messages).unwrap();
+
+    for i in 0..feature_records {
+        let is_spam = i % 2 == 0;
+        let record = serde_json::json!({
+            "message_id": format!("train_{}", i),
+            "word_count": if is_spam { 12 } else { 8 },
+            "link_count": if is_spam { 4 } else { 0 },
+            "emoji_count": if is_spam { 6 } else { 1 },
+            "caps_ratio": if is_spam { 0.8 } else { 0.1 },
+            "learning_type": if is_spam { "spam" } else { "ham" },
+        });
+        let _: () = conn
+            .set(format!("{}:train_{}", neural::NEURAL_FEATURES_KEY, i), record.to_string())
+            .unwrap();
+    }
+
+    conn
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_neural_train_not_ready() {
+    use rspamd_telegram_bot::neural_manager::TrainOutcome;
+
+    setup();
+    seed_training_data(neural::MIN_SAMPLES_REQUIRED - 10, 0);
+
+    let manager = NeuralManager::new().unwrap();
+    let outcome = manager.train().unwrap();
+    assert_eq!(
+        outcome,
+        TrainOutcome::NotReady { samples: neural::MIN_SAMPLES_REQUIRED - 10, missing: 10 },
+        "Training should report how many samples are still missing"
+    );
+
+    let stats = manager.get_neural_stats().unwrap();
+    assert_eq!(stats.training_iterations, 0, "Training should not run without enough samples");
+}
+
+#[tokio::test]
+#[serial_test::serial]
+async fn test_neural_train_ready() {
+    use redis::Commands;
+    use rspamd_telegram_bot::neural_manager::TrainOutcome;
+
+    setup();
+    let mut conn = seed_training_data(neural::MIN_SAMPLES_REQUIRED, neural::MIN_SAMPLES_REQUIRED);
+
+    let manager = NeuralManager::new().unwrap();
+    match manager.train().unwrap() {
+        TrainOutcome::Trained { samples, iterations, accuracy } => {
+            assert_eq!(samples as i64, neural::MIN_SAMPLES_REQUIRED);
+            assert_eq!(iterations, 1);
+            assert_eq!(accuracy, 1.0, "Well separated classes should be classified perfectly");
+        }
+        other => panic!("Expected training to run, got {:?}", other),
+    }
+
+    let stats = manager.get_neural_stats().unwrap();
+    assert_eq!(stats.training_iterations, 1);
+    assert_eq!(stats.model_accuracy, 1.0);
+    assert!(manager.is_ready().unwrap(), "Trained model with enough samples should be ready");
+
+    let spam_links: f64 = conn.hget(neural::NEURAL_MODEL_KEY, "spam:link_count").unwrap();
+    assert_eq!(spam_links, 4.0, "Spam centroid should be stored in the model key");
+}
@@ -0,0 +1,31 @@
+use rspamd_telegram_bot::config::{action, notification};
+use rspamd_telegram_bot::notifications::{action_severity, should_notify, Severity};
+
+#[test]
+fn test_should_notify_respects_level() {
+    // (level, notify about a high severity alert, notify about a low severity alert)
+    let matrix = [
+        (notification::ALL, true, true),
+        (notification::LOW, true, true),
+        (notification::MEDIUM, true, false),
+        (notification::HIGH, true, false),
+        (notification::NONE, false, false),
+    ];
+
+    for (level, high, low) in matrix {
+        assert_eq!(should_notify(Severity::High, level), high, "high severity at level `{}`", level);
+        assert_eq!(should_notify(Severity::Low, level), low, "low severity at level `{}`", level);
+    }
+
+    assert!(should_notify(Severity::Medium, notification::MEDIUM));
+    assert!(!should_notify(Severity::Medium, notification::HIGH));
+    assert!(should_notify(Severity::Low, "bogus"), "Unknown levels fall back to notifying");
+}
+
+#[test]
+fn test_action_severity() {
+    assert_eq!(action_severity(action::BAN), Severity::High);
+    assert_eq!(action_severity(action::MUTE), Severity::High);
+    assert_eq!(action_severity(action::DELETE), Severity::Medium);
+    assert_eq!(action_severity(action::WARN), Severity::Low);
+}
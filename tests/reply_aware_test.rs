@@ -1,5 +1,5 @@
 use rspamd_telegram_bot::trust_manager::{TrustManager, TrustedMessageType, TrustedMessageMetadata};
-use rspamd_telegram_bot::config::{key, symbol, reply_aware, rate_limit, selective_trust};
+use rspamd_telegram_bot::config::{key, suffix, symbol, reply_aware, rate_limit, selective_trust};
 use rspamd_telegram_bot::handlers::{scan_msg, check_reply_symbols};
 use teloxide::types::{Chat, ChatId, ChatKind, ChatPrivate, MediaKind, MediaText, Message, MessageCommon, MessageId, MessageKind, User, UserId};
 use chrono::Utc;
@@ -362,6 +362,42 @@ async fn test_cleanup_functionality() {
     assert!(result.is_ok());
 }
 
+#[tokio::test]
+#[serial]
+async fn test_cleanup_removes_orphaned_entries() {
+    flush_redis();
+
+    let trust_manager = TrustManager::new("redis://127.0.0.1/").unwrap();
+    let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let mut conn = client.get_connection().unwrap();
+    let chat_id = ChatId(-100777);
+
+    // A live trusted message with a reply to it
+    let live = TrustedMessageMetadata::new(MessageId(700), chat_id, UserId(70), TrustedMessageType::Bot);
+    trust_manager.mark_trusted(live.clone()).await.unwrap();
+    trust_manager.track_reply(chat_id, MessageId(701), MessageId(700)).await.unwrap();
+
+    // A reply to a trusted message that has expired, and metadata left behind by another
+    trust_manager.track_reply(chat_id, MessageId(703), MessageId(702)).await.unwrap();
+    let expired = TrustedMessageMetadata::new(MessageId(704), chat_id, UserId(70), TrustedMessageType::Admin);
+    trust_manager.mark_trusted(expired.clone()).await.unwrap();
+    let _: () = conn.del(expired.redis_key()).unwrap();
+
+    let orphaned_reply = format!("{}{}:702:703", key::TG_REPLIES_PREFIX, chat_id.0);
+    let live_reply = format!("{}{}:700:701", key::TG_REPLIES_PREFIX, chat_id.0);
+    assert!(conn.exists::<_, bool>(&orphaned_reply).unwrap());
+
+    assert_eq!(trust_manager.cleanup_expired().await.unwrap(), 2);
+    assert!(!conn.exists::<_, bool>(&orphaned_reply).unwrap(), "Replies to gone messages should be removed");
+    assert!(!conn.exists::<_, bool>(expired.metadata_key()).unwrap(), "Orphaned metadata should be removed");
+    assert!(conn.exists::<_, bool>(&live_reply).unwrap(), "Replies to live trusted messages stay");
+    assert!(conn.exists::<_, bool>(live.metadata_key()).unwrap(), "Metadata of live trusted messages stays");
+    assert!(trust_manager.is_reply_to_trusted(chat_id, MessageId(701)).await.unwrap().is_some());
+
+    // Nothing is left to clean up
+    assert_eq!(trust_manager.cleanup_expired().await.unwrap(), 0);
+}
+
 #[tokio::test]
 #[serial]
 async fn test_error_handling() {
@@ -512,6 +548,45 @@ async fn test_reply_to_verified_user_message_gets_score_reduction() {
     let _: () = conn.del(format!("{}{}", key::TG_TRUSTED_PREFIX, trusted_message_id.0)).unwrap_or_default();
 }
 
+#[tokio::test]
+#[serial]
+async fn test_reply_to_own_trusted_message_gets_no_score_reduction() {
+    flush_redis();
+    
+    // A verified user's message was marked trusted
+    let trusted_message_id = MessageId(12348);
+    let chat_id = 67893;
+    let user_id = 11114;
+    
+    let trust_manager = TrustManager::new("redis://127.0.0.1/").expect("Failed to create trust manager");
+    let metadata = TrustedMessageMetadata::new(
+        trusted_message_id,
+        ChatId(chat_id),
+        UserId(user_id),
+        TrustedMessageType::Verified,
+    );
+    trust_manager.mark_trusted(metadata.clone()).await.expect("Failed to mark message as trusted");
+    
+    // The same user replies to it
+    let own_message = make_message(chat_id, user_id, "verified", "Original verified message", trusted_message_id.0 as u32);
+    let self_reply = make_message_with_reply(chat_id, user_id, "verified", "Buy followers now", 1, own_message);
+    
+    let scan_result = scan_msg(self_reply.clone(), "Buy followers now".to_string()).await;
+    assert!(scan_result.is_ok(), "Scan should succeed");
+    
+    let reply_symbols = check_reply_symbols(&self_reply).await;
+    assert!(reply_symbols.contains_key(symbol::TG_REPLY_SELF), "Self-reply should be flagged");
+    for reduction in [symbol::TG_REPLY, symbol::TG_REPLY_BOT, symbol::TG_REPLY_ADMIN, symbol::TG_REPLY_VERIFIED] {
+        assert!(!reply_symbols.contains_key(reduction), "Self-reply must not get {}", reduction);
+    }
+    
+    // No reduction and no reply tracking either
+    let reduction = trust_manager.calculate_score_reduction(&metadata, UserId(user_id)).await.expect("Failed to calculate reduction");
+    assert_eq!(reduction, 0.0);
+    let tracked = trust_manager.is_reply_to_trusted(ChatId(chat_id), MessageId(1)).await.expect("Failed to check reply tracking");
+    assert!(tracked.is_none(), "Self-reply should not be tracked");
+}
+
 #[tokio::test]
 #[serial]
 async fn test_regular_message_does_not_get_reply_symbols() {
@@ -802,6 +877,27 @@ async fn test_rate_limiting() -> Result<(), Box<dyn Error + Send + Sync>> {
         }
     }
     
+    // Simulate a window boundary: events from 59 minutes ago still count,
+    // while events from 61 minutes ago have slid out of the window.
+    let _: () = conn.del(&trusted_rate_key)?;
+    let now_ms = Utc::now().timestamp_millis();
+    let inside_window = now_ms - 59 * 60 * 1000;
+    let outside_window = now_ms - 61 * 60 * 1000;
+    for i in 0..reply_aware::MAX_TRUSTED_MESSAGES_PER_HOUR {
+        let _: () = conn.zadd(&trusted_rate_key, format!("recent-{}", i), inside_window)?;
+    }
+    assert!(!trust_manager.can_create_trusted_message_check_only(test_user_id).await?,
+            "Events inside the window should still count towards the limit");
+
+    let _: () = conn.del(&trusted_rate_key)?;
+    for i in 0..reply_aware::MAX_TRUSTED_MESSAGES_PER_HOUR {
+        let _: () = conn.zadd(&trusted_rate_key, format!("old-{}", i), outside_window)?;
+    }
+    assert!(trust_manager.can_create_trusted_message(test_user_id).await?,
+            "Events outside the window should not count towards the limit");
+    let remaining: u32 = conn.zcard(&trusted_rate_key)?;
+    assert_eq!(remaining, 1, "Expired events should be pruned and the new one recorded");
+    
     println!("Rate limiting tests passed");
     
     // Clean up
@@ -857,36 +953,39 @@ async fn test_selective_trusting() -> Result<(), Box<dyn Error + Send + Sync>> {
 async fn test_anti_evasion_patterns() -> Result<(), Box<dyn Error + Send + Sync>> {
     let trust_manager = TrustManager::new("redis://127.0.0.1/")?;
     let test_user_id = UserId(123456789);
+    let test_chat_id = ChatId(-100123456789);
     
     // Clean up any existing test data by creating a new connection
     let redis_client = redis::Client::open("redis://127.0.0.1/")?;
     let mut conn = redis_client.get_connection()?;
     let spam_key = format!("{}{}", rate_limit::SPAM_PATTERN_PREFIX, test_user_id.0);
+    let overrides_key = format!("{}{}{}", key::TG_CHATS_PREFIX, test_chat_id.0, suffix::ANTI_EVASION);
     let _: () = conn.del(&spam_key)?;
+    let _: () = conn.del(&overrides_key)?;
     
     // Test excessive links
     let text_with_links = "Check out these links: http://example1.com https://example2.com http://example3.com";
-    let patterns = trust_manager.check_reply_spam_patterns(text_with_links, test_user_id).await?;
+    let patterns = trust_manager.check_reply_spam_patterns(text_with_links, test_user_id, test_chat_id).await?;
     assert!(patterns.contains(&"TG_REPLY_LINK_SPAM".to_string()));
     
     // Test phone numbers
     let text_with_phones = "Call me at +1234567890 or +9876543210";
-    let patterns = trust_manager.check_reply_spam_patterns(text_with_phones, test_user_id).await?;
+    let patterns = trust_manager.check_reply_spam_patterns(text_with_phones, test_user_id, test_chat_id).await?;
     assert!(patterns.contains(&"TG_REPLY_PHONE_SPAM".to_string()));
     
     // Test invite links
     let text_with_invites = "Join our group: t.me/joinchat/abc123";
-    let patterns = trust_manager.check_reply_spam_patterns(text_with_invites, test_user_id).await?;
+    let patterns = trust_manager.check_reply_spam_patterns(text_with_invites, test_user_id, test_chat_id).await?;
     assert!(patterns.contains(&"TG_REPLY_INVITE_SPAM".to_string()));
     
     // Test excessive caps
     let text_with_caps = "THIS IS A MESSAGE WITH TOO MANY CAPITAL LETTERS";
-    let patterns = trust_manager.check_reply_spam_patterns(text_with_caps, test_user_id).await?;
+    let patterns = trust_manager.check_reply_spam_patterns(text_with_caps, test_user_id, test_chat_id).await?;
     assert!(patterns.contains(&"TG_REPLY_CAPS_SPAM".to_string()));
     
     // Test normal text (should not trigger patterns)
     let normal_text = "This is a normal message with some links: http://example.com";
-    let patterns = trust_manager.check_reply_spam_patterns(normal_text, test_user_id).await?;
+    let patterns = trust_manager.check_reply_spam_patterns(normal_text, test_user_id, test_chat_id).await?;
     assert!(patterns.is_empty());
     
     println!("Anti-evasion pattern tests passed");
@@ -897,6 +996,42 @@ async fn test_anti_evasion_patterns() -> Result<(), Box<dyn Error + Send + Sync>
     Ok(())
 }
 
+#[tokio::test]
+async fn test_anti_evasion_per_chat_override() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let trust_manager = TrustManager::new("redis://127.0.0.1/")?;
+    let test_user_id = UserId(223456789);
+    let support_chat = ChatId(-100223456789);
+    let other_chat = ChatId(-100323456789);
+    
+    let redis_client = redis::Client::open("redis://127.0.0.1/")?;
+    let mut conn = redis_client.get_connection()?;
+    let spam_key = format!("{}{}", rate_limit::SPAM_PATTERN_PREFIX, test_user_id.0);
+    let support_key = format!("{}{}{}", key::TG_CHATS_PREFIX, support_chat.0, suffix::ANTI_EVASION);
+    let other_key = format!("{}{}{}", key::TG_CHATS_PREFIX, other_chat.0, suffix::ANTI_EVASION);
+    let _: () = conn.del(&spam_key)?;
+    let _: () = conn.del(&support_key)?;
+    let _: () = conn.del(&other_key)?;
+    
+    // The support chat legitimately shares many links
+    let _: () = conn.hset(&support_key, reply_aware::anti_evasion::LINKS_FIELD, 10)?;
+    
+    let link_heavy = "Docs: http://a.example http://b.example http://c.example http://d.example";
+    
+    let patterns = trust_manager.check_reply_spam_patterns(link_heavy, test_user_id, support_chat).await?;
+    assert!(!patterns.contains(&"TG_REPLY_LINK_SPAM".to_string()),
+            "Raised link limit should suppress TG_REPLY_LINK_SPAM in the support chat");
+    
+    let patterns = trust_manager.check_reply_spam_patterns(link_heavy, test_user_id, other_chat).await?;
+    assert!(patterns.contains(&"TG_REPLY_LINK_SPAM".to_string()),
+            "Other chats should keep the global link limit");
+    
+    // Clean up
+    let _: () = conn.del(&spam_key)?;
+    let _: () = conn.del(&support_key)?;
+    
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_spam_pattern_tracking() -> Result<(), Box<dyn Error + Send + Sync>> {
     let trust_manager = TrustManager::new("redis://127.0.0.1/")?;
@@ -965,6 +1100,51 @@ async fn test_score_reduction_calculation() -> Result<(), Box<dyn Error + Send +
 
 
 
+#[tokio::test]
+async fn test_score_reduction_is_clamped_to_configured_cap() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let trust_manager = TrustManager::new("redis://127.0.0.1/")?;
+    let test_user_id = UserId(123456790);
+    
+    let redis_client = redis::Client::open("redis://127.0.0.1/")?;
+    let mut conn = redis_client.get_connection()?;
+    let spam_key = format!("{}{}", rate_limit::SPAM_PATTERN_PREFIX, test_user_id.0);
+    let _: () = conn.del(&spam_key)?;
+    let _: () = conn.hdel(reply_aware::CONFIG_KEY, reply_aware::MAX_REDUCTION_FIELD)?;
+    
+    let bot_metadata = TrustedMessageMetadata::new(
+        MessageId(1),
+        ChatId(100),
+        UserId(999),
+        TrustedMessageType::Bot,
+    );
+    assert_eq!(trust_manager.max_score_reduction()?, reply_aware::MAX_SCORE_REDUCTION);
+    
+    // A bot reply's reduction exceeds a cap of -2 and is clamped to it
+    trust_manager.set_max_score_reduction(-2.0)?;
+    let reduction = trust_manager.calculate_score_reduction(&bot_metadata, test_user_id).await?;
+    assert_eq!(reduction, -2.0);
+    
+    // Reductions within the cap are left alone
+    let verified_metadata = TrustedMessageMetadata::new(
+        MessageId(2),
+        ChatId(100),
+        UserId(998),
+        TrustedMessageType::Verified,
+    );
+    let reduction = trust_manager.calculate_score_reduction(&verified_metadata, test_user_id).await?;
+    assert_eq!(reduction, reply_aware::trust_levels::VERIFIED_TRUST_LEVEL);
+    
+    // A positive cap would turn reductions into penalties
+    assert!(trust_manager.set_max_score_reduction(1.0).is_err());
+    
+    // Without the override the built-in cap applies again
+    let _: () = conn.hdel(reply_aware::CONFIG_KEY, reply_aware::MAX_REDUCTION_FIELD)?;
+    let reduction = trust_manager.calculate_score_reduction(&bot_metadata, test_user_id).await?;
+    assert_eq!(reduction, reply_aware::trust_levels::BOT_TRUST_LEVEL);
+    
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_reputation_integration() -> Result<(), Box<dyn Error + Send + Sync>> {
     let trust_manager = TrustManager::new("redis://127.0.0.1/")?;
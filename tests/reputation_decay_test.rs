@@ -0,0 +1,85 @@
+use redis::Commands;
+use rspamd_telegram_bot::config::{field, key, reputation};
+use rspamd_telegram_bot::reputation_decay::ReputationDecay;
+use serial_test::serial;
+use std::error::Error;
+
+#[tokio::test]
+#[serial]
+async fn test_decay_cycle_with_custom_rate_and_floor() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let redis_client = redis::Client::open("redis://127.0.0.1/")?;
+    let mut redis_conn = redis_client.get_connection()?;
+
+    let high_key = format!("{}{}", key::TG_USERS_PREFIX, 910001);
+    let near_floor_key = format!("{}{}", key::TG_USERS_PREFIX, 910002);
+    let banned_key = format!("{}{}", key::TG_USERS_PREFIX, 910003);
+    let fresh_key = format!("{}{}", key::TG_USERS_PREFIX, 910004);
+
+    // Clean up any existing test data
+    let _: () = redis_conn.del(&[&high_key, &near_floor_key, &banned_key, &fresh_key])?;
+    let _: () = redis_conn.hdel(reputation::SETTINGS_KEY, &[reputation::DECAY_RATE, reputation::DECAY_FLOOR])?;
+
+    let _: () = redis_conn.hset(reputation::SETTINGS_KEY, reputation::DECAY_RATE, "3")?;
+    let _: () = redis_conn.hset(reputation::SETTINGS_KEY, reputation::DECAY_FLOOR, "2")?;
+
+    let _: () = redis_conn.hset(&high_key, field::REP, 10)?;
+    let _: () = redis_conn.hset(&near_floor_key, field::REP, 3)?;
+    let _: () = redis_conn.hset(&banned_key, field::REP, 10)?;
+    let _: () = redis_conn.hset(&banned_key, field::BANNED, "1")?;
+    let _: () = redis_conn.hset(&fresh_key, field::USERNAME, "fresh")?;
+
+    let decay = ReputationDecay::new()?;
+    assert_eq!(decay.decay_settings()?, (3, 2));
+    let processed = decay.run_decay_cycle().await?;
+    assert!(processed >= 4, "All seeded users should be visited");
+
+    let high: i64 = redis_conn.hget(&high_key, field::REP)?;
+    let near_floor: i64 = redis_conn.hget(&near_floor_key, field::REP)?;
+    let banned: i64 = redis_conn.hget(&banned_key, field::REP)?;
+    let fresh: i64 = redis_conn.hget(&fresh_key, field::REP)?;
+
+    assert_eq!(high, 7, "Rep should decay by the configured rate");
+    assert_eq!(near_floor, 2, "Rep should not decay below the floor");
+    assert_eq!(banned, 10, "Banned users should be skipped");
+    assert_eq!(fresh, 0, "Missing rep should be initialized to 0");
+
+    // Clean up
+    let _: () = redis_conn.del(&[&high_key, &near_floor_key, &banned_key, &fresh_key])?;
+    let _: () = redis_conn.hdel(reputation::SETTINGS_KEY, &[reputation::DECAY_RATE, reputation::DECAY_FLOOR])?;
+
+    Ok(())
+}
+
+#[tokio::test]
+#[serial]
+async fn test_decay_cycle_visits_every_user_with_scan() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let redis_client = redis::Client::open("redis://127.0.0.1/")?;
+    let mut redis_conn = redis_client.get_connection()?;
+
+    let keys: Vec<String> = (0..3000)
+        .map(|i| format!("{}decay_scan_{}", key::TG_USERS_PREFIX, i))
+        .collect();
+
+    let _: () = redis_conn.hdel(reputation::SETTINGS_KEY, &[reputation::DECAY_RATE, reputation::DECAY_FLOOR])?;
+    let mut pipe = redis::pipe();
+    for key in &keys {
+        pipe.hset(key, field::REP, 5).ignore();
+    }
+    let _: () = pipe.query(&mut redis_conn)?;
+
+    let decay = ReputationDecay::new()?;
+    let processed = decay.run_decay_cycle().await?;
+    assert!(processed >= keys.len(), "Cursor should visit every seeded user, got {}", processed);
+
+    let mut read = redis::pipe();
+    for key in &keys {
+        read.hget(key, field::REP);
+    }
+    let reps: Vec<i64> = read.query(&mut redis_conn)?;
+    assert!(reps.iter().all(|rep| *rep == 4), "Every seeded user should decay exactly once");
+
+    // Clean up
+    let _: () = redis_conn.del(&keys)?;
+
+    Ok(())
+}
@@ -81,4 +81,57 @@ async fn test_reputation_decay() -> Result<(), Box<dyn Error + Send + Sync>> {
     let _: () = redis_conn.del(&reputation_key)?;
     
     Ok(())
-} 
\ No newline at end of file
+} 
+#[tokio::test]
+async fn test_run_migrations_reconciles_legacy_reputation() -> Result<(), Box<dyn Error + Send + Sync>> {
+    use rspamd_telegram_bot::config::key;
+    use rspamd_telegram_bot::migration;
+
+    let redis_client = redis::Client::open("redis://127.0.0.1/")?;
+    let mut redis_conn = redis_client.get_connection()?;
+    
+    // A legacy user with only the `rep` field, and a user whose reputation hash is half-written
+    let legacy_user = "555000111";
+    let partial_user = "555000222";
+    let legacy_user_key = format!("{}{}", key::TG_USERS_PREFIX, legacy_user);
+    let legacy_rep_key = format!("{}{}", key::TG_REPUTATION_USER_PREFIX, legacy_user);
+    let partial_user_key = format!("{}{}", key::TG_USERS_PREFIX, partial_user);
+    let partial_rep_key = format!("{}{}", key::TG_REPUTATION_USER_PREFIX, partial_user);
+    
+    for k in [&legacy_user_key, &legacy_rep_key, &partial_user_key, &partial_rep_key] {
+        let _: () = redis_conn.del(k)?;
+    }
+    let _: () = redis_conn.del(key::SCHEMA_VERSION_KEY)?;
+    
+    let _: () = redis_conn.hset(&legacy_user_key, "rep", -3)?;
+    let _: () = redis_conn.hset(&partial_user_key, "rep", 4)?;
+    let _: () = redis_conn.hset(&partial_rep_key, "bad", 7)?;
+    
+    let version = migration::run_migrations().await?;
+    assert_eq!(version, migration::latest_schema_version());
+    let recorded: u32 = redis_conn.get(key::SCHEMA_VERSION_KEY)?;
+    assert_eq!(recorded, version, "Schema version should be recorded in Redis");
+    
+    // Legacy rep is converted into the good/bad layout
+    let bad: i64 = redis_conn.hget(&legacy_rep_key, "bad")?;
+    let good: i64 = redis_conn.hget(&legacy_rep_key, "good")?;
+    assert_eq!((bad, good), (0, 3));
+    
+    // Existing reputation counters are kept and only missing fields are added
+    let bad: i64 = redis_conn.hget(&partial_rep_key, "bad")?;
+    let good: i64 = redis_conn.hget(&partial_rep_key, "good")?;
+    assert_eq!((bad, good), (7, 0));
+    
+    // Running again is a no-op
+    let _: () = redis_conn.hset(&legacy_rep_key, "good", 9)?;
+    migration::run_migrations().await?;
+    let good: i64 = redis_conn.hget(&legacy_rep_key, "good")?;
+    assert_eq!(good, 9, "Applied migrations should not run twice");
+    
+    // Clean up
+    for k in [&legacy_user_key, &legacy_rep_key, &partial_user_key, &partial_rep_key] {
+        let _: () = redis_conn.del(k)?;
+    }
+    
+    Ok(())
+}
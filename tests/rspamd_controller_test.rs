@@ -0,0 +1,50 @@
+use rspamd_telegram_bot::bayes_manager::BayesManager;
+use rspamd_telegram_bot::config::rspamd;
+use rspamd_telegram_bot::fuzzy_trainer::FuzzyTrainer;
+use std::sync::{Arc, Mutex};
+use warp::Filter;
+
+/// Requests captured by the mock controller: (path, Password header).
+type Captured = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+fn start_mock_controller() -> (String, Captured) {
+    let captured: Captured = Arc::new(Mutex::new(Vec::new()));
+    let recorded = captured.clone();
+    let route = warp::post()
+        .and(warp::path::full())
+        .and(warp::header::optional::<String>("password"))
+        .map(move |path: warp::path::FullPath, password: Option<String>| {
+            recorded.lock().unwrap().push((path.as_str().to_string(), password));
+            warp::reply::json(&serde_json::json!({"success": true}))
+        });
+    let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+    tokio::spawn(server);
+    (format!("http://{}", addr), captured)
+}
+
+#[tokio::test]
+async fn test_controller_url_and_password_come_from_the_environment() {
+    let (url, captured) = start_mock_controller();
+
+    std::env::remove_var(rspamd::CONTROLLER_URL_ENV);
+    std::env::remove_var(rspamd::PASSWORD_ENV);
+    assert_eq!(rspamd::controller_url(), rspamd::CONTROLLER_URL);
+    assert_eq!(rspamd::password(), rspamd::PASSWORD);
+
+    std::env::set_var(rspamd::CONTROLLER_URL_ENV, &url);
+    std::env::set_var(rspamd::PASSWORD_ENV, "from-env");
+    assert_eq!(FuzzyTrainer::new().controller_url, url);
+
+    let bayes = BayesManager::new().unwrap();
+    bayes.learn_spam("env_override_spam", "Buy now! Limited time offer!").await.expect("learn_spam failed");
+    bayes.learn_ham("env_override_ham", "Hello, how are you today?").await.expect("learn_ham failed");
+
+    let requests = captured.lock().unwrap().clone();
+    assert_eq!(requests, vec![
+        ("/learnspam".to_string(), Some("from-env".to_string())),
+        ("/learnham".to_string(), Some("from-env".to_string())),
+    ]);
+
+    std::env::remove_var(rspamd::CONTROLLER_URL_ENV);
+    std::env::remove_var(rspamd::PASSWORD_ENV);
+}